@@ -1,9 +1,10 @@
 //! Dispatch micro-benchmarks: eventp vs event-manager vs mio (with a user-side
-//! Token→Handler table).
+//! Token→Handler table), and vs a hand-written `epoll_wait` loop.
 //!
 //! Run with:
 //!     cargo bench --bench dispatch
 //!     cargo bench --bench dispatch -- dispatch_one_single_fd
+//!     cargo bench --bench dispatch --features remote-endpoint -- remote_call
 //! HTML report: target/criterion/report/index.html
 //!
 //! All three reactors are exercised through `eventfd` sources to keep socket /
@@ -36,8 +37,9 @@ use std::time::{Duration, Instant};
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use event_manager::{EventManager, EventOps, EventSet, Events, MutEventSubscriber, SubscriberOps};
 use eventp::epoll::{EpollCreateFlags, EpollTimeout};
+use eventp::thin::ThinBoxSubscriber;
 use eventp::tri_subscriber::WithHandler;
-use eventp::{Eventp, Subscriber};
+use eventp::{Eventp, EventpOps, EventpOpsAdd, Subscriber};
 use mio::unix::SourceFd;
 use mio::{Events as MioEvents, Interest, Poll, Token};
 use nix::sys::eventfd::{EfdFlags, EventFd};
@@ -59,6 +61,9 @@ fn drain(efd: &EventFd) {
     let _ = efd.read();
 }
 
+// The slots of the `eventp_fixed` rows, which only run for the N that fit.
+const FIXED_SLOTS: usize = 16;
+
 // An owned counter shared between the harness (which asserts the dispatch
 // actually fired) and the per-event handler closures.
 type Counter = Rc<Cell<u64>>;
@@ -75,6 +80,9 @@ mod eventp_impl {
         // Owns the writer end of each eventfd so the benchmark loop can fire
         // events without the subscriber giving up its own fd.
         pub writers: Vec<EventFd>,
+        // The registered fd of each subscriber, parallel to `writers` (only
+        // filled by `build`).
+        pub fds: Vec<RawFd>,
         pub counter: Counter,
     }
 
@@ -82,13 +90,28 @@ mod eventp_impl {
     /// The buffer capacity is sized to `cap` so a single `run_once` can drain
     /// any batch the bench fires.
     pub fn build(n: usize, cap: usize) -> Harness {
-        let mut reactor =
+        let reactor =
             Eventp::new(cap.max(1), EpollCreateFlags::EPOLL_CLOEXEC).expect("Eventp::new");
+        build_in(reactor, n)
+    }
+
+    /// Same as `build`, into a reactor of `FIXED_SLOTS` fixed slots.
+    pub fn build_fixed(n: usize) -> Harness {
+        let reactor = Eventp::builder()
+            .fixed_capacity(FIXED_SLOTS)
+            .build()
+            .expect("Builder::fixed_capacity");
+        build_in(reactor, n)
+    }
+
+    fn build_in(mut reactor: Eventp, n: usize) -> Harness {
         let counter: Counter = Rc::new(Cell::new(0));
 
         let mut writers = Vec::with_capacity(n);
+        let mut fds = Vec::with_capacity(n);
         for _ in 0..n {
             let efd_for_sub = new_eventfd();
+            fds.push(efd_for_sub.as_raw_fd());
             // Dup the fd into a separate writer; both EventFd's point at the
             // same kernel-side eventfd object, so writing on `writer` wakes
             // the one registered with eventp.
@@ -114,6 +137,7 @@ mod eventp_impl {
         Harness {
             reactor,
             writers,
+            fds,
             counter,
         }
     }
@@ -169,6 +193,7 @@ mod eventp_impl {
             Harness {
                 reactor,
                 writers: flat_writers,
+                fds: Vec::new(),
                 counter,
             },
             grouped_writers,
@@ -451,6 +476,62 @@ mod mio_impl {
     }
 }
 
+// ===================================================================
+// raw epoll side — the hand-written baseline
+// ===================================================================
+
+// What a loop written straight against `epoll_wait` looks like: the data word
+// is an index into a `Vec` of eventfds, and the "handler" is inlined. No
+// registry, no virtual call, no bookkeeping: the floor eventp is measured
+// against.
+mod raw_impl {
+    use nix::sys::epoll::{Epoll, EpollEvent, EpollFlags};
+
+    use super::*;
+
+    pub struct Harness {
+        pub epoll: Epoll,
+        pub events: Vec<EpollEvent>,
+        pub fds: Vec<EventFd>,
+        pub writers: Vec<EventFd>,
+        pub counter: u64,
+    }
+
+    pub fn build(n: usize, cap: usize) -> Harness {
+        let epoll = Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC).expect("epoll_create");
+        let mut fds = Vec::with_capacity(n);
+        let mut writers = Vec::with_capacity(n);
+        for i in 0..n {
+            let efd = new_eventfd();
+            epoll
+                .add(&efd, EpollEvent::new(EpollFlags::EPOLLIN, i as u64))
+                .expect("epoll add");
+            let dup = efd.as_fd().try_clone_to_owned().expect("dup eventfd");
+            writers.push(unsafe { EventFd::from_owned_fd(dup) });
+            fds.push(efd);
+        }
+        Harness {
+            epoll,
+            events: vec![EpollEvent::empty(); cap.max(1)],
+            fds,
+            writers,
+            counter: 0,
+        }
+    }
+
+    #[inline]
+    pub fn run_once(h: &mut Harness) {
+        let n = h
+            .epoll
+            .wait(&mut h.events, EpollTimeout::ZERO)
+            .expect("epoll_wait");
+        for ev in &h.events[..n] {
+            drain(&h.fds[ev.data() as usize]);
+            h.counter += 1;
+        }
+    }
+}
+
 // ===================================================================
 // helpers
 // ===================================================================
@@ -711,6 +792,433 @@ fn bench_register(c: &mut Criterion) {
     group.finish();
 }
 
+// ===================================================================
+// group 5: modify_one (interest flip on one registered fd)
+// ===================================================================
+
+// `modify` is dominated by `epoll_ctl(EPOLL_CTL_MOD)`; what differs between
+// backends is the bookkeeping around it. For eventp that is the lookup of the
+// subscriber and the update of its stored interest. Compare runs across commits
+// with `-- --save-baseline <name>` / `-- --baseline <name>`.
+//
+// Moving the interest from a `Cell` inside the subscriber (one virtual call to
+// reach it) into the thin-box header (a plain store) measured within
+// run-to-run noise here (~270-310 ns either way): the syscall swamps it. The
+// change is about not requiring `HasInterest`, and it costs nothing.
+const MODIFY_NS: &[usize] = &[1, 1_000, 10_000];
+
+fn bench_modify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("modify_one");
+    group.throughput(Throughput::Elements(1));
+
+    for &n in MODIFY_NS {
+        group.bench_with_input(BenchmarkId::new("eventp", n), &n, |b, &n| {
+            let mut h = eventp_impl::build(n, n.max(1));
+            let target = h.fds[h.fds.len() / 2];
+            let interests = [
+                eventp::interest().read(),
+                eventp::interest().read().edge_triggered(),
+            ];
+            let mut i = 0;
+            b.iter(|| {
+                i ^= 1;
+                h.reactor.modify(target, interests[i]).unwrap();
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("mio_with_table", n), &n, |b, &n| {
+            let h = mio_impl::build(n, n.max(1));
+            let target = h._owned_fds[h._owned_fds.len() / 2].as_raw_fd();
+            let tok = Token(h._owned_fds.len() / 2);
+            let interests = [Interest::READABLE, Interest::READABLE | Interest::WRITABLE];
+            let mut i = 0;
+            b.iter(|| {
+                i ^= 1;
+                h.poll
+                    .registry()
+                    .reregister(&mut SourceFd(&target), tok, interests[i])
+                    .unwrap();
+            });
+        });
+    }
+
+    group.finish();
+}
+
+// ===================================================================
+// group 6: register_batch (one-by-one vs `add_all`, eventp only)
+// ===================================================================
+
+// What an accept storm looks like: many fresh fds registered back to back into
+// an empty loop. Both variants issue one `EPOLL_CTL_ADD` per fd; `add_all` saves
+// the map's incremental rehashes and the per-call result plumbing. Observed
+// ~7.1 ms one-by-one vs ~5.9 ms batched for 10k fds; the syscalls dominate both.
+const BATCH_N: usize = 10_000;
+
+fn bench_register_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("register_batch");
+    group.throughput(Throughput::Elements(BATCH_N as u64));
+    group.sample_size(10);
+
+    let subscribers = || -> Vec<ThinBoxSubscriber<Eventp>> {
+        (0..BATCH_N)
+            .map(|_| {
+                let sub = eventp::interest()
+                    .read()
+                    .with_fd(new_eventfd())
+                    .with_handler(|_efd: &mut EventFd| {});
+                ThinBoxSubscriber::new(sub)
+            })
+            .collect()
+    };
+
+    group.bench_function(BenchmarkId::new("one_by_one", BATCH_N), |b| {
+        b.iter_custom(|iters| {
+            let mut total = Duration::ZERO;
+            for _ in 0..iters {
+                let mut reactor = Eventp::default();
+                let subs = subscribers();
+                let start = Instant::now();
+                for sub in subs {
+                    reactor.add(sub).unwrap();
+                }
+                total += start.elapsed();
+                drop(reactor);
+            }
+            total
+        });
+    });
+
+    group.bench_function(BenchmarkId::new("add_all", BATCH_N), |b| {
+        b.iter_custom(|iters| {
+            let mut total = Duration::ZERO;
+            for _ in 0..iters {
+                let mut reactor = Eventp::default();
+                let subs = subscribers();
+                let start = Instant::now();
+                let results = reactor.add_all(subs);
+                total += start.elapsed();
+                assert!(results.iter().all(|r| r.is_ok()));
+                drop(reactor);
+            }
+            total
+        });
+    });
+
+    group.finish();
+}
+
+// ===================================================================
+// group 7: delete_other_in_handler (eventp only)
+// ===================================================================
+
+// One wakeup whose handler deletes another subscriber, the path that parks the
+// victim until the end of the batch. Only the wakeup is timed; a fresh victim is
+// registered before each one. The parking list lives on the loop and keeps its
+// allocation between batches, rather than being allocated by the first delete of
+// every batch. The difference is one `malloc`/`free` pair per wakeup, well within
+// the run-to-run noise of the syscalls (~1.3-2.0 µs either way on a shared host).
+fn bench_delete_other_in_handler(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delete_other_in_handler");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("eventp", |b| {
+        let mut reactor = Eventp::default();
+        let victim: Rc<Cell<RawFd>> = Rc::new(Cell::new(-1));
+        let killer = new_eventfd();
+        let writer =
+            unsafe { EventFd::from_owned_fd(killer.as_fd().try_clone_to_owned().unwrap()) };
+        let v = victim.clone();
+        eventp::interest()
+            .read()
+            .with_fd(killer)
+            .with_handler(
+                move |efd: &mut EventFd, mut reactor: eventp::Pinned<'_, Eventp>| {
+                    drain(efd);
+                    reactor.delete(v.get()).unwrap();
+                },
+            )
+            .register_into(&mut reactor)
+            .unwrap();
+
+        b.iter_custom(|iters| {
+            let mut total = Duration::ZERO;
+            for _ in 0..iters {
+                let efd = new_eventfd();
+                victim.set(efd.as_raw_fd());
+                eventp::interest()
+                    .read()
+                    .with_fd(efd)
+                    .with_handler(|_efd: &mut EventFd| {})
+                    .register_into(&mut reactor)
+                    .unwrap();
+                fire(&writer);
+
+                let start = Instant::now();
+                run_once_eventp(&mut reactor);
+                total += start.elapsed();
+            }
+            total
+        });
+        assert!(!reactor.contains(victim.get()));
+    });
+
+    group.finish();
+}
+
+// ===================================================================
+// group 8: vs_raw_epoll (eventp vs the hand-written baseline)
+// ===================================================================
+
+// The "zero-cost" claim, measured: eventp against `raw_impl`, which does
+// nothing but `epoll_wait` and the inlined drain. `single_wakeup` is one fire +
+// one wait + one dispatch with a single subscriber, the round trip of an
+// eventfd-driven loop; `fanout_64` fires 64 fds and dispatches them in one wait.
+// The per-row delta is the whole cost of eventp's dispatch: the thin-box
+// pointer from the data word, the generation check, the bookkeeping of the
+// handled fd and the virtual call. Observed ~810 vs ~750 ns for
+// `single_wakeup`, and ~36 vs ~30 µs for `fanout_64` (~90 ns per event) on a
+// shared single-core host.
+const FANOUT_N: usize = 64;
+
+fn bench_vs_raw_epoll(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vs_raw_epoll");
+
+    group.throughput(Throughput::Elements(1));
+    group.bench_function(BenchmarkId::new("eventp", "single_wakeup"), |b| {
+        let mut h = eventp_impl::build(1, 1);
+        b.iter(|| {
+            fire(&h.writers[0]);
+            run_once_eventp(&mut h.reactor);
+        });
+        assert!(h.counter.get() > 0, "eventp: dispatch never fired");
+    });
+    group.bench_function(BenchmarkId::new("raw_epoll", "single_wakeup"), |b| {
+        let mut h = raw_impl::build(1, 1);
+        b.iter(|| {
+            fire(&h.writers[0]);
+            raw_impl::run_once(&mut h);
+        });
+        assert!(h.counter > 0, "raw epoll: dispatch never fired");
+    });
+
+    group.throughput(Throughput::Elements(FANOUT_N as u64));
+    group.bench_function(BenchmarkId::new("eventp", "fanout_64"), |b| {
+        let mut h = eventp_impl::build(FANOUT_N, FANOUT_N);
+        b.iter(|| {
+            for w in &h.writers {
+                fire(w);
+            }
+            run_once_eventp(&mut h.reactor);
+        });
+        assert!(h.counter.get() > 0, "eventp: dispatch never fired");
+    });
+    group.bench_function(BenchmarkId::new("raw_epoll", "fanout_64"), |b| {
+        let mut h = raw_impl::build(FANOUT_N, FANOUT_N);
+        b.iter(|| {
+            for w in &h.writers {
+                fire(w);
+            }
+            raw_impl::run_once(&mut h);
+        });
+        assert!(h.counter > 0, "raw epoll: dispatch never fired");
+    });
+
+    group.finish();
+}
+
+// ===================================================================
+// group 9: add_delete_churn (one add + delete next to N subscribers)
+// ===================================================================
+
+// The registry bookkeeping around `EPOLL_CTL_ADD` / `EPOLL_CTL_DEL`, with N
+// subscribers already registered: the allocation of the thin box, the map
+// insert and remove. The same fd is registered every time, through a raw fd
+// source, so that no eventfd is created in the measured loop. `raw_epoll` is
+// the two syscalls alone, which account for nearly all of it (~0.8-0.9 µs of
+// ~0.9-1.1 µs on a shared host). `eventp_fixed` is the same with the slots of
+// `Builder::fixed_capacity` in place of the map, for the N below 16: it
+// measured ~70-160 ns under `eventp` from 1 to 7 subscribers, and level at 0,
+// while `eventp` measured the same as before the storage could be switched.
+const CHURN_NS: &[usize] = &[0, 1, 4, 7, 64, 1_000];
+
+fn bench_add_delete_churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add_delete_churn");
+    group.throughput(Throughput::Elements(1));
+
+    for &n in CHURN_NS {
+        group.bench_with_input(BenchmarkId::new("eventp", n), &n, |b, &n| {
+            let mut h = eventp_impl::build(n, n.max(1));
+            let efd = new_eventfd();
+            let raw = efd.as_raw_fd();
+            b.iter(|| {
+                // SAFETY: `efd` outlives every registration of its number.
+                unsafe { eventp::interest().read().with_raw_fd(raw) }
+                    .with_handler(|| {})
+                    .register_into(&mut h.reactor)
+                    .unwrap();
+                h.reactor.delete(raw).unwrap();
+            });
+        });
+
+        if n < FIXED_SLOTS {
+            group.bench_with_input(BenchmarkId::new("eventp_fixed", n), &n, |b, &n| {
+                let mut h = eventp_impl::build_fixed(n);
+                let efd = new_eventfd();
+                let raw = efd.as_raw_fd();
+                b.iter(|| {
+                    // SAFETY: As above.
+                    unsafe { eventp::interest().read().with_raw_fd(raw) }
+                        .with_handler(|| {})
+                        .register_into(&mut h.reactor)
+                        .unwrap();
+                    h.reactor.delete(raw).unwrap();
+                });
+            });
+        }
+
+        group.bench_with_input(BenchmarkId::new("raw_epoll", n), &n, |b, &n| {
+            use nix::sys::epoll::{EpollEvent, EpollFlags};
+
+            let h = raw_impl::build(n, n.max(1));
+            let efd = new_eventfd();
+            b.iter(|| {
+                h.epoll
+                    .add(&efd, EpollEvent::new(EpollFlags::EPOLLIN, n as u64))
+                    .unwrap();
+                h.epoll.delete(&efd).unwrap();
+            });
+        });
+    }
+
+    group.finish();
+}
+
+// ===================================================================
+// group 10: lookup (the registry alone, eventp only)
+// ===================================================================
+
+// `Eventp::contains` of a registered fd, with no syscall to drown the registry
+// in: the lookup every add, delete and modify starts with.
+//
+// A linear scan of the fds for loops of up to 8 subscribers, in front of the
+// map and promoted to it past them, was tried for the one- or two-fd loops (an
+// eventfd and a queue) many deployments are. On a shared single-core host, it
+// measured no faster than the FxHash lookup at 1 and 2 fds (~3.5 ns for
+// either), and slower at 4 and 8 (~6 ns against ~3 ns). `add_delete_churn` and
+// `vs_raw_epoll`, at ~600-1000 ns, are their syscalls, and moved by up to 20%
+// at every N, past the threshold too, as between runs. So the map stays. The
+// slots of `Builder::fixed_capacity`, in the `eventp_fixed` rows, measured
+// within the same ~3-5 ns.
+const LOOKUP_NS: &[usize] = &[1, 2, 4, 8, 64, 1_000];
+
+fn bench_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    group.throughput(Throughput::Elements(1));
+
+    for &n in LOOKUP_NS {
+        group.bench_with_input(BenchmarkId::new("eventp", n), &n, |b, &n| {
+            let h = eventp_impl::build(n, n.max(1));
+            let target = h.fds[h.fds.len() - 1];
+            b.iter(|| black_box(h.reactor.contains(black_box(target))));
+        });
+
+        if n <= FIXED_SLOTS {
+            group.bench_with_input(BenchmarkId::new("eventp_fixed", n), &n, |b, &n| {
+                let h = eventp_impl::build_fixed(n);
+                let target = h.fds[h.fds.len() - 1];
+                b.iter(|| black_box(h.reactor.contains(black_box(target))));
+            });
+        }
+    }
+
+    group.finish();
+}
+
+// ===================================================================
+// group 11: remote_call (needs `--features remote-endpoint`)
+// ===================================================================
+
+// The latency of `RemoteEndpoint::call_blocking` with an empty closure: the
+// channel send, the eventfd wakeup of the loop thread, the run of the closure
+// and the `oneshot` reply. Dominated by the two thread wakeups: ~3.9 µs on a
+// shared single-core host, and far worse on a busy one. Compiled out without
+// the feature, as `cargo bench` does not enable it.
+#[cfg(feature = "remote-endpoint")]
+fn bench_remote_call(c: &mut Criterion) {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+
+    let mut group = c.benchmark_group("remote_call");
+    group.throughput(Throughput::Elements(1));
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+    let loop_stop = stop.clone();
+    let handle = thread::spawn(move || {
+        let mut reactor = Eventp::default();
+        let endpoint = eventp::remote_endpoint()
+            .unwrap()
+            .register_into(&mut reactor)
+            .unwrap();
+        tx.send(endpoint).unwrap();
+        while !loop_stop.load(Ordering::Acquire) {
+            reactor.run_once().unwrap();
+        }
+    });
+    let endpoint = rx.recv().unwrap();
+
+    group.bench_function("call_blocking", |b| {
+        b.iter(|| endpoint.call_blocking(|_| Ok(black_box(1))).unwrap());
+    });
+
+    stop.store(true, Ordering::Release);
+    // Wakes the loop, which then sees the flag.
+    endpoint.wake().unwrap();
+    handle.join().unwrap();
+
+    // The cost of a non-blocking delete, as a boxed closure and as a message of
+    // its own, on one thread to leave the wakeups out: the sends of a batch of
+    // `BATCH`, then one dispatch running them all. The delete of an fd that is
+    // not registered fails without a syscall. Both are dominated by the eventfd
+    // write of each send, ~370 ns on a shared single-core host, next to which the
+    // allocation does not show; it does with an allocator contended by the
+    // sending threads.
+    const BATCH: u64 = 64;
+    group.throughput(Throughput::Elements(BATCH));
+    let mut reactor = Eventp::default();
+    let endpoint = eventp::remote_endpoint()
+        .unwrap()
+        .register_into(&mut reactor)
+        .unwrap();
+    group.bench_function("nonblocking_delete_boxed", |b| {
+        b.iter(|| {
+            for _ in 0..BATCH {
+                endpoint
+                    .call_nonblocking(|mut ep| {
+                        let _ = black_box(ep.delete(-1));
+                    })
+                    .unwrap();
+            }
+            reactor.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        });
+    });
+    group.bench_function("nonblocking_delete_message", |b| {
+        b.iter(|| {
+            for _ in 0..BATCH {
+                endpoint.delete_remote(black_box(-1), None).unwrap();
+            }
+            reactor.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        });
+    });
+
+    group.finish();
+}
+
+#[cfg(not(feature = "remote-endpoint"))]
+fn bench_remote_call(_c: &mut Criterion) {}
+
 criterion_group! {
     name = benches;
     config = Criterion::default()
@@ -724,5 +1232,12 @@ criterion_group! {
         bench_dispatch_one_multi_fd,
         bench_dispatch_all_ready,
         bench_register,
+        bench_modify,
+        bench_register_batch,
+        bench_delete_other_in_handler,
+        bench_vs_raw_epoll,
+        bench_add_delete_churn,
+        bench_lookup,
+        bench_remote_call,
 }
 criterion_main!(benches);
@@ -0,0 +1,128 @@
+//! An echo server on port 3000, embedded into an async application through
+//! `eventp::async_bridge`.
+//!
+//! To stay dependency-free, the "application" runs on a minimal std-only
+//! executor, and the runtime's readiness is a helper thread blocking in `poll(2)`.
+//! With tokio, replace both with `#[tokio::main]` and the `AsyncFd` adapter shown
+//! in the `async_bridge` docs; the eventp side stays the same.
+//!
+//! Run it with `cargo run --example async-echo-server --features async-bridge`,
+//! then `nc 127.0.0.1 3000`.
+
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use eventp::async_bridge::Readiness;
+use eventp::tri_subscriber::WithHandler;
+use eventp::{Event, Eventp, EventpOps, Pinned, Subscriber};
+
+fn main() -> io::Result<()> {
+    block_on(async_main())
+}
+
+async fn async_main() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:3000")?;
+    listener.set_nonblocking(true)?;
+
+    let mut reactor = Eventp::default();
+    eventp::interest()
+        .read()
+        .with_fd(listener)
+        .with_handler(on_connection)
+        .register_into(&mut reactor)?;
+
+    let mut reactor = reactor.into_async(|fd| Ok(PollThread(Arc::new(fd))))?;
+    reactor.run().await
+}
+
+fn on_connection(
+    listener: &mut TcpListener,
+    mut reactor: Pinned<impl EventpOps>,
+) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    stream.set_nonblocking(true)?;
+
+    eventp::interest()
+        .edge_triggered()
+        .read()
+        .with_fd(stream)
+        .with_handler(on_data)
+        .register_into(&mut reactor)
+}
+
+fn on_data(
+    stream: &mut TcpStream,
+    ev: Event,
+    mut reactor: Pinned<impl EventpOps>,
+) -> io::Result<()> {
+    if ev.is_closed() {
+        return reactor.delete(stream.as_fd().as_raw_fd());
+    }
+
+    let mut buf = [0; 512];
+    loop {
+        match stream.read(&mut buf) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(_) | Ok(0) => return reactor.delete(stream.as_fd().as_raw_fd()),
+            Ok(n) => stream.write_all(&buf[..n])?, // Send buffer omitted.
+        }
+    }
+}
+
+/// Readiness from a helper thread that blocks in `poll(2)` until the epoll fd is
+/// readable, then wakes the task.
+struct PollThread(Arc<OwnedFd>);
+
+impl Readiness for PollThread {
+    fn poll_readable(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if poll_in(&self.0, 0)? {
+            return Poll::Ready(Ok(()));
+        }
+        let fd = Arc::clone(&self.0);
+        let waker = cx.waker().clone();
+        thread::spawn(move || {
+            let _ = poll_in(&fd, -1);
+            waker.wake();
+        });
+        Poll::Pending
+    }
+}
+
+fn poll_in(fd: &OwnedFd, timeout: libc::c_int) -> io::Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+        -1 => Err(io::Error::last_os_error()),
+        n => Ok(n > 0),
+    }
+}
+
+/// A single-future executor: polls, and parks the thread until woken.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+            return out;
+        }
+        thread::park();
+    }
+}
@@ -0,0 +1,91 @@
+//! A daemon reloading its TOML configuration whenever it is written, with
+//! `eventp::inotify`.
+//!
+//! Run it with `cargo run --example config-reload --features inotify -- app.toml`,
+//! then edit `app.toml`.
+//!
+//! The directory of the file is watched rather than the file itself: editors
+//! often save by writing a new file and renaming it over the old one, which a
+//! watch on the old file would not see. A real program would parse the file with
+//! the `toml` crate; the few `key = value` lines of this one are parsed by hand.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+use eventp::inotify::{self, AddWatchFlags, FileEvent};
+use eventp::{Eventp, Pinned, Subscriber};
+
+#[derive(Debug, Default)]
+struct Config {
+    listen: String,
+    workers: u32,
+}
+
+fn main() -> io::Result<()> {
+    let path = PathBuf::from(env::args().nth(1).unwrap_or_else(|| "app.toml".into()));
+    let path = fs::canonicalize(path)?;
+    let mut config = load(&path)?;
+    println!("loaded {config:?}");
+
+    let dir = path.parent().unwrap_or(Path::new("/")).to_owned();
+    let file_name = path.file_name().map(OsStr::to_owned);
+
+    let mut reactor = Eventp::default();
+    inotify::watch([&dir])
+        .events(AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO)
+        .with_handler(move |event: FileEvent, _reactor: Pinned<'_, Eventp>| {
+            match event {
+                FileEvent::Path { name, .. } if name == file_name => {}
+                // Some events may have been for our file.
+                FileEvent::Overflow => {}
+                FileEvent::Path { .. } => return,
+            }
+            match load(&path) {
+                Ok(new) => {
+                    println!("reloaded {new:?}");
+                    config = new;
+                }
+                // Keep running with the last good configuration.
+                Err(e) => eprintln!("not reloaded, {e}; keeping {config:?}"),
+            }
+        })?
+        .register_into(&mut reactor)?;
+    reactor.run_forever()
+}
+
+fn load(path: &Path) -> io::Result<Config> {
+    let text = fs::read_to_string(path)?;
+    parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Parses the top-level `key = value` lines, with `#` comments.
+fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", n + 1))?;
+        let value = value.trim();
+        match key.trim() {
+            "listen" => {
+                config.listen = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .ok_or_else(|| format!("line {}: `listen` must be a string", n + 1))?
+                    .to_owned();
+            }
+            "workers" => {
+                config.workers = value
+                    .parse()
+                    .map_err(|e| format!("line {}: `workers`: {e}", n + 1))?;
+            }
+            key => return Err(format!("line {}: unknown key `{key}`", n + 1)),
+        }
+    }
+    Ok(config)
+}
@@ -0,0 +1,128 @@
+#![cfg_attr(rustfmt, rustfmt_skip)]
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+
+use eventp::tri_subscriber::WithHandler;
+use eventp::{Event, Eventp, Interest, Pinned, Subscriber};
+
+/// Connections served at once; the listener takes one more slot.
+const MAX_CONNECTIONS: usize = 2;
+
+// Set up an echo server on port 3000, serving at most `MAX_CONNECTIONS` clients.
+// Others wait in the listen backlog until a connection closes.
+fn main() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:3000")?;
+    serve(listener, MAX_CONNECTIONS)?.run_forever()
+}
+
+fn serve(listener: TcpListener, max_connections: usize) -> io::Result<Eventp> {
+    listener.set_nonblocking(true)?;
+    let listener_fd = listener.as_raw_fd();
+
+    let mut reactor = Eventp::builder()
+        .max_subscribers(max_connections + 1)
+        .build()?;
+    eventp::interest()
+        .read()
+        .with_fd(listener)
+        .with_handler(move |listener: &mut TcpListener, reactor: Pinned<Eventp>| {
+            on_connection(listener, listener_fd, reactor)
+        })
+        .register_into(&mut reactor)?;
+    Ok(reactor)
+}
+
+fn on_connection(
+    listener: &mut TcpListener,
+    listener_fd: RawFd,
+    mut reactor: Pinned<Eventp>,
+) -> io::Result<()> {
+    // Stop accepting while full: the listener stays registered, without interest.
+    // Pending clients are not refused, they wait in the backlog of the kernel.
+    while reactor.capacity_remaining() != Some(0) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        stream.set_nonblocking(true)?;
+
+        Interest::stream_read_et()
+            .with_fd(stream)
+            .with_handler(move |ev: Event, stream: &mut TcpStream, reactor: Pinned<Eventp>| {
+                on_data(ev, stream, listener_fd, reactor)
+            })
+            .register_into(&mut reactor)?;
+    }
+    reactor.modify(listener_fd, eventp::interest())
+}
+
+fn on_data(
+    ev: Event,
+    stream: &mut TcpStream,
+    listener_fd: RawFd,
+    mut reactor: Pinned<Eventp>,
+) -> io::Result<()> {
+    let mut buf = [0; 512];
+    while ev.is_readable() {
+        match stream.read(&mut buf) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(_) | Ok(0) => break,
+            Ok(n) => stream.write_all(&buf[..n])?, // Send buffer omitted.
+        }
+    }
+    if !ev.is_readable() && !ev.is_closed() {
+        return Ok(());
+    }
+
+    reactor.delete(stream.as_fd().as_raw_fd())?;
+    // A slot is free again: resume accepting. A no-op if the listener never paused.
+    reactor.modify(listener_fd, eventp::interest().read())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use eventp::epoll::EpollTimeout;
+
+    use super::*;
+
+    fn step(reactor: &mut Eventp) {
+        for _ in 0..3 {
+            reactor.run_once_with_timeout(EpollTimeout::from(50u16)).unwrap();
+        }
+    }
+
+    fn echoed(client: &mut TcpStream) -> bool {
+        let mut buf = [0; 2];
+        client.read_exact(&mut buf).is_ok() && &buf == b"hi"
+    }
+
+    #[test]
+    fn waiting_clients_are_served_once_a_connection_closes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut reactor = serve(listener, MAX_CONNECTIONS).unwrap();
+
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            let client = TcpStream::connect(addr).unwrap();
+            client.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+            (&client).write_all(b"hi").unwrap();
+            clients.push(client);
+        }
+        step(&mut reactor);
+        assert!(echoed(&mut clients[0]));
+        assert!(echoed(&mut clients[1]));
+        assert!(!echoed(&mut clients[2]));
+        assert_eq!(reactor.capacity_remaining(), Some(0));
+
+        drop(clients.remove(0));
+        step(&mut reactor);
+        assert!(echoed(&mut clients[1]));
+        assert_eq!(reactor.capacity_remaining(), Some(0));
+    }
+}
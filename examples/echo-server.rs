@@ -1,107 +1,77 @@
 #![cfg_attr(rustfmt, rustfmt_skip)]
 
 use std::io::{self, Read, Write};
-use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::net::TcpListener;
 use std::os::fd::{AsFd, AsRawFd};
 
+use eventp::net::Accept;
 use eventp::tri_subscriber::WithHandler;
-use eventp::{Eventp, EventpOps, Pinned, Subscriber};
+use eventp::{Eventp, EventpOps, Interest, Pinned, Subscriber};
 
 // Set up an echo server on port 3000.
 fn main() -> io::Result<()> {
     let listener = TcpListener::bind("127.0.0.1:3000")?;
-    listener.set_nonblocking(true)?;
 
     let mut reactor = Eventp::default();// Internally it creates an epoll fd.
     eventp::interest()
         .read()                         // Interested in readable events, i.e. new connections.
-        .with_fd(listener)
+        .with_fd_nonblocking(listener)? // Sets O_NONBLOCK, which a handler needs not to block the loop.
         .with_handler(on_connection)
         .register_into(&mut reactor)?;
 
     reactor.run_forever()               // Enter loop, epoll_wait and dispatch event.
 }
 
-fn on_connection(
-    listener: &mut impl Accept,         // Will receive `TcpListener`. To make it testable, we define a trait below.
+fn on_connection<L>(
+    listener: &mut L,                   // Will receive `TcpListener`. To make it testable, it is taken as `Accept`.
     mut reactor: Pinned<impl EventpOps>,// Will receive `Pinned<Eventp>`.
-) {
-    let (stream, _) = listener.accept().expect("accept failed");
-
-    eventp::interest()
-        .edge_triggered()
-        .read()                         // Interested in readable events, edge triggered.
+) -> io::Result<()>                     // Errors are handled by the loop's `ErrorPolicy`.
+where
+    L: Accept,                          // Implemented for TCP, unix and vsock listeners, and mocked by `eventp::mock::MockAccept`.
+    L::Stream: 'static + Read + Write,
+{
+    // One connection per event: the listener is level-triggered, so the others are
+    // reported again. `eventp::acceptor` drains the backlog, also edge-triggered.
+    let (stream, _) = listener.accept()?;
+
+    Interest::stream_read_et()          // Interested in readable events and peer shutdown, edge triggered.
         .with_fd(stream)
         .with_handler(on_data)
         .register_into(&mut reactor)
-        .unwrap();
 }
 
 fn on_data(
     // Rustacean Dependency Injection👇 Place any parameters you like, in any order.
-    _interest: eventp::Interest,        // Previously registered interests.
+    _interest: Interest,                // Previously registered interests.
     mut eventp: Pinned<impl EventpOps>,
     ev: eventp::Event,                  // The triggered event.
     stream: &mut (impl Read + Write + AsFd),
-) {
-    if ev.is_error() || ev.is_hangup() {
-        eventp.delete(stream.as_fd().as_raw_fd()).unwrap();
-        return;
-    }
+) -> io::Result<()> {
     if !ev.is_readable() {
-        return;
+        if ev.is_closed() {             // Hangup, error, or peer shutdown, with nothing left to read.
+            if let Ok(Some(e)) = ev.socket_error(stream.as_fd()) {
+                eprintln!("connection failed: {e}"); // Why, e.g. reset by the peer.
+            }
+            return eventp.delete(stream.as_fd().as_raw_fd());
+        }
+        return Ok(());
     }
 
     let mut buf = [0; 512];
     loop {
         match stream.read(&mut buf) {
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                return;
+                return Ok(());
             }
             Err(_) | Ok(0) => {
-                eventp.delete(stream.as_fd().as_raw_fd()).unwrap();
-                return;
+                return eventp.delete(stream.as_fd().as_raw_fd());
             }
-            Ok(n) => stream.write_all(&buf[..n]).unwrap(), // Send buffer omitted.
+            Ok(n) => stream.write_all(&buf[..n])?, // Send buffer omitted.
         }
     }
 }
 
-// Here goes mocking.
-
-#[cfg_attr(feature = "mock", mockall::automock(type Stream = MockStream;))]
-trait Accept {
-    type Stream:'static + Read + Write + AsFd;
-
-    fn accept(&self) -> io::Result<(Self::Stream, SocketAddr)>;
-}
-
-impl Accept for TcpListener {
-    type Stream = TcpStream;
-
-    fn accept(&self) -> io::Result<(Self::Stream, SocketAddr)> {
-        let (stream, addr) = self.accept()?;
-        stream.set_nonblocking(true)?;
-
-        Ok((stream, addr))
-    }
-}
-
-#[cfg(feature = "mock")]
-mockall::mock! {
-    pub Stream {}
-
-    impl Read for Stream {
-        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
-    }
-    impl Write for Stream {
-        fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
-        fn flush(&mut self) -> io::Result<()>;
-    }
-    impl AsFd for Stream {
-        fn as_fd(&self) -> std::os::fd::BorrowedFd<'_>;
-    }
-}
+// Here goes mocking, with the listener and stream mocks of `eventp::mock`.
 
 #[cfg(all(test, feature = "mock"))]
 mod tests {
@@ -109,8 +79,10 @@ mod tests {
     use std::os::fd::BorrowedFd;
 
     use eventp::epoll::EpollFlags;
-    use eventp::{pinned, MockEventp, Interest};
+    use eventp::mock::{self, added_with_fd, added_with_interest, MockAccept, MockStream};
+    use eventp::{pinned, MockEventp};
     use mockall::predicate::*;
+    use mockall::PredicateBooleanExt;
 
     use super::*;
 
@@ -130,14 +102,87 @@ mod tests {
             Ok((stream, addr))
         });
 
+        // The accepted stream is registered edge-triggered.
         mock_eventp
             .expect_add()
-            .with(always())
+            .with(added_with_fd(42).and(added_with_interest(Interest::stream_read_et())))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        // 2. Act
+        on_connection(&mut mock_listener, pinned!(mock_eventp)).unwrap();
+    }
+
+    #[test]
+    fn test_on_connection_accept_error_is_returned() {
+        // 1. Setup
+        let mut mock_listener = MockAccept::new();
+        let mut mock_eventp = MockEventp::new();
+
+        mock_listener
+            .expect_accept()
+            .returning(|| Err(io::Error::new(ErrorKind::Other, "too many open files")));
+        mock_eventp.expect_add().never();
+
+        // 2. Act
+        let err = on_connection(&mut mock_listener, pinned!(mock_eventp)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_accepted_connection_is_echoed_then_deleted() {
+        // 1. Setup
+        let mut mock_listener = MockAccept::new();
+        let mut mock_eventp = MockEventp::new();
+        let captured = mock::capture_add(&mut mock_eventp);
+        let mut seq = mockall::Sequence::new();
+        let fd = 46;
+
+        mock_listener.expect_accept().times(1).returning(move || {
+            let mut stream = MockStream::new();
+            stream
+                .expect_as_fd()
+                .returning(move || unsafe { BorrowedFd::borrow_raw(fd) });
+            stream
+                .expect_read()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|buf| {
+                    buf[..4].copy_from_slice(b"ping");
+                    Ok(4)
+                });
+            stream
+                .expect_write()
+                .with(eq(b"ping".as_slice()))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|buf| Ok(buf.len()));
+            stream
+                .expect_read()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_| Ok(0)); // EOF
+
+            let addr = "127.0.0.1:12345".parse().unwrap();
+            Ok((stream, addr))
+        });
+
+        let mut mock_eventp2 = MockEventp::new();
+        mock_eventp2
+            .expect_delete()
+            .with(eq(fd))
             .times(1)
             .returning(|_| Ok(()));
 
         // 2. Act
-        on_connection(&mut mock_listener, pinned!(mock_eventp));
+        on_connection(&mut mock_listener, pinned!(mock_eventp)).unwrap();
+        assert_eq!(captured.len(), 1);
+        assert_eq!(captured.raw_fd(0), fd);
+        assert_eq!(captured.interest(0), Interest::stream_read_et());
+
+        captured
+            .fire(0, EpollFlags::EPOLLIN.into(), pinned!(mock_eventp2))
+            .unwrap();
     }
 
     #[test]
@@ -179,7 +224,8 @@ mod tests {
             pinned!(mock_eventp),
             EpollFlags::EPOLLIN.into(),
             &mut mock_stream,
-        );
+        )
+        .unwrap();
     }
 
     #[test]
@@ -206,7 +252,8 @@ mod tests {
             pinned!(mock_eventp),
             EpollFlags::EPOLLIN.into(),
             &mut mock_stream,
-        );
+        )
+        .unwrap();
     }
 
     #[test]
@@ -236,7 +283,55 @@ mod tests {
             pinned!(mock_eventp),
             EpollFlags::EPOLLIN.into(),
             &mut mock_stream,
-        );
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_on_stream_rdhup_drains_before_closing() {
+        // 1. Setup
+        let mut mock_stream = MockStream::new();
+        let mut mock_eventp = MockEventp::new();
+        let mut seq = mockall::Sequence::new();
+        let fd = 45;
+
+        mock_stream
+            .expect_as_fd()
+            .returning(move || unsafe { BorrowedFd::borrow_raw(fd) });
+        mock_stream
+            .expect_read()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|buf| {
+                buf[..3].copy_from_slice(b"bye");
+                Ok(3)
+            });
+        mock_stream
+            .expect_write()
+            .with(eq(b"bye".as_slice()))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|buf| Ok(buf.len()));
+        mock_stream
+            .expect_read()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Ok(0)); // EOF
+
+        mock_eventp
+            .expect_delete()
+            .with(eq(fd))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        // 2. Act
+        on_data(
+            Interest::default(),
+            pinned!(mock_eventp),
+            (EpollFlags::EPOLLIN | EpollFlags::EPOLLRDHUP).into(),
+            &mut mock_stream,
+        )
+        .unwrap();
     }
 
     #[test]
@@ -264,6 +359,7 @@ mod tests {
             pinned!(mock_eventp),
             (EpollFlags::EPOLLHUP | EpollFlags::EPOLLERR).into(),
             &mut mock_stream,
-        );
+        )
+        .unwrap();
     }
 }
@@ -0,0 +1,101 @@
+//! An echo server on port 3000, whose connections are accepted by a "control
+//! plane" thread and passed to the event loop over a unix socket, with
+//! `eventp::fd_receiver`.
+//!
+//! Run it with `cargo run --example fd-passing-echo-server --features fd-receiver`,
+//! then `nc 127.0.0.1 3000`.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::{mem, ptr, thread};
+
+use eventp::tri_subscriber::WithHandler;
+use eventp::{fd_receiver, Event, Eventp, EventpOps, Pinned, Subscriber};
+
+fn main() -> io::Result<()> {
+    let (control_plane, data_plane) = UnixStream::pair()?;
+    thread::spawn(move || accept_loop(control_plane));
+
+    let mut reactor = Eventp::default();
+    fd_receiver(data_plane)
+        .with_handler(on_fd_received)
+        .register_into(&mut reactor)?;
+    reactor.run_forever()
+}
+
+/// The control plane: accepts connections, and hands them over to the loop.
+fn accept_loop(control_plane: UnixStream) -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:3000")?;
+    for stream in listener.incoming() {
+        // Our copy is closed when `stream` drops, the loop keeps its own.
+        send_fd(&control_plane, stream?.as_fd())?;
+    }
+    Ok(())
+}
+
+fn on_fd_received(fd: OwnedFd, mut reactor: Pinned<'_, Eventp>) -> io::Result<()> {
+    let stream = TcpStream::from(fd);
+    stream.set_nonblocking(true)?;
+
+    eventp::interest()
+        .edge_triggered()
+        .read()
+        .with_fd(stream)
+        .with_handler(on_data)
+        .register_into(&mut reactor)
+}
+
+fn on_data(
+    stream: &mut TcpStream,
+    ev: Event,
+    mut reactor: Pinned<impl EventpOps>,
+) -> io::Result<()> {
+    if ev.is_closed() {
+        return reactor.delete(stream.as_fd().as_raw_fd());
+    }
+
+    let mut buf = [0; 512];
+    loop {
+        match stream.read(&mut buf) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(_) | Ok(0) => return reactor.delete(stream.as_fd().as_raw_fd()),
+            Ok(n) => stream.write_all(&buf[..n])?, // Send buffer omitted.
+        }
+    }
+}
+
+/// Sends `fd` with `SCM_RIGHTS`, along with the one byte of payload it requires.
+fn send_fd(socket: &UnixStream, fd: BorrowedFd<'_>) -> io::Result<()> {
+    const LEN: usize = mem::size_of::<RawFd>();
+    // Aligned for `cmsghdr`, and large enough for one fd.
+    let mut cmsg_buf = [0u64; 4];
+
+    let mut byte = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: 1,
+    };
+    // SAFETY: `msg` points to `iov` and `cmsg_buf`, valid for the duration of the
+    // call, and the one control message written fits in `cmsg_buf`.
+    let ret = unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr().cast();
+        msg.msg_controllen = libc::CMSG_SPACE(LEN as _) as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(LEN as _) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast(), fd.as_raw_fd());
+
+        libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+    };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! One loop per worker thread, each started with `eventp::spawn_loop`, then stopped
+//! and joined by the main thread.
+//!
+//! Run it with `cargo run --example loop-per-thread --features remote-endpoint`.
+//! Every worker counts the ticks of a timerfd created on its own thread by the
+//! setup closure, in a thread-local. The main thread asks each worker for its
+//! count through the endpoint of its loop, then stops them all.
+
+use std::cell::Cell;
+use std::time::Duration;
+use std::{io, thread};
+
+use eventp::tri_subscriber::WithHandler;
+use eventp::{interest, spawn_loop, LoopHandle, Subscriber};
+use nix::sys::time::TimeSpec;
+use nix::sys::timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags};
+
+const WORKERS: usize = 3;
+
+thread_local!(static TICKS: Cell<u64> = const { Cell::new(0) });
+
+fn main() -> io::Result<()> {
+    let workers = (0..WORKERS)
+        .map(|i| spawn_loop(&format!("worker-{i}"), setup))
+        .collect::<io::Result<Vec<LoopHandle>>>()?;
+
+    thread::sleep(Duration::from_secs(1));
+    for (i, worker) in workers.iter().enumerate() {
+        let ticks = worker
+            .endpoint()
+            .call_blocking(|_eventp| Ok(TICKS.with(Cell::get)))?;
+        println!("worker-{i} ticked {ticks} times");
+    }
+
+    for worker in &workers {
+        worker.stop()?;
+    }
+    for worker in workers {
+        worker.join()?;
+    }
+    Ok(())
+}
+
+/// Runs on the thread of the worker, before its loop starts.
+fn setup(eventp: &mut eventp::Eventp) -> io::Result<()> {
+    let timerfd = TimerFd::new(
+        ClockId::CLOCK_MONOTONIC,
+        TimerFlags::TFD_NONBLOCK | TimerFlags::TFD_CLOEXEC,
+    )?;
+    let tick = TimeSpec::from_duration(Duration::from_millis(100));
+    timerfd.set(Expiration::Interval(tick), TimerSetTimeFlags::empty())?;
+
+    interest()
+        .read()
+        .with_fd(timerfd)
+        .with_handler(|timerfd: &mut TimerFd| -> io::Result<()> {
+            timerfd.wait()?;
+            TICKS.with(|ticks| ticks.set(ticks.get() + 1));
+            Ok(())
+        })
+        .register_into(eventp)
+}
@@ -0,0 +1,228 @@
+//! mio's `tcp_server` example, migrated to eventp with `eventp::mio_compat`.
+//!
+//! The two halves show the two ways to migrate:
+//!
+//! - The listener moved to a regular subscriber: [`MioSource`] makes the mio
+//!   `TcpListener` usable as its fd.
+//! - The connections are still kept by the legacy code, in a
+//!   `HashMap<Token, TcpStream>`, and handled by the unchanged
+//!   `handle_connection_event`, which dispatches on the token. Each connection
+//!   is watched by a [`TokenSubscriber`] over a duplicate of its fd.
+//!
+//! | mio                                      | eventp                                   |
+//! |------------------------------------------|------------------------------------------|
+//! | `poll.poll(&mut events, None)` + `match` | `reactor.run_forever()`                  |
+//! | `registry.register(.., token, i)`        | `TokenSubscriber::new(token, fd, i.into(), ..)` |
+//! | `registry.reregister(.., token, i)`      | `eventp.modify(fd, i.into())`            |
+//! | `registry.deregister(..)`                | `eventp.delete(fd)`                      |
+//!
+//! Run it with `cargo run --example mio-tcp-server --features mio-compat`, then
+//! `nc 127.0.0.1 9000`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::rc::Rc;
+use std::str::from_utf8;
+
+use eventp::mio_compat::{MioSource, TokenSubscriber};
+use eventp::tri_subscriber::WithHandler;
+use eventp::{Event, Eventp, EventpOps, Pinned, Subscriber};
+use mio::net::{TcpListener, TcpStream};
+use mio::{Interest, Token};
+
+// Setup some tokens to allow us to identify which event is for which socket.
+const SERVER: Token = Token(0);
+
+// Some data we'll send over the connection.
+const DATA: &[u8] = b"Hello world!\n";
+
+/// Map of `Token` -> `TcpStream`, plus the fd watched for it.
+type Connections = Rc<RefCell<HashMap<Token, (TcpStream, RawFd)>>>;
+
+fn main() -> io::Result<()> {
+    let mut reactor = Eventp::default();
+
+    // Setup the TCP server socket.
+    let addr = "127.0.0.1:9000".parse().unwrap();
+    let server = MioSource::new(TcpListener::bind(addr)?)?;
+
+    let connections = Connections::default();
+    // Unique token for each incoming connection.
+    let mut unique_token = Token(SERVER.0 + 1);
+
+    eventp::Interest::from(Interest::READABLE)
+        .with_fd(server)
+        .with_handler(
+            move |server: &mut MioSource<TcpListener>, eventp: Pinned<'_, Eventp>| {
+                accept(server.get_ref(), &connections, &mut unique_token, eventp)
+            },
+        )
+        .register_into(&mut reactor)?;
+
+    println!("You can connect to the server using `nc`:");
+    println!(" $ nc 127.0.0.1 9000");
+    println!("You'll see our welcome message and anything you type will be printed here.");
+
+    reactor.run_forever()
+}
+
+/// The `SERVER => loop { .. }` arm.
+fn accept(
+    server: &TcpListener,
+    connections: &Connections,
+    unique_token: &mut Token,
+    mut eventp: Pinned<'_, Eventp>,
+) -> io::Result<()> {
+    loop {
+        // Received an event for the TCP server socket, which indicates we can
+        // accept a connection.
+        let (connection, address) = match server.accept() {
+            Ok((connection, address)) => (connection, address),
+            // If we get a `WouldBlock` error we know our listener has no more
+            // incoming connections queued, so we can return to polling and wait
+            // for some more.
+            Err(e) if would_block(&e) => return Ok(()),
+            // If it was any other kind of error, something went wrong, and the
+            // loop's `ErrorPolicy` decides what to do.
+            Err(e) => return Err(e),
+        };
+
+        println!("Accepted connection from: {address}");
+
+        let token = next(unique_token);
+        let watched = connection.as_fd().try_clone_to_owned()?;
+        let watched_fd = watched.as_raw_fd();
+        let conns = connections.clone();
+        TokenSubscriber::new(
+            token,
+            watched,
+            Interest::READABLE.add(Interest::WRITABLE).into(),
+            move |token, event, mut eventp: Pinned<'_, Eventp>| {
+                on_connection_event(&conns, token, event, &mut eventp)
+            },
+        )
+        .register_into(&mut eventp)?;
+
+        connections
+            .borrow_mut()
+            .insert(token, (connection, watched_fd));
+    }
+}
+
+/// The `token => { .. }` arm.
+fn on_connection_event(
+    connections: &Connections,
+    token: Token,
+    event: Event,
+    eventp: &mut Pinned<'_, impl EventpOps>,
+) -> io::Result<()> {
+    let mut connections = connections.borrow_mut();
+    // Maybe received an event for a TCP connection.
+    let done = if let Some((connection, fd)) = connections.get_mut(&token) {
+        handle_connection_event(eventp, *fd, connection, &event)?
+    } else {
+        // Sporadic events happen, we can safely ignore them.
+        false
+    };
+    if done {
+        if let Some((_connection, fd)) = connections.remove(&token) {
+            eventp.delete(fd)?;
+        }
+    }
+    Ok(())
+}
+
+fn next(current: &mut Token) -> Token {
+    let next = current.0;
+    current.0 += 1;
+    Token(next)
+}
+
+/// Returns `true` if the connection is done.
+///
+/// Unchanged from mio, except for `registry.reregister(..)`.
+fn handle_connection_event(
+    eventp: &mut Pinned<'_, impl EventpOps>,
+    fd: RawFd,
+    connection: &mut TcpStream,
+    event: &Event,
+) -> io::Result<bool> {
+    if event.is_writable() {
+        // We can (maybe) write to the connection.
+        match connection.write(DATA) {
+            // We want to write the entire `DATA` buffer in a single go. If we
+            // write less we'll return a short write error (same as
+            // `io::Write::write_all` does).
+            Ok(n) if n < DATA.len() => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(_) => {
+                // After we've written something we'll reregister the connection
+                // to only respond to readable events.
+                eventp.modify(fd, Interest::READABLE.into())?
+            }
+            // Would block "errors" are the OS's way of saying that the
+            // connection is not actually ready to perform this I/O operation.
+            Err(ref err) if would_block(err) => {}
+            // Got interrupted (how rude!), we'll try again.
+            Err(ref err) if interrupted(err) => {
+                return handle_connection_event(eventp, fd, connection, event)
+            }
+            // Other errors we'll consider fatal.
+            Err(err) => return Err(err),
+        }
+    }
+
+    if event.is_readable() {
+        let mut connection_closed = false;
+        let mut received_data = vec![0; 4096];
+        let mut bytes_read = 0;
+        // We can (maybe) read from the connection.
+        loop {
+            match connection.read(&mut received_data[bytes_read..]) {
+                Ok(0) => {
+                    // Reading 0 bytes means the other side has closed the
+                    // connection or is done writing, then so are we.
+                    connection_closed = true;
+                    break;
+                }
+                Ok(n) => {
+                    bytes_read += n;
+                    if bytes_read == received_data.len() {
+                        received_data.resize(received_data.len() + 1024, 0);
+                    }
+                }
+                // Would block "errors" are the OS's way of saying that the
+                // connection is not actually ready to perform this I/O operation.
+                Err(ref err) if would_block(err) => break,
+                Err(ref err) if interrupted(err) => continue,
+                // Other errors we'll consider fatal.
+                Err(err) => return Err(err),
+            }
+        }
+
+        if bytes_read != 0 {
+            let received_data = &received_data[..bytes_read];
+            if let Ok(str_buf) = from_utf8(received_data) {
+                println!("Received data: {}", str_buf.trim_end());
+            } else {
+                println!("Received (none UTF-8) data: {received_data:?}");
+            }
+        }
+
+        if connection_closed {
+            println!("Connection closed");
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn would_block(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::WouldBlock
+}
+
+fn interrupted(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::Interrupted
+}
@@ -0,0 +1,83 @@
+//! Prints every change of the network interfaces and their addresses, with
+//! `eventp::netlink`.
+//!
+//! Run it with `cargo run --example netlink-monitor --features netlink`, then e.g.
+//! `ip link set dev <name> down` or `ip addr add 10.1.2.3/24 dev <name>` in another
+//! terminal. It starts with a dump of the current interfaces and addresses, and
+//! dumps them again whenever changes were lost.
+
+use std::io;
+
+use eventp::netlink::{self, Dump, Groups, RouteEvent, Socket};
+use eventp::{Eventp, Pinned, Subscriber};
+
+fn main() -> io::Result<()> {
+    let mut reactor = Eventp::default();
+    let monitor = netlink::monitor(Groups::LINK | Groups::IPV4_ADDR | Groups::IPV6_ADDR)?;
+    let mut printer = Printer {
+        socket: monitor.socket(),
+        dumping: None,
+        lost: false,
+    };
+    printer.request(Dump::Links)?;
+    monitor
+        .with_handler(move |event: RouteEvent, _reactor: Pinned<'_, Eventp>| {
+            printer.on_event(event)
+        })
+        .register_into(&mut reactor)?;
+    reactor.run_forever()
+}
+
+/// Prints the events, and requests the dumps of a resync.
+struct Printer {
+    socket: Socket,
+    /// The dump running, if any.
+    dumping: Option<Dump>,
+    /// Whether changes were lost during the dump running, which must be done again.
+    lost: bool,
+}
+
+impl Printer {
+    fn on_event(&mut self, event: RouteEvent) -> io::Result<()> {
+        match event {
+            RouteEvent::LinkUp { ifindex, name } => println!("link {ifindex} {name:?} up"),
+            RouteEvent::LinkDown { ifindex, name } => println!("link {ifindex} {name:?} down"),
+            RouteEvent::LinkRemoved { ifindex, name } => {
+                println!("link {ifindex} {name:?} removed")
+            }
+            RouteEvent::AddrAdded {
+                ifindex,
+                addr,
+                prefix_len,
+            } => println!("link {ifindex}: added {addr}/{prefix_len}"),
+            RouteEvent::AddrRemoved {
+                ifindex,
+                addr,
+                prefix_len,
+            } => println!("link {ifindex}: removed {addr}/{prefix_len}"),
+            RouteEvent::Overflow => {
+                println!("changes lost, resyncing");
+                match self.dumping {
+                    // Requested once the running one is done.
+                    Some(_) => self.lost = true,
+                    None => self.request(Dump::Links)?,
+                }
+            }
+            RouteEvent::DumpDone => match (self.dumping.take(), self.lost) {
+                (_, true) => {
+                    self.lost = false;
+                    self.request(Dump::Links)?;
+                }
+                (Some(Dump::Links), false) => self.request(Dump::Addrs)?,
+                _ => println!("in sync"),
+            },
+            RouteEvent::Other(_) => {}
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, dump: Dump) -> io::Result<()> {
+        self.dumping = Some(dump);
+        self.socket.request_dump(dump)
+    }
+}
@@ -0,0 +1,101 @@
+//! Plugins contributing subscribers to a loop they only know as `Ep: EventpOps`,
+//! wired in with `Eventp::register_all`.
+//!
+//! Run it with `cargo run --example plugins`. The ticker plugin is ticked by a
+//! thread, the echo plugin echoes what the host writes to a pipe, and a third
+//! plugin fails to find its device. Each plugin deletes its subscriber once done,
+//! and the loop returns when none is left.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::thread;
+use std::time::Duration;
+
+use eventp::thin::ThinBoxSubscriber;
+use eventp::tri_subscriber::WithHandler;
+use eventp::{Eventp, EventpOps, Pinned, RegisterPolicy, SubscriberFactory};
+use nix::sys::eventfd::EventFd;
+
+const TICKS: u64 = 3;
+
+fn main() -> io::Result<()> {
+    let (input, host) = nix::unistd::pipe()?;
+    let plugins: Vec<Box<dyn SubscriberFactory<Eventp>>> = vec![
+        Box::new(Ticker),
+        Box::new(Echo { input }),
+        Box::new(|| -> io::Result<Vec<ThinBoxSubscriber<Eventp>>> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "no /dev/toy0"))
+        }),
+    ];
+
+    let mut reactor = Eventp::default();
+    let report = reactor.register_all(plugins, RegisterPolicy::BestEffort)?;
+    for (index, err) in report.failures() {
+        println!("plugin {index} not loaded: {err}");
+    }
+
+    // Closing the pipe once written lets the echo plugin see the end of it.
+    File::from(host).write_all(b"hello\n")?;
+
+    while !reactor.is_empty() {
+        reactor.run_once()?;
+    }
+    Ok(())
+}
+
+/// Ticks an eventfd from a thread, `TICKS` times.
+struct Ticker;
+
+impl<Ep: EventpOps> SubscriberFactory<Ep> for Ticker {
+    fn build(self: Box<Self>) -> io::Result<Vec<ThinBoxSubscriber<Ep>>> {
+        let efd = EventFd::new()?;
+        let writer = efd.as_fd().try_clone_to_owned()?;
+        thread::spawn(move || {
+            for _ in 0..TICKS {
+                thread::sleep(Duration::from_millis(100));
+                nix::unistd::write(&writer, &1u64.to_ne_bytes()).unwrap();
+            }
+        });
+
+        let mut ticks = 0;
+        let ticker = eventp::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move |efd: &mut EventFd, mut reactor: Pinned<'_, Ep>| {
+                ticks += efd.read()?;
+                println!("tick {ticks}");
+                if ticks >= TICKS {
+                    reactor.delete(efd.as_raw_fd())?;
+                }
+                io::Result::Ok(())
+            })
+            .into();
+        Ok(vec![ticker])
+    }
+}
+
+/// Echoes the lines written to `input`, until it is closed.
+struct Echo {
+    input: OwnedFd,
+}
+
+impl<Ep: EventpOps> SubscriberFactory<Ep> for Echo {
+    fn build(self: Box<Self>) -> io::Result<Vec<ThinBoxSubscriber<Ep>>> {
+        let echo = eventp::interest()
+            .read()
+            .with_fd(File::from(self.input))
+            .with_handler(|input: &mut File, mut reactor: Pinned<'_, Ep>| {
+                let mut buf = [0; 256];
+                match input.read(&mut buf)? {
+                    0 => reactor.delete(input.as_raw_fd()),
+                    n => {
+                        print!("echo: {}", String::from_utf8_lossy(&buf[..n]));
+                        Ok(())
+                    }
+                }
+            })
+            .into();
+        Ok(vec![echo])
+    }
+}
@@ -0,0 +1,52 @@
+//! A supervisor respawning its worker process whenever it crashes, with
+//! `eventp::process`.
+//!
+//! Run it with `cargo run --example supervisor`. The worker is a shell crashing
+//! after a second; the supervisor gives up after `MAX_RESTARTS` restarts. A real
+//! supervisor would watch other fds in the same loop, such as a control socket.
+
+use std::cell::Cell;
+use std::io;
+use std::process::{Command, ExitStatus};
+use std::rc::Rc;
+
+use eventp::{process, Eventp, EventpOpsAdd, Pinned, Subscriber};
+
+const MAX_RESTARTS: u32 = 3;
+
+fn main() -> io::Result<()> {
+    let mut reactor = Eventp::default();
+    let done = Rc::new(Cell::new(false));
+    supervise(&mut reactor, 0, done.clone())?;
+    while !done.get() {
+        reactor.run_once()?;
+    }
+    Ok(())
+}
+
+/// Spawns the worker, and watches it for being respawned once it exits.
+fn supervise<R>(reactor: &mut R, restarts: u32, done: Rc<Cell<bool>>) -> io::Result<()>
+where
+    R: EventpOpsAdd<Eventp>,
+{
+    let worker = Command::new("sh")
+        .args(["-c", "echo worker $$ up; sleep 1; exit 1"])
+        .spawn()?;
+
+    process::watch_child(&worker)?
+        .with_handler(
+            move |status: ExitStatus, mut reactor: Pinned<'_, Eventp>| -> io::Result<()> {
+                if status.success() {
+                    println!("worker done");
+                } else if restarts < MAX_RESTARTS {
+                    println!("worker failed with {status}, restarting");
+                    return supervise(&mut reactor, restarts + 1, done);
+                } else {
+                    println!("worker failed with {status}, giving up");
+                }
+                done.set(true);
+                Ok(())
+            },
+        )
+        .register_into(reactor)
+}
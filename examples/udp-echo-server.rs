@@ -0,0 +1,61 @@
+//! A UDP echo server, receiving every datagram whole with `eventp::dgram`.
+//!
+//! Run it with `cargo run --example udp-echo-server`. A thread plays the client:
+//! it sends datagrams of various sizes, checks each echo, then sends `quit`, on
+//! which the server deletes its socket and the loop ends.
+
+use std::net::UdpSocket;
+use std::os::fd::AsRawFd;
+use std::{io, thread};
+
+use eventp::dgram::{self, Datagram};
+use eventp::{Eventp, Pinned, Subscriber};
+
+fn main() -> io::Result<()> {
+    let socket = UdpSocket::bind("127.0.0.1:0")?;
+    socket.set_nonblocking(true)?;
+    let addr = socket.local_addr()?;
+    let client = thread::spawn(move || -> io::Result<()> {
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        socket.connect(addr)?;
+        let mut buf = vec![0; 65536];
+        for len in [1, 1000, 60000] {
+            let sent = vec![b'x'; len];
+            socket.send(&sent)?;
+            let n = socket.recv(&mut buf)?;
+            assert_eq!(&buf[..n], &sent[..]);
+        }
+        socket.send(b"quit")?;
+        Ok(())
+    });
+
+    let mut reactor = Eventp::default();
+    dgram::receiver(socket)
+        .with_handler(on_datagram)
+        .register_into(&mut reactor)?;
+    while !reactor.is_empty() {
+        reactor.run_once()?;
+    }
+    client.join().expect("client panicked")?;
+    Ok(())
+}
+
+fn on_datagram(
+    socket: &mut UdpSocket,
+    datagram: Datagram,
+    mut reactor: Pinned<'_, Eventp>,
+) -> io::Result<()> {
+    let Some(peer) = datagram.peer else {
+        return Ok(());
+    };
+    println!("{} bytes from {peer}", datagram.data.len());
+    if datagram.data == b"quit" {
+        return reactor.delete(socket.as_raw_fd());
+    }
+    // The send buffer of a UDP socket seldom fills up; a datagram that would
+    // block is dropped, as the network could have.
+    match socket.send_to(&datagram.data, peer) {
+        Err(e) if e.kind() != io::ErrorKind::WouldBlock => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,80 @@
+//! A server of a protocol whose clients interrupt a transfer with a TCP urgent
+//! byte, read with `eventp::oob`.
+//!
+//! Run it with `cargo run --example urgent-data`. A thread plays the client: it
+//! sends a few lines, then an urgent `!` telling the server to drop what it has not
+//! read yet. The server registers the connection with `.read().priority()`, and
+//! handles `EPOLLPRI` before the in-band data of the same event.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{AsFd, AsRawFd};
+use std::thread;
+use std::time::Duration;
+
+use eventp::tri_subscriber::WithHandler;
+use eventp::{oob, Event, Eventp, Pinned, Subscriber};
+
+fn main() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let client = thread::spawn(move || -> io::Result<()> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.write_all(b"line 1\nline 2\n")?;
+        thread::sleep(Duration::from_millis(100));
+        // In one segment, reported as `EPOLLIN | EPOLLPRI` at once.
+        oob::send_oob(stream.as_fd(), b"line 3, never read\n!")?;
+        thread::sleep(Duration::from_millis(100));
+        stream.write_all(b"line 4\n")
+    });
+
+    let (stream, _) = listener.accept()?;
+    stream.set_nonblocking(true)?;
+    let mut reactor = Eventp::default();
+    eventp::interest()
+        .read()
+        .priority()
+        .with_fd(stream)
+        .with_handler(on_data)
+        .register_into(&mut reactor)?;
+
+    while !reactor.is_empty() {
+        reactor.run_once()?;
+    }
+    client.join().expect("client panicked")
+}
+
+fn on_data(
+    stream: &mut TcpStream,
+    event: Event,
+    mut reactor: Pinned<'_, Eventp>,
+) -> io::Result<()> {
+    let mut buf = [0; 512];
+    // First, so the in-band data sent before the urgent byte is dropped, rather
+    // than handled by the read below.
+    if event.is_priority() {
+        let mut urgent = [0];
+        oob::recv_oob(stream.as_fd(), &mut urgent)?;
+        println!(
+            "urgent byte {:?}, dropping the data before it",
+            urgent[0] as char
+        );
+        while !oob::at_mark(stream.as_fd())? {
+            match stream.read(&mut buf)? {
+                0 => break,
+                n => println!("dropped {:?}", String::from_utf8_lossy(&buf[..n])),
+            }
+        }
+    }
+    if !event.is_readable() {
+        return Ok(());
+    }
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => return reactor.delete(stream.as_raw_fd()),
+            Ok(n) => print!("{}", String::from_utf8_lossy(&buf[..n])),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! An echo server over vsock, accepting connections from the virtual machines of
+//! the host, or from the host when run in a guest.
+//!
+//! Run it with `cargo run --example vsock-echo-server --features vsock [port]`,
+//! then connect e.g. with `socat - VSOCK-CONNECT:<cid>:<port>` from the other side,
+//! `<cid>` being 2 to reach the host. The port defaults to 1024.
+//!
+//! The connection handler knows nothing of vsock: it serves any stream that
+//! `eventp::net::Accept` hands out, TCP or unix sockets as well.
+
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd};
+
+use eventp::vsock::{VsockAddr, VsockListener, VsockStream};
+use eventp::{acceptor, Event, Eventp, EventpOps, Pinned, Subscriber};
+
+fn main() -> io::Result<()> {
+    let port = match std::env::args().nth(1) {
+        Some(port) => port
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        None => 1024,
+    };
+    let listener = VsockListener::bind(VsockAddr::new(VsockAddr::CID_ANY, port))?;
+    listener.set_nonblocking(true)?;
+    println!("listening on {}", listener.local_addr()?);
+
+    let mut reactor = Eventp::default();
+    acceptor(listener)
+        .with_conn_handler(|_stream: &VsockStream, addr: VsockAddr| {
+            println!("{addr} connected");
+            on_data::<VsockStream, Eventp>
+        })
+        .register_into(&mut reactor)?;
+    reactor.run_forever()
+}
+
+fn on_data<S, Ep>(stream: &mut S, event: Event, mut reactor: Pinned<'_, Ep>) -> io::Result<()>
+where
+    S: Read + Write + AsFd,
+    Ep: EventpOps,
+{
+    if !event.is_readable() {
+        if event.is_closed() {
+            return reactor.delete(stream.as_fd().as_raw_fd());
+        }
+        return Ok(());
+    }
+
+    let mut buf = [0; 512];
+    loop {
+        match stream.read(&mut buf) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(_) | Ok(0) => return reactor.delete(stream.as_fd().as_raw_fd()),
+            Ok(n) => stream.write_all(&buf[..n])?, // Send buffer omitted.
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! A listener subscriber running the accept loop, and registering every
+//! connection into the same event loop.
+//!
+//! [`acceptor()`] wraps a nonblocking listener. When it is readable, the
+//! subscriber accepts until it would block, builds a handler for each connection
+//! with the factory given to [`with_conn_handler`](Acceptor::with_conn_handler),
+//! and registers the connection as a [`TriSubscriber`], with the interest of
+//! [`with_conn_interest`](Acceptor::with_conn_interest). Draining the backlog also
+//! makes the listener correct when registered edge-triggered, which would not
+//! wake again for connections left behind.
+//!
+//! Any listener implementing [`Accept`] can be used, such as a
+//! [`TcpListener`](std::net::TcpListener), a
+//! [`UnixListener`](std::os::unix::net::UnixListener), or a `VsockListener` of the
+//! `vsock` feature. Connection
+//! handlers generic over [`Accept::Stream`] then serve any of them.
+//!
+//! # Running out of fds
+//!
+//! When `accept` fails with `EMFILE` or `ENFILE`, or the kernel is short of
+//! memory, the pending connection stays in the backlog and a level-triggered
+//! listener would be reported again right away. The subscriber instead drops its
+//! interest in reading, and resumes once the [`backoff`](Acceptor::backoff) has
+//! elapsed, 100ms by default, through an [`idle_timeout`](Interest::idle_timeout).
+//! Only [`Eventp`](crate::Eventp) runs idle timeouts: with other loops, the
+//! listener stays paused.
+//!
+//! # Errors
+//!
+//! A connection the loop refuses, e.g. past
+//! [`max_subscribers`](crate::Builder::max_subscribers), is closed, and the error
+//! is reported to the loop's [`ErrorPolicy`](crate::ErrorPolicy) once the backlog
+//! has been drained. So are the errors of `accept` other than the above, which
+//! end the loop early.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::io::{Read, Write};
+//! use std::net::{TcpListener, TcpStream};
+//! use std::os::fd::AsRawFd;
+//!
+//! use eventp::{acceptor, Eventp, EventpOps, Pinned, Subscriber};
+//!
+//! # fn main() -> io::Result<()> {
+//! let listener = TcpListener::bind("127.0.0.1:0")?;
+//! listener.set_nonblocking(true)?;
+//!
+//! let mut eventp = Eventp::default();
+//! acceptor(listener)
+//!     .with_conn_handler(|stream: &TcpStream, _addr| {
+//!         let _ = stream.set_nodelay(true);
+//!         |stream: &mut TcpStream, mut eventp: Pinned<'_, Eventp>| -> io::Result<()> {
+//!             let mut buf = [0; 512];
+//!             loop {
+//!                 match stream.read(&mut buf) {
+//!                     Ok(0) => return eventp.delete(stream.as_raw_fd()),
+//!                     Ok(n) => stream.write_all(&buf[..n])?, // Send buffer omitted.
+//!                     Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+//!                     Err(_) => return eventp.delete(stream.as_raw_fd()),
+//!                 }
+//!             }
+//!         }
+//!     })
+//!     .register_into(&mut eventp)?;
+//! # Ok(()) }
+//! ```
+
+use std::cell::Cell;
+use std::io;
+use std::marker::PhantomData;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::time::Duration;
+
+/// Re-exported from [`net`](crate::net), where it is declared.
+pub use crate::net::Accept;
+use crate::subscriber::{Handler, HasInterest};
+use crate::tri_subscriber::{TriSubscriber, WithHandler};
+use crate::{interest, Event, EventpOps, Interest, Pinned};
+
+/// Creates an [`Acceptor`] over a nonblocking listener.
+///
+/// For more information, see the [mod-level documentation](self).
+pub fn acceptor<L: Accept>(listener: L) -> Acceptor<L> {
+    Acceptor {
+        listener,
+        conn_interest: Interest::stream_read_et(),
+        backoff: Duration::from_millis(100),
+    }
+}
+
+/// A not yet complete acceptor, waiting for its connection handler factory.
+pub struct Acceptor<L> {
+    listener: L,
+    conn_interest: Interest,
+    backoff: Duration,
+}
+
+impl<L: Accept> Acceptor<L> {
+    /// Sets the interest every connection is registered with, by default
+    /// [`Interest::stream_read_et`].
+    pub fn with_conn_interest(mut self, interest: Interest) -> Self {
+        self.conn_interest = interest;
+        self
+    }
+
+    /// Sets how long to stop accepting after running out of fds, see
+    /// [Running out of fds](self#running-out-of-fds).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `backoff` is zero.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        assert!(!backoff.is_zero(), "backoff must be greater than zero");
+        self.backoff = backoff;
+        self
+    }
+
+    /// Completes the acceptor with the factory called for every accepted
+    /// connection, returning its handler.
+    ///
+    /// The factory borrows the connection, e.g. to set socket options, as the
+    /// subscriber keeps it to register. The handler takes the parameters of a [`tri_subscriber`](crate::tri_subscriber)
+    /// handler, with the connection as the fd.
+    pub fn with_conn_handler<F, H, Args>(self, factory: F) -> Subscriber<L, F, Args>
+    where
+        F: FnMut(&L::Stream, L::Addr) -> H,
+    {
+        Subscriber {
+            listener: self.listener,
+            interest: Cell::new(interest().read()),
+            conn_interest: self.conn_interest,
+            backoff: self.backoff,
+            paused: None,
+            factory,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The subscriber created by [`Acceptor::with_conn_handler`].
+pub struct Subscriber<L, F, Args> {
+    listener: L,
+    interest: Cell<Interest>,
+    conn_interest: Interest,
+    backoff: Duration,
+    /// The interest to restore once the backoff has elapsed, while paused.
+    paused: Option<Interest>,
+    factory: F,
+    _marker: PhantomData<fn(Args)>,
+}
+
+impl<L, F, Args> Subscriber<L, F, Args> {
+    /// Returns a reference to the listener.
+    pub fn get_ref(&self) -> &L {
+        &self.listener
+    }
+
+    /// Returns `true` while accepting is paused after running out of fds.
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+}
+
+impl<L: AsFd, F, Args> AsFd for Subscriber<L, F, Args> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.listener.as_fd()
+    }
+}
+
+impl<L, F, Args> HasInterest for Subscriber<L, F, Args> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, L, F, H, Args> Handler<Ep> for Subscriber<L, F, Args>
+where
+    Ep: EventpOps,
+    L: Accept,
+    F: FnMut(&L::Stream, L::Addr) -> H,
+    TriSubscriber<L::Stream, Args, H>: crate::Subscriber<Ep>,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        use crate::Subscriber as _;
+
+        let mut result = Ok(());
+        loop {
+            let (stream, addr) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return result,
+                Err(e) if is_transient(&e) => continue,
+                Err(e) if is_out_of_fds(&e) => {
+                    let interest = eventp.current_interest().unwrap_or(self.interest.get());
+                    let fd = self.listener.as_fd().as_raw_fd();
+                    eventp.modify(fd, crate::interest().idle_timeout(self.backoff))?;
+                    self.paused = Some(interest);
+                    return result;
+                }
+                Err(e) => return result.and(Err(e)),
+            };
+            let handler = (self.factory)(&stream, addr);
+            let r = self
+                .conn_interest
+                .with_fd(stream)
+                .with_handler(handler)
+                .register_into(&mut eventp);
+            result = result.and(r);
+        }
+    }
+
+    fn on_idle(&mut self, mut eventp: Pinned<'_, Ep>) -> bool {
+        let Some(interest) = self.paused.take() else {
+            // Registered with an idle timeout of its own.
+            return true;
+        };
+        let fd = self.listener.as_fd().as_raw_fd();
+        if eventp.modify(fd, interest).is_err() {
+            // Still paused: try again after another backoff.
+            self.paused = Some(interest);
+        }
+        false
+    }
+}
+
+/// Errors of `accept` for a connection that failed before it could be accepted,
+/// after which the next one can be.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EINTR | libc::ECONNABORTED | libc::EPROTO)
+    )
+}
+
+/// Errors of `accept` that leave the connection pending until resources are freed.
+fn is_out_of_fds(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM)
+    )
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::os::fd::RawFd;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::rc::Rc;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{Eventp, Subscriber as _};
+
+    fn listener() -> TcpListener {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        listener
+    }
+
+    fn poll_timeout() -> EpollTimeout {
+        EpollTimeout::from(500u16)
+    }
+
+    /// Registers `acceptor` with echoing connections, and returns their raw fds.
+    fn register_echoing<L>(
+        eventp: &mut Eventp,
+        acceptor: Acceptor<L>,
+        interest: Interest,
+    ) -> Rc<RefCell<Vec<RawFd>>>
+    where
+        L: Accept + 'static,
+        L::Stream: Read + Write + 'static,
+        L::Addr: 'static,
+    {
+        let accepted = Rc::new(RefCell::new(Vec::new()));
+        let a = accepted.clone();
+        acceptor
+            .with_conn_handler(move |stream: &L::Stream, _| {
+                a.borrow_mut().push(stream.as_fd().as_raw_fd());
+                |stream: &mut L::Stream| {
+                    let mut buf = [0; 16];
+                    while let Ok(n @ 1..) = stream.read(&mut buf) {
+                        stream.write_all(&buf[..n]).unwrap();
+                    }
+                }
+            })
+            .register_with_interest(interest, eventp)
+            .unwrap();
+        accepted
+    }
+
+    fn assert_echoes<S>(eventp: &mut Eventp, client: &mut S)
+    where
+        S: Read,
+        for<'a> &'a S: Write,
+    {
+        (&*client).write_all(b"hi").unwrap();
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        let mut buf = [0; 2];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hi");
+    }
+
+    #[test]
+    fn edge_triggered_listener_accepts_every_pending_connection() {
+        let listener = listener();
+        let addr = listener.local_addr().unwrap();
+        let mut eventp = Eventp::default();
+        let interest = interest().read().edge_triggered();
+        let accepted = register_echoing(&mut eventp, acceptor(listener), interest);
+
+        let mut clients: Vec<_> = (0..3).map(|_| TcpStream::connect(addr).unwrap()).collect();
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(accepted.borrow().len(), 3);
+        for fd in accepted.borrow().iter() {
+            assert_eq!(eventp.interest(fd), Some(Interest::stream_read_et()));
+        }
+
+        for client in &mut clients {
+            assert_echoes(&mut eventp, client);
+        }
+    }
+
+    #[test]
+    fn connections_get_the_conn_interest() {
+        let listener = listener();
+        let addr = listener.local_addr().unwrap();
+        let mut eventp = Eventp::default();
+        let conn_interest = interest().read();
+        let acceptor = acceptor(listener).with_conn_interest(conn_interest);
+        let accepted = register_echoing(&mut eventp, acceptor, interest().read());
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(eventp.interest(&accepted.borrow()[0]), Some(conn_interest));
+
+        assert_echoes(&mut eventp, &mut client);
+    }
+
+    #[test]
+    fn unix_listener_accepts_the_same_way() {
+        let dir = std::env::temp_dir().join(format!("eventp-acceptor-{}", std::process::id()));
+        let _ = std::fs::remove_file(&dir);
+        let listener = UnixListener::bind(&dir).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let mut eventp = Eventp::default();
+        let accepted = register_echoing(&mut eventp, acceptor(listener), interest().read());
+
+        let mut client = UnixStream::connect(&dir).unwrap();
+        std::fs::remove_file(&dir).unwrap();
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(accepted.borrow().len(), 1);
+
+        assert_echoes(&mut eventp, &mut client);
+    }
+
+    /// A listener failing with `EMFILE` a number of times first.
+    struct Exhausted {
+        listener: TcpListener,
+        failures: Cell<u32>,
+    }
+
+    impl AsFd for Exhausted {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.listener.as_fd()
+        }
+    }
+
+    impl Accept for Exhausted {
+        type Stream = TcpStream;
+        type Addr = SocketAddr;
+
+        fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(io::Error::from_raw_os_error(libc::EMFILE));
+            }
+            Accept::accept(&self.listener)
+        }
+    }
+
+    #[test]
+    fn running_out_of_fds_pauses_until_the_backoff_elapsed() {
+        let listener = listener();
+        let addr = listener.local_addr().unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let exhausted = Exhausted {
+            listener,
+            failures: Cell::new(1),
+        };
+        let mut eventp = Eventp::default();
+        let backoff = Duration::from_millis(50);
+        let accepted = register_echoing(
+            &mut eventp,
+            acceptor(exhausted).backoff(backoff),
+            interest().read(),
+        );
+
+        let _client = TcpStream::connect(addr).unwrap();
+        let start = Instant::now();
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(accepted.borrow().is_empty());
+        assert_eq!(
+            eventp.interest(&raw_fd),
+            Some(crate::interest().idle_timeout(backoff))
+        );
+
+        // Not reported while paused, though the connection is still pending.
+        eventp
+            .run_once_with_timeout(EpollTimeout::from(10u16))
+            .unwrap();
+        assert!(accepted.borrow().is_empty());
+
+        while accepted.borrow().is_empty() {
+            assert!(start.elapsed() < Duration::from_secs(5), "never resumed");
+            eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert!(start.elapsed() >= backoff);
+        assert_eq!(eventp.interest(&raw_fd), Some(crate::interest().read()));
+    }
+}
@@ -0,0 +1,351 @@
+//! Driving an [`Eventp`] from an async runtime, for sync components living inside
+//! a larger async application.
+//!
+//! The epoll fd of an `Eventp` is itself pollable: it is readable whenever one of
+//! its registrations has events ready. [`Eventp::into_async`] hands a duplicate of
+//! it to the runtime, through a [`Readiness`] implementation, and
+//! [`AsyncEventp::run`] awaits that readiness and then dispatches the ready events
+//! with [`Eventp::try_run_once`], so `epoll_wait` never blocks the executor.
+//!
+//! The registrations inside the loop are unaffected, edge-triggered or not, and
+//! anything that wakes the loop when it is run directly, such as a
+//! `remote_endpoint`, wakes it here as well.
+//!
+//! # Runtimes
+//!
+//! The bridge does not depend on any runtime. With tokio, wrap the fd in an
+//! `AsyncFd`:
+//!
+//! ```rust,ignore
+//! use std::io;
+//! use std::os::fd::OwnedFd;
+//! use std::task::{ready, Context, Poll};
+//!
+//! use eventp::async_bridge::Readiness;
+//! use tokio::io::unix::AsyncFd;
+//!
+//! struct Tokio(AsyncFd<OwnedFd>);
+//!
+//! impl Readiness for Tokio {
+//!     fn poll_readable(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+//!         let mut guard = ready!(self.0.poll_read_ready(cx))?;
+//!         guard.clear_ready();
+//!         Poll::Ready(Ok(()))
+//!     }
+//! }
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> io::Result<()> {
+//!     let mut reactor = eventp::Eventp::default();
+//!     // ... register subscribers ...
+//!     let mut reactor = reactor.into_async(|fd| AsyncFd::new(fd).map(Tokio))?;
+//!     reactor.run().await
+//! }
+//! ```
+//!
+//! See [examples/async-echo-server.rs](https://github.com/FuuuOverclocking/eventp/blob/main/examples/async-echo-server.rs)
+//! for a complete program, on a minimal std-only executor.
+
+use std::future::poll_fn;
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::task::{Context, Poll};
+
+use crate::Eventp;
+
+/// Readiness notifications of the runtime for the epoll fd of an [`AsyncEventp`].
+///
+/// Implemented for closures of the same signature.
+pub trait Readiness {
+    /// Polls until the epoll fd may be readable, registering `cx` to be woken
+    /// otherwise.
+    ///
+    /// A runtime that caches readiness, like tokio's `AsyncFd`, should clear it
+    /// before returning `Ready`: [`AsyncEventp`] itself checks whether events are
+    /// left after dispatching, and does not poll again until they are drained.
+    fn poll_readable(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+impl<F> Readiness for F
+where
+    F: FnMut(&mut Context<'_>) -> Poll<io::Result<()>>,
+{
+    fn poll_readable(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self(cx)
+    }
+}
+
+/// An [`Eventp`] driven by an async runtime, see the [module docs](self).
+pub struct AsyncEventp<R> {
+    eventp: Eventp,
+    readiness: R,
+}
+
+impl Eventp {
+    /// Converts this loop into one driven by an async runtime.
+    ///
+    /// `readiness` receives a duplicate of the epoll fd, and returns the runtime's
+    /// [`Readiness`] for it.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from duplicating the fd, or from `readiness`.
+    pub fn into_async<R, F>(self, readiness: F) -> io::Result<AsyncEventp<R>>
+    where
+        R: Readiness,
+        F: FnOnce(OwnedFd) -> io::Result<R>,
+    {
+        let fd = self.poll_fd().try_clone_to_owned()?;
+        Ok(AsyncEventp {
+            readiness: readiness(fd)?,
+            eventp: self,
+        })
+    }
+}
+
+impl<R: Readiness> AsyncEventp<R> {
+    /// Dispatches events as they become ready, forever.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Eventp::run_forever`], plus any error from [`Readiness`].
+    pub async fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.run_once().await?;
+        }
+    }
+
+    /// Waits until events are ready, and dispatches them, including any that
+    /// become ready meanwhile.
+    ///
+    /// Between batches, it yields to the executor once, so a loop that is always
+    /// busy does not starve other tasks.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Eventp::run_once`], plus any error from [`Readiness`].
+    pub async fn run_once(&mut self) -> io::Result<()> {
+        poll_fn(|cx| self.readiness.poll_readable(cx)).await?;
+        loop {
+            self.eventp.try_run_once()?;
+            if !self.has_ready_events()? {
+                return Ok(());
+            }
+            yield_now().await;
+        }
+    }
+
+    /// Checks, without consuming anything, whether the epoll fd is still readable,
+    /// e.g. because more events were ready than fit in the event buffer.
+    fn has_ready_events(&self) -> io::Result<bool> {
+        if self.eventp.pending_events() > 0 {
+            return Ok(true);
+        }
+        let mut pollfd = libc::pollfd {
+            fd: self.eventp.poll_fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `pollfd` is a valid array of one element, for the duration of
+        // the call.
+        match unsafe { libc::poll(&mut pollfd, 1, 0) } {
+            -1 => Err(io::Error::last_os_error()),
+            n => Ok(n > 0),
+        }
+    }
+}
+
+impl<R> AsyncEventp<R> {
+    /// Returns a reference to the inner loop.
+    pub fn get_ref(&self) -> &Eventp {
+        &self.eventp
+    }
+
+    /// Returns a mutable reference to the inner loop, e.g. to register
+    /// subscribers while it is not running.
+    pub fn get_mut(&mut self) -> &mut Eventp {
+        &mut self.eventp
+    }
+
+    /// Unwraps the inner loop, dropping the runtime's readiness.
+    pub fn into_inner(self) -> Eventp {
+        self.eventp
+    }
+}
+
+/// Returns `Pending` once, after waking the task, so the executor can run others.
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::os::fd::AsFd;
+    use std::pin::pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use std::thread::{self, Thread};
+
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use super::*;
+    use crate::tri_subscriber::WithHandler;
+    use crate::{interest, Subscriber};
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// A single-future executor.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+                return out;
+            }
+            thread::park();
+        }
+    }
+
+    fn poll_in(fd: &OwnedFd, timeout: libc::c_int) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: See `has_ready_events`.
+        unsafe { libc::poll(&mut pollfd, 1, timeout) > 0 }
+    }
+
+    /// A runtime stand-in that waits for readiness with a blocking `poll` on a
+    /// helper thread, and counts how often it was polled.
+    struct Blocking {
+        fd: Arc<OwnedFd>,
+        polls: Rc<Cell<u32>>,
+    }
+
+    impl Readiness for Blocking {
+        fn poll_readable(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.polls.set(self.polls.get() + 1);
+            if poll_in(&self.fd, 0) {
+                return Poll::Ready(Ok(()));
+            }
+            let fd = self.fd.clone();
+            let waker = cx.waker().clone();
+            thread::spawn(move || {
+                poll_in(&fd, -1);
+                waker.wake();
+            });
+            Poll::Pending
+        }
+    }
+
+    fn blocking(polls: &Rc<Cell<u32>>) -> impl FnOnce(OwnedFd) -> io::Result<Blocking> {
+        let polls = polls.clone();
+        move |fd| {
+            Ok(Blocking {
+                fd: Arc::new(fd),
+                polls,
+            })
+        }
+    }
+
+    fn new_eventfd() -> EventFd {
+        EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap()
+    }
+
+    #[test]
+    fn run_once_dispatches_after_readiness() {
+        let efd = new_eventfd();
+        let writer = unsafe { EventFd::from_owned_fd(efd.as_fd().try_clone_to_owned().unwrap()) };
+        let handled = Rc::new(Cell::new(0));
+
+        let mut ep = Eventp::default();
+        let h = handled.clone();
+        interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move |efd: &mut EventFd| {
+                let _ = efd.read();
+                h.set(h.get() + 1);
+            })
+            .register_into(&mut ep)
+            .unwrap();
+
+        let polls = Rc::new(Cell::new(0));
+        let mut ep = ep.into_async(blocking(&polls)).unwrap();
+
+        let fire = thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(50));
+            writer.write(1).unwrap()
+        });
+        block_on(ep.run_once()).unwrap();
+        fire.join().unwrap();
+        assert_eq!(handled.get(), 1);
+        // Pending first, then ready after the wakeup.
+        assert_eq!(polls.get(), 2);
+    }
+
+    #[test]
+    fn run_once_drains_more_events_than_the_buffer_holds() {
+        let mut ep = Eventp::builder().capacity(1).build().unwrap();
+        let handled = Rc::new(Cell::new(0));
+        let mut efds = Vec::new();
+        for _ in 0..3 {
+            let efd = new_eventfd();
+            efd.write(1).unwrap();
+            let h = handled.clone();
+            efds.push(efd.as_fd().try_clone_to_owned().unwrap());
+            interest()
+                .read()
+                .with_fd(efd)
+                .with_handler(move |efd: &mut EventFd| {
+                    let _ = efd.read();
+                    h.set(h.get() + 1);
+                })
+                .register_into(&mut ep)
+                .unwrap();
+        }
+
+        let mut ep = ep
+            .into_async(|_fd| Ok(|_: &mut Context<'_>| Poll::Ready(Ok(()))))
+            .unwrap();
+        block_on(ep.run_once()).unwrap();
+        assert_eq!(handled.get(), 3);
+    }
+
+    #[cfg(feature = "remote-endpoint")]
+    #[test]
+    fn remote_endpoint_wakes_the_bridge() {
+        let mut ep = Eventp::default();
+        let endpoint = crate::remote_endpoint()
+            .unwrap()
+            .register_into(&mut ep)
+            .unwrap();
+
+        let polls = Rc::new(Cell::new(0));
+        let mut ep = ep.into_async(blocking(&polls)).unwrap();
+
+        let caller = thread::spawn(move || endpoint.call_blocking(|_| Ok(42)).unwrap());
+        block_on(ep.run_once()).unwrap();
+        assert_eq!(caller.join().unwrap(), 42);
+    }
+}
@@ -0,0 +1,582 @@
+use std::os::fd::RawFd;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use std::{fmt, io, mem};
+
+use rustc_hash::FxHashSet;
+
+#[cfg(feature = "log")]
+use crate::describe_fd;
+use crate::epoll::EpollCreateFlags;
+use crate::storm::ErrorStorms;
+use crate::{Event, Eventp, Interest, Pinned, DEFAULT_EVENT_BUF_CAPACITY};
+
+/// What the event loop does when a handler reports an error.
+///
+/// Handlers report errors through [`Handler::try_handle`](crate::subscriber::Handler::try_handle),
+/// e.g. by using a closure returning `io::Result<()>` with the
+/// [`tri_subscriber`](crate::tri_subscriber) builder. Set the policy with
+/// [`Builder::error_policy`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ErrorPolicy {
+    /// Logs the error and keeps the subscriber registered.
+    ///
+    /// The error is emitted through the [`log`](https://docs.rs/log) crate with the
+    /// `log` feature enabled, and dropped otherwise: the loop never writes to
+    /// stderr.
+    Log,
+
+    /// Deletes the failing subscriber from the loop, as if its handler had called
+    /// [`delete`](crate::EventpOps::delete) on its own fd.
+    Remove,
+
+    /// Returns the error from [`run_once_with_timeout`](Eventp::run_once_with_timeout).
+    ///
+    /// The rest of the batch is still dispatched, so edge-triggered subscribers later
+    /// in the batch do not lose their events. If several handlers fail within one
+    /// batch, the first error is returned and the others are dropped.
+    #[default]
+    Propagate,
+}
+
+/// The order in which the events of a batch are dispatched, see
+/// [`Builder::dispatch_order`].
+///
+/// Whatever the order, a batch has at most one event per fd, as epoll merges the
+/// readiness of an fd into one event, so the events of an fd are dispatched in the
+/// order of the batches. The events left over by
+/// [`run_once_budgeted`](Eventp::run_once_budgeted) keep their order, and are
+/// dispatched by the next call before waiting again, ahead of any fd that became
+/// ready meanwhile.
+///
+/// Across batches, nothing is assumed of the kernel, whose order Linux does not
+/// document. `epoll_wait` reports the fds in the order they became ready, except
+/// that a level-triggered fd is queued again as soon as it is reported, in the
+/// order of its batch, so it keeps that place in the next batch if still ready, or
+/// ready again, by then.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DispatchOrder {
+    /// In the order `epoll_wait` returned the events, rotated by
+    /// [`fair_dispatch`](Builder::fair_dispatch) if enabled.
+    #[default]
+    KernelOrder,
+
+    /// In the order of the [`dispatch_priority`](crate::Interest::dispatch_priority)
+    /// of the subscribers, lower first. The sort is stable, so subscribers of equal
+    /// priority keep the order of [`KernelOrder`](Self::KernelOrder).
+    PriorityStable,
+}
+
+/// Why a subscriber left the loop, see [`Builder::on_deregister`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DeregisterReason {
+    /// Deleted with [`delete`](crate::EventpOps::delete), or by the loop on its
+    /// behalf: under [`ErrorPolicy::Remove`], [`StormAction::Delete`], or once idle.
+    /// Reported when the subscriber is dropped, after its handler if it deleted
+    /// itself.
+    ExplicitDelete,
+
+    /// Deleted by [`Eventp::shutdown`], or dropped with the loop, or taken out of it
+    /// by [`Eventp::into_parts`].
+    Shutdown,
+
+    /// No longer watched, after an error storm, see [`StormAction::Quarantine`].
+    /// The subscriber stays in the loop, and is reported registered again by
+    /// [`Eventp::unquarantine`]; deleting it meanwhile reports nothing more.
+    Quarantined,
+
+    /// Evicted by [`Eventp::sweep`], as its fd was found closed while registered.
+    /// A [`delete`](crate::EventpOps::delete) finding it closed reports its own
+    /// reason instead, such as [`ExplicitDelete`](Self::ExplicitDelete).
+    Swept,
+}
+
+/// The callback of [`Builder::on_register`], called with the fd and the interest of
+/// each subscriber added to the loop.
+pub type RegisterCallback = dyn FnMut(RawFd, Interest) + Send;
+
+/// The callback of [`Builder::on_deregister`], called with the fd of each
+/// subscriber leaving the loop, and why.
+pub type DeregisterCallback = dyn FnMut(RawFd, DeregisterReason) + Send;
+
+/// See [`Builder::on_register`] and [`Builder::on_deregister`].
+#[derive(Clone, Default)]
+pub(crate) struct Lifecycle {
+    on_register: Option<Arc<Mutex<RegisterCallback>>>,
+    on_deregister: Option<Arc<Mutex<DeregisterCallback>>>,
+    /// The fds reported registered, and not yet deregistered, to report those left
+    /// when the loop is dropped. Only kept with `on_deregister`.
+    live: FxHashSet<RawFd>,
+}
+
+impl Lifecycle {
+    pub(crate) fn registered(&mut self, fd: RawFd, interest: Interest) {
+        if self.on_deregister.is_some() {
+            self.live.insert(fd);
+        }
+        if let Some(callback) = &self.on_register {
+            (callback.lock().unwrap_or_else(PoisonError::into_inner))(fd, interest);
+        }
+    }
+
+    pub(crate) fn deregistered(&mut self, fd: RawFd, reason: DeregisterReason) {
+        if let Some(callback) = &self.on_deregister {
+            self.live.remove(&fd);
+            (callback.lock().unwrap_or_else(PoisonError::into_inner))(fd, reason);
+        }
+    }
+}
+
+impl Drop for Lifecycle {
+    fn drop(&mut self) {
+        for fd in mem::take(&mut self.live) {
+            self.deregistered(fd, DeregisterReason::Shutdown);
+        }
+    }
+}
+
+impl fmt::Debug for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lifecycle")
+            .field("on_register", &self.on_register.is_some())
+            .field("on_deregister", &self.on_deregister.is_some())
+            .finish()
+    }
+}
+
+/// What the event loop does with an fd caught in an error storm, see
+/// [`Builder::error_storm_policy`].
+#[derive(Copy, Clone)]
+pub enum StormAction {
+    /// Stops watching the fd, but keeps its subscriber registered, until
+    /// [`Eventp::unquarantine`].
+    Quarantine,
+
+    /// Deletes the subscriber from the loop, with its [group](Eventp::add_group), as
+    /// [`ErrorPolicy::Remove`] does.
+    Delete,
+
+    /// Calls the function with the fd of the subscriber, in its handling state, so
+    /// that deleting the fd deletes the subscriber once its handler has returned.
+    /// A panic of the function ends the batch and is resumed, as for a handler.
+    Callback(fn(RawFd, Pinned<'_, Eventp>)),
+}
+
+impl fmt::Debug for StormAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Quarantine => f.write_str("Quarantine"),
+            Self::Delete => f.write_str("Delete"),
+            Self::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
+/// A builder for [`Eventp`], created by [`Eventp::builder`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::io;
+/// use eventp::epoll::EpollCreateFlags;
+/// use eventp::{ErrorPolicy, Eventp};
+///
+/// # fn main() -> io::Result<()> {
+/// let eventp = Eventp::builder()
+///     .capacity(64)
+///     .flags(EpollCreateFlags::EPOLL_CLOEXEC)
+///     .error_policy(ErrorPolicy::Remove)
+///     .build()?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Builder {
+    pub(crate) capacity: usize,
+    pub(crate) flags: EpollCreateFlags,
+    pub(crate) error_policy: ErrorPolicy,
+    pub(crate) fair_dispatch: bool,
+    pub(crate) dispatch_order: DispatchOrder,
+    pub(crate) max_subscribers: Option<usize>,
+    pub(crate) fixed_capacity: Option<usize>,
+    pub(crate) max_subscriber_bytes: Option<usize>,
+    pub(crate) slow_handler: Option<SlowHandler>,
+    pub(crate) reject_empty_interests: bool,
+    pub(crate) error_context: bool,
+    pub(crate) debug_assert_nonblocking: bool,
+    pub(crate) disarmed_warning: Option<u64>,
+    pub(crate) error_storms: Option<ErrorStorms>,
+    pub(crate) lifecycle: Lifecycle,
+}
+
+/// The callback of [`Builder::slow_handler_threshold`], called with the fd of the
+/// handler, the [name](crate::thin::ThinBoxSubscriber::name) of its subscriber, the
+/// time it took and the event it handled.
+pub type SlowHandlerCallback = dyn Fn(RawFd, Option<&'static str>, Duration, Event) + Send + Sync;
+
+/// See [`Builder::slow_handler_threshold`].
+#[derive(Clone)]
+pub(crate) struct SlowHandler {
+    threshold: Duration,
+    callback: Arc<SlowHandlerCallback>,
+}
+
+impl SlowHandler {
+    /// Reports the handler of `fd` if it took longer than the threshold.
+    pub(crate) fn check(
+        &self,
+        fd: RawFd,
+        name: Option<&'static str>,
+        elapsed: Duration,
+        event: Event,
+    ) {
+        if elapsed <= self.threshold {
+            return;
+        }
+        #[cfg(feature = "log")]
+        log::warn!("handler for {} took {elapsed:?}", describe_fd(fd, name));
+        (self.callback)(fd, name, elapsed, event);
+    }
+}
+
+impl fmt::Debug for SlowHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlowHandler")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Builder {
+    /// Same defaults as [`Eventp::default`].
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_EVENT_BUF_CAPACITY,
+            flags: EpollCreateFlags::EPOLL_CLOEXEC,
+            error_policy: ErrorPolicy::default(),
+            fair_dispatch: false,
+            dispatch_order: DispatchOrder::KernelOrder,
+            max_subscribers: None,
+            fixed_capacity: None,
+            max_subscriber_bytes: None,
+            slow_handler: None,
+            reject_empty_interests: true,
+            error_context: false,
+            debug_assert_nonblocking: false,
+            disarmed_warning: None,
+            error_storms: None,
+            lifecycle: Lifecycle::default(),
+        }
+    }
+}
+
+impl Builder {
+    /// Sets the number of [`EpollEvent`](crate::epoll::EpollEvent) slots reserved for
+    /// one `epoll_wait` call. See [`Eventp::new`].
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the flags passed to `epoll_create1`.
+    pub fn flags(mut self, flags: EpollCreateFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets what the loop does when a handler reports an error.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Rotates the position each batch of events starts being dispatched from.
+    ///
+    /// By default, a batch is dispatched in the order `epoll_wait` returned it, so
+    /// with a busy loop the same fds may keep coming last, after handlers that
+    /// generate more work. With fair dispatch, each batch starts one position further
+    /// than the last one, wrapping around. Only the order within a batch changes.
+    /// Disabled by default.
+    pub fn fair_dispatch(mut self, fair: bool) -> Self {
+        self.fair_dispatch = fair;
+        self
+    }
+
+    /// Sets the order in which each batch of events is dispatched, e.g. with
+    /// [`DispatchOrder::PriorityStable`], so that a shutdown eventfd is handled, and
+    /// deletes the data fds, before their events of the same batch.
+    ///
+    /// Only the order within a batch changes. Defaults to
+    /// [`DispatchOrder::KernelOrder`], in which case priorities are ignored.
+    pub fn dispatch_order(mut self, order: DispatchOrder) -> Self {
+        self.dispatch_order = order;
+        self
+    }
+
+    /// Same as [`dispatch_order`](Self::dispatch_order) with
+    /// [`DispatchOrder::PriorityStable`] if `enabled`, and
+    /// [`DispatchOrder::KernelOrder`] otherwise.
+    pub fn priority_dispatch(self, enabled: bool) -> Self {
+        self.dispatch_order(if enabled {
+            DispatchOrder::PriorityStable
+        } else {
+            DispatchOrder::KernelOrder
+        })
+    }
+
+    /// Limits the number of subscribers registered at once, each fd of a group
+    /// counting as one, e.g. to stop accepting connections before running out of
+    /// fds. Unlimited by default.
+    ///
+    /// Beyond it, [`add`](crate::EventpOpsAdd::add) fails with
+    /// [`Error::AtCapacity`](crate::Error::AtCapacity). A subscriber deleted during a
+    /// batch frees its slot right away, even if it is the one whose handler is
+    /// running, so a handler can close a connection and register another.
+    pub fn max_subscribers(mut self, max: usize) -> Self {
+        self.max_subscribers = Some(max);
+        self
+    }
+
+    /// Keeps the subscribers in `n` slots allocated by [`build`](Self::build),
+    /// rather than in a map that grows and rehashes as fds are added, e.g. for an
+    /// embedded loop with a known set of fds.
+    ///
+    /// A slot is found by probing from the fd modulo `n`, so lookups stay short
+    /// while few fds share a remainder. An [`add`](crate::EventpOpsAdd::add) beyond
+    /// `n` subscribers fails with [`Error::RegistryFull`](crate::Error::RegistryFull).
+    /// A subscriber deleted by its own handler keeps its slot until the handler
+    /// returns. `build` fails with [`io::ErrorKind::InvalidInput`] if `n` is zero.
+    ///
+    /// The slots are one heap allocation, sized at run time, not an array inline in
+    /// the `Eventp` sized at compile time: that would make `Eventp` generic over its
+    /// storage, and its subscribers, of type `ThinBoxSubscriber<Eventp>`, a
+    /// different type for each capacity. What they save is the hashing, and the
+    /// reallocations of a growing map.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::tri_subscriber::WithHandler;
+    /// use eventp::{interest, Error, Eventp, Subscriber};
+    /// use nix::sys::eventfd::{EfdFlags, EventFd};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::builder().fixed_capacity(1).build()?;
+    /// for expected in [None, Some(&Error::RegistryFull { capacity: 1 })] {
+    ///     let efd = EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+    ///     let result = interest()
+    ///         .read()
+    ///         .with_fd(efd)
+    ///         .with_handler(|_efd: &mut EventFd| {})
+    ///         .register_into(&mut eventp);
+    ///     assert_eq!(result.as_ref().err().and_then(Error::from_io), expected);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn fixed_capacity(mut self, n: usize) -> Self {
+        self.fixed_capacity = Some(n);
+        self
+    }
+
+    /// Limits the memory of the registered subscribers, as the sum of their
+    /// [`allocated_bytes`](crate::thin::ThinBoxSubscriber::allocated_bytes), e.g. to
+    /// bound what a tenant sharing the process can take. Unlimited by default.
+    ///
+    /// Beyond it, [`add`](crate::EventpOpsAdd::add) fails with
+    /// [`Error::OverMemoryBudget`](crate::Error::OverMemoryBudget). Like with
+    /// [`max_subscribers`](Self::max_subscribers), a subscriber deleted during a batch
+    /// frees its bytes right away, though its memory is only released at the end of
+    /// the batch. Only the boxes are counted, not what the subscribers allocate.
+    pub fn max_subscriber_bytes(mut self, max: usize) -> Self {
+        self.max_subscriber_bytes = Some(max);
+        self
+    }
+
+    /// Refuses to [`add`](crate::EventpOpsAdd::add) a subscriber whose interest asks
+    /// for no event, with [`Error::EmptyInterest`](crate::Error::EmptyInterest), unless
+    /// it is marked with [`hangup_only`](crate::Interest::hangup_only). Enabled by
+    /// default.
+    pub fn reject_empty_interests(mut self, reject: bool) -> Self {
+        self.reject_empty_interests = reject;
+        self
+    }
+
+    /// Reports the failures of `epoll_ctl` and `epoll_wait` as an
+    /// [`Error::Syscall`](crate::Error::Syscall), with the operation, the fd and the
+    /// name of its subscriber, e.g. `EPOLL_CTL_MOD failed for fd 17 ("vhost queue
+    /// 1"): ENOENT`. So are the fds [`modify`](crate::EventpOps::modify) and
+    /// [`delete`](crate::EventpOps::delete) find not registered, as `ENOENT`.
+    /// Disabled by default.
+    ///
+    /// The errors keep their [`io::ErrorKind`], but no longer return the errno from
+    /// [`raw_os_error`](io::Error::raw_os_error), as an `io::Error` with a payload
+    /// never does; match it with [`Error::raw_os_error`](crate::Error::raw_os_error)
+    /// instead. The errors of handlers are returned as they are.
+    pub fn error_context(mut self, enable: bool) -> Self {
+        self.error_context = enable;
+        self
+    }
+
+    /// Refuses, in debug builds, to [`add`](crate::EventpOpsAdd::add) an fd
+    /// [edge-triggered](crate::Interest::edge_triggered) without `O_NONBLOCK`, with
+    /// [`Error::BlockingFd`](crate::Error::BlockingFd): its handler has to read or
+    /// write until `EAGAIN`, and would block the loop instead. Regular files, which
+    /// never block, are not refused. Disabled by default, and never checked in
+    /// release builds.
+    ///
+    /// Set `O_NONBLOCK` with
+    /// [`Interest::with_fd_nonblocking`](crate::Interest::with_fd_nonblocking).
+    pub fn debug_assert_nonblocking(mut self, enable: bool) -> Self {
+        self.debug_assert_nonblocking = enable;
+        self
+    }
+
+    /// Calls `callback` after each handler that took longer than `threshold`, e.g.
+    /// to find the one adding latency to every other fd of the loop. With the `log`
+    /// feature, a warning is logged as well.
+    ///
+    /// Handlers are only timed when a threshold is set, or the
+    /// [event log](Eventp::enable_event_log) enabled. The time includes that of the
+    /// subscribers the handler adds or deletes. Disabled by default.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::time::Duration;
+    ///
+    /// use eventp::Eventp;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let eventp = Eventp::builder()
+    ///     .slow_handler_threshold(Duration::from_millis(10), |fd, name, elapsed, _event| {
+    ///         eprintln!("handler of fd {fd} ({name:?}) took {elapsed:?}");
+    ///     })
+    ///     .build()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn slow_handler_threshold<F>(mut self, threshold: Duration, callback: F) -> Self
+    where
+        F: Fn(RawFd, Option<&'static str>, Duration, Event) + Send + Sync + 'static,
+    {
+        self.slow_handler = Some(SlowHandler {
+            threshold,
+            callback: Arc::new(callback),
+        });
+        self
+    }
+
+    /// Warns about every [`oneshot`](crate::Interest::oneshot) subscriber left
+    /// disarmed for `batches` batches after its event, neither rearmed by
+    /// [`modify`](crate::EventpOps::modify) nor deleted. Such a subscriber gets no
+    /// more events, so it is always leaked, typically by a handler returning early
+    /// before its rearm.
+    ///
+    /// Each disarm is counted once by
+    /// [`Stats::disarmed_leaks`](crate::Stats::disarmed_leaks), and with the `log`
+    /// feature, logged as a warning; the loop never writes to stderr. A fd rearmed from
+    /// outside the loop, e.g. by a worker thread, needs enough batches to do so.
+    /// Disabled by default.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::Eventp;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let eventp = Eventp::builder().warn_disarmed_after(16).build()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn warn_disarmed_after(mut self, batches: u64) -> Self {
+        self.disarmed_warning = Some(batches);
+        self
+    }
+
+    /// Applies `action` to every fd reporting `EPOLLERR` in `threshold` events in a
+    /// row, all dispatched within `window` of the first, e.g. a faulty device the
+    /// handler cannot recover, which `epoll` reports on every wait, whatever the
+    /// interest. Any event without `EPOLLERR` starts the count over.
+    ///
+    /// The action is applied after the handler of the last event, unless it
+    /// deleted its subscriber. With the `log` feature, a warning is logged as well.
+    /// Every storm is counted by [`Stats::error_storms`](crate::Stats::error_storms).
+    /// Disabled by default.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::time::Duration;
+    ///
+    /// use eventp::{Eventp, StormAction};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let eventp = Eventp::builder()
+    ///     .error_storm_policy(100, Duration::from_secs(1), StormAction::Quarantine)
+    ///     .build()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn error_storm_policy(
+        mut self,
+        threshold: u32,
+        window: Duration,
+        action: StormAction,
+    ) -> Self {
+        self.error_storms = Some(ErrorStorms::new(threshold, window, action));
+        self
+    }
+
+    /// Calls `callback` with the fd and the interest of every subscriber added to the
+    /// loop, whichever the way, and of every one [unquarantined](Eventp::unquarantine),
+    /// e.g. to keep an index of the fds, or a gauge of the connections, in sync.
+    ///
+    /// The callback gets no access to the loop, and must not panic. It is shared by
+    /// the clones of the builder, and the loops they build. Replaces any callback set
+    /// before.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::sync::atomic::{AtomicIsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use eventp::Eventp;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let connections = Arc::new(AtomicIsize::new(0));
+    /// let (up, down) = (connections.clone(), connections.clone());
+    /// let eventp = Eventp::builder()
+    ///     .on_register(move |_fd, _interest| {
+    ///         up.fetch_add(1, Ordering::Relaxed);
+    ///     })
+    ///     .on_deregister(move |_fd, _reason| {
+    ///         down.fetch_sub(1, Ordering::Relaxed);
+    ///     })
+    ///     .build()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn on_register<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(RawFd, Interest) + Send + 'static,
+    {
+        self.lifecycle.on_register = Some(Arc::new(Mutex::new(callback)));
+        self
+    }
+
+    /// Calls `callback` with the fd of every subscriber leaving the loop, and why,
+    /// exactly once per [`on_register`](Self::on_register), including for the
+    /// subscribers still registered when the loop is dropped.
+    ///
+    /// A subscriber deleted by its own handler is reported once the handler has
+    /// returned, when it is dropped; others are reported when deleted. Same
+    /// constraints as for `on_register`. Replaces any callback set before.
+    pub fn on_deregister<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(RawFd, DeregisterReason) + Send + 'static,
+    {
+        self.lifecycle.on_deregister = Some(Arc::new(Mutex::new(callback)));
+        self
+    }
+
+    /// Creates the `Eventp`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Eventp::new`].
+    pub fn build(self) -> io::Result<Eventp> {
+        Eventp::from_builder(self)
+    }
+}
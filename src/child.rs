@@ -0,0 +1,311 @@
+use std::cell::{Cell, RefCell, RefMut};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::rc::Rc;
+
+use crate::epoll::EpollTimeout;
+use crate::subscriber::{Handler, HasInterest};
+use crate::thin::ThinBoxSubscriber;
+use crate::{
+    interest, Event, Eventp, EventpOps, EventpOpsAdd, EventpOpsCtl, Interest, Pinned, Waker,
+};
+
+/// An [`Eventp`] registered as a subscriber of another loop, the parent.
+///
+/// The epoll fd of the child is readable while any of its fds is ready, so the
+/// parent wakes up for them, and the handler dispatches the child's events without
+/// waiting. This lets a module keep its fds in a loop of its own, e.g. every fd of
+/// one virtual device, and plug it into the loop of the application. Children can
+/// be nested to any depth.
+///
+/// The child is reached through the [`ChildGuard`] returned by
+/// [`register_into`](Self::register_into).
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::io;
+/// use eventp::{tri_subscriber::WithHandler, ChildEventp, Eventp, Subscriber};
+/// use nix::sys::eventfd::EventFd;
+///
+/// fn thread_main(device_fd: EventFd) -> io::Result<()> {
+///     let mut parent = Eventp::default();
+///     let device = ChildEventp::new(Eventp::default()).register_into(&mut parent)?;
+///
+///     eventp::interest()
+///         .read()
+///         .with_fd(device_fd)
+///         .with_handler(|fd: &mut EventFd| {
+///             let _ = fd.read();
+///         })
+///         .register_into(&mut *device.child())?;
+///
+///     parent.run_forever()
+/// }
+/// ```
+pub struct ChildEventp {
+    child: Rc<RefCell<Eventp>>,
+    /// The epoll fd of `child`, which it keeps for as long as it lives.
+    poll_fd: RawFd,
+    interest: Cell<Interest>,
+}
+
+/// The module-side end of a [`ChildEventp`]: gives access to the child loop, and
+/// deletes it from its parent when dropped.
+///
+/// Dropping the guard cannot reach the parent, so it wakes the child instead, and
+/// the registration is deleted, and the child dropped, by the next dispatch of the
+/// parent. [`deregister`](Self::deregister) deletes it right away.
+pub struct ChildGuard {
+    child: Rc<RefCell<Eventp>>,
+    poll_fd: RawFd,
+    waker: Waker,
+}
+
+impl ChildEventp {
+    /// Wraps `child`, to be registered with a read interest on its epoll fd.
+    pub fn new(child: Eventp) -> Self {
+        let poll_fd = child.poll_fd().as_raw_fd();
+        Self {
+            child: Rc::new(RefCell::new(child)),
+            poll_fd,
+            interest: Cell::new(interest().read()),
+        }
+    }
+
+    /// Registers the child into `parent`, and returns the guard to reach it.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from registering the wake-up `eventfd` into the child, or
+    /// the child into `parent`.
+    pub fn register_into<Ep, R>(self, parent: &mut R) -> io::Result<ChildGuard>
+    where
+        Ep: EventpOps,
+        R: EventpOpsAdd<Ep>,
+    {
+        let waker = Waker::new(&mut *self.child.borrow_mut())?;
+        let guard = ChildGuard {
+            child: Rc::clone(&self.child),
+            poll_fd: self.poll_fd,
+            waker,
+        };
+        parent.add(ThinBoxSubscriber::new(self))?;
+        Ok(guard)
+    }
+}
+
+impl ChildGuard {
+    /// Borrows the child loop, e.g. to register subscribers into it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the child is already borrowed, i.e. if called from one of its own
+    /// handlers; those are given the child as a [`Pinned`] instead.
+    pub fn child(&self) -> RefMut<'_, Eventp> {
+        self.child.borrow_mut()
+    }
+
+    /// Returns the raw fd the child is registered with in its parent.
+    pub fn raw_fd(&self) -> RawFd {
+        self.poll_fd
+    }
+
+    /// Deletes the child from `parent` right away, instead of on its next dispatch.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`EventpOps::delete`]; the guard is consumed either way.
+    pub fn deregister<Ep, R>(self, parent: &mut R) -> io::Result<()>
+    where
+        Ep: EventpOps,
+        R: EventpOpsCtl<Ep>,
+    {
+        parent.ctl_delete(self.poll_fd)
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        // A deregistered child is gone from its parent, and the wakeup is ignored.
+        let _ = self.waker.wake();
+    }
+}
+
+impl AsFd for ChildEventp {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: `self.child` keeps the `Eventp`, and so its epoll fd, alive.
+        unsafe { BorrowedFd::borrow_raw(self.poll_fd) }
+    }
+}
+
+impl HasInterest for ChildEventp {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep: EventpOps> Handler<Ep> for ChildEventp {
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    /// Dispatches the ready events of the child, or deletes it once its guard is
+    /// dropped. Errors of the child, e.g. under
+    /// [`ErrorPolicy::Propagate`](crate::ErrorPolicy::Propagate), are reported to
+    /// the parent.
+    fn try_handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        if Rc::strong_count(&self.child) == 1 {
+            return eventp.delete(self.poll_fd);
+        }
+        // Each loop has its own re-entrancy guard, so this is not a recursive call.
+        self.child
+            .borrow_mut()
+            .run_once_with_timeout(EpollTimeout::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsFd;
+
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use super::*;
+    use crate::tri_subscriber::WithHandler;
+    use crate::{ErrorPolicy, Subscriber};
+
+    fn new_eventfd() -> EventFd {
+        EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap()
+    }
+
+    fn writer_for(efd: &EventFd) -> EventFd {
+        let dup = efd.as_fd().try_clone_to_owned().unwrap();
+        unsafe { EventFd::from_owned_fd(dup) }
+    }
+
+    /// Counts its drops, to tell when a nested subscriber is gone.
+    struct DropCounter(Rc<Cell<u32>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    /// Registers a drained `eventfd` into `eventp`, counting the calls and drops of
+    /// its handler, and returns a writer to fire it.
+    fn leaf(eventp: &mut Eventp, calls: &Rc<Cell<u32>>, drops: &Rc<Cell<u32>>) -> EventFd {
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let (c, guard) = (calls.clone(), DropCounter(drops.clone()));
+        interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move |efd: &mut EventFd| {
+                let _ = &guard;
+                let _ = efd.read();
+                c.set(c.get() + 1);
+            })
+            .register_into(eventp)
+            .unwrap();
+        writer
+    }
+
+    fn timeout() -> EpollTimeout {
+        EpollTimeout::from(500u16)
+    }
+
+    #[test]
+    fn two_levels_of_nesting_reach_the_leaf() {
+        let mut root = Eventp::default();
+        let middle = ChildEventp::new(Eventp::default())
+            .register_into(&mut root)
+            .unwrap();
+        let inner = ChildEventp::new(Eventp::default())
+            .register_into(&mut *middle.child())
+            .unwrap();
+        let (calls, drops) = Default::default();
+        let writer = leaf(&mut inner.child(), &calls, &drops);
+
+        for expected in 1..=2 {
+            writer.write(1).unwrap();
+            root.run_once_with_timeout(timeout()).unwrap();
+            assert_eq!(calls.get(), expected);
+        }
+
+        // Drained by the leaf: nothing is ready at any level.
+        root.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(calls.get(), 2);
+        assert!(root.contains(middle.raw_fd()));
+        assert!(middle.child().contains(inner.raw_fd()));
+    }
+
+    #[test]
+    fn dropping_the_guard_deletes_the_child_from_its_parent() {
+        let mut root = Eventp::default();
+        let middle = ChildEventp::new(Eventp::default())
+            .register_into(&mut root)
+            .unwrap();
+        let inner = ChildEventp::new(Eventp::default())
+            .register_into(&mut *middle.child())
+            .unwrap();
+        let (calls, drops) = Default::default();
+        let _writer = leaf(&mut inner.child(), &calls, &drops);
+        let (middle_fd, inner_fd) = (middle.raw_fd(), inner.raw_fd());
+
+        drop(inner);
+        root.run_once_with_timeout(timeout()).unwrap();
+        assert!(!middle.child().contains(inner_fd));
+        assert_eq!((calls.get(), drops.get()), (0, 1));
+
+        drop(middle);
+        root.run_once_with_timeout(timeout()).unwrap();
+        assert!(!root.contains(middle_fd));
+    }
+
+    #[test]
+    fn deregister_deletes_right_away() {
+        let mut root = Eventp::default();
+        let child = ChildEventp::new(Eventp::default())
+            .register_into(&mut root)
+            .unwrap();
+        let (calls, drops) = Default::default();
+        let _writer = leaf(&mut child.child(), &calls, &drops);
+        let raw_fd = child.raw_fd();
+
+        child.deregister(&mut root).unwrap();
+        assert!(!root.contains(raw_fd));
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn child_errors_reach_the_parent() {
+        let propagating = || {
+            Eventp::builder()
+                .error_policy(ErrorPolicy::Propagate)
+                .build()
+                .unwrap()
+        };
+        let mut root = propagating();
+        let child = ChildEventp::new(propagating())
+            .register_into(&mut root)
+            .unwrap();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|efd: &mut EventFd| -> io::Result<()> {
+                let _ = efd.read();
+                Err(io::Error::new(io::ErrorKind::Other, "leaf failed"))
+            })
+            .register_into(&mut *child.child())
+            .unwrap();
+
+        writer.write(1).unwrap();
+        let err = root.run_once_with_timeout(timeout()).unwrap_err();
+        assert_eq!(err.to_string(), "leaf failed");
+    }
+}
@@ -0,0 +1,460 @@
+//! The semantics every reactor of this crate must share, run against each of
+//! them: [`Eventp`], and `UringEventp` with the `uring` feature.
+
+use std::cell::{Cell, RefCell};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::rc::Rc;
+use std::time::Duration;
+use std::{io, thread};
+
+use nix::sys::eventfd::{EfdFlags, EventFd};
+
+use crate::epoll::EpollTimeout;
+use crate::tri_subscriber::{SinceLast, WithHandler};
+use crate::{interest, Event, Eventp, EventpOps, Interest, Pinned, Subscriber};
+
+/// What the suite needs beyond [`EventpOps`].
+pub(crate) trait Runner: EventpOps {
+    fn run_once_with_timeout(&mut self, timeout: EpollTimeout) -> io::Result<()>;
+
+    fn contains(&self, fd: RawFd) -> bool;
+
+    /// Dispatches one batch, waiting long enough for an event that is ready.
+    fn dispatch(&mut self) {
+        self.run_once_with_timeout(EpollTimeout::from(500u16))
+            .unwrap();
+    }
+
+    /// Dispatches whatever comes within a short wait.
+    fn settle(&mut self) {
+        self.run_once_with_timeout(EpollTimeout::from(50u16))
+            .unwrap();
+    }
+}
+
+impl Runner for Eventp {
+    fn run_once_with_timeout(&mut self, timeout: EpollTimeout) -> io::Result<()> {
+        Eventp::run_once_with_timeout(self, timeout)
+    }
+
+    fn contains(&self, fd: RawFd) -> bool {
+        Eventp::contains(self, fd)
+    }
+}
+
+#[cfg(feature = "uring")]
+impl Runner for crate::uring::UringEventp {
+    fn run_once_with_timeout(&mut self, timeout: EpollTimeout) -> io::Result<()> {
+        crate::uring::UringEventp::run_once_with_timeout(self, timeout)
+    }
+
+    fn contains(&self, fd: RawFd) -> bool {
+        crate::uring::UringEventp::contains(self, fd)
+    }
+}
+
+fn new_eventfd() -> EventFd {
+    EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap()
+}
+
+/// A second `EventFd` on the kernel object of `efd`, to fire it once it is moved.
+fn writer_for(efd: &EventFd) -> EventFd {
+    let dup = efd.as_fd().try_clone_to_owned().unwrap();
+    // SAFETY: `dup` is an eventfd, as a duplicate of one.
+    unsafe { EventFd::from_owned_fd(dup) }
+}
+
+fn counter() -> Rc<Cell<u32>> {
+    Rc::new(Cell::new(0))
+}
+
+/// Counts its drops, to check when, and how often, a subscriber is dropped.
+struct DropCounter(Rc<Cell<u32>>);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+/// Registers `fd` with `interest`, counting its events without draining it.
+fn register_counting<R, F>(ep: &mut R, fd: F, interest: Interest) -> Rc<Cell<u32>>
+where
+    R: Runner,
+    F: AsFd + 'static,
+{
+    let calls = counter();
+    let c = calls.clone();
+    interest
+        .with_fd(fd)
+        .with_handler(move || c.set(c.get() + 1))
+        .register_into(ep)
+        .unwrap();
+    calls
+}
+
+pub(crate) fn level_triggered_is_reported_until_drained<R: Runner>(ep: &mut R) {
+    let efd = new_eventfd();
+    let writer = writer_for(&efd);
+    let calls = register_counting(ep, efd, interest().read());
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+    ep.dispatch();
+    assert_eq!(calls.get(), 2);
+
+    writer.read().unwrap();
+    ep.settle();
+    assert_eq!(calls.get(), 2);
+}
+
+pub(crate) fn edge_triggered_is_reported_once_per_change<R: Runner>(ep: &mut R) {
+    let efd = new_eventfd();
+    let writer = writer_for(&efd);
+    let calls = register_counting(ep, efd, interest().read().edge_triggered());
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+    ep.settle();
+    assert_eq!(calls.get(), 1);
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+    assert_eq!(calls.get(), 2);
+}
+
+pub(crate) fn oneshot_is_rearmed_by_modify<R: Runner>(ep: &mut R) {
+    let efd = new_eventfd();
+    let (raw, writer) = (efd.as_fd().as_raw_fd(), writer_for(&efd));
+    let read_oneshot = interest().read().oneshot();
+    let calls = register_counting(ep, efd, read_oneshot);
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+    ep.settle();
+    assert_eq!(calls.get(), 1);
+
+    ep.modify(raw, read_oneshot).unwrap();
+    ep.dispatch();
+    assert_eq!(calls.get(), 2);
+}
+
+pub(crate) fn modify_changes_the_reported_events<R: Runner>(ep: &mut R) {
+    let efd = new_eventfd();
+    let raw = efd.as_fd().as_raw_fd();
+    let events = Rc::new(Cell::new(None));
+    let e = events.clone();
+    interest()
+        .read()
+        .with_fd(efd)
+        .with_handler(move |ev: Event| e.set(Some(ev)))
+        .register_into(ep)
+        .unwrap();
+
+    // An empty eventfd is only writable.
+    ep.settle();
+    assert_eq!(events.get(), None);
+
+    ep.modify(raw, interest().write()).unwrap();
+    ep.dispatch();
+    let ev = events.get().unwrap();
+    assert!(ev.is_writable() && !ev.is_readable());
+}
+
+pub(crate) fn current_interest_is_seen_by_the_handler<R: Runner>(ep: &mut R) {
+    let efd = new_eventfd();
+    let writer = writer_for(&efd);
+    let seen = Rc::new(Cell::new(None));
+    let s = seen.clone();
+    interest()
+        .read()
+        .with_fd(efd)
+        .with_handler(move |efd: &mut EventFd, ep: Pinned<'_, R>| {
+            let _ = efd.read();
+            s.set(ep.current_interest());
+        })
+        .register_into(ep)
+        .unwrap();
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+    assert_eq!(seen.get(), Some(interest().read()));
+}
+
+pub(crate) fn deltas_are_kept_across_events<R: Runner>(ep: &mut R) {
+    let efd = new_eventfd();
+    let writer = writer_for(&efd);
+    let seen = Rc::new(Cell::new(None));
+    let s = seen.clone();
+    interest()
+        .read()
+        .edge_triggered()
+        .track_deltas()
+        .with_fd(efd)
+        .with_handler(move |ep: Pinned<'_, R>| s.set(ep.current_delta()))
+        .register_into(ep)
+        .unwrap();
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+    assert!(seen.take().unwrap().newly_readable());
+
+    // Not drained: still readable, so not newly.
+    writer.write(1).unwrap();
+    ep.dispatch();
+    let delta = seen.take().unwrap();
+    assert!(delta.previous().is_readable() && !delta.newly_readable());
+}
+
+pub(crate) fn the_time_since_the_last_event_is_none_first<R: Runner>(ep: &mut R) {
+    const PACE: Duration = Duration::from_millis(15);
+    let mut writers = Vec::new();
+    let mut logs = Vec::new();
+    for interest in [interest().read().track_last_event(), interest().read()] {
+        let efd = new_eventfd();
+        writers.push(writer_for(&efd));
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let l = log.clone();
+        interest
+            .with_fd(efd)
+            .with_handler(move |efd: &mut EventFd, SinceLast(since): SinceLast| {
+                efd.read().unwrap();
+                l.borrow_mut().push(since);
+            })
+            .register_into(ep)
+            .unwrap();
+        logs.push(log);
+    }
+
+    // Each write comes a full `PACE` after the dispatch of the previous one.
+    for _ in 0..3 {
+        thread::sleep(PACE);
+        for writer in &writers {
+            writer.write(1).unwrap();
+        }
+        ep.dispatch();
+    }
+    let tracked = logs[0].borrow();
+    assert_eq!(tracked.len(), 3);
+    assert_eq!(tracked[0], None);
+    for since in &tracked[1..] {
+        assert!(since.is_some_and(|since| PACE <= since && since < Duration::from_secs(5)));
+    }
+    assert_eq!(*logs[1].borrow(), [None; 3]);
+}
+
+pub(crate) fn self_delete_drops_after_the_handler_returns<R: Runner>(ep: &mut R) {
+    let efd = new_eventfd();
+    let (raw, writer) = (efd.as_fd().as_raw_fd(), writer_for(&efd));
+    let drops = counter();
+    let guard = DropCounter(drops.clone());
+    let d = drops.clone();
+    interest()
+        .read()
+        .with_fd(efd)
+        .with_handler(move |efd: &mut EventFd, mut ep: Pinned<'_, R>| {
+            let _ = &guard;
+            ep.delete(efd.as_fd().as_raw_fd()).unwrap();
+            // Still alive until the handler returns.
+            assert_eq!(d.get(), 0);
+            let again = ep.delete(efd.as_fd().as_raw_fd()).unwrap_err();
+            assert_eq!(again.kind(), io::ErrorKind::NotFound);
+        })
+        .register_into(ep)
+        .unwrap();
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+    assert_eq!(drops.get(), 1);
+    assert!(!ep.contains(raw));
+
+    writer.write(1).unwrap();
+    ep.settle();
+    assert_eq!(drops.get(), 1);
+}
+
+pub(crate) fn deleting_another_fd_skips_its_event_in_the_batch<R: Runner>(ep: &mut R) {
+    let (a, b) = (new_eventfd(), new_eventfd());
+    let (raw_a, raw_b) = (a.as_fd().as_raw_fd(), b.as_fd().as_raw_fd());
+    let writers = [writer_for(&a), writer_for(&b)];
+
+    let calls = counter();
+    let drops = counter();
+    for (efd, other) in [(a, raw_b), (b, raw_a)] {
+        let (c, guard) = (calls.clone(), DropCounter(drops.clone()));
+        interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move |mut ep: Pinned<'_, R>| {
+                let _ = &guard;
+                c.set(c.get() + 1);
+                // Deleted already when called again, below.
+                let _ = ep.delete(other);
+            })
+            .register_into(ep)
+            .unwrap();
+    }
+
+    for writer in &writers {
+        writer.write(1).unwrap();
+    }
+    ep.dispatch();
+    // Whichever ran first deleted the other, which was dropped right away.
+    assert_eq!((calls.get(), drops.get()), (1, 1));
+    assert!(ep.contains(raw_a) ^ ep.contains(raw_b));
+
+    ep.settle();
+    assert_eq!(calls.get(), 2);
+}
+
+pub(crate) fn readding_an_fd_deleted_in_the_same_batch<R: Runner>(ep: &mut R) {
+    let (trigger, target) = (new_eventfd(), new_eventfd());
+    let (writer, raw_target) = (writer_for(&trigger), target.as_fd().as_raw_fd());
+    // SAFETY: `target` stays open until its last registration is deleted, below.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(raw_target) };
+    let old_calls = register_counting(ep, borrowed, interest().read());
+
+    let new_calls = counter();
+    let c = new_calls.clone();
+    interest()
+        .read()
+        .with_fd(trigger)
+        .with_handler(move |efd: &mut EventFd, mut ep: Pinned<'_, R>| {
+            let _ = efd.read();
+            ep.delete(raw_target).unwrap();
+            let c = c.clone();
+            interest()
+                .read()
+                .with_fd(borrowed)
+                .with_handler(move || c.set(c.get() + 1))
+                .register_into(&mut ep)
+                .unwrap();
+        })
+        .register_into(ep)
+        .unwrap();
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+    target.write(1).unwrap();
+    ep.dispatch();
+    assert_eq!((old_calls.get(), new_calls.get()), (0, 1));
+
+    ep.delete(raw_target).unwrap();
+}
+
+pub(crate) fn recursive_run_panics<R: Runner>(ep: &mut R) {
+    let efd = new_eventfd();
+    let writer = writer_for(&efd);
+    interest()
+        .read()
+        .with_fd(efd)
+        .with_handler(|mut ep: Pinned<'_, R>| {
+            // SAFETY: Not moved; only to reach the guard of the loop.
+            let ep = unsafe { ep.0.as_mut().get_unchecked_mut() };
+            let _ = ep.run_once_with_timeout(EpollTimeout::ZERO);
+        })
+        .register_into(ep)
+        .unwrap();
+
+    writer.write(1).unwrap();
+    ep.dispatch();
+}
+
+pub(crate) fn dropping_the_loop_drops_every_subscriber_once<R: Runner>(mut ep: R) {
+    let drops = counter();
+    let mut raws = vec![];
+    for _ in 0..3 {
+        let efd = new_eventfd();
+        raws.push(efd.as_fd().as_raw_fd());
+        let guard = DropCounter(drops.clone());
+        interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move || {
+                let _ = &guard;
+            })
+            .register_into(&mut ep)
+            .unwrap();
+    }
+    ep.settle();
+    // One deleted before the drop, still referred to by a cancellation in flight.
+    ep.delete(raws[0]).unwrap();
+    assert_eq!(drops.get(), 1);
+
+    drop(ep);
+    assert_eq!(drops.get(), 3);
+}
+
+macro_rules! conformance_suite {
+    ($backend:ident, $new:expr) => {
+        mod $backend {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #[test]
+            fn level_triggered_is_reported_until_drained() {
+                super::level_triggered_is_reported_until_drained(&mut $new);
+            }
+
+            #[test]
+            fn edge_triggered_is_reported_once_per_change() {
+                super::edge_triggered_is_reported_once_per_change(&mut $new);
+            }
+
+            #[test]
+            fn oneshot_is_rearmed_by_modify() {
+                super::oneshot_is_rearmed_by_modify(&mut $new);
+            }
+
+            #[test]
+            fn modify_changes_the_reported_events() {
+                super::modify_changes_the_reported_events(&mut $new);
+            }
+
+            #[test]
+            fn current_interest_is_seen_by_the_handler() {
+                super::current_interest_is_seen_by_the_handler(&mut $new);
+            }
+
+            #[test]
+            fn deltas_are_kept_across_events() {
+                super::deltas_are_kept_across_events(&mut $new);
+            }
+
+            #[test]
+            fn the_time_since_the_last_event_is_none_first() {
+                super::the_time_since_the_last_event_is_none_first(&mut $new);
+            }
+
+            #[test]
+            fn self_delete_drops_after_the_handler_returns() {
+                super::self_delete_drops_after_the_handler_returns(&mut $new);
+            }
+
+            #[test]
+            fn deleting_another_fd_skips_its_event_in_the_batch() {
+                super::deleting_another_fd_skips_its_event_in_the_batch(&mut $new);
+            }
+
+            #[test]
+            fn readding_an_fd_deleted_in_the_same_batch() {
+                super::readding_an_fd_deleted_in_the_same_batch(&mut $new);
+            }
+
+            #[test]
+            #[should_panic(expected = "Recursive call")]
+            fn recursive_run_panics() {
+                super::recursive_run_panics(&mut $new);
+            }
+
+            #[test]
+            fn dropping_the_loop_drops_every_subscriber_once() {
+                super::dropping_the_loop_drops_every_subscriber_once($new);
+            }
+        }
+    };
+}
+
+conformance_suite!(eventp, Eventp::default());
+#[cfg(feature = "uring")]
+conformance_suite!(uring, crate::uring::UringEventp::default());
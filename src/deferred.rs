@@ -0,0 +1,36 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+
+use crate::Pinned;
+
+/// A closure queued by [`EventpOps::defer`](crate::EventpOps::defer), run once the
+/// current batch is over.
+pub type Deferred<Ep> = Box<dyn FnOnce(Pinned<'_, Ep>)>;
+
+/// How many rounds of deferred closures run at once: the closures queued by those
+/// already queued, and so on, down to this depth.
+///
+/// Closures queued deeper are left for the next time deferred closures run, around
+/// the next batch, so a closure that queues itself again cannot keep the loop from
+/// waiting.
+pub const MAX_DEFER_DEPTH: usize = 16;
+
+/// Runs the closures of `queue(ep)`, in the order they were queued, round by round.
+///
+/// A closure that panics leaves the ones after it queued, for the next call.
+pub(crate) fn run_deferred<Ep>(ep: &mut Ep, queue: fn(&mut Ep) -> &mut VecDeque<Deferred<Ep>>) {
+    for _ in 0..MAX_DEFER_DEPTH {
+        let round = queue(ep).len();
+        if round == 0 {
+            return;
+        }
+        for _ in 0..round {
+            let Some(f) = queue(ep).pop_front() else {
+                return;
+            };
+            // SAFETY: As for the handlers of the dispatch loop: `Pinned` does not
+            // re-expose anything that could move the loop.
+            f(Pinned(unsafe { Pin::new_unchecked(&mut *ep) }));
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Datagram sockets: the length of the next datagram, and receiving it into a
+//! buffer of that size.
+//!
+//! [`next_datagram_len`] peeks at the length of the datagram at the head of the
+//! queue, with `MSG_PEEK | MSG_TRUNC`, and [`recv_vec`] receives it into a
+//! `Vec<u8>` of exactly that length. Both work with UDP sockets and unix datagram
+//! or seqpacket sockets, blocking or not, and never block.
+//!
+//! [`receiver()`] wraps such a socket into a subscriber handing every datagram
+//! out to its handler, as a [`Datagram`] along with the address of its sender.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::os::fd::AsFd;
+//! use std::os::unix::net::UnixDatagram;
+//!
+//! use eventp::dgram;
+//!
+//! # fn main() -> io::Result<()> {
+//! let (ours, theirs) = UnixDatagram::pair()?;
+//! ours.send(b"hello")?;
+//!
+//! assert_eq!(dgram::next_datagram_len(theirs.as_fd())?, Some(5));
+//! assert_eq!(dgram::recv_vec(theirs.as_fd())?.as_deref(), Some(&b"hello"[..]));
+//! assert_eq!(dgram::recv_vec(theirs.as_fd())?, None);
+//! # Ok(()) }
+//! ```
+//!
+//! See [examples/udp-echo-server.rs](https://github.com/FuuuOverclocking/eventp/blob/main/examples/udp-echo-server.rs)
+//! for a server built on [`receiver()`].
+
+use std::cell::Cell;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::{io, mem, ptr};
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::tri_subscriber::HandlerReturn;
+use crate::{interest, Event, EventpOps, Interest, Pinned};
+
+/// Returns the length of the next datagram queued on the socket `fd`, or `None` if
+/// there is none.
+///
+/// On a connected seqpacket socket, `Some(0)` is also returned once the peer has
+/// closed its end.
+///
+/// # Errors
+///
+/// Forwards any error of recv(2) but `EINTR`, which is retried, and `EAGAIN`, e.g.
+/// `ECONNREFUSED` for a connected UDP socket whose peer is not listening.
+pub fn next_datagram_len(fd: BorrowedFd<'_>) -> io::Result<Option<usize>> {
+    // SAFETY: No buffer is written to, as its length is zero.
+    let n = retry(|| unsafe {
+        libc::recv(
+            fd.as_raw_fd(),
+            ptr::null_mut(),
+            0,
+            libc::MSG_PEEK | libc::MSG_TRUNC | libc::MSG_DONTWAIT,
+        )
+    });
+    would_block_to_none(n)
+}
+
+/// Receives the next datagram queued on the socket `fd`, into a buffer of its
+/// length, or returns `None` if there is none.
+///
+/// # Errors
+///
+/// Forwards any error of recv(2), as [`next_datagram_len`]. With several readers of
+/// the socket, another one may take the datagram peeked at in between: the next one
+/// is then received, or `None` returned if there is none. If the next one is
+/// larger than the one peeked at, it is truncated, and
+/// [`io::ErrorKind::InvalidData`] returned.
+pub fn recv_vec(fd: BorrowedFd<'_>) -> io::Result<Option<Vec<u8>>> {
+    Ok(recv(fd, None)?.map(|datagram| datagram.data))
+}
+
+/// Same as [`recv_vec`], along with the address of the sender.
+pub fn recv_datagram(fd: BorrowedFd<'_>) -> io::Result<Option<Datagram>> {
+    // SAFETY: All zeroes is a valid `sockaddr_storage`.
+    let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    recv(fd, Some(&mut addr))
+}
+
+/// A datagram received by [`recv_datagram`] or [`receiver()`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct Datagram {
+    /// The payload, of its exact length.
+    pub data: Vec<u8>,
+
+    /// The address of the sender, for IPv4 and IPv6 sockets. `None` for other
+    /// families, such as unix sockets.
+    pub peer: Option<SocketAddr>,
+}
+
+fn recv(
+    fd: BorrowedFd<'_>,
+    mut addr: Option<&mut libc::sockaddr_storage>,
+) -> io::Result<Option<Datagram>> {
+    let Some(len) = next_datagram_len(fd)? else {
+        return Ok(None);
+    };
+    let mut data = vec![0u8; len];
+    let mut addr_len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let (addr_ptr, addr_len_ptr) = match &mut addr {
+        Some(addr) => (
+            (*addr as *mut libc::sockaddr_storage).cast(),
+            &mut addr_len as *mut _,
+        ),
+        None => (ptr::null_mut(), ptr::null_mut()),
+    };
+    // SAFETY: `data` is valid for writes of its length, and `addr`, if any, for
+    // writes of `addr_len`.
+    let n = retry(|| unsafe {
+        libc::recvfrom(
+            fd.as_raw_fd(),
+            data.as_mut_ptr().cast(),
+            data.len(),
+            libc::MSG_TRUNC | libc::MSG_DONTWAIT,
+            addr_ptr,
+            addr_len_ptr,
+        )
+    });
+    // Taken by another reader, and none left.
+    let Some(n) = would_block_to_none(n)? else {
+        return Ok(None);
+    };
+    if n > len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("datagram of {n} bytes truncated to {len}, the length peeked at"),
+        ));
+    }
+    data.truncate(n);
+    Ok(Some(Datagram {
+        data,
+        peer: addr.and_then(|addr| socket_addr(addr)),
+    }))
+}
+
+/// Converts an IPv4 or IPv6 address filled in by the kernel.
+fn socket_addr(addr: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match addr.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // SAFETY: The kernel filled in a `sockaddr_in`, which fits in a
+            // `sockaddr_storage`.
+            let addr =
+                unsafe { &*(addr as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            Some(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                u16::from_be(addr.sin_port),
+            )))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: As above, for a `sockaddr_in6`.
+            let addr =
+                unsafe { &*(addr as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>() };
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                u16::from_be(addr.sin6_port),
+                u32::from_be(addr.sin6_flowinfo),
+                addr.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+/// Calls `f` until it does not fail with `EINTR`, and converts its result.
+fn retry(mut f: impl FnMut() -> libc::ssize_t) -> io::Result<usize> {
+    loop {
+        let n = f();
+        if n >= 0 {
+            return Ok(n as usize);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+fn would_block_to_none(result: io::Result<usize>) -> io::Result<Option<usize>> {
+    match result {
+        Ok(n) => Ok(Some(n)),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates a [`Receiver`] over a datagram socket, such as a
+/// [`UdpSocket`](std::net::UdpSocket) or a
+/// [`UnixDatagram`](std::os::unix::net::UnixDatagram).
+///
+/// When the socket is readable, the subscriber receives every datagram queued,
+/// until none is left, and calls the handler once per datagram. Over a seqpacket
+/// socket, it deletes itself once the peer has closed its end.
+pub fn receiver<Fd: AsFd>(socket: Fd) -> Receiver<Fd> {
+    Receiver { socket }
+}
+
+/// A not yet complete datagram receiver, waiting for its handler.
+pub struct Receiver<Fd> {
+    socket: Fd,
+}
+
+impl<Fd: AsFd> Receiver<Fd> {
+    /// Completes the receiver with the handler called for every datagram, with the
+    /// socket, e.g. to reply.
+    ///
+    /// The handler returns either `()` or `io::Result<()>`. After an error, the
+    /// remaining datagrams are left for the next time the loop polls.
+    pub fn with_handler<F>(self, handler: F) -> Subscriber<Fd, F> {
+        Subscriber {
+            seqpacket: socket_type(self.socket.as_fd()) == Some(libc::SOCK_SEQPACKET),
+            socket: self.socket,
+            interest: Cell::new(interest().read()),
+            handler,
+        }
+    }
+}
+
+fn socket_type(fd: BorrowedFd<'_>) -> Option<libc::c_int> {
+    let mut ty: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `ty` is valid for writes of `len` bytes.
+    let ret = unsafe {
+        libc::getsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            (&mut ty as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    };
+    (ret == 0).then_some(ty)
+}
+
+/// The subscriber created by [`Receiver::with_handler`].
+pub struct Subscriber<Fd, F> {
+    socket: Fd,
+    interest: Cell<Interest>,
+    /// Whether an empty datagram is the end of the connection.
+    seqpacket: bool,
+    handler: F,
+}
+
+impl<Fd, F> Subscriber<Fd, F> {
+    /// Returns a reference to the socket.
+    pub fn get_ref(&self) -> &Fd {
+        &self.socket
+    }
+}
+
+impl<Fd: AsFd, F> AsFd for Subscriber<Fd, F> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+impl<Fd, F> HasInterest for Subscriber<Fd, F> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, Fd, F, R> Handler<Ep> for Subscriber<Fd, F>
+where
+    Ep: EventpOps,
+    Fd: AsFd,
+    F: FnMut(&mut Fd, Datagram, Pinned<'_, Ep>) -> R,
+    R: HandlerReturn,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        while let Some(datagram) = recv_datagram(self.socket.as_fd())? {
+            if self.seqpacket && datagram.data.is_empty() {
+                return eventp.delete(self.socket.as_fd().as_raw_fd());
+            }
+            (self.handler)(&mut self.socket, datagram, eventp.as_mut()).into_result()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::net::UdpSocket;
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use std::os::unix::net::UnixDatagram;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{Eventp, Subscriber as _};
+
+    fn seqpacket_pair() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+        // SAFETY: `fds` is valid for writes of two fds.
+        let ret = unsafe {
+            libc::socketpair(
+                libc::AF_UNIX,
+                libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+                0,
+                fds.as_mut_ptr(),
+            )
+        };
+        assert_eq!(ret, 0, "{}", io::Error::last_os_error());
+        // SAFETY: Just created, and owned by nothing else.
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn peeks_then_receives_each_datagram_whole() {
+        let (ours, theirs) = UnixDatagram::pair().unwrap();
+        assert_eq!(next_datagram_len(theirs.as_fd()).unwrap(), None);
+
+        let large = vec![7u8; 100_000];
+        ours.send(&large).unwrap();
+        ours.send(b"").unwrap();
+        ours.send(b"end").unwrap();
+
+        // Peeking leaves the datagram queued.
+        assert_eq!(
+            next_datagram_len(theirs.as_fd()).unwrap(),
+            Some(large.len())
+        );
+        assert_eq!(
+            next_datagram_len(theirs.as_fd()).unwrap(),
+            Some(large.len())
+        );
+        assert_eq!(recv_vec(theirs.as_fd()).unwrap(), Some(large));
+        assert_eq!(recv_vec(theirs.as_fd()).unwrap(), Some(Vec::new()));
+        assert_eq!(
+            recv_vec(theirs.as_fd()).unwrap().as_deref(),
+            Some(&b"end"[..])
+        );
+        assert_eq!(recv_vec(theirs.as_fd()).unwrap(), None);
+    }
+
+    #[test]
+    fn udp_datagrams_carry_the_sender() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .send_to(b"ping", server.local_addr().unwrap())
+            .unwrap();
+
+        let datagram = loop {
+            if let Some(datagram) = recv_datagram(server.as_fd()).unwrap() {
+                break datagram;
+            }
+        };
+        assert_eq!(datagram.data, b"ping");
+        assert_eq!(datagram.peer, Some(client.local_addr().unwrap()));
+    }
+
+    #[test]
+    fn receiver_hands_out_every_datagram_then_deletes_itself_on_eof() {
+        let mut eventp = Eventp::default();
+        let (ours, theirs) = seqpacket_pair();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let s = seen.clone();
+        receiver(theirs)
+            .with_handler(
+                move |_: &mut OwnedFd, datagram: Datagram, _: Pinned<'_, Eventp>| {
+                    assert_eq!(datagram.peer, None);
+                    s.borrow_mut().push(datagram.data);
+                },
+            )
+            .register_into(&mut eventp)
+            .unwrap();
+
+        let ours = UnixDatagram::from(ours);
+        ours.send(b"a").unwrap();
+        ours.send(b"bc").unwrap();
+        eventp
+            .run_once_with_timeout(EpollTimeout::from(500u16))
+            .unwrap();
+        assert_eq!(*seen.borrow(), [b"a".to_vec(), b"bc".to_vec()]);
+
+        drop(ours);
+        eventp
+            .run_once_with_timeout(EpollTimeout::from(500u16))
+            .unwrap();
+        assert!(eventp.is_empty());
+    }
+}
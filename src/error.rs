@@ -0,0 +1,379 @@
+use std::os::fd::RawFd;
+use std::{fmt, io};
+
+use crate::epoll::EpollFlags;
+
+/// Structured errors of this crate.
+///
+/// The public API keeps returning [`io::Result`]; when a failure has more to say
+/// than an [`io::ErrorKind`], the `io::Error` carries one of these as its payload.
+/// Recover it with [`Error::from_io`].
+///
+/// ```rust
+/// # use std::io;
+/// use eventp::Error;
+///
+/// fn explain(err: &io::Error) {
+///     match Error::from_io(err) {
+///         Some(Error::ExclusiveIncompatible { flags }) => {
+///             eprintln!("cannot use {flags:?} together with EPOLLEXCLUSIVE")
+///         }
+///         _ => eprintln!("{err}"),
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// An interest registered with `EPOLLEXCLUSIVE` contains flags the kernel
+    /// rejects in combination with it. Converts to [`io::ErrorKind::InvalidInput`].
+    ExclusiveIncompatible {
+        /// The offending flags, i.e. the interest minus the compatible ones.
+        flags: EpollFlags,
+    },
+    /// An interest with `EPOLLWAKEUP` also has `EPOLLONESHOT` or `EPOLLET`, with
+    /// which the kernel does not promise to keep the system awake, see
+    /// [`Interest::wakeup`](crate::Interest::wakeup). Converts to
+    /// [`io::ErrorKind::InvalidInput`].
+    WakeupIneffective {
+        /// The flags that make `EPOLLWAKEUP` ineffective.
+        flags: EpollFlags,
+    },
+    /// The registration of the fd, or the new interest, has `EPOLLEXCLUSIVE`, which
+    /// the kernel refuses to modify. Delete the subscriber and add it again instead.
+    /// Converts to [`io::ErrorKind::InvalidInput`].
+    ExclusiveModify {
+        /// The fd being modified.
+        fd: RawFd,
+    },
+    /// The fd is already registered with another `Eventp`, as detected by the
+    /// `debug-ownership` feature. Converts to [`io::ErrorKind::AlreadyExists`].
+    RegisteredElsewhere {
+        /// The fd being registered.
+        fd: RawFd,
+    },
+    /// The loop already has as many subscribers as allowed by
+    /// [`Builder::max_subscribers`](crate::Builder::max_subscribers). Converts to
+    /// [`io::ErrorKind::Other`], as no other kind fits on every supported Rust version.
+    AtCapacity {
+        /// The maximum number of subscribers.
+        max: usize,
+    },
+    /// Every slot of a loop built with
+    /// [`Builder::fixed_capacity`](crate::Builder::fixed_capacity) holds a
+    /// subscriber. Converts to [`io::ErrorKind::Other`], like
+    /// [`AtCapacity`](Error::AtCapacity).
+    RegistryFull {
+        /// The number of slots.
+        capacity: usize,
+    },
+    /// Adding the subscriber would take the memory of the registered subscribers
+    /// over [`Builder::max_subscriber_bytes`](crate::Builder::max_subscriber_bytes).
+    /// Converts to [`io::ErrorKind::Other`], like [`AtCapacity`](Error::AtCapacity).
+    OverMemoryBudget {
+        /// The fd being registered.
+        fd: RawFd,
+        /// The [allocated bytes](crate::thin::ThinBoxSubscriber::allocated_bytes) of
+        /// its subscriber.
+        requested: usize,
+        /// The bytes already used by the registered subscribers.
+        used: usize,
+        /// The maximum number of bytes.
+        max: usize,
+    },
+    /// The subscriber is added with an interest in no event, which the kernel would
+    /// only report hangups and errors for, most often [`Interest::default`] left by
+    /// mistake. Mark the interest with [`Interest::hangup_only`] if intended, or
+    /// disable the check with
+    /// [`Builder::reject_empty_interests`](crate::Builder::reject_empty_interests).
+    /// Converts to [`io::ErrorKind::InvalidInput`].
+    ///
+    /// [`Interest::default`]: crate::Interest::default
+    /// [`Interest::hangup_only`]: crate::Interest::hangup_only
+    EmptyInterest {
+        /// The fd being registered.
+        fd: RawFd,
+    },
+    /// The subscriber is added with a negative fd, which can only come from an
+    /// `unsafe` conversion gone wrong. Converts to [`io::ErrorKind::InvalidInput`].
+    NegativeFd {
+        /// The fd being registered.
+        fd: RawFd,
+    },
+    /// The subscriber is added with the [epoll fd](crate::Eventp::poll_fd) of the
+    /// loop itself, whose readiness would only ever report its own events. Register
+    /// a loop into another one instead, see [`ChildEventp`](crate::ChildEventp).
+    /// Converts to [`io::ErrorKind::InvalidInput`].
+    SelfRegistration {
+        /// The epoll fd of the loop.
+        fd: RawFd,
+    },
+    /// The subscriber is added with an fd that is not open, most often one closed
+    /// by its previous owner. Only checked in debug builds, with an extra
+    /// `fcntl(F_GETFD)`; release builds get the `EBADF` of `epoll_ctl` instead.
+    /// Converts to [`io::ErrorKind::InvalidInput`].
+    ClosedFd {
+        /// The fd being registered.
+        fd: RawFd,
+    },
+    /// The fd is added edge-triggered without `O_NONBLOCK`, so its handler blocks
+    /// the loop once it reads or writes until `EAGAIN`. Only checked in debug
+    /// builds, with [`Builder::debug_assert_nonblocking`](crate::Builder::debug_assert_nonblocking).
+    /// Converts to [`io::ErrorKind::InvalidInput`].
+    BlockingFd {
+        /// The fd being registered.
+        fd: RawFd,
+    },
+    /// The subscriber is the one whose handler is running, so it cannot be lent
+    /// out again by [`Pinned::with_subscriber_mut`](crate::Pinned::with_subscriber_mut).
+    /// Converts to [`io::ErrorKind::InvalidInput`].
+    CurrentlyHandled {
+        /// The fd of the running handler.
+        fd: RawFd,
+    },
+    /// The subscriber of the fd deleted itself, and is dropped once its handler
+    /// returns, so the handler can neither modify nor delete it again, nor add
+    /// another subscriber for the fd. Defer the add with
+    /// [`EventpOps::defer`](crate::EventpOps::defer) to replace the subscriber.
+    /// Converts to [`io::ErrorKind::NotFound`], as the fd is no longer registered,
+    /// except when returned by [`add`](crate::EventpOpsAdd::add), with
+    /// [`io::ErrorKind::AlreadyExists`], as its subscriber still is.
+    PendingRemoval {
+        /// The fd of the deleted subscriber.
+        fd: RawFd,
+    },
+    /// The subscriber is added while the loop shuts down, see
+    /// [`Eventp::shutdown`](crate::Eventp::shutdown). Converts to
+    /// [`io::ErrorKind::Other`], like [`AtCapacity`](Error::AtCapacity).
+    ShuttingDown {
+        /// The fd being registered.
+        fd: RawFd,
+    },
+    /// Creating the epoll, or registering an fd, failed for want of a resource:
+    /// `EMFILE` or `ENFILE` for fds, `ENOSPC` for the epoll watches of the user, see
+    /// `/proc/sys/fs/epoll/max_user_watches`. Converts to [`io::ErrorKind::Other`],
+    /// like [`AtCapacity`](Error::AtCapacity).
+    ///
+    /// Unlike other errors of `add`, it says nothing about the subscriber, so an
+    /// accept loop would rather shed connections for a while.
+    ResourceExhausted {
+        /// `EMFILE`, `ENFILE` or `ENOSPC`.
+        errno: i32,
+        /// The soft `RLIMIT_NOFILE` of the process, for `EMFILE` and `ENFILE`, unless
+        /// unlimited.
+        fd_limit: Option<u64>,
+        /// How many fds the process had open, for `EMFILE` and `ENFILE`, if they
+        /// could be counted.
+        open_fds: Option<usize>,
+    },
+    /// A call to the kernel failed, or would have, for an fd the loop knows is not
+    /// registered, reported with the fd and subscriber it was made for under
+    /// [`Builder::error_context`](crate::Builder::error_context), and always for the
+    /// poll requests of `UringEventp`. Converts to the [`io::ErrorKind`] of `errno`.
+    ///
+    /// The `io::Error` no longer returns the errno from
+    /// [`raw_os_error`](io::Error::raw_os_error), see [`Error::raw_os_error`].
+    Syscall {
+        /// `"EPOLL_CTL_ADD"`, `"EPOLL_CTL_MOD"`, `"EPOLL_CTL_DEL"`, `"epoll_wait"`,
+        /// `"IORING_OP_POLL_ADD"`, or `"set_user_data"`, which the kernel never sees.
+        op: &'static str,
+        /// The fd of the call, or the epoll fd for `epoll_wait`.
+        fd: RawFd,
+        /// The [name](crate::thin::ThinBoxSubscriber::name) of the subscriber of
+        /// `fd`, if any.
+        name: Option<&'static str>,
+        /// The errno of the call, `ENOENT` for an fd not registered.
+        errno: i32,
+    },
+}
+
+impl Error {
+    /// Returns the `Error` carried by `err`, if it was produced by this crate.
+    pub fn from_io(err: &io::Error) -> Option<&Error> {
+        err.get_ref().and_then(|inner| inner.downcast_ref())
+    }
+
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::ExclusiveIncompatible { .. } => io::ErrorKind::InvalidInput,
+            Error::WakeupIneffective { .. } => io::ErrorKind::InvalidInput,
+            Error::ExclusiveModify { .. } => io::ErrorKind::InvalidInput,
+            Error::RegisteredElsewhere { .. } => io::ErrorKind::AlreadyExists,
+            Error::AtCapacity { .. } => io::ErrorKind::Other,
+            Error::RegistryFull { .. } => io::ErrorKind::Other,
+            Error::OverMemoryBudget { .. } => io::ErrorKind::Other,
+            Error::NegativeFd { .. } => io::ErrorKind::InvalidInput,
+            Error::SelfRegistration { .. } => io::ErrorKind::InvalidInput,
+            Error::ClosedFd { .. } => io::ErrorKind::InvalidInput,
+            Error::BlockingFd { .. } => io::ErrorKind::InvalidInput,
+            Error::CurrentlyHandled { .. } => io::ErrorKind::InvalidInput,
+            Error::EmptyInterest { .. } => io::ErrorKind::InvalidInput,
+            Error::PendingRemoval { .. } => io::ErrorKind::NotFound,
+            Error::ShuttingDown { .. } => io::ErrorKind::Other,
+            Error::ResourceExhausted { .. } => io::ErrorKind::Other,
+            Error::Syscall { errno, .. } => io::Error::from_raw_os_error(*errno).kind(),
+        }
+    }
+
+    /// Returns the errno of `err`, whether it is a bare OS error or carried by an
+    /// [`Error::Syscall`] or [`Error::ResourceExhausted`], to match errnos the same
+    /// way with or without [`Builder::error_context`](crate::Builder::error_context).
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::{Eventp, EventpOps};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::builder().error_context(true).build()?;
+    /// let err = eventp.modify(17, eventp::interest().read()).unwrap_err();
+    /// assert_eq!(err.raw_os_error(), None);
+    /// assert_eq!(eventp::Error::raw_os_error(&err), Some(libc::ENOENT));
+    /// # Ok(()) }
+    /// ```
+    pub fn raw_os_error(err: &io::Error) -> Option<i32> {
+        err.raw_os_error().or_else(|| match Error::from_io(err)? {
+            Error::Syscall { errno, .. } | Error::ResourceExhausted { errno, .. } => Some(*errno),
+            _ => None,
+        })
+    }
+
+    /// Turns an `EMFILE`, `ENFILE` or `ENOSPC` error into an
+    /// [`Error::ResourceExhausted`], and returns other errors as they are.
+    pub(crate) fn exhausted(err: io::Error) -> io::Error {
+        let errno = match err.raw_os_error() {
+            Some(errno @ (libc::EMFILE | libc::ENFILE | libc::ENOSPC)) => errno,
+            _ => return err,
+        };
+        let (fd_limit, open_fds) = match errno {
+            libc::ENOSPC => (None, None),
+            _ => {
+                let fd_limit = fd_limit();
+                (fd_limit, count_open_fds(fd_limit))
+            }
+        };
+        Error::ResourceExhausted {
+            errno,
+            fd_limit,
+            open_fds,
+        }
+        .into()
+    }
+}
+
+/// Counting the open fds one by one is given up above this limit.
+const MAX_SCANNED_FDS: u64 = 1 << 16;
+
+/// Returns the soft `RLIMIT_NOFILE`, unless unlimited.
+fn fd_limit() -> Option<u64> {
+    // SAFETY: All zeroes is a valid `rlimit`.
+    let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+    // SAFETY: `limit` is a valid `rlimit` to write into.
+    let ret = unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) };
+    (ret == 0 && limit.rlim_cur != libc::RLIM_INFINITY).then_some(limit.rlim_cur)
+}
+
+/// Counts the open fds of the process, from `/proc/self/fd`, or else one by one
+/// below `fd_limit`, as there may be no fd left to open the directory with.
+fn count_open_fds(fd_limit: Option<u64>) -> Option<usize> {
+    if let Ok(entries) = std::fs::read_dir("/proc/self/fd") {
+        // Less the fd of the directory itself.
+        return Some(entries.count().saturating_sub(1));
+    }
+    let limit = fd_limit.filter(|&limit| limit <= MAX_SCANNED_FDS)?;
+    let open = (0..limit as RawFd)
+        // SAFETY: `F_GETFD` takes no pointer, and fails on a closed fd.
+        .filter(|&fd| unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1)
+        .count();
+    Some(open)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ExclusiveIncompatible { flags } => {
+                write!(f, "{flags:?} cannot be combined with EPOLLEXCLUSIVE")
+            }
+            Error::WakeupIneffective { flags } => {
+                write!(f, "EPOLLWAKEUP has no effect together with {flags:?}")
+            }
+            Error::ExclusiveModify { fd } => write!(
+                f,
+                "the EPOLLEXCLUSIVE registration of fd {fd} cannot be modified, delete it and add it again"
+            ),
+            Error::RegisteredElsewhere { fd } => {
+                write!(f, "fd {fd} is already registered with another Eventp")
+            }
+            Error::AtCapacity { max } => write!(f, "the loop is full, with {max} subscribers"),
+            Error::RegistryFull { capacity } => {
+                write!(f, "every one of the {capacity} fixed slots holds a subscriber")
+            }
+            Error::OverMemoryBudget {
+                fd,
+                requested,
+                used,
+                max,
+            } => write!(
+                f,
+                "the subscriber of fd {fd} takes {requested} bytes, over the budget of {max} bytes with {used} used"
+            ),
+            Error::NegativeFd { fd } => write!(f, "fd {fd} is negative"),
+            Error::SelfRegistration { fd } => {
+                write!(f, "fd {fd} is the epoll fd of the loop it is added to")
+            }
+            Error::ClosedFd { fd } => write!(f, "fd {fd} is not open"),
+            Error::BlockingFd { fd } => write!(
+                f,
+                "fd {fd} is edge-triggered without O_NONBLOCK, see `Interest::with_fd_nonblocking`"
+            ),
+            Error::CurrentlyHandled { fd } => {
+                write!(f, "the subscriber of fd {fd} is the one being handled")
+            }
+            Error::EmptyInterest { fd } => write!(
+                f,
+                "fd {fd} is added with an interest in no event, see `Interest::hangup_only`"
+            ),
+            Error::PendingRemoval { fd } => write!(
+                f,
+                "the subscriber of fd {fd} is deleted, and dropped once its handler returns"
+            ),
+            Error::ShuttingDown { fd } => {
+                write!(f, "fd {fd} is added while the loop shuts down")
+            }
+            Error::ResourceExhausted {
+                errno,
+                fd_limit,
+                open_fds,
+            } => {
+                write!(f, "{}", io::Error::from_raw_os_error(*errno))?;
+                match (open_fds, fd_limit) {
+                    (Some(open), Some(limit)) => {
+                        write!(f, ", with {open} fds open of a RLIMIT_NOFILE of {limit}")
+                    }
+                    (Some(open), None) => write!(f, ", with {open} fds open"),
+                    (None, Some(limit)) => write!(f, ", with a RLIMIT_NOFILE of {limit}"),
+                    (None, None) => Ok(()),
+                }
+            }
+            Error::Syscall {
+                op,
+                fd,
+                name,
+                errno,
+            } => {
+                write!(f, "{op} failed for fd {fd}")?;
+                if let Some(name) = name {
+                    write!(f, " ({name:?})")?;
+                }
+                write!(f, ": {:?}", nix::errno::Errno::from_raw(*errno))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        io::Error::new(err.kind(), err)
+    }
+}
@@ -1,4 +1,8 @@
+use std::os::fd::{AsFd, AsRawFd};
+use std::{io, mem};
+
 use crate::epoll::{EpollEvent, EpollFlags};
+use crate::Interest;
 
 /// A readiness event from the I/O reactor.
 ///
@@ -8,8 +12,10 @@ use crate::epoll::{EpollEvent, EpollFlags};
 /// References for epoll flags provided on each method's documentation, or see
 /// [epoll_ctl(2)](https://man.archlinux.org/man/epoll_ctl.2.en#EPOLLIN).
 #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-#[repr(transparent)]
-pub struct Event(EpollFlags);
+pub struct Event {
+    flags: EpollFlags,
+    data: u64,
+}
 
 impl From<EpollFlags> for Event {
     fn from(value: EpollFlags) -> Self {
@@ -19,13 +25,13 @@ impl From<EpollFlags> for Event {
 
 impl From<&EpollEvent> for Event {
     fn from(value: &EpollEvent) -> Self {
-        let ptr = value as *const _ as *const libc::epoll_event;
-        // SAFETY: `nix::sys::epoll::EpollEvent` is a `#[repr(C)]` struct with a
-        // single `libc::epoll_event` field, so it is layout-compatible with
-        // `libc::epoll_event` and a pointer cast between the two is sound.
-        Self(EpollFlags::from_bits_retain(
-            unsafe { *ptr }.events as libc::c_int,
-        ))
+        Self::new(value.events()).with_data(value.data())
+    }
+}
+
+impl From<EpollEvent> for Event {
+    fn from(value: EpollEvent) -> Self {
+        Self::from(&value)
     }
 }
 
@@ -35,29 +41,61 @@ impl From<Event> for EpollFlags {
     }
 }
 
+impl PartialEq<EpollFlags> for Event {
+    /// Compares the flags only, not the [data word](Event::data).
+    fn eq(&self, other: &EpollFlags) -> bool {
+        self.flags == *other
+    }
+}
+
 impl Event {
-    /// Creates a new `Event` from the given `EpollFlags`.
+    /// Creates a new `Event` from the given `EpollFlags`, with a data word of 0.
     pub const fn new(flags: EpollFlags) -> Self {
-        Self(flags)
+        Self { flags, data: 0 }
+    }
+
+    /// Returns the event with its [data word](Self::data) set to `data`.
+    pub const fn with_data(self, data: u64) -> Self {
+        Self { data, ..self }
     }
 
     /// Returns the underlying `EpollFlags` bitmask.
     pub const fn bitflags(&self) -> EpollFlags {
-        self.0
+        self.flags
+    }
+
+    /// Returns the `data` word of the `struct epoll_event` the event was
+    /// converted from, or 0.
+    ///
+    /// Events dispatched by [`Eventp`](crate::Eventp) always carry 0: the loop
+    /// keeps its own bookkeeping in the word handed to the kernel.
+    pub const fn data(&self) -> u64 {
+        self.data
+    }
+
+    /// Returns the raw `events` mask, as in `struct epoll_event`.
+    pub const fn as_raw(&self) -> u32 {
+        self.flags.bits() as u32
+    }
+
+    /// Creates an `Event` from a raw `events` mask, keeping bits without an
+    /// [`EpollFlags`] name.
+    pub const fn from_raw(events: u32) -> Self {
+        Self::new(EpollFlags::from_bits_retain(events as i32))
     }
 
     /// Returns `true` if the event indicates readable readiness (`EPOLLIN`).
     ///
     /// The associated file is available for read(2) operations.
     pub const fn is_readable(&self) -> bool {
-        self.0.contains(EpollFlags::EPOLLIN)
+        self.flags.contains(EpollFlags::EPOLLIN)
     }
 
     /// Returns `true` if the event indicates writable readiness (`EPOLLOUT`).
     ///
     /// The associated file is available for write(2) operations.
     pub const fn is_writable(&self) -> bool {
-        self.0.contains(EpollFlags::EPOLLOUT)
+        self.flags.contains(EpollFlags::EPOLLOUT)
     }
 
     /// Returns `true` if the event indicates priority readiness (`EPOLLPRI`).
@@ -65,18 +103,62 @@ impl Event {
     /// There is an exceptional condition on the file descriptor. See the discussion of
     /// POLLPRI in poll(2).
     pub const fn is_priority(&self) -> bool {
-        self.0.contains(EpollFlags::EPOLLPRI)
+        self.flags.contains(EpollFlags::EPOLLPRI)
     }
 
     /// Returns `true` if the event indicates an error condition (`EPOLLERR`).
     ///
     /// Error condition happened on the associated file descriptor. This event is also
-    /// reported for the write end of a pipe when the read end has been closed.
+    /// reported for the write end of a pipe when the read end has been closed, see
+    /// [`is_write_hangup`](Self::is_write_hangup).
     ///
     /// epoll_wait(2) will always report for this event; it is not necessary to set it in
     /// events when calling epoll_ctl().
     pub const fn is_error(&self) -> bool {
-        self.0.contains(EpollFlags::EPOLLERR)
+        self.flags.contains(EpollFlags::EPOLLERR)
+    }
+
+    /// Returns `true` if the event is an error alone, neither readable nor a hang up:
+    /// what the write end of a pipe or FIFO reports once its read end is closed.
+    ///
+    /// Writes to the fd fail with `EPIPE`, and nothing will read what was written;
+    /// there is no error to query. On a socket, the same flags are rather a pending
+    /// error, found with [`socket_error`](Self::socket_error).
+    pub const fn is_write_hangup(&self) -> bool {
+        self.is_error() && !self.is_readable() && !self.is_hangup()
+    }
+
+    /// Takes the pending error of the socket `fd` the event is for, with
+    /// `getsockopt(SO_ERROR)`, e.g. to tell a refused connection from a reset one.
+    ///
+    /// Returns `Ok(None)` if the socket has no pending error, and, without asking
+    /// the kernel, if the event is not an [error](Self::is_error). The kernel clears
+    /// the pending error as it returns it.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error of `getsockopt`, e.g. `ENOTSOCK` if `fd` is not a socket.
+    pub fn socket_error(&self, fd: impl AsFd) -> io::Result<Option<io::Error>> {
+        if !self.is_error() {
+            return Ok(None);
+        }
+        let mut errno: libc::c_int = 0;
+        let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // SAFETY: `errno` and `len` are valid for writes, and `len` is the size of
+        // `errno`.
+        let ret = unsafe {
+            libc::getsockopt(
+                fd.as_fd().as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_ERROR,
+                &mut errno as *mut libc::c_int as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((errno != 0).then(|| io::Error::from_raw_os_error(errno)))
     }
 
     /// Returns `true` if the event indicates that a hang up has occurred (`EPOLLHUP`).
@@ -91,7 +173,7 @@ impl Event {
     /// reads from the channel will return 0 (end of file) only after all outstanding
     /// data in the channel has been consumed.
     pub const fn is_hangup(&self) -> bool {
-        self.0.contains(EpollFlags::EPOLLHUP)
+        self.flags.contains(EpollFlags::EPOLLHUP)
     }
 
     /// Returns `true` if the peer has closed their writing end of the connection (`EPOLLRDHUP`).
@@ -100,6 +182,335 @@ impl Event {
     /// (This flag is especially useful for writing simple code to detect peer shutdown
     /// when using edge-triggered monitoring.)
     pub const fn is_read_closed(&self) -> bool {
-        self.0.contains(EpollFlags::EPOLLRDHUP)
+        self.flags.contains(EpollFlags::EPOLLRDHUP)
+    }
+
+    /// Returns `true` if the stream is closed in any way, i.e. if any of `EPOLLHUP`,
+    /// `EPOLLERR` or `EPOLLRDHUP` is set, the flags of [`Interest::all_read_errors`].
+    ///
+    /// Data sent before the close may still be buffered: if the event is also
+    /// [readable](Self::is_readable), read until end of file, or an error, before
+    /// closing. `EPOLLRDHUP` alone is only reported if registered, e.g. with
+    /// [`Interest::stream_read`].
+    pub const fn is_closed(&self) -> bool {
+        self.flags
+            .intersects(Interest::all_read_errors().bitflags())
+    }
+
+    /// Returns `true` if every flag of `other` is set in the event.
+    ///
+    /// `other` can be another `Event`, [`EpollFlags`] or an [`Interest`]. Mode
+    /// flags like `EPOLLET` are never reported, so an interest with one is never
+    /// contained.
+    pub fn contains(&self, other: impl Into<EpollFlags>) -> bool {
+        self.flags.contains(other.into())
+    }
+
+    /// Returns `true` if all the raw `raw_bits` are set in the event, such as those
+    /// asked for with [`Interest::with_custom`].
+    pub const fn has_custom(&self, raw_bits: u32) -> bool {
+        self.flags
+            .contains(EpollFlags::from_bits_retain(raw_bits as i32))
+    }
+
+    /// Returns `true` if any flag of `other` is set in the event.
+    ///
+    /// `other` can be another `Event`, [`EpollFlags`] or an [`Interest`]. Mode
+    /// flags like `EPOLLET` are never reported, so they never match.
+    pub fn intersects(&self, other: impl Into<EpollFlags>) -> bool {
+        self.flags.intersects(other.into())
+    }
+}
+
+/// How an [`Event`] differs from the previous one dispatched to the same
+/// subscriber, for subscribers registered with [`Interest::track_deltas`].
+///
+/// With `EPOLLET`, each wake-up reports the whole readiness of the fd, not only
+/// what caused it. A handler interested in reading and writing is woken again with
+/// `EPOLLIN` when only `EPOLLOUT` came back, and vice versa; the delta tells the
+/// two apart without keeping that state in every subscriber.
+///
+/// Handlers of [`tri_subscriber`](crate::tri_subscriber) can take it as a
+/// parameter.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct EventDelta {
+    previous: Event,
+    current: Event,
+}
+
+impl EventDelta {
+    /// Creates the delta from `previous` to `current`.
+    pub const fn between(previous: Event, current: Event) -> Self {
+        Self { previous, current }
+    }
+
+    /// Returns the previous event, or an empty one before the first event.
+    pub const fn previous(&self) -> Event {
+        self.previous
+    }
+
+    /// Returns `true` if the fd became [readable](Event::is_readable).
+    pub const fn newly_readable(&self) -> bool {
+        self.current.is_readable() && !self.previous.is_readable()
+    }
+
+    /// Returns `true` if the fd became [writable](Event::is_writable), e.g. once
+    /// the peer drained a full send buffer.
+    pub const fn newly_writable(&self) -> bool {
+        self.current.is_writable() && !self.previous.is_writable()
+    }
+
+    /// Returns `true` if the fd became [closed](Event::is_closed).
+    pub const fn newly_closed(&self) -> bool {
+        self.current.is_closed() && !self.previous.is_closed()
+    }
+}
+
+/// Where the event being dispatched stands in its batch, the events returned by one
+/// `epoll_wait`, e.g. to skip an expensive path while many events are waiting.
+///
+/// Handlers of [`tri_subscriber`](crate::tri_subscriber) can take it as a
+/// parameter, others get it from [`Pinned::batch_info`](crate::Pinned::batch_info).
+/// With a budget, as with [`Eventp::run_once_budgeted`](crate::Eventp::run_once_budgeted),
+/// the events left over make a batch of their own.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct BatchInfo {
+    index: usize,
+    total: usize,
+    sequence: u64,
+}
+
+impl BatchInfo {
+    /// Creates the info of the event at `index` in a batch of `total` events, the
+    /// batch being number `sequence` of its loop.
+    pub const fn new(index: usize, total: usize, sequence: u64) -> Self {
+        Self {
+            index,
+            total,
+            sequence,
+        }
+    }
+
+    /// Returns the index of the event within its batch, from 0.
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the number of events in the batch, including those of subscribers
+    /// deleted before their turn.
+    pub const fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the number of events after this one in the batch.
+    pub const fn remaining(&self) -> usize {
+        self.total.saturating_sub(self.index + 1)
+    }
+
+    /// Returns the number of the batch, counted by the loop from 0, and increasing
+    /// by one with each batch it dispatches.
+    pub const fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every flag `epoll_wait` can report.
+    const REPORTED: [EpollFlags; 8] = [
+        EpollFlags::EPOLLIN,
+        EpollFlags::EPOLLPRI,
+        EpollFlags::EPOLLOUT,
+        EpollFlags::EPOLLERR,
+        EpollFlags::EPOLLHUP,
+        EpollFlags::EPOLLRDNORM,
+        EpollFlags::EPOLLMSG,
+        EpollFlags::EPOLLRDHUP,
+    ];
+
+    #[test]
+    fn is_closed_for_every_single_flag() {
+        for flags in REPORTED {
+            let closed = matches!(
+                flags,
+                EpollFlags::EPOLLERR | EpollFlags::EPOLLHUP | EpollFlags::EPOLLRDHUP
+            );
+            assert_eq!(Event::new(flags).is_closed(), closed, "{flags:?}");
+            assert_eq!(
+                Event::new(flags | EpollFlags::EPOLLIN).is_closed(),
+                closed,
+                "{flags:?} | EPOLLIN"
+            );
+        }
+        assert!(!Event::new(EpollFlags::empty()).is_closed());
+    }
+
+    #[test]
+    fn intersects_any_common_flag() {
+        const EVENT: Event = Event::new(EpollFlags::EPOLLIN.union(EpollFlags::EPOLLHUP));
+        const _: () = assert!(EVENT.is_closed());
+
+        assert!(EVENT.intersects(Interest::stream_read()));
+        assert!(EVENT.intersects(Interest::all_read_errors()));
+        assert!(!EVENT.intersects(crate::interest().write()));
+        assert!(!EVENT.intersects(crate::interest()));
+        assert!(!Event::new(EpollFlags::EPOLLOUT).intersects(crate::interest().edge_triggered()));
+
+        assert!(EVENT.intersects(Event::new(EpollFlags::EPOLLHUP | EpollFlags::EPOLLOUT)));
+        assert!(!EVENT.intersects(EpollFlags::EPOLLOUT));
+    }
+
+    #[test]
+    fn contains_every_flag() {
+        let event = Event::new(EpollFlags::EPOLLIN | EpollFlags::EPOLLHUP);
+
+        assert!(event.contains(event));
+        assert!(event.contains(Event::new(EpollFlags::EPOLLHUP)));
+        assert!(event.contains(EpollFlags::empty()));
+        assert!(event.contains(crate::interest().read()));
+        assert!(!event.contains(EpollFlags::EPOLLIN | EpollFlags::EPOLLOUT));
+        assert!(!event.contains(crate::interest().read().edge_triggered()));
+    }
+
+    #[test]
+    fn has_custom_checks_the_raw_bits() {
+        const UNKNOWN: u32 = 1 << 14;
+        let event = Event::from_raw(EpollFlags::EPOLLIN.bits() as u32 | UNKNOWN);
+        assert!(event.has_custom(UNKNOWN));
+        assert!(event.has_custom(UNKNOWN | EpollFlags::EPOLLIN.bits() as u32));
+        assert!(!event.has_custom(UNKNOWN | 1 << 15));
+        assert!(!Event::new(EpollFlags::EPOLLIN).has_custom(UNKNOWN));
+        assert_eq!(Event::from_raw(event.as_raw()), event);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn round_trips_events_from_epoll_wait() {
+        use crate::epoll::{Epoll, EpollCreateFlags, EpollTimeout};
+
+        let epoll = Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC).unwrap();
+        let (reader, writer) = nix::unistd::pipe().unwrap();
+        let both = EpollFlags::EPOLLIN | EpollFlags::EPOLLOUT;
+        epoll.add(&reader, EpollEvent::new(both, 1)).unwrap();
+        epoll.add(&writer, EpollEvent::new(both, u64::MAX)).unwrap();
+        nix::unistd::write(&writer, b"x").unwrap();
+        drop(writer);
+
+        let mut events = [EpollEvent::empty(); 2];
+        assert_eq!(epoll.wait(&mut events, EpollTimeout::ZERO).unwrap(), 1);
+        let event = Event::from(&events[0]);
+        assert_eq!(event, EpollFlags::EPOLLIN | EpollFlags::EPOLLHUP);
+        assert_eq!(event.data(), 1);
+        assert_eq!(event, Event::from(events[0]));
+        assert_eq!(EpollFlags::from(event), events[0].events());
+        assert_eq!(Event::from_raw(event.as_raw()), event.with_data(0));
+
+        // The data word is part of the identity, unlike in the comparison with flags.
+        assert_ne!(event, Event::new(event.bitflags()));
+        assert_eq!(Event::new(event.bitflags()).with_data(1), event);
+    }
+
+    /// Waits for an event on `fd`, registered for `flags`.
+    #[cfg(target_os = "linux")]
+    fn wait_for(fd: impl AsFd, flags: EpollFlags) -> Event {
+        use crate::epoll::{Epoll, EpollCreateFlags, EpollTimeout};
+
+        let epoll = Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC).unwrap();
+        epoll.add(fd, EpollEvent::new(flags, 0)).unwrap();
+        let mut events = [EpollEvent::empty(); 1];
+        let n = epoll.wait(&mut events, EpollTimeout::from(5000u16));
+        assert_eq!(n.unwrap(), 1);
+        Event::from(&events[0])
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn socket_error_of_a_reset_connection() {
+        use std::net::{TcpListener, TcpStream};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let healthy = wait_for(&client, EpollFlags::EPOLLOUT);
+        assert!(healthy.is_writable());
+        assert!(healthy.socket_error(&client).unwrap().is_none());
+        // No error flag, so the socket is not even asked.
+        let not_a_socket = nix::unistd::pipe().unwrap().0;
+        assert!(healthy.socket_error(&not_a_socket).unwrap().is_none());
+
+        // Closing with a zero linger timeout sends a RST instead of a FIN.
+        let linger = libc::linger {
+            l_onoff: 1,
+            l_linger: 0,
+        };
+        // SAFETY: `linger` is valid for reads, with its size passed along.
+        let ret = unsafe {
+            libc::setsockopt(
+                server.as_fd().as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_LINGER,
+                &linger as *const libc::linger as *const libc::c_void,
+                mem::size_of::<libc::linger>() as libc::socklen_t,
+            )
+        };
+        assert_eq!(ret, 0);
+        drop(server);
+
+        let reset = wait_for(&client, EpollFlags::EPOLLIN);
+        assert!(reset.is_error() && !reset.is_write_hangup(), "{reset:?}");
+        let error = reset.socket_error(&client).unwrap().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+        // Taken by the first call.
+        assert!(reset.socket_error(&client).unwrap().is_none());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn write_hangup_of_a_pipe_without_reader() {
+        let (reader, writer) = nix::unistd::pipe().unwrap();
+        drop(reader);
+
+        let event = wait_for(&writer, EpollFlags::EPOLLOUT);
+        assert!(event.is_write_hangup(), "{event:?}");
+        let err = event.socket_error(&writer).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOTSOCK));
+        assert_eq!(
+            nix::unistd::write(&writer, b"x"),
+            Err(nix::errno::Errno::EPIPE)
+        );
+
+        assert!(!Event::new(EpollFlags::EPOLLERR | EpollFlags::EPOLLHUP).is_write_hangup());
+        assert!(!Event::new(EpollFlags::EPOLLOUT).is_write_hangup());
+    }
+
+    #[test]
+    fn delta_reports_only_flags_that_appeared() {
+        let none = Event::new(EpollFlags::empty());
+        let read = Event::new(EpollFlags::EPOLLIN);
+        let read_write = Event::new(EpollFlags::EPOLLIN | EpollFlags::EPOLLOUT);
+        let hangup = Event::new(EpollFlags::EPOLLIN | EpollFlags::EPOLLRDHUP);
+
+        let first = EventDelta::between(none, read_write);
+        assert!(first.newly_readable() && first.newly_writable() && !first.newly_closed());
+
+        let write_back = EventDelta::between(read, read_write);
+        assert!(!write_back.newly_readable() && write_back.newly_writable());
+        assert_eq!(write_back.previous(), read);
+
+        let closed = EventDelta::between(read_write, hangup);
+        assert!(closed.newly_closed() && !closed.newly_readable() && !closed.newly_writable());
+        assert!(!EventDelta::between(hangup, hangup).newly_closed());
+    }
+
+    #[test]
+    fn batch_info_counts_the_remaining_events() {
+        let first = BatchInfo::new(0, 3, 7);
+        assert_eq!((first.index(), first.total(), first.sequence()), (0, 3, 7));
+        assert_eq!(first.remaining(), 2);
+        assert_eq!(BatchInfo::new(2, 3, 7).remaining(), 0);
+        assert_eq!(BatchInfo::default().remaining(), 0);
     }
 }
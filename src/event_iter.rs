@@ -0,0 +1,77 @@
+use std::iter::FusedIterator;
+use std::os::fd::RawFd;
+use std::pin::Pin;
+
+use crate::epoll::EpollEvent;
+use crate::thin::ThinBoxSubscriber;
+use crate::{Event, Eventp, Pinned};
+
+/// The ready events of one batch, returned by
+/// [`Eventp::wait_events`](crate::Eventp::wait_events), as `(fd, event)` pairs.
+///
+/// No handler is called. While the iterator is alive, the loop is in the same
+/// state as while a handler runs: the `run_*` methods panic, and subscribers
+/// deleted through [`eventp`](Self::eventp) are dropped at once but skipped by the
+/// iterator, their memory being released when it is dropped.
+///
+/// Events not yet yielded when the iterator is dropped are kept, as those beyond
+/// the budget of [`run_once_budgeted`](crate::Eventp::run_once_budgeted), and
+/// handed out first by the next `run_*` or `wait_events` call.
+pub struct EventIter<'a> {
+    pub(crate) eventp: &'a mut Eventp,
+    /// The events written by the kernel into the start of `eventp.event_buf`.
+    pub(crate) len: usize,
+    pub(crate) next: usize,
+}
+
+impl EventIter<'_> {
+    /// Returns the loop, e.g. to delete the fd of an event that was yielded.
+    pub fn eventp(&mut self) -> Pinned<'_, Eventp> {
+        // SAFETY: As for the handlers of the dispatch loop: `Pinned` does not
+        // re-expose anything that could move the loop.
+        Pinned(unsafe { Pin::new_unchecked(&mut *self.eventp) })
+    }
+
+    /// Returns the event at `index` in the batch with the fd of its subscriber,
+    /// unless the subscriber was deleted since.
+    fn live_event(&self, index: usize) -> Option<(RawFd, EpollEvent)> {
+        // SAFETY: The first `len` entries were written by `epoll_wait`.
+        let ev = unsafe { self.eventp.event_buf[index].assume_init_read() };
+        // SAFETY: The subscriber is in `eventp.registered`, or was deleted during
+        // the iteration and is kept allocated in `deferred_drop` until its end.
+        let subscriber = unsafe { ThinBoxSubscriber::<Eventp>::from_data(ev.data()) };
+        let live = subscriber.is_current(ev.data()) && subscriber.try_deref().is_some();
+        live.then(|| (subscriber.raw_fd(), ev))
+    }
+}
+
+impl Iterator for EventIter<'_> {
+    type Item = (RawFd, Event);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < self.len {
+            self.next += 1;
+            if let Some((fd, ev)) = self.live_event(self.next - 1) {
+                return Some((fd, Event::new(ev.events())));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.len - self.next))
+    }
+}
+
+impl FusedIterator for EventIter<'_> {}
+
+impl Drop for EventIter<'_> {
+    fn drop(&mut self) {
+        for index in self.next..self.len {
+            if let Some((_, ev)) = self.live_event(index) {
+                self.eventp.pending.push(ev);
+            }
+        }
+        self.eventp.end_abandoned_batch();
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use std::os::fd::RawFd;
+use std::time::Duration;
+
+use crate::Event;
+
+/// One dispatch recorded by [`Eventp::enable_event_log`](crate::Eventp::enable_event_log).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct LoggedEvent {
+    /// The position of the dispatch among those recorded since the log was
+    /// enabled, from zero. Not restarted by
+    /// [`clear_event_log`](crate::Eventp::clear_event_log).
+    pub seq: u64,
+
+    /// The fd the event was dispatched for.
+    pub fd: RawFd,
+
+    /// The [name](crate::thin::ThinBoxSubscriber::name) of its subscriber, if any.
+    pub name: Option<&'static str>,
+
+    /// The event, as reported by the kernel.
+    pub event: Event,
+
+    /// The time spent in the handler.
+    pub duration: Duration,
+}
+
+/// A ring of the latest dispatches, allocated once when enabled.
+pub(crate) struct EventLog {
+    entries: VecDeque<LoggedEvent>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl EventLog {
+    pub(crate) fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Event log capacity must be greater than zero");
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            next_seq: 0,
+        }
+    }
+
+    /// Records a dispatch, evicting the oldest one if full. Never allocates.
+    pub(crate) fn record(
+        &mut self,
+        fd: RawFd,
+        name: Option<&'static str>,
+        event: Event,
+        duration: Duration,
+    ) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LoggedEvent {
+            seq: self.next_seq,
+            fd,
+            name,
+            event,
+            duration,
+        });
+        self.next_seq += 1;
+    }
+
+    /// Returns the recorded dispatches, oldest first.
+    pub(crate) fn entries(&mut self) -> &[LoggedEvent] {
+        self.entries.make_contiguous()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoll::EpollFlags;
+
+    #[test]
+    fn keeps_the_latest_entries_in_order() {
+        let mut log = EventLog::new(3);
+        let event = Event::new(EpollFlags::EPOLLIN);
+        let allocated = log.entries.capacity();
+        for fd in 0..5 {
+            log.record(fd, None, event, Duration::ZERO);
+        }
+
+        let entries = log.entries();
+        assert_eq!(entries.iter().map(|e| e.fd).collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(entries.iter().map(|e| e.seq).collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(log.entries.capacity(), allocated);
+
+        log.clear();
+        assert!(log.entries().is_empty());
+        log.record(7, None, event, Duration::ZERO);
+        assert_eq!(log.entries()[0].seq, 5);
+    }
+}
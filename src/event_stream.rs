@@ -0,0 +1,253 @@
+//! A subscriber sending every event of its fd into a channel, for consumers
+//! outside the loop, such as a metrics thread or a test harness.
+//!
+//! [`event_stream()`] takes an fd with its interest, as built by [`interest()`],
+//! and returns the subscriber along with the [`Receiver`] of its events, which can
+//! be moved to any thread. The handler only sends the event: it never blocks the
+//! loop, and drops the event instead when the channel of [`with_capacity`] is full,
+//! or once the receiver is gone. [`Subscriber::dropped`] counts those.
+//!
+//! Nor does the handler read the fd. Registered level-triggered, an fd is reported
+//! again by every wait until it is read elsewhere, e.g. through a duplicate of it,
+//! and a hung up one until it is deleted. Edge-triggered, it is only reported when
+//! its readiness changes.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::os::fd::{AsFd, AsRawFd};
+//! use std::thread;
+//!
+//! use eventp::{event_stream, interest, Eventp, Subscriber};
+//! use nix::sys::eventfd::EventFd;
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut eventp = Eventp::default();
+//! let efd = EventFd::new()?;
+//! efd.write(1)?;
+//!
+//! let (subscriber, events) =
+//!     event_stream::with_capacity(interest().read().edge_triggered().with_fd(efd), 64);
+//! let fd = subscriber.as_fd().as_raw_fd();
+//! subscriber.register_into(&mut eventp)?;
+//!
+//! let consumer = thread::spawn(move || events.iter().next());
+//! eventp.run_once()?;
+//! assert!(consumer.join().unwrap().unwrap().is_readable());
+//!
+//! // The overflow counter, through the type of the subscriber.
+//! let dropped = eventp
+//!     .get(&fd)
+//!     .and_then(|s| s.downcast_ref::<event_stream::Subscriber<EventFd>>())
+//!     .map(|s| s.dropped());
+//! assert_eq!(dropped, Some(0));
+//! # Ok(()) }
+//! ```
+//!
+//! [`interest()`]: crate::interest()
+//! [`Receiver`]: mpsc::Receiver
+
+use std::cell::Cell;
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::{Event, EventpOps, Interest, Pinned};
+
+/// Creates a subscriber sending every event of `fd`, registered with `interest`,
+/// into an unbounded channel.
+///
+/// For more information, see the [mod-level documentation](self).
+pub fn event_stream<Fd: AsFd>((interest, fd): (Interest, Fd)) -> (Subscriber<Fd>, Receiver<Event>) {
+    let (tx, rx) = mpsc::channel();
+    (Subscriber::new(interest, fd, Tx::Unbounded(tx)), rx)
+}
+
+/// Same as [`event_stream()`], with a channel holding up to `capacity` events,
+/// beyond which the events are dropped.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero, which would drop every event not already waited
+/// for by the receiver.
+pub fn with_capacity<Fd: AsFd>(
+    (interest, fd): (Interest, Fd),
+    capacity: usize,
+) -> (Subscriber<Fd>, Receiver<Event>) {
+    assert!(capacity > 0, "capacity must be greater than zero");
+    let (tx, rx) = mpsc::sync_channel(capacity);
+    (Subscriber::new(interest, fd, Tx::Bounded(tx)), rx)
+}
+
+enum Tx {
+    Unbounded(Sender<Event>),
+    Bounded(SyncSender<Event>),
+}
+
+/// The subscriber created by [`event_stream()`] and [`with_capacity`].
+pub struct Subscriber<Fd> {
+    fd: Fd,
+    interest: Cell<Interest>,
+    tx: Tx,
+    dropped: u64,
+}
+
+impl<Fd> Subscriber<Fd> {
+    fn new(interest: Interest, fd: Fd, tx: Tx) -> Self {
+        Self {
+            fd,
+            interest: Cell::new(interest),
+            tx,
+            dropped: 0,
+        }
+    }
+
+    /// Returns how many events were dropped, because the channel was full, or the
+    /// receiver gone.
+    ///
+    /// Reached from the loop by the type of the subscriber, e.g. with
+    /// [`Eventp::get`](crate::Eventp::get) or
+    /// [`Pinned::with_subscriber_mut`](crate::Pinned::with_subscriber_mut), then
+    /// `downcast_ref::<event_stream::Subscriber<Fd>>()`.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns a reference to the fd.
+    pub fn get_ref(&self) -> &Fd {
+        &self.fd
+    }
+
+    /// Returns a mutable reference to the fd.
+    pub fn get_mut(&mut self) -> &mut Fd {
+        &mut self.fd
+    }
+}
+
+impl<Fd: AsFd> AsFd for Subscriber<Fd> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl<Fd> HasInterest for Subscriber<Fd> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep: EventpOps, Fd> Handler<Ep> for Subscriber<Fd> {
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // Never fails.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    /// Sends `event` into the channel, or counts it as dropped.
+    fn try_handle(&mut self, event: Event, _eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        let sent = match &self.tx {
+            Tx::Unbounded(tx) => tx.send(event).is_ok(),
+            Tx::Bounded(tx) => match tx.try_send(event) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+            },
+        };
+        if !sent {
+            self.dropped += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{interest, Eventp, Subscriber as _};
+
+    fn timeout() -> EpollTimeout {
+        EpollTimeout::from(500u16)
+    }
+
+    fn dropped(eventp: &Eventp, fd: RawFd) -> u64 {
+        eventp
+            .get(&fd)
+            .and_then(|s| s.downcast_ref::<Subscriber<OwnedFd>>())
+            .unwrap()
+            .dropped()
+    }
+
+    #[test]
+    fn receiver_sees_every_event_of_a_pipe() {
+        let mut eventp = Eventp::default();
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let mut reader = std::fs::File::from(read.try_clone().unwrap());
+        let mut writer = std::fs::File::from(write);
+        let fd = read.as_raw_fd();
+        let (subscriber, events) = event_stream(interest().read().with_fd(read));
+        subscriber.register_into(&mut eventp).unwrap();
+
+        writer.write_all(b"ab").unwrap();
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        // Not read by the handler, so still readable.
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        eventp.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        drop(writer);
+        eventp.run_once_with_timeout(timeout()).unwrap();
+
+        let all: Vec<_> = events.try_iter().collect();
+        assert_eq!(all.len(), 3);
+        assert!(all[..2].iter().all(|e| e.is_readable() && !e.is_hangup()));
+        assert!(all[2].is_hangup());
+        assert_eq!(dropped(&eventp, fd), 0);
+    }
+
+    #[test]
+    fn full_or_disconnected_channel_drops_events() {
+        let mut eventp = Eventp::default();
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let fd = read.as_raw_fd();
+        let (subscriber, events) = with_capacity(interest().read().with_fd(read), 2);
+        subscriber.register_into(&mut eventp).unwrap();
+
+        nix::unistd::write(&write, b"a").unwrap();
+        for _ in 0..3 {
+            eventp.run_once_with_timeout(timeout()).unwrap();
+        }
+        assert_eq!(events.try_iter().count(), 2);
+        assert_eq!(dropped(&eventp, fd), 1);
+
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        drop(events);
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        assert_eq!(dropped(&eventp, fd), 2);
+    }
+
+    #[test]
+    fn edge_triggered_stream_reports_each_new_readiness() {
+        let mut eventp = Eventp::default();
+        let (mut ours, theirs) = UnixStream::pair().unwrap();
+        theirs.set_nonblocking(true).unwrap();
+        let (subscriber, events) = event_stream(interest().read().edge_triggered().with_fd(theirs));
+        subscriber.register_into(&mut eventp).unwrap();
+
+        ours.write_all(b"a").unwrap();
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        eventp.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(events.try_iter().count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be greater than zero")]
+    fn zero_capacity_panics() {
+        let (read, _write) = nix::unistd::pipe().unwrap();
+        let _ = with_capacity(interest().read().with_fd(read), 0);
+    }
+}
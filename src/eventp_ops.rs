@@ -1,8 +1,9 @@
-use std::io;
-use std::os::fd::RawFd;
+use std::os::fd::{AsRawFd, RawFd};
+use std::time::Duration;
+use std::{fmt, io};
 
 use crate::thin::ThinBoxSubscriber;
-use crate::Interest;
+use crate::{BatchInfo, Deferred, EventDelta, Interest, Subscriber};
 
 /// A trait for types that can add subscribers, modify interests, and delete subscribers.
 ///
@@ -22,8 +23,109 @@ pub trait EventpOps: 'static + EventpOpsAdd<Self> + sealed::Sealed + Sized {
     #[doc = include_str!("../docs/eventp-ops.modify.md")]
     fn modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<()>;
 
+    /// Same as [`modify`](Self::modify), but skips the `epoll_ctl` call if `fd`
+    /// already has exactly `interest`, e.g. for handlers defensively re-enabling
+    /// their read interest. Returns whether the interest was modified.
+    ///
+    /// An interest with `EPOLLONESHOT` is always modified, as that is how its fd is
+    /// rearmed, even with the same flags. So is one with `EPOLLEXCLUSIVE`, which
+    /// fails as with `modify`.
+    ///
+    /// The default implementation always modifies, as does `UringEventp`, whose
+    /// polls are rearmed by modifying them. [`MockEventp`](crate::MockEventp)
+    /// records the call, for `expect_try_modify`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`modify`](Self::modify).
+    fn try_modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<bool> {
+        self.modify(fd, interest).map(|()| true)
+    }
+
     #[doc = include_str!("../docs/eventp-ops.delete.md")]
     fn delete(&mut self, fd: RawFd) -> io::Result<()>;
+
+    /// Queues `f` to run once the batch being dispatched is over: after every
+    /// handler has returned, and the subscribers deleted meanwhile are dropped. For
+    /// work done once for many events, e.g. flushing statistics.
+    ///
+    /// Closures run in the order they were queued, and may add and delete
+    /// subscribers. Queued outside of a batch, they run at the start of the next
+    /// `run_*` call, before it waits. Closures queued by deferred closures run in
+    /// the same go, down to [`MAX_DEFER_DEPTH`](crate::MAX_DEFER_DEPTH).
+    ///
+    /// Usually called as [`Pinned::defer`](crate::Pinned::defer), which boxes the
+    /// closure. [`MockEventp`](crate::MockEventp) records the call, for
+    /// `expect_defer`.
+    fn defer(&mut self, f: Deferred<Self>);
+
+    /// Returns the interest of the subscriber whose handler is running, as kept by
+    /// the loop, or `None` outside of a handler.
+    ///
+    /// [`MockEventp`](crate::MockEventp) always returns `None`.
+    fn current_interest(&self) -> Option<Interest> {
+        None
+    }
+
+    /// Returns how the event being dispatched differs from the previous one of its
+    /// subscriber, if registered with [`Interest::track_deltas`], or `None`.
+    ///
+    /// [`MockEventp`](crate::MockEventp) always returns `None`.
+    fn current_delta(&self) -> Option<EventDelta> {
+        None
+    }
+
+    /// Returns the time since the previous event dispatched to the subscriber whose
+    /// handler is running, if registered with [`Interest::track_last_event`], or
+    /// `None`, as for its first event.
+    ///
+    /// [`MockEventp`](crate::MockEventp) always returns `None`.
+    fn since_last_event(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns where the event being dispatched stands in its batch, or `None`
+    /// outside of a handler.
+    ///
+    /// [`MockEventp`](crate::MockEventp) always returns `None`, and so does
+    /// `UringEventp`, whose completions are not batches of events.
+    fn batch_info(&self) -> Option<BatchInfo> {
+        None
+    }
+
+    /// Returns the [user data](Interest::user_data) of the subscriber registered
+    /// with `fd`, 0 if it has none, or `None` if no subscriber is registered for it.
+    ///
+    /// [`MockEventp`](crate::MockEventp) always returns `None`.
+    fn user_data_of(&self, _fd: RawFd) -> Option<u64> {
+        None
+    }
+
+    /// Sets the [user data](Interest::user_data) of the subscriber registered with
+    /// `fd`, which its handler sees from its next call on, or at once if it is the
+    /// one running. Unlike [`modify`](Self::modify), the kernel is not involved,
+    /// and a `data` of 0 resets it: `modify` treats 0 as none, and keeps the user
+    /// data it finds.
+    ///
+    /// [`MockEventp`](crate::MockEventp) records the call, for
+    /// `expect_set_user_data`.
+    ///
+    /// # Errors
+    ///
+    /// - [`io::ErrorKind::NotFound`] if no subscriber is registered for `fd`, as an
+    ///   [`Error::Syscall`](crate::Error::Syscall) for `"set_user_data"` with
+    ///   [`Builder::error_context`](crate::Builder::error_context).
+    /// - [`Error::PendingRemoval`](crate::Error::PendingRemoval), of that kind too,
+    ///   if `fd` is that of the running handler, which deleted it.
+    fn set_user_data(&mut self, fd: RawFd, data: u64) -> io::Result<()>;
+
+    /// Deletes every fd of `fds`, carrying on past failures.
+    ///
+    /// Returns one result per fd, in order, each as [`delete`](Self::delete)
+    /// would have returned it.
+    fn delete_all(&mut self, fds: &[RawFd]) -> Vec<io::Result<()>> {
+        fds.iter().map(|&fd| self.delete(fd)).collect()
+    }
 }
 
 /// A helper trait that lets [`Subscriber::register_into`] accept both
@@ -37,14 +139,165 @@ pub trait EventpOps: 'static + EventpOpsAdd<Self> + sealed::Sealed + Sized {
 /// [`Pinned<'_, Ep>`]: crate::Pinned
 pub trait EventpOpsAdd<Ep: EventpOps>: sealed::Sealed {
     #[doc = include_str!("../docs/eventp-ops.add.md")]
-    fn add(&mut self, subscriber: ThinBoxSubscriber<Ep>) -> io::Result<()>;
+    fn add(&mut self, subscriber: ThinBoxSubscriber<Ep>) -> io::Result<()> {
+        self.try_add(subscriber).map_err(io::Error::from)
+    }
+
+    /// Same as [`add`](Self::add), but gives the subscriber back on failure, e.g.
+    /// to shut a connection down gracefully when it cannot be registered.
+    ///
+    /// # Errors
+    ///
+    /// An [`AddError`] carrying the error `add` would have returned.
+    ///
+    /// # Panics
+    ///
+    /// Same as [`add`](Self::add).
+    fn try_add(&mut self, subscriber: ThinBoxSubscriber<Ep>) -> Result<(), AddError<Ep>>;
+
+    /// Registers an already type-erased subscriber with `interest`, as produced by
+    /// plugin-style code that cannot name the concrete type.
+    ///
+    /// Equivalent to `self.add(ThinBoxSubscriber::from_box_dyn(subscriber, interest))`;
+    /// see [`from_box_dyn`](ThinBoxSubscriber::from_box_dyn) for how the value is moved.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`add`](Self::add).
+    fn add_boxed(
+        &mut self,
+        interest: Interest,
+        subscriber: Box<dyn Subscriber<Ep>>,
+    ) -> io::Result<()> {
+        self.add(ThinBoxSubscriber::from_box_dyn(subscriber, interest))
+    }
+
+    /// Registers every subscriber of `subscribers`, carrying on past failures,
+    /// e.g. for the connections accepted in one go by a listener's handler.
+    ///
+    /// Returns one result per subscriber, in order: its raw fd if it was added,
+    /// otherwise the error [`add`](Self::add) returned for it, in which case the
+    /// subscriber has been dropped.
+    ///
+    /// # Panics
+    ///
+    /// Same as [`add`](Self::add).
+    fn add_all<I>(&mut self, subscribers: I) -> Vec<io::Result<RawFd>>
+    where
+        I: IntoIterator<Item = ThinBoxSubscriber<Ep>>,
+    {
+        subscribers
+            .into_iter()
+            .map(|subscriber| {
+                let raw_fd = raw_fd_of(&subscriber);
+                self.add(subscriber).map(|()| raw_fd)
+            })
+            .collect()
+    }
+}
+
+/// The error of [`EventpOpsAdd::try_add`]: why the subscriber could not be
+/// registered, and the subscriber itself.
+///
+/// Converts into the bare [`io::Error`], dropping the subscriber.
+pub struct AddError<Ep: EventpOps> {
+    /// The error [`add`](EventpOpsAdd::add) would have returned.
+    pub error: io::Error,
+    /// The subscriber, not registered.
+    pub subscriber: ThinBoxSubscriber<Ep>,
+}
+
+impl<Ep: EventpOps> AddError<Ep> {
+    pub(crate) fn new(error: impl Into<io::Error>, subscriber: ThinBoxSubscriber<Ep>) -> Self {
+        Self {
+            error: error.into(),
+            subscriber,
+        }
+    }
+}
+
+impl<Ep: EventpOps> fmt::Debug for AddError<Ep> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AddError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Ep: EventpOps> fmt::Display for AddError<Ep> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<Ep: EventpOps> std::error::Error for AddError<Ep> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl<Ep: EventpOps> From<AddError<Ep>> for io::Error {
+    fn from(err: AddError<Ep>) -> Self {
+        err.error
+    }
+}
+
+pub(crate) fn raw_fd_of<Ep: EventpOps>(subscriber: &ThinBoxSubscriber<Ep>) -> RawFd {
+    match subscriber.try_deref() {
+        Some(s) => s.as_fd().as_raw_fd(),
+        None => panic!("Subscriber is already dropped"),
+    }
+}
+
+/// A helper trait that lets [`SubscriberHandle`] modify and delete through both
+/// `&mut Ep` and [`Pinned<'_, Ep>`], i.e. from outside and from inside a handler.
+///
+/// Its methods are only meant to be called by [`SubscriberHandle`]; use
+/// [`EventpOps`] or [`Pinned`] directly otherwise.
+///
+/// # Sealed
+///
+/// This trait is sealed and cannot be implemented for types outside of this crate.
+///
+/// [`SubscriberHandle`]: crate::subscriber::SubscriberHandle
+/// [`Pinned<'_, Ep>`]: crate::Pinned
+/// [`Pinned`]: crate::Pinned
+pub trait EventpOpsCtl<Ep: EventpOps>: sealed::Sealed {
+    #[doc(hidden)]
+    fn ctl_modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<()>;
+
+    #[doc(hidden)]
+    fn ctl_delete(&mut self, fd: RawFd) -> io::Result<()>;
+}
+
+impl<Ep: EventpOps> EventpOpsCtl<Ep> for Ep {
+    fn ctl_modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<()> {
+        self.modify(fd, interest)
+    }
+
+    fn ctl_delete(&mut self, fd: RawFd) -> io::Result<()> {
+        self.delete(fd)
+    }
+}
+
+impl<Ep: EventpOps> EventpOpsCtl<Ep> for crate::Pinned<'_, Ep> {
+    fn ctl_modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<()> {
+        self.modify(fd, interest)
+    }
+
+    fn ctl_delete(&mut self, fd: RawFd) -> io::Result<()> {
+        self.delete(fd)
+    }
 }
 
 pub(crate) mod sealed {
     pub trait Sealed {}
 
+    #[cfg(target_os = "linux")]
     impl Sealed for crate::Eventp {}
     impl<Ep: super::EventpOps> Sealed for crate::Pinned<'_, Ep> {}
     #[cfg(feature = "mock")]
     impl Sealed for crate::mock::MockEventp {}
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    impl Sealed for crate::uring::UringEventp {}
 }
@@ -0,0 +1,246 @@
+//! Registering one shared fd, such as a listening socket, with several event loops
+//! using `EPOLLEXCLUSIVE`, so the kernel wakes only one of them per event.
+//!
+//! Each loop needs its own subscriber, hence its own fd: [`register`] takes a fd
+//! already duplicated for one loop, and validates the interest before adding it.
+//! [`register_shared`] does the duplication, builds a subscriber per loop, and
+//! registers them all, rolling back on failure.
+//!
+//! An [`Eventp`] is not `Send`, so with one loop per thread, call [`register`] on
+//! each thread with a clone of the fd.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::net::TcpListener;
+//!
+//! use eventp::Eventp;
+//!
+//! # fn main() -> io::Result<()> {
+//! let listener = TcpListener::bind("127.0.0.1:0")?;
+//! listener.set_nonblocking(true)?;
+//!
+//! let mut loops = [Eventp::default(), Eventp::default()];
+//! let fds = eventp::exclusive::register_shared(
+//!     || listener.try_clone(),
+//!     eventp::interest().read(),
+//!     |_index| {
+//!         |listener: &mut TcpListener| {
+//!             // Another loop may have taken the connection first.
+//!             if let Ok((_stream, _addr)) = listener.accept() {}
+//!         }
+//!     },
+//!     &mut loops,
+//! )?;
+//! assert_eq!(fds.len(), 2);
+//! # Ok(()) }
+//! ```
+
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+
+use crate::tri_subscriber::{TriSubscriber, WithHandler};
+use crate::{Eventp, EventpOps, EventpOpsAdd, ExclusiveInterest, Interest, Subscriber};
+
+/// Adds `EPOLLEXCLUSIVE` to `interest`, after checking that the rest of it can be
+/// combined with it.
+///
+/// # Errors
+///
+/// [`Error::ExclusiveIncompatible`], as the payload of an
+/// [`io::ErrorKind::InvalidInput`] error, listing the offending flags, or another
+/// error of [`Interest::validate`].
+///
+/// [`Error::ExclusiveIncompatible`]: crate::Error::ExclusiveIncompatible
+pub fn validate(interest: Interest) -> io::Result<Interest> {
+    ExclusiveInterest::try_from(interest).map(Interest::from)
+}
+
+/// Registers `fd` with `handler` into one loop, as `interest` plus `EPOLLEXCLUSIVE`.
+///
+/// `fd` should be this loop's own duplicate of the shared fd, e.g. from
+/// [`TcpListener::try_clone`](std::net::TcpListener::try_clone). Returns its raw
+/// fd, to [`delete`](EventpOps::delete) it later.
+///
+/// # Errors
+///
+/// See [`validate`] and [`EventpOpsAdd::add`].
+pub fn register<Ep, R, Fd, Args, F>(
+    fd: Fd,
+    interest: Interest,
+    handler: F,
+    eventp: &mut R,
+) -> io::Result<RawFd>
+where
+    Ep: EventpOps,
+    R: EventpOpsAdd<Ep>,
+    Fd: AsFd,
+    TriSubscriber<Fd, Args, F>: Subscriber<Ep>,
+{
+    let interest = validate(interest)?;
+    let raw_fd = fd.as_fd().as_raw_fd();
+    interest
+        .with_fd(fd)
+        .with_handler(handler)
+        .register_into(eventp)?;
+    Ok(raw_fd)
+}
+
+/// Registers a duplicate of one shared fd into every loop in `loops`, each with
+/// `interest` plus `EPOLLEXCLUSIVE`.
+///
+/// `fd_factory` is called once per loop to produce its duplicate, typically a
+/// `try_clone` of the shared fd, and `handler_factory` with the index of the loop
+/// to produce its handler. Returns the raw fd registered into each loop, in the
+/// order of `loops`.
+///
+/// If anything fails, the fds already registered are deleted again, and the
+/// first error is returned.
+///
+/// # Errors
+///
+/// See [`register`]. The interest is validated before any fd is duplicated.
+pub fn register_shared<Fd, Args, F>(
+    mut fd_factory: impl FnMut() -> io::Result<Fd>,
+    interest: Interest,
+    mut handler_factory: impl FnMut(usize) -> F,
+    loops: &mut [Eventp],
+) -> io::Result<Vec<RawFd>>
+where
+    Fd: AsFd,
+    TriSubscriber<Fd, Args, F>: Subscriber<Eventp>,
+{
+    let interest = validate(interest)?;
+
+    let mut fds = Vec::with_capacity(loops.len());
+    for index in 0..loops.len() {
+        let result = fd_factory()
+            .and_then(|fd| register(fd, interest, handler_factory(index), &mut loops[index]));
+        match result {
+            Ok(raw_fd) => fds.push(raw_fd),
+            Err(err) => {
+                for (eventp, raw_fd) in loops.iter_mut().zip(fds) {
+                    let _ = eventp.delete(raw_fd);
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(fds)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::net::{TcpListener, TcpStream};
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::epoll::{EpollFlags, EpollTimeout};
+    use crate::{interest, Error};
+
+    fn listener() -> TcpListener {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        listener
+    }
+
+    #[test]
+    fn validate_adds_exclusive() {
+        let interest = validate(interest().read().edge_triggered()).unwrap();
+        assert!(interest.bitflags().contains(EpollFlags::EPOLLEXCLUSIVE));
+        assert_eq!(validate(interest).unwrap(), interest);
+    }
+
+    #[test]
+    fn validate_rejects_incompatible_flags() {
+        let err = validate(interest().read().oneshot().read_hangup()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::ExclusiveIncompatible {
+                flags: EpollFlags::EPOLLONESHOT | EpollFlags::EPOLLRDHUP,
+            })
+        );
+    }
+
+    #[test]
+    fn register_shared_dispatches_each_connection_once() {
+        let listener = listener();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Rc::new(Cell::new(0));
+
+        let mut loops = [Eventp::default(), Eventp::default(), Eventp::default()];
+        let fds = register_shared(
+            || listener.try_clone(),
+            interest().read(),
+            |_| {
+                let accepted = accepted.clone();
+                move |listener: &mut TcpListener| {
+                    if listener.accept().is_ok() {
+                        accepted.set(accepted.get() + 1);
+                    }
+                }
+            },
+            &mut loops,
+        )
+        .unwrap();
+        assert_eq!(fds.len(), 3);
+        for (eventp, fd) in loops.iter().zip(&fds) {
+            assert!(eventp
+                .interest(fd)
+                .unwrap()
+                .bitflags()
+                .contains(EpollFlags::EPOLLEXCLUSIVE));
+        }
+
+        let _client = TcpStream::connect(addr).unwrap();
+        loops[0]
+            .run_once_with_timeout(EpollTimeout::from(500u16))
+            .unwrap();
+        for eventp in &mut loops[1..] {
+            eventp.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        }
+        assert_eq!(accepted.get(), 1);
+    }
+
+    #[test]
+    fn register_shared_rolls_back_on_failure() {
+        let listener = listener();
+        let mut clones = 0;
+        let mut loops = [Eventp::default(), Eventp::default()];
+
+        let err = register_shared(
+            || {
+                clones += 1;
+                if clones == 2 {
+                    return Err(io::Error::from(io::ErrorKind::OutOfMemory));
+                }
+                listener.try_clone()
+            },
+            interest().read(),
+            |_| |_: &mut TcpListener| {},
+            &mut loops,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+        assert!(loops.iter().all(|eventp| eventp.registered.is_empty()));
+    }
+
+    #[test]
+    fn register_shared_validates_before_cloning() {
+        let mut loops = [Eventp::default()];
+        let err = register_shared(
+            || -> io::Result<TcpListener> { unreachable!() },
+            interest().read().priority(),
+            |_| |_: &mut TcpListener| {},
+            &mut loops,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            Error::from_io(&err),
+            Some(Error::ExclusiveIncompatible { .. })
+        ));
+    }
+}
@@ -0,0 +1,275 @@
+use std::io;
+use std::os::fd::RawFd;
+
+use crate::thin::ThinBoxSubscriber;
+use crate::{eventp_ops, Eventp, EventpOps, EventpOpsAdd};
+
+/// Makes the subscribers of a plugin, for [`Eventp::register_all`] to add them to
+/// a loop the plugin does not know the type of.
+///
+/// A factory is a trait object, so it builds type-erased subscribers, e.g. with
+/// [`Interest::with_fd`](crate::Interest::with_fd) and
+/// [`with_handler`](crate::tri_subscriber::WithHandler::with_handler), turned into
+/// a [`ThinBoxSubscriber`] with `into()`. Closures returning them are factories
+/// too.
+///
+/// ```rust
+/// # use std::io;
+/// use eventp::thin::ThinBoxSubscriber;
+/// use eventp::tri_subscriber::WithHandler;
+/// use eventp::{EventpOps, SubscriberFactory};
+/// use nix::sys::eventfd::EventFd;
+///
+/// struct Ticker;
+///
+/// impl<Ep: EventpOps> SubscriberFactory<Ep> for Ticker {
+///     fn build(self: Box<Self>) -> io::Result<Vec<ThinBoxSubscriber<Ep>>> {
+///         let ticker = eventp::interest()
+///             .read()
+///             .with_fd(EventFd::new()?)
+///             .with_handler(|efd: &mut EventFd| {
+///                 let _ = efd.read();
+///             })
+///             .into();
+///         Ok(vec![ticker])
+///     }
+/// }
+/// ```
+pub trait SubscriberFactory<Ep: EventpOps> {
+    /// Makes the subscribers, to be added in order.
+    ///
+    /// # Errors
+    ///
+    /// Whatever kept the plugin from making them, which fails the factory.
+    fn build(self: Box<Self>) -> io::Result<Vec<ThinBoxSubscriber<Ep>>>;
+}
+
+impl<Ep, F> SubscriberFactory<Ep> for F
+where
+    Ep: EventpOps,
+    F: FnOnce() -> io::Result<Vec<ThinBoxSubscriber<Ep>>>,
+{
+    fn build(self: Box<Self>) -> io::Result<Vec<ThinBoxSubscriber<Ep>>> {
+        self()
+    }
+}
+
+/// What [`Eventp::register_all`] does once a factory fails.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum RegisterPolicy {
+    /// Carries on with the next factories, keeping the subscribers of the others.
+    #[default]
+    BestEffort,
+
+    /// Deletes the subscribers of every factory added so far, drops the factories
+    /// left without building them, and returns the error.
+    AllOrNothing,
+}
+
+/// What [`Eventp::register_all`] registered, factory by factory.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct RegisteredReport {
+    /// One result per factory, in order: the raw fds of its subscribers if they
+    /// were all added, or the error of the factory otherwise.
+    pub factories: Vec<io::Result<Vec<RawFd>>>,
+}
+
+impl RegisteredReport {
+    /// Returns the raw fds of every subscriber added, factory by factory.
+    pub fn fds(&self) -> impl Iterator<Item = RawFd> + '_ {
+        self.factories.iter().flatten().flatten().copied()
+    }
+
+    /// Returns the index of every factory that failed, with its error.
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &io::Error)> {
+        self.factories
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| Some((index, result.as_ref().err()?)))
+    }
+
+    /// Returns `true` if no factory failed.
+    pub fn is_complete(&self) -> bool {
+        self.factories.iter().all(Result::is_ok)
+    }
+}
+
+impl Eventp {
+    /// Builds every factory in order, and adds its subscribers, e.g. for the
+    /// plugins loaded at startup.
+    ///
+    /// A factory fails if [`build`](SubscriberFactory::build) fails, or if
+    /// [`add`](EventpOpsAdd::add) fails for one of its subscribers, in which case
+    /// those of its subscribers already added are deleted, and the rest dropped.
+    /// So a plugin is either fully registered or not at all. With
+    /// [`RegisterPolicy::AllOrNothing`], so are the factories together.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::thin::ThinBoxSubscriber;
+    /// use eventp::{Eventp, RegisterPolicy, SubscriberFactory};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let plugins: Vec<Box<dyn SubscriberFactory<Eventp>>> = vec![
+    ///     Box::new(|| Ok(Vec::<ThinBoxSubscriber<Eventp>>::new())),
+    ///     Box::new(|| -> io::Result<Vec<ThinBoxSubscriber<Eventp>>> {
+    ///         Err(io::Error::new(io::ErrorKind::NotFound, "no such device"))
+    ///     }),
+    /// ];
+    /// let mut eventp = Eventp::default();
+    /// let report = eventp.register_all(plugins, RegisterPolicy::BestEffort)?;
+    /// assert_eq!(report.failures().map(|(index, _)| index).collect::<Vec<_>>(), [1]);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// With [`RegisterPolicy::AllOrNothing`], the error of the first factory that
+    /// failed, once the subscribers of the others are deleted. Never otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Same as [`add`](EventpOpsAdd::add).
+    pub fn register_all(
+        &mut self,
+        factories: Vec<Box<dyn SubscriberFactory<Eventp>>>,
+        policy: RegisterPolicy,
+    ) -> io::Result<RegisteredReport> {
+        let mut report = RegisteredReport::default();
+        for factory in factories {
+            match self.register_factory(factory) {
+                Err(e) if policy == RegisterPolicy::AllOrNothing => {
+                    for fd in report.fds() {
+                        self.roll_back(fd);
+                    }
+                    return Err(e);
+                }
+                result => report.factories.push(result),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Adds the subscribers of `factory`, or none of them.
+    fn register_factory(
+        &mut self,
+        factory: Box<dyn SubscriberFactory<Eventp>>,
+    ) -> io::Result<Vec<RawFd>> {
+        let subscribers = factory.build()?;
+        let mut fds = Vec::with_capacity(subscribers.len());
+        for subscriber in subscribers {
+            let fd = eventp_ops::raw_fd_of(&subscriber);
+            if let Err(e) = self.add(subscriber) {
+                for &fd in &fds {
+                    self.roll_back(fd);
+                }
+                return Err(e);
+            }
+            fds.push(fd);
+        }
+        Ok(fds)
+    }
+
+    fn roll_back(&mut self, fd: RawFd) {
+        // Added moments ago, so only refused by the kernel, with nowhere better for
+        // the error to go.
+        let _ = self.delete(fd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::sys::eventfd::EventFd;
+
+    use super::*;
+    use crate::tri_subscriber::WithHandler;
+    use crate::{Error, Subscriber};
+
+    fn ticker() -> ThinBoxSubscriber<Eventp> {
+        crate::interest()
+            .read()
+            .with_fd(EventFd::new().unwrap())
+            .with_handler(|efd: &mut EventFd| {
+                let _ = efd.read();
+            })
+            .into()
+    }
+
+    /// A subscriber `add` refuses, with [`Error::NegativeFd`].
+    fn refused() -> ThinBoxSubscriber<Eventp> {
+        // SAFETY: The fd is never used, as `add` refuses it.
+        unsafe { crate::interest().read().with_raw_fd(-2) }
+            .with_handler(|| {})
+            .into()
+    }
+
+    fn plugin(
+        make: fn() -> io::Result<Vec<ThinBoxSubscriber<Eventp>>>,
+    ) -> Box<dyn SubscriberFactory<Eventp>> {
+        Box::new(make)
+    }
+
+    fn unavailable() -> io::Result<Vec<ThinBoxSubscriber<Eventp>>> {
+        Err(io::Error::new(io::ErrorKind::NotFound, "no such device"))
+    }
+
+    #[test]
+    fn best_effort_keeps_the_factories_that_succeed() {
+        let mut ep = Eventp::default();
+        let factories = vec![
+            plugin(|| Ok(vec![ticker(), ticker()])),
+            plugin(unavailable),
+            plugin(|| Ok(vec![ticker(), refused(), ticker()])),
+            plugin(|| Ok(vec![ticker()])),
+        ];
+
+        let report = ep
+            .register_all(factories, RegisterPolicy::BestEffort)
+            .unwrap();
+        assert!(!report.is_complete());
+        let failures: Vec<_> = report.failures().map(|(index, _)| index).collect();
+        assert_eq!(failures, [1, 2]);
+        let err = report.factories[2].as_ref().unwrap_err();
+        assert_eq!(Error::from_io(err), Some(&Error::NegativeFd { fd: -2 }));
+
+        // The first ticker of the third factory was deleted along with it.
+        let fds: Vec<_> = report.fds().collect();
+        assert_eq!(fds.len(), 3);
+        assert_eq!(ep.len(), 3);
+        assert!(fds.iter().all(|&fd| ep.contains(fd)));
+    }
+
+    #[test]
+    fn all_or_nothing_rolls_back_every_factory() {
+        let mut ep = Eventp::default();
+        let kept = EventFd::new().unwrap();
+        crate::interest()
+            .read()
+            .with_fd(kept)
+            .with_handler(|_: &mut EventFd| {})
+            .register_into(&mut ep)
+            .unwrap();
+        let factories = vec![
+            plugin(|| Ok(vec![ticker(), ticker()])),
+            plugin(|| Ok(vec![ticker(), refused()])),
+            plugin(|| panic!("built after a failure")),
+        ];
+
+        let err = ep
+            .register_all(factories, RegisterPolicy::AllOrNothing)
+            .unwrap_err();
+        assert_eq!(Error::from_io(&err), Some(&Error::NegativeFd { fd: -2 }));
+        // Only what was registered before is left.
+        assert_eq!(ep.len(), 1);
+
+        let factories = vec![plugin(|| Ok(vec![ticker()])), plugin(|| Ok(vec![]))];
+        let report = ep
+            .register_all(factories, RegisterPolicy::AllOrNothing)
+            .unwrap();
+        assert!(report.is_complete());
+        let fd = report.fds().next().unwrap();
+        assert!(ep.contains(fd));
+        assert!(report.factories[1].as_ref().unwrap().is_empty());
+    }
+}
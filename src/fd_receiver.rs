@@ -0,0 +1,354 @@
+//! A subscriber receiving fds over a unix socket, with `SCM_RIGHTS`.
+//!
+//! [`fd_receiver()`] wraps the local end of a connected [`UnixStream`]. When it is
+//! readable, the subscriber calls `recvmsg` until it would block, extracts every
+//! fd of every message, and calls the handler once per fd, in the order they were
+//! sent. The payload bytes carrying the fds are discarded. Received fds are
+//! close-on-exec.
+//!
+//! Once the peer closes its end, the subscriber deletes itself from the loop.
+//!
+//! # Truncation
+//!
+//! A message can carry at most [`max_fds`](FdReceiver::max_fds) fds, 253 by
+//! default, the kernel's own limit. The kernel closes the fds of a message that do
+//! not fit and flags it with `MSG_CTRUNC`; the fds that did fit are still handed
+//! out, and the subscriber then reports [`io::ErrorKind::InvalidData`] to the
+//! loop's [`ErrorPolicy`](crate::ErrorPolicy).
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::net::TcpStream;
+//! use std::os::fd::OwnedFd;
+//! use std::os::unix::net::UnixStream;
+//!
+//! use eventp::{fd_receiver, Eventp, EventpOps, Pinned, Subscriber};
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut eventp = Eventp::default();
+//! let (_control_plane, local) = UnixStream::pair()?;
+//!
+//! fd_receiver(local)
+//!     .with_handler(|fd: OwnedFd, _eventp: Pinned<'_, Eventp>| -> io::Result<()> {
+//!         let stream = TcpStream::from(fd);
+//!         stream.set_nonblocking(true)?;
+//!         // ... register it ...
+//!         Ok(())
+//!     })
+//!     .register_into(&mut eventp)?;
+//! # Ok(()) }
+//! ```
+//!
+//! See [examples/fd-passing-echo-server.rs](https://github.com/FuuuOverclocking/eventp/blob/main/examples/fd-passing-echo-server.rs)
+//! for a complete program.
+
+use std::cell::Cell;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::{io, mem, ptr};
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::tri_subscriber::HandlerReturn;
+use crate::{interest, Event, EventpOps, Interest, Pinned};
+
+/// The most fds one `sendmsg` can pass, `SCM_MAX_FD` in the kernel.
+const SCM_MAX_FD: usize = 253;
+
+/// Creates an [`FdReceiver`] over the local end of a unix stream.
+///
+/// For more information, see the [mod-level documentation](self).
+pub fn fd_receiver(stream: UnixStream) -> FdReceiver {
+    FdReceiver {
+        stream,
+        max_fds: SCM_MAX_FD,
+    }
+}
+
+/// A not yet complete fd receiver, waiting for its handler.
+pub struct FdReceiver {
+    stream: UnixStream,
+    max_fds: usize,
+}
+
+impl FdReceiver {
+    /// Sets the most fds a single message can carry, see
+    /// [Truncation](self#truncation).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_fds` is zero.
+    pub fn max_fds(mut self, max_fds: usize) -> Self {
+        assert!(max_fds > 0, "max_fds must be greater than zero");
+        self.max_fds = max_fds;
+        self
+    }
+
+    /// Completes the receiver with the handler called for every received fd.
+    ///
+    /// The handler returns either `()` or `io::Result<()>`. After an error, the fds
+    /// already received are still handed out, and the remaining messages are left
+    /// for the next time the loop polls.
+    pub fn with_handler<F>(self, handler: F) -> Subscriber<F> {
+        // SAFETY: `CMSG_SPACE` only computes a size.
+        let space = unsafe { libc::CMSG_SPACE((self.max_fds * mem::size_of::<RawFd>()) as _) };
+        Subscriber {
+            stream: self.stream,
+            interest: Cell::new(interest().read()),
+            // `u64`s, so the buffer is aligned for `cmsghdr`.
+            cmsg_buf: vec![0; (space as usize + 7) / 8],
+            handler,
+        }
+    }
+}
+
+/// The subscriber created by [`FdReceiver::with_handler`].
+pub struct Subscriber<F> {
+    stream: UnixStream,
+    interest: Cell<Interest>,
+    cmsg_buf: Vec<u64>,
+    handler: F,
+}
+
+impl<F> Subscriber<F> {
+    /// Returns a reference to the unix stream.
+    pub fn get_ref(&self) -> &UnixStream {
+        &self.stream
+    }
+}
+
+impl<F> AsFd for Subscriber<F> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+impl<F> HasInterest for Subscriber<F> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, F, R> Handler<Ep> for Subscriber<F>
+where
+    Ep: EventpOps,
+    F: FnMut(OwnedFd, Pinned<'_, Ep>) -> R,
+    R: HandlerReturn,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        let mut fds = Vec::new();
+        loop {
+            let received = match recv_fds(self.stream.as_fd(), &mut self.cmsg_buf, &mut fds) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            let mut result = Ok(());
+            for fd in fds.drain(..) {
+                let r = (self.handler)(fd, eventp.as_mut()).into_result();
+                result = result.and(r);
+            }
+            match received {
+                Received::Message { truncated: false } => result?,
+                Received::Message { truncated: true } => {
+                    result?;
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "control message truncated, some received fds were closed",
+                    ));
+                }
+                Received::Eof => return eventp.delete(self.stream.as_raw_fd()),
+            }
+        }
+    }
+}
+
+enum Received {
+    Message { truncated: bool },
+    Eof,
+}
+
+/// Receives one message without blocking, and appends the fds it carries to `fds`.
+fn recv_fds(
+    stream: BorrowedFd<'_>,
+    cmsg_buf: &mut [u64],
+    fds: &mut Vec<OwnedFd>,
+) -> io::Result<Received> {
+    // Senders over a stream socket must send at least one byte with the fds.
+    let mut data = [0u8; 64];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    // SAFETY: `msghdr` is a plain C struct, for which all zeroes is a valid value.
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr().cast();
+    msg.msg_controllen = mem::size_of_val(cmsg_buf) as _;
+
+    // SAFETY: `msg` points to `iov` and `cmsg_buf`, which are valid for writes of the
+    // lengths given, for the duration of the call.
+    let n = unsafe {
+        libc::recvmsg(
+            stream.as_raw_fd(),
+            &mut msg,
+            libc::MSG_DONTWAIT | libc::MSG_CMSG_CLOEXEC,
+        )
+    };
+    if n == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let before = fds.len();
+    // SAFETY: The kernel set `msg_controllen` to the length of the control messages
+    // it wrote into `cmsg_buf`, so the `CMSG_*` macros only walk initialized
+    // headers within it.
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg);
+                let len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+                for i in 0..len / mem::size_of::<RawFd>() {
+                    let fd = ptr::read_unaligned(data.cast::<RawFd>().add(i));
+                    // The kernel installed `fd` for us, nothing else owns it.
+                    fds.push(OwnedFd::from_raw_fd(fd));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    if n == 0 && fds.len() == before {
+        return Ok(Received::Eof);
+    }
+    Ok(Received::Message {
+        truncated: msg.msg_flags & libc::MSG_CTRUNC != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use nix::sys::epoll::EpollTimeout;
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use super::*;
+    use crate::{Eventp, Subscriber as _};
+
+    /// Sends `fds` in one message, with a one-byte payload.
+    fn send_fds(stream: &UnixStream, fds: &[BorrowedFd<'_>]) {
+        let raw: Vec<RawFd> = fds.iter().map(|fd| fd.as_raw_fd()).collect();
+        let len = mem::size_of_val(&raw[..]);
+        // SAFETY: Only sizes are computed.
+        let space = unsafe { libc::CMSG_SPACE(len as _) } as usize;
+        let mut cmsg_buf = vec![0u64; (space + 7) / 8];
+
+        let mut byte = [0u8];
+        let mut iov = libc::iovec {
+            iov_base: byte.as_mut_ptr().cast(),
+            iov_len: 1,
+        };
+        // SAFETY: As in `recv_fds`; the control message is written within
+        // `cmsg_buf`, which is large enough for `len` bytes of data.
+        let n = unsafe {
+            let mut msg: libc::msghdr = mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = cmsg_buf.as_mut_ptr().cast();
+            msg.msg_controllen = space as _;
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(len as _) as _;
+            ptr::copy_nonoverlapping(raw.as_ptr(), libc::CMSG_DATA(cmsg).cast(), raw.len());
+            libc::sendmsg(stream.as_raw_fd(), &msg, 0)
+        };
+        assert_eq!(n, 1, "sendmsg: {}", io::Error::last_os_error());
+    }
+
+    /// Eventfds holding `1..=count`, to tell the received fds apart.
+    fn counters(count: u64) -> Vec<EventFd> {
+        (1..=count)
+            .map(|value| {
+                EventFd::from_value_and_flags(value as u32, EfdFlags::EFD_CLOEXEC).unwrap()
+            })
+            .collect()
+    }
+
+    fn register_collecting(
+        eventp: &mut Eventp,
+        receiver: FdReceiver,
+    ) -> (RawFd, Rc<RefCell<Vec<u64>>>) {
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let r = received.clone();
+        let subscriber = receiver.with_handler(move |fd: OwnedFd, _: Pinned<'_, Eventp>| {
+            // SAFETY: The test only sends eventfds.
+            let efd = unsafe { EventFd::from_owned_fd(fd) };
+            r.borrow_mut().push(efd.read().unwrap());
+        });
+        let raw = subscriber.as_fd().as_raw_fd();
+        subscriber.register_into(eventp).unwrap();
+        (raw, received)
+    }
+
+    fn poll_timeout() -> EpollTimeout {
+        EpollTimeout::from(500u16)
+    }
+
+    #[test]
+    fn handler_is_called_once_per_fd_in_order() {
+        let mut eventp = Eventp::default();
+        let (peer, local) = UnixStream::pair().unwrap();
+        let (_, received) = register_collecting(&mut eventp, fd_receiver(local));
+
+        let efds = counters(4);
+        let fds: Vec<_> = efds.iter().map(|efd| efd.as_fd()).collect();
+        send_fds(&peer, &fds[..3]);
+        send_fds(&peer, &fds[3..]);
+
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(*received.borrow(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn truncated_message_hands_out_what_fit_and_reports_it() {
+        let mut eventp = Eventp::default();
+        let (peer, local) = UnixStream::pair().unwrap();
+        let (_, received) = register_collecting(&mut eventp, fd_receiver(local).max_fds(2));
+
+        let efds = counters(3);
+        let fds: Vec<_> = efds.iter().map(|efd| efd.as_fd()).collect();
+        send_fds(&peer, &fds);
+
+        let err = eventp.run_once_with_timeout(poll_timeout()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(*received.borrow(), [1, 2]);
+    }
+
+    #[test]
+    fn subscriber_deletes_itself_when_the_peer_closes() {
+        let mut eventp = Eventp::default();
+        let (peer, local) = UnixStream::pair().unwrap();
+        let (raw, received) = register_collecting(&mut eventp, fd_receiver(local));
+
+        let efds = counters(1);
+        send_fds(&peer, &[efds[0].as_fd()]);
+        drop(peer);
+
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(*received.borrow(), [1]);
+        assert!(eventp.get(&raw).is_none());
+    }
+}
@@ -0,0 +1,124 @@
+use std::io;
+use std::os::fd::RawFd;
+
+use crate::thin::ThinBoxSubscriber;
+use crate::{Builder, Eventp, EventpOpsAdd, Interest};
+
+/// The registrations of an `Eventp`, taken before a `fork(2)` by
+/// [`Eventp::prepare_fork`], to register them again in a loop of the child.
+///
+/// The child inherits the fds of the parent, and its epoll fd, but not a loop it
+/// can use: that epoll shares its interest list with the parent, so registering
+/// or deleting through it changes the loop of the parent too. The child instead
+/// rebuilds a loop of its own with [`rebuild_in_child`](Self::rebuild_in_child),
+/// whose factory makes a new subscriber for every fd.
+///
+/// The inherited `Eventp` is then to be [forgotten](std::mem::forget) by the
+/// child rather than dropped, as dropping it closes the fds of its subscribers,
+/// which the rebuilt subscribers use. In debug builds, running or changing it from
+/// the child panics.
+///
+/// ```rust,no_run
+/// # use std::io;
+/// use std::mem;
+/// use std::os::fd::{FromRawFd, OwnedFd};
+///
+/// use eventp::tri_subscriber::WithHandler;
+/// use eventp::Eventp;
+/// use nix::unistd::{fork, ForkResult};
+///
+/// # fn main() -> io::Result<()> {
+/// let eventp = Eventp::default();
+/// // Register the listeners here, before forking the workers.
+/// let blueprint = eventp.prepare_fork();
+/// match unsafe { fork() }? {
+///     ForkResult::Parent { .. } => { /* Keep running `eventp`. */ }
+///     ForkResult::Child => {
+///         mem::forget(eventp);
+///         let mut eventp = blueprint.rebuild_in_child(|fd, interest| {
+///             // SAFETY: The inherited `Eventp` is forgotten, so nothing else
+///             // closes the fd.
+///             let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+///             interest
+///                 .with_fd(fd)
+///                 .with_handler(|_fd: &mut OwnedFd| {})
+///                 .into()
+///         })?;
+///         eventp.run_forever()?;
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ForkBlueprint {
+    capacity: usize,
+    registrations: Vec<(RawFd, Interest, Option<&'static str>)>,
+    /// The inherited loop, whose claims on the fds the rebuilt one takes over.
+    #[cfg(feature = "debug-ownership")]
+    owner: u64,
+}
+
+impl ForkBlueprint {
+    pub(crate) fn new(
+        capacity: usize,
+        mut registrations: Vec<(RawFd, Interest, Option<&'static str>)>,
+        #[cfg(feature = "debug-ownership")] owner: u64,
+    ) -> Self {
+        registrations.sort_unstable_by_key(|&(fd, ..)| fd);
+        Self {
+            capacity,
+            registrations,
+            #[cfg(feature = "debug-ownership")]
+            owner,
+        }
+    }
+
+    /// Returns every registered fd, with its interest and the
+    /// [name](ThinBoxSubscriber::name) of its subscriber, in the order of the fds.
+    pub fn registrations(&self) -> &[(RawFd, Interest, Option<&'static str>)] {
+        &self.registrations
+    }
+
+    /// Creates a new `Eventp`, with the event buffer capacity of the parent, and
+    /// adds the subscriber `factory` makes with every fd and its interest.
+    ///
+    /// A subscriber without a name is given that of the one it replaces. See
+    /// [`rebuild_in_child_with`](Self::rebuild_in_child_with) to configure the loop.
+    ///
+    /// # Errors
+    ///
+    /// - The error of creating the `Eventp`, see [`Eventp::new`].
+    /// - The first error of adding a subscriber, see
+    ///   [`add`](EventpOpsAdd::add). The loop is dropped, with the subscribers
+    ///   added so far.
+    pub fn rebuild_in_child<F>(self, factory: F) -> io::Result<Eventp>
+    where
+        F: FnMut(RawFd, Interest) -> ThinBoxSubscriber<Eventp>,
+    {
+        let builder = Eventp::builder().capacity(self.capacity);
+        self.rebuild_in_child_with(builder, factory)
+    }
+
+    /// Same as [`rebuild_in_child`](Self::rebuild_in_child), but creates the loop
+    /// with `builder`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`rebuild_in_child`](Self::rebuild_in_child).
+    pub fn rebuild_in_child_with<F>(self, builder: Builder, mut factory: F) -> io::Result<Eventp>
+    where
+        F: FnMut(RawFd, Interest) -> ThinBoxSubscriber<Eventp>,
+    {
+        let mut eventp = builder.build()?;
+        #[cfg(feature = "debug-ownership")]
+        crate::ownership::release_all(self.owner);
+        for (fd, interest, name) in self.registrations {
+            let mut subscriber = factory(fd, interest);
+            if subscriber.name().is_none() {
+                subscriber.set_name(name);
+            }
+            eventp.add(subscriber)?;
+        }
+        Ok(eventp)
+    }
+}
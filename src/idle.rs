@@ -0,0 +1,106 @@
+//! The timers of [`Interest::idle_timeout`](crate::Interest::idle_timeout).
+//!
+//! Deadlines are milliseconds since the creation of the loop. The one of a
+//! subscriber lives in its [`ThinBoxSubscriber`](crate::thin::ThinBoxSubscriber)
+//! header, and is pushed back by every event dispatched to it. The heap holds one
+//! entry per subscriber with a timeout, which is only refreshed from the header
+//! when it comes due, so dispatching an event never touches the heap.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::os::fd::RawFd;
+use std::time::Instant;
+
+use crate::epoll::EpollTimeout;
+
+pub(crate) struct IdleTimers {
+    epoch: Instant,
+    /// `(deadline, fd)`, earliest first. Empty, and unallocated, until a subscriber
+    /// with a timeout is added.
+    heap: BinaryHeap<Reverse<(u64, RawFd)>>,
+}
+
+impl IdleTimers {
+    pub(crate) fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Returns the current time, in the unit of deadlines.
+    pub(crate) fn now(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+
+    /// Adds the entry of `fd`, which must not have one yet.
+    pub(crate) fn schedule(&mut self, fd: RawFd, deadline: u64) {
+        self.heap.push(Reverse((deadline, fd)));
+    }
+
+    /// Removes the entry of `fd`, if any. Linear in the number of entries.
+    pub(crate) fn cancel(&mut self, fd: RawFd) {
+        self.heap.retain(|&Reverse((_, entry))| entry != fd);
+    }
+
+    /// Shortens `timeout` so that the wait ends by the earliest deadline.
+    pub(crate) fn clamp(&self, timeout: EpollTimeout) -> EpollTimeout {
+        let Some(&Reverse((deadline, _))) = self.heap.peek() else {
+            return timeout;
+        };
+        let remaining = deadline.saturating_sub(self.now());
+        // Negative for no timeout. `as_millis` would panic on it.
+        match u64::try_from(i32::from(timeout)) {
+            Ok(millis) if millis <= remaining => timeout,
+            _ => EpollTimeout::try_from(remaining).unwrap_or(EpollTimeout::MAX),
+        }
+    }
+
+    /// Removes and returns the earliest entry, if due at `now`.
+    pub(crate) fn pop_due(&mut self, now: u64) -> Option<(u64, RawFd)> {
+        match self.heap.peek() {
+            Some(&Reverse((deadline, _))) if deadline <= now => {
+                self.heap.pop().map(|Reverse(entry)| entry)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_come_due_earliest_first() {
+        let mut timers = IdleTimers::new();
+        timers.schedule(3, 30);
+        timers.schedule(1, 10);
+        timers.schedule(2, 20);
+        timers.cancel(2);
+
+        assert_eq!(timers.pop_due(5), None);
+        assert_eq!(timers.pop_due(40), Some((10, 1)));
+        assert_eq!(timers.pop_due(40), Some((30, 3)));
+        assert_eq!(timers.pop_due(40), None);
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn clamp_ends_the_wait_by_the_earliest_deadline() {
+        let mut timers = IdleTimers::new();
+        assert_eq!(timers.clamp(EpollTimeout::NONE), EpollTimeout::NONE);
+
+        timers.schedule(1, timers.now() + 60_000);
+        let clamped = i32::from(timers.clamp(EpollTimeout::NONE));
+        assert!((59_000..=60_000).contains(&clamped));
+        assert_eq!(timers.clamp(EpollTimeout::ZERO), EpollTimeout::ZERO);
+
+        timers.schedule(2, 0);
+        assert_eq!(timers.clamp(EpollTimeout::MAX), EpollTimeout::ZERO);
+    }
+}
@@ -0,0 +1,494 @@
+//! A subscriber watching files and directories with inotify.
+//!
+//! [`watch()`] starts with the paths to watch, and [`Watch::with_handler`]
+//! creates the inotify fd and adds a watch for each of them. When the fd is
+//! readable, the subscriber reads it until it would block, and calls the handler
+//! once per event, with the watched path it happened to resolved from the watch
+//! descriptor.
+//!
+//! Watches can be added and removed at runtime, from any handler of the loop,
+//! through the [`Watches`] handle of the subscriber.
+//!
+//! # Overflow
+//!
+//! When events come in faster than the loop reads them, the kernel drops them and
+//! queues an `IN_Q_OVERFLOW` instead, handed out as [`FileEvent::Overflow`]. The
+//! watched files may have changed in any way since, so rescan them.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use eventp::inotify::{self, AddWatchFlags, FileEvent};
+//! use eventp::{Eventp, Pinned, Subscriber};
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut eventp = Eventp::default();
+//! let dir = std::env::temp_dir();
+//!
+//! let subscriber = inotify::watch([&dir])
+//!     .events(AddWatchFlags::IN_CLOSE_WRITE)
+//!     .with_handler(|event: FileEvent, _eventp: Pinned<'_, Eventp>| match event {
+//!         FileEvent::Path { path, name, .. } => println!("{path:?}: {name:?} written"),
+//!         FileEvent::Overflow => println!("events lost, rescanning"),
+//!     })?;
+//! let watches = subscriber.watches();
+//! subscriber.register_into(&mut eventp)?;
+//!
+//! assert!(watches.remove(&dir)?);
+//! # Ok(()) }
+//! ```
+//!
+//! See [examples/config-reload.rs](https://github.com/FuuuOverclocking/eventp/blob/main/examples/config-reload.rs)
+//! for a complete program.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use nix::errno::Errno;
+pub use nix::sys::inotify::AddWatchFlags;
+use nix::sys::inotify::{InitFlags, Inotify, WatchDescriptor};
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::tri_subscriber::HandlerReturn;
+use crate::{interest, Event, EventpOps, Interest, Pinned};
+
+/// The size of `struct inotify_event`, before its name.
+const HEADER_LEN: usize = 16;
+/// Room for 16 events with the longest names, at least one with any name.
+const BUF_LEN: usize = 16 * (HEADER_LEN + libc::NAME_MAX as usize + 1);
+
+/// Starts a [`Watch`] of `paths`, for every event until set otherwise with
+/// [`events`](Watch::events).
+///
+/// For more information, see the [mod-level documentation](self).
+pub fn watch<I>(paths: I) -> Watch
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    Watch {
+        paths: paths.into_iter().map(|p| p.as_ref().to_owned()).collect(),
+        mask: AddWatchFlags::IN_ALL_EVENTS,
+    }
+}
+
+/// A not yet complete inotify watch, waiting for its handler.
+pub struct Watch {
+    paths: Vec<PathBuf>,
+    mask: AddWatchFlags,
+}
+
+impl Watch {
+    /// Sets the events to watch the paths for, e.g. `IN_CLOSE_WRITE`.
+    pub fn events(mut self, mask: AddWatchFlags) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Creates the inotify fd and watches every path, for a subscriber calling
+    /// `handler` with every event.
+    ///
+    /// The handler returns either `()` or `io::Result<()>`. After an error, the
+    /// events already read are still handed out, and the remaining ones are left
+    /// for the next time the loop polls.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from creating the fd or adding a watch, e.g. for a path
+    /// that does not exist.
+    pub fn with_handler<F>(self, handler: F) -> io::Result<Subscriber<F>> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .map_err(io::Error::from)?;
+        let watches = Watches {
+            shared: Rc::new(Shared {
+                inotify,
+                paths: RefCell::new(HashMap::new()),
+            }),
+        };
+        for path in self.paths {
+            watches.add(path, self.mask)?;
+        }
+
+        Ok(Subscriber {
+            watches,
+            interest: Cell::new(interest().read()),
+            buf: vec![0; BUF_LEN].into_boxed_slice(),
+            filled: 0,
+            handler,
+        })
+    }
+}
+
+/// An event read from inotify, see inotify(7).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FileEvent {
+    /// An event on a watched path, or on the entry `name` of a watched directory.
+    Path {
+        /// The watched path, as given to [`watch`] or [`Watches::add`].
+        path: PathBuf,
+        /// What happened, e.g. `IN_CLOSE_WRITE`, with `IN_ISDIR` if it happened
+        /// to a directory. `IN_IGNORED` when the watch is gone, e.g. because the
+        /// path was deleted, after which it gets no more events.
+        mask: AddWatchFlags,
+        /// The name of the entry, for an event inside a watched directory.
+        name: Option<OsString>,
+        /// The same for the `IN_MOVED_FROM` and `IN_MOVED_TO` of one rename.
+        cookie: u32,
+    },
+    /// Events were lost, see [Overflow](self#overflow).
+    Overflow,
+}
+
+/// Adds and removes the watches of a [`Subscriber`], see [`Subscriber::watches`].
+///
+/// Cheap to clone, and usable from the handlers of the loop the subscriber is
+/// registered with.
+#[derive(Clone)]
+pub struct Watches {
+    shared: Rc<Shared>,
+}
+
+struct Shared {
+    inotify: Inotify,
+    /// The watched paths, by raw watch descriptor.
+    paths: RefCell<HashMap<i32, (WatchDescriptor, PathBuf)>>,
+}
+
+impl Watches {
+    /// Watches `path` for `mask`, replacing the mask it was watched for, if any.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from `inotify_add_watch`.
+    pub fn add(&self, path: impl AsRef<Path>, mask: AddWatchFlags) -> io::Result<()> {
+        let path = path.as_ref();
+        let wd = self
+            .shared
+            .inotify
+            .add_watch(path, mask)
+            .map_err(io::Error::from)?;
+        self.shared
+            .paths
+            .borrow_mut()
+            .insert(wd.as_raw(), (wd, path.to_owned()));
+        Ok(())
+    }
+
+    /// Stops watching `path`, and returns whether it was watched. No event is
+    /// handed out for it afterwards, not even `IN_IGNORED`.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from `inotify_rm_watch`.
+    pub fn remove(&self, path: impl AsRef<Path>) -> io::Result<bool> {
+        let path = path.as_ref();
+        let mut paths = self.shared.paths.borrow_mut();
+        let Some(raw) = paths
+            .iter()
+            .find(|(_, (_, p))| p == path)
+            .map(|(&raw, _)| raw)
+        else {
+            return Ok(false);
+        };
+        let (wd, _) = paths.remove(&raw).unwrap();
+        match self.shared.inotify.rm_watch(wd) {
+            // Already removed by the kernel, with its `IN_IGNORED` not read yet.
+            Ok(()) | Err(Errno::EINVAL) => Ok(true),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns whether `path` is watched.
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.shared.paths.borrow().values().any(|(_, p)| p == path)
+    }
+}
+
+/// The subscriber created by [`Watch::with_handler`].
+pub struct Subscriber<F> {
+    watches: Watches,
+    interest: Cell<Interest>,
+    buf: Box<[u8]>,
+    /// The bytes of `buf` read but not handed out yet, at its start.
+    filled: usize,
+    handler: F,
+}
+
+impl<F> Subscriber<F> {
+    /// Returns a handle to add and remove watches.
+    pub fn watches(&self) -> Watches {
+        self.watches.clone()
+    }
+}
+
+impl<F> AsFd for Subscriber<F> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.watches.shared.inotify.as_fd()
+    }
+}
+
+impl<F> HasInterest for Subscriber<F> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, F, R> Handler<Ep> for Subscriber<F>
+where
+    Ep: EventpOps,
+    F: FnMut(FileEvent, Pinned<'_, Ep>) -> R,
+    R: HandlerReturn,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        let shared = &self.watches.shared;
+        loop {
+            match nix::unistd::read(shared.inotify.as_fd(), &mut self.buf[self.filled..]) {
+                Ok(0) => return Ok(()),
+                Ok(n) => self.filled += n,
+                Err(Errno::EAGAIN) => return Ok(()),
+                Err(Errno::EINTR) => continue,
+                Err(e) => return Err(e.into()),
+            }
+
+            let mut result = Ok(());
+            let mut offset = 0;
+            while let Some((record, len)) = decode(&self.buf[offset..self.filled]) {
+                offset += len;
+                if let Some(event) = shared.resolve(&record) {
+                    let r = (self.handler)(event, eventp.as_mut()).into_result();
+                    result = result.and(r);
+                }
+            }
+            // An event cut off by the end of the read, if the kernel ever does so,
+            // is completed by the next one.
+            self.buf.copy_within(offset..self.filled, 0);
+            self.filled -= offset;
+            result?;
+        }
+    }
+}
+
+impl Shared {
+    /// Turns a record into the event handed out, if it is for a path still
+    /// watched. Forgets the path once its watch is gone.
+    fn resolve(&self, record: &Record<'_>) -> Option<FileEvent> {
+        let mask = AddWatchFlags::from_bits_retain(record.mask);
+        if mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+            return Some(FileEvent::Overflow);
+        }
+
+        let mut paths = self.paths.borrow_mut();
+        let path = if mask.contains(AddWatchFlags::IN_IGNORED) {
+            paths.remove(&record.wd)?.1
+        } else {
+            paths.get(&record.wd)?.1.clone()
+        };
+        Some(FileEvent::Path {
+            path,
+            mask,
+            name: record.name.map(|name| OsStr::from_bytes(name).to_owned()),
+            cookie: record.cookie,
+        })
+    }
+}
+
+struct Record<'a> {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    name: Option<&'a [u8]>,
+}
+
+/// Decodes the `struct inotify_event` at the start of `buf`, if it is complete,
+/// and returns it with its length.
+fn decode(buf: &[u8]) -> Option<(Record<'_>, usize)> {
+    let field = |at: usize| {
+        buf.get(at..at + 4)
+            .map(|b| u32::from_ne_bytes(b.try_into().unwrap()))
+    };
+    let (wd, mask, cookie, name_len) = (field(0)?, field(4)?, field(8)?, field(12)?);
+    let len = HEADER_LEN + name_len as usize;
+    // Padded with NULs up to an aligned length.
+    let name = buf.get(HEADER_LEN..len)?;
+    let name = name
+        .split(|&b| b == 0)
+        .next()
+        .filter(|name| !name.is_empty());
+
+    let record = Record {
+        wd: wd as i32,
+        mask,
+        cookie,
+        name,
+    };
+    Some((record, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{Eventp, Subscriber as _};
+
+    fn encode(wd: i32, mask: AddWatchFlags, name: &str) -> Vec<u8> {
+        let padded = if name.is_empty() {
+            0
+        } else {
+            (name.len() + 1 + 15) / 16 * 16
+        };
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&wd.to_ne_bytes());
+        buf.extend_from_slice(&mask.bits().to_ne_bytes());
+        buf.extend_from_slice(&0u32.to_ne_bytes());
+        buf.extend_from_slice(&(padded as u32).to_ne_bytes());
+        buf.extend_from_slice(name.as_bytes());
+        buf.resize(HEADER_LEN + padded, 0);
+        buf
+    }
+
+    #[test]
+    fn decode_waits_for_complete_records() {
+        let mut buf = encode(1, AddWatchFlags::IN_CREATE, "config.toml");
+        buf.extend(encode(2, AddWatchFlags::IN_CLOSE_WRITE, ""));
+        let first = HEADER_LEN + 16;
+
+        for cut in 0..first {
+            assert!(decode(&buf[..cut]).is_none(), "cut at {cut}");
+        }
+        let (record, len) = decode(&buf).unwrap();
+        assert_eq!((record.wd, len), (1, first));
+        assert_eq!(record.name, Some(&b"config.toml"[..]));
+
+        let (record, len) = decode(&buf[first..]).unwrap();
+        assert_eq!(
+            (record.wd, record.mask, len),
+            (2, AddWatchFlags::IN_CLOSE_WRITE.bits(), HEADER_LEN)
+        );
+        assert_eq!(record.name, None);
+    }
+
+    /// A fresh directory, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("eventp-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn run(eventp: &mut Eventp) {
+        eventp
+            .run_once_with_timeout(EpollTimeout::try_from(1000).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn events_carry_the_watched_path_and_name() {
+        let dir = TempDir::new("inotify-events");
+        let mut eventp = Eventp::default();
+        let events = Rc::new(RefCell::new(Vec::new()));
+
+        let e = events.clone();
+        let subscriber = watch([&dir.0])
+            .events(AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_DELETE_SELF)
+            .with_handler(move |event: FileEvent, _: Pinned<'_, Eventp>| e.borrow_mut().push(event))
+            .unwrap();
+        let watches = subscriber.watches();
+        subscriber.register_into(&mut eventp).unwrap();
+
+        fs::write(dir.0.join("a.toml"), "x = 1").unwrap();
+        run(&mut eventp);
+        assert_eq!(
+            *events.borrow(),
+            [FileEvent::Path {
+                path: dir.0.clone(),
+                mask: AddWatchFlags::IN_CLOSE_WRITE,
+                name: Some("a.toml".into()),
+                cookie: 0,
+            }]
+        );
+
+        // The watch goes away with the directory.
+        events.borrow_mut().clear();
+        fs::remove_file(dir.0.join("a.toml")).unwrap();
+        fs::remove_dir(&dir.0).unwrap();
+        run(&mut eventp);
+        let masks: Vec<_> = events
+            .borrow()
+            .iter()
+            .map(|event| match event {
+                FileEvent::Path { mask, .. } => *mask,
+                FileEvent::Overflow => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            masks,
+            [AddWatchFlags::IN_DELETE_SELF, AddWatchFlags::IN_IGNORED]
+        );
+        assert!(!watches.contains(&dir.0));
+    }
+
+    #[test]
+    fn watches_are_added_and_removed_at_runtime() {
+        let dir = TempDir::new("inotify-runtime");
+        let (a, b) = (dir.0.join("a"), dir.0.join("b"));
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        let mut eventp = Eventp::default();
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let w = written.clone();
+        let subscriber = watch(None::<&Path>)
+            .with_handler(move |event: FileEvent, _: Pinned<'_, Eventp>| {
+                if let FileEvent::Path { path, .. } = event {
+                    w.borrow_mut().push(path);
+                }
+            })
+            .unwrap();
+        let watches = subscriber.watches();
+        subscriber.register_into(&mut eventp).unwrap();
+
+        watches.add(&a, AddWatchFlags::IN_CLOSE_WRITE).unwrap();
+        watches.add(&b, AddWatchFlags::IN_CLOSE_WRITE).unwrap();
+        assert!(watches.remove(&a).unwrap());
+        assert!(!watches.remove(&a).unwrap());
+
+        fs::write(&a, "1").unwrap();
+        fs::write(&b, "1").unwrap();
+        run(&mut eventp);
+        assert_eq!(*written.borrow(), [b]);
+    }
+
+    #[test]
+    fn missing_paths_fail_to_watch() {
+        let err = watch(["/nonexistent/eventp"])
+            .with_handler(|_: FileEvent, _: Pinned<'_, Eventp>| {})
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
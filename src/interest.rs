@@ -1,18 +1,108 @@
+use std::io;
+use std::time::Duration;
+
 use crate::epoll::EpollFlags;
+use crate::Error;
+
+/// The mark of [`Interest::track_deltas`] in [`Settings`].
+const TRACK_DELTAS: u8 = 1 << 0;
+
+/// The mark of [`Interest::track_last_event`].
+const TRACK_LAST_EVENT: u8 = 1 << 1;
+
+/// The mark of [`Interest::writable_edge_emulation`].
+const WRITABLE_EDGE: u8 = 1 << 2;
+
+/// The mark of [`Interest::hangup_only`].
+const HANGUP_ONLY: u8 = 1 << 3;
+
+/// The flags that only change how the others are reported.
+#[cfg(not(target_arch = "mips"))]
+const MODIFIERS: EpollFlags = EpollFlags::EPOLLET
+    .union(EpollFlags::EPOLLONESHOT)
+    .union(EpollFlags::EPOLLWAKEUP)
+    .union(EpollFlags::EPOLLEXCLUSIVE);
+#[cfg(target_arch = "mips")]
+const MODIFIERS: EpollFlags = EpollFlags::EPOLLET
+    .union(EpollFlags::EPOLLONESHOT)
+    .union(EpollFlags::EPOLLEXCLUSIVE);
+
+/// The flags the kernel accepts next to `EPOLLEXCLUSIVE`, see
+/// [`Interest::exclusive`].
+#[cfg(not(target_arch = "mips"))]
+pub(crate) const EXCLUSIVE_COMPATIBLE: EpollFlags = EpollFlags::EPOLLEXCLUSIVE
+    .union(EpollFlags::EPOLLIN)
+    .union(EpollFlags::EPOLLOUT)
+    .union(EpollFlags::EPOLLET)
+    .union(EpollFlags::EPOLLHUP)
+    .union(EpollFlags::EPOLLERR)
+    .union(EpollFlags::EPOLLWAKEUP);
+#[cfg(target_arch = "mips")]
+pub(crate) const EXCLUSIVE_COMPATIBLE: EpollFlags = EpollFlags::EPOLLEXCLUSIVE
+    .union(EpollFlags::EPOLLIN)
+    .union(EpollFlags::EPOLLOUT)
+    .union(EpollFlags::EPOLLET)
+    .union(EpollFlags::EPOLLHUP)
+    .union(EpollFlags::EPOLLERR);
+
+/// The settings of an interest kept by the loop, next to its flags rather than in
+/// bits of them, which are the kernel's.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Settings {
+    /// The marks set, such as [`TRACK_DELTAS`].
+    marks: u8,
+    /// See [`Interest::dispatch_priority`].
+    priority: u8,
+}
+
+impl Settings {
+    const DEFAULT: Self = Self {
+        marks: 0,
+        priority: Interest::DEFAULT_PRIORITY,
+    };
+
+    const fn has(&self, mark: u8) -> bool {
+        self.marks & mark != 0
+    }
+
+    const fn mark(self, mark: u8) -> Self {
+        Self {
+            marks: self.marks | mark,
+            ..self
+        }
+    }
+
+    const fn unmark(self, mark: u8) -> Self {
+        Self {
+            marks: self.marks & !mark,
+            ..self
+        }
+    }
+}
 
 /// A wrapper around [`EpollFlags`], represents interest in I/O readiness events
 /// for a file descriptor.
 ///
 /// References for epoll flags provided on each method's documentation, or see
 /// [epoll_ctl(2)](https://man.archlinux.org/man/epoll_ctl.2.en#EPOLLIN).
+///
+/// Besides the flags, an interest carries the settings of the loop, such as an
+/// [`idle_timeout`](Interest::idle_timeout) or [`user_data`](Interest::user_data),
+/// which are not passed to the kernel.
 #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-#[repr(transparent)]
-pub struct Interest(EpollFlags);
+pub struct Interest {
+    flags: EpollFlags,
+    settings: Settings,
+    /// In milliseconds, 0 for none.
+    idle_millis: u32,
+    /// 0 for none.
+    user_data: u64,
+}
 
 impl Default for Interest {
     /// Creates a default `Interest` with no flags set.
     fn default() -> Self {
-        Self(EpollFlags::empty())
+        Self::new(EpollFlags::empty())
     }
 }
 
@@ -29,24 +119,267 @@ impl From<Interest> for EpollFlags {
 }
 
 impl Interest {
+    /// The priority of interests without [`dispatch_priority`](Self::dispatch_priority).
+    pub const DEFAULT_PRIORITY: u8 = 128;
+
     /// Creates a new `Interest` from raw `EpollFlags`.
     pub const fn new(flags: EpollFlags) -> Self {
-        Self(flags)
+        Self {
+            flags,
+            settings: Settings::DEFAULT,
+            idle_millis: 0,
+            user_data: 0,
+        }
     }
 
-    /// Returns the underlying `EpollFlags` bitmask.
+    /// Returns the underlying `EpollFlags` bitmask, as handed to the kernel.
     pub const fn bitflags(&self) -> EpollFlags {
-        self.0
+        self.flags
+    }
+
+    /// Returns the raw `events` mask handed to the kernel in `struct epoll_event`.
+    ///
+    /// The settings kept by the loop are not part of it: delta tracking, last event
+    /// tracking, writable edge emulation, hangup only, dispatch priority, idle
+    /// timeout and user data.
+    pub const fn as_raw(&self) -> u32 {
+        self.flags.bits() as u32
+    }
+
+    /// Creates an `Interest` from a raw `events` mask, such as one returned by
+    /// [`as_raw`](Self::as_raw), without any setting of the loop.
+    pub const fn from_raw(events: u32) -> Self {
+        Self::new(EpollFlags::from_bits_retain(events as i32))
+    }
+
+    /// Checks the combination of flags against the constraints of the kernel, as
+    /// [`add`](crate::EventpOpsAdd::add) does before registering the interest.
+    ///
+    /// The builder methods can combine any flags, and so can [`Interest::new`], as
+    /// an escape hatch. [`ExclusiveInterest`] is the builder that cannot go wrong
+    /// with `EPOLLEXCLUSIVE`.
+    ///
+    /// # Errors
+    ///
+    /// As the payload of an [`io::ErrorKind::InvalidInput`] error:
+    ///
+    /// - [`Error::ExclusiveIncompatible`] if `EPOLLEXCLUSIVE` is set with flags the
+    ///   kernel rejects next to it, which it would fail with `EINVAL`.
+    /// - [`Error::WakeupIneffective`] if `EPOLLWAKEUP` is set with `EPOLLONESHOT` or
+    ///   `EPOLLET`, with which the kernel does not promise to keep the system awake.
+    ///
+    /// ```rust
+    /// use eventp::{interest, Error};
+    ///
+    /// assert!(interest().read().edge_triggered().exclusive().validate().is_ok());
+    ///
+    /// let err = interest().read().oneshot().exclusive().validate().unwrap_err();
+    /// assert!(matches!(
+    ///     Error::from_io(&err),
+    ///     Some(Error::ExclusiveIncompatible { .. })
+    /// ));
+    /// ```
+    pub fn validate(&self) -> io::Result<()> {
+        let flags = self.bitflags();
+        if flags.contains(EpollFlags::EPOLLEXCLUSIVE) {
+            let incompatible = flags.difference(EXCLUSIVE_COMPATIBLE);
+            if !incompatible.is_empty() {
+                return Err(Error::ExclusiveIncompatible {
+                    flags: incompatible,
+                }
+                .into());
+            }
+        }
+        #[cfg(not(target_arch = "mips"))]
+        if flags.contains(EpollFlags::EPOLLWAKEUP) {
+            let ineffective = flags.intersection(EpollFlags::EPOLLONESHOT | EpollFlags::EPOLLET);
+            if !ineffective.is_empty() {
+                return Err(Error::WakeupIneffective { flags: ineffective }.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if no event is asked for, not even explicitly only hangups and
+    /// errors with [`hangup_only`](Self::hangup_only).
+    pub(crate) const fn is_empty(&self) -> bool {
+        !self.settings.has(HANGUP_ONLY) && self.bitflags().difference(MODIFIERS).is_empty()
+    }
+
+    /// Returns the priority set by [`dispatch_priority`](Self::dispatch_priority).
+    pub(crate) const fn dispatch_rank(&self) -> u8 {
+        self.settings.priority
+    }
+
+    /// Returns `true` if set by [`track_deltas`](Self::track_deltas).
+    pub(crate) const fn tracks_deltas(&self) -> bool {
+        self.settings.has(TRACK_DELTAS)
+    }
+
+    /// Returns `true` if set by [`track_last_event`](Self::track_last_event).
+    pub(crate) const fn tracks_last_event(&self) -> bool {
+        self.settings.has(TRACK_LAST_EVENT)
+    }
+
+    /// Returns `true` if set by [`writable_edge_emulation`](Self::writable_edge_emulation).
+    pub(crate) const fn emulates_writable_edge(&self) -> bool {
+        self.settings.has(WRITABLE_EDGE)
+    }
+
+    /// Returns the timeout set by [`idle_timeout`](Self::idle_timeout), in
+    /// milliseconds, or 0.
+    pub(crate) const fn idle_millis(&self) -> u32 {
+        self.idle_millis
+    }
+
+    /// Returns the data set by [`user_data`](Self::user_data), or 0.
+    pub(crate) const fn user_data_value(&self) -> u64 {
+        self.user_data
+    }
+
+    /// Returns `self` with the user data of `previous` if it has none, as kept by
+    /// [`modify`](crate::EventpOps::modify).
+    pub(crate) const fn or_user_data_of(self, previous: Interest) -> Self {
+        if self.user_data != 0 {
+            self
+        } else {
+            self.user_data(previous.user_data)
+        }
     }
 
     /// Adds the given flags to this interest set.
     const fn add(self, flags: EpollFlags) -> Self {
-        Self(self.0.union(flags))
+        Self {
+            flags: self.flags.union(flags),
+            ..self
+        }
+    }
+
+    /// Sets the given marks of the settings.
+    const fn mark(self, mark: u8) -> Self {
+        Self {
+            settings: self.settings.mark(mark),
+            ..self
+        }
+    }
+
+    /// Unsets the given marks of the settings.
+    const fn unmark(self, mark: u8) -> Self {
+        Self {
+            settings: self.settings.unmark(mark),
+            ..self
+        }
     }
 
     /// Removes the given flags from this interest set.
     const fn remove(self, flags: EpollFlags) -> Self {
-        Self(self.0.difference(flags))
+        Self {
+            flags: self.flags.difference(flags),
+            ..self
+        }
+    }
+
+    /// Returns the flags and settings set in either `self` or `other`, with the
+    /// dispatch priority, idle timeout and user data of `other` if it has them, or
+    /// else those of `self`.
+    pub const fn union(self, other: Interest) -> Self {
+        let priority = if other.settings.priority != Self::DEFAULT_PRIORITY {
+            other.settings.priority
+        } else {
+            self.settings.priority
+        };
+        let idle_millis = if other.idle_millis != 0 {
+            other.idle_millis
+        } else {
+            self.idle_millis
+        };
+        Self {
+            flags: self.flags.union(other.flags),
+            settings: Settings {
+                marks: self.settings.marks | other.settings.marks,
+                priority,
+            },
+            idle_millis,
+            user_data: other.or_user_data_of(self).user_data,
+        }
+    }
+
+    /// Returns `true` if all the flags of `other` are set in `self`.
+    pub const fn contains(&self, other: Interest) -> bool {
+        self.flags.contains(other.flags)
+    }
+
+    /// Interest in reading a stream socket: `EPOLLIN | EPOLLRDHUP`, level-triggered.
+    ///
+    /// With `EPOLLRDHUP`, the peer shutting down its writing half is reported even
+    /// while no data is left, see [`Event::is_closed`](crate::Event::is_closed).
+    pub const fn stream_read() -> Self {
+        interest().read().read_hangup()
+    }
+
+    /// Same as [`stream_read`](Self::stream_read), edge-triggered.
+    pub const fn stream_read_et() -> Self {
+        Self::stream_read().edge_triggered()
+    }
+
+    /// Interest in reading and writing a stream socket:
+    /// `EPOLLIN | EPOLLOUT | EPOLLRDHUP`, level-triggered.
+    pub const fn stream_read_write() -> Self {
+        Self::stream_read().write()
+    }
+
+    /// Same as [`stream_read_write`](Self::stream_read_write), edge-triggered.
+    pub const fn stream_read_write_et() -> Self {
+        Self::stream_read_write().edge_triggered()
+    }
+
+    /// Every flag reporting that a stream can no longer be read from:
+    /// `EPOLLRDHUP | EPOLLHUP | EPOLLERR`.
+    ///
+    /// `EPOLLHUP` and `EPOLLERR` are always reported, so registering this only adds
+    /// `EPOLLRDHUP`; it makes the full set explicit, e.g. with
+    /// [`Event::intersects`](crate::Event::intersects). The flags are those of
+    /// [`Event::is_closed`](crate::Event::is_closed).
+    pub const fn all_read_errors() -> Self {
+        Self::new(
+            EpollFlags::EPOLLRDHUP
+                .union(EpollFlags::EPOLLHUP)
+                .union(EpollFlags::EPOLLERR),
+        )
+    }
+
+    /// Interest in the raw `raw_bits` of the `events` mask, see
+    /// [`with_custom`](Self::with_custom).
+    pub const fn custom(raw_bits: u32) -> Self {
+        interest().with_custom(raw_bits)
+    }
+
+    /// Adds the raw `raw_bits` to the `events` mask handed to the kernel, whether
+    /// [`EpollFlags`] has a name for them or not, e.g. for a flag of a newer kernel
+    /// than nix knows about. Events reporting them are told apart with
+    /// [`Event::has_custom`](crate::Event::has_custom).
+    ///
+    /// The bits are kept as given, so [`validate`](Self::validate) still checks
+    /// them, e.g. next to [`exclusive`](Self::exclusive). Serialized with the
+    /// `serde` feature, the bits without a name are written in hexadecimal.
+    ///
+    /// ```rust
+    /// use eventp::{interest, Event};
+    ///
+    /// /// A flag of a newer kernel, not known to nix yet.
+    /// const EPOLLNEW: u32 = 1 << 14;
+    ///
+    /// let interest = interest().read().with_custom(EPOLLNEW);
+    /// assert_eq!(interest.as_raw(), libc::EPOLLIN as u32 | EPOLLNEW);
+    ///
+    /// // As reported to the handler.
+    /// let event = Event::from_raw(libc::EPOLLIN as u32 | EPOLLNEW);
+    /// assert!(event.is_readable());
+    /// assert!(event.has_custom(EPOLLNEW));
+    /// ```
+    ///
+    pub const fn with_custom(self, raw_bits: u32) -> Self {
+        self.add(EpollFlags::from_bits_retain(raw_bits as i32))
     }
 
     /// Adds interest in readable events (`EPOLLIN`).
@@ -102,6 +435,11 @@ impl Interest {
     /// descriptor is disabled in the interest list and no other events will be reported
     /// by the epoll interface. The user must call epoll_ctl() with EPOLL_CTL_MOD to rearm
     /// the file descriptor with a new event mask.
+    ///
+    /// Meanwhile, [`Eventp::is_armed`](crate::Eventp::is_armed) returns `false` for
+    /// it, while its interest is still the one registered. See
+    /// [`Builder::warn_disarmed_after`](crate::Builder::warn_disarmed_after) to
+    /// catch handlers never rearming.
     pub const fn oneshot(self) -> Self {
         self.add(EpollFlags::EPOLLONESHOT)
     }
@@ -148,10 +486,74 @@ impl Interest {
     /// to epoll_ctl() that specifies EPOLLEXCLUSIVE in events and specifies the target
     /// file descriptor fd as an epoll instance will likewise fail. The error in all of
     /// these cases is EINVAL.
+    ///
+    /// This method adds the flag to any interest, which
+    /// [`validate`](Self::validate) then checks when it is added. Build the interest
+    /// from [`ExclusiveInterest`] instead to only be offered the flags allowed with
+    /// it. Either way, [`Eventp::modify`](crate::EventpOps::modify) refuses it with
+    /// [`Error::ExclusiveModify`].
     pub const fn exclusive(self) -> Self {
         self.add(EpollFlags::EPOLLEXCLUSIVE)
     }
 
+    /// Asks the loop to remember the last event dispatched to the subscriber, so that
+    /// its handler can tell which readiness changed, see [`EventDelta`]. Not an epoll
+    /// flag, and never passed to the kernel.
+    ///
+    /// Useful with [`edge_triggered`](Self::edge_triggered) interests in both reading
+    /// and writing, where a wake-up for one reports the other again while it lasts.
+    ///
+    /// [`EventDelta`]: crate::EventDelta
+    pub const fn track_deltas(self) -> Self {
+        self.mark(TRACK_DELTAS)
+    }
+
+    /// Asks the loop to note when it dispatches an event to the subscriber, so that
+    /// its handler can tell how long it has been since the previous one, see
+    /// [`SinceLast`]. Not an epoll flag, and never passed to the kernel.
+    ///
+    /// Meant for keepalives and pacing, in place of an `Instant` kept by the
+    /// handler. The clock is only read for the subscribers with this setting.
+    ///
+    /// [`SinceLast`]: crate::tri_subscriber::SinceLast
+    pub const fn track_last_event(self) -> Self {
+        self.mark(TRACK_LAST_EVENT)
+    }
+
+    /// Asks the loop to drop an event reporting nothing but `EPOLLOUT` if the last
+    /// one dispatched to the subscriber reported `EPOLLOUT` too. Not an epoll flag,
+    /// and never passed to the kernel.
+    ///
+    /// A level-triggered interest in writing is reported by every wait while the
+    /// socket buffer has room, which keeps calling a handler with nothing to write.
+    /// This makes `EPOLLOUT` behave as if edge-triggered, while reading stays
+    /// level-triggered. Events with any other flag, such as `EPOLLIN`, `EPOLLERR` or
+    /// `EPOLLHUP`, are always dispatched.
+    ///
+    /// The loop cannot see the buffer fill up, as the fd is then just not reported.
+    /// So once a write fails with `EAGAIN`, the handler calls
+    /// [`modify`](crate::EventpOps::modify), e.g. with the same interest, to be told
+    /// again when there is room: `modify` forgets the last event.
+    ///
+    /// Without `EPOLLET` or `EPOLLONESHOT`, the first event dropped also makes the
+    /// kernel stop watching `EPOLLOUT` for the fd until `modify`, as a writable fd
+    /// would otherwise wake every wait for nothing.
+    pub const fn writable_edge_emulation(self) -> Self {
+        self.mark(WRITABLE_EDGE)
+    }
+
+    /// Marks an interest with no event flag as intended, for an fd only watched for
+    /// the hangups and errors the kernel always reports, e.g. to detect the peer
+    /// closing a connection without reading from it. Not an epoll flag, and never
+    /// passed to the kernel.
+    ///
+    /// Otherwise, such an interest is refused by [`add`](crate::EventpOpsAdd::add)
+    /// with [`Error::EmptyInterest`](crate::Error::EmptyInterest), as it is most often
+    /// [`Interest::default`] left by mistake.
+    pub const fn hangup_only(self) -> Self {
+        self.mark(HANGUP_ONLY)
+    }
+
     /// Removes interest in readable events.
     pub const fn remove_read(self) -> Self {
         self.remove(EpollFlags::EPOLLIN)
@@ -172,6 +574,11 @@ impl Interest {
         self.remove(EpollFlags::EPOLLPRI)
     }
 
+    /// Removes the mark of [`hangup_only`](Self::hangup_only).
+    pub const fn remove_hangup_only(self) -> Self {
+        self.unmark(HANGUP_ONLY)
+    }
+
     /// Unsets edge-triggered mode, reverting to the default level-triggered behavior.
     pub const fn remove_edge_triggered(self) -> Self {
         self.remove(EpollFlags::EPOLLET)
@@ -192,6 +599,260 @@ impl Interest {
     pub const fn remove_exclusive(self) -> Self {
         self.remove(EpollFlags::EPOLLEXCLUSIVE)
     }
+
+    /// Sets the priority of the subscriber within a batch of events, for loops built
+    /// with [`DispatchOrder::PriorityStable`](crate::DispatchOrder::PriorityStable): lower values
+    /// are dispatched first, and equal ones in the usual order. Not an epoll flag,
+    /// and never passed to the kernel. Defaults to
+    /// [`DEFAULT_PRIORITY`](Self::DEFAULT_PRIORITY).
+    ///
+    /// Replaces any priority set before.
+    pub const fn dispatch_priority(self, priority: u8) -> Self {
+        Self {
+            settings: Settings {
+                priority,
+                ..self.settings
+            },
+            ..self
+        }
+    }
+
+    /// Stops tracking event deltas.
+    pub const fn remove_track_deltas(self) -> Self {
+        self.unmark(TRACK_DELTAS)
+    }
+
+    /// Stops tracking the time of the last event.
+    pub const fn remove_track_last_event(self) -> Self {
+        self.unmark(TRACK_LAST_EVENT)
+    }
+
+    /// Stops emulating edge-triggered writing.
+    pub const fn remove_writable_edge_emulation(self) -> Self {
+        self.unmark(WRITABLE_EDGE)
+    }
+
+    /// Asks the loop to evict the subscriber once no event has been dispatched to it
+    /// for `timeout`, e.g. to drop silent connections. Not an epoll flag, and never
+    /// passed to the kernel.
+    ///
+    /// Eviction calls [`Handler::on_idle`](crate::subscriber::Handler::on_idle),
+    /// which deletes the subscriber by default. The timeout is kept in milliseconds,
+    /// rounded up, and saturates at about 49 days; a zero `timeout` removes it. Only
+    /// [`Eventp`](crate::Eventp) evicts idle subscribers.
+    pub const fn idle_timeout(self, timeout: Duration) -> Self {
+        let millis = (timeout.as_nanos() + 999_999) / 1_000_000;
+        let idle_millis = if millis > u32::MAX as u128 {
+            u32::MAX
+        } else {
+            millis as u32
+        };
+        Self {
+            idle_millis,
+            ..self
+        }
+    }
+
+    /// Removes the idle timeout.
+    pub const fn remove_idle_timeout(self) -> Self {
+        self.idle_timeout(Duration::ZERO)
+    }
+
+    /// Tags the registration with `data`, such as a tenant id or a queue index,
+    /// without changing the state of its handler. Not an epoll flag, and never
+    /// passed to the kernel.
+    ///
+    /// Handlers of [`tri_subscriber`](crate::tri_subscriber) can take it as a
+    /// [`UserData`](crate::tri_subscriber::UserData) parameter, others read it
+    /// with [`EventpOps::user_data_of`](crate::EventpOps::user_data_of), and
+    /// [`set_user_data`](crate::EventpOps::set_user_data) changes it. A zero
+    /// `data` is none: [`modify`](crate::EventpOps::modify) keeps the user data of
+    /// the registration unless the new interest has some.
+    ///
+    /// The data is kept next to every subscriber, whether set or not, where it
+    /// takes 8 bytes.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::os::fd::{AsFd, AsRawFd};
+    ///
+    /// use eventp::tri_subscriber::{UserData, WithHandler};
+    /// use eventp::{interest, Eventp, EventpOps, Subscriber};
+    /// use nix::sys::eventfd::EventFd;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// let efd = EventFd::new()?;
+    /// let raw_fd = efd.as_fd().as_raw_fd();
+    /// interest()
+    ///     .read()
+    ///     .user_data(0xBEEF)
+    ///     .with_fd(efd)
+    ///     .with_handler(|UserData(tenant): UserData| assert_eq!(tenant, 0xBEEF))
+    ///     .register_into(&mut eventp)?;
+    /// assert_eq!(eventp.user_data_of(raw_fd), Some(0xBEEF));
+    /// # Ok(()) }
+    /// ```
+    pub const fn user_data(self, data: u64) -> Self {
+        Self {
+            user_data: data,
+            ..self
+        }
+    }
+}
+
+/// An [`Interest`] with `EPOLLEXCLUSIVE`, only offering the flags the kernel accepts
+/// next to it: reading, writing, edge-triggered mode and `EPOLLWAKEUP`, besides the
+/// settings kept by the loop. `EPOLLHUP` and `EPOLLERR` are always reported anyway.
+///
+/// It turns into an [`Interest`] with [`with_fd`](Self::with_fd), to build a
+/// subscriber that [`add`](crate::EventpOpsAdd::add) accepts. The kernel refuses to
+/// modify such a registration, and so does
+/// [`Eventp::modify`](crate::EventpOps::modify), with [`Error::ExclusiveModify`]:
+/// delete it and add it again instead.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::io;
+/// use std::net::TcpListener;
+///
+/// use eventp::{tri_subscriber::WithHandler, Eventp, ExclusiveInterest, Subscriber};
+///
+/// # fn main() -> io::Result<()> {
+/// let listener = TcpListener::bind("127.0.0.1:0")?;
+/// listener.set_nonblocking(true)?;
+///
+/// let mut eventp = Eventp::default();
+/// ExclusiveInterest::new()
+///     .read()
+///     .edge_triggered()
+///     .with_fd(listener)
+///     .with_handler(|listener: &mut TcpListener| {
+///         while let Ok((_stream, _addr)) = listener.accept() {}
+///     })
+///     .register_into(&mut eventp)?;
+/// # Ok(()) }
+/// ```
+///
+/// The flags the kernel rejects next to `EPOLLEXCLUSIVE` cannot be asked for:
+///
+/// ```compile_fail
+/// eventp::ExclusiveInterest::new().read().oneshot();
+/// ```
+///
+/// ```compile_fail
+/// eventp::ExclusiveInterest::new().read_hangup();
+/// ```
+///
+/// ```compile_fail
+/// eventp::ExclusiveInterest::new().priority();
+/// ```
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ExclusiveInterest(Interest);
+
+impl Default for ExclusiveInterest {
+    /// Creates an `ExclusiveInterest` with no flag but `EPOLLEXCLUSIVE`.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<ExclusiveInterest> for Interest {
+    fn from(value: ExclusiveInterest) -> Self {
+        value.0
+    }
+}
+
+impl TryFrom<Interest> for ExclusiveInterest {
+    type Error = io::Error;
+
+    /// Adds `EPOLLEXCLUSIVE` to `interest`, after checking that the rest of it can
+    /// be combined with it, see [`Interest::validate`].
+    fn try_from(interest: Interest) -> io::Result<Self> {
+        let interest = interest.exclusive();
+        interest.validate()?;
+        Ok(Self(interest))
+    }
+}
+
+impl ExclusiveInterest {
+    /// Creates an `ExclusiveInterest` with no flag but `EPOLLEXCLUSIVE`.
+    pub const fn new() -> Self {
+        Self(interest().exclusive())
+    }
+
+    /// Returns the [`Interest`], with `EPOLLEXCLUSIVE`.
+    pub const fn interest(self) -> Interest {
+        self.0
+    }
+
+    /// See [`Interest::read`].
+    pub const fn read(self) -> Self {
+        Self(self.0.read())
+    }
+
+    /// See [`Interest::write`].
+    pub const fn write(self) -> Self {
+        Self(self.0.write())
+    }
+
+    /// See [`Interest::read_write`].
+    pub const fn read_write(self) -> Self {
+        Self(self.0.read_write())
+    }
+
+    /// See [`Interest::edge_triggered`].
+    pub const fn edge_triggered(self) -> Self {
+        Self(self.0.edge_triggered())
+    }
+
+    /// See [`Interest::wakeup`]. It keeps the system awake only without
+    /// [`edge_triggered`](Self::edge_triggered), which [`Interest::validate`] checks.
+    #[cfg(not(target_arch = "mips"))]
+    pub const fn wakeup(self) -> Self {
+        Self(self.0.wakeup())
+    }
+
+    /// See [`Interest::track_deltas`].
+    pub const fn track_deltas(self) -> Self {
+        Self(self.0.track_deltas())
+    }
+
+    /// See [`Interest::track_last_event`].
+    pub const fn track_last_event(self) -> Self {
+        Self(self.0.track_last_event())
+    }
+
+    /// See [`Interest::dispatch_priority`].
+    pub const fn dispatch_priority(self, priority: u8) -> Self {
+        Self(self.0.dispatch_priority(priority))
+    }
+
+    /// See [`Interest::idle_timeout`].
+    pub const fn idle_timeout(self, timeout: Duration) -> Self {
+        Self(self.0.idle_timeout(timeout))
+    }
+
+    /// See [`Interest::user_data`].
+    pub const fn user_data(self, data: u64) -> Self {
+        Self(self.0.user_data(data))
+    }
+
+    /// Combines the [`Interest`] with a file descriptor, see [`Interest::with_fd`].
+    pub const fn with_fd<Fd: std::os::fd::AsFd>(self, fd: Fd) -> (Interest, Fd) {
+        (self.0, fd)
+    }
+
+    /// Same as [`with_fd`](Self::with_fd), but sets `O_NONBLOCK` on `fd` first, see
+    /// [`Interest::with_fd_nonblocking`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Interest::with_fd_nonblocking`].
+    pub fn with_fd_nonblocking<Fd: std::os::fd::AsFd>(self, fd: Fd) -> io::Result<(Interest, Fd)> {
+        self.0.with_fd_nonblocking(fd)
+    }
 }
 
 /// Creates a new, empty [`Interest`] set. This is the **recommended** API entry point.
@@ -234,3 +895,259 @@ impl Interest {
 pub const fn interest() -> Interest {
     Interest::new(EpollFlags::empty())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_unless_an_event_or_hangup_only_is_asked_for() {
+        assert!(Interest::default().is_empty());
+        assert!(interest()
+            .edge_triggered()
+            .oneshot()
+            .track_deltas()
+            .is_empty());
+        assert!(!interest().read().is_empty());
+        assert!(!Interest::all_read_errors().is_empty());
+
+        let hangup_only = interest().edge_triggered().hangup_only();
+        assert!(!hangup_only.is_empty());
+        assert_eq!(hangup_only.bitflags(), EpollFlags::EPOLLET);
+        assert!(hangup_only.remove_hangup_only().is_empty());
+    }
+
+    #[test]
+    fn presets_have_exactly_their_flags() {
+        use EpollFlags as F;
+
+        const CASES: [(Interest, EpollFlags); 5] = [
+            (Interest::stream_read(), F::EPOLLIN.union(F::EPOLLRDHUP)),
+            (
+                Interest::stream_read_et(),
+                F::EPOLLIN.union(F::EPOLLRDHUP).union(F::EPOLLET),
+            ),
+            (
+                Interest::stream_read_write(),
+                F::EPOLLIN.union(F::EPOLLOUT).union(F::EPOLLRDHUP),
+            ),
+            (
+                Interest::stream_read_write_et(),
+                F::EPOLLIN
+                    .union(F::EPOLLOUT)
+                    .union(F::EPOLLRDHUP)
+                    .union(F::EPOLLET),
+            ),
+            (
+                Interest::all_read_errors(),
+                F::EPOLLRDHUP.union(F::EPOLLHUP).union(F::EPOLLERR),
+            ),
+        ];
+        for (interest, flags) in CASES {
+            assert_eq!(interest.bitflags(), flags);
+        }
+    }
+
+    #[test]
+    fn union_and_contains() {
+        const READ_ET: Interest = interest().read().union(interest().edge_triggered());
+        assert_eq!(READ_ET, interest().read().edge_triggered());
+        assert!(READ_ET.contains(interest().read()));
+        assert!(READ_ET.contains(interest()));
+        assert!(!READ_ET.contains(interest().read().write()));
+        assert!(Interest::stream_read_et().contains(READ_ET));
+    }
+
+    #[test]
+    fn custom_bits_reach_the_kernel_as_given() {
+        const UNKNOWN: u32 = 1 << 14;
+        let custom = interest().read().with_custom(UNKNOWN);
+        assert_eq!(custom.as_raw(), EpollFlags::EPOLLIN.bits() as u32 | UNKNOWN);
+        assert_eq!(Interest::from_raw(custom.as_raw()), custom);
+        assert!(custom.contains(Interest::custom(UNKNOWN)));
+        assert_eq!(
+            Interest::custom(EpollFlags::EPOLLMSG.bits() as u32),
+            Interest::new(EpollFlags::EPOLLMSG)
+        );
+        // Still checked next to `EPOLLEXCLUSIVE`.
+        assert!(custom.exclusive().validate().is_err());
+
+        // Not taken for settings of the loop, such as `EPOLL_URING_WAKE`.
+        const URING_WAKE: u32 = 1 << 27;
+        for raw in [URING_WAKE, 0xff << 16, 0xf << 24] {
+            let custom = interest().read().with_custom(raw);
+            assert_eq!(custom.as_raw(), EpollFlags::EPOLLIN.bits() as u32 | raw);
+            assert!(!custom.tracks_deltas() && !custom.tracks_last_event());
+            assert!(!custom.emulates_writable_edge());
+            assert_eq!(custom.dispatch_rank(), Interest::DEFAULT_PRIORITY);
+            assert_eq!(custom.remove_hangup_only(), custom);
+        }
+    }
+
+    #[test]
+    fn track_deltas_never_reaches_the_kernel() {
+        let interest = Interest::stream_read_write_et().track_deltas();
+        assert!(interest.tracks_deltas());
+        assert_eq!(
+            interest.bitflags(),
+            Interest::stream_read_write_et().bitflags()
+        );
+        assert!(!interest.remove_track_deltas().tracks_deltas());
+    }
+
+    #[test]
+    fn track_last_event_never_reaches_the_kernel() {
+        let interest = Interest::stream_read_write_et().track_last_event();
+        assert!(interest.tracks_last_event() && !interest.tracks_deltas());
+        assert_eq!(
+            interest.bitflags(),
+            Interest::stream_read_write_et().bitflags()
+        );
+        assert!(!interest.remove_track_last_event().tracks_last_event());
+    }
+
+    #[test]
+    fn writable_edge_emulation_never_reaches_the_kernel() {
+        let interest = Interest::stream_read_write().writable_edge_emulation();
+        assert!(interest.emulates_writable_edge());
+        assert_eq!(
+            interest.bitflags(),
+            Interest::stream_read_write().bitflags()
+        );
+        assert!(!interest
+            .remove_writable_edge_emulation()
+            .emulates_writable_edge());
+    }
+
+    #[test]
+    fn dispatch_priority_round_trips_outside_the_kernel_flags() {
+        assert_eq!(
+            interest().read().dispatch_rank(),
+            Interest::DEFAULT_PRIORITY
+        );
+        for priority in [0, 1, 127, 128, 255] {
+            let interest = Interest::stream_read_et().dispatch_priority(priority);
+            assert_eq!(interest.dispatch_rank(), priority);
+            assert_eq!(interest.bitflags(), Interest::stream_read_et().bitflags());
+        }
+        let replaced = interest().dispatch_priority(0).dispatch_priority(200);
+        assert_eq!(replaced.dispatch_rank(), 200);
+        assert_eq!(replaced.union(interest().read()).dispatch_rank(), 200);
+        assert_eq!(
+            interest()
+                .union(interest().dispatch_priority(3))
+                .dispatch_rank(),
+            3
+        );
+        assert_eq!(
+            interest().dispatch_priority(Interest::DEFAULT_PRIORITY),
+            interest()
+        );
+    }
+
+    #[test]
+    fn idle_timeout_is_kept_in_whole_milliseconds() {
+        let idle = Interest::stream_read().idle_timeout(Duration::from_micros(1500));
+        assert_eq!(idle.idle_millis(), 2);
+        assert_eq!(idle.bitflags(), Interest::stream_read().bitflags());
+        assert_eq!(
+            idle.union(interest().write()).idle_millis(),
+            idle.idle_millis()
+        );
+        assert_eq!(
+            interest().idle_timeout(Duration::MAX).idle_millis(),
+            u32::MAX
+        );
+        assert_eq!(idle.remove_idle_timeout(), Interest::stream_read());
+    }
+
+    #[test]
+    fn user_data_never_reaches_the_kernel() {
+        let tagged = Interest::stream_read().user_data(0xBEEF);
+        assert_eq!(tagged.user_data_value(), 0xBEEF);
+        assert_eq!(tagged.as_raw(), Interest::stream_read().as_raw());
+        assert_eq!(tagged.union(interest().write()).user_data_value(), 0xBEEF);
+        assert_eq!(tagged.union(interest().user_data(7)).user_data_value(), 7);
+        // Kept by `modify` unless replaced.
+        assert_eq!(
+            interest().write().or_user_data_of(tagged).user_data_value(),
+            0xBEEF
+        );
+        assert_eq!(
+            interest()
+                .user_data(7)
+                .or_user_data_of(tagged)
+                .user_data_value(),
+            7
+        );
+    }
+
+    #[test]
+    fn validate_checks_exclusive_and_wakeup() {
+        assert!(Interest::stream_read_write_et()
+            .oneshot()
+            .validate()
+            .is_ok());
+        assert!(interest().read_write().exclusive().validate().is_ok());
+
+        let err =
+            Interest::new(EpollFlags::EPOLLIN | EpollFlags::EPOLLPRI | EpollFlags::EPOLLEXCLUSIVE)
+                .validate()
+                .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::ExclusiveIncompatible {
+                flags: EpollFlags::EPOLLPRI
+            })
+        );
+
+        #[cfg(not(target_arch = "mips"))]
+        {
+            assert!(interest().read().wakeup().validate().is_ok());
+            let err = interest()
+                .read()
+                .wakeup()
+                .oneshot()
+                .edge_triggered()
+                .validate()
+                .unwrap_err();
+            assert_eq!(
+                Error::from_io(&err),
+                Some(&Error::WakeupIneffective {
+                    flags: EpollFlags::EPOLLONESHOT | EpollFlags::EPOLLET
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn exclusive_interest_converts_both_ways() {
+        let exclusive = ExclusiveInterest::new().read().edge_triggered();
+        assert_eq!(
+            Interest::from(exclusive),
+            interest().read().edge_triggered().exclusive()
+        );
+        assert_eq!(
+            ExclusiveInterest::try_from(interest().read().edge_triggered()).unwrap(),
+            exclusive
+        );
+        assert!(ExclusiveInterest::try_from(Interest::stream_read()).is_err());
+        assert!(exclusive.interest().validate().is_ok());
+    }
+
+    #[test]
+    fn raw_mask_has_only_the_epoll_flags() {
+        let interest = Interest::stream_read_et()
+            .track_deltas()
+            .dispatch_priority(0)
+            .idle_timeout(Duration::from_secs(1));
+        let raw = interest.as_raw();
+        assert_eq!(raw, Interest::stream_read_et().bitflags().bits() as u32);
+        assert_eq!(Interest::from_raw(raw), Interest::stream_read_et());
+        assert_eq!(
+            Interest::from_raw(interest.bitflags().bits() as u32),
+            Interest::from_raw(raw)
+        );
+    }
+}
@@ -4,10 +4,19 @@
 //!
 //! # Platform support
 //!
-//! Linux only, on 64-bit targets. Non-Linux and non-64-bit platforms are
-//! rejected at compile time.
+//! Linux only, on 64-bit targets. Non-64-bit platforms are rejected at compile
+//! time.
 //!
-//! Tested in CI on `x86_64` and `aarch64`.
+//! On other 64-bit platforms, such as macOS, only the portable core is built, to
+//! compile and unit test handlers during development: [`Interest`], [`Event`], the
+//! [`Subscriber`] and [`Handler`](subscriber::Handler) traits,
+//! [`TriSubscriber`](tri_subscriber::TriSubscriber) and its handlers taking their
+//! parameters in any order, [`Pinned`] and [`pinned!`], and [`MockEventp`]. The
+//! loop and everything needing epoll or other Linux APIs is left out, and
+//! [`epoll::EpollFlags`] and [`epoll::EpollEvent`] are defined by this crate, with
+//! the values of Linux.
+//!
+//! Tested in CI on `x86_64` and `aarch64`, and the portable core on macOS.
 //!
 //! # Motivation
 //!
@@ -61,7 +70,7 @@
 //! # Concepts
 //!
 //! 1.  **The [`Eventp`] Reactor**: The central event loop that manages all I/O sources.
-//! 2.  **The [`Subscriber`]**: A combination of an I/O source (anything that is [`AsFd`](std::os::fd::AsFd)),
+//! 2.  **The [`Subscriber`]**: A combination of an I/O source (anything that is [`AsFd`]),
 //!     its event [`Interest`] (e.g., readable, writable), and a [`Handler`](subscriber::Handler) function.
 //!     -   [`Interest`] vs [`Event`]: Both wrap [`EpollFlags`]. [`Interest`] is what you ask the OS to
 //!         monitor (e.g., `EPOLLIN`). [`Event`] is what the OS reports back (e.g., `EPOLLIN | EPOLLHUP`).
@@ -73,9 +82,47 @@
 //!
 //! -   [`tri_subscriber`]: The helper subscriber constructed by the builder-like API starting from
 //!     [`interest()`], where is the **recommended** API entry point.
+//! -   [`mod@acceptor`]: The accept loop of a listener, registering every connection with a
+//!     handler built for it, from any listener implementing [`net::Accept`].
 //! -   [`mod@remote_endpoint`]: <span class="stab portability" title="Available on crate feature `remote-endpoint` only"><code>remote-endpoint</code></span>
 //!     A remote control for an `Eventp` instance running on another thread, allows sending closures
 //!     to the `Eventp` thread to be executed.
+//! -   [`spawn_loop`]: <span class="stab portability" title="Available on crate feature `remote-endpoint` only"><code>remote-endpoint</code></span>
+//!     An `Eventp` run on a thread of its own, set up there, reached with its remote endpoint,
+//!     then stopped and joined.
+//! -   [`Waker`]: Wakes an `Eventp` from another thread, optionally running a callback on it,
+//!     without the channels of `remote_endpoint`.
+//! -   [`timer_wheel`]: Any number of cheap timeouts, such as one per connection, on a
+//!     single `timerfd`.
+//! -   [`multi_fd`]: One handler object watching several fds, registered with
+//!     [`Eventp::add_group`].
+//! -   [`ChildEventp`]: An `Eventp` registered into another one, which dispatches its
+//!     events when any of its fds is ready.
+//! -   [`process`]: Waits for a child process to exit through its pidfd, handing out the
+//!     exit status.
+//! -   [`mod@event_stream`]: Sends every event of an fd into a channel, for consumers outside
+//!     the loop.
+//! -   [`dgram`]: Receives every datagram of a UDP or unix datagram socket, into a buffer of
+//!     its exact length.
+//! -   [`oob`]: Reads the urgent byte of TCP sockets, reported as `EPOLLPRI`.
+//! -   [`write_queue`]: Queues the bytes a stream would block writing, requesting
+//!     `EPOLLOUT` only until they are written.
+//! -   [`exclusive`]: One shared fd, such as a listener, registered with several loops
+//!     using `EPOLLEXCLUSIVE`.
+//! -   [`mod@fd_receiver`]: <span class="stab portability" title="Available on crate feature `fd-receiver` only"><code>fd-receiver</code></span>
+//!     Receives fds sent over a unix socket with `SCM_RIGHTS`, and hands each one to a handler.
+//! -   [`inotify`]: <span class="stab portability" title="Available on crate feature `inotify` only"><code>inotify</code></span>
+//!     Watches files and directories, handing out each event with the watched path.
+//! -   [`netlink`]: <span class="stab portability" title="Available on crate feature `netlink` only"><code>netlink</code></span>
+//!     Monitors network interfaces and their addresses, handing out each change decoded.
+//! -   [`vsock`]: <span class="stab portability" title="Available on crate feature `vsock` only"><code>vsock</code></span>
+//!     `AF_VSOCK` listeners and streams for virtual machines, accepted by [`mod@acceptor`].
+//! -   [`async_bridge`]: <span class="stab portability" title="Available on crate feature `async-bridge` only"><code>async-bridge</code></span>
+//!     Driving an `Eventp` from an async runtime, through the readiness of its epoll fd.
+//! -   [`mio_compat`]: <span class="stab portability" title="Available on crate feature `mio-compat` only"><code>mio-compat</code></span>
+//!     Adapters for migrating from mio: `Source` types as fds, and `Token`-based handlers.
+//! -   [`uring`]: <span class="stab portability" title="Available on crate feature `uring` only"><code>uring</code></span>
+//!     An experimental reactor waiting with io_uring poll requests, running the same handlers.
 //!
 //! # Testability and Type Hierarchy
 //!
@@ -97,23 +144,105 @@
 #![warn(missing_docs)]
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(rustdoc::private_intra_doc_links)]
+// The internals of the portable core that only `Eventp` uses.
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
 
+pub mod acceptor;
+#[cfg(all(target_os = "linux", feature = "async-bridge"))]
+pub mod async_bridge;
+#[cfg(target_os = "linux")]
+mod builder;
+#[cfg(target_os = "linux")]
+mod child;
+#[cfg(all(test, target_os = "linux"))]
+mod conformance;
+mod deferred;
+#[cfg(target_os = "linux")]
+pub mod dgram;
+mod error;
 mod event;
+#[cfg(target_os = "linux")]
+mod event_iter;
+#[cfg(target_os = "linux")]
+mod event_log;
+#[cfg(target_os = "linux")]
+pub mod event_stream;
 mod eventp_ops;
+#[cfg(target_os = "linux")]
+pub mod exclusive;
+#[cfg(target_os = "linux")]
+mod factory;
+#[cfg(all(target_os = "linux", feature = "fd-receiver"))]
+pub mod fd_receiver;
+#[cfg(target_os = "linux")]
+mod fork;
+#[cfg(target_os = "linux")]
+mod idle;
+#[cfg(all(target_os = "linux", feature = "inotify"))]
+pub mod inotify;
 mod interest;
+#[cfg(all(target_os = "linux", feature = "mio-compat"))]
+pub mod mio_compat;
 #[cfg(feature = "mock")]
 pub mod mock;
+#[cfg(target_os = "linux")]
+pub mod multi_fd;
+pub mod net;
+#[cfg(all(target_os = "linux", feature = "netlink"))]
+pub mod netlink;
+#[cfg(target_os = "linux")]
+pub mod oob;
+#[cfg(all(target_os = "linux", feature = "debug-ownership"))]
+mod ownership;
 mod pinned;
-#[cfg(feature = "remote-endpoint")]
+#[cfg(not(target_os = "linux"))]
+mod portable_epoll;
+#[cfg(target_os = "linux")]
+pub mod process;
+#[cfg(target_os = "linux")]
+mod registration;
+#[cfg(all(target_os = "linux", feature = "remote-endpoint"))]
 pub mod remote_endpoint;
+#[cfg(target_os = "linux")]
+pub mod replay;
+#[cfg(target_os = "linux")]
+mod run;
+#[cfg(target_os = "linux")]
+mod scope;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(all(target_os = "linux", feature = "remote-endpoint"))]
+mod spawn;
+#[cfg(target_os = "linux")]
+mod stats;
+#[cfg(target_os = "linux")]
+mod storage;
+#[cfg(target_os = "linux")]
+mod storm;
 pub mod subscriber;
 pub mod thin;
+#[cfg(target_os = "linux")]
+pub mod timer_wheel;
 pub mod tri_subscriber;
+#[cfg(all(target_os = "linux", feature = "uring"))]
+pub mod uring;
 mod utils;
+#[cfg(all(target_os = "linux", feature = "vsock"))]
+pub mod vsock;
+#[cfg(target_os = "linux")]
+mod waker;
+pub mod write_queue;
 
 pub mod epoll {
     //! Re-exports of epoll related types from the [`nix` crate](nix::sys::epoll).
+    //!
+    //! On other platforms, only [`EpollFlags`] and [`EpollEvent`] exist, defined by
+    //! this crate with the values of Linux, see [Platform support](crate#platform-support).
+    #[cfg(target_os = "linux")]
     pub use nix::sys::epoll::{Epoll, EpollCreateFlags, EpollEvent, EpollFlags, EpollTimeout};
+
+    #[cfg(not(target_os = "linux"))]
+    pub use crate::portable_epoll::{EpollEvent, EpollFlags};
 }
 
 #[cfg(docsrs)]
@@ -126,27 +255,179 @@ pub mod _technical_zh {
     #![doc = include_str!("../docs/technical.zh.md")]
 }
 
-use std::marker::PhantomPinned;
-use std::mem::{self, ManuallyDrop, MaybeUninit};
-use std::os::fd::{AsRawFd, RawFd};
+#[cfg(target_os = "linux")]
+use std::any::Any;
+#[cfg(target_os = "linux")]
+use std::cell::RefCell;
+#[cfg(target_os = "linux")]
+use std::collections::VecDeque;
+#[cfg(target_os = "linux")]
+use std::marker::{PhantomData, PhantomPinned};
+#[cfg(target_os = "linux")]
+use std::mem::{self, MaybeUninit};
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+#[cfg(target_os = "linux")]
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(target_os = "linux")]
 use std::pin::Pin;
-use std::{hint, io, ptr};
+#[cfg(target_os = "linux")]
+use std::rc::Rc;
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+#[cfg(target_os = "linux")]
+use std::{hint, io, iter, ptr, thread};
 
-use rustc_hash::FxHashMap;
+#[cfg(target_os = "linux")]
+use rustc_hash::{FxHashMap, FxHashSet};
 
+pub use crate::acceptor::acceptor;
+#[cfg(target_os = "linux")]
+pub use crate::builder::{
+    Builder, DeregisterCallback, DeregisterReason, DispatchOrder, ErrorPolicy, RegisterCallback,
+    SlowHandlerCallback, StormAction,
+};
+#[cfg(target_os = "linux")]
+use crate::builder::{Lifecycle, SlowHandler};
+#[cfg(target_os = "linux")]
+pub use crate::child::{ChildEventp, ChildGuard};
+pub use crate::deferred::{Deferred, MAX_DEFER_DEPTH};
+#[cfg(target_os = "linux")]
 use crate::epoll::*;
-pub use crate::event::Event;
-pub use crate::eventp_ops::{EventpOps, EventpOpsAdd};
-pub use crate::interest::{interest, Interest};
+pub use crate::error::Error;
+pub use crate::event::{BatchInfo, Event, EventDelta};
+#[cfg(target_os = "linux")]
+pub use crate::event_iter::EventIter;
+#[cfg(target_os = "linux")]
+use crate::event_log::EventLog;
+#[cfg(target_os = "linux")]
+pub use crate::event_log::LoggedEvent;
+#[cfg(target_os = "linux")]
+pub use crate::event_stream::event_stream;
+pub use crate::eventp_ops::{AddError, EventpOps, EventpOpsAdd, EventpOpsCtl};
+#[cfg(target_os = "linux")]
+pub use crate::factory::{RegisterPolicy, RegisteredReport, SubscriberFactory};
+#[cfg(all(target_os = "linux", feature = "fd-receiver"))]
+pub use crate::fd_receiver::fd_receiver;
+#[cfg(target_os = "linux")]
+pub use crate::fork::ForkBlueprint;
+#[cfg(target_os = "linux")]
+use crate::idle::IdleTimers;
+pub use crate::interest::{interest, ExclusiveInterest, Interest};
 #[cfg(feature = "mock")]
 pub use crate::mock::MockEventp;
-pub use crate::pinned::Pinned;
-#[cfg(feature = "remote-endpoint")]
+#[cfg(target_os = "linux")]
+use crate::multi_fd::{GroupMembers, Member, MultiFdSubscriber};
+pub use crate::pinned::{Pinned, ViewOps};
+#[cfg(target_os = "linux")]
+use crate::registration::Released;
+#[cfg(all(target_os = "linux", feature = "remote-endpoint"))]
+pub use crate::registration::RemoteRegistration;
+#[cfg(target_os = "linux")]
+pub use crate::registration::{Registration, Registry};
+#[cfg(all(target_os = "linux", feature = "remote-endpoint"))]
 pub use crate::remote_endpoint::remote_endpoint;
-pub use crate::subscriber::Subscriber;
+#[cfg(target_os = "linux")]
+use crate::replay::{Capture, Keys};
+#[cfg(target_os = "linux")]
+use crate::run::Exit;
+#[cfg(target_os = "linux")]
+pub use crate::run::{AlreadyRunning, DrainSummary, RunOutcome};
+#[cfg(target_os = "linux")]
+pub use crate::scope::{scope, Scope};
+#[cfg(all(target_os = "linux", feature = "remote-endpoint"))]
+pub use crate::spawn::{spawn_loop, LoopHandle};
+#[cfg(target_os = "linux")]
+pub use crate::stats::Stats;
+#[cfg(target_os = "linux")]
+use crate::storage::Registered;
+#[cfg(target_os = "linux")]
+use crate::storm::ErrorStorms;
+#[cfg(feature = "send-subscribers")]
+pub use crate::subscriber::SendSubscriber;
+pub use crate::subscriber::{Subscriber, SubscriberHandle};
+#[cfg(target_os = "linux")]
 use crate::thin::ThinBoxSubscriber;
+#[cfg(target_os = "linux")]
+use crate::utils::unlikely;
+#[cfg(target_os = "linux")]
+pub use crate::waker::Waker;
+
+#[cfg(target_os = "linux")]
+const DEFAULT_EVENT_BUF_CAPACITY: usize = 256;
+
+/// The largest event buffer capacity [`Eventp::new`] accepts, far beyond what one
+/// `epoll_wait` call usefully returns.
+#[cfg(target_os = "linux")]
+pub const MAX_EVENT_BUF_CAPACITY: usize = 1 << 20;
+
+/// Converts the time left until a deadline into a timeout, rounding up to whole
+/// milliseconds and clamping to [`EpollTimeout::MAX`].
+#[cfg(target_os = "linux")]
+fn timeout_for(remaining: Duration) -> EpollTimeout {
+    let millis = (remaining.as_nanos() + 999_999) / 1_000_000;
+    EpollTimeout::try_from(millis).unwrap_or(EpollTimeout::MAX)
+}
+
+/// Returns the data words of the registrations of the epoll `epfd`, as read from
+/// its fdinfo, see proc_pid_fdinfo(5), or `None` if that cannot be read.
+#[cfg(target_os = "linux")]
+fn watched_data(epfd: RawFd) -> Option<FxHashSet<u64>> {
+    let fdinfo = std::fs::read_to_string(format!("/proc/self/fdinfo/{epfd}")).ok()?;
+    fdinfo
+        .lines()
+        .filter(|line| line.starts_with("tfd:"))
+        .map(|line| {
+            let data = line
+                .split_whitespace()
+                .skip_while(|&word| word != "data:")
+                .nth(1)?;
+            u64::from_str_radix(data, 16).ok()
+        })
+        .collect()
+}
+
+/// Panics if the epoll `epfd` does not watch `fd` for `interest`, as read from its
+/// fdinfo, see proc_pid_fdinfo(5). Skipped if that cannot be read.
+#[cfg(all(target_os = "linux", debug_assertions))]
+fn assert_watched(epfd: RawFd, fd: RawFd, interest: Interest) {
+    let Ok(fdinfo) = std::fs::read_to_string(format!("/proc/self/fdinfo/{epfd}")) else {
+        return;
+    };
+    let watched = fdinfo
+        .lines()
+        .filter_map(|line| line.strip_prefix("tfd:"))
+        .find_map(|rest| {
+            let mut words = rest.split_whitespace();
+            if words.next()?.parse::<RawFd>().ok()? != fd {
+                return None;
+            }
+            let events = words.skip_while(|&word| word != "events:").nth(1)?;
+            u32::from_str_radix(events, 16).ok()
+        });
+    let Some(watched) = watched else {
+        return;
+    };
 
-const DEFAULT_EVENT_BUF_CAPACITY: usize = 512;
+    // The kernel always adds `EPOLLHUP | EPOLLERR`, drops `EPOLLWAKEUP` without
+    // `CAP_BLOCK_SUSPEND`, and disarms a fired `EPOLLONESHOT` down to its modes.
+    let always = (EpollFlags::EPOLLHUP | EpollFlags::EPOLLERR).bits() as u32;
+    let wakeup = EpollFlags::EPOLLWAKEUP.bits() as u32;
+    let modes =
+        (EpollFlags::EPOLLONESHOT | EpollFlags::EPOLLET | EpollFlags::EPOLLEXCLUSIVE).bits() as u32;
+    // `EPOLLOUT` may be parked by writable edge emulation.
+    let parked = match interest.emulates_writable_edge() {
+        true => EpollFlags::EPOLLOUT.bits() as u32,
+        false => 0,
+    };
+    let expected = (interest.bitflags().bits() as u32 | always) & !wakeup & !parked;
+    let watched = (watched | always) & !wakeup & !parked;
+    let disarmed = expected & (modes | always);
+    debug_assert!(
+        watched == expected || watched == disarmed,
+        "fd {fd} is watched for {watched:#x}, but the loop keeps {expected:#x}"
+    );
+}
 
 /// The central event loop reactor, built on top of Linux's `epoll`.
 ///
@@ -156,20 +437,226 @@ const DEFAULT_EVENT_BUF_CAPACITY: usize = 512;
 ///
 /// See the [crate-level documentation](crate) for a detailed overview of the design,
 /// motivation, and key concepts.
+///
+/// # Threads
+///
+/// `Eventp` is neither `Send` nor `Sync`. Subscribers need not be `Send`, e.g. to
+/// share an `Rc` between handlers, so the loop stays with them on the thread that
+/// created it. Other threads reach it through a [`Waker`] or a
+/// [`RemoteEndpoint`](remote_endpoint::RemoteEndpoint), or hand it subscribers
+/// boxed as [`SendThinBox`](thin::SendThinBox)es, with the `send-subscribers`
+/// feature.
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<eventp::Eventp>();
+/// ```
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<eventp::Eventp>();
+/// ```
+///
+/// # Drop
+///
+/// Dropping an `Eventp` drops every registered subscriber exactly once, and then
+/// closes the epoll. Subscribers deleted from inside a handler have already been
+/// dropped by the time [`run_once`](Eventp::run_once) returns.
+///
+/// # Ownership checks
+///
+/// Registering one fd with two `Eventp`s makes both loops dispatch its events,
+/// which is rarely intended. With the `debug-ownership` feature, meant for debug
+/// and test builds, [`add`](EventpOpsAdd::add) fails with
+/// [`Error::RegisteredElsewhere`] instead. Duplicates of an fd, as used by
+/// [`exclusive`], are not affected.
+#[cfg(target_os = "linux")]
 pub struct Eventp {
-    registered: FxHashMap<RawFd, ThinBoxSubscriber<Eventp>>,
+    // Declared before `epoll` so the subscribers, which may own the fds registered
+    // with it, are dropped first.
+    registered: Registered<ThinBoxSubscriber<Eventp>>,
+    /// The siblings of every fd registered by [`add_group`](Eventp::add_group).
+    groups: FxHashMap<RawFd, GroupMembers>,
     epoll: Epoll,
     event_buf: Vec<MaybeUninit<EpollEvent>>,
     handling: Option<Handling>,
+    /// The fd of the handler whose panic ended the last batch, for
+    /// [`run`](Eventp::run) to report.
+    panicked: Option<RawFd>,
+    /// Subscribers deleted by the handler of another one, dropped in place but
+    /// deallocated only at the end of the batch. Emptied after every batch, and
+    /// kept to reuse its allocation.
+    deferred_drop: Vec<ThinBoxSubscriber<Eventp>>,
+    /// Subscribers evicted because their fd was closed while registered, see
+    /// [`sweep`](Eventp::sweep). Dropped in place, but deallocated only once the
+    /// kernel no longer has their registration, which it keeps while the file is
+    /// still open through another fd, or with the loop.
+    evicted: Vec<ThinBoxSubscriber<Eventp>>,
+    error_policy: ErrorPolicy,
+    fair_dispatch: bool,
+    dispatch_order: DispatchOrder,
+    max_subscribers: Option<usize>,
+    max_subscriber_bytes: Option<usize>,
+    /// The sum of the [allocated bytes](ThinBoxSubscriber::allocated_bytes) of the
+    /// subscribers in `registered`.
+    subscriber_bytes: usize,
+    /// Where the next batch starts, modulo its length, if `fair_dispatch`.
+    dispatch_offset: usize,
+    /// The sequence number of the next batch, see [`BatchInfo::sequence`].
+    next_batch: u64,
+    /// Events left over by [`run_once_budgeted`](Eventp::run_once_budgeted), to be
+    /// dispatched before waiting again.
+    pending: Vec<EpollEvent>,
+    idle: IdleTimers,
+    /// See [`set_idle_callback`](Eventp::set_idle_callback).
+    idle_callback: Option<IdleCallback>,
+    /// A change of `idle_callback` made by a handler or the callback itself, applied
+    /// at the end of the batch.
+    idle_callback_update: Option<Option<IdleCallback>>,
+    /// See [`EventpOps::defer`].
+    deferred: VecDeque<Deferred<Eventp>>,
+    /// See [`Registration`].
+    released: Released,
+    stats: Stats,
+    /// The generation of the next subscriber added, see
+    /// [Generations](ThinBoxSubscriber#generations).
+    next_generation: u16,
+    /// The order of the next subscriber added, see [`shutdown`](Eventp::shutdown).
+    next_order: u32,
+    /// Set by [`shutdown`](Eventp::shutdown), which refuses new subscribers.
+    shutting_down: bool,
+    /// See [`enable_event_log`](Eventp::enable_event_log).
+    event_log: Option<EventLog>,
+    /// See [`enable_capture`](Eventp::enable_capture).
+    capture: Option<Capture>,
+    /// The registration numbers of [`replay`], if capturing or replaying.
+    keys: Option<Keys>,
+    /// See [`Builder::slow_handler_threshold`].
+    slow_handler: Option<SlowHandler>,
+    /// See [`Builder::reject_empty_interests`].
+    reject_empty_interests: bool,
+    /// See [`Builder::error_context`].
+    error_context: bool,
+    /// See [`Builder::debug_assert_nonblocking`].
+    debug_assert_nonblocking: bool,
+    /// See [`Builder::warn_disarmed_after`].
+    disarmed_warning: Option<u64>,
+    /// The oneshot subscribers disarmed since the last check, with their generation
+    /// and the batch of their event, if `disarmed_warning`.
+    disarmed: FxHashMap<RawFd, (u16, u64)>,
+    /// See [`Builder::error_storm_policy`].
+    error_storms: Option<ErrorStorms>,
+    /// See [`stop`](Eventp::stop).
+    stop_requested: bool,
+    /// The process the loop was created in, see [`prepare_fork`](Eventp::prepare_fork).
+    #[cfg(debug_assertions)]
+    pid: u32,
+    /// The registered fds no longer watched, see [`StormAction::Quarantine`].
+    quarantined: FxHashSet<RawFd>,
+    /// See [`Builder::on_register`] and [`Builder::on_deregister`].
+    lifecycle: Lifecycle,
+    #[cfg(feature = "debug-ownership")]
+    owner: ownership::Owner,
     _pinned: PhantomPinned,
+    /// Keeps `Eventp` `!Send` and `!Sync` whatever its other fields, see above.
+    _not_send: PhantomData<*mut ()>,
 }
 
+#[cfg(target_os = "linux")]
+type IdleCallback = Box<dyn FnMut(Pinned<'_, Eventp>)>;
+
+#[cfg(target_os = "linux")]
 struct Handling {
     fd: RawFd,
+    /// The interest of `fd`, kept in sync by `modify`.
+    interest: Interest,
+    /// The delta of the event being dispatched, if its interest tracks deltas.
+    delta: Option<EventDelta>,
+    /// The time since the previous event of `fd`, if its interest tracks it.
+    since_last: Option<Duration>,
+    /// Where the event being dispatched stands in the batch.
+    batch: BatchInfo,
     drop_current: bool,
-    deferred_drop: Vec<ThinBoxSubscriber<Eventp>>,
+    /// Set along with `drop_current` if `fd` was closed while registered, for the
+    /// subscriber to go to `Eventp::evicted`.
+    evict_current: bool,
+    /// Set along with `drop_current`, to report once the subscriber is dropped, or
+    /// `None` if its deregistration was reported already.
+    reason_current: Option<DeregisterReason>,
+    /// The first error reported by a handler under [`ErrorPolicy::Propagate`], with
+    /// its fd.
+    error: Option<(RawFd, io::Error)>,
+}
+
+/// Same as [`Epoll::wait`], but checks in debug builds that the epoll fd was not
+/// closed from outside the loop, and adds the context of [`Builder::error_context`]
+/// if `context`.
+#[cfg(target_os = "linux")]
+fn wait(
+    epoll: &Epoll,
+    events: &mut [EpollEvent],
+    timeout: EpollTimeout,
+    context: bool,
+) -> io::Result<usize> {
+    epoll.wait(events, timeout).map_err(|errno| {
+        // `EBADF` once closed, `EINVAL` once reused by another file; `events` is
+        // never empty.
+        debug_assert!(
+            !matches!(errno, nix::errno::Errno::EBADF | nix::errno::Errno::EINVAL),
+            "the epoll fd {} was closed behind the loop's back",
+            epoll.0.as_raw_fd()
+        );
+        if !context {
+            return errno.into();
+        }
+        Error::Syscall {
+            op: "epoll_wait",
+            fd: epoll.0.as_raw_fd(),
+            name: None,
+            errno: errno as i32,
+        }
+        .into()
+    })
+}
+
+/// Refuses an edge-triggered `fd` without `O_NONBLOCK`, unless a regular file, see
+/// [`Builder::debug_assert_nonblocking`]. Other failures are left to `epoll_ctl`.
+#[cfg(target_os = "linux")]
+fn check_nonblocking(fd: RawFd, interest: Interest) -> Result<(), Error> {
+    if !interest.bitflags().contains(EpollFlags::EPOLLET) {
+        return Ok(());
+    }
+    // SAFETY: `F_GETFL` takes no pointer.
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 || flags & libc::O_NONBLOCK != 0 {
+        return Ok(());
+    }
+    // SAFETY: All zeroes is a valid `stat`, for `fstat` to write into.
+    let mut stat: libc::stat = unsafe { mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } == 0 && stat.st_mode & libc::S_IFMT == libc::S_IFREG {
+        return Ok(());
+    }
+    Err(Error::BlockingFd { fd })
+}
+
+/// Formats `fd` for diagnostics, with the name of its subscriber, if any.
+#[cfg(all(target_os = "linux", feature = "log"))]
+fn describe_fd(fd: RawFd, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("fd {fd} ({name})"),
+        None => format!("fd {fd}"),
+    }
 }
 
+#[cfg(target_os = "linux")]
+impl AsFd for Eventp {
+    /// Same as [`poll_fd`](Eventp::poll_fd).
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.poll_fd()
+    }
+}
+
+#[cfg(target_os = "linux")]
 impl Default for Eventp {
     /// Creates a new `Eventp` with an event buffer capacity of 256 and the
     /// `EPOLL_CLOEXEC` flag set.
@@ -177,13 +664,13 @@ impl Default for Eventp {
     /// # Panics
     ///
     /// Panics if the underlying `epoll_create1` syscall fails. Use
-    /// [`Eventp::new`] if you need to handle that error.
+    /// [`Eventp::try_default`] if you need to handle that error.
     fn default() -> Self {
-        Self::new(DEFAULT_EVENT_BUF_CAPACITY, EpollCreateFlags::EPOLL_CLOEXEC)
-            .expect("Failed to create epoll instance")
+        Self::try_default().unwrap_or_else(|e| panic!("Failed to create epoll instance: {e}"))
     }
 }
 
+#[cfg(target_os = "linux")]
 impl Eventp {
     /// Creates a new `Eventp` instance with a specified event buffer
     /// capacity and `epoll_create1` flags.
@@ -194,840 +681,7150 @@ impl Eventp {
     ///
     /// # Errors
     ///
-    /// Returns the underlying `io::Error` if `epoll_create1` fails.
+    /// - [`io::ErrorKind::InvalidInput`] if `capacity` is zero, or above
+    ///   [`MAX_EVENT_BUF_CAPACITY`].
+    /// - Otherwise, the underlying `io::Error` if `epoll_create1` fails, as an
+    ///   [`Error::ResourceExhausted`] for `EMFILE` and `ENFILE`.
+    pub fn new(capacity: usize, flags: EpollCreateFlags) -> io::Result<Self> {
+        Self::builder().capacity(capacity).flags(flags).build()
+    }
+
+    /// Same as [`Eventp::default`], but returns the error of `epoll_create1`
+    /// instead of panicking, e.g. to degrade gracefully when out of fds.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics if `capacity` is zero.
-    pub fn new(capacity: usize, flags: EpollCreateFlags) -> io::Result<Self> {
-        assert!(capacity > 0, "Capacity must be greater than zero");
+    /// - [`Error::ResourceExhausted`] for `EMFILE` and `ENFILE`, with the fd limit
+    ///   of the process and its open fds.
+    /// - Otherwise, the underlying `io::Error`.
+    pub fn try_default() -> io::Result<Self> {
+        Self::new(DEFAULT_EVENT_BUF_CAPACITY, EpollCreateFlags::EPOLL_CLOEXEC)
+    }
+
+    /// Returns a [`Builder`] to configure a new `Eventp`.
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    fn from_builder(builder: Builder) -> io::Result<Self> {
+        let Builder {
+            capacity,
+            flags,
+            error_policy,
+            fair_dispatch,
+            dispatch_order,
+            max_subscribers,
+            fixed_capacity,
+            max_subscriber_bytes,
+            slow_handler,
+            reject_empty_interests,
+            error_context,
+            debug_assert_nonblocking,
+            disarmed_warning,
+            error_storms,
+            lifecycle,
+        } = builder;
+        // `epoll_wait` rejects a zero-length buffer with `EINVAL`, which would
+        // only be reported by the first wait.
+        if !(1..=MAX_EVENT_BUF_CAPACITY).contains(&capacity) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("event buffer capacity {capacity} not in 1..={MAX_EVENT_BUF_CAPACITY}"),
+            ));
+        }
+        let registered = match fixed_capacity {
+            Some(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "fixed capacity of zero subscribers",
+                ))
+            }
+            Some(n) => Registered::fixed(n),
+            None => Registered::default(),
+        };
 
-        let mut buf = Vec::with_capacity(capacity);
-        // SAFETY: `Vec::set_len` requires `new_len <= capacity` and that the
-        //         first `new_len` elements be initialized. The element type
-        //         here is `MaybeUninit<EpollEvent>`, for which any bit pattern
-        //         (including uninit) is a valid value, so the second condition
-        //         is trivially satisfied.
-        unsafe { buf.set_len(capacity) };
+        let mut buf = Vec::new();
+        buf.resize_with(capacity, MaybeUninit::uninit);
 
         Ok(Self {
-            epoll: Epoll::new(flags).map_err(io::Error::from)?,
-            registered: Default::default(),
+            epoll: Epoll::new(flags).map_err(|e| Error::exhausted(e.into()))?,
+            registered,
+            groups: Default::default(),
             event_buf: buf,
             handling: None,
+            panicked: None,
+            deferred_drop: Vec::new(),
+            evicted: Vec::new(),
+            error_policy,
+            fair_dispatch,
+            dispatch_order,
+            max_subscribers,
+            max_subscriber_bytes,
+            subscriber_bytes: 0,
+            dispatch_offset: 0,
+            next_batch: 0,
+            pending: Vec::new(),
+            idle: IdleTimers::new(),
+            idle_callback: None,
+            idle_callback_update: None,
+            deferred: VecDeque::new(),
+            released: Released::default(),
+            stats: Stats::default(),
+            next_generation: 0,
+            next_order: 0,
+            shutting_down: false,
+            event_log: None,
+            capture: None,
+            keys: None,
+            slow_handler,
+            reject_empty_interests,
+            error_context,
+            debug_assert_nonblocking,
+            disarmed_warning,
+            disarmed: Default::default(),
+            error_storms,
+            quarantined: Default::default(),
+            lifecycle,
+            stop_requested: false,
+            #[cfg(debug_assertions)]
+            pid: std::process::id(),
+            #[cfg(feature = "debug-ownership")]
+            owner: ownership::Owner::new(),
             _pinned: PhantomPinned,
+            _not_send: PhantomData,
         })
     }
 
-    /// Consumes the `Eventp`, returning the underlying [`Epoll`] handle and
-    /// the registry of subscribers, keyed by their raw file descriptor.
-    pub fn into_inner(self) -> (Epoll, impl Iterator<Item = ThinBoxSubscriber<Eventp>>) {
+    /// Consumes the `Eventp`, returning the underlying [`Epoll`] handle and the
+    /// subscribers that were registered with it.
+    ///
+    /// Every fd is deregistered from the epoll first, so the returned handle holds
+    /// no registrations whose data words point into the returned subscribers, and
+    /// the two can be used or dropped in any order. Deregistration is best effort:
+    /// an fd that can no longer be deleted, e.g. because the subscriber's owner
+    /// already closed it, is skipped.
+    ///
+    /// The memory of the subscribers [evicted](Self::sweep) but still registered,
+    /// as their file is open through another fd, is leaked: the returned handle
+    /// may still report their events, with data words pointing into it.
+    pub fn into_parts(self) -> (Epoll, impl Iterator<Item = ThinBoxSubscriber<Eventp>>) {
+        for &fd in self.registered.keys() {
+            // SAFETY: Same as in `delete`. The result is ignored, see the docs.
+            unsafe {
+                libc::epoll_ctl(
+                    self.epoll.0.as_raw_fd(),
+                    libc::EPOLL_CTL_DEL,
+                    fd,
+                    ptr::null_mut(),
+                );
+            }
+        }
+
+        // Their registrations may outlive the deregistration above, along with
+        // the epoll.
+        mem::forget(self.evicted);
         (self.epoll, self.registered.into_values())
     }
 
-    /// Returns a reference to the subscriber corresponding to the raw fd.
-    pub fn get(&self, raw_fd: &RawFd) -> Option<&dyn Subscriber<Eventp>> {
-        self.registered.get(raw_fd).and_then(|s| s.try_deref())
+    /// Returns the counters of the dispatch loop so far.
+    pub fn stats(&self) -> Stats {
+        self.stats
     }
 
-    /// Returns a mutable reference to the subscriber corresponding to the raw fd.
-    pub fn get_mut(&mut self, raw_fd: &RawFd) -> Option<&mut dyn Subscriber<Eventp>> {
-        self.registered
-            .get_mut(raw_fd)
-            .and_then(|s| s.try_deref_mut())
+    /// Restarts all [`Stats`] counters from zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
     }
 
-    /// Runs the event loop until a non-`EINTR` error occurs.
+    /// Starts recording every dispatch, with its fd, event and the time spent in
+    /// its handler, into a ring of the latest `capacity` ones. Meant for debugging
+    /// and tests of dispatch order, read back with [`event_log`](Self::event_log).
     ///
-    /// This is the typical entry point for starting the event loop. It
-    /// repeatedly calls [`run_once`](Self::run_once); if `epoll_wait` is
-    /// interrupted by a signal (`EINTR` /
-    /// [`io::ErrorKind::Interrupted`]), the loop transparently retries.
+    /// The ring is allocated here, so recording never allocates; until the log is
+    /// enabled, it costs a branch per event. Replaces any log enabled before.
     ///
-    /// # Errors
+    /// ```rust
+    /// # use std::io;
+    /// use std::os::fd::{AsFd, AsRawFd};
     ///
-    /// Returns the first `io::Error` from `epoll_wait` that is not
-    /// [`io::ErrorKind::Interrupted`]. The function never returns `Ok(())`.
-    pub fn run_forever(&mut self) -> io::Result<()> {
-        loop {
-            match self.run_once() {
-                Ok(_) => continue,
-                // `epoll_wait` can be interrupted by a signal. This is not a fatal
-                // error, so we simply continue the loop.
-                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                Err(e) => return Err(e),
-            }
-        }
-    }
-
-    /// Performs one `epoll_wait` with no timeout and dispatches every ready
-    /// event to its handler.
+    /// use eventp::{tri_subscriber::WithHandler, Eventp, Subscriber};
+    /// use nix::sys::eventfd::EventFd;
     ///
-    /// Equivalent to calling
-    /// [`run_once_with_timeout`](Self::run_once_with_timeout) with
-    /// [`EpollTimeout::NONE`] (i.e. block indefinitely until at least one
-    /// event is ready, or until interrupted by a signal).
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// eventp.enable_event_log(64);
     ///
-    /// # Errors
+    /// let eventfd = EventFd::from_value(1)?;
+    /// let fd = eventfd.as_fd().as_raw_fd();
+    /// eventp::interest()
+    ///     .read()
+    ///     .with_fd(eventfd)
+    ///     .with_handler(|eventfd: &mut EventFd| {
+    ///         let _ = eventfd.read();
+    ///     })
+    ///     .register_into(&mut eventp)?;
     ///
-    /// Forwards any `io::Error` from `epoll_wait`, including
-    /// [`io::ErrorKind::Interrupted`] when the syscall is interrupted by a
-    /// signal. Use [`run_forever`](Self::run_forever) if you want `EINTR`
-    /// to be retried automatically.
+    /// eventp.run_once()?;
+    /// let log = eventp.event_log();
+    /// assert_eq!((log.len(), log[0].seq, log[0].fd), (1, 0, fd));
+    /// # Ok(()) }
+    /// ```
     ///
     /// # Panics
     ///
-    /// Panics if called recursively from within an event handler -- see
-    /// [`run_once_with_timeout`](Self::run_once_with_timeout).
-    pub fn run_once(&mut self) -> io::Result<()> {
-        self.run_once_with_timeout(EpollTimeout::NONE)
+    /// Panics if `capacity` is zero.
+    pub fn enable_event_log(&mut self, capacity: usize) {
+        self.event_log = Some(EventLog::new(capacity));
     }
 
-    /// Performs one `epoll_wait` with the given timeout and dispatches every
-    /// ready event to its handler.
+    /// Stops recording dispatches, and frees the log.
+    pub fn disable_event_log(&mut self) {
+        self.event_log = None;
+    }
+
+    /// Returns the dispatches recorded since the log was enabled or last cleared,
+    /// oldest first, or nothing if it is not enabled. Takes `&mut self` to lay the
+    /// ring out as one slice.
+    pub fn event_log(&mut self) -> &[LoggedEvent] {
+        match &mut self.event_log {
+            Some(log) => log.entries(),
+            None => &[],
+        }
+    }
+
+    /// Forgets the dispatches recorded so far, keeping the log enabled. The
+    /// [`seq`](LoggedEvent::seq) of the next ones goes on from the last.
+    pub fn clear_event_log(&mut self) {
+        if let Some(log) = &mut self.event_log {
+            log.clear();
+        }
+    }
+
+    /// Starts writing every batch of events waited for by the `run_*` methods into
+    /// `writer`, as delivered by the kernel, to be replayed by [`replay::run`].
+    ///
+    /// Each batch is written at once, before it is dispatched, so wrap a file in a
+    /// [`BufWriter`](std::io::BufWriter) only if losing the latest batches on a
+    /// crash is acceptable. Only the events of the subscribers added from here on
+    /// are captured, see [`replay`]. Replaces any capture enabled before, without
+    /// flushing it.
     ///
     /// # Errors
     ///
-    /// Forwards any `io::Error` from `epoll_wait`.
+    /// Forwards any error from writing the header of the capture. Errors writing
+    /// the batches end the capture, and are returned by
+    /// [`disable_capture`](Self::disable_capture).
+    pub fn enable_capture(&mut self, writer: impl io::Write + 'static) -> io::Result<()> {
+        self.capture = Some(Capture::new(Box::new(writer))?);
+        self.keys = Some(Keys::default());
+        Ok(())
+    }
+
+    /// Stops capturing, and flushes the writer.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics if called recursively (i.e. from within an event handler).
-    /// Recursing would corrupt the internal `handling` state and risk
-    /// invalidating iterators on the registry.
-    pub fn run_once_with_timeout(&mut self, timeout: EpollTimeout) -> io::Result<()> {
-        if let Some(handling) = &self.handling {
-            // Recursive calls would corrupt the `handling` state and could lead to
-            // iterator invalidation issues. This panic prevents such misuse.
-            panic!(
-                "Recursive call to `Eventp::run_once_with_timeout` while handling fd {}",
-                handling.fd
-            );
+    /// Returns the first error writing a batch, or forwards any error from
+    /// flushing.
+    pub fn disable_capture(&mut self) -> io::Result<()> {
+        self.keys = None;
+        match self.capture.take() {
+            Some(capture) => capture.finish(),
+            None => Ok(()),
         }
+    }
+
+    /// Numbers the subscribers added from here on, as
+    /// [`enable_capture`](Self::enable_capture) does, for [`replay::run`] to
+    /// dispatch a capture to them. Restarts the numbering if called again.
+    pub fn enable_replay(&mut self) {
+        self.keys = Some(Keys::default());
+    }
 
-        // SAFETY: `EpollEvent` is a POD wrapping `libc::epoll_event`, so any bit
-        // pattern is a valid `EpollEvent` value -- meaning `MaybeUninit<EpollEvent>`
-        // and `EpollEvent` have the same layout and the latter is sound to read
-        // even before the kernel writes into it. We immediately re-slice to the
-        // first `n` elements that `epoll_wait` actually wrote, so any consumer of
-        // `buf` only observes kernel-initialized entries.
-        let buf: &mut [MaybeUninit<EpollEvent>] = &mut self.event_buf;
-        let buf: &mut [EpollEvent] = unsafe { mem::transmute(buf) };
+    /// Sets the callback run when a wait of [`run_once`](Self::run_once) and its
+    /// variants returns no event, e.g. to flush buffered writes while there is no
+    /// I/O to do. It runs at most once per wait, after the idle timeouts of
+    /// [`Interest::idle_timeout`], and may add, modify or delete subscribers through
+    /// `eventp`, like a handler. Replaces any callback set before.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::epoll::EpollTimeout;
+    /// use eventp::Eventp;
+    ///
+    /// # fn flush_write_behind_buffers() {}
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// eventp.set_idle_callback(|_eventp| flush_write_behind_buffers());
+    /// eventp.run_once_with_timeout(EpollTimeout::ZERO)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// When called from a handler, or from the idle callback, through
+    /// [`Pinned::set_idle_callback`], the change takes effect at the end of the
+    /// batch.
+    ///
+    /// If the callback panics, the panic ends the batch and is resumed, as for a
+    /// handler, and the callback stays set.
+    pub fn set_idle_callback(&mut self, callback: impl FnMut(Pinned<'_, Self>) + 'static) {
+        self.update_idle_callback(Some(Box::new(callback)));
+    }
 
-        let n = self.epoll.wait(buf, timeout)?;
-        let buf = &buf[..n];
+    /// Removes the callback set by [`set_idle_callback`](Self::set_idle_callback),
+    /// at the end of the batch if called from a handler.
+    pub fn clear_idle_callback(&mut self) {
+        self.update_idle_callback(None);
+    }
 
-        // Enter the 'handling' state to manage re-entrancy safely.
+    fn update_idle_callback(&mut self, callback: Option<IdleCallback>) {
         if self.handling.is_some() {
-            // SAFETY: The recursion guard at the top of this function panics if
-            //         `self.handling` is `Some`, and `epoll.wait` cannot mutate
-            //         `self.handling`. So this branch is unreachable; the assignment
-            //         in the `else` branch is the only path that initializes the
-            //         field, which avoids an unnecessary drop check on the prior
-            //         (`None`) value.
-            unsafe { hint::unreachable_unchecked() }
+            self.idle_callback_update = Some(callback);
         } else {
-            self.handling = Some(Handling {
-                fd: -1, // Invalid fd, will be updated for each event.
-                drop_current: false,
-                deferred_drop: vec![],
-            });
+            self.idle_callback = callback;
         }
+    }
 
-        for ev in buf {
-            // Reconstruct the subscriber pointer from the `epoll` event data.
-            // SAFETY: `addr` was set from a `ThinBoxSubscriber` in `add()` whose
-            // owning entry still lives in `self.registered` (or, for an in-flight
-            // delete, in `handling.deferred_drop` after a `drop_in_place`). The
-            // thin pointer's heap target is therefore still allocated. We wrap
-            // the reconstructed value in `ManuallyDrop` because the real owner
-            // is elsewhere; if we let `Drop` run -- including during a panic
-            // unwind out of `handle()` -- the heap slot would be double-freed.
-            let addr = ev.data() as usize;
-            let mut subscriber = ManuallyDrop::new(unsafe {
-                mem::transmute::<usize, ThinBoxSubscriber<Eventp>>(addr)
-            });
+    /// Returns whether the raw fd is registered with this `Eventp`.
+    pub fn contains(&self, raw_fd: RawFd) -> bool {
+        self.registered.contains_key(&raw_fd)
+    }
 
-            // Update the currently handled fd in the `Handling` state.
-            {
-                let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
-                handling.fd = *subscriber.raw_fd_ref();
-            }
+    /// Returns the number of registered subscribers.
+    ///
+    /// A subscriber deleting itself is counted until its handler returns.
+    pub fn len(&self) -> usize {
+        self.registered.len()
+    }
 
-            // Dispatch the event to the subscriber's handler.
-            // SAFETY: `Eventp` is `!Unpin` (via `_pinned: PhantomPinned`), so once
-            // exposed as `Pin<&mut Eventp>` the handler cannot, in safe code,
-            // recover an `&mut Eventp` and `mem::replace` the loop out from under
-            // us. We further wrap the pin in `Pinned`, which only re-exposes
-            // add/modify/delete, none of which move `self`. The original
-            // `&mut self` passed into this function is the unique mutable borrow
-            // for the duration of dispatch, so pinning it here is sound.
-            if let Some(s) = subscriber.try_deref_mut() {
-                s.handle(Event::from(ev), Pinned(unsafe { Pin::new_unchecked(self) }));
-            }
+    /// Returns whether no subscriber is registered.
+    pub fn is_empty(&self) -> bool {
+        self.registered.is_empty()
+    }
 
-            let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
-            if handling.drop_current {
-                handling.drop_current = false;
+    /// Evicts the subscribers whose fd was closed while registered, and returns
+    /// how many there were.
+    ///
+    /// The kernel deregisters a file once its last fd is closed, without the loop
+    /// knowing, so the subscriber of an fd closed elsewhere would stay registered
+    /// for good. Each fd is checked with `fcntl(F_GETFD)`; an fd number reused
+    /// since by another file goes unnoticed, but is evicted by [`delete`], which
+    /// fails to deregister it.
+    ///
+    /// Evicted subscribers are dropped at once, and counted by
+    /// [`Stats::fds_evicted`]. Their memory is released once the epoll no longer
+    /// lists their registration in its fdinfo, see proc_pid_fdinfo(5): were the
+    /// file still open through another fd, its registration would outlive the
+    /// closed fd, and its events would keep coming, to be ignored. Every sweep checks
+    /// again those still listed, which are otherwise released with the loop.
+    ///
+    /// [`delete`]: EventpOps::delete
+    pub fn sweep(&mut self) -> usize {
+        self.end_abandoned_batch();
 
-                debug_assert!(handling.fd >= 0, "Invalid fd in handling state.");
-                self.registered.remove(&handling.fd);
-            }
+        let closed: Vec<RawFd> = self
+            .registered
+            .keys()
+            .copied()
+            .filter(|&fd| {
+                // SAFETY: `F_GETFD` takes no pointer, and fails on a closed fd.
+                let ret = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+                ret == -1 && io::Error::last_os_error().raw_os_error() == Some(libc::EBADF)
+            })
+            .collect();
+        for &fd in &closed {
+            self.evict(fd, DeregisterReason::Swept);
         }
+        self.release_evicted();
+        closed.len()
+    }
 
-        // Take the handling state to process deferred removals.
-        // SAFETY: `self.handling` is guaranteed to be `Some` at this point.
-        unsafe { self.handling.take().unwrap_unchecked() };
+    /// Returns how many more subscribers can be registered, or `None` without
+    /// [`Builder::max_subscribers`].
+    ///
+    /// Subscribers deleted during the current batch are not counted.
+    pub fn capacity_remaining(&self) -> Option<usize> {
+        let deleted_current = matches!(&self.handling, Some(h) if h.drop_current);
+        let occupied = self.registered.len() - deleted_current as usize;
+        self.max_subscribers.map(|max| max.saturating_sub(occupied))
+    }
 
-        Ok(())
+    /// Returns the memory of the registered subscribers, as the sum of their
+    /// [`allocated_bytes`](ThinBoxSubscriber::allocated_bytes), see
+    /// [`Builder::max_subscriber_bytes`].
+    ///
+    /// Subscribers deleted during the current batch are not counted.
+    pub fn subscriber_bytes(&self) -> usize {
+        let deleted_current = match &self.handling {
+            Some(h) if h.drop_current => self
+                .registered
+                .get(&h.fd)
+                .map_or(0, ThinBoxSubscriber::allocated_bytes),
+            _ => 0,
+        };
+        self.subscriber_bytes - deleted_current
     }
-}
 
-impl EventpOpsAdd<Self> for Eventp {
-    #[doc = include_str!("../docs/eventp-ops.add.md")]
-    fn add(&mut self, subscriber: ThinBoxSubscriber<Self>) -> io::Result<()> {
-        // Pointer laundering: convert the subscriber's thin pointer into a `usize`
-        // so it can be stashed in `epoll_event.data` without a borrow-checker tie.
-        // SAFETY: `ThinBoxSubscriber<Self>` consists of a single `NonNull<u8>`
-        // field plus a ZST `PhantomData`, giving it the same size and alignment
-        // as `usize` on a 64-bit target. `transmute_copy` only requires equal
-        // size and is used here (rather than `transmute`) to avoid consuming
-        // the value, since we still need to move it into `self.registered`.
-        let addr = unsafe { mem::transmute_copy::<_, usize>(&subscriber) };
+    /// Returns a reference to the subscriber corresponding to the raw fd.
+    pub fn get(&self, raw_fd: &RawFd) -> Option<&dyn Subscriber<Eventp>> {
+        self.registered.get(raw_fd).and_then(|s| s.try_deref())
+    }
 
-        let dyn_subscriber = match subscriber.try_deref() {
-            Some(s) => s,
-            None => panic!("Subscriber is already dropped"),
-        };
+    /// Returns a mutable reference to the subscriber corresponding to the raw fd.
+    pub fn get_mut(&mut self, raw_fd: &RawFd) -> Option<&mut dyn Subscriber<Eventp>> {
+        self.registered
+            .get_mut(raw_fd)
+            .and_then(|s| s.try_deref_mut())
+    }
 
-        let raw_fd = dyn_subscriber.as_fd().as_raw_fd();
-        if self.registered.contains_key(&raw_fd) {
+    /// Returns the fd whose handler is running, if any.
+    fn handled_fd(&self) -> Option<RawFd> {
+        self.handling.as_ref().map(|handling| handling.fd)
+    }
+
+    /// Registers a [`MultiFdSubscriber`], giving each of its fds its own epoll
+    /// registration that dispatches into the shared object.
+    ///
+    /// See the [`multi_fd`] module for the semantics of deleting a group.
+    ///
+    /// # Errors
+    ///
+    /// - [`io::ErrorKind::InvalidInput`] if the group has no fds.
+    /// - Any error [`add`](EventpOpsAdd::add) would return for one of the fds,
+    ///   such as [`io::ErrorKind::AlreadyExists`]. The fds added before the failing
+    ///   one are deleted again and the group is dropped.
+    pub fn add_group<G: MultiFdSubscriber<Self>>(&mut self, group: G) -> io::Result<()> {
+        let fds: Vec<(RawFd, Interest)> = group
+            .fds()
+            .into_iter()
+            .map(|(fd, interest)| (fd.as_raw_fd(), interest))
+            .collect();
+        if fds.is_empty() {
             return Err(io::Error::new(
-                io::ErrorKind::AlreadyExists,
-                "subscriber with same fd already registered",
+                io::ErrorKind::InvalidInput,
+                "group has no fds",
             ));
         }
 
-        let interest = dyn_subscriber.interest().get();
+        let group = Rc::new(RefCell::new(group));
+        let members: GroupMembers = Rc::new(RefCell::new(Vec::with_capacity(fds.len())));
 
-        let epoll_event = EpollEvent::new(interest.bitflags(), addr as u64);
-        self.epoll.add(dyn_subscriber.as_fd(), epoll_event)?;
+        for (index, (raw_fd, interest)) in fds.into_iter().enumerate() {
+            let member = Member::new(group.clone(), index, raw_fd);
+            if let Err(e) = self.add(ThinBoxSubscriber::with_interest(member, interest)) {
+                let added = members.borrow().clone();
+                for fd in added {
+                    // Just added, so this can only fail if the kernel rejects
+                    // `EPOLL_CTL_DEL`, and the original error matters more.
+                    let _ = self.delete(fd);
+                }
+                return Err(e);
+            }
+            members.borrow_mut().push(raw_fd);
+            self.groups.insert(raw_fd, members.clone());
+        }
+        Ok(())
+    }
 
-        // Take ownership of the subscriber. This is the only place that owns it.
-        self.registered.insert(raw_fd, subscriber);
+    /// Deletes `fd` together with every other fd of its group.
+    ///
+    /// If `fd` was not registered by [`add_group`](Self::add_group), this is the
+    /// same as [`delete`](EventpOps::delete).
+    ///
+    /// # Errors
+    ///
+    /// - [`io::ErrorKind::NotFound`] if `fd` is not registered.
+    /// - The first error from deleting one of the fds. The remaining fds are still
+    ///   deleted.
+    pub fn delete_group(&mut self, fd: RawFd) -> io::Result<()> {
+        let members = match self.groups.get(&fd) {
+            Some(members) => members.borrow().clone(),
+            None => return self.delete(fd),
+        };
 
-        Ok(())
+        let mut result = Ok(());
+        for member in members {
+            if let Err(e) = self.delete(member) {
+                result = result.and(Err(e));
+            }
+        }
+        result
     }
-}
 
-impl EventpOps for Eventp {
-    #[doc = include_str!("../docs/eventp-ops.modify.md")]
-    fn modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<()> {
-        let subscriber = self
-            .registered
-            .get_mut(&fd)
-            .ok_or(io::Error::new(io::ErrorKind::NotFound, "fd not registered"))?;
+    /// Returns the interest the raw fd is currently registered with.
+    ///
+    /// For an [`oneshot`](Interest::oneshot) interest, this is still the one
+    /// registered after the kernel has disarmed the fd, see
+    /// [`is_armed`](Self::is_armed).
+    pub fn interest(&self, raw_fd: &RawFd) -> Option<Interest> {
+        self.registered.get(raw_fd).map(|s| s.interest())
+    }
 
-        // Perform the same pointer laundering as in `add` to get the address for `epoll_ctl`.
-        // SAFETY: see the SAFETY note in `add()` -- `ThinBoxSubscriber` and `usize`
-        // have the same size on a 64-bit target.
-        let addr = unsafe { mem::transmute_copy::<_, usize>(subscriber) };
-        let mut epoll_event = EpollEvent::new(interest.bitflags(), addr as u64);
+    /// Returns whether the raw fd can still report events, i.e. `false` if it is
+    /// registered [`oneshot`](Interest::oneshot) and has reported one since it was
+    /// last armed. See [`ThinBoxSubscriber::is_armed`].
+    pub fn is_armed(&self, raw_fd: &RawFd) -> Option<bool> {
+        self.registered.get(raw_fd).map(|s| s.is_armed())
+    }
 
-        // SAFETY: This is a direct FFI call to `epoll_ctl`. The arguments are
-        // constructed correctly, so it's as safe as the underlying syscall.
+    /// Returns whether the raw fd is registered, but no longer watched, after an
+    /// error storm, see [`StormAction::Quarantine`].
+    pub fn is_quarantined(&self, raw_fd: &RawFd) -> bool {
+        self.quarantined.contains(raw_fd)
+    }
+
+    /// Watches the raw fd again, with the interest it was registered with, after
+    /// it was quarantined by an error storm, see [`StormAction::Quarantine`].
+    ///
+    /// While quarantined, the subscriber keeps its fd, and counts in
+    /// [`len`](Self::len), but gets no events; [`modify`](EventpOps::modify) fails
+    /// with [`io::ErrorKind::NotFound`], and [`delete`](EventpOps::delete) succeeds.
+    ///
+    /// # Errors
+    ///
+    /// - [`io::ErrorKind::NotFound`] if the fd is not quarantined.
+    /// - Otherwise, the [`io::Error`] of `epoll_ctl`, leaving the fd quarantined.
+    pub fn unquarantine(&mut self, fd: RawFd) -> io::Result<()> {
+        if !self.quarantined.remove(&fd) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "fd not quarantined",
+            ));
+        }
+        let subscriber = &self.registered[&fd];
+        let interest = subscriber.interest();
+        // The same data as in `add`.
+        let mut epoll_event = EpollEvent::new(interest.bitflags(), subscriber.to_data());
+        // SAFETY: Same as in `modify`.
         let ret = unsafe {
             libc::epoll_ctl(
                 self.epoll.0.as_raw_fd(),
-                libc::EPOLL_CTL_MOD,
+                libc::EPOLL_CTL_ADD,
                 fd,
                 &mut epoll_event as *mut _ as _,
             )
         };
         if ret == -1 {
+            self.quarantined.insert(fd);
             return Err(io::Error::last_os_error());
         }
-        // Update the interest stored within the subscriber itself.
-        if let Some(s) = subscriber.try_deref_mut() {
-            s.interest().set(interest);
-        }
-
+        self.lifecycle.registered(fd, interest);
         Ok(())
     }
 
-    #[doc = include_str!("../docs/eventp-ops.delete.md")]
-    fn delete(&mut self, fd: RawFd) -> io::Result<()> {
-        if !self.registered.contains_key(&fd) {
-            return Err(io::Error::new(io::ErrorKind::NotFound, "fd not registered"));
-        }
+    /// Returns the registrations of the loop, to rebuild it in the child of a
+    /// `fork(2)`, see [`ForkBlueprint`].
+    ///
+    /// In debug builds, running the loop, or adding, modifying or deleting through
+    /// it, panics in any other process than the one it was created in.
+    pub fn prepare_fork(&self) -> ForkBlueprint {
+        let registrations = self
+            .registered
+            .iter()
+            .map(|(&fd, s)| (fd, s.interest(), s.name()))
+            .collect();
+        ForkBlueprint::new(
+            self.event_buf.len(),
+            registrations,
+            #[cfg(feature = "debug-ownership")]
+            self.owner.id(),
+        )
+    }
 
-        // Use a direct syscall for `EPOLL_CTL_DEL` as `nix`'s `epoll.delete`
-        // requires a `AsFd` source, which we may not have if the source is already dropped.
-        // We only need the raw fd.
-        // SAFETY: This is a direct FFI call to `epoll_ctl`. The arguments are
-        // constructed correctly, so it's as safe as the underlying syscall.
-        let ret = unsafe {
-            libc::epoll_ctl(
-                self.epoll.0.as_raw_fd(),
-                libc::EPOLL_CTL_DEL,
-                fd,
-                ptr::null_mut(),
-            )
+    /// Panics in debug builds if the loop is used in a child of the process it
+    /// was created in, whose changes would reach the interest list of the parent.
+    #[inline]
+    fn debug_assert_same_process(&self) {
+        #[cfg(debug_assertions)]
+        {
+            let pid = std::process::id();
+            assert!(
+                pid == self.pid,
+                "the Eventp of process {} is used in process {pid}, see `Eventp::prepare_fork`",
+                self.pid
+            );
+        }
+    }
+
+    /// Returns every registered fd, with its interest and the
+    /// [name](ThinBoxSubscriber::name) of its subscriber, in the order of the fds,
+    /// e.g. for a debugging endpoint.
+    pub fn dump(&self) -> Vec<(RawFd, Interest, Option<&'static str>)> {
+        let mut dump: Vec<_> = self
+            .registered
+            .iter()
+            .map(|(&fd, s)| (fd, s.interest(), s.name()))
+            .collect();
+        dump.sort_unstable_by_key(|&(fd, ..)| fd);
+        dump
+    }
+
+    /// Runs the event loop until it is [stopped](Self::stop), fails, or a handler
+    /// panics, and tells which.
+    ///
+    /// Same as [`run_forever`](Self::run_forever), but the error of a handler is
+    /// told apart from that of `epoll_wait`, and the panic of a handler is caught
+    /// rather than resumed. The loop can be run again after any outcome.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::tri_subscriber::WithHandler;
+    /// use eventp::{Eventp, Pinned, RunOutcome, Subscriber};
+    /// use nix::sys::eventfd::{EfdFlags, EventFd};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// let efd = EventFd::from_value_and_flags(1, EfdFlags::EFD_NONBLOCK)?;
+    /// eventp::interest()
+    ///     .read()
+    ///     .with_fd(efd)
+    ///     .with_handler(|efd: &mut EventFd, mut eventp: Pinned<'_, Eventp>| {
+    ///         let _ = efd.read();
+    ///         eventp.stop();
+    ///     })
+    ///     .register_into(&mut eventp)?;
+    ///
+    /// match eventp.run() {
+    ///     RunOutcome::Stopped => println!("stopped"),
+    ///     RunOutcome::HandlerError { fd, error } => eprintln!("fd {fd} failed: {error}"),
+    ///     RunOutcome::Panicked { payload, .. } => std::panic::resume_unwind(payload),
+    ///     outcome => eprintln!("{outcome:?}"),
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn run(&mut self) -> RunOutcome {
+        self.panicked = None;
+        match panic::catch_unwind(AssertUnwindSafe(|| self.run_loop(|_| EpollTimeout::NONE))) {
+            Ok(Ok(())) => RunOutcome::Stopped,
+            Ok(Err(Exit::Epoll(e))) => RunOutcome::EpollError(e),
+            Ok(Err(Exit::Handler { fd, error })) => RunOutcome::HandlerError { fd, error },
+            Err(payload) => {
+                // The batch of a handler is ended already; the idle callback runs
+                // as the handler of no fd.
+                let fd = self.panicked.take().or_else(|| {
+                    let fd = self.handling.as_ref()?.fd;
+                    (fd >= 0).then_some(fd)
+                });
+                self.end_abandoned_batch();
+                RunOutcome::Panicked { fd, payload }
+            }
+        }
+    }
+
+    /// Makes [`run`](Self::run) and [`run_forever`](Self::run_forever) return once
+    /// the current batch, if any, is over, e.g. from a handler through
+    /// [`Pinned::stop`]. A stop requested outside of them ends their next run
+    /// after its first batch.
+    pub fn stop(&mut self) {
+        self.stop_requested = true;
+    }
+
+    /// Runs batches until stopped or failed, retrying on `EINTR`.
+    fn run_loop(&mut self, mut timeout_fn: impl FnMut(&Self) -> EpollTimeout) -> Result<(), Exit> {
+        loop {
+            match self.dispatch(timeout_fn(self), usize::MAX, true) {
+                Ok(_) => {}
+                // `epoll_wait` can be interrupted by a signal. This is not a fatal
+                // error, so we simply continue the loop.
+                Err(Exit::Epoll(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(exit) => return Err(exit),
+            }
+            if mem::take(&mut self.stop_requested) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs the event loop until a non-`EINTR` error occurs, or it is
+    /// [stopped](Self::stop).
+    ///
+    /// This is the typical entry point for starting the event loop. It
+    /// repeatedly calls [`run_once`](Self::run_once); if `epoll_wait` is
+    /// interrupted by a signal (`EINTR` /
+    /// [`io::ErrorKind::Interrupted`]), the loop transparently retries. See
+    /// [`run`](Self::run) to tell apart why it returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `io::Error` from `epoll_wait` that is not
+    /// [`io::ErrorKind::Interrupted`], or from a handler under
+    /// [`ErrorPolicy::Propagate`]. Returns `Ok(())` only once stopped.
+    pub fn run_forever(&mut self) -> io::Result<()> {
+        self.run_forever_with(|_| EpollTimeout::NONE)
+    }
+
+    /// Like [`run_forever`](Self::run_forever), but waits at most the timeout
+    /// returned by `timeout_fn` each time, e.g. until the next deadline of timers
+    /// kept outside of the loop.
+    ///
+    /// `timeout_fn` is called before every wait, once the previous batch is over
+    /// and its deleted subscribers are dropped, so it sees the registrations as
+    /// they are, e.g. through [`len`](Self::len) or [`interest`](Self::interest). A
+    /// wait interrupted by a signal calls it again, rather than waiting the same
+    /// timeout anew. Timers firing when the wait times out can be run by the
+    /// [idle callback](Self::set_idle_callback).
+    ///
+    /// ```rust,no_run
+    /// # use std::io;
+    /// # use std::time::Instant;
+    /// use eventp::epoll::EpollTimeout;
+    /// use eventp::Eventp;
+    ///
+    /// # fn next_deadline() -> Instant { Instant::now() }
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// eventp.run_forever_with(|_eventp| {
+    ///     let remaining = next_deadline().saturating_duration_since(Instant::now());
+    ///     EpollTimeout::try_from(remaining).unwrap_or(EpollTimeout::MAX)
+    /// })
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run_forever`](Self::run_forever).
+    pub fn run_forever_with(
+        &mut self,
+        timeout_fn: impl FnMut(&Self) -> EpollTimeout,
+    ) -> io::Result<()> {
+        self.run_loop(timeout_fn).map_err(io::Error::from)
+    }
+
+    /// Performs one `epoll_wait` with no timeout and dispatches every ready
+    /// event to its handler.
+    ///
+    /// Equivalent to calling
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout) with
+    /// [`EpollTimeout::NONE`] (i.e. block indefinitely until at least one
+    /// event is ready, or until interrupted by a signal).
+    ///
+    /// # Errors
+    ///
+    /// Forwards any `io::Error` from `epoll_wait`, including
+    /// [`io::ErrorKind::Interrupted`] when the syscall is interrupted by a
+    /// signal. Use [`run_forever`](Self::run_forever) if you want `EINTR`
+    /// to be retried automatically.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called recursively from within an event handler -- see
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout).
+    pub fn run_once(&mut self) -> io::Result<()> {
+        self.run_once_with_timeout(EpollTimeout::NONE)
+    }
+
+    /// Dispatches the events that are ready, without waiting for any.
+    ///
+    /// Equivalent to calling
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout) with
+    /// [`EpollTimeout::ZERO`]. Meant to be called when [`poll_fd`](Self::poll_fd)
+    /// is readable.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run_once_with_timeout`](Self::run_once_with_timeout).
+    ///
+    /// # Panics
+    ///
+    /// Panics if called recursively from within an event handler -- see
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout).
+    pub fn try_run_once(&mut self) -> io::Result<()> {
+        self.run_once_with_timeout(EpollTimeout::ZERO)
+    }
+
+    /// Returns the epoll fd of this loop, to nest it inside another epoll or
+    /// `poll(2)` loop.
+    ///
+    /// The fd is readable when events are ready, i.e. when
+    /// [`run_once`](Self::run_once) would not block, and stays readable, as
+    /// reported level-triggered, until they are all dispatched, e.g. with
+    /// [`try_run_once`](Self::try_run_once). Watching it edge-triggered is only
+    /// correct if the outer handler calls `try_run_once` until the fd is no longer
+    /// readable.
+    ///
+    /// The same fd is returned by the [`AsFd`] impl, so an `Eventp` can be the fd
+    /// of a subscriber of another `Eventp`, as done by [`ChildEventp`]:
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::{tri_subscriber::WithHandler, Eventp, Subscriber};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let inner = Eventp::default();
+    /// let mut outer = Eventp::default();
+    /// eventp::interest()
+    ///     .read()
+    ///     .with_fd(inner)
+    ///     .with_handler(|inner: &mut Eventp| inner.try_run_once())
+    ///     .register_into(&mut outer)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn poll_fd(&self) -> BorrowedFd<'_> {
+        self.epoll.0.as_fd()
+    }
+
+    /// Duplicates the [epoll fd](Self::poll_fd), e.g. to hand it to a sandbox or
+    /// supervisor process. The duplicate is close-on-exec.
+    ///
+    /// The duplicate shares the interest list of this loop: subscribers added or
+    /// deleted here are seen through it, and fds that `epoll_ctl` adds to it are
+    /// seen here, without a subscriber. Waiting on it elsewhere, be it in another
+    /// thread or process, is outside what this crate can keep sound, as the data
+    /// word of every event points into the memory of this `Eventp`, and such waits
+    /// consume the events this loop would dispatch.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::os::fd::AsRawFd;
+    ///
+    /// use eventp::Eventp;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let eventp = Eventp::default();
+    /// let dup = eventp.try_clone_poll_fd()?;
+    /// assert_ne!(dup.as_raw_fd(), eventp.poll_fd().as_raw_fd());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The [`io::Error`] of `fcntl(F_DUPFD_CLOEXEC)`, e.g. when out of fds.
+    pub fn try_clone_poll_fd(&self) -> io::Result<OwnedFd> {
+        let fd = self.epoll.0.as_raw_fd();
+        // SAFETY: `F_DUPFD_CLOEXEC` takes no pointer.
+        let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if dup == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `dup` is a fresh fd, owned by nobody else.
+        Ok(unsafe { OwnedFd::from_raw_fd(dup) })
+    }
+
+    /// Sets or clears the close-on-exec flag of the [epoll fd](Self::poll_fd),
+    /// whatever the [`EpollCreateFlags`] it was created with, e.g. to let a
+    /// child process inherit it. Duplicates have a flag of their own.
+    ///
+    /// # Errors
+    ///
+    /// The [`io::Error`] of `fcntl(F_GETFD)` or `fcntl(F_SETFD)`.
+    pub fn set_poll_fd_cloexec(&self, cloexec: bool) -> io::Result<()> {
+        let fd = self.epoll.0.as_raw_fd();
+        // SAFETY: `F_GETFD` and `F_SETFD` take no pointer.
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        if flags == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = if cloexec {
+            flags | libc::FD_CLOEXEC
+        } else {
+            flags & !libc::FD_CLOEXEC
         };
-        if ret == -1 {
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, flags) } == -1 {
             return Err(io::Error::last_os_error());
         }
+        Ok(())
+    }
 
-        if let Some(handling) = &mut self.handling {
-            if handling.fd == fd {
-                // Delete self while handling. This will actually do the drop
-                // after the handler returns.
-                handling.drop_current = true;
-            } else {
-                // Delete another fd while handling.
+    /// Performs one `epoll_wait` with the given timeout and dispatches every
+    /// ready event to its handler.
+    ///
+    /// If events were left over by [`run_once_budgeted`](Self::run_once_budgeted),
+    /// those are dispatched instead, without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any `io::Error` from `epoll_wait`. Under [`ErrorPolicy::Propagate`],
+    /// also returns the first error reported by a handler, after the whole batch has
+    /// been dispatched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called recursively (i.e. from within an event handler).
+    /// Recursing would corrupt the internal `handling` state and risk
+    /// invalidating iterators on the registry.
+    pub fn run_once_with_timeout(&mut self, timeout: EpollTimeout) -> io::Result<()> {
+        self.wait_and_dispatch(timeout, usize::MAX).map(drop)
+    }
 
-                // Safe to unwrap, because just checked that it exists.
-                let mut subscriber = self.registered.remove(&fd).unwrap();
+    /// Same as [`run_once_with_timeout`](Self::run_once_with_timeout), but backs off
+    /// with [`AlreadyRunning`] rather than panicking if the loop is dispatching a
+    /// batch already, and returns how many handlers were called otherwise.
+    ///
+    /// It is meant for helpers that drive the loop when nothing else does, e.g. to
+    /// flush opportunistically, and may be handed a `&mut Eventp` taken from within
+    /// a handler. [`Pinned`] does not offer it, as a handler never drives its loop.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::epoll::EpollTimeout;
+    /// use eventp::Eventp;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// match eventp.try_run_once_with_timeout(EpollTimeout::ZERO) {
+    ///     Ok(dispatched) => assert_eq!(dispatched?, 0),
+    ///     Err(running) => eprintln!("{running}, not flushing"),
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`AlreadyRunning`] if called from within a handler. Otherwise, the result of
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout).
+    pub fn try_run_once_with_timeout(
+        &mut self,
+        timeout: EpollTimeout,
+    ) -> Result<io::Result<usize>, AlreadyRunning> {
+        match &self.handling {
+            Some(handling) => Err(AlreadyRunning { fd: handling.fd }),
+            None => Ok(self.wait_and_dispatch(timeout, usize::MAX)),
+        }
+    }
 
-                // Drop in place immediately. This will not release the heap memory.
-                subscriber.drop_in_place();
+    /// Like [`run_once_with_timeout`](Self::run_once_with_timeout), but calls at
+    /// most `max_events` handlers, and returns how many were called.
+    ///
+    /// The events of the batch beyond the budget are kept, and dispatched first by
+    /// the next `run_*` call, which does not wait for new events until they are
+    /// all dispatched. So edge-triggered subscribers do not lose events, and
+    /// level-triggered ones are not reported twice. A kept event is discarded if
+    /// its subscriber is deleted before it is dispatched.
+    ///
+    /// Kept events do not make [`poll_fd`](Self::poll_fd) readable: a loop nested
+    /// in another one should be called again while
+    /// [`pending_events`](Self::pending_events) is non-zero.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run_once_with_timeout`](Self::run_once_with_timeout).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_events` is zero, or if called recursively from within an
+    /// event handler -- see [`run_once_with_timeout`](Self::run_once_with_timeout).
+    pub fn run_once_budgeted(
+        &mut self,
+        timeout: EpollTimeout,
+        max_events: usize,
+    ) -> io::Result<usize> {
+        assert!(max_events > 0, "Budget must be greater than zero");
+        self.wait_and_dispatch(timeout, max_events)
+    }
 
-                // Defer the dealloc to the end of the event dispatch.
-                handling.deferred_drop.push(subscriber);
+    /// Dispatches batch after batch without waiting, until the kernel has no event
+    /// ready, or after `max_batches`, e.g. to catch up after a stall that let more
+    /// events pile up than [`capacity`](Builder::capacity) fits in one batch.
+    ///
+    /// Every batch is dispatched as by
+    /// [`try_run_once`](Self::try_run_once), and its deleted subscribers are
+    /// dropped, and deferred closures run, before the next. Only the
+    /// [idle callback](Self::set_idle_callback) is not called, when the last wait
+    /// finds nothing ready: the loop was not idle.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::Eventp;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// let summary = eventp.drain(Some(16))?;
+    /// assert_eq!(summary.batches, 0);
+    /// assert!(!summary.more_may_remain);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run_once_with_timeout`](Self::run_once_with_timeout), at the end
+    /// of the batch that failed, which ends the drain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called recursively from within an event handler -- see
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout).
+    pub fn drain(&mut self, max_batches: Option<usize>) -> io::Result<DrainSummary> {
+        let mut summary = DrainSummary::default();
+        loop {
+            if max_batches.is_some_and(|max| summary.batches >= max as u64) {
+                summary.more_may_remain = true;
+                return Ok(summary);
+            }
+            let (ready, dispatched) = self.dispatch(EpollTimeout::ZERO, usize::MAX, false)?;
+            if ready == 0 {
+                return Ok(summary);
+            }
+            summary.batches += 1;
+            summary.events += dispatched as u64;
+        }
+    }
+
+    /// Returns the number of events left over by
+    /// [`run_once_budgeted`](Self::run_once_budgeted), still to be dispatched.
+    pub fn pending_events(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Dispatches events until `deadline`, waiting as needed, and returns
+    /// whether the deadline has passed.
+    ///
+    /// Returns `Ok(false)` as soon as one batch of events has been dispatched,
+    /// and `Ok(true)` once `deadline` has passed without any. The remaining time
+    /// is recomputed on every wait: a wait interrupted by a signal (`EINTR`) is
+    /// retried with what is left, and a sub-millisecond remainder is rounded up
+    /// to a whole millisecond rather than spun on. Waits longer than
+    /// [`EpollTimeout::MAX`] are clamped to it and retried.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run_once_with_timeout`](Self::run_once_with_timeout), except for
+    /// [`io::ErrorKind::Interrupted`], which is retried.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called recursively from within an event handler -- see
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout).
+    pub fn run_once_with_deadline(&mut self, deadline: Instant) -> io::Result<bool> {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                // Still dispatch what is already ready, so a tight deadline cannot
+                // starve the loop.
+                return self
+                    .wait_and_dispatch(EpollTimeout::ZERO, usize::MAX)
+                    .map(|n| n == 0);
+            }
+            match self.wait_and_dispatch(timeout_for(remaining), usize::MAX) {
+                Ok(0) => continue,
+                Ok(_) => return Ok(false),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
             }
-        } else {
-            // Otherwise, it's safe to remove immediately.
-            self.registered.remove(&fd);
         }
+    }
+
+    /// Dispatches events until `deadline` has passed.
+    ///
+    /// Repeatedly calls [`run_once_with_deadline`](Self::run_once_with_deadline),
+    /// so signals do not extend the wait, unlike retrying
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout) with the same
+    /// timeout.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run_once_with_deadline`](Self::run_once_with_deadline).
+    pub fn run_until(&mut self, deadline: Instant) -> io::Result<()> {
+        while !self.run_once_with_deadline(deadline)? {}
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::cell::{Cell, RefCell};
-    use std::os::fd::{AsFd, BorrowedFd};
-    use std::panic::{catch_unwind, AssertUnwindSafe};
-    use std::rc::Rc;
-    use std::time::Duration;
+    /// Shuts the loop down, letting every subscriber flush its state first.
+    ///
+    /// From then on, subscribers cannot be added, with [`Error::ShuttingDown`].
+    /// Each subscriber has its [`on_shutdown`](subscriber::Handler::on_shutdown)
+    /// hook called, in the reverse order of their registration. Then the loop keeps
+    /// running, for up to `timeout`, until no subscriber is left, for in-flight
+    /// work to finish: a subscriber done with it deletes itself. Those still
+    /// registered are finally deleted, and dropped, again in the reverse order of
+    /// their registration.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::time::Duration;
+    ///
+    /// use eventp::{tri_subscriber::WithHandler, Eventp, Subscriber};
+    /// use nix::sys::eventfd::{EfdFlags, EventFd};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// eventp::interest()
+    ///     .read()
+    ///     .with_fd(EventFd::from_flags(EfdFlags::EFD_NONBLOCK)?)
+    ///     .with_handler(|efd: &mut EventFd| {
+    ///         let _ = efd.read();
+    ///     })
+    ///     .register_into(&mut eventp)?;
+    ///
+    /// // Returns as soon as the subscribers are gone, or after the timeout.
+    /// eventp.shutdown(Duration::from_millis(10))?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The first error of the loop while it keeps running, as with
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout), after which the
+    /// subscribers are deleted without waiting further.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a hook panics, as if a handler did.
+    pub fn shutdown(mut self, timeout: Duration) -> io::Result<()> {
+        self.shutting_down = true;
+        self.run_shutdown_hooks();
 
-    use nix::sys::eventfd::{EfdFlags, EventFd};
+        // Far enough if `timeout` cannot be added to now.
+        let now = Instant::now();
+        let deadline = now
+            .checked_add(timeout)
+            .unwrap_or_else(|| now + Duration::from_secs(365 * 24 * 3600));
+        let mut result = Ok(());
+        while !self.registered.is_empty() && Instant::now() < deadline {
+            if let Err(e) = self.run_once_with_deadline(deadline) {
+                result = Err(e);
+                break;
+            }
+        }
+
+        for fd in self.registration_order().into_iter().rev() {
+            // Deleted by the drop of another one.
+            if self.registered.contains_key(&fd) {
+                // Only fails if the kernel rejects `EPOLL_CTL_DEL`; dropped anyway
+                // with the loop.
+                let _ = self.delete_as(fd, DeregisterReason::Shutdown);
+            }
+        }
+        result
+    }
+
+    /// Returns the registered fds, from the oldest registration to the newest.
+    fn registration_order(&self) -> Vec<RawFd> {
+        let mut fds: Vec<_> = self
+            .registered
+            .iter()
+            .map(|(&fd, subscriber)| (subscriber.order().wrapping_sub(self.next_order), fd))
+            .collect();
+        // From `next_order`, so that the order survives a wrap-around, as long as no
+        // subscriber outlives 2^32 registrations.
+        fds.sort_unstable();
+        fds.into_iter().map(|(_, fd)| fd).collect()
+    }
+
+    /// Calls [`Handler::on_shutdown`](subscriber::Handler::on_shutdown) for every
+    /// subscriber, newest first, in a handling state of its own, as for a batch.
+    fn run_shutdown_hooks(&mut self) {
+        self.handling = Some(Handling {
+            fd: -1,
+            interest: Interest::default(),
+            delta: None,
+            since_last: None,
+            batch: BatchInfo::default(),
+            drop_current: false,
+            evict_current: false,
+            reason_current: None,
+            error: None,
+        });
+
+        for fd in self.registration_order().into_iter().rev() {
+            // A hook may delete other subscribers.
+            let Some(subscriber) = self.registered.get(&fd) else {
+                continue;
+            };
+            // SAFETY: As in `evict_idle`.
+            let mut subscriber =
+                unsafe { ThinBoxSubscriber::<Eventp>::from_data(subscriber.to_data()) };
+            {
+                let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+                handling.fd = fd;
+                handling.interest = subscriber.interest();
+                handling.delta = None;
+                handling.since_last = None;
+            }
+            if let Some(s) = subscriber.try_deref_mut() {
+                // SAFETY: See the dispatch loop.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    s.on_shutdown(Pinned(unsafe { Pin::new_unchecked(&mut *self) }))
+                }));
+                if let Err(payload) = result {
+                    self.on_handler_panic(subscriber.name());
+                    panic::resume_unwind(payload);
+                }
+            }
+
+            let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+            if handling.drop_current {
+                handling.drop_current = false;
+                let evict = mem::take(&mut handling.evict_current);
+                let reason = handling.reason_current.take();
+                self.remove_deleted(fd, evict, reason);
+            }
+        }
+
+        self.end_abandoned_batch();
+        if !self.released.borrow().is_empty() {
+            self.delete_released();
+        }
+        if !self.deferred.is_empty() {
+            self.run_deferred();
+        }
+    }
+
+    /// Performs one `epoll_wait` with the given timeout, and returns the ready
+    /// events as `(fd, event)` pairs, without calling any handler.
+    ///
+    /// Meant for code that keeps the registrations in an `Eventp` but forwards
+    /// readiness elsewhere. The events are read from the loop's own buffer, in the
+    /// order `epoll_wait` returned them; see [`EventIter`] for how the loop behaves
+    /// until the iterator is dropped. Events left over by
+    /// [`run_once_budgeted`](Self::run_once_budgeted), or by an earlier iterator,
+    /// are returned instead, without waiting.
+    ///
+    /// Handlers, delta tracking and idle timeouts do not apply to these events, and
+    /// the `run_*` methods can be called again once the iterator is dropped.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::epoll::EpollTimeout;
+    /// use eventp::Eventp;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// for (fd, event) in eventp.wait_events(EpollTimeout::ZERO)? {
+    ///     println!("fd {fd} is ready: {event:?}");
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Forwards any `io::Error` from `epoll_wait`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within an event handler, or while another iterator is
+    /// alive, which only [`mem::forget`] allows.
+    pub fn wait_events(&mut self, timeout: EpollTimeout) -> io::Result<EventIter<'_>> {
+        if let Some(handling) = &self.handling {
+            panic!(
+                "Call to `Eventp::wait_events` while handling fd {}",
+                handling.fd
+            );
+        }
+
+        let len = if !self.pending.is_empty() {
+            // Kept events come from this buffer, so they fit in it.
+            let len = self.pending.len();
+            for (slot, ev) in self.event_buf.iter_mut().zip(self.pending.drain(..)) {
+                slot.write(ev);
+            }
+            len
+        } else {
+            // SAFETY: As in `wait_and_dispatch`.
+            let buf: &mut [MaybeUninit<EpollEvent>] = &mut self.event_buf;
+            let buf: &mut [EpollEvent] = unsafe { mem::transmute(buf) };
+            let n = wait(&self.epoll, buf, timeout, self.error_context)?;
+            if n > 0 {
+                self.stats.wakeups += 1;
+                self.stats.max_batch = self.stats.max_batch.max(n as u64);
+            }
+            n
+        };
+
+        // As while dispatching, so that `delete` keeps the subscribers of the batch
+        // allocated, and the `run_*` methods refuse to run.
+        self.handling = Some(Handling {
+            fd: -1,
+            interest: Interest::default(),
+            delta: None,
+            since_last: None,
+            batch: BatchInfo::default(),
+            drop_current: false,
+            evict_current: false,
+            reason_current: None,
+            error: None,
+        });
+        Ok(EventIter {
+            eventp: self,
+            len,
+            next: 0,
+        })
+    }
+
+    /// The body of [`run_once_with_timeout`](Self::run_once_with_timeout),
+    /// dispatching at most `budget` events, and returning how many handlers were
+    /// called.
+    fn wait_and_dispatch(&mut self, timeout: EpollTimeout, budget: usize) -> io::Result<usize> {
+        match self.dispatch(timeout, budget, true) {
+            Ok((_, dispatched)) => Ok(dispatched),
+            Err(exit) => Err(exit.into()),
+        }
+    }
+
+    /// Same as `wait_and_dispatch`, telling apart the errors of handlers, and
+    /// returning the number of events in the batch first. With `idle`, a wait
+    /// without events calls the idle callback.
+    fn dispatch(
+        &mut self,
+        timeout: EpollTimeout,
+        budget: usize,
+        idle: bool,
+    ) -> Result<(usize, usize), Exit> {
+        self.debug_assert_same_process();
+        if let Some(handling) = &self.handling {
+            // Recursive calls would corrupt the `handling` state and could lead to
+            // iterator invalidation issues. This panic prevents such misuse.
+            panic!(
+                "Recursive call to `Eventp::run_once_with_timeout` while handling fd {}",
+                handling.fd
+            );
+        }
+
+        // Queued outside of a batch, e.g. before the loop first runs.
+        if !self.released.borrow().is_empty() {
+            self.delete_released();
+        }
+        if !self.deferred.is_empty() {
+            self.run_deferred();
+        }
+
+        // Events left over by a budget are dispatched before waiting again, so an
+        // fd is never in the batch twice. Taken out so that `delete`, which purges
+        // `self.pending`, cannot touch the batch while it is dispatched.
+        let pending = mem::take(&mut self.pending);
+        let mut woke_idle = false;
+        let batch: &[EpollEvent] = if !pending.is_empty() {
+            &pending
+        } else {
+            // SAFETY: `EpollEvent` is a POD wrapping `libc::epoll_event`, so any bit
+            // pattern is a valid `EpollEvent` value -- meaning `MaybeUninit<EpollEvent>`
+            // and `EpollEvent` have the same layout and the latter is sound to read
+            // even before the kernel writes into it. We immediately re-slice to the
+            // first `n` elements that `epoll_wait` actually wrote, so any consumer of
+            // `buf` only observes kernel-initialized entries.
+            let buf: &mut [MaybeUninit<EpollEvent>] = &mut self.event_buf;
+            let buf: &mut [EpollEvent] = unsafe { mem::transmute(buf) };
+
+            // Wake up for the earliest idle deadline, if any.
+            let timeout = if self.idle.is_empty() {
+                timeout
+            } else {
+                self.idle.clamp(timeout)
+            };
+            let n = wait(&self.epoll, buf, timeout, self.error_context).map_err(Exit::Epoll)?;
+            let buf = &mut buf[..n];
+            woke_idle = idle && n == 0;
+
+            if n > 0 {
+                self.stats.wakeups += 1;
+                self.stats.max_batch = self.stats.max_batch.max(n as u64);
+
+                // As delivered, before any reordering.
+                if let (Some(capture), Some(keys)) = (&mut self.capture, &self.keys) {
+                    capture.write(keys, buf);
+                }
+                if self.fair_dispatch {
+                    buf.rotate_left(self.dispatch_offset % n);
+                    self.dispatch_offset = self.dispatch_offset.wrapping_add(1);
+                }
+                if self.dispatch_order == DispatchOrder::PriorityStable {
+                    buf.sort_by_key(|ev| {
+                        // SAFETY: Same as for the dispatched events below; no
+                        // handler has run yet, so every subscriber is registered,
+                        // or evicted with its header left in place.
+                        let subscriber =
+                            unsafe { ThinBoxSubscriber::<Eventp>::from_data(ev.data()) };
+                        subscriber.interest().dispatch_rank()
+                    });
+                }
+            }
+            buf
+        };
+        let ready = batch.len();
+        let (batch, rest) = batch.split_at(budget.min(batch.len()));
+
+        // Enter the 'handling' state to manage re-entrancy safely.
+        if self.handling.is_some() {
+            // SAFETY: The recursion guard at the top of this function panics if
+            //         `self.handling` is `Some`, and `epoll.wait` cannot mutate
+            //         `self.handling`. So this branch is unreachable; the assignment
+            //         in the `else` branch is the only path that initializes the
+            //         field, which avoids an unnecessary drop check on the prior
+            //         (`None`) value.
+            unsafe { hint::unreachable_unchecked() }
+        } else {
+            self.handling = Some(Handling {
+                fd: -1, // Invalid fd, will be updated for each event.
+                interest: Interest::default(),
+                delta: None,
+                since_last: None,
+                batch: BatchInfo::new(0, batch.len(), self.next_batch),
+                drop_current: false,
+                evict_current: false,
+                reason_current: None,
+                error: None,
+            });
+        }
+        if !batch.is_empty() {
+            self.next_batch += 1;
+        }
+
+        // Only read the clock if some subscriber has an idle timeout.
+        let idle_now = (!self.idle.is_empty()).then(|| self.idle.now());
+
+        // Whether an event left undispatched is to be kept, unless its subscriber
+        // was deleted during the batch. Those are dropped in place, but not
+        // deallocated until the handling state is dropped, so their headers are
+        // still readable. The self-deleted subscriber, already deallocated, is never
+        // kept, as an fd appears at most once per batch.
+        let live = |ev: &&EpollEvent| {
+            // SAFETY: Same as for the dispatched events below.
+            let subscriber = unsafe { ThinBoxSubscriber::<Eventp>::from_data(ev.data()) };
+            subscriber.try_deref().is_some()
+        };
+
+        let mut dispatched = 0;
+        for (index, ev) in batch.iter().enumerate() {
+            // Reconstruct the subscriber pointer from the `epoll` event data.
+            // SAFETY: The data was set from a `ThinBoxSubscriber` in `add()` whose
+            // owning entry still lives in `self.registered` (or, for an in-flight
+            // delete, in `self.deferred_drop` after a `drop_in_place`, and for an
+            // fd closed while registered, in `self.evicted`). The
+            // thin pointer's heap target is therefore still allocated. It comes
+            // back in `ManuallyDrop` because the real owner is elsewhere; if we
+            // let `Drop` run -- including during a panic unwind out of
+            // `handle()` -- the heap slot would be double-freed.
+            let mut subscriber = unsafe { ThinBoxSubscriber::<Eventp>::from_data(ev.data()) };
+            if unlikely(!subscriber.is_current(ev.data())) {
+                self.stats.stale_events += 1;
+                continue;
+            }
+
+            // Not `Event::from(ev)`: the data word is the subscriber pointer, which
+            // handlers have no use for.
+            let event = Event::new(ev.events());
+            if subscriber.suppresses(event) {
+                if subscriber.is_writable_parked() {
+                    self.park_writable(&subscriber);
+                }
+                continue;
+            }
+
+            // Update the currently handled fd in the `Handling` state.
+            {
+                let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+                handling.fd = *subscriber.raw_fd_ref();
+                handling.interest = subscriber.interest();
+                handling.delta = subscriber.record_event(event);
+                handling.since_last = subscriber.record_dispatch_time();
+                handling.batch = BatchInfo::new(index, batch.len(), handling.batch.sequence());
+                if self.disarmed_warning.is_some() && !subscriber.is_armed() {
+                    let since = (subscriber.generation(), handling.batch.sequence());
+                    self.disarmed.insert(handling.fd, since);
+                }
+            }
+            if let Some(now) = idle_now {
+                subscriber.refresh_idle_deadline(now);
+            }
+
+            // Dispatch the event to the subscriber's handler.
+            // SAFETY: `Eventp` is `!Unpin` (via `_pinned: PhantomPinned`), so once
+            // exposed as `Pin<&mut Eventp>` the handler cannot, in safe code,
+            // recover an `&mut Eventp` and `mem::replace` the loop out from under
+            // us. We further wrap the pin in `Pinned`, which only re-exposes
+            // add/modify/delete, none of which move `self`. The original
+            // `&mut self` passed into this function is the unique mutable borrow
+            // for the duration of dispatch, so pinning it here is sound.
+            let name = subscriber.name();
+            if let Some(s) = subscriber.try_deref_mut() {
+                dispatched += 1;
+                self.stats.events_dispatched += 1;
+                // Only read the clock if the log or the watchdog is enabled.
+                let timed = self.event_log.is_some() || self.slow_handler.is_some();
+                let started = timed.then(Instant::now);
+                // Catching is free unless the handler panics; the panic is only
+                // counted, then resumed.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    s.try_handle(event, Pinned(unsafe { Pin::new_unchecked(&mut *self) }))
+                }));
+                if let Some(started) = started {
+                    let elapsed = started.elapsed();
+                    let fd = unsafe { self.handling.as_ref().unwrap_unchecked() }.fd;
+                    if let Some(log) = &mut self.event_log {
+                        log.record(fd, name, event, elapsed);
+                    }
+                    if let Some(slow_handler) = &self.slow_handler {
+                        slow_handler.check(fd, name, elapsed, event);
+                    }
+                }
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => self.on_handler_error(e, name),
+                    Err(payload) => {
+                        self.on_handler_panic(name);
+                        let events = batch[index + 1..].iter().chain(rest);
+                        self.resume_panic(events.filter(live), payload);
+                    }
+                }
+                if let Some(storms) = &mut self.error_storms {
+                    let handling = unsafe { self.handling.as_ref().unwrap_unchecked() };
+                    if let Some(action) = storms.record(handling.fd, event) {
+                        if !handling.drop_current {
+                            if let Err(payload) = self.on_error_storm(handling.fd, action, name) {
+                                let events = batch[index + 1..].iter().chain(rest);
+                                self.resume_panic(events.filter(live), payload);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+            if handling.drop_current {
+                handling.drop_current = false;
+
+                debug_assert!(handling.fd >= 0, "Invalid fd in handling state.");
+                let (fd, evict) = (handling.fd, mem::take(&mut handling.evict_current));
+                let reason = handling.reason_current.take();
+                self.remove_deleted(fd, evict, reason);
+            }
+        }
+
+        // Keep the events beyond the budget.
+        self.pending.extend(rest.iter().filter(live));
+
+        // After the batch, so that `delete` sees the kept events in `self.pending`.
+        if !self.idle.is_empty() {
+            self.evict_idle();
+        }
+        if woke_idle {
+            self.run_idle_callback();
+        }
+
+        // Take the handling state to process deferred removals.
+        // SAFETY: `self.handling` is guaranteed to be `Some` at this point.
+        let handling = unsafe { self.handling.take().unwrap_unchecked() };
+        self.deferred_drop.clear();
+        if let Some(callback) = self.idle_callback_update.take() {
+            self.idle_callback = callback;
+        }
+        if !self.released.borrow().is_empty() {
+            self.delete_released();
+        }
+        if !self.disarmed.is_empty() {
+            self.check_disarmed();
+        }
+        if !self.deferred.is_empty() {
+            self.run_deferred();
+        }
+
+        match handling.error {
+            Some((fd, error)) => Err(Exit::Handler { fd, error }),
+            None => Ok((ready, dispatched)),
+        }
+    }
+
+    /// Counts, and with the `log` feature warns about, the oneshot subscribers still
+    /// disarmed `disarmed_warning` batches after their event, and forgets those
+    /// rearmed or deleted since.
+    fn check_disarmed(&mut self) {
+        let Some(batches) = self.disarmed_warning else {
+            return;
+        };
+        let now = self.next_batch;
+        let registered = &self.registered;
+        let stats = &mut self.stats;
+        self.disarmed.retain(|fd, &mut (generation, since)| {
+            let Some(subscriber) = registered.get(fd) else {
+                return false;
+            };
+            if subscriber.generation() != generation || subscriber.is_armed() {
+                return false;
+            }
+            if now - since <= batches {
+                return true;
+            }
+            stats.disarmed_leaks += 1;
+            #[cfg(feature = "log")]
+            log::warn!(
+                "{} is oneshot, but was not rearmed for {batches} batches",
+                describe_fd(*fd, subscriber.name())
+            );
+            false
+        });
+    }
+
+    /// Runs the closures queued by [`EventpOps::defer`], outside of any batch.
+    fn run_deferred(&mut self) {
+        deferred::run_deferred(self, |eventp| &mut eventp.deferred);
+    }
+
+    /// Calls the idle callback, if any, in the handling state of no fd, so that it
+    /// deletes subscribers as a handler would.
+    fn run_idle_callback(&mut self) {
+        let Some(mut callback) = self.idle_callback.take() else {
+            return;
+        };
+        {
+            let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+            handling.fd = -1;
+            handling.interest = Interest::default();
+            handling.delta = None;
+            handling.since_last = None;
+        }
+        // SAFETY: Same as for the handlers of the dispatch loop.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            callback(Pinned(unsafe { Pin::new_unchecked(&mut *self) }))
+        }));
+        // Even on a panic, as a change made by the callback applies over it.
+        self.idle_callback = Some(callback);
+        if let Err(payload) = result {
+            self.resume_panic(iter::empty(), payload);
+        }
+    }
+
+    /// Calls [`Handler::on_idle`](subscriber::Handler::on_idle) for every subscriber
+    /// past its idle deadline, and deletes those it does not keep, with their group.
+    fn evict_idle(&mut self) {
+        let now = self.idle.now();
+        while let Some((deadline, fd)) = self.idle.pop_due(now) {
+            // Entries are cancelled by `delete`, so `fd` is registered.
+            let Some(subscriber) = self.registered.get_mut(&fd) else {
+                continue;
+            };
+            let current = subscriber.idle_deadline();
+            if current > deadline {
+                // Pushed back by an event since the entry was scheduled.
+                self.idle.schedule(fd, current);
+                continue;
+            }
+
+            // Detached from the borrow of `self.registered`, as in the dispatch loop.
+            // SAFETY: Also as there: the owner stays in `self.registered`, or in
+            // `deferred_drop` once deleted.
+            let mut subscriber =
+                unsafe { ThinBoxSubscriber::<Eventp>::from_data(subscriber.to_data()) };
+            {
+                let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+                handling.fd = fd;
+                handling.interest = subscriber.interest();
+                handling.delta = None;
+                handling.since_last = None;
+            }
+
+            let mut evict = true;
+            if let Some(s) = subscriber.try_deref_mut() {
+                // SAFETY: See the dispatch loop.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    s.on_idle(Pinned(unsafe { Pin::new_unchecked(&mut *self) }))
+                }));
+                match result {
+                    Ok(keep) => evict = keep,
+                    Err(payload) => {
+                        self.on_handler_panic(subscriber.name());
+                        // Kept for another idle period, unless it deleted itself.
+                        let handling = unsafe { self.handling.as_ref().unwrap_unchecked() };
+                        if !handling.drop_current {
+                            if let Some(deadline) =
+                                subscriber.refresh_idle_deadline(self.idle.now())
+                            {
+                                self.idle.cancel(fd);
+                                self.idle.schedule(fd, deadline);
+                            }
+                        }
+                        // The events beyond the budget are kept already.
+                        self.resume_panic(iter::empty(), payload);
+                    }
+                }
+            }
+
+            let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+            if evict && !handling.drop_current {
+                // Only fails if the kernel rejects `EPOLL_CTL_DEL`, as under
+                // `ErrorPolicy::Remove`.
+                let _ = self.delete_group(fd);
+            }
+            let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+            if handling.drop_current {
+                handling.drop_current = false;
+                let evict = mem::take(&mut handling.evict_current);
+                let reason = handling.reason_current.take();
+                self.remove_deleted(fd, evict, reason);
+            } else if let Some(deadline) = subscriber.refresh_idle_deadline(self.idle.now()) {
+                // Kept: `on_idle` may have rescheduled it through `modify`.
+                self.idle.cancel(fd);
+                self.idle.schedule(fd, deadline);
+            }
+        }
+    }
+
+    /// Applies the [`StormAction`] to the fd of the handler that just returned, and
+    /// returns the panic of its callback, if any.
+    fn on_error_storm(
+        &mut self,
+        fd: RawFd,
+        action: StormAction,
+        name: Option<&str>,
+    ) -> thread::Result<()> {
+        self.stats.error_storms += 1;
+        #[cfg(feature = "log")]
+        log::warn!(
+            "{} keeps reporting EPOLLERR, applying {action:?}",
+            describe_fd(fd, name)
+        );
+        #[cfg(not(feature = "log"))]
+        let _ = name;
+
+        match action {
+            StormAction::Quarantine => {
+                // SAFETY: Same as in `delete`.
+                let ret = unsafe {
+                    libc::epoll_ctl(
+                        self.epoll.0.as_raw_fd(),
+                        libc::EPOLL_CTL_DEL,
+                        fd,
+                        ptr::null_mut(),
+                    )
+                };
+                // Otherwise, the fd was closed while registered, which `delete`
+                // and `sweep` deal with.
+                if ret == 0 {
+                    self.quarantined.insert(fd);
+                    if !self.pending.is_empty() {
+                        let data = self.registered[&fd].to_data();
+                        self.pending.retain(|ev| ev.data() != data);
+                    }
+                    self.lifecycle
+                        .deregistered(fd, DeregisterReason::Quarantined);
+                }
+            }
+            // As for `ErrorPolicy::Remove`, only the kernel rejecting
+            // `EPOLL_CTL_DEL` can fail this.
+            StormAction::Delete => {
+                let _ = self.delete_group(fd);
+            }
+            StormAction::Callback(f) => {
+                // SAFETY: Same as for the handlers of the dispatch loop.
+                return panic::catch_unwind(AssertUnwindSafe(|| {
+                    f(fd, Pinned(unsafe { Pin::new_unchecked(&mut *self) }))
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the [`ErrorPolicy`] to an error reported by the handler of the
+    /// currently-handled fd.
+    fn on_handler_error(&mut self, error: io::Error, name: Option<&str>) {
+        // SAFETY: Only called from the dispatch loop, where `handling` is `Some`.
+        let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+        let fd = handling.fd;
+
+        match self.error_policy {
+            #[cfg(feature = "log")]
+            ErrorPolicy::Log => {
+                log::warn!("handler for {} failed: {error}", describe_fd(fd, name));
+            }
+            // Without `log`, there is nowhere to report it.
+            #[cfg(not(feature = "log"))]
+            ErrorPolicy::Log => drop((error, name)),
+            ErrorPolicy::Remove => {
+                // The handler may already have deleted itself before failing.
+                if !handling.drop_current {
+                    // `fd` is still registered, so the only way this can fail is
+                    // the kernel rejecting `EPOLL_CTL_DEL`; then there is nothing
+                    // left to remove and the error has nowhere better to go.
+                    // A group shares one handler, so it goes as a whole.
+                    let _ = self.delete_group(fd);
+                }
+            }
+            ErrorPolicy::Propagate => {
+                if handling.error.is_none() {
+                    handling.error = Some((fd, error));
+                }
+            }
+        }
+    }
+
+    /// Ends the batch on a panic caught out of user code, keeping `undispatched` for
+    /// the next one, so that the loop still runs if the panic is caught around
+    /// `run_once`, then resumes the panic.
+    fn resume_panic<'a>(
+        &mut self,
+        undispatched: impl Iterator<Item = &'a EpollEvent>,
+        payload: Box<dyn Any + Send>,
+    ) -> ! {
+        self.pending.extend(undispatched);
+        let fd = self.handling.as_ref().map_or(-1, |handling| handling.fd);
+        self.panicked = (fd >= 0).then_some(fd);
+        self.end_abandoned_batch();
+        panic::resume_unwind(payload)
+    }
+
+    /// Counts the panic of the handler of the currently-handled fd, and with the
+    /// `log` feature, logs which one it was, before the panic is resumed. The panic
+    /// hook has printed the panic already.
+    fn on_handler_panic(&mut self, name: Option<&str>) {
+        self.stats.handler_panics += 1;
+        #[cfg(feature = "log")]
+        log::error!(
+            "handler for {} panicked",
+            // SAFETY: Only called from the dispatch loop, where `handling` is `Some`.
+            describe_fd(
+                unsafe { self.handling.as_ref().unwrap_unchecked() }.fd,
+                name
+            )
+        );
+        #[cfg(not(feature = "log"))]
+        let _ = name;
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl EventpOpsAdd<Self> for Eventp {
+    fn try_add(&mut self, mut subscriber: ThinBoxSubscriber<Self>) -> Result<(), AddError<Self>> {
+        self.debug_assert_same_process();
+        subscriber.set_generation(self.next_generation);
+        self.next_generation = self.next_generation.wrapping_add(1);
+        subscriber.set_order(self.next_order);
+        self.next_order = self.next_order.wrapping_add(1);
+
+        // The thin pointer, stashed in `epoll_event.data` without a borrow-checker
+        // tie. The subscriber itself is moved into `self.registered`.
+        let data = subscriber.to_data();
+
+        let dyn_subscriber = match subscriber.try_deref() {
+            Some(s) => s,
+            None => panic!("Subscriber is already dropped"),
+        };
+
+        let raw_fd = dyn_subscriber.as_fd().as_raw_fd();
+        if let Err(e) = self.validate_fd(raw_fd) {
+            return Err(AddError::new(e, subscriber));
+        }
+        if self.pending_removal(raw_fd) {
+            let error = io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                Error::PendingRemoval { fd: raw_fd },
+            );
+            return Err(AddError::new(error, subscriber));
+        }
+        if self.registered.contains_key(&raw_fd) {
+            let error = io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "subscriber with same fd already registered",
+            );
+            return Err(AddError::new(error, subscriber));
+        }
+
+        if self.shutting_down {
+            return Err(AddError::new(
+                Error::ShuttingDown { fd: raw_fd },
+                subscriber,
+            ));
+        }
+
+        let interest = subscriber.interest();
+        if self.reject_empty_interests && interest.is_empty() {
+            return Err(AddError::new(
+                Error::EmptyInterest { fd: raw_fd },
+                subscriber,
+            ));
+        }
+
+        if let Err(e) = interest.validate() {
+            return Err(AddError::new(e, subscriber));
+        }
+        if cfg!(debug_assertions) && self.debug_assert_nonblocking {
+            if let Err(e) = check_nonblocking(raw_fd, interest) {
+                return Err(AddError::new(e, subscriber));
+            }
+        }
+
+        if let (Some(0), Some(max)) = (self.capacity_remaining(), self.max_subscribers) {
+            return Err(AddError::new(Error::AtCapacity { max }, subscriber));
+        }
+        // Even with the current subscriber deleted, as it keeps its slot until its
+        // handler returns.
+        if let (true, Some(capacity)) = (self.registered.is_full(), self.registered.capacity()) {
+            return Err(AddError::new(Error::RegistryFull { capacity }, subscriber));
+        }
+
+        let bytes = subscriber.allocated_bytes();
+        if let Some(max) = self.max_subscriber_bytes {
+            let used = self.subscriber_bytes();
+            if bytes > max.saturating_sub(used) {
+                return Err(AddError::new(
+                    Error::OverMemoryBudget {
+                        fd: raw_fd,
+                        requested: bytes,
+                        used,
+                        max,
+                    },
+                    subscriber,
+                ));
+            }
+        }
+
+        #[cfg(feature = "debug-ownership")]
+        if let Err(e) = self.owner.claim(dyn_subscriber.as_fd()) {
+            return Err(AddError::new(e, subscriber));
+        }
+
+        let epoll_event = EpollEvent::new(interest.bitflags(), data);
+        if let Err(e) = self.epoll.add(dyn_subscriber.as_fd(), epoll_event) {
+            #[cfg(feature = "debug-ownership")]
+            self.owner.release(raw_fd);
+            let name = subscriber.name();
+            let error =
+                self.with_context("EPOLL_CTL_ADD", raw_fd, name, Error::exhausted(e.into()));
+            return Err(AddError::new(error, subscriber));
+        }
+
+        let mut subscriber = subscriber;
+        if let Some(deadline) = subscriber.refresh_idle_deadline(self.idle.now()) {
+            self.idle.schedule(raw_fd, deadline);
+        }
+        // Take ownership of the subscriber. This is the only place that owns it.
+        // There is room, as checked above.
+        if self.registered.insert(raw_fd, subscriber).is_err() {
+            unreachable!("registry full");
+        }
+        self.subscriber_bytes += bytes;
+        if let Some(keys) = &mut self.keys {
+            keys.added(raw_fd);
+        }
+        self.lifecycle.registered(raw_fd, interest);
+
+        Ok(())
+    }
+
+    fn add_all<I>(&mut self, subscribers: I) -> Vec<io::Result<RawFd>>
+    where
+        I: IntoIterator<Item = ThinBoxSubscriber<Self>>,
+    {
+        let subscribers = subscribers.into_iter();
+        // One rehash for the whole batch, rather than as the map grows.
+        self.registered.reserve(subscribers.size_hint().0);
+
+        subscribers
+            .map(|subscriber| {
+                let raw_fd = eventp_ops::raw_fd_of(&subscriber);
+                self.add(subscriber).map(|()| raw_fd)
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl EventpOps for Eventp {
+    #[doc = include_str!("../docs/eventp-ops.modify.md")]
+    fn modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<()> {
+        self.debug_assert_same_process();
+        // Deleted from the epoll already, and about to be dropped.
+        if self.pending_removal(fd) {
+            return Err(Error::PendingRemoval { fd }.into());
+        }
+        let Some(subscriber) = self.registered.get_mut(&fd) else {
+            return Err(self.not_registered("EPOLL_CTL_MOD", fd));
+        };
+        let interest = interest.or_user_data_of(subscriber.interest());
+
+        // The kernel refuses it with a bare `EINVAL`.
+        let exclusive = EpollFlags::EPOLLEXCLUSIVE;
+        if interest.bitflags().contains(exclusive)
+            || subscriber.interest().bitflags().contains(exclusive)
+        {
+            return Err(Error::ExclusiveModify { fd }.into());
+        }
+        interest.validate()?;
+
+        // The same data as in `add`, as `EPOLL_CTL_MOD` replaces it too.
+        let mut epoll_event = EpollEvent::new(interest.bitflags(), subscriber.to_data());
+
+        // SAFETY: This is a direct FFI call to `epoll_ctl`. The arguments are
+        // constructed correctly, so it's as safe as the underlying syscall.
+        let ret = unsafe {
+            libc::epoll_ctl(
+                self.epoll.0.as_raw_fd(),
+                libc::EPOLL_CTL_MOD,
+                fd,
+                &mut epoll_event as *mut _ as _,
+            )
+        };
+        if ret == -1 {
+            let error = io::Error::last_os_error();
+            #[cfg(debug_assertions)]
+            assert_watched(self.epoll.0.as_raw_fd(), fd, subscriber.interest());
+            let name = subscriber.name();
+            return Err(self.with_context("EPOLL_CTL_MOD", fd, name, error));
+        }
+        // Update the interest stored next to the subscriber.
+        let previous = subscriber.interest();
+        subscriber.set_interest(interest);
+        if previous.idle_millis() != interest.idle_millis() {
+            if previous.idle_millis() != 0 {
+                self.idle.cancel(fd);
+            }
+            if let Some(deadline) = subscriber.refresh_idle_deadline(self.idle.now()) {
+                self.idle.schedule(fd, deadline);
+            }
+        }
+        if let Some(handling) = &mut self.handling {
+            if handling.fd == fd {
+                handling.interest = interest;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn try_modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<bool> {
+        let always = EpollFlags::EPOLLONESHOT | EpollFlags::EPOLLEXCLUSIVE;
+        let unchanged = self
+            .registered
+            .get(&fd)
+            .is_some_and(|s| interest.or_user_data_of(s.interest()) == s.interest());
+        if unchanged && !self.pending_removal(fd) && !interest.bitflags().intersects(always) {
+            return Ok(false);
+        }
+        self.modify(fd, interest).map(|()| true)
+    }
+
+    fn current_interest(&self) -> Option<Interest> {
+        self.handling.as_ref().map(|handling| handling.interest)
+    }
+
+    fn current_delta(&self) -> Option<EventDelta> {
+        self.handling.as_ref().and_then(|handling| handling.delta)
+    }
+
+    fn since_last_event(&self) -> Option<Duration> {
+        self.handling
+            .as_ref()
+            .and_then(|handling| handling.since_last)
+    }
+
+    fn batch_info(&self) -> Option<BatchInfo> {
+        self.handling.as_ref().map(|handling| handling.batch)
+    }
+
+    fn user_data_of(&self, fd: RawFd) -> Option<u64> {
+        self.registered
+            .get(&fd)
+            .map(|subscriber| subscriber.interest().user_data_value())
+    }
+
+    fn set_user_data(&mut self, fd: RawFd, data: u64) -> io::Result<()> {
+        self.debug_assert_same_process();
+        if self.pending_removal(fd) {
+            return Err(Error::PendingRemoval { fd }.into());
+        }
+        let Some(subscriber) = self.registered.get_mut(&fd) else {
+            return Err(self.not_registered("set_user_data", fd));
+        };
+        subscriber.set_user_data(data);
+        if let Some(handling) = &mut self.handling {
+            if handling.fd == fd {
+                handling.interest = handling.interest.user_data(data);
+            }
+        }
+        Ok(())
+    }
+
+    fn defer(&mut self, f: Deferred<Self>) {
+        self.deferred.push_back(f);
+    }
+
+    #[doc = include_str!("../docs/eventp-ops.delete.md")]
+    fn delete(&mut self, fd: RawFd) -> io::Result<()> {
+        self.delete_as(fd, DeregisterReason::ExplicitDelete)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Eventp {
+    /// [`delete`](EventpOps::delete), reporting `reason` to
+    /// [`on_deregister`](Builder::on_deregister).
+    fn delete_as(&mut self, fd: RawFd, reason: DeregisterReason) -> io::Result<()> {
+        self.debug_assert_same_process();
+        if self.pending_removal(fd) {
+            return Err(Error::PendingRemoval { fd }.into());
+        }
+        if !self.registered.contains_key(&fd) {
+            return Err(self.not_registered("EPOLL_CTL_DEL", fd));
+        }
+        // No longer watched, so not to be deleted from the epoll.
+        if !self.quarantined.is_empty() && self.quarantined.contains(&fd) {
+            self.unregister(fd, false, reason);
+            return Ok(());
+        }
+
+        // Use a direct syscall for `EPOLL_CTL_DEL` as `nix`'s `epoll.delete`
+        // requires a `AsFd` source, which we may not have if the source is already dropped.
+        // We only need the raw fd.
+        // SAFETY: This is a direct FFI call to `epoll_ctl`. The arguments are
+        // constructed correctly, so it's as safe as the underlying syscall.
+        let ret = unsafe {
+            libc::epoll_ctl(
+                self.epoll.0.as_raw_fd(),
+                libc::EPOLL_CTL_DEL,
+                fd,
+                ptr::null_mut(),
+            )
+        };
+        if ret == -1 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                // The fd was closed while registered, and possibly reused since.
+                Some(libc::EBADF | libc::ENOENT) => {
+                    self.evict(fd, reason);
+                    self.release_evicted();
+                }
+                _ => {
+                    let name = self.registered[&fd].name();
+                    return Err(self.with_context("EPOLL_CTL_DEL", fd, name, err));
+                }
+            }
+        } else {
+            self.unregister(fd, false, reason);
+        }
+        Ok(())
+    }
+
+    /// Returns `err`, of `op` on `fd`, as an [`Error::Syscall`] if it is an OS error
+    /// and [`Builder::error_context`] is enabled.
+    fn with_context(
+        &self,
+        op: &'static str,
+        fd: RawFd,
+        name: Option<&'static str>,
+        err: io::Error,
+    ) -> io::Error {
+        match err.raw_os_error() {
+            Some(errno) if self.error_context => Error::Syscall {
+                op,
+                fd,
+                name,
+                errno,
+            }
+            .into(),
+            _ => err,
+        }
+    }
+
+    /// The error of `op` on `fd`, which is not registered.
+    /// Stops the kernel from watching `EPOLLOUT` for a level-triggered subscriber
+    /// whose write readiness was just dropped by
+    /// [`writable_edge_emulation`](Interest::writable_edge_emulation), as the fd
+    /// would otherwise wake every wait while writable. `modify` watches it again.
+    fn park_writable(&self, subscriber: &ThinBoxSubscriber<Eventp>) {
+        let flags = subscriber.interest().remove_write().bitflags();
+        let mut epoll_event = EpollEvent::new(flags, subscriber.to_data());
+        // SAFETY: Same as in `modify`. A failure, e.g. for an fd closed behind the
+        // loop's back, only leaves the fd waking the loop as it did.
+        unsafe {
+            libc::epoll_ctl(
+                self.epoll.0.as_raw_fd(),
+                libc::EPOLL_CTL_MOD,
+                *subscriber.raw_fd_ref(),
+                &mut epoll_event as *mut _ as _,
+            )
+        };
+    }
+
+    fn not_registered(&self, op: &'static str, fd: RawFd) -> io::Error {
+        if !self.error_context {
+            return io::Error::new(io::ErrorKind::NotFound, "fd not registered");
+        }
+        Error::Syscall {
+            op,
+            fd,
+            name: None,
+            errno: libc::ENOENT,
+        }
+        .into()
+    }
+
+    /// Rejects the fds `add` would otherwise hand to the kernel for a bare `EBADF`
+    /// or `EINVAL`, or in debug builds, an `EBADF` the fd may no longer explain by
+    /// the time it is looked at.
+    fn validate_fd(&self, fd: RawFd) -> Result<(), Error> {
+        if fd < 0 {
+            return Err(Error::NegativeFd { fd });
+        }
+        if fd == self.epoll.0.as_raw_fd() {
+            return Err(Error::SelfRegistration { fd });
+        }
+        #[cfg(debug_assertions)]
+        {
+            // SAFETY: `F_GETFD` takes no pointer, and fails on a closed fd.
+            let ret = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+            if ret == -1 && io::Error::last_os_error().raw_os_error() == Some(libc::EBADF) {
+                return Err(Error::ClosedFd { fd });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `fd` is that of the running handler, which deleted itself:
+    /// its subscriber is kept registered until the handler returns.
+    fn pending_removal(&self, fd: RawFd) -> bool {
+        matches!(&self.handling, Some(h) if h.drop_current && h.fd == fd)
+    }
+
+    /// The part of `delete` after `EPOLL_CTL_DEL`: forgets the registered `fd`, and
+    /// drops its subscriber, now or at the end of the batch, then reports `reason`
+    /// unless it was quarantined.
+    ///
+    /// With `evict`, the subscriber then goes to `self.evicted` rather than being
+    /// deallocated.
+    fn unregister(&mut self, fd: RawFd, evict: bool, reason: DeregisterReason) {
+        #[cfg(feature = "debug-ownership")]
+        self.owner.release(fd);
+
+        if self.registered[&fd].interest().idle_millis() != 0 {
+            self.idle.cancel(fd);
+        }
+
+        if let Some(members) = self.groups.remove(&fd) {
+            members.borrow_mut().retain(|&member| member != fd);
+        }
+
+        if let Some(storms) = &mut self.error_storms {
+            storms.forget(fd);
+        }
+        // Reported when quarantined.
+        let quarantined = !self.quarantined.is_empty() && self.quarantined.remove(&fd);
+        let reason = (!quarantined).then_some(reason);
+
+        if let Some(keys) = &mut self.keys {
+            keys.deleted(fd);
+        }
+
+        if !self.pending.is_empty() {
+            let data = self.registered[&fd].to_data();
+            self.pending.retain(|ev| ev.data() != data);
+        }
+
+        if let Some(handling) = &mut self.handling {
+            if handling.fd == fd {
+                // Delete self while handling. This will actually do the drop
+                // after the handler returns.
+                handling.drop_current = true;
+                handling.evict_current = evict;
+                handling.reason_current = reason;
+            } else {
+                // Delete another fd while handling.
+
+                // Safe to unwrap, because just checked that it exists.
+                let mut subscriber = self.registered.remove(&fd).unwrap();
+                self.subscriber_bytes -= subscriber.allocated_bytes();
+
+                // Drop in place immediately. This will not release the heap memory.
+                subscriber.drop_in_place();
+
+                // Defer the dealloc to the end of the event dispatch, or to the
+                // drop of the loop if evicted.
+                if evict {
+                    self.evicted.push(subscriber);
+                } else {
+                    self.deferred_drop.push(subscriber);
+                }
+                if let Some(reason) = reason {
+                    self.lifecycle.deregistered(fd, reason);
+                }
+            }
+        } else {
+            // Otherwise, it's safe to remove immediately.
+            self.remove_deleted(fd, evict, reason);
+        }
+    }
+
+    /// Removes the registry entry of the deleted `fd`, drops its subscriber, then
+    /// reports `reason`, if any.
+    ///
+    /// With `evict`, the subscriber is only dropped in place, and kept allocated in
+    /// `self.evicted`.
+    fn remove_deleted(&mut self, fd: RawFd, evict: bool, reason: Option<DeregisterReason>) {
+        let Some(mut subscriber) = self.registered.remove(&fd) else {
+            return;
+        };
+        self.subscriber_bytes -= subscriber.allocated_bytes();
+        if evict {
+            subscriber.drop_in_place();
+            self.evicted.push(subscriber);
+        } else {
+            drop(subscriber);
+        }
+        if let Some(reason) = reason {
+            self.lifecycle.deregistered(fd, reason);
+        }
+    }
+
+    /// Forgets the registered `fd`, found closed while registered, and reports
+    /// `reason`: that of the `delete` which found it, or a sweep.
+    fn evict(&mut self, fd: RawFd, reason: DeregisterReason) {
+        #[cfg(feature = "log")]
+        log::warn!("fd {fd} was closed while registered, evicting its subscriber");
+        self.stats.fds_evicted += 1;
+        self.unregister(fd, true, reason);
+    }
+
+    /// Deallocates the evicted subscribers whose registration the epoll no longer
+    /// lists, at the end of the batch if called from a handler. Keeps them all if
+    /// its fdinfo cannot be read.
+    fn release_evicted(&mut self) {
+        if self.evicted.is_empty() {
+            return;
+        }
+        let Some(watched) = watched_data(self.epoll.0.as_raw_fd()) else {
+            return;
+        };
+        let (kept, released): (Vec<_>, Vec<_>) = mem::take(&mut self.evicted)
+            .into_iter()
+            .partition(|subscriber| watched.contains(&subscriber.to_data()));
+        self.evicted = kept;
+        // Events of the batch may still point to them. Deallocated here otherwise.
+        if self.handling.is_some() {
+            self.deferred_drop.extend(released);
+        }
+    }
+
+    /// Ends the handling state left behind by a handler that panicked, by an
+    /// [`EventIter`], or by the shutdown hooks, as if its batch had ended. Does
+    /// nothing if there is none.
+    ///
+    /// Must not be called while a batch is dispatched, which holds `&mut self`.
+    fn end_abandoned_batch(&mut self) {
+        let Some(handling) = self.handling.take() else {
+            return;
+        };
+        if handling.drop_current {
+            self.remove_deleted(handling.fd, handling.evict_current, handling.reason_current);
+        }
+        self.deferred_drop.clear();
+        if let Some(callback) = self.idle_callback_update.take() {
+            self.idle_callback = callback;
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::os::fd::{AsFd, BorrowedFd};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use super::*;
+    use crate::subscriber::{Handler, HasInterest};
+    use crate::tri_subscriber::{RawFdSource, WithHandler, WithState};
+
+    fn new_eventfd() -> EventFd {
+        EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap()
+    }
+
+    /// Wakes an `eventfd` so the next `epoll_wait` call returns it as ready.
+    fn fire(efd: &EventFd) {
+        efd.write(1).expect("eventfd write");
+    }
+
+    /// Drains an `eventfd` so a level-triggered subscriber doesn't keep firing.
+    fn drain(efd: &EventFd) {
+        // Ignore WouldBlock; we only care that the kernel-side counter resets.
+        let _ = efd.read();
+    }
+
+    /// A subscriber backed by an `EventFd` whose handler invokes a closure.
+    /// The closure receives the fd's raw value and the `Pinned` reactor handle,
+    /// so re-entrancy scenarios (delete/add/modify from inside a handler) can
+    /// be exercised directly.
+    struct CbSub<F> {
+        eventfd: EventFd,
+        interest: Cell<Interest>,
+        f: F,
+    }
+
+    impl<F> AsFd for CbSub<F> {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.eventfd.as_fd()
+        }
+    }
+
+    impl<F> HasInterest for CbSub<F> {
+        fn interest(&self) -> &Cell<Interest> {
+            &self.interest
+        }
+    }
+
+    impl<F> Handler<Eventp> for CbSub<F>
+    where
+        F: FnMut(&EventFd, Pinned<'_, Eventp>),
+    {
+        fn handle(&mut self, _event: Event, eventp: Pinned<'_, Eventp>) {
+            // Drain so level-triggered notifications don't repeat within the
+            // same batch (each test wires a single, deterministic sequence).
+            drain(&self.eventfd);
+            (self.f)(&self.eventfd, eventp);
+        }
+    }
+
+    fn cb_sub<F>(efd: EventFd, f: F) -> CbSub<F>
+    where
+        F: FnMut(&EventFd, Pinned<'_, Eventp>),
+    {
+        CbSub {
+            eventfd: efd,
+            interest: Cell::new(crate::interest().read()),
+            f,
+        }
+    }
+
+    /// Returns an `EventFd` sharing the kernel object of `efd`, so a test can
+    /// fire an eventfd whose owner has been moved into the reactor.
+    fn writer_for(efd: &EventFd) -> EventFd {
+        let dup = efd.as_fd().try_clone_to_owned().unwrap();
+        unsafe { EventFd::from_owned_fd(dup) }
+    }
+
+    /// Registers an eventfd whose handler drains it and then fails with
+    /// `PermissionDenied`. Returns the raw fd and a writer to fire it.
+    fn register_failing(ep: &mut Eventp) -> (RawFd, EventFd) {
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|efd: &mut EventFd| -> io::Result<()> {
+                drain(efd);
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied"))
+            })
+            .register_into(ep)
+            .unwrap();
+        (raw, writer)
+    }
+
+    /// A subscriber that *borrows* an fd it does not own. Useful for tests
+    /// that need to register the same `RawFd` twice (which is impossible with
+    /// `OwnedFd`, since dup yields a new fd number).
+    struct BorrowSub {
+        raw: RawFd,
+        interest: Cell<Interest>,
+    }
+    impl AsFd for BorrowSub {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            // SAFETY: each test that constructs a `BorrowSub` keeps the
+            // owning `EventFd` alive for the whole test body.
+            unsafe { BorrowedFd::borrow_raw(self.raw) }
+        }
+    }
+    impl HasInterest for BorrowSub {
+        fn interest(&self) -> &Cell<Interest> {
+            &self.interest
+        }
+    }
+    impl Handler<Eventp> for BorrowSub {
+        fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {}
+    }
+
+    /// Short timeout used by every test; long enough to absorb scheduling
+    /// jitter on a busy CI runner, short enough to fail fast on a real bug.
+    fn poll_timeout() -> EpollTimeout {
+        EpollTimeout::from(500u16)
+    }
+
+    #[test]
+    fn default_creates_usable_reactor() {
+        let mut ep = Eventp::default();
+        // No subscribers: epoll_wait should time out cleanly.
+        ep.run_once_with_timeout(EpollTimeout::from(10u16)).unwrap();
+    }
+
+    #[test]
+    fn new_rejects_zero_and_huge_capacities() {
+        // A zero-length event buffer cannot dispatch anything (`epoll_wait`
+        // would also reject it with EINVAL), so the constructor checks it up
+        // front.
+        for capacity in [0, MAX_EVENT_BUF_CAPACITY + 1, usize::MAX] {
+            let Err(err) = Eventp::new(capacity, EpollCreateFlags::EPOLL_CLOEXEC) else {
+                panic!("capacity {capacity} accepted");
+            };
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        let ep = Eventp::new(MAX_EVENT_BUF_CAPACITY, EpollCreateFlags::EPOLL_CLOEXEC).unwrap();
+        assert_eq!(ep.event_buf.len(), MAX_EVENT_BUF_CAPACITY);
+        assert_eq!(Eventp::default().event_buf.len(), 256);
+    }
+
+    #[test]
+    fn add_duplicate_fd_returns_already_exists() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+
+        // First registration owns the fd.
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+
+        // Second registration references the same `RawFd` via a non-owning
+        // wrapper. `Eventp::add` must reject it before reaching `epoll_ctl`,
+        // returning `AlreadyExists`.
+        let err = BorrowSub {
+            raw,
+            interest: Cell::new(crate::interest().read()),
+        }
+        .register_into(&mut ep)
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    /// Adds a [`BorrowSub`] of `raw`, which the test keeps open if it is open.
+    fn add_borrowed(ep: &mut Eventp, raw: RawFd) -> io::Error {
+        BorrowSub {
+            raw,
+            interest: Cell::new(crate::interest().read()),
+        }
+        .register_into(ep)
+        .unwrap_err()
+    }
+
+    #[test]
+    fn add_rejects_a_negative_fd() {
+        let mut ep = Eventp::default();
+        let err = add_borrowed(&mut ep, -2);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(Error::from_io(&err), Some(&Error::NegativeFd { fd: -2 }));
+        assert!(ep.is_empty());
+    }
+
+    #[test]
+    fn add_rejects_the_epoll_fd_of_the_loop() {
+        let mut ep = Eventp::default();
+        let raw = ep.poll_fd().as_raw_fd();
+        let err = add_borrowed(&mut ep, raw);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::SelfRegistration { fd: raw })
+        );
+
+        // Another loop may still register it.
+        let mut parent = Eventp::default();
+        BorrowSub {
+            raw,
+            interest: Cell::new(crate::interest().read()),
+        }
+        .register_into(&mut parent)
+        .unwrap();
+    }
+
+    #[test]
+    fn add_rejects_a_closed_fd_in_debug_builds() {
+        let mut ep = Eventp::default();
+        // Far above any fd a parallel test could open meanwhile.
+        let err = add_borrowed(&mut ep, RawFd::MAX);
+        if cfg!(debug_assertions) {
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(
+                Error::from_io(&err),
+                Some(&Error::ClosedFd { fd: RawFd::MAX })
+            );
+        } else {
+            assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+        }
+    }
+
+    fn is_nonblocking(fd: BorrowedFd<'_>) -> bool {
+        // SAFETY: `F_GETFL` takes no pointer.
+        let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) };
+        assert_ne!(flags, -1);
+        flags & libc::O_NONBLOCK != 0
+    }
+
+    #[test]
+    fn with_fd_nonblocking_sets_o_nonblocking() {
+        let (a, b) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert!(!is_nonblocking(a.as_fd()));
+        let (_, a) = crate::interest().read().with_fd_nonblocking(a).unwrap();
+        assert!(is_nonblocking(a.as_fd()));
+        assert!(!is_nonblocking(b.as_fd()));
+
+        // Not only sockets, and already nonblocking is fine.
+        let (read, _write) = nix::unistd::pipe().unwrap();
+        let (_, read) = crate::interest()
+            .read()
+            .edge_triggered()
+            .exclusive()
+            .with_fd_nonblocking(read)
+            .unwrap();
+        let (_, read) = crate::interest().read().with_fd_nonblocking(read).unwrap();
+        assert!(is_nonblocking(read.as_fd()));
+    }
+
+    #[test]
+    fn debug_assert_nonblocking_refuses_a_blocking_edge_triggered_socket() {
+        use std::os::unix::net::UnixStream;
+
+        let mut ep = Eventp::builder()
+            .debug_assert_nonblocking(true)
+            .build()
+            .unwrap();
+        let add = |ep: &mut Eventp, interest: Interest, stream: UnixStream| {
+            interest
+                .with_fd(stream)
+                .with_handler(|_: &mut UnixStream| {})
+                .register_into(ep)
+        };
+        let (blocking, _peer) = UnixStream::pair().unwrap();
+        let raw = blocking.as_raw_fd();
+
+        let result = add(&mut ep, crate::interest().read().edge_triggered(), blocking);
+        if cfg!(debug_assertions) {
+            let err = result.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(Error::from_io(&err), Some(&Error::BlockingFd { fd: raw }));
+        } else {
+            result.unwrap();
+        }
+
+        // Level-triggered, or nonblocking, it is fine.
+        let (blocking, _peer) = UnixStream::pair().unwrap();
+        add(&mut ep, crate::interest().read(), blocking).unwrap();
+        let (stream, _peer) = UnixStream::pair().unwrap();
+        let (interest, stream) = crate::interest()
+            .read()
+            .edge_triggered()
+            .with_fd_nonblocking(stream)
+            .unwrap();
+        add(&mut ep, interest, stream).unwrap();
+
+        // A regular file is left to the kernel, which refuses it anyway.
+        let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+        let err = crate::interest()
+            .read()
+            .edge_triggered()
+            .with_fd(file)
+            .with_handler(|| {})
+            .register_into(&mut ep)
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        // Not checked unless asked for.
+        let mut ep = Eventp::default();
+        let (blocking, _peer) = UnixStream::pair().unwrap();
+        add(&mut ep, crate::interest().read().edge_triggered(), blocking).unwrap();
+    }
+
+    #[test]
+    fn contains_tracks_add_and_delete() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        assert!(!ep.contains(raw));
+
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        assert!(ep.contains(raw));
+
+        ep.delete(raw).unwrap();
+        assert!(!ep.contains(raw));
+    }
+
+    #[test]
+    fn add_all_carries_on_past_failures() {
+        let mut ep = Eventp::default();
+        let (first, second, third) = (new_eventfd(), new_eventfd(), new_eventfd());
+        let raw = |efd: &EventFd| efd.as_fd().as_raw_fd();
+        let (raw1, raw2, raw3) = (raw(&first), raw(&second), raw(&third));
+        cb_sub(first, |_, _| {}).register_into(&mut ep).unwrap();
+
+        let results = ep.add_all([
+            ThinBoxSubscriber::new(cb_sub(second, |_, _| {})),
+            ThinBoxSubscriber::new(BorrowSub {
+                raw: raw1,
+                interest: Cell::new(crate::interest().read()),
+            }),
+            ThinBoxSubscriber::new(cb_sub(third, |_, _| {})),
+        ]);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &raw2);
+        assert_eq!(
+            results[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(results[2].as_ref().unwrap(), &raw3);
+        assert!(ep.contains(raw1) && ep.contains(raw2) && ep.contains(raw3));
+    }
+
+    #[test]
+    fn delete_all_carries_on_past_failures() {
+        let mut ep = Eventp::default();
+        let (first, second) = (new_eventfd(), new_eventfd());
+        let (raw1, raw2) = (first.as_fd().as_raw_fd(), second.as_fd().as_raw_fd());
+        cb_sub(first, |_, _| {}).register_into(&mut ep).unwrap();
+        cb_sub(second, |_, _| {}).register_into(&mut ep).unwrap();
+
+        let results = ep.delete_all(&[raw1, 424242, raw2]);
+
+        assert!(results[0].is_ok() && results[2].is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(!ep.contains(raw1) && !ep.contains(raw2));
+    }
+
+    #[test]
+    fn batch_ops_from_inside_a_handler() {
+        let mut ep = Eventp::default();
+        let trigger = new_eventfd();
+        let writer = writer_for(&trigger);
+        let accepted: Vec<EventFd> = (0..4).map(|_| new_eventfd()).collect();
+        let raws: Vec<RawFd> = accepted.iter().map(|e| e.as_fd().as_raw_fd()).collect();
+
+        let mut accepted = Some(accepted);
+        let added = Rc::new(RefCell::new(Vec::new()));
+        let a = added.clone();
+        cb_sub(trigger, move |_, mut eventp| {
+            let batch = accepted
+                .take()
+                .into_iter()
+                .flatten()
+                .map(|efd| ThinBoxSubscriber::new(cb_sub(efd, |_, _| {})));
+            let fds: Vec<RawFd> = eventp.add_all(batch).into_iter().flatten().collect();
+            // Deleting the batch again in the same dispatch defers its drop.
+            assert!(eventp.delete_all(&fds[2..]).iter().all(|r| r.is_ok()));
+            *a.borrow_mut() = fds;
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        assert_eq!(*added.borrow(), raws);
+        assert!(ep.contains(raws[0]) && ep.contains(raws[1]));
+        assert!(!ep.contains(raws[2]) && !ep.contains(raws[3]));
+    }
+
+    #[test]
+    fn modify_unknown_fd_returns_not_found() {
+        let mut ep = Eventp::default();
+        let err = ep.modify(424242, crate::interest().read()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn delete_unknown_fd_returns_not_found() {
+        let mut ep = Eventp::default();
+        let err = ep.delete(424242).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn ready_event_dispatches_to_handler() {
+        let mut ep = Eventp::default();
+
+        let efd = new_eventfd();
+        let counter = Rc::new(Cell::new(0u32));
+        let c = counter.clone();
+        cb_sub(efd, move |_, _| c.set(c.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+
+        // Use the registered fd to wake itself.
+        let raw = ep.registered.keys().copied().next().unwrap();
+        let dup = unsafe { BorrowedFd::borrow_raw(raw) }
+            .try_clone_to_owned()
+            .unwrap();
+        let writer = unsafe { EventFd::from_owned_fd(dup) };
+        fire(&writer);
+
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(counter.get(), 1);
+    }
+
+    #[test]
+    fn handler_can_delete_self_and_subscriber_is_dropped() {
+        let mut ep = Eventp::default();
+
+        // Track whether the subscriber's destructor ran by tying it to an Rc
+        // that we observe externally.
+        let drop_witness = Rc::new(());
+        let weak = Rc::downgrade(&drop_witness);
+
+        struct Witness(#[allow(dead_code)] Rc<()>);
+
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+
+        let sub = CbSub {
+            eventfd: efd,
+            interest: Cell::new(crate::interest().read()),
+            f: {
+                let _w = Witness(drop_witness);
+                move |fd: &EventFd, mut ep: Pinned<'_, Eventp>| {
+                    drain(fd);
+                    // Touching `_w` here keeps it alive until the closure drops.
+                    let _ = &_w;
+                    ep.delete(fd.as_fd().as_raw_fd()).unwrap();
+                }
+            },
+        };
+        sub.register_into(&mut ep).unwrap();
+
+        let dup = unsafe { BorrowedFd::borrow_raw(raw) }
+            .try_clone_to_owned()
+            .unwrap();
+        let writer = unsafe { EventFd::from_owned_fd(dup) };
+        fire(&writer);
+
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        // After dispatch, the registry must no longer contain the fd, and the
+        // subscriber (which captured the strong Rc) must have been dropped.
+        assert!(!ep.registered.contains_key(&raw));
+        assert!(
+            weak.upgrade().is_none(),
+            "self-delete must drop the subscriber"
+        );
+    }
+
+    #[test]
+    fn handler_can_delete_other_fd_during_dispatch() {
+        let mut ep = Eventp::default();
+
+        // Subscriber A: when triggered, deletes B.
+        let efd_a = new_eventfd();
+        let raw_a = efd_a.as_fd().as_raw_fd();
+
+        // Subscriber B: tracks its own destruction.
+        let efd_b = new_eventfd();
+        let raw_b = efd_b.as_fd().as_raw_fd();
+        let b_drop = Rc::new(());
+        let b_weak = Rc::downgrade(&b_drop);
+
+        struct B {
+            eventfd: EventFd,
+            interest: Cell<Interest>,
+            _w: Rc<()>,
+        }
+        impl AsFd for B {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.eventfd.as_fd()
+            }
+        }
+        impl HasInterest for B {
+            fn interest(&self) -> &Cell<Interest> {
+                &self.interest
+            }
+        }
+        impl Handler<Eventp> for B {
+            fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {}
+        }
+
+        B {
+            eventfd: efd_b,
+            interest: Cell::new(crate::interest().read()),
+            _w: b_drop,
+        }
+        .register_into(&mut ep)
+        .unwrap();
+
+        cb_sub(efd_a, move |_, mut ep| {
+            ep.delete(raw_b).unwrap();
+            // While we are still inside A's handler, B's heap slot must NOT
+            // yet be deallocated -- the deferred_drop list keeps it alive
+            // until end-of-batch. We can't observe that directly, but we can
+            // confirm B's entry was already removed from the registry.
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        let dup = unsafe { BorrowedFd::borrow_raw(raw_a) }
+            .try_clone_to_owned()
+            .unwrap();
+        fire(&unsafe { EventFd::from_owned_fd(dup) });
+
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        assert!(!ep.registered.contains_key(&raw_b));
+        assert!(
+            b_weak.upgrade().is_none(),
+            "B must be deallocated by end of dispatch"
+        );
+    }
+
+    #[test]
+    fn churned_registrations_only_see_their_own_events() {
+        const SLOTS: usize = 8;
+        const ROUNDS: u64 = 200;
+
+        /// Registers an eventfd holding `id`, whose handler checks it reads `id`, and
+        /// writes it back to stay ready.
+        fn register_slot(
+            id: u64,
+            counts: &Rc<(Cell<u64>, Cell<u64>)>,
+            ep: &mut impl EventpOpsAdd<Eventp>,
+        ) -> RawFd {
+            use crate::tri_subscriber::WithHandler;
+
+            let efd = new_eventfd();
+            efd.write(id).unwrap();
+            let raw = efd.as_fd().as_raw_fd();
+            let counts = counts.clone();
+            crate::interest()
+                .read()
+                .with_fd(efd)
+                .with_handler(move |efd: &mut EventFd| match efd.read() {
+                    Ok(value) if value == id => {
+                        counts.0.set(counts.0.get() + 1);
+                        efd.write(id).unwrap();
+                    }
+                    _ => counts.1.set(counts.1.get() + 1),
+                })
+                .register_into(ep)
+                .unwrap();
+            raw
+        }
+
+        let mut ep = Eventp::builder().priority_dispatch(true).build().unwrap();
+        // Events read by their own handler, and events a handler found nothing for.
+        let counts = Rc::new((Cell::new(0), Cell::new(0)));
+        let slots: Vec<RawFd> = (1..=SLOTS as u64)
+            .map(|id| register_slot(id, &counts, &mut ep))
+            .collect();
+        let slots = Rc::new(RefCell::new(slots));
+
+        // Dispatched first, replaces every other slot while its event is in the
+        // batch. The fds, and likely the allocations of earlier rounds, are reused
+        // right away.
+        let (c, s) = (counts.clone(), slots.clone());
+        let mut next_id = SLOTS as u64 + 1;
+        let mut round = 0;
+        let reaper = new_eventfd();
+        let fire_reaper = writer_for(&reaper);
+        cb_sub(reaper, move |efd, mut ep| {
+            drain(efd);
+            round += 1;
+            let mut slots = s.borrow_mut();
+            for slot in slots.iter_mut().skip(round % 2).step_by(2) {
+                ep.delete(*slot).unwrap();
+                *slot = register_slot(next_id, &c, &mut ep);
+                next_id += 1;
+            }
+        })
+        .register_with_interest(crate::interest().read().dispatch_priority(0), &mut ep)
+        .unwrap();
+
+        for _ in 0..ROUNDS {
+            fire(&fire_reaper);
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        assert_eq!(
+            counts.1.get(),
+            0,
+            "a handler was dispatched an event of another"
+        );
+        assert_eq!(ep.stats().stale_events, 0);
+        // Half of the slots survive each round.
+        assert!(
+            counts.0.get() >= ROUNDS * SLOTS as u64 / 2,
+            "{}",
+            counts.0.get()
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn events_of_an_earlier_generation_are_dropped() {
+        let mut ep = Eventp::default();
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        cb_sub(efd, move |_, _| c.set(c.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+
+        // As the kernel would hand back an event of a previous registration, had
+        // the allocation been reused.
+        let data = ep.registered[&raw].to_data();
+        let stale = data.wrapping_sub(1 << 48);
+        ep.pending.push(EpollEvent::new(EpollFlags::EPOLLIN, stale));
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(calls.get(), 0);
+        assert_eq!(ep.stats().stale_events, 1);
+
+        ep.pending.push(EpollEvent::new(EpollFlags::EPOLLIN, data));
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(calls.get(), 1);
+        assert_eq!(ep.stats().stale_events, 1);
+    }
+
+    #[test]
+    fn handler_can_re_add_other_fd_after_delete() {
+        // CHANGELOG note: deleting another fd from inside a handler removes
+        // its registry entry *immediately* (its heap slot is parked on
+        // `deferred_drop` and dealloc'd at end-of-batch). That gap allows the
+        // same `RawFd` to be re-added inside the same dispatch.
+        //
+        // Note: the analogous self-delete + self-re-add is NOT supported,
+        // because self-delete only sets `drop_current = true` and leaves the
+        // registry entry in place until the dispatch loop tail. We pin that
+        // contract in `self_delete_then_re_add_same_fd_returns_already_exists`
+        // below.
+        let mut ep = Eventp::default();
+
+        // Subscriber A wakes B's deletion + re-add.
+        let efd_a = new_eventfd();
+        let raw_a = efd_a.as_fd().as_raw_fd();
+
+        // B is owned by the test; the subscribers only borrow its fd.
+        let efd_b = new_eventfd();
+        let raw_b = efd_b.as_fd().as_raw_fd();
+
+        let marker = Rc::new(Cell::new(0u32));
+        let m_for_first = marker.clone();
+        // First B subscriber: never fires in this test, only its re-add does.
+        struct CountingBorrowSub {
+            raw: RawFd,
+            interest: Cell<Interest>,
+            marker: Rc<Cell<u32>>,
+        }
+        impl AsFd for CountingBorrowSub {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                unsafe { BorrowedFd::borrow_raw(self.raw) }
+            }
+        }
+        impl HasInterest for CountingBorrowSub {
+            fn interest(&self) -> &Cell<Interest> {
+                &self.interest
+            }
+        }
+        impl Handler<Eventp> for CountingBorrowSub {
+            fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {
+                self.marker.set(self.marker.get() + 1);
+            }
+        }
+
+        CountingBorrowSub {
+            raw: raw_b,
+            interest: Cell::new(crate::interest().read()),
+            marker: m_for_first,
+        }
+        .register_into(&mut ep)
+        .unwrap();
+
+        let m_for_readd = marker.clone();
+        cb_sub(efd_a, move |_, mut ep| {
+            ep.delete(raw_b).unwrap();
+            CountingBorrowSub {
+                raw: raw_b,
+                interest: Cell::new(crate::interest().read()),
+                marker: m_for_readd.clone(),
+            }
+            .register_into(&mut ep)
+            .expect("re-add of same fd within handler must succeed");
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        // Trigger A; its handler swaps out B's subscriber.
+        let dup_a = unsafe { BorrowedFd::borrow_raw(raw_a) }
+            .try_clone_to_owned()
+            .unwrap();
+        fire(&unsafe { EventFd::from_owned_fd(dup_a) });
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(ep.registered.contains_key(&raw_b));
+        assert_eq!(marker.get(), 0, "B's handler should not have run yet");
+
+        // Now wake B; the *re-added* subscriber should be the one that fires.
+        fire(&efd_b);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(marker.get(), 1);
+
+        // Drain the registry before `efd_b` goes out of scope so the
+        // borrow-style subscriber's fd reference doesn't outlive the owner.
+        let _ = ep.into_parts();
+        drop(efd_b);
+    }
+
+    #[test]
+    fn self_delete_then_re_add_same_fd_returns_already_exists() {
+        // Documents the *opposite* contract from the test above: the
+        // currently-handled subscriber's registry entry survives until the
+        // dispatch-loop tail (because self-delete only flips `drop_current`),
+        // so an attempt to re-add the same `RawFd` inside the same handler
+        // is rejected with `AlreadyExists`. If this behaviour ever changes,
+        // the change is breaking and the test must be updated deliberately.
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+
+        let observed_kind = Rc::new(Cell::new(None::<io::ErrorKind>));
+        let ok = observed_kind.clone();
+        cb_sub(efd, move |_, mut ep| {
+            ep.delete(raw).unwrap();
+            let err = BorrowSub {
+                raw,
+                interest: Cell::new(crate::interest().read()),
+            }
+            .register_into(&mut ep)
+            .unwrap_err();
+            assert_eq!(
+                Error::from_io(&err),
+                Some(&Error::PendingRemoval { fd: raw })
+            );
+            ok.set(Some(err.kind()));
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        let raw_dup = unsafe { BorrowedFd::borrow_raw(raw) }
+            .try_clone_to_owned()
+            .unwrap();
+        fire(&unsafe { EventFd::from_owned_fd(raw_dup) });
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        assert_eq!(observed_kind.get(), Some(io::ErrorKind::AlreadyExists));
+    }
+
+    #[test]
+    fn modify_of_a_deleted_fd_fails_without_reaching_the_kernel() {
+        // In one batch, A deletes B, then C modifies B, deletes itself and
+        // modifies itself.
+        let mut ep = Eventp::default();
+        let (a, b, c) = (new_eventfd(), new_eventfd(), new_eventfd());
+        let (raw_b, raw_c) = (b.as_fd().as_raw_fd(), c.as_fd().as_raw_fd());
+        let writers = [writer_for(&a), writer_for(&b), writer_for(&c)];
+
+        cb_sub(a, move |_, mut ep| ep.delete(raw_b).unwrap())
+            .register_into(&mut ep)
+            .unwrap();
+        cb_sub(b, |_, _| panic!("B is deleted before its event"))
+            .register_into(&mut ep)
+            .unwrap();
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let e = errors.clone();
+        cb_sub(c, move |_, mut ep| {
+            let read_write = crate::interest().read_write();
+            e.borrow_mut()
+                .push(ep.modify(raw_b, read_write).unwrap_err());
+            ep.delete(raw_c).unwrap();
+            e.borrow_mut()
+                .push(ep.modify(raw_c, read_write).unwrap_err());
+            let read = crate::interest().read();
+            e.borrow_mut().push(ep.try_modify(raw_c, read).unwrap_err());
+            e.borrow_mut().push(ep.delete(raw_c).unwrap_err());
+            assert_eq!(ep.current_interest(), Some(read));
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        // Fired in order, and so dispatched in order.
+        writers.iter().for_each(fire);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        let errors = errors.take();
+        assert_eq!(errors.len(), 4);
+        assert!(errors.iter().all(|e| e.kind() == io::ErrorKind::NotFound));
+        // B is no longer registered at all.
+        assert_eq!(Error::from_io(&errors[0]), None);
+        for error in &errors[1..] {
+            let pending = Error::PendingRemoval { fd: raw_c };
+            assert_eq!(Error::from_io(error), Some(&pending));
+        }
+        assert_eq!(ep.len(), 1);
+    }
+
+    #[test]
+    fn handler_add_new_fd_fires_on_next_iteration() {
+        let mut ep = Eventp::default();
+
+        let trigger = new_eventfd();
+        let raw_trigger = trigger.as_fd().as_raw_fd();
+
+        // The subscriber added from inside the handler will fire on the next
+        // poll. We pre-create its eventfd so we can fire it from outside.
+        let added = new_eventfd();
+        let added_raw = added.as_fd().as_raw_fd();
+        let added_slot = Rc::new(RefCell::new(Some(added)));
+        let added_fired = Rc::new(Cell::new(false));
+        let af = added_fired.clone();
+        let slot = added_slot.clone();
+
+        cb_sub(trigger, move |_, mut ep| {
+            let efd = slot.borrow_mut().take().unwrap();
+            let af = af.clone();
+            cb_sub(efd, move |_, _| af.set(true))
+                .register_into(&mut ep)
+                .unwrap();
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        let dup = unsafe { BorrowedFd::borrow_raw(raw_trigger) }
+            .try_clone_to_owned()
+            .unwrap();
+        fire(&unsafe { EventFd::from_owned_fd(dup) });
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(
+            !added_fired.get(),
+            "newly-added fd must not fire in same batch"
+        );
+
+        // Now wake the newly-added fd and poll again.
+        let dup2 = unsafe { BorrowedFd::borrow_raw(added_raw) }
+            .try_clone_to_owned()
+            .unwrap();
+        fire(&unsafe { EventFd::from_owned_fd(dup2) });
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(added_fired.get());
+    }
+
+    #[test]
+    fn modify_updates_interest_kept_by_the_loop() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        assert_eq!(ep.interest(&raw), Some(crate::interest().read()));
+
+        let new_interest = crate::interest().read().write();
+        ep.modify(raw, new_interest).unwrap();
+        assert_eq!(ep.interest(&raw), Some(new_interest));
+    }
+
+    #[test]
+    fn try_modify_skips_an_unchanged_interest() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+
+        let read = crate::interest().read();
+        assert!(!ep.try_modify(raw, read).unwrap());
+        assert!(ep.try_modify(raw, read.write()).unwrap());
+        assert_eq!(ep.interest(&raw), Some(read.write()));
+        assert!(!ep.try_modify(raw, read.write()).unwrap());
+        // The extra settings count too, not only the epoll flags.
+        assert!(ep
+            .try_modify(raw, read.write().dispatch_priority(1))
+            .unwrap());
+
+        let err = ep.try_modify(424242, read).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn try_modify_always_rearms_a_oneshot_fd() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+        let oneshot = crate::interest().read().oneshot();
+        cb_sub(efd, move |_, _| c.set(c.get() + 1))
+            .register_with_interest(oneshot, &mut ep)
+            .unwrap();
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        fire(&writer);
+        ep.run_once_with_timeout(EpollTimeout::from(50u16)).unwrap();
+        assert_eq!(calls.get(), 1);
+
+        // Identical flags, but the fd is disarmed until modified.
+        assert!(ep.try_modify(raw, oneshot).unwrap());
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn oneshot_fd_is_disarmed_by_its_event_until_modified() {
+        use std::os::fd::OwnedFd;
+
+        let mut ep = Eventp::default();
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let raw = read.as_raw_fd();
+        let oneshot = crate::interest().read().oneshot();
+        let armed_in_handler = Rc::new(Cell::new(None));
+        let a = armed_in_handler.clone();
+        oneshot
+            .with_owned_fd(read)
+            .with_handler(move |pipe: &mut OwnedFd, eventp: Pinned<'_, Eventp>| {
+                nix::unistd::read(&*pipe, &mut [0; 1]).unwrap();
+                a.set(eventp.is_armed(&pipe.as_raw_fd()));
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        assert_eq!(ep.is_armed(&raw), Some(true));
+
+        nix::unistd::write(&write, b"x").unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(armed_in_handler.get(), Some(false));
+        // The interest is kept to rearm with.
+        assert_eq!(ep.interest(&raw), Some(oneshot));
+        assert_eq!(ep.is_armed(&raw), Some(false));
+
+        ep.modify(raw, oneshot).unwrap();
+        assert_eq!(ep.is_armed(&raw), Some(true));
+        ep.modify(raw, crate::interest().read()).unwrap();
+        nix::unistd::write(&write, b"x").unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(ep.is_armed(&raw), Some(true));
+        assert_eq!(ep.is_armed(&-1), None);
+    }
+
+    #[test]
+    fn oneshot_fd_left_disarmed_is_reported_once() {
+        use std::os::fd::OwnedFd;
+
+        let mut ep = Eventp::builder().warn_disarmed_after(2).build().unwrap();
+        let oneshot = crate::interest().read().oneshot();
+        // Forgets to rearm.
+        let (leaked, leaked_write) = nix::unistd::pipe().unwrap();
+        oneshot
+            .with_owned_fd(leaked)
+            .with_handler(|pipe: &mut OwnedFd| {
+                nix::unistd::read(&*pipe, &mut [0; 1]).unwrap();
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        let (rearmed, rearmed_write) = nix::unistd::pipe().unwrap();
+        oneshot
+            .with_owned_fd(rearmed)
+            .with_handler(
+                move |pipe: &mut OwnedFd, mut eventp: Pinned<'_, Eventp>| -> io::Result<()> {
+                    nix::unistd::read(&*pipe, &mut [0; 1])?;
+                    eventp.modify(pipe.as_raw_fd(), oneshot)
+                },
+            )
+            .register_into(&mut ep)
+            .unwrap();
+        // Keeps the batches coming.
+        let ticker = new_eventfd();
+        let ticks = writer_for(&ticker);
+        cb_sub(ticker, |_, _| {}).register_into(&mut ep).unwrap();
+
+        nix::unistd::write(&leaked_write, b"x").unwrap();
+        nix::unistd::write(&rearmed_write, b"x").unwrap();
+        for _ in 0..2 {
+            fire(&ticks);
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert_eq!(ep.stats().disarmed_leaks, 0);
+
+        for _ in 0..3 {
+            fire(&ticks);
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert_eq!(ep.stats().disarmed_leaks, 1);
+        assert!(ep.disarmed.is_empty());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn try_modify_is_mocked_and_passed_through_pinned() {
+        use mockall::predicate::eq;
+
+        let mut mock = crate::MockEventp::new();
+        let read = crate::interest().read();
+        mock.expect_try_modify()
+            .with(eq(5), eq(read))
+            .times(1)
+            .returning(|_, _| Ok(false));
+        let mut pinned = Pinned(Pin::new(&mut mock));
+        assert!(!pinned.try_modify(5, read).unwrap());
+    }
+
+    #[test]
+    fn handler_toggles_its_own_write_interest() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let s = seen.clone();
+
+        cb_sub(efd, move |efd, mut ep| {
+            let raw = efd.as_fd().as_raw_fd();
+            let interest = ep.current_interest().unwrap();
+            if interest.contains(crate::interest().write()) {
+                s.borrow_mut().push("writable");
+                ep.modify(raw, interest.remove_write()).unwrap();
+            } else {
+                s.borrow_mut().push("readable");
+                ep.modify(raw, interest.write()).unwrap();
+            }
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        fire(&writer);
+        for _ in 0..3 {
+            ep.run_once_with_timeout(EpollTimeout::from(50u16)).unwrap();
+        }
+        assert_eq!(*seen.borrow(), ["readable", "writable"]);
+    }
+
+    #[test]
+    fn oneshot_handler_rearms_itself() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+
+        cb_sub(efd, move |efd, mut ep| {
+            c.set(c.get() + 1);
+            if c.get() < 3 {
+                let interest = ep.current_interest().unwrap();
+                ep.modify(efd.as_fd().as_raw_fd(), interest).unwrap();
+            }
+        })
+        .register_with_interest(crate::interest().read().oneshot(), &mut ep)
+        .unwrap();
+
+        for _ in 0..3 {
+            fire(&writer);
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert_eq!(calls.get(), 3);
+        // Not rearmed by the last call.
+        fire(&writer);
+        ep.run_once_with_timeout(EpollTimeout::from(20u16)).unwrap();
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn failed_modify_keeps_the_interest() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let exclusive = crate::interest().read().exclusive();
+        cb_sub(efd, |_, _| {})
+            .register_with_interest(exclusive, &mut ep)
+            .unwrap();
+
+        // `EPOLLEXCLUSIVE` registrations cannot be modified, which is refused
+        // before the kernel is asked.
+        let err = ep
+            .modify(raw, crate::interest().read().write())
+            .unwrap_err();
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::ExclusiveModify { fd: raw })
+        );
+        assert_eq!(ep.interest(&raw), Some(exclusive));
+    }
+
+    #[test]
+    fn exclusive_interest_is_added_but_not_modified() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        ExclusiveInterest::new()
+            .read()
+            .with_fd(efd)
+            .with_handler(|_: &mut EventFd| {})
+            .register_into(&mut ep)
+            .unwrap();
+        let err = ep
+            .modify(raw, ExclusiveInterest::new().write().interest())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // Nor can a subscriber be made exclusive after it is added.
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        cb_sub(efd, |_, _| {})
+            .register_with_interest(crate::interest().read(), &mut ep)
+            .unwrap();
+        let err = ep
+            .modify(raw, crate::interest().read().exclusive())
+            .unwrap_err();
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::ExclusiveModify { fd: raw })
+        );
+        assert_eq!(ep.interest(&raw), Some(crate::interest().read()));
+    }
+
+    #[test]
+    fn add_and_modify_validate_the_interest() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let interest = Interest::new(EpollFlags::EPOLLIN | EpollFlags::EPOLLONESHOT).exclusive();
+        let err = cb_sub(efd, |_, _| {})
+            .register_with_interest(interest, &mut ep)
+            .unwrap_err();
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::ExclusiveIncompatible {
+                flags: EpollFlags::EPOLLONESHOT
+            })
+        );
+        assert!(ep.registered.is_empty());
+
+        #[cfg(not(target_arch = "mips"))]
+        {
+            let efd = new_eventfd();
+            let raw = efd.as_fd().as_raw_fd();
+            cb_sub(efd, |_, _| {})
+                .register_with_interest(crate::interest().read(), &mut ep)
+                .unwrap();
+            let err = ep
+                .modify(raw, crate::interest().read().edge_triggered().wakeup())
+                .unwrap_err();
+            assert_eq!(
+                Error::from_io(&err),
+                Some(&Error::WakeupIneffective {
+                    flags: EpollFlags::EPOLLET
+                })
+            );
+            assert_eq!(ep.interest(&raw), Some(crate::interest().read()));
+        }
+    }
+
+    #[test]
+    fn handlers_see_their_place_in_the_batch() {
+        let mut ep = Eventp::default();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        for _ in 0..3 {
+            let efd = new_eventfd();
+            fire(&efd);
+            let seen = seen.clone();
+            crate::interest()
+                .read()
+                .with_fd(efd)
+                .with_handler(move |batch: BatchInfo| seen.borrow_mut().push(batch))
+                .register_into(&mut ep)
+                .unwrap();
+        }
+        // Implementing `Handler` directly, through `Pinned`. Fired again, as it
+        // drains its eventfd.
+        let efd = new_eventfd();
+        fire(&efd);
+        let s = seen.clone();
+        cb_sub(efd, move |efd, ep| {
+            fire(efd);
+            s.borrow_mut().push(ep.batch_info().unwrap());
+        })
+        .register_into(&mut ep)
+        .unwrap();
+        assert_eq!(ep.batch_info(), None);
+
+        for sequence in 0..2 {
+            ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+            let mut batches = mem::take(&mut *seen.borrow_mut());
+            batches.sort_by_key(BatchInfo::index);
+            let expected: Vec<_> = (0..4).map(|i| BatchInfo::new(i, 4, sequence)).collect();
+            assert_eq!(batches, expected);
+            assert_eq!(batches[0].remaining(), 3);
+        }
+
+        // A budget splits the batch.
+        ep.run_once_budgeted(EpollTimeout::ZERO, 3).unwrap();
+        ep.run_once_budgeted(EpollTimeout::ZERO, 3).unwrap();
+        let batches = mem::take(&mut *seen.borrow_mut());
+        let totals: Vec<_> = batches.iter().map(|b| (b.total(), b.sequence())).collect();
+        assert_eq!(totals, [(3, 2), (3, 2), (3, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn current_interest_follows_modify_inside_handler() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let s = seen.clone();
+
+        cb_sub(efd, move |efd, mut ep| {
+            let raw = efd.as_fd().as_raw_fd();
+            s.borrow_mut().push(ep.current_interest());
+            ep.modify(raw, crate::interest().read().edge_triggered())
+                .unwrap();
+            s.borrow_mut().push(ep.current_interest());
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        assert_eq!(ep.current_interest(), None);
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(
+            *seen.borrow(),
+            [
+                Some(crate::interest().read()),
+                Some(crate::interest().read().edge_triggered()),
+            ]
+        );
+    }
+
+    #[test]
+    fn user_data_is_seen_by_the_handler_and_kept_by_modify() {
+        use crate::tri_subscriber::UserData;
+
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let s = seen.clone();
+        crate::interest()
+            .read()
+            .user_data(0xBEEF)
+            .with_fd(efd)
+            .with_handler(
+                move |efd: &mut EventFd, UserData(data), mut ep: Pinned<'_, Eventp>| {
+                    drain(efd);
+                    s.borrow_mut().push(data);
+                    let raw = efd.as_fd().as_raw_fd();
+                    ep.set_user_data(raw, data + 1).unwrap();
+                    s.borrow_mut()
+                        .push(ep.current_interest().unwrap().user_data_value());
+                },
+            )
+            .register_into(&mut ep)
+            .unwrap();
+        assert_eq!(ep.user_data_of(raw), Some(0xBEEF));
+        assert_eq!(ep.user_data_of(-1), None);
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(*seen.borrow(), [0xBEEF, 0xBEF0]);
+
+        // Kept by `modify`, unless replaced.
+        ep.modify(raw, crate::interest().read().edge_triggered())
+            .unwrap();
+        assert_eq!(ep.user_data_of(raw), Some(0xBEF0));
+        assert!(!ep
+            .try_modify(raw, crate::interest().read().edge_triggered())
+            .unwrap());
+        ep.modify(raw, crate::interest().read().user_data(7))
+            .unwrap();
+        assert_eq!(
+            ep.interest(&raw),
+            Some(crate::interest().read().user_data(7))
+        );
+
+        ep.set_user_data(raw, 0).unwrap();
+        assert_eq!(ep.user_data_of(raw), Some(0));
+        let err = ep.set_user_data(-1, 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn tri_subscriber_interest_param_reflects_modify() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        let seen = Rc::new(Cell::new(Interest::default()));
+        let s = seen.clone();
+
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move |efd: &mut EventFd, interest: Interest| {
+                drain(efd);
+                s.set(interest);
+            })
+            .register_into(&mut ep)
+            .unwrap();
+
+        let modified = crate::interest().read().read_hangup();
+        ep.modify(raw, modified).unwrap();
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(seen.get(), modified);
+    }
+
+    #[test]
+    fn register_with_interest_needs_no_has_interest() {
+        struct Bare(EventFd, Rc<Cell<bool>>);
+
+        impl AsFd for Bare {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.0.as_fd()
+            }
+        }
+
+        impl Handler<Eventp> for Bare {
+            fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {
+                drain(&self.0);
+                self.1.set(true);
+            }
+        }
+
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        let fired = Rc::new(Cell::new(false));
+
+        Bare(efd, fired.clone())
+            .register_with_interest(crate::interest().read(), &mut ep)
+            .unwrap();
+        assert_eq!(ep.interest(&raw), Some(crate::interest().read()));
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(fired.get());
+    }
+
+    /// Re-entrancy guard test.
+    ///
+    /// `Eventp::run_once_with_timeout` documents a panic when called
+    /// recursively from a handler. The dispatch loop wraps the
+    /// reconstructed `ThinBoxSubscriber` in `ManuallyDrop`, so the panic
+    /// unwinding out of `handle()` cannot double-free the heap slot still
+    /// owned by the registry. This test exercises that path end-to-end.
+    #[test]
+    fn recursive_run_inside_handler_panics() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+
+        cb_sub(efd, |_, mut ep| {
+            // SAFETY: deliberately bypassing `Pinned`'s narrow API to invoke
+            // the recursion guard, which is the property under test.
+            let inner: &mut Eventp = unsafe { ep.0.as_mut().get_unchecked_mut() };
+            let _ = inner.run_once_with_timeout(EpollTimeout::from(1u16));
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        let raw = *ep.registered.keys().next().unwrap();
+        let dup = unsafe { BorrowedFd::borrow_raw(raw) }
+            .try_clone_to_owned()
+            .unwrap();
+        fire(&unsafe { EventFd::from_owned_fd(dup) });
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }));
+        assert!(result.is_err(), "recursive run_once must panic");
+    }
+
+    #[test]
+    fn try_run_once_backs_off_where_run_once_panics() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let tried = Rc::new(Cell::new(None));
+        let tried_in_handler = tried.clone();
+
+        cb_sub(efd, move |efd, mut ep| {
+            drain(efd);
+            // SAFETY: As in `recursive_run_inside_handler_panics`.
+            let inner: &mut Eventp = unsafe { ep.0.as_mut().get_unchecked_mut() };
+            let tried = inner.try_run_once_with_timeout(EpollTimeout::ZERO);
+            tried_in_handler.set(Some(tried.map(Result::unwrap).unwrap_err()));
+            let _ = inner.run_once_with_timeout(EpollTimeout::ZERO);
+        })
+        .register_into(&mut ep)
+        .unwrap();
+        let raw = *ep.registered.keys().next().unwrap();
+
+        assert_eq!(
+            ep.try_run_once_with_timeout(EpollTimeout::ZERO)
+                .unwrap()
+                .unwrap(),
+            0
+        );
+        fire(&writer);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ep.try_run_once_with_timeout(poll_timeout())
+        }));
+        assert!(result.is_err(), "run_once must still panic");
+        assert_eq!(tried.get(), Some(AlreadyRunning { fd: raw }));
+    }
+
+    #[test]
+    fn into_parts_returns_registered_subscribers() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+
+        let (_epoll, mut registered) = ep.into_parts();
+        assert!(registered.any(|s| {
+            let boxed: Box<dyn Subscriber<Eventp>> = s.try_into().ok().unwrap();
+            boxed.as_fd().as_raw_fd() == raw
+        }));
+    }
+
+    #[test]
+    fn into_parts_deregisters_every_fd() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+
+        let (epoll, registered) = ep.into_parts();
+        // Still alive and readable, but no longer known to the epoll.
+        let registered: Vec<_> = registered.collect();
+        fire(&writer);
+        let mut events = [EpollEvent::empty(); 1];
+        assert_eq!(epoll.wait(&mut events, EpollTimeout::ZERO).unwrap(), 0);
+
+        // Either drop order is fine.
+        drop(epoll);
+        drop(registered);
+    }
+
+    /// Counts how many times subscribers of this type were dropped.
+    struct DropCounting {
+        eventfd: EventFd,
+        drops: Rc<Cell<u32>>,
+    }
+
+    impl AsFd for DropCounting {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.eventfd.as_fd()
+        }
+    }
+
+    impl Handler<Eventp> for DropCounting {
+        fn handle(&mut self, _event: Event, _eventp: Pinned<'_, Eventp>) {}
+    }
+
+    impl Drop for DropCounting {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    #[test]
+    fn lifecycle_construct_register_drop() {
+        let drops = Rc::new(Cell::new(0));
+        let mut ep = Eventp::default();
+        for _ in 0..3 {
+            DropCounting {
+                eventfd: new_eventfd(),
+                drops: drops.clone(),
+            }
+            .register_with_interest(crate::interest().read(), &mut ep)
+            .unwrap();
+        }
+        assert_eq!(drops.get(), 0);
+
+        drop(ep);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn lifecycle_drop_after_delete_in_handler() {
+        let drops = Rc::new(Cell::new(0));
+        let mut ep = Eventp::default();
+
+        let victim = new_eventfd();
+        let victim_raw = victim.as_fd().as_raw_fd();
+        DropCounting {
+            eventfd: victim,
+            drops: drops.clone(),
+        }
+        .register_with_interest(crate::interest().read(), &mut ep)
+        .unwrap();
+        DropCounting {
+            eventfd: new_eventfd(),
+            drops: drops.clone(),
+        }
+        .register_with_interest(crate::interest().read(), &mut ep)
+        .unwrap();
+
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        cb_sub(efd, move |_, mut eventp| eventp.delete(victim_raw).unwrap())
+            .register_into(&mut ep)
+            .unwrap();
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(drops.get(), 1);
+
+        drop(ep);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn lifecycle_add_dispatch_delete_over_a_pipe() {
+        use std::fs::File;
+        use std::io::{Read, Write};
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        let mut fds = [-1; 2];
+        // SAFETY: `pipe2` writes two fresh fds into `fds` on success, owned here.
+        let (reader, mut writer) = unsafe {
+            assert_eq!(
+                libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK),
+                0
+            );
+            (
+                File::from(OwnedFd::from_raw_fd(fds[0])),
+                File::from(OwnedFd::from_raw_fd(fds[1])),
+            )
+        };
+        let reader_raw = reader.as_raw_fd();
+
+        let mut ep = Eventp::default();
+        let reads = Rc::new(Cell::new(0));
+        let r = reads.clone();
+        crate::interest()
+            .read()
+            .with_fd(reader)
+            .with_handler(
+                move |reader: &mut File,
+                      handle: SubscriberHandle,
+                      mut eventp: Pinned<'_, Eventp>| {
+                    let mut byte = [0];
+                    reader.read_exact(&mut byte)?;
+                    r.set(r.get() + 1);
+                    handle.delete(&mut eventp)
+                },
+            )
+            .register_into(&mut ep)
+            .unwrap();
+        assert_eq!(ep.interest(&reader_raw), Some(crate::interest().read()));
+
+        writer.write_all(b"x").unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(reads.get(), 1);
+        // Deleted by its own handler, and freed by the end of the batch.
+        assert_eq!(ep.interest(&reader_raw), None);
+        assert_eq!(Rc::strong_count(&reads), 1);
+    }
+
+    #[test]
+    fn handler_error_propagates_after_whole_batch() {
+        let mut ep = Eventp::default();
+        let (_, failing) = register_failing(&mut ep);
+
+        let efd = new_eventfd();
+        let ok = writer_for(&efd);
+        let counter = Rc::new(Cell::new(0u32));
+        let c = counter.clone();
+        cb_sub(efd, move |_, _| c.set(c.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&failing);
+        fire(&ok);
+        let err = ep.run_once_with_timeout(poll_timeout()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        // The healthy subscriber in the same batch must still have been served,
+        // regardless of whether it came before or after the failing one.
+        assert_eq!(counter.get(), 1);
+
+        // The error is reported once; the next batch starts clean.
+        ep.run_once_with_timeout(EpollTimeout::from(10u16)).unwrap();
+    }
+
+    #[test]
+    fn handler_error_with_remove_policy_deletes_subscriber() {
+        let mut ep = Eventp::builder()
+            .error_policy(ErrorPolicy::Remove)
+            .build()
+            .unwrap();
+        let (raw, failing) = register_failing(&mut ep);
+
+        fire(&failing);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(!ep.registered.contains_key(&raw));
+    }
+
+    #[test]
+    fn handler_error_with_remove_policy_tolerates_self_delete() {
+        let mut ep = Eventp::builder()
+            .error_policy(ErrorPolicy::Remove)
+            .build()
+            .unwrap();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|efd: &mut EventFd, mut ep: Pinned<'_, Eventp>| {
+                ep.delete(efd.as_fd().as_raw_fd())?;
+                Err(io::Error::new(io::ErrorKind::Other, "after self-delete"))
+            })
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(!ep.registered.contains_key(&raw));
+    }
+
+    #[test]
+    fn handler_error_with_log_policy_keeps_subscriber() {
+        let mut ep = Eventp::builder()
+            .error_policy(ErrorPolicy::Log)
+            .build()
+            .unwrap();
+        let (raw, failing) = register_failing(&mut ep);
+
+        fire(&failing);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(ep.registered.contains_key(&raw));
+    }
+
+    #[test]
+    fn add_boxed_registers_heterogeneous_subscribers() {
+        /// What plugin-style code produces: subscribers of unrelated types behind
+        /// one trait object, and the writers to fire them.
+        fn plugins(
+            log: &Rc<RefCell<Vec<&'static str>>>,
+        ) -> Vec<(Box<dyn Subscriber<Eventp>>, EventFd)> {
+            let efd = new_eventfd();
+            let writer = writer_for(&efd);
+            let l = log.clone();
+            let first: Box<dyn Subscriber<Eventp>> =
+                Box::new(cb_sub(efd, move |_, _| l.borrow_mut().push("callback")));
+
+            let efd = new_eventfd();
+            let writer2 = writer_for(&efd);
+            let l = log.clone();
+            let second: Box<dyn Subscriber<Eventp>> = Box::new(
+                crate::interest()
+                    .with_fd(efd)
+                    .with_handler(move |efd: &mut EventFd| {
+                        drain(efd);
+                        l.borrow_mut().push("tri");
+                    }),
+            );
+
+            vec![(first, writer), (second, writer2)]
+        }
+
+        let mut ep = Eventp::default();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut writers = Vec::new();
+        for (plugin, writer) in plugins(&log) {
+            ep.add_boxed(crate::interest().read(), plugin).unwrap();
+            writers.push(writer);
+        }
+        assert_eq!(ep.registered.len(), 2);
+
+        for writer in &writers {
+            fire(writer);
+        }
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        let mut log = log.borrow().clone();
+        log.sort_unstable();
+        assert_eq!(log, ["callback", "tri"]);
+    }
+
+    #[test]
+    fn zst_subscriber_over_a_fixed_fd_is_dispatched() {
+        thread_local! {
+            static FD: Cell<RawFd> = const { Cell::new(-1) };
+            static HITS: Cell<u32> = const { Cell::new(0) };
+        }
+
+        /// Stateless: the fd lives outside, so the subscriber is zero-sized.
+        struct Stateless;
+
+        impl AsFd for Stateless {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                // SAFETY: The test keeps the eventfd open while it is registered.
+                unsafe { BorrowedFd::borrow_raw(FD.with(Cell::get)) }
+            }
+        }
+
+        impl Handler<Eventp> for Stateless {
+            fn handle(&mut self, _event: Event, _eventp: Pinned<'_, Eventp>) {
+                HITS.with(|h| h.set(h.get() + 1));
+            }
+        }
+
+        assert_eq!(std::mem::size_of::<Stateless>(), 0);
+
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        FD.with(|fd| fd.set(raw));
+
+        let mut ep = Eventp::default();
+        Stateless
+            .register_with_interest(crate::interest().read(), &mut ep)
+            .unwrap();
+        fire(&efd);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(HITS.with(Cell::get), 1);
+
+        ep.delete(raw).unwrap();
+        assert!(ep.registered.is_empty());
+    }
+
+    fn is_readable(fd: BorrowedFd<'_>) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut pollfd, 1, 0) > 0 }
+    }
+
+    #[test]
+    fn poll_fd_is_readable_while_events_are_ready() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let handled = Rc::new(Cell::new(0));
+        let h = handled.clone();
+        cb_sub(efd, move |_, _| h.set(h.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+
+        assert!(!is_readable(ep.poll_fd()));
+        ep.try_run_once().unwrap();
+        assert_eq!(handled.get(), 0);
+
+        fire(&writer);
+        assert!(is_readable(ep.poll_fd()));
+        ep.try_run_once().unwrap();
+        assert_eq!(handled.get(), 1);
+        // The handler drained the eventfd.
+        assert!(!is_readable(ep.poll_fd()));
+    }
+
+    fn is_cloexec(fd: BorrowedFd<'_>) -> bool {
+        let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFD) };
+        assert_ne!(flags, -1);
+        flags & libc::FD_CLOEXEC != 0
+    }
+
+    #[test]
+    fn cloned_poll_fd_shares_the_interest_list() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let handled = Rc::new(Cell::new(0));
+        let h = handled.clone();
+        cb_sub(efd, move |_, _| h.set(h.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+
+        let dup = ep.try_clone_poll_fd().unwrap();
+        assert!(is_cloexec(dup.as_fd()));
+        ep.set_poll_fd_cloexec(false).unwrap();
+        assert!(!is_cloexec(ep.poll_fd()));
+        assert!(is_cloexec(dup.as_fd()));
+        ep.set_poll_fd_cloexec(true).unwrap();
+        assert!(is_cloexec(ep.poll_fd()));
+
+        fire(&writer);
+        assert!(is_readable(dup.as_fd()));
+        drop(dup);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(handled.get(), 1);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn closed_poll_fd_is_caught_in_debug_builds() {
+        let mut ep = Eventp::default();
+        // Reuse the number of the epoll fd for another file, as if it had been
+        // closed and reopened behind the loop's back.
+        let efd = new_eventfd();
+        let raw = ep.poll_fd().as_raw_fd();
+        assert_ne!(unsafe { libc::dup2(efd.as_fd().as_raw_fd(), raw) }, -1);
+
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| ep.try_run_once())).unwrap_err();
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert!(
+            message.contains("closed behind the loop's back"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn run_returns_once_stopped_and_runs_again() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let handled = Rc::new(Cell::new(0));
+        let h = handled.clone();
+        cb_sub(efd, move |_, mut ep| {
+            h.set(h.get() + 1);
+            ep.stop();
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        fire(&writer);
+        assert!(matches!(ep.run(), RunOutcome::Stopped));
+        fire(&writer);
+        ep.run_forever().unwrap();
+        assert_eq!(handled.get(), 2);
+
+        // Requested outside of a run, the stop ends the next one.
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        fire(&efd);
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        ep.stop();
+        assert!(matches!(ep.run(), RunOutcome::Stopped));
+    }
+
+    #[test]
+    fn run_tells_the_failed_handler_apart() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|efd: &mut EventFd| -> io::Result<()> {
+                drain(efd);
+                Err(io::Error::new(io::ErrorKind::InvalidData, "bad frame"))
+            })
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&writer);
+        match ep.run() {
+            RunOutcome::HandlerError { fd, error } => {
+                assert_eq!(fd, raw);
+                assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            }
+            outcome => panic!("unexpected {outcome:?}"),
+        }
+        fire(&writer);
+        assert_eq!(
+            ep.run_forever().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn run_catches_the_panic_of_a_handler() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        cb_sub(efd, |_, _| panic!("handler panic"))
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&writer);
+        match ep.run() {
+            RunOutcome::Panicked { fd, payload } => {
+                assert_eq!(fd, Some(raw));
+                assert_eq!(payload.downcast_ref::<&str>(), Some(&"handler panic"));
+            }
+            outcome => panic!("unexpected {outcome:?}"),
+        }
+        // The batch is over, so the loop runs again.
+        ep.delete(raw).unwrap();
+        ep.try_run_once().unwrap();
+    }
+
+    #[test]
+    fn run_reports_the_failure_of_epoll_wait() {
+        let mut ep = Eventp::default();
+        // As in `closed_poll_fd_is_caught_in_debug_builds`.
+        let efd = new_eventfd();
+        let raw = ep.poll_fd().as_raw_fd();
+        assert_ne!(unsafe { libc::dup2(efd.as_fd().as_raw_fd(), raw) }, -1);
+
+        match ep.run() {
+            #[cfg(debug_assertions)]
+            RunOutcome::Panicked { fd: None, payload } => {
+                let message = payload.downcast_ref::<String>().unwrap();
+                assert!(
+                    message.contains("closed behind the loop's back"),
+                    "{message}"
+                );
+            }
+            #[cfg(not(debug_assertions))]
+            RunOutcome::EpollError(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+            outcome => panic!("unexpected {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn nested_eventp_propagates_events_through_both_layers() {
+        let mut inner = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let handled = Rc::new(Cell::new(0));
+        let h = handled.clone();
+        cb_sub(efd, move |_, _| h.set(h.get() + 1))
+            .register_into(&mut inner)
+            .unwrap();
+
+        let mut outer = Eventp::default();
+        let woken = Rc::new(Cell::new(0));
+        let w = woken.clone();
+        crate::interest()
+            .read()
+            .with_fd(inner)
+            .with_handler(move |inner: &mut Eventp| {
+                w.set(w.get() + 1);
+                inner.try_run_once()
+            })
+            .register_into(&mut outer)
+            .unwrap();
+
+        outer.try_run_once().unwrap();
+        assert_eq!((woken.get(), handled.get()), (0, 0));
+
+        fire(&writer);
+        outer.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!((woken.get(), handled.get()), (1, 1));
+
+        // Level-triggered: once the inner loop is drained, the outer one is quiet.
+        outer.try_run_once().unwrap();
+        assert_eq!((woken.get(), handled.get()), (1, 1));
+    }
+
+    #[test]
+    fn timeout_for_rounds_up_and_clamps() {
+        let ms = |t: EpollTimeout| i32::from(t);
+        assert_eq!(ms(timeout_for(Duration::from_nanos(1))), 1);
+        assert_eq!(ms(timeout_for(Duration::from_micros(1_500))), 2);
+        assert_eq!(ms(timeout_for(Duration::from_millis(3))), 3);
+        assert_eq!(ms(timeout_for(Duration::from_secs(u64::MAX))), i32::MAX);
+    }
+
+    #[test]
+    fn run_forever_with_waits_the_timeout_of_each_call() {
+        let mut ep = Eventp::builder()
+            .error_policy(ErrorPolicy::Propagate)
+            .build()
+            .unwrap();
+        let efd = new_eventfd();
+        let stop = writer_for(&efd);
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        // Fails the loop once fired, to end `run_forever_with`.
+        crate::interest()
+            .read()
+            .with_fd(writer_for(&stop))
+            .with_handler(|| -> io::Result<()> {
+                Err(io::Error::new(io::ErrorKind::Other, "stop"))
+            })
+            .register_into(&mut ep)
+            .unwrap();
+
+        let timeouts = [40u16, 20, 10, 5];
+        let mut calls = Vec::new();
+        let err = ep
+            .run_forever_with(|eventp| {
+                assert_eq!(eventp.len(), 2);
+                calls.push(Instant::now());
+                match timeouts.get(calls.len() - 1) {
+                    Some(&ms) => EpollTimeout::from(ms),
+                    None => {
+                        fire(&stop);
+                        EpollTimeout::NONE
+                    }
+                }
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "stop");
+
+        assert_eq!(calls.len(), timeouts.len() + 1);
+        for (gap, &ms) in calls.windows(2).zip(&timeouts) {
+            let waited = gap[1] - gap[0];
+            assert!(
+                waited >= Duration::from_millis(ms.into()),
+                "{waited:?} < {ms}ms"
+            );
+        }
+    }
+
+    #[test]
+    fn run_once_with_deadline_reports_expiry() {
+        let mut ep = Eventp::default();
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(30);
+        assert!(ep.run_once_with_deadline(deadline).unwrap());
+        assert!(Instant::now() >= deadline);
+
+        // A deadline in the past still polls once, without waiting.
+        assert!(ep.run_once_with_deadline(start).unwrap());
+    }
+
+    #[test]
+    fn run_once_with_deadline_returns_after_dispatch() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let handled = Rc::new(Cell::new(0));
+        let h = handled.clone();
+        cb_sub(efd, move |_, _| h.set(h.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&writer);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        assert!(!ep.run_once_with_deadline(deadline).unwrap());
+        assert_eq!(handled.get(), 1);
+
+        // Ready events are dispatched even when the deadline has passed.
+        fire(&writer);
+        assert!(!ep.run_once_with_deadline(Instant::now()).unwrap());
+        assert_eq!(handled.get(), 2);
+    }
+
+    #[test]
+    fn run_once_with_deadline_retries_eintr_with_the_remaining_time() {
+        extern "C" fn on_signal(_: libc::c_int) {}
+
+        // Without a handler, SIGUSR1 would terminate the process. No SA_RESTART,
+        // though epoll_wait is never restarted anyway.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as usize;
+            assert_eq!(libc::sigaction(libc::SIGUSR1, &action, ptr::null_mut()), 0);
+        }
+
+        let mut ep = Eventp::default();
+        let this = unsafe { libc::pthread_self() } as usize;
+        let signaller = std::thread::spawn(move || {
+            for _ in 0..3 {
+                std::thread::sleep(Duration::from_millis(25));
+                unsafe { libc::pthread_kill(this as libc::pthread_t, libc::SIGUSR1) };
+            }
+        });
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(100);
+        assert!(ep.run_once_with_deadline(deadline).unwrap());
+        let elapsed = start.elapsed();
+        signaller.join().unwrap();
+
+        assert!(elapsed >= Duration::from_millis(100));
+        // Restarting the full timeout after the last signal would take 175ms+.
+        assert!(elapsed < Duration::from_millis(160), "{elapsed:?}");
+    }
+
+    #[test]
+    fn run_until_dispatches_until_the_deadline() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let handled = Rc::new(Cell::new(0));
+        let h = handled.clone();
+        cb_sub(efd, move |_, _| h.set(h.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&writer);
+        let deadline = Instant::now() + Duration::from_millis(30);
+        ep.run_until(deadline).unwrap();
+        assert!(Instant::now() >= deadline);
+        assert_eq!(handled.get(), 1);
+    }
+
+    #[test]
+    fn stats_follow_a_scripted_sequence() {
+        let mut ep = Eventp::default();
+        let mut writers = Vec::new();
+        for _ in 0..3 {
+            let efd = new_eventfd();
+            writers.push(writer_for(&efd));
+            cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        }
+        assert_eq!(ep.stats(), Stats::default());
+
+        // One wakeup with all three ready.
+        writers.iter().for_each(fire);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        // Then one with a single fd, written twice.
+        fire(&writers[0]);
+        fire(&writers[0]);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        // A timeout is not a wakeup.
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+
+        let stats = ep.stats();
+        assert_eq!(stats.events_dispatched, 4);
+        assert_eq!(stats.wakeups, 2);
+        assert_eq!(stats.max_batch, 3);
+        assert_eq!(stats.handler_panics, 0);
+
+        ep.reset_stats();
+        assert_eq!(ep.stats(), Stats::default());
+        fire(&writers[1]);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!((ep.stats().wakeups, ep.stats().max_batch), (1, 1));
+    }
+
+    #[test]
+    fn stats_count_handler_panics() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        cb_sub(efd, |_, _| panic!("handler panic"))
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&writer);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ep.run_once_with_timeout(poll_timeout())
+        }));
+        assert!(result.is_err());
+        assert_eq!(ep.stats().handler_panics, 1);
+        assert_eq!(ep.stats().events_dispatched, 1);
+    }
+
+    #[test]
+    fn a_caught_panic_keeps_the_rest_of_the_batch() {
+        let mut ep = Eventp::default();
+        let calls = Rc::new(Cell::new(0));
+        let mut writers = Vec::new();
+        for _ in 0..2 {
+            let efd = new_eventfd();
+            writers.push(writer_for(&efd));
+            let (c, panicked) = (calls.clone(), Cell::new(false));
+            cb_sub(efd, move |_, _| {
+                c.set(c.get() + 1);
+                if !panicked.replace(true) {
+                    panic!("handler panic");
+                }
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        }
+
+        writers.iter().for_each(fire);
+        let run = |ep: &mut Eventp| catch_unwind(AssertUnwindSafe(|| ep.try_run_once()));
+        assert!(run(&mut ep).is_err());
+        assert_eq!((calls.get(), ep.pending_events()), (1, 1));
+        // The other event of the batch comes first, without waiting.
+        assert!(run(&mut ep).is_err());
+        assert_eq!((calls.get(), ep.pending_events()), (2, 0));
+
+        writers.iter().for_each(fire);
+        run(&mut ep).unwrap().unwrap();
+        assert_eq!(calls.get(), 4);
+    }
+
+    /// Registers three always-ready eventfds and returns the order their handlers
+    /// run in over `batches` batches.
+    fn dispatch_orders(ep: &mut Eventp, batches: usize) -> Vec<Vec<RawFd>> {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        for _ in 0..3 {
+            let efd = new_eventfd();
+            // Never drained, so always ready.
+            fire(&efd);
+            let o = order.clone();
+            crate::interest()
+                .read()
+                .with_fd(efd)
+                .with_handler(move |efd: &mut EventFd| o.borrow_mut().push(efd.as_fd().as_raw_fd()))
+                .register_into(ep)
+                .unwrap();
+        }
+
+        (0..batches)
+            .map(|_| {
+                ep.run_once_with_timeout(poll_timeout()).unwrap();
+                order.take()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fair_dispatch_rotates_the_first_dispatched_fd() {
+        let mut ep = Eventp::builder().fair_dispatch(true).build().unwrap();
+        let orders = dispatch_orders(&mut ep, 4);
+
+        let firsts: Vec<_> = orders.iter().map(|o| o[0]).collect();
+        assert_ne!(firsts[0], firsts[1]);
+        assert_ne!(firsts[1], firsts[2]);
+        assert_ne!(firsts[0], firsts[2]);
+        assert_eq!(firsts[0], firsts[3]);
+
+        // Still rotations of one batch: every fd once per batch, in the same cycle.
+        for order in &orders {
+            let mut sorted = order.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            assert_eq!(sorted.len(), 3);
+        }
+        let at = orders[0].iter().position(|&fd| fd == firsts[1]).unwrap();
+        assert_eq!(orders[1], [&orders[0][at..], &orders[0][..at]].concat());
+    }
+
+    #[test]
+    fn dispatch_keeps_kernel_order_by_default() {
+        let mut ep = Eventp::default();
+        let orders = dispatch_orders(&mut ep, 3);
+        assert_eq!(orders[0], orders[1]);
+        assert_eq!(orders[1], orders[2]);
+    }
+
+    #[test]
+    fn priority_dispatch_deletes_before_lower_priorities_run() {
+        let mut ep = Eventp::builder().priority_dispatch(true).build().unwrap();
+        ep.enable_event_log(8);
+        let (data, control) = (new_eventfd(), new_eventfd());
+        let (data_fd, control_fd) = (data.as_fd().as_raw_fd(), control.as_fd().as_raw_fd());
+        let (data_writer, control_writer) = (writer_for(&data), writer_for(&control));
+
+        cb_sub(data, |efd, _| drain(efd))
+            .register_into(&mut ep)
+            .unwrap();
+        cb_sub(control, move |efd, mut ep| {
+            drain(efd);
+            ep.delete(data_fd).unwrap();
+        })
+        .register_with_interest(crate::interest().read().dispatch_priority(0), &mut ep)
+        .unwrap();
+
+        // The kernel reports fds in the order they became ready.
+        fire(&data_writer);
+        fire(&control_writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(logged_fds(&mut ep), [control_fd]);
+        assert!(!ep.contains(data_fd));
+    }
+
+    fn logged_fds(ep: &mut Eventp) -> Vec<RawFd> {
+        ep.event_log().iter().map(|logged| logged.fd).collect()
+    }
+
+    #[test]
+    fn event_log_follows_dispatch_priorities() {
+        let mut ep = Eventp::builder().priority_dispatch(true).build().unwrap();
+        ep.enable_event_log(8);
+        let mut fds = Vec::new();
+        for priority in [255, 0, 7] {
+            let efd = new_eventfd();
+            fds.push(efd.as_fd().as_raw_fd());
+            fire(&efd);
+            cb_sub(efd, |efd, _| drain(efd))
+                .register_with_interest(
+                    crate::interest().read().dispatch_priority(priority),
+                    &mut ep,
+                )
+                .unwrap();
+        }
+
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(logged_fds(&mut ep), [fds[1], fds[2], fds[0]]);
+    }
+
+    #[test]
+    fn event_log_skips_subscribers_deleted_earlier_in_the_batch() {
+        let mut ep = Eventp::default();
+        ep.enable_event_log(8);
+        let (a, b) = (new_eventfd(), new_eventfd());
+        let (raw_a, raw_b) = (a.as_fd().as_raw_fd(), b.as_fd().as_raw_fd());
+        let (writer_a, writer_b) = (writer_for(&a), writer_for(&b));
+
+        cb_sub(a, move |efd, mut ep| {
+            drain(efd);
+            ep.delete(raw_b).unwrap();
+        })
+        .register_into(&mut ep)
+        .unwrap();
+        cb_sub(b, |efd, _| drain(efd))
+            .register_into(&mut ep)
+            .unwrap();
+
+        // Both in the same batch, `a` first: the event of `b`, whose subscriber is
+        // dropped by then, must not reach it.
+        fire(&writer_a);
+        fire(&writer_b);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(logged_fds(&mut ep), [raw_a]);
+        assert_eq!(ep.stats().events_dispatched, 1);
+    }
+
+    /// Registers a level-triggered eventfd counting the calls of its handler, which
+    /// does not drain it. Returns its raw fd and a writer to fire it.
+    fn register_counting(ep: &mut Eventp, calls: &Rc<Cell<u32>>) -> (RawFd, EventFd) {
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        let calls = calls.clone();
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move || calls.set(calls.get() + 1))
+            .register_into(ep)
+            .unwrap();
+        (raw, writer)
+    }
+
+    #[test]
+    fn wait_events_forwards_the_batch_without_calling_handlers() {
+        let mut ep = Eventp::default();
+        let calls = Rc::new(Cell::new(0));
+        let (raw_a, a) = register_counting(&mut ep, &calls);
+        let (raw_b, b) = register_counting(&mut ep, &calls);
+        fire(&a);
+        fire(&b);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let consumer = std::thread::spawn(move || rx.iter().collect::<Vec<(RawFd, Event)>>());
+        for ready in ep.wait_events(poll_timeout()).unwrap() {
+            tx.send(ready).unwrap();
+        }
+        drop(tx);
+
+        let mut forwarded = consumer.join().unwrap();
+        forwarded.sort_by_key(|&(fd, _)| fd);
+        let mut expected = [
+            (raw_a, Event::new(EpollFlags::EPOLLIN)),
+            (raw_b, Event::new(EpollFlags::EPOLLIN)),
+        ];
+        expected.sort_by_key(|&(fd, _)| fd);
+        assert_eq!(forwarded, expected);
+        assert_eq!(calls.get(), 0);
+
+        // Still ready, and now dispatched to the handlers.
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(ep.stats().wakeups, 2);
+    }
+
+    #[test]
+    fn wait_events_skips_deleted_fds_and_keeps_what_was_not_taken() {
+        let mut ep = Eventp::default();
+        let calls = Rc::new(Cell::new(0));
+        let fds: Vec<RawFd> = (0..3)
+            .map(|_| {
+                let (raw, writer) = register_counting(&mut ep, &calls);
+                fire(&writer);
+                raw
+            })
+            .collect();
+
+        let mut events = ep.wait_events(poll_timeout()).unwrap();
+        let (first, _) = events.next().unwrap();
+        let others: Vec<RawFd> = fds.iter().copied().filter(|&fd| fd != first).collect();
+        // Deleting the fd just handed out, and one still to come.
+        events.eventp().delete(first).unwrap();
+        events.eventp().delete(others[0]).unwrap();
+        drop(events);
+
+        assert_eq!(ep.pending_events(), 1);
+        assert!(!ep.contains(first) && !ep.contains(others[0]));
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(calls.get(), 1);
+        assert_eq!(ep.pending_events(), 0);
+
+        // Kept events are handed out again by `wait_events` too.
+        drop(ep.wait_events(poll_timeout()).unwrap());
+        assert_eq!(ep.pending_events(), 1);
+        let events: Vec<_> = ep.wait_events(EpollTimeout::ZERO).unwrap().collect();
+        assert_eq!(events, [(others[1], Event::new(EpollFlags::EPOLLIN))]);
+        assert_eq!(ep.pending_events(), 0);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "while handling fd")]
+    fn run_once_panics_while_events_are_borrowed() {
+        let mut ep = Eventp::default();
+        mem::forget(ep.wait_events(EpollTimeout::ZERO).unwrap());
+        let _ = ep.run_once_with_timeout(EpollTimeout::ZERO);
+    }
+
+    #[test]
+    fn event_log_records_seq_event_and_duration() {
+        let mut ep = Eventp::default();
+        assert!(ep.event_log().is_empty());
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        cb_sub(efd, |efd, _| {
+            drain(efd);
+            std::thread::sleep(Duration::from_millis(2));
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        // Not recorded until enabled.
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(ep.event_log().is_empty());
+
+        ep.enable_event_log(2);
+        for _ in 0..3 {
+            fire(&writer);
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        let log = ep.event_log();
+        assert_eq!(log.iter().map(|l| l.seq).collect::<Vec<_>>(), [1, 2]);
+        for logged in log {
+            assert_eq!(logged.fd, raw);
+            assert_eq!(logged.event.bitflags(), EpollFlags::EPOLLIN);
+            assert!(logged.duration >= Duration::from_millis(2));
+        }
+
+        ep.clear_event_log();
+        assert!(ep.event_log().is_empty());
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(ep.event_log()[0].seq, 3);
+
+        ep.disable_event_log();
+        assert!(ep.event_log().is_empty());
+    }
+
+    #[test]
+    fn slow_handlers_are_reported() {
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let r = reports.clone();
+        let mut ep = Eventp::builder()
+            .slow_handler_threshold(
+                Duration::from_millis(20),
+                move |fd, name, elapsed, event| {
+                    r.lock().unwrap().push((fd, name, elapsed, event));
+                },
+            )
+            .build()
+            .unwrap();
+        let (slow, fast) = (new_eventfd(), new_eventfd());
+        let (slow_fd, fast_fd) = (slow.as_raw_fd(), fast.as_raw_fd());
+        let (slow_writer, fast_writer) = (writer_for(&slow), writer_for(&fast));
+        crate::interest()
+            .read()
+            .with_fd(slow)
+            .with_handler(|efd: &mut EventFd| {
+                drain(efd);
+                std::thread::sleep(Duration::from_millis(30));
+            })
+            .named("slow")
+            .register_into(&mut ep)
+            .unwrap();
+        cb_sub(fast, |efd, _| drain(efd))
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&fast_writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(ep.contains(fast_fd));
+        assert!(reports.lock().unwrap().is_empty());
+
+        fire(&slow_writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        let (fd, name, elapsed, event) = reports[0];
+        assert_eq!((fd, name), (slow_fd, Some("slow")));
+        assert!(elapsed >= Duration::from_millis(30) && elapsed < Duration::from_secs(5));
+        assert_eq!(event.bitflags(), EpollFlags::EPOLLIN);
+    }
+
+    #[test]
+    fn dump_lists_the_registrations_with_their_names() {
+        let mut ep = Eventp::default();
+        let mut expected = Vec::new();
+        for name in [Some("queue 0"), None, Some("queue 1"), None] {
+            let efd = new_eventfd();
+            let raw = efd.as_fd().as_raw_fd();
+            let interest = if name.is_some() {
+                crate::interest().read()
+            } else {
+                crate::interest().read().edge_triggered()
+            };
+            let subscriber = interest
+                .with_fd(efd)
+                .with_handler(|efd: &mut EventFd| drain(efd));
+            match name {
+                Some(name) => subscriber.named(name).register_into(&mut ep).unwrap(),
+                None => subscriber.register_into(&mut ep).unwrap(),
+            }
+            expected.push((raw, interest, name));
+        }
+        let state = crate::interest()
+            .read()
+            .with_fd(new_eventfd())
+            .with_state(0u32)
+            .with_handler(|efd: &mut EventFd, _: &mut u32| drain(efd))
+            .named("with state");
+        expected.push((
+            state.as_fd().as_raw_fd(),
+            crate::interest().read(),
+            Some("with state"),
+        ));
+        state.register_into(&mut ep).unwrap();
+        expected.sort_unstable_by_key(|&(fd, ..)| fd);
+        assert_eq!(ep.dump(), expected);
+
+        let (fd, ..) = expected.remove(2);
+        ep.delete(fd).unwrap();
+        assert_eq!(ep.dump(), expected);
+    }
+
+    #[test]
+    fn event_log_records_the_name() {
+        let mut ep = Eventp::default();
+        ep.enable_event_log(4);
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|efd: &mut EventFd| drain(efd))
+            .named("notifier")
+            .register_into(&mut ep)
+            .unwrap();
+        let (_, unnamed) = register_counting(&mut ep, &Rc::new(Cell::new(0)));
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        fire(&unnamed);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        let names: Vec<_> = ep.event_log().iter().map(|logged| logged.name).collect();
+        assert_eq!(names, [Some("notifier"), None]);
+    }
+
+    #[test]
+    fn priorities_are_ignored_without_priority_dispatch() {
+        let mut ep = Eventp::default();
+        let (low, high) = (new_eventfd(), new_eventfd());
+        let (low_writer, high_writer) = (writer_for(&low), writer_for(&high));
+        let order = Rc::new(RefCell::new(Vec::new()));
+
+        for (efd, name, priority) in [(low, "low", 255), (high, "high", 0)] {
+            let o = order.clone();
+            cb_sub(efd, move |efd, _| {
+                drain(efd);
+                o.borrow_mut().push(name);
+            })
+            .register_with_interest(
+                crate::interest().read().dispatch_priority(priority),
+                &mut ep,
+            )
+            .unwrap();
+        }
+
+        fire(&low_writer);
+        fire(&high_writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(*order.borrow(), ["low", "high"]);
+    }
+
+    #[test]
+    fn fair_dispatch_defers_deleting_the_current_subscriber() {
+        let mut ep = Eventp::builder().fair_dispatch(true).build().unwrap();
+        let mut writers = Vec::new();
+        let handled = Rc::new(Cell::new(0));
+        for _ in 0..3 {
+            let efd = new_eventfd();
+            writers.push(writer_for(&efd));
+            let h = handled.clone();
+            cb_sub(efd, move |efd, mut eventp| {
+                h.set(h.get() + 1);
+                eventp.delete(efd.as_fd().as_raw_fd()).unwrap();
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        }
+
+        // Skip a batch so the next one starts at an offset.
+        fire(&writers[0]);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        writers[1..].iter().for_each(fire);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(handled.get(), 3);
+        assert!(ep.registered.is_empty());
+    }
+
+    /// Registers `count` fired eventfds whose handlers record their fd, and returns
+    /// the fds and the record.
+    fn register_recording(
+        ep: &mut Eventp,
+        count: usize,
+        interest: Interest,
+    ) -> (Vec<RawFd>, Rc<RefCell<Vec<RawFd>>>) {
+        let handled = Rc::new(RefCell::new(Vec::new()));
+        let fds = (0..count)
+            .map(|_| {
+                let efd = new_eventfd();
+                fire(&efd);
+                let raw = efd.as_fd().as_raw_fd();
+                let h = handled.clone();
+                interest
+                    .with_fd(efd)
+                    .with_handler(move |efd: &mut EventFd| {
+                        h.borrow_mut().push(efd.as_fd().as_raw_fd())
+                    })
+                    .register_into(ep)
+                    .unwrap();
+                raw
+            })
+            .collect();
+        (fds, handled)
+    }
+
+    #[test]
+    fn budget_keeps_edge_triggered_events_beyond_it() {
+        let mut ep = Eventp::default();
+        let (mut fds, handled) =
+            register_recording(&mut ep, 5, crate::interest().read().edge_triggered());
+
+        let mut counts = vec![];
+        for _ in 0..3 {
+            counts.push(ep.run_once_budgeted(poll_timeout(), 2).unwrap());
+            counts.push(ep.pending_events());
+        }
+        assert_eq!(counts, [2, 3, 2, 1, 1, 0]);
+
+        // Every event dispatched once, although ET reports none of them again.
+        let mut handled = handled.take();
+        handled.sort_unstable();
+        fds.sort_unstable();
+        assert_eq!(handled, fds);
+        assert_eq!(ep.run_once_budgeted(EpollTimeout::ZERO, 2).unwrap(), 0);
+    }
+
+    #[test]
+    fn budget_does_not_report_level_triggered_events_twice() {
+        let mut ep = Eventp::default();
+        let (fds, handled) = register_recording(&mut ep, 3, crate::interest().read());
+
+        // Always ready, but the kept events are dispatched before waiting again.
+        for _ in 0..3 {
+            assert_eq!(ep.run_once_budgeted(poll_timeout(), 1).unwrap(), 1);
+        }
+        let mut first_round = handled.take();
+        first_round.sort_unstable();
+        first_round.dedup();
+        assert_eq!(first_round.len(), fds.len());
+
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(handled.take().len(), 3);
+    }
+
+    #[test]
+    fn budget_skips_kept_events_of_deleted_subscribers() {
+        let mut ep = Eventp::default();
+        let (fds, handled) =
+            register_recording(&mut ep, 3, crate::interest().read().edge_triggered());
+
+        ep.run_once_budgeted(poll_timeout(), 1).unwrap();
+        assert_eq!(ep.pending_events(), 2);
+        let kept: Vec<_> = fds
+            .iter()
+            .copied()
+            .filter(|fd| !handled.borrow().contains(fd))
+            .collect();
+
+        ep.delete(kept[0]).unwrap();
+        assert_eq!(ep.pending_events(), 1);
+        assert_eq!(ep.run_once_budgeted(poll_timeout(), 1).unwrap(), 1);
+        assert_eq!(handled.borrow().last(), Some(&kept[1]));
+    }
+
+    #[test]
+    fn budget_skips_kept_events_of_subscribers_deleted_while_handling() {
+        let mut ep = Eventp::default();
+        let fds = Rc::new(RefCell::new(Vec::new()));
+        let handled = Rc::new(Cell::new(0));
+        for _ in 0..3 {
+            let efd = new_eventfd();
+            fire(&efd);
+            fds.borrow_mut().push(efd.as_fd().as_raw_fd());
+            let (f, h) = (fds.clone(), handled.clone());
+            cb_sub(efd, move |efd, mut eventp| {
+                h.set(h.get() + 1);
+                // Delete all the others, whose events are kept.
+                let me = efd.as_fd().as_raw_fd();
+                for &fd in f.borrow().iter().filter(|&&fd| fd != me) {
+                    eventp.delete(fd).unwrap();
+                }
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        }
+
+        assert_eq!(ep.run_once_budgeted(poll_timeout(), 1).unwrap(), 1);
+        assert_eq!(ep.pending_events(), 0);
+        assert_eq!(handled.get(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Budget must be greater than zero")]
+    fn zero_budget_panics() {
+        let _ = Eventp::default().run_once_budgeted(EpollTimeout::ZERO, 0);
+    }
+
+    #[test]
+    fn drain_dispatches_more_events_than_fit_in_a_batch() {
+        let mut ep = Eventp::builder().capacity(4).build().unwrap();
+        let idle = Rc::new(Cell::new(0));
+        let i = idle.clone();
+        ep.set_idle_callback(move |_| i.set(i.get() + 1));
+        let handled = Rc::new(Cell::new(0));
+        for _ in 0..10 {
+            let efd = new_eventfd();
+            fire(&efd);
+            let h = handled.clone();
+            cb_sub(efd, move |efd, mut eventp| {
+                h.set(h.get() + 1);
+                eventp.delete(efd.as_fd().as_raw_fd()).unwrap();
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        }
+
+        let summary = ep.drain(Some(1)).unwrap();
+        assert_eq!((summary.batches, summary.events), (1, 4));
+        assert!(summary.more_may_remain);
+        assert_eq!(ep.len(), 6);
+
+        let summary = ep.drain(None).unwrap();
+        assert_eq!((summary.batches, summary.events), (2, 6));
+        assert!(!summary.more_may_remain);
+        assert_eq!(handled.get(), 10);
+        assert!(ep.is_empty());
+        // The last wait found nothing ready, without the loop being idle.
+        assert_eq!(idle.get(), 0);
+        assert_eq!(ep.drain(None).unwrap(), DrainSummary::default());
+    }
+
+    #[test]
+    fn handler_deletes_itself_through_the_injected_handle() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        let calls = Rc::new(Cell::new(0));
+
+        let c = calls.clone();
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(
+                move |handle: SubscriberHandle, mut eventp: Pinned<'_, Eventp>| {
+                    c.set(c.get() + 1);
+                    assert_eq!(handle.raw_fd(), raw);
+                    handle.delete(&mut eventp)
+                },
+            )
+            .register_into(&mut ep)
+            .unwrap();
+
+        for _ in 0..2 {
+            fire(&writer);
+            ep.run_once_with_timeout(EpollTimeout::from(50u16)).unwrap();
+        }
+        assert_eq!(calls.get(), 1);
+        assert!(ep.get(&raw).is_none());
+    }
+
+    #[test]
+    fn register_into_with_returns_a_working_handle() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+
+        let handle = crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|| {})
+            .register_into_with(&mut ep)
+            .unwrap();
+        assert_eq!(handle.raw_fd(), raw);
+
+        let interest = crate::interest().read().edge_triggered();
+        handle.modify(&mut ep, interest).unwrap();
+        assert_eq!(ep.interest(&raw), Some(interest));
+        handle.delete(&mut ep).unwrap();
+        assert!(ep.get(&raw).is_none());
+        assert_eq!(
+            handle.delete(&mut ep).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn handler_takes_every_injectable_parameter() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        fire(&efd);
+        let called = Rc::new(Cell::new(false));
+
+        let c = called.clone();
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(
+                move |event: Event,
+                      efd: &mut EventFd,
+                      handle: SubscriberHandle,
+                      interest: Interest,
+                      _eventp: Pinned<'_, Eventp>| {
+                    assert!(event.is_readable());
+                    assert_eq!(interest, crate::interest().read());
+                    assert_eq!(handle.raw_fd(), efd.as_fd().as_raw_fd());
+                    drain(efd);
+                    c.set(true);
+                },
+            )
+            .register_into(&mut ep)
+            .unwrap();
+
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(called.get());
+        assert!(ep.get(&raw).is_some());
+    }
+
+    /// Counts the events of an eventfd into its state, shared with the test.
+    fn count_events(efd: &mut EventFd, seen: &mut Rc<Cell<u32>>, event: Event) {
+        assert!(event.is_readable());
+        drain(efd);
+        seen.set(seen.get() + 1);
+    }
+
+    #[test]
+    fn state_is_handed_out_apart_from_the_fd() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let seen = Rc::new(Cell::new(0));
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_state(seen.clone())
+            .with_handler(count_events)
+            .register_into(&mut ep)
+            .unwrap();
+
+        for _ in 0..3 {
+            fire(&writer);
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert_eq!(seen.get(), 3);
+    }
+
+    /// The state of a connection speaking a toy protocol: `hello` first, then any
+    /// number of messages, then `bye`.
+    #[derive(Debug, PartialEq)]
+    enum Session {
+        Greeting,
+        Open { messages: u32 },
+    }
+
+    fn on_message(
+        stream: &mut std::os::unix::net::UnixStream,
+        session: &mut Session,
+        mut eventp: Pinned<'_, impl EventpOps>,
+    ) -> io::Result<()> {
+        use std::io::Read;
+
+        let mut buf = [0; 16];
+        let n = stream.read(&mut buf)?;
+        match (&mut *session, &buf[..n]) {
+            (Session::Greeting, b"hello") => *session = Session::Open { messages: 0 },
+            (Session::Greeting, _) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "no hello"));
+            }
+            (Session::Open { .. }, b"bye") => return eventp.delete(stream.as_raw_fd()),
+            (Session::Open { messages }, _) => *messages += 1,
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn stateful_handler_is_tested_on_its_own() {
+        use std::io::Write;
+        use std::os::unix::net::UnixStream;
+
+        use mockall::predicate::eq;
+
+        let (mut stream, mut peer) = UnixStream::pair().unwrap();
+        let mut session = Session::Greeting;
+        let mut mock = crate::MockEventp::new();
+        mock.expect_delete()
+            .with(eq(stream.as_raw_fd()))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        for (message, expected) in [
+            (&b"hello"[..], Session::Open { messages: 0 }),
+            (b"ping", Session::Open { messages: 1 }),
+            (b"ping", Session::Open { messages: 2 }),
+            (b"bye", Session::Open { messages: 2 }),
+        ] {
+            peer.write_all(message).unwrap();
+            on_message(&mut stream, &mut session, Pinned(Pin::new(&mut mock))).unwrap();
+            assert_eq!(session, expected);
+        }
+
+        let mut fresh = Session::Greeting;
+        peer.write_all(b"ping").unwrap();
+        let err = on_message(&mut stream, &mut fresh, Pinned(Pin::new(&mut mock))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn stateful_handler_runs_in_the_loop() {
+        use std::io::Write;
+        use std::os::unix::net::UnixStream;
+
+        let mut ep = Eventp::default();
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        let raw = stream.as_raw_fd();
+        crate::interest()
+            .read()
+            .with_fd(stream)
+            .with_state(Session::Greeting)
+            .with_handler(on_message)
+            .register_into(&mut ep)
+            .unwrap();
+
+        for message in [&b"hello"[..], b"ping", b"bye"] {
+            peer.write_all(message).unwrap();
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert!(!ep.contains(raw));
+    }
+
+    #[test]
+    fn view_ops_handlers_observe_the_loop() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let writer = writer_for(&efd);
+        let seen = Rc::new(Cell::new(None));
+        let s = seen.clone();
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move |efd: &mut EventFd, eventp: ViewOps<'_, Eventp>| {
+                drain(efd);
+                let raw = efd.as_fd().as_raw_fd();
+                s.set(Some((
+                    eventp.len(),
+                    eventp.interest(&raw),
+                    eventp.current_interest(),
+                )));
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        let counted = Rc::new(Cell::new(0));
+        let c = counted.clone();
+        let other = new_eventfd();
+        let other_writer = writer_for(&other);
+        crate::interest()
+            .read()
+            .with_fd(other)
+            .with_state(0)
+            .with_handler(
+                move |efd: &mut EventFd, n: &mut usize, eventp: ViewOps<'_, Eventp>, _: Event| {
+                    drain(efd);
+                    *n += 1;
+                    c.set(eventp.batch_info().unwrap().total() + *n);
+                },
+            )
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&writer);
+        fire(&other_writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        let read = crate::interest().read();
+        assert_eq!(seen.get(), Some((2, Some(read), Some(read))));
+        assert_eq!(counted.get(), 3);
+        assert_eq!(ep.interest(&raw), Some(read));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn view_ops_of_a_mock_use_its_defaults() {
+        fn observe(eventp: ViewOps<'_, impl EventpOps>) -> Option<BatchInfo> {
+            assert_eq!(eventp.current_interest(), None);
+            eventp.batch_info()
+        }
+
+        // No expectation is needed, as nothing but the defaults is called.
+        let mock = crate::MockEventp::new();
+        assert_eq!(observe(ViewOps::new(&mock)), None);
+        assert_eq!(observe(crate::pinned!(mock).view()), None);
+    }
+
+    /// Registers the write end of a pipe whose read end is closed, reporting
+    /// `EPOLLERR` on every wait, and returns its fd and the count of its events.
+    fn erroring_pipe(ep: &mut Eventp) -> (RawFd, Rc<Cell<usize>>) {
+        use std::os::fd::OwnedFd;
+
+        let (read, write) = nix::unistd::pipe().unwrap();
+        drop(read);
+        let raw = write.as_raw_fd();
+        let events = Rc::new(Cell::new(0));
+        let e = events.clone();
+        crate::interest()
+            .read()
+            .with_owned_fd(write)
+            .with_handler(move |_: &mut OwnedFd, event: Event| {
+                assert!(event.is_error());
+                e.set(e.get() + 1);
+            })
+            .register_into(ep)
+            .unwrap();
+        (raw, events)
+    }
+
+    #[test]
+    fn error_storm_quarantines_the_fd_until_unquarantined() {
+        let mut ep = Eventp::builder()
+            .error_storm_policy(3, Duration::from_secs(10), StormAction::Quarantine)
+            .build()
+            .unwrap();
+        let (raw, events) = erroring_pipe(&mut ep);
+
+        for _ in 0..4 {
+            ep.run_once_with_timeout(EpollTimeout::from(50u16)).unwrap();
+        }
+        assert_eq!(events.get(), 3);
+        assert!(ep.is_quarantined(&raw));
+        assert_eq!(ep.len(), 1);
+        assert_eq!(ep.stats().error_storms, 1);
+        let err = ep.modify(raw, crate::interest().write()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        ep.unquarantine(raw).unwrap();
+        assert!(!ep.is_quarantined(&raw));
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(events.get(), 4);
+        let err = ep.unquarantine(raw).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        // Quarantined again, then deleted without being taken for closed.
+        for _ in 0..2 {
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert!(ep.is_quarantined(&raw));
+        ep.delete(raw).unwrap();
+        assert!(ep.is_empty());
+        assert!(!ep.is_quarantined(&raw));
+        assert_eq!(ep.stats().fds_evicted, 0);
+    }
+
+    #[test]
+    fn error_storm_deletes_or_calls_back() {
+        let mut ep = Eventp::builder()
+            .error_storm_policy(2, Duration::from_secs(10), StormAction::Delete)
+            .build()
+            .unwrap();
+        let (_, events) = erroring_pipe(&mut ep);
+        for _ in 0..3 {
+            ep.run_once_with_timeout(EpollTimeout::from(50u16)).unwrap();
+        }
+        assert_eq!(events.get(), 2);
+        assert!(ep.is_empty());
+
+        fn demote(fd: RawFd, mut eventp: Pinned<'_, Eventp>) {
+            eventp.delete(fd).unwrap();
+        }
+        let mut ep = Eventp::builder()
+            .error_storm_policy(2, Duration::from_secs(10), StormAction::Callback(demote))
+            .build()
+            .unwrap();
+        let (_, events) = erroring_pipe(&mut ep);
+        for _ in 0..3 {
+            ep.run_once_with_timeout(EpollTimeout::from(50u16)).unwrap();
+        }
+        assert_eq!(events.get(), 2);
+        assert!(ep.is_empty());
+        assert_eq!(ep.stats().error_storms, 1);
+    }
+
+    #[test]
+    fn a_caught_storm_callback_panic_keeps_the_rest_of_the_batch() {
+        thread_local! {
+            static PANICKED: Cell<bool> = const { Cell::new(false) };
+        }
+        fn demote_or_panic(fd: RawFd, mut eventp: Pinned<'_, Eventp>) {
+            if !PANICKED.with(|panicked| panicked.replace(true)) {
+                panic!("storm callback panic");
+            }
+            eventp.delete(fd).unwrap();
+        }
+
+        let mut ep = Eventp::builder()
+            .error_storm_policy(
+                1,
+                Duration::from_secs(10),
+                StormAction::Callback(demote_or_panic),
+            )
+            .build()
+            .unwrap();
+        let (raw, events) = erroring_pipe(&mut ep);
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+        cb_sub(efd, move |_, _| c.set(c.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+
+        fire(&writer);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ep.run_once_with_timeout(poll_timeout())
+        }));
+        assert!(result.is_err());
+        assert_eq!(events.get(), 1);
+        // Dispatched before the storm, or kept for the next batch.
+        assert_eq!(calls.get() + ep.pending_events(), 1);
+
+        while ep.contains(raw) {
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert_eq!(calls.get(), 1);
+        assert_eq!(ep.stats().error_storms, 2);
+    }
+
+    #[test]
+    fn filtered_events_skip_the_handler() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+        crate::interest()
+            .read()
+            .write()
+            .with_fd(efd)
+            .with_handler(move |efd: &mut EventFd| {
+                drain(efd);
+                c.set(c.get() + 1);
+            })
+            .filter(|event: Event| event.is_readable())
+            .register_into(&mut ep)
+            .unwrap();
+
+        // Always writable, but only readable once fired.
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(calls.get(), 0);
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn one_fallback_reaps_the_closed_connections() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+
+        fn reap(event: Event, fd: RawFd, mut eventp: Pinned<'_, Eventp>) -> io::Result<()> {
+            assert!(event.is_read_closed());
+            eventp.delete(fd)
+        }
+
+        let mut ep = Eventp::default();
+        let received = Rc::new(Cell::new(0));
+        let mut peers = Vec::new();
+        for _ in 0..32 {
+            let (stream, peer) = UnixStream::pair().unwrap();
+            let r = received.clone();
+            crate::interest()
+                .read()
+                .read_hangup()
+                .with_fd(stream)
+                .with_handler(move |stream: &mut UnixStream| {
+                    let n = stream.read(&mut [0; 16]).unwrap();
+                    r.set(r.get() + n);
+                })
+                .filter(|event: Event| !event.is_read_closed())
+                .on_filtered(reap)
+                .register_into(&mut ep)
+                .unwrap();
+            peers.push(peer);
+        }
+
+        // Every other peer sends a byte, the others hang up.
+        let mut kept = Vec::new();
+        for (i, mut peer) in peers.into_iter().enumerate() {
+            if i % 2 == 0 {
+                peer.write_all(b"x").unwrap();
+                kept.push(peer);
+            }
+        }
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(received.get(), 16);
+        assert_eq!(ep.len(), 16);
+        drop(kept);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(ep.is_empty());
+    }
+
+    #[test]
+    fn owned_fd_is_closed_when_deleted() {
+        use std::os::fd::OwnedFd;
+
+        let mut ep = Eventp::default();
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let raw_read = read.as_raw_fd();
+        nix::unistd::write(&write, b"x").unwrap();
+        let called = Rc::new(Cell::new(false));
+
+        let c = called.clone();
+        crate::interest()
+            .read()
+            .with_owned_fd(read)
+            .with_handler(move |pipe: &mut OwnedFd, fd: RawFd| {
+                assert_eq!(fd, pipe.as_raw_fd());
+                nix::unistd::read(&*pipe, &mut [0; 1]).unwrap();
+                c.set(true);
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(called.get());
+
+        ep.delete(raw_read).unwrap();
+        // The pipe has no reader left. Checked through the write end, as the number
+        // of the read end may already be reused by a test on another thread.
+        assert_eq!(
+            nix::unistd::write(&write, b"x"),
+            Err(nix::errno::Errno::EPIPE)
+        );
+    }
+
+    #[test]
+    fn raw_fd_is_left_open_when_deleted() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_raw_fd();
+        fire(&efd);
+        let called = Rc::new(Cell::new(false));
+
+        let c = called.clone();
+        // SAFETY: `efd` outlives the registration.
+        unsafe { crate::interest().read().with_raw_fd(raw) }
+            .with_handler(move |source: &mut RawFdSource, fd: RawFd| {
+                assert_eq!(fd, raw);
+                assert_eq!(source.as_raw_fd(), raw);
+                c.set(true);
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(called.get());
+
+        ep.delete(raw).unwrap();
+        // Still open, with the count of both writes.
+        fire(&efd);
+        assert_eq!(efd.read().unwrap(), 2);
+    }
+
+    /// Returns a duplicate of `fd` numbered `min` or above, out of the way of the
+    /// fds of the tests running alongside, so that its number is not reused once
+    /// closed.
+    fn dup_above(fd: impl AsFd, min: RawFd) -> std::os::fd::OwnedFd {
+        use std::os::fd::FromRawFd;
+
+        // SAFETY: `F_DUPFD_CLOEXEC` takes no pointer.
+        let dup = unsafe { libc::fcntl(fd.as_fd().as_raw_fd(), libc::F_DUPFD_CLOEXEC, min) };
+        assert!(dup >= min, "{}", io::Error::last_os_error());
+        // SAFETY: Just opened, and owned by nothing else.
+        unsafe { std::os::fd::OwnedFd::from_raw_fd(dup) }
+    }
+
+    /// Registers the read end of a new pipe by its raw fd, numbered `min` or above,
+    /// and returns the pipe, to be closed behind the loop's back.
+    fn register_raw_pipe(
+        ep: &mut Eventp,
+        min: RawFd,
+        calls: &Rc<Cell<u32>>,
+    ) -> (std::os::fd::OwnedFd, std::os::fd::OwnedFd) {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let read = dup_above(read, min);
+        let calls = calls.clone();
+        // SAFETY: The fd is closed while registered on purpose, and only ever
+        // used by number afterwards.
+        unsafe { crate::interest().read().with_raw_fd(read.as_raw_fd()) }
+            .with_handler(move || calls.set(calls.get() + 1))
+            .register_into(ep)
+            .unwrap();
+        (read, write)
+    }
+
+    #[test]
+    fn delete_evicts_an_fd_closed_behind_the_loop() {
+        let mut ep = Eventp::default();
+        let calls = Rc::new(Cell::new(0));
+        let (read, _write) = register_raw_pipe(&mut ep, 700, &calls);
+        let raw = read.as_raw_fd();
+
+        drop(read);
+        assert!(ep.contains(raw));
+        ep.delete(raw).unwrap();
+        assert!(ep.is_empty());
+        assert_eq!(ep.stats().fds_evicted, 1);
+        assert_eq!(ep.delete(raw).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn error_context_reports_an_unregistered_fd_as_enoent() {
+        let mut ep = Eventp::builder().error_context(true).build().unwrap();
+        let err = ep.modify(424242, crate::interest().read()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "EPOLL_CTL_MOD failed for fd 424242: ENOENT"
+        );
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(Error::raw_os_error(&err), Some(libc::ENOENT));
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::Syscall {
+                op: "EPOLL_CTL_MOD",
+                fd: 424242,
+                name: None,
+                errno: libc::ENOENT,
+            })
+        );
+
+        let err = ep.delete(424242).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "EPOLL_CTL_DEL failed for fd 424242: ENOENT"
+        );
+        let err = ep.set_user_data(424242, 7).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "set_user_data failed for fd 424242: ENOENT"
+        );
+
+        // Without it, the error is as it has always been.
+        let mut ep = Eventp::default();
+        let err = ep.modify(424242, crate::interest().read()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(Error::from_io(&err).is_none());
+    }
+
+    #[test]
+    fn error_context_names_the_subscriber_of_a_closed_fd() {
+        let mut ep = Eventp::builder().error_context(true).build().unwrap();
+        let (read, _write) = nix::unistd::pipe().unwrap();
+        let read = dup_above(read, 1100);
+        let raw = read.as_raw_fd();
+        // SAFETY: The fd is closed while registered on purpose, and only ever used
+        // by number afterwards.
+        unsafe { crate::interest().read().with_raw_fd(raw) }
+            .with_handler(|| {})
+            .named("vhost queue 1")
+            .register_into(&mut ep)
+            .unwrap();
+
+        drop(read);
+        let err = ep.modify(raw, crate::interest().write()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("EPOLL_CTL_MOD failed for fd {raw} (\"vhost queue 1\"): EBADF")
+        );
+        assert_eq!(err.raw_os_error(), None);
+        assert_eq!(Error::raw_os_error(&err), Some(libc::EBADF));
+        // Deleting it evicts the subscriber rather than failing.
+        ep.delete(raw).unwrap();
+    }
+
+    #[test]
+    fn a_bare_os_error_keeps_its_errno_without_error_context() {
+        let mut ep = Eventp::default();
+        let calls = Rc::new(Cell::new(0));
+        let (read, _write) = register_raw_pipe(&mut ep, 1200, &calls);
+        let raw = read.as_raw_fd();
+
+        drop(read);
+        let err = ep.modify(raw, crate::interest().write()).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+        assert_eq!(Error::raw_os_error(&err), Some(libc::EBADF));
+        ep.delete(raw).unwrap();
+    }
+
+    #[test]
+    fn handler_can_delete_its_own_closed_fd() {
+        let mut ep = Eventp::default();
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let read = dup_above(read, 750);
+        let raw = read.as_raw_fd();
+        let read = Rc::new(Cell::new(Some(read)));
+        nix::unistd::write(&write, b"x").unwrap();
+
+        let r = read.clone();
+        // SAFETY: As in `register_raw_pipe`.
+        unsafe { crate::interest().read().with_raw_fd(raw) }
+            .with_handler(move |fd: RawFd, mut ep: Pinned<'_, Eventp>| {
+                drop(r.take());
+                ep.delete(fd).unwrap();
+                // Kept until the handler returns, as for any self-delete.
+                assert!(ep.0.contains(fd));
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(ep.is_empty());
+        assert_eq!(ep.stats().fds_evicted, 1);
+    }
+
+    #[test]
+    fn sweep_evicts_only_the_closed_fds() {
+        let mut ep = Eventp::default();
+        let calls = Rc::new(Cell::new(0));
+        let (closed, _) = register_raw_pipe(&mut ep, 800, &calls);
+        let (open, write) = register_raw_pipe(&mut ep, 900, &calls);
+        let raw = closed.as_raw_fd();
+
+        drop(closed);
+        assert_eq!(ep.sweep(), 1);
+        assert!(!ep.contains(raw));
+        assert!(ep.contains(open.as_raw_fd()));
+        // Its file is closed along with its registration, so it is released at once.
+        assert!(ep.evicted.is_empty());
+        assert_eq!(ep.sweep(), 0);
+        assert_eq!(ep.stats().fds_evicted, 1);
+
+        nix::unistd::write(&write, b"x").unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(calls.get(), 1);
+    }
+
+    type LifecycleLog = std::sync::Arc<std::sync::Mutex<Vec<(RawFd, Option<DeregisterReason>)>>>;
+
+    /// A builder logging every registration as `(fd, None)`, and every
+    /// deregistration as `(fd, Some(reason))`.
+    fn logging_lifecycle() -> (Builder, LifecycleLog) {
+        let log = LifecycleLog::default();
+        let (on_register, on_deregister) = (log.clone(), log.clone());
+        let builder = Eventp::builder()
+            .on_register(move |fd, _| on_register.lock().unwrap().push((fd, None)))
+            .on_deregister(move |fd, reason| {
+                on_deregister.lock().unwrap().push((fd, Some(reason)))
+            });
+        (builder, log)
+    }
+
+    #[test]
+    fn lifecycle_callbacks_report_every_transition_once() {
+        use DeregisterReason::{ExplicitDelete, Shutdown};
+
+        let (builder, log) = logging_lifecycle();
+        let mut ep = builder.build().unwrap();
+
+        // Deletes itself, to be replaced by a subscriber added once it is dropped.
+        let replaced = new_eventfd();
+        let raw_replaced = replaced.as_fd().as_raw_fd();
+        let replaced_writer = writer_for(&replaced);
+        let replacement = Rc::new(Cell::new(-1));
+        let (l, r) = (log.clone(), replacement.clone());
+        let sub = cb_sub(replaced, move |efd, mut ep| {
+            let fd = efd.as_fd().as_raw_fd();
+            ep.delete(fd).unwrap();
+            // Reported once dropped, after the handler.
+            assert!(!l.lock().unwrap().contains(&(fd, Some(ExplicitDelete))));
+            let r = r.clone();
+            ep.defer(move |mut ep| {
+                let efd = new_eventfd();
+                r.set(efd.as_fd().as_raw_fd());
+                ep.add(ThinBoxSubscriber::new(cb_sub(efd, |_, _| {})))
+                    .unwrap();
+            });
+        });
+        ep.add(ThinBoxSubscriber::new(sub)).unwrap();
+
+        // Deletes another one, reported at once.
+        let victim = new_eventfd();
+        let raw_victim = victim.as_fd().as_raw_fd();
+        ep.add(ThinBoxSubscriber::new(cb_sub(victim, |_, _| {})))
+            .unwrap();
+        let deleter = new_eventfd();
+        let raw_deleter = deleter.as_fd().as_raw_fd();
+        let deleter_writer = writer_for(&deleter);
+        let l = log.clone();
+        let sub = cb_sub(deleter, move |_, mut ep| {
+            ep.delete(raw_victim).unwrap();
+            assert_eq!(
+                l.lock().unwrap().last(),
+                Some(&(raw_victim, Some(ExplicitDelete)))
+            );
+        });
+        ep.add(ThinBoxSubscriber::new(sub)).unwrap();
+
+        // Refused, so never registered.
+        let kept = new_eventfd();
+        let raw_kept = kept.as_fd().as_raw_fd();
+        ep.add(ThinBoxSubscriber::new(cb_sub(kept, |_, _| {})))
+            .unwrap();
+        let refused = BorrowSub {
+            raw: raw_kept,
+            interest: Cell::new(crate::interest().read()),
+        };
+        ep.add(ThinBoxSubscriber::new(refused)).unwrap_err();
+
+        fire(&replaced_writer);
+        fire(&deleter_writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        let raw_replacement = replacement.get();
+        assert!(raw_replacement >= 0);
+
+        drop(ep);
+        let log = mem::take(&mut *log.lock().unwrap());
+        let (registered, deregistered): (Vec<_>, Vec<_>) =
+            log.into_iter().partition(|(_, reason)| reason.is_none());
+        assert_eq!(
+            registered,
+            [
+                raw_replaced,
+                raw_victim,
+                raw_deleter,
+                raw_kept,
+                raw_replacement
+            ]
+            .map(|fd| (fd, None))
+        );
+        let (mut explicit, mut shutdown): (Vec<_>, Vec<_>) = deregistered
+            .into_iter()
+            .partition(|(_, reason)| *reason == Some(ExplicitDelete));
+        explicit.sort_unstable_by_key(|&(fd, _)| fd);
+        shutdown.sort_unstable_by_key(|&(fd, _)| fd);
+        let mut expected = [raw_replaced, raw_victim].map(|fd| (fd, Some(ExplicitDelete)));
+        expected.sort_unstable_by_key(|&(fd, _)| fd);
+        assert_eq!(explicit, expected);
+        let mut expected = [raw_deleter, raw_kept, raw_replacement].map(|fd| (fd, Some(Shutdown)));
+        expected.sort_unstable_by_key(|&(fd, _)| fd);
+        assert_eq!(shutdown, expected);
+    }
+
+    #[test]
+    fn lifecycle_callbacks_report_quarantines_and_sweeps() {
+        use DeregisterReason::{ExplicitDelete, Quarantined, Swept};
+
+        let (builder, log) = logging_lifecycle();
+        let mut ep = builder
+            .error_storm_policy(2, Duration::from_secs(10), StormAction::Quarantine)
+            .build()
+            .unwrap();
+        let (raw, _) = erroring_pipe(&mut ep);
+        for _ in 0..2 {
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert!(ep.is_quarantined(&raw));
+        ep.unquarantine(raw).unwrap();
+        for _ in 0..2 {
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        // Already reported by the quarantine.
+        ep.delete(raw).unwrap();
+        assert_eq!(
+            mem::take(&mut *log.lock().unwrap()),
+            [
+                (raw, None),
+                (raw, Some(Quarantined)),
+                (raw, None),
+                (raw, Some(Quarantined)),
+            ]
+        );
+
+        let calls = Rc::new(Cell::new(0));
+        let (closed, _write) = register_raw_pipe(&mut ep, 800, &calls);
+        let raw = closed.as_raw_fd();
+        drop(closed);
+        assert_eq!(ep.sweep(), 1);
+        let (other, _write) = register_raw_pipe(&mut ep, 900, &calls);
+        ep.delete(other.as_raw_fd()).unwrap();
+        // Found closed by an explicit delete, and reported as one.
+        let (deleted, _write) = register_raw_pipe(&mut ep, 950, &calls);
+        let raw_deleted = deleted.as_raw_fd();
+        drop(deleted);
+        ep.delete(raw_deleted).unwrap();
+        assert_eq!(ep.stats().fds_evicted, 2);
+        drop(ep);
+        assert_eq!(
+            mem::take(&mut *log.lock().unwrap()),
+            [
+                (raw, None),
+                (raw, Some(Swept)),
+                (other.as_raw_fd(), None),
+                (other.as_raw_fd(), Some(ExplicitDelete)),
+                (raw_deleted, None),
+                (raw_deleted, Some(ExplicitDelete)),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_of_a_file_still_open_elsewhere_are_ignored_once_evicted() {
+        let mut ep = Eventp::default();
+        let calls = Rc::new(Cell::new(0));
+        let (read, write) = register_raw_pipe(&mut ep, 1000, &calls);
+        // Keeps the pipe, and so its registration, alive.
+        let dup = read.try_clone().unwrap();
+
+        drop(read);
+        assert_eq!(ep.sweep(), 1);
+        nix::unistd::write(&write, b"x").unwrap();
+        ep.run_once_with_timeout(EpollTimeout::from(50u16)).unwrap();
+        assert_eq!(calls.get(), 0);
+        assert_eq!(ep.stats().events_dispatched, 0);
+
+        // Released by the next sweep once the registration is gone.
+        assert_eq!(ep.evicted.len(), 1);
+        drop(dup);
+        assert_eq!(ep.sweep(), 0);
+        assert!(ep.evicted.is_empty());
+    }
+
+    /// Registers a new eventfd with a guard, and a handler holding `token`, whose
+    /// strong count tells whether the subscriber is dropped.
+    fn register_guarded(ep: &mut Eventp, token: &Rc<()>) -> (RawFd, EventFd, Registration) {
+        let efd = new_eventfd();
+        let raw = efd.as_raw_fd();
+        let writer = writer_for(&efd);
+        let token = token.clone();
+        let registration = crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(move |efd: &mut EventFd| {
+                let _ = &token;
+                drain(efd);
+            })
+            .register_guarded(ep)
+            .unwrap();
+        (raw, writer, registration)
+    }
+
+    #[test]
+    fn dropped_registration_is_deleted_before_the_next_wait() {
+        let mut ep = Eventp::default();
+        let token = Rc::new(());
+        let (raw, _, registration) = register_guarded(&mut ep, &token);
+        assert_eq!(registration.raw_fd(), raw);
+
+        drop(registration);
+        assert!(ep.contains(raw));
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert!(!ep.contains(raw));
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn registration_dropped_by_a_handler_is_deleted_after_the_batch() {
+        let mut ep = Eventp::default();
+        let token = Rc::new(());
+        let (raw, _, registration) = register_guarded(&mut ep, &token);
+        let registration = Rc::new(Cell::new(Some(registration)));
+
+        let efd = new_eventfd();
+        fire(&efd);
+        let r = registration.clone();
+        cb_sub(efd, move |_, ep| {
+            drop(r.take());
+            // Still there until the batch is over.
+            assert!(ep.0.contains(raw));
+        })
+        .register_into(&mut ep)
+        .unwrap();
+
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(!ep.contains(raw));
+        assert_eq!(Rc::strong_count(&token), 1);
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(ep.len(), 1);
+    }
+
+    #[test]
+    fn registration_leaves_a_later_registration_of_its_fd_alone() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_raw_fd();
+        let register = |ep: &mut Eventp| {
+            // SAFETY: `efd` outlives both registrations.
+            unsafe { crate::interest().read().with_raw_fd(raw) }
+                .with_handler(|| {})
+                .register_guarded(ep)
+                .unwrap()
+        };
+
+        let first = register(&mut ep);
+        ep.delete(raw).unwrap();
+        let second = register(&mut ep);
+        drop(first);
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert!(ep.contains(raw));
+
+        second.deregister(&mut ep).unwrap();
+        assert!(!ep.contains(raw));
+    }
+
+    #[test]
+    fn deregister_deletes_at_once_and_only_once() {
+        let mut ep = Eventp::default();
+        let token = Rc::new(());
+        let (raw, _, registration) = register_guarded(&mut ep, &token);
+
+        registration.deregister(&mut ep).unwrap();
+        assert!(!ep.contains(raw));
+        assert_eq!(Rc::strong_count(&token), 1);
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+
+        let (raw, _, registration) = register_guarded(&mut ep, &token);
+        ep.delete(raw).unwrap();
+        let err = registration.deregister(&mut ep).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn writable_edge_emulation_drops_repeated_write_readiness() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+
+        let mut ep = Eventp::default();
+        let register = |ep: &mut Eventp, interest: Interest| {
+            let (a, b) = UnixStream::pair().unwrap();
+            a.set_nonblocking(true).unwrap();
+            let raw = a.as_raw_fd();
+            let calls = Rc::new(Cell::new(0));
+            let c = calls.clone();
+            interest
+                .with_fd(a)
+                .with_handler(move |a: &mut UnixStream| {
+                    while a.read(&mut [0; 64]).is_ok_and(|n| n > 0) {}
+                    c.set(c.get() + 1);
+                })
+                .register_into(ep)
+                .unwrap();
+            (raw, b, calls)
+        };
+        let emulated_interest = Interest::stream_read_write().writable_edge_emulation();
+        let (_, _plain_peer, plain) = register(&mut ep, Interest::stream_read_write());
+        let (emulated_fd, mut peer, emulated) = register(&mut ep, emulated_interest);
+
+        for _ in 0..5 {
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert_eq!((plain.get(), emulated.get()), (5, 1));
+
+        // Reading is still level-triggered, and comes with the writability.
+        peer.write_all(b"x").unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(emulated.get(), 2);
+
+        // `modify` forgets the last event.
+        ep.modify(emulated_fd, emulated_interest).unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(emulated.get(), 3);
+        assert_eq!(plain.get(), 9);
+    }
+
+    #[test]
+    fn writable_edge_emulation_lets_the_loop_block() {
+        use std::os::unix::net::UnixStream;
+
+        let mut ep = Eventp::default();
+        let (a, _b) = UnixStream::pair().unwrap();
+        a.set_nonblocking(true).unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+        Interest::stream_read_write()
+            .writable_edge_emulation()
+            .with_fd(a)
+            .with_handler(move || c.set(c.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        // The repeated write readiness is dropped, and no longer watched.
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(calls.get(), 1);
+
+        let wakeups = ep.stats().wakeups;
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert!(ep.run_once_with_deadline(deadline).unwrap());
+        assert!(Instant::now() >= deadline);
+        assert_eq!(ep.stats().wakeups, wakeups);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn deltas_follow_write_readiness_of_a_socketpair() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+
+        let mut ep = Eventp::default();
+        let (a, mut b) = UnixStream::pair().unwrap();
+        a.set_nonblocking(true).unwrap();
+        b.set_nonblocking(true).unwrap();
+        let mut writer = a.try_clone().unwrap();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let s = seen.clone();
+        Interest::stream_read_write_et()
+            .track_deltas()
+            .with_fd(a)
+            .with_handler(move |event: Event, delta: EventDelta| {
+                s.borrow_mut().push((event, delta));
+            })
+            .register_into(&mut ep)
+            .unwrap();
+        let mut step = || {
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+            seen.borrow_mut().pop().expect("an event")
+        };
+
+        let (event, delta) = step();
+        assert!(event.is_writable() && delta.newly_writable());
+
+        // Fill the send buffer, then have the peer wake us up for reading.
+        let chunk = [0u8; 4096];
+        while writer.write(&chunk).is_ok() {}
+        b.write_all(b"x").unwrap();
+        let (event, delta) = step();
+        assert!(!event.is_writable() && delta.newly_readable());
+
+        // Draining the peer reports IN again, but only OUT is new.
+        let mut buf = [0u8; 4096];
+        while b.read(&mut buf).is_ok() {}
+        let (event, delta) = step();
+        assert!(event.is_readable() && event.is_writable());
+        assert!(delta.newly_writable() && !delta.newly_readable());
+        assert!(!delta.newly_closed());
+
+        drop(b);
+        let (event, delta) = step();
+        assert!(event.is_closed() && delta.newly_closed());
+        assert!(!delta.newly_writable());
+    }
+
+    #[test]
+    fn deltas_are_not_kept_without_track_deltas() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let seen = Rc::new(Cell::new(None));
+
+        let s = seen.clone();
+        crate::interest()
+            .read()
+            .edge_triggered()
+            .with_fd(efd)
+            .with_handler(move |delta: EventDelta, eventp: Pinned<'_, Eventp>| {
+                assert_eq!(eventp.current_delta(), None);
+                s.set(Some(delta));
+            })
+            .register_into(&mut ep)
+            .unwrap();
+
+        for _ in 0..2 {
+            fire(&writer);
+            ep.run_once_with_timeout(poll_timeout()).unwrap();
+            // Every event is diffed against an empty one.
+            assert!(seen.take().unwrap().newly_readable());
+        }
+    }
+
+    #[test]
+    fn idle_subscribers_are_evicted_after_their_own_timeouts() {
+        let mut ep = Eventp::default();
+        let (short, long) = (new_eventfd(), new_eventfd());
+        let (short_fd, long_fd) = (short.as_fd().as_raw_fd(), long.as_fd().as_raw_fd());
+        let long_writer = writer_for(&long);
+        let start = Instant::now();
+
+        for (efd, millis) in [(short, 50), (long, 300)] {
+            cb_sub(efd, |efd, _| drain(efd))
+                .register_with_interest(
+                    crate::interest()
+                        .read()
+                        .idle_timeout(Duration::from_millis(millis)),
+                    &mut ep,
+                )
+                .unwrap();
+        }
+
+        // The wait ends at the first deadline rather than never.
+        ep.run_once_with_timeout(EpollTimeout::NONE).unwrap();
+        assert!(!ep.contains(short_fd));
+        assert!(ep.contains(long_fd));
+
+        // An event pushes the deadline back.
+        ep.run_until(start + Duration::from_millis(150)).unwrap();
+        fire(&long_writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        ep.run_until(start + Duration::from_millis(350)).unwrap();
+        assert!(ep.contains(long_fd));
+
+        ep.run_until(start + Duration::from_millis(700)).unwrap();
+        assert!(!ep.contains(long_fd));
+        assert!(ep.idle.is_empty());
+    }
+
+    #[test]
+    fn on_idle_can_keep_the_subscriber() {
+        struct Sleepy {
+            eventfd: EventFd,
+            idles: Rc<Cell<u32>>,
+        }
+        impl AsFd for Sleepy {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.eventfd.as_fd()
+            }
+        }
+        impl Handler<Eventp> for Sleepy {
+            fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {}
+
+            fn on_idle(&mut self, _: Pinned<'_, Eventp>) -> bool {
+                self.idles.set(self.idles.get() + 1);
+                self.idles.get() == 3
+            }
+        }
+
+        let mut ep = Eventp::default();
+        let idles = Rc::new(Cell::new(0));
+        let eventfd = new_eventfd();
+        let raw = eventfd.as_fd().as_raw_fd();
+        let interest = crate::interest()
+            .read()
+            .idle_timeout(Duration::from_millis(20));
+        Sleepy {
+            eventfd,
+            idles: idles.clone(),
+        }
+        .register_with_interest(interest, &mut ep)
+        .unwrap();
+
+        while ep.contains(raw) {
+            ep.run_once_with_timeout(EpollTimeout::NONE).unwrap();
+        }
+        assert_eq!(idles.get(), 3);
+    }
+
+    #[test]
+    fn a_caught_on_idle_panic_leaves_the_loop_usable() {
+        struct Panicky {
+            eventfd: EventFd,
+            idles: Rc<Cell<u32>>,
+        }
+        impl AsFd for Panicky {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.eventfd.as_fd()
+            }
+        }
+        impl Handler<Eventp> for Panicky {
+            fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {}
+
+            fn on_idle(&mut self, _: Pinned<'_, Eventp>) -> bool {
+                self.idles.set(self.idles.get() + 1);
+                if self.idles.get() == 1 {
+                    panic!("on_idle panic");
+                }
+                true
+            }
+        }
+
+        let mut ep = Eventp::default();
+        let idles = Rc::new(Cell::new(0));
+        let eventfd = new_eventfd();
+        let raw = eventfd.as_fd().as_raw_fd();
+        let interest = crate::interest()
+            .read()
+            .idle_timeout(Duration::from_millis(20));
+        Panicky {
+            eventfd,
+            idles: idles.clone(),
+        }
+        .register_with_interest(interest, &mut ep)
+        .unwrap();
 
-    use super::*;
-    use crate::subscriber::{Handler, HasInterest};
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+        cb_sub(efd, move |_, _| c.set(c.get() + 1))
+            .register_into(&mut ep)
+            .unwrap();
 
-    fn new_eventfd() -> EventFd {
-        EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap()
-    }
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ep.run_once_with_timeout(EpollTimeout::NONE)
+        }));
+        assert!(result.is_err());
+        assert_eq!(idles.get(), 1);
+        assert!(ep.contains(raw));
+        assert_eq!(ep.stats().handler_panics, 1);
 
-    /// Wakes an `eventfd` so the next `epoll_wait` call returns it as ready.
-    fn fire(efd: &EventFd) {
-        efd.write(1).expect("eventfd write");
-    }
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(calls.get(), 1);
 
-    /// Drains an `eventfd` so a level-triggered subscriber doesn't keep firing.
-    fn drain(efd: &EventFd) {
-        // Ignore WouldBlock; we only care that the kernel-side counter resets.
-        let _ = efd.read();
+        // Idle again after a new period, and evicted this time.
+        while ep.contains(raw) {
+            ep.run_once_with_timeout(EpollTimeout::NONE).unwrap();
+        }
+        assert_eq!(idles.get(), 2);
     }
 
-    /// A subscriber backed by an `EventFd` whose handler invokes a closure.
-    /// The closure receives the fd's raw value and the `Pinned` reactor handle,
-    /// so re-entrancy scenarios (delete/add/modify from inside a handler) can
-    /// be exercised directly.
-    struct CbSub<F> {
+    /// Logs its shutdown: `hook`, `done` once finished if it fires itself in the
+    /// hook, then `drop`.
+    struct Closing {
         eventfd: EventFd,
-        interest: Cell<Interest>,
-        f: F,
+        id: usize,
+        fire_in_hook: bool,
+        log: Rc<RefCell<Vec<(&'static str, usize)>>>,
     }
 
-    impl<F> AsFd for CbSub<F> {
+    impl AsFd for Closing {
         fn as_fd(&self) -> BorrowedFd<'_> {
             self.eventfd.as_fd()
         }
     }
 
-    impl<F> HasInterest for CbSub<F> {
-        fn interest(&self) -> &Cell<Interest> {
-            &self.interest
-        }
-    }
-
-    impl<F> Handler<Eventp> for CbSub<F>
-    where
-        F: FnMut(&EventFd, Pinned<'_, Eventp>),
-    {
-        fn handle(&mut self, _event: Event, eventp: Pinned<'_, Eventp>) {
-            // Drain so level-triggered notifications don't repeat within the
-            // same batch (each test wires a single, deterministic sequence).
+    impl Handler<Eventp> for Closing {
+        fn handle(&mut self, _: Event, mut eventp: Pinned<'_, Eventp>) {
             drain(&self.eventfd);
-            (self.f)(&self.eventfd, eventp);
+            self.log.borrow_mut().push(("done", self.id));
+            eventp.delete(self.eventfd.as_fd().as_raw_fd()).unwrap();
         }
-    }
 
-    fn cb_sub<F>(efd: EventFd, f: F) -> CbSub<F>
-    where
-        F: FnMut(&EventFd, Pinned<'_, Eventp>),
-    {
-        CbSub {
-            eventfd: efd,
-            interest: Cell::new(crate::interest().read()),
-            f,
+        fn on_shutdown(&mut self, mut eventp: Pinned<'_, Eventp>) {
+            self.log.borrow_mut().push(("hook", self.id));
+            let err = cb_sub(new_eventfd(), |_, _| {})
+                .register_into(&mut eventp)
+                .unwrap_err();
+            assert!(matches!(
+                Error::from_io(&err),
+                Some(Error::ShuttingDown { .. })
+            ));
+            if self.fire_in_hook {
+                fire(&self.eventfd);
+            }
         }
     }
 
-    /// A subscriber that *borrows* an fd it does not own. Useful for tests
-    /// that need to register the same `RawFd` twice (which is impossible with
-    /// `OwnedFd`, since dup yields a new fd number).
-    struct BorrowSub {
-        raw: RawFd,
-        interest: Cell<Interest>,
-    }
-    impl AsFd for BorrowSub {
-        fn as_fd(&self) -> BorrowedFd<'_> {
-            // SAFETY: each test that constructs a `BorrowSub` keeps the
-            // owning `EventFd` alive for the whole test body.
-            unsafe { BorrowedFd::borrow_raw(self.raw) }
+    impl Drop for Closing {
+        fn drop(&mut self) {
+            self.log.borrow_mut().push(("drop", self.id));
         }
     }
-    impl HasInterest for BorrowSub {
-        fn interest(&self) -> &Cell<Interest> {
-            &self.interest
+
+    #[test]
+    fn shutdown_calls_the_hooks_then_drops_newest_first() {
+        let mut ep = Eventp::default();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for id in 0..4 {
+            Closing {
+                eventfd: new_eventfd(),
+                id,
+                fire_in_hook: id == 1,
+                log: log.clone(),
+            }
+            .register_with_interest(crate::interest().read(), &mut ep)
+            .unwrap();
         }
-    }
-    impl Handler<Eventp> for BorrowSub {
-        fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {}
-    }
 
-    /// Short timeout used by every test; long enough to absorb scheduling
-    /// jitter on a busy CI runner, short enough to fail fast on a real bug.
-    fn poll_timeout() -> EpollTimeout {
-        EpollTimeout::from(500u16)
+        ep.shutdown(Duration::from_millis(50)).unwrap();
+        assert_eq!(
+            *log.borrow(),
+            [
+                ("hook", 3),
+                ("hook", 2),
+                ("hook", 1),
+                ("hook", 0),
+                ("done", 1),
+                ("drop", 1),
+                ("drop", 3),
+                ("drop", 2),
+                ("drop", 0),
+            ]
+        );
     }
 
     #[test]
-    fn default_creates_usable_reactor() {
+    fn shutdown_returns_once_the_subscribers_are_done() {
         let mut ep = Eventp::default();
-        // No subscribers: epoll_wait should time out cleanly.
-        ep.run_once_with_timeout(EpollTimeout::from(10u16)).unwrap();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        Closing {
+            eventfd: new_eventfd(),
+            id: 0,
+            fire_in_hook: true,
+            log: log.clone(),
+        }
+        .register_with_interest(crate::interest().read(), &mut ep)
+        .unwrap();
+
+        let started = Instant::now();
+        ep.shutdown(Duration::from_secs(60)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(*log.borrow(), [("hook", 0), ("done", 0), ("drop", 0)]);
     }
 
     #[test]
-    #[should_panic]
-    fn new_with_zero_capacity_panics() {
-        // A zero-length event buffer cannot dispatch anything (`epoll_wait`
-        // would also reject it with EINVAL), so the constructor asserts the
-        // precondition up front.
-        let _ = Eventp::new(0, EpollCreateFlags::EPOLL_CLOEXEC);
+    fn shutdown_timeout_bounds_a_subscriber_that_never_finishes() {
+        let mut ep = Eventp::default();
+        let drops = Rc::new(Cell::new(0));
+        let eventfd = new_eventfd();
+        // Always ready, and never deleted by its handler.
+        fire(&eventfd);
+        DropCounting {
+            eventfd,
+            drops: drops.clone(),
+        }
+        .register_with_interest(crate::interest().read(), &mut ep)
+        .unwrap();
+
+        let started = Instant::now();
+        ep.shutdown(Duration::from_millis(30)).unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(30));
+        assert!(elapsed < Duration::from_secs(10));
+        assert_eq!(drops.get(), 1);
     }
 
     #[test]
-    fn add_duplicate_fd_returns_already_exists() {
+    fn modify_and_delete_update_the_idle_timers() {
         let mut ep = Eventp::default();
         let efd = new_eventfd();
         let raw = efd.as_fd().as_raw_fd();
-
-        // First registration owns the fd.
         cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        assert!(ep.idle.is_empty());
 
-        // Second registration references the same `RawFd` via a non-owning
-        // wrapper. `Eventp::add` must reject it before reaching `epoll_ctl`,
-        // returning `AlreadyExists`.
-        let err = BorrowSub {
-            raw,
-            interest: Cell::new(crate::interest().read()),
-        }
-        .register_into(&mut ep)
-        .unwrap_err();
-        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        let idle = crate::interest()
+            .read()
+            .idle_timeout(Duration::from_secs(60));
+        ep.modify(raw, idle).unwrap();
+        assert!(!ep.idle.is_empty());
+        ep.modify(raw, crate::interest().read()).unwrap();
+        assert!(ep.idle.is_empty());
+
+        ep.modify(raw, idle).unwrap();
+        ep.delete(raw).unwrap();
+        assert!(ep.idle.is_empty());
     }
 
     #[test]
-    fn modify_unknown_fd_returns_not_found() {
+    fn idle_callback_runs_only_when_the_wait_times_out() {
         let mut ep = Eventp::default();
-        let err = ep.modify(424242, crate::interest().read()).unwrap_err();
-        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let runs = Rc::new(Cell::new(0));
+        let r = runs.clone();
+        ep.set_idle_callback(move |_| r.set(r.get() + 1));
+
+        ep.run_once_with_timeout(EpollTimeout::from(10u16)).unwrap();
+        assert_eq!(runs.get(), 1);
+
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        fire(&writer);
+        ep.run_once_with_timeout(EpollTimeout::from(10u16)).unwrap();
+        assert_eq!(runs.get(), 1);
+
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(runs.get(), 2);
+
+        ep.clear_idle_callback();
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(runs.get(), 2);
     }
 
     #[test]
-    fn delete_unknown_fd_returns_not_found() {
+    fn idle_callback_can_register_and_clear_itself() {
         let mut ep = Eventp::default();
-        let err = ep.delete(424242).unwrap_err();
-        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let raw = efd.as_fd().as_raw_fd();
+        let fired = Rc::new(Cell::new(0));
+        let f = fired.clone();
+
+        let mut pending = Some(cb_sub(efd, move |_, _| f.set(f.get() + 1)));
+        ep.set_idle_callback(move |mut eventp| {
+            if let Some(subscriber) = pending.take() {
+                subscriber.register_into(&mut eventp).unwrap();
+            }
+            eventp.clear_idle_callback();
+        });
+
+        ep.run_once_with_timeout(EpollTimeout::from(10u16)).unwrap();
+        assert!(ep.contains(raw));
+        assert!(ep.idle_callback.is_none());
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(fired.get(), 1);
     }
 
     #[test]
-    fn ready_event_dispatches_to_handler() {
+    fn a_caught_idle_callback_panic_keeps_the_callback() {
         let mut ep = Eventp::default();
+        let runs = Rc::new(Cell::new(0));
+        let r = runs.clone();
+        ep.set_idle_callback(move |_| {
+            r.set(r.get() + 1);
+            if r.get() == 1 {
+                panic!("idle callback panic");
+            }
+        });
 
         let efd = new_eventfd();
-        let counter = Rc::new(Cell::new(0u32));
-        let c = counter.clone();
+        let writer = writer_for(&efd);
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
         cb_sub(efd, move |_, _| c.set(c.get() + 1))
             .register_into(&mut ep)
             .unwrap();
 
-        // Use the registered fd to wake itself.
-        let raw = ep.registered.keys().copied().next().unwrap();
-        let dup = unsafe { BorrowedFd::borrow_raw(raw) }
-            .try_clone_to_owned()
-            .unwrap();
-        let writer = unsafe { EventFd::from_owned_fd(dup) };
-        fire(&writer);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ep.run_once_with_timeout(EpollTimeout::ZERO)
+        }));
+        assert!(result.is_err());
+        assert_eq!(runs.get(), 1);
 
+        fire(&writer);
         ep.run_once_with_timeout(poll_timeout()).unwrap();
-        assert_eq!(counter.get(), 1);
+        assert_eq!(calls.get(), 1);
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(runs.get(), 2);
     }
 
     #[test]
-    fn handler_can_delete_self_and_subscriber_is_dropped() {
+    fn handler_replaces_the_idle_callback_after_the_batch() {
         let mut ep = Eventp::default();
-
-        // Track whether the subscriber's destructor ran by tying it to an Rc
-        // that we observe externally.
-        let drop_witness = Rc::new(());
-        let weak = Rc::downgrade(&drop_witness);
-
-        struct Witness(#[allow(dead_code)] Rc<()>);
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let r = runs.clone();
+        ep.set_idle_callback(move |_| r.borrow_mut().push("old"));
 
         let efd = new_eventfd();
-        let raw = efd.as_fd().as_raw_fd();
-
-        let sub = CbSub {
-            eventfd: efd,
-            interest: Cell::new(crate::interest().read()),
-            f: {
-                let _w = Witness(drop_witness);
-                move |fd: &EventFd, mut ep: Pinned<'_, Eventp>| {
-                    drain(fd);
-                    // Touching `_w` here keeps it alive until the closure drops.
-                    let _ = &_w;
-                    ep.delete(fd.as_fd().as_raw_fd()).unwrap();
-                }
-            },
-        };
-        sub.register_into(&mut ep).unwrap();
+        let writer = writer_for(&efd);
+        let r = runs.clone();
+        cb_sub(efd, move |_, mut eventp| {
+            let r = r.clone();
+            eventp.set_idle_callback(move |_| r.borrow_mut().push("new"));
+            // Deferred, as the batch is not over.
+            assert!(eventp.0.idle_callback_update.is_some());
+        })
+        .register_into(&mut ep)
+        .unwrap();
 
-        let dup = unsafe { BorrowedFd::borrow_raw(raw) }
-            .try_clone_to_owned()
-            .unwrap();
-        let writer = unsafe { EventFd::from_owned_fd(dup) };
         fire(&writer);
-
         ep.run_once_with_timeout(poll_timeout()).unwrap();
-
-        // After dispatch, the registry must no longer contain the fd, and the
-        // subscriber (which captured the strong Rc) must have been dropped.
-        assert!(!ep.registered.contains_key(&raw));
-        assert!(
-            weak.upgrade().is_none(),
-            "self-delete must drop the subscriber"
-        );
+        assert!(ep.idle_callback_update.is_none());
+        ep.run_once_with_timeout(EpollTimeout::from(10u16)).unwrap();
+        assert_eq!(*runs.borrow(), ["new"]);
     }
 
     #[test]
-    fn handler_can_delete_other_fd_during_dispatch() {
+    fn deferred_closures_run_after_the_batch_in_order() {
         let mut ep = Eventp::default();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let victim = new_eventfd();
+        let victim_raw = victim.as_raw_fd();
+        cb_sub(victim, |_, _| {}).register_into(&mut ep).unwrap();
 
-        // Subscriber A: when triggered, deletes B.
-        let efd_a = new_eventfd();
-        let raw_a = efd_a.as_fd().as_raw_fd();
-
-        // Subscriber B: tracks its own destruction.
-        let efd_b = new_eventfd();
-        let raw_b = efd_b.as_fd().as_raw_fd();
-        let b_drop = Rc::new(());
-        let b_weak = Rc::downgrade(&b_drop);
-
-        struct B {
-            eventfd: EventFd,
-            interest: Cell<Interest>,
-            _w: Rc<()>,
-        }
-        impl AsFd for B {
-            fn as_fd(&self) -> BorrowedFd<'_> {
-                self.eventfd.as_fd()
-            }
-        }
-        impl HasInterest for B {
-            fn interest(&self) -> &Cell<Interest> {
-                &self.interest
-            }
-        }
-        impl Handler<Eventp> for B {
-            fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {}
-        }
-
-        B {
-            eventfd: efd_b,
-            interest: Cell::new(crate::interest().read()),
-            _w: b_drop,
+        let mut writers = Vec::new();
+        for name in ["a", "b"] {
+            let efd = new_eventfd();
+            writers.push(writer_for(&efd));
+            let log = log.clone();
+            cb_sub(efd, move |_, mut eventp| {
+                log.borrow_mut().push(name.to_owned());
+                let _ = eventp.delete(victim_raw);
+                let log = log.clone();
+                eventp.defer(move |eventp| {
+                    // The batch is over, and the deleted subscriber freed.
+                    assert!(eventp.0.handling.is_none());
+                    assert!(eventp.0.deferred_drop.is_empty());
+                    log.borrow_mut().push(format!("deferred {name}"));
+                });
+            })
+            .register_into(&mut ep)
+            .unwrap();
         }
-        .register_into(&mut ep)
-        .unwrap();
 
-        cb_sub(efd_a, move |_, mut ep| {
-            ep.delete(raw_b).unwrap();
-            // While we are still inside A's handler, B's heap slot must NOT
-            // yet be deallocated -- the deferred_drop list keeps it alive
-            // until end-of-batch. We can't observe that directly, but we can
-            // confirm B's entry was already removed from the registry.
-        })
-        .register_into(&mut ep)
-        .unwrap();
+        writers.iter().for_each(fire);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        let log = log.borrow();
+        assert_eq!(log.len(), 4);
+        let handlers: Vec<_> = log[..2].iter().map(String::as_str).collect();
+        let deferred: Vec<_> = log[2..].iter().map(|d| &d["deferred ".len()..]).collect();
+        // Whatever order the kernel reported the fds in, the closures follow it.
+        assert_eq!(handlers, deferred);
+        assert!(!ep.contains(victim_raw));
+    }
 
-        let dup = unsafe { BorrowedFd::borrow_raw(raw_a) }
-            .try_clone_to_owned()
-            .unwrap();
-        fire(&unsafe { EventFd::from_owned_fd(dup) });
+    #[test]
+    fn closures_deferred_outside_a_batch_run_with_the_next_one() {
+        let mut ep = Eventp::default();
+        let ran = Rc::new(Cell::new(false));
+        let r = ran.clone();
+        ep.defer(Box::new(move |mut eventp| {
+            r.set(true);
+            // Registered before the wait, so its event is in the batch.
+            let efd = new_eventfd();
+            fire(&efd);
+            cb_sub(efd, |_, _| {}).register_into(&mut eventp).unwrap();
+        }));
+        assert!(!ran.get());
 
-        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        let dispatched = ep.run_once_budgeted(poll_timeout(), usize::MAX).unwrap();
+        assert!(ran.get());
+        assert_eq!(dispatched, 1);
+    }
 
-        assert!(!ep.registered.contains_key(&raw_b));
-        assert!(
-            b_weak.upgrade().is_none(),
-            "B must be deallocated by end of dispatch"
-        );
+    #[test]
+    fn deferring_from_deferred_closures_stops_at_the_max_depth() {
+        fn again(runs: Rc<Cell<usize>>) -> Deferred<Eventp> {
+            Box::new(move |mut eventp| {
+                runs.set(runs.get() + 1);
+                eventp.defer(again(runs));
+            })
+        }
+
+        let mut ep = Eventp::default();
+        let runs = Rc::new(Cell::new(0));
+        ep.defer(again(runs.clone()));
+        // Before the wait, and after the empty batch.
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(runs.get(), 2 * MAX_DEFER_DEPTH);
+        assert_eq!(ep.deferred.len(), 1);
     }
 
     #[test]
-    fn handler_can_re_add_other_fd_after_delete() {
-        // CHANGELOG note: deleting another fd from inside a handler removes
-        // its registry entry *immediately* (its heap slot is parked on
-        // `deferred_drop` and dealloc'd at end-of-batch). That gap allows the
-        // same `RawFd` to be re-added inside the same dispatch.
-        //
-        // Note: the analogous self-delete + self-re-add is NOT supported,
-        // because self-delete only sets `drop_current = true` and leaves the
-        // registry entry in place until the dispatch loop tail. We pin that
-        // contract in `self_delete_then_re_add_same_fd_returns_already_exists`
-        // below.
+    fn panicking_deferred_closure_keeps_the_rest_queued() {
         let mut ep = Eventp::default();
+        let ran = Rc::new(Cell::new(false));
+        ep.defer(Box::new(|_| panic!("deferred panic")));
+        let r = ran.clone();
+        ep.defer(Box::new(move |_| r.set(true)));
 
-        // Subscriber A wakes B's deletion + re-add.
-        let efd_a = new_eventfd();
-        let raw_a = efd_a.as_fd().as_raw_fd();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ep.run_once_with_timeout(EpollTimeout::ZERO)
+        }));
+        assert!(result.is_err());
+        assert!(!ran.get());
 
-        // B is owned by the test; the subscribers only borrow its fd.
-        let efd_b = new_eventfd();
-        let raw_b = efd_b.as_fd().as_raw_fd();
+        // Still usable, as deferred closures run outside of the batch.
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert!(ran.get());
+    }
 
-        let marker = Rc::new(Cell::new(0u32));
-        let m_for_first = marker.clone();
-        // First B subscriber: never fires in this test, only its re-add does.
-        struct CountingBorrowSub {
-            raw: RawFd,
-            interest: Cell<Interest>,
-            marker: Rc<Cell<u32>>,
-        }
-        impl AsFd for CountingBorrowSub {
-            fn as_fd(&self) -> BorrowedFd<'_> {
-                unsafe { BorrowedFd::borrow_raw(self.raw) }
-            }
-        }
-        impl HasInterest for CountingBorrowSub {
-            fn interest(&self) -> &Cell<Interest> {
-                &self.interest
-            }
+    /// A subscriber with state for other handlers to reach.
+    struct Tally {
+        eventfd: EventFd,
+        bumps: u32,
+        seen: Rc<Cell<u32>>,
+    }
+
+    impl AsFd for Tally {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.eventfd.as_fd()
         }
-        impl Handler<Eventp> for CountingBorrowSub {
-            fn handle(&mut self, _: Event, _: Pinned<'_, Eventp>) {
-                self.marker.set(self.marker.get() + 1);
-            }
+    }
+
+    impl Handler<Eventp> for Tally {
+        fn handle(&mut self, _event: Event, _eventp: Pinned<'_, Eventp>) {
+            drain(&self.eventfd);
+            self.seen.set(self.bumps);
         }
+    }
 
-        CountingBorrowSub {
-            raw: raw_b,
-            interest: Cell::new(crate::interest().read()),
-            marker: m_for_first,
+    #[test]
+    fn with_subscriber_mut_reaches_another_subscriber() {
+        let mut ep = Eventp::default();
+        let (data, control) = (new_eventfd(), new_eventfd());
+        let (data_fd, data_writer) = (data.as_fd().as_raw_fd(), writer_for(&data));
+        let control_writer = writer_for(&control);
+        let seen = Rc::new(Cell::new(0));
+
+        Tally {
+            eventfd: data,
+            bumps: 0,
+            seen: seen.clone(),
         }
-        .register_into(&mut ep)
+        .register_with_interest(crate::interest().read(), &mut ep)
         .unwrap();
-
-        let m_for_readd = marker.clone();
-        cb_sub(efd_a, move |_, mut ep| {
-            ep.delete(raw_b).unwrap();
-            CountingBorrowSub {
-                raw: raw_b,
-                interest: Cell::new(crate::interest().read()),
-                marker: m_for_readd.clone(),
-            }
-            .register_into(&mut ep)
-            .expect("re-add of same fd within handler must succeed");
+        cb_sub(control, move |efd, mut ep| {
+            drain(efd);
+            ep.with_subscriber_mut(data_fd, |s| {
+                s.downcast_mut::<Tally>().unwrap().bumps += 1;
+            })
+            .unwrap();
         })
         .register_into(&mut ep)
         .unwrap();
 
-        // Trigger A; its handler swaps out B's subscriber.
-        let dup_a = unsafe { BorrowedFd::borrow_raw(raw_a) }
-            .try_clone_to_owned()
-            .unwrap();
-        fire(&unsafe { EventFd::from_owned_fd(dup_a) });
+        fire(&control_writer);
         ep.run_once_with_timeout(poll_timeout()).unwrap();
-        assert!(ep.registered.contains_key(&raw_b));
-        assert_eq!(marker.get(), 0, "B's handler should not have run yet");
-
-        // Now wake B; the *re-added* subscriber should be the one that fires.
-        fire(&efd_b);
+        fire(&data_writer);
         ep.run_once_with_timeout(poll_timeout()).unwrap();
-        assert_eq!(marker.get(), 1);
-
-        // Drain the registry before `efd_b` goes out of scope so the
-        // borrow-style subscriber's fd reference doesn't outlive the owner.
-        let _ = ep.into_inner();
-        drop(efd_b);
+        assert_eq!(seen.get(), 1);
     }
 
     #[test]
-    fn self_delete_then_re_add_same_fd_returns_already_exists() {
-        // Documents the *opposite* contract from the test above: the
-        // currently-handled subscriber's registry entry survives until the
-        // dispatch-loop tail (because self-delete only flips `drop_current`),
-        // so an attempt to re-add the same `RawFd` inside the same handler
-        // is rejected with `AlreadyExists`. If this behaviour ever changes,
-        // the change is breaking and the test must be updated deliberately.
+    fn with_subscriber_mut_refuses_the_running_subscriber() {
         let mut ep = Eventp::default();
         let efd = new_eventfd();
-        let raw = efd.as_fd().as_raw_fd();
+        let (raw, writer) = (efd.as_fd().as_raw_fd(), writer_for(&efd));
+        let errors = Rc::new(RefCell::new(Vec::new()));
 
-        let observed_kind = Rc::new(Cell::new(None::<io::ErrorKind>));
-        let ok = observed_kind.clone();
-        cb_sub(efd, move |_, mut ep| {
-            ep.delete(raw).unwrap();
-            let err = BorrowSub {
-                raw,
-                interest: Cell::new(crate::interest().read()),
-            }
-            .register_into(&mut ep)
-            .unwrap_err();
-            ok.set(Some(err.kind()));
+        let e = errors.clone();
+        cb_sub(efd, move |efd, mut ep| {
+            drain(efd);
+            let raw = efd.as_fd().as_raw_fd();
+            let err = ep.with_subscriber_mut(raw, |_| ()).unwrap_err();
+            e.borrow_mut()
+                .push((err.kind(), Error::from_io(&err).cloned()));
+            let err = ep.with_subscriber_mut(-1, |_| ()).unwrap_err();
+            e.borrow_mut()
+                .push((err.kind(), Error::from_io(&err).cloned()));
         })
         .register_into(&mut ep)
         .unwrap();
 
-        let raw_dup = unsafe { BorrowedFd::borrow_raw(raw) }
-            .try_clone_to_owned()
-            .unwrap();
-        fire(&unsafe { EventFd::from_owned_fd(raw_dup) });
+        fire(&writer);
         ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(
+            *errors.borrow(),
+            [
+                (
+                    io::ErrorKind::InvalidInput,
+                    Some(Error::CurrentlyHandled { fd: raw })
+                ),
+                (io::ErrorKind::NotFound, None),
+            ]
+        );
+    }
 
-        assert_eq!(observed_kind.get(), Some(io::ErrorKind::AlreadyExists));
+    #[test]
+    fn downcast_only_to_the_concrete_type() {
+        let efd = new_eventfd();
+        let seen = Rc::new(Cell::new(0));
+        let mut tally: Box<dyn Subscriber<Eventp>> = Box::new(Tally {
+            eventfd: efd,
+            bumps: 7,
+            seen,
+        });
+        assert_eq!(tally.downcast_ref::<Tally>().unwrap().bumps, 7);
+        tally.downcast_mut::<Tally>().unwrap().bumps += 1;
+        assert_eq!(tally.downcast_ref::<Tally>().unwrap().bumps, 8);
+        assert!(tally
+            .downcast_ref::<CbSub<fn(&EventFd, Pinned<'_, Eventp>)>>()
+            .is_none());
     }
 
     #[test]
-    fn handler_add_new_fd_fires_on_next_iteration() {
+    fn try_register_gives_the_subscriber_back() {
         let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_fd().as_raw_fd();
+        let dup = writer_for(&efd);
+        // SAFETY: `dup` outlives the loop.
+        let borrowed = unsafe { BorrowedFd::borrow_raw(raw) };
+        crate::interest()
+            .read()
+            .with_fd(borrowed)
+            .with_handler(|| {})
+            .register_into(&mut ep)
+            .unwrap();
 
-        let trigger = new_eventfd();
-        let raw_trigger = trigger.as_fd().as_raw_fd();
+        let (err, subscriber) = crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|| {})
+            .try_register(&mut ep)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(subscriber.into_fd().as_fd().as_raw_fd(), raw);
+        drop(dup);
+    }
 
-        // The subscriber added from inside the handler will fire on the next
-        // poll. We pre-create its eventfd so we can fire it from outside.
-        let added = new_eventfd();
-        let added_raw = added.as_fd().as_raw_fd();
-        let added_slot = Rc::new(RefCell::new(Some(added)));
-        let added_fired = Rc::new(Cell::new(false));
-        let af = added_fired.clone();
-        let slot = added_slot.clone();
+    #[test]
+    fn add_rejects_an_empty_interest() {
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_raw_fd();
+        let subscriber =
+            ThinBoxSubscriber::with_interest(cb_sub(efd, |_, _| {}), Interest::default());
 
-        cb_sub(trigger, move |_, mut ep| {
-            let efd = slot.borrow_mut().take().unwrap();
-            let af = af.clone();
-            cb_sub(efd, move |_, _| af.set(true))
-                .register_into(&mut ep)
-                .unwrap();
-        })
-        .register_into(&mut ep)
-        .unwrap();
+        let err = ep.try_add(subscriber).unwrap_err();
+        assert_eq!(err.error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            Error::from_io(&err.error),
+            Some(&Error::EmptyInterest { fd: raw })
+        );
+        assert!(!ep.contains(raw));
 
-        let dup = unsafe { BorrowedFd::borrow_raw(raw_trigger) }
-            .try_clone_to_owned()
+        // Unless disabled.
+        let mut ep = Eventp::builder()
+            .reject_empty_interests(false)
+            .build()
             .unwrap();
-        fire(&unsafe { EventFd::from_owned_fd(dup) });
-        ep.run_once_with_timeout(poll_timeout()).unwrap();
-        assert!(
-            !added_fired.get(),
-            "newly-added fd must not fire in same batch"
-        );
+        ep.add(err.subscriber).unwrap();
+        assert!(ep.contains(raw));
+    }
 
-        // Now wake the newly-added fd and poll again.
-        let dup2 = unsafe { BorrowedFd::borrow_raw(added_raw) }
-            .try_clone_to_owned()
+    #[test]
+    fn hangup_only_interest_reports_the_peer_closing() {
+        use std::io::Write;
+        use std::os::unix::net::UnixStream;
+
+        let mut ep = Eventp::default();
+        let (stream, peer) = UnixStream::pair().unwrap();
+        let hangups = Rc::new(Cell::new(0));
+        let h = hangups.clone();
+        crate::interest()
+            .hangup_only()
+            .with_fd(stream)
+            .with_handler(
+                move |stream: &mut UnixStream, event: Event, mut ep: Pinned<'_, Eventp>| {
+                    assert!(event.is_hangup());
+                    h.set(h.get() + 1);
+                    ep.delete(stream.as_raw_fd()).unwrap();
+                },
+            )
+            .register_into(&mut ep)
             .unwrap();
-        fire(&unsafe { EventFd::from_owned_fd(dup2) });
+
+        // Data alone is not reported.
+        (&peer).write_all(b"ignored").unwrap();
+        ep.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(hangups.get(), 0);
+
+        drop(peer);
         ep.run_once_with_timeout(poll_timeout()).unwrap();
-        assert!(added_fired.get());
+        assert_eq!(hangups.get(), 1);
+        assert!(ep.is_empty());
     }
 
     #[test]
-    fn modify_updates_subscriber_interest_cell() {
+    fn try_add_gives_the_thin_box_back_when_epoll_refuses() {
         let mut ep = Eventp::default();
-        let efd = new_eventfd();
-        let raw = efd.as_fd().as_raw_fd();
+        // Regular files cannot be watched by epoll.
+        let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+        let raw = file.as_raw_fd();
+        let subscriber =
+            ThinBoxSubscriber::new(crate::interest().read().with_fd(file).with_handler(|| {}));
 
-        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        let err = ep.try_add(subscriber).unwrap_err();
+        assert_eq!(err.error.raw_os_error(), Some(libc::EPERM));
+        assert_eq!(err.subscriber.try_deref().unwrap().as_fd().as_raw_fd(), raw);
+        assert!(!ep.contains(raw));
+    }
 
-        let new_interest = crate::interest().read().write();
-        ep.modify(raw, new_interest).unwrap();
+    /// Set for the child process of `try_default_reports_the_fd_limit`.
+    const FD_LIMIT_CHILD: &str = "EVENTP_TEST_FD_LIMIT_CHILD";
 
-        // The subscriber's internal Cell<Interest> must reflect the new value
-        // (not just the kernel-side state).
-        let stored = ep
-            .registered
-            .get_mut(&raw)
-            .unwrap()
-            .try_deref_mut()
-            .unwrap()
-            .interest()
-            .get();
-        assert_eq!(stored, new_interest);
+    #[test]
+    fn try_default_reports_the_fd_limit() {
+        if std::env::var_os(FD_LIMIT_CHILD).is_none() {
+            // The limit is that of the whole process, so it is lowered in a child
+            // running this test alone.
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "tests::try_default_reports_the_fd_limit"])
+                .env(FD_LIMIT_CHILD, "1")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+
+        // SAFETY: All zeroes is a valid `rlimit`, which `getrlimit` then fills in.
+        let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+        assert_eq!(
+            unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) },
+            0
+        );
+        limit.rlim_cur = 64;
+        assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) }, 0);
+        let mut fds = Vec::new();
+        while let Ok(fd) = EventFd::from_flags(EfdFlags::EFD_CLOEXEC) {
+            fds.push(fd);
+        }
+
+        let Err(err) = Eventp::try_default() else {
+            panic!("no fd left for the epoll instance");
+        };
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::ResourceExhausted {
+                errno: libc::EMFILE,
+                fd_limit: Some(64),
+                open_fds: Some(64),
+            })
+        );
+        assert!(err
+            .to_string()
+            .ends_with(", with 64 fds open of a RLIMIT_NOFILE of 64"));
+
+        fds.truncate(fds.len() - 1);
+        assert!(Eventp::try_default().is_ok());
     }
 
-    /// Re-entrancy guard test.
-    ///
-    /// `Eventp::run_once_with_timeout` documents a panic when called
-    /// recursively from a handler. The dispatch loop wraps the
-    /// reconstructed `ThinBoxSubscriber` in `ManuallyDrop`, so the panic
-    /// unwinding out of `handle()` cannot double-free the heap slot still
-    /// owned by the registry. This test exercises that path end-to-end.
     #[test]
-    fn recursive_run_inside_handler_panics() {
-        let mut ep = Eventp::default();
+    fn add_fails_at_capacity() {
+        let mut ep = Eventp::builder().max_subscribers(2).build().unwrap();
+        assert_eq!(Eventp::default().capacity_remaining(), None);
+        assert_eq!(ep.capacity_remaining(), Some(2));
+        for _ in 0..2 {
+            cb_sub(new_eventfd(), |_, _| {})
+                .register_into(&mut ep)
+                .unwrap();
+        }
+        assert_eq!(ep.capacity_remaining(), Some(0));
+
+        let (err, _) = cb_sub(new_eventfd(), |_, _| {})
+            .try_register(&mut ep)
+            .unwrap_err();
+        assert_eq!(Error::from_io(&err), Some(&Error::AtCapacity { max: 2 }));
+        assert_eq!(ep.registered.len(), 2);
+
+        let raw = *ep.registered.keys().next().unwrap();
+        ep.delete(raw).unwrap();
+        assert_eq!(ep.capacity_remaining(), Some(1));
+    }
+
+    #[test]
+    fn deleting_the_current_subscriber_frees_its_slot_during_the_batch() {
+        let mut ep = Eventp::builder().max_subscribers(1).build().unwrap();
         let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let replaced = Rc::new(Cell::new(false));
+        let r = replaced.clone();
+        cb_sub(efd, move |efd, mut eventp| {
+            assert_eq!(eventp.capacity_remaining(), Some(0));
+            eventp.delete(efd.as_fd().as_raw_fd()).unwrap();
+            assert_eq!(eventp.capacity_remaining(), Some(1));
+            cb_sub(new_eventfd(), |_, _| {})
+                .register_into(&mut eventp)
+                .unwrap();
+            r.set(true);
+        })
+        .register_into(&mut ep)
+        .unwrap();
 
-        cb_sub(efd, |_, mut ep| {
-            // SAFETY: deliberately bypassing `Pinned`'s narrow API to invoke
-            // the recursion guard, which is the property under test.
-            let inner: &mut Eventp = unsafe { ep.0.as_mut().get_unchecked_mut() };
-            let _ = inner.run_once_with_timeout(EpollTimeout::from(1u16));
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(replaced.get());
+        assert_eq!(ep.registered.len(), 1);
+        assert_eq!(ep.capacity_remaining(), Some(0));
+    }
+
+    #[test]
+    fn fixed_capacity_refuses_an_add_until_a_slot_is_freed() {
+        assert!(Eventp::builder().fixed_capacity(0).build().is_err());
+        let mut ep = Eventp::builder().fixed_capacity(2).build().unwrap();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let full_in_handler = Rc::new(Cell::new(false));
+        let f = full_in_handler.clone();
+        cb_sub(efd, move |efd, mut eventp| {
+            drain(efd);
+            eventp.delete(efd.as_fd().as_raw_fd()).unwrap();
+            // Still in its slot, until the handler returns.
+            let (err, _) = cb_sub(new_eventfd(), |_, _| {})
+                .try_register(&mut eventp)
+                .unwrap_err();
+            f.set(Error::from_io(&err) == Some(&Error::RegistryFull { capacity: 2 }));
         })
         .register_into(&mut ep)
         .unwrap();
+        cb_sub(new_eventfd(), |_, _| {})
+            .register_into(&mut ep)
+            .unwrap();
 
-        let raw = *ep.registered.keys().next().unwrap();
-        let dup = unsafe { BorrowedFd::borrow_raw(raw) }
-            .try_clone_to_owned()
+        let (err, _) = cb_sub(new_eventfd(), |_, _| {})
+            .try_register(&mut ep)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::RegistryFull { capacity: 2 })
+        );
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(full_in_handler.get());
+        assert_eq!(ep.len(), 1);
+        cb_sub(new_eventfd(), |_, _| {})
+            .register_into(&mut ep)
             .unwrap();
-        fire(&unsafe { EventFd::from_owned_fd(dup) });
+        assert_eq!(ep.len(), 2);
+    }
 
-        let result = catch_unwind(AssertUnwindSafe(|| {
-            ep.run_once_with_timeout(poll_timeout()).unwrap();
+    /// Boxes a subscriber whose closure holds `N` bytes, returning its raw fd and
+    /// allocated bytes along with it.
+    fn ballast<const N: usize>(efd: EventFd) -> (ThinBoxSubscriber<Eventp>, RawFd, usize) {
+        let raw = efd.as_fd().as_raw_fd();
+        let ballast = [0u8; N];
+        let thin = ThinBoxSubscriber::new(cb_sub(efd, move |_, _| {
+            std::hint::black_box(&ballast);
         }));
-        assert!(result.is_err(), "recursive run_once must panic");
+        let bytes = thin.allocated_bytes();
+        (thin, raw, bytes)
     }
 
     #[test]
-    fn into_inner_returns_registered_subscribers() {
+    fn add_fails_over_the_memory_budget() {
+        let (small, _, small_bytes) = ballast::<0>(new_eventfd());
+        let (large, large_fd, large_bytes) = ballast::<4096>(new_eventfd());
+        assert!(large_bytes >= small_bytes + 4096);
+
+        let max = small_bytes + large_bytes;
+        let mut ep = Eventp::builder().max_subscriber_bytes(max).build().unwrap();
+        assert_eq!(ep.subscriber_bytes(), 0);
+        ep.add(small).unwrap();
+        assert_eq!(ep.subscriber_bytes(), small_bytes);
+        ep.add(large).unwrap();
+        assert_eq!(ep.subscriber_bytes(), max);
+
+        let (extra, extra_fd, _) = ballast::<0>(new_eventfd());
+        let err = ep.try_add(extra).unwrap_err();
+        assert_eq!(
+            Error::from_io(&err.error),
+            Some(&Error::OverMemoryBudget {
+                fd: extra_fd,
+                requested: small_bytes,
+                used: max,
+                max,
+            })
+        );
+        assert_eq!(ep.subscriber_bytes(), max);
+
+        ep.delete(large_fd).unwrap();
+        assert_eq!(ep.subscriber_bytes(), small_bytes);
+        ep.add(err.subscriber).unwrap();
+        assert_eq!(ep.subscriber_bytes(), 2 * small_bytes);
+
+        // The bytes are counted without a budget too.
         let mut ep = Eventp::default();
-        let efd = new_eventfd();
-        let raw = efd.as_fd().as_raw_fd();
-        cb_sub(efd, |_, _| {}).register_into(&mut ep).unwrap();
+        ep.add(ballast::<4096>(new_eventfd()).0).unwrap();
+        assert_eq!(ep.subscriber_bytes(), large_bytes);
+    }
 
-        let (_epoll, mut registered) = ep.into_inner();
-        assert!(registered.any(|s| {
-            let boxed: Box<dyn Subscriber<Eventp>> = s.try_into().ok().unwrap();
-            boxed.as_fd().as_raw_fd() == raw
+    #[test]
+    fn deleting_during_the_batch_frees_bytes_right_away() {
+        let (other, other_fd, other_bytes) = ballast::<4096>(new_eventfd());
+        let (replacement, _, replacement_bytes) = ballast::<4096>(new_eventfd());
+        let replacement = Rc::new(Cell::new(Some(replacement)));
+        let r = replacement.clone();
+        let efd = new_eventfd();
+        let writer = writer_for(&efd);
+        let current = ThinBoxSubscriber::new(cb_sub(efd, move |efd, mut eventp| {
+            let current_bytes = eventp.subscriber_bytes() - other_bytes;
+            // Dropped in place, deallocated at the end of the batch.
+            eventp.delete(other_fd).unwrap();
+            assert_eq!(eventp.subscriber_bytes(), current_bytes);
+            // Kept registered until the handler returns.
+            eventp.delete(efd.as_fd().as_raw_fd()).unwrap();
+            assert_eq!(eventp.subscriber_bytes(), 0);
+            eventp.add(r.take().unwrap()).unwrap();
+            assert_eq!(eventp.subscriber_bytes(), replacement_bytes);
         }));
+
+        // Room for the replacement only once both are deleted.
+        let max = current.allocated_bytes() + other_bytes;
+        let mut ep = Eventp::builder().max_subscriber_bytes(max).build().unwrap();
+        ep.add(current).unwrap();
+        ep.add(other).unwrap();
+        assert_eq!(ep.subscriber_bytes(), max);
+
+        fire(&writer);
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(replacement.take().is_none());
+        assert_eq!(ep.registered.len(), 1);
+        assert_eq!(ep.subscriber_bytes(), replacement_bytes);
     }
 
     #[test]
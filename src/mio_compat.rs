@@ -0,0 +1,301 @@
+//! Adapters for migrating code written against [mio](https://docs.rs/mio) to eventp,
+//! one handler at a time.
+//!
+//! - [`MioSource`] makes a type that implements [`mio::event::Source`], but not
+//!   [`AsFd`], usable as the fd of a subscriber.
+//! - [`TokenSubscriber`] keeps a handler that dispatches on a [`mio::Token`] working
+//!   unchanged: it is called with the token it was constructed with.
+//! - `Interest::from(mio::Interest)` translates interests with mio's semantics,
+//!   which are always edge-triggered.
+//!
+//! See [examples/mio-tcp-server.rs](https://github.com/FuuuOverclocking/eventp/blob/main/examples/mio-tcp-server.rs)
+//! for mio's TCP server example, translated.
+//!
+//! ```rust
+//! # use std::io;
+//! use eventp::mio_compat::{MioSource, TokenSubscriber};
+//! use eventp::{Event, Eventp, Pinned, Subscriber};
+//! use mio::net::TcpListener;
+//! use mio::Token;
+//!
+//! # fn main() -> io::Result<()> {
+//! const SERVER: Token = Token(0);
+//!
+//! let mut reactor = Eventp::default();
+//! let listener = MioSource::new(TcpListener::bind("127.0.0.1:0".parse().unwrap())?)?;
+//! TokenSubscriber::new(
+//!     SERVER,
+//!     listener,
+//!     mio::Interest::READABLE.into(),
+//!     |token, _event: Event, _eventp: Pinned<'_, Eventp>| assert_eq!(token, SERVER),
+//! )
+//! .register_into(&mut reactor)?;
+//! # Ok(()) }
+//! ```
+
+use std::cell::Cell;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::{fs, io};
+
+use mio::event::Source;
+use mio::{Poll, Token};
+
+use crate::epoll::EpollFlags;
+use crate::subscriber::{Handler, HasInterest};
+use crate::tri_subscriber::HandlerReturn;
+use crate::{Event, EventpOps, Interest, Pinned};
+
+/// Wraps a [`Source`] so it implements [`AsFd`].
+///
+/// A `Source` only reveals its fd by registering it. [`MioSource::new`] registers
+/// the source once with a private, short-lived [`Poll`], reads the registered fd
+/// back from `/proc/self/fdinfo`, and deregisters it again. The source is otherwise
+/// left alone, so it must not replace its fd while wrapped.
+#[derive(Debug)]
+pub struct MioSource<S> {
+    source: S,
+    raw_fd: RawFd,
+}
+
+impl<S: Source> MioSource<S> {
+    /// Wraps `source`, discovering its fd as described on the [type](MioSource).
+    ///
+    /// # Errors
+    ///
+    /// - [`io::ErrorKind::InvalidInput`] if the source does not register exactly
+    ///   one fd, as with sources made of several fds;
+    /// - any error from registering the source, or from reading the fdinfo of the
+    ///   probing [`Poll`], e.g. when `/proc` is not mounted.
+    pub fn new(mut source: S) -> io::Result<Self> {
+        let poll = Poll::new()?;
+        poll.registry()
+            .register(&mut source, Token(0), mio::Interest::READABLE)?;
+        let fds = registered_fds(poll.as_raw_fd());
+        poll.registry().deregister(&mut source)?;
+
+        match fds?.as_slice() {
+            &[raw_fd] => Ok(Self { source, raw_fd }),
+            fds => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("mio source registered {} fds, expected 1", fds.len()),
+            )),
+        }
+    }
+}
+
+impl<S> MioSource<S> {
+    /// Returns a reference to the wrapped source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Returns a mutable reference to the wrapped source.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Unwraps the source.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S> AsFd for MioSource<S> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: The fd is owned by `self.source`, which lives as long as `self`,
+        // and is required not to replace it.
+        unsafe { BorrowedFd::borrow_raw(self.raw_fd) }
+    }
+}
+
+/// Returns the target fds registered with the epoll `epfd`, from the `tfd:` lines
+/// of its fdinfo, see proc_pid_fdinfo(5).
+fn registered_fds(epfd: RawFd) -> io::Result<Vec<RawFd>> {
+    let fdinfo = fs::read_to_string(format!("/proc/self/fdinfo/{epfd}"))?;
+    Ok(fdinfo
+        .lines()
+        .filter_map(|line| line.strip_prefix("tfd:"))
+        .filter_map(|rest| rest.split_whitespace().next()?.parse().ok())
+        .collect())
+}
+
+impl From<mio::Interest> for Interest {
+    /// Translates with mio's semantics: edge-triggered, and readable includes
+    /// `EPOLLRDHUP`.
+    fn from(value: mio::Interest) -> Self {
+        let mut flags = EpollFlags::EPOLLET;
+        if value.is_readable() {
+            flags |= EpollFlags::EPOLLIN | EpollFlags::EPOLLRDHUP;
+        }
+        if value.is_writable() {
+            flags |= EpollFlags::EPOLLOUT;
+        }
+        if value.is_priority() {
+            flags |= EpollFlags::EPOLLPRI;
+        }
+        Interest::new(flags)
+    }
+}
+
+/// A subscriber whose handler receives a [`Token`], the way a `match event.token()`
+/// arm of a mio event loop does.
+///
+/// The token is fixed at construction; eventp itself dispatches by fd and never
+/// looks at it. Like [`tri_subscriber`](crate::tri_subscriber) closures, the handler
+/// may return `()` or `io::Result<()>`.
+pub struct TokenSubscriber<Fd, F> {
+    /// The file descriptor being watched.
+    pub fd: Fd,
+
+    /// The token passed to the handler.
+    pub token: Token,
+
+    /// The interest this subscriber is registered with, see
+    /// [`TriSubscriber::interest`](crate::tri_subscriber::TriSubscriber::interest).
+    pub interest: Cell<Interest>,
+
+    handler: F,
+}
+
+impl<Fd, F> TokenSubscriber<Fd, F> {
+    /// Creates a subscriber watching `fd`, handled by `handler` with `token`.
+    pub fn new(token: Token, fd: Fd, interest: Interest, handler: F) -> Self {
+        Self {
+            fd,
+            token,
+            interest: Cell::new(interest),
+            handler,
+        }
+    }
+}
+
+impl<Fd: AsFd, F> AsFd for TokenSubscriber<Fd, F> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl<Fd, F> HasInterest for TokenSubscriber<Fd, F> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, Fd, F, R> Handler<Ep> for TokenSubscriber<Fd, F>
+where
+    Ep: EventpOps,
+    F: FnMut(Token, Event, Pinned<'_, Ep>) -> R,
+    R: HandlerReturn,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        (self.handler)(self.token, event, eventp).into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::rc::Rc;
+
+    use mio::net::{TcpListener, UdpSocket};
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{Eventp, Subscriber};
+
+    #[test]
+    fn mio_source_finds_the_registered_fd() {
+        let listener = TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let expected = listener.as_raw_fd();
+
+        let source = MioSource::new(listener).unwrap();
+        assert_eq!(source.as_fd().as_raw_fd(), expected);
+        assert_eq!(source.into_inner().as_raw_fd(), expected);
+    }
+
+    /// A source that registers two fds at once.
+    #[derive(Debug)]
+    struct Both(UdpSocket, UdpSocket);
+
+    impl Source for Both {
+        fn register(
+            &mut self,
+            registry: &mio::Registry,
+            token: Token,
+            interests: mio::Interest,
+        ) -> io::Result<()> {
+            self.0.register(registry, token, interests)?;
+            self.1.register(registry, token, interests)
+        }
+
+        fn reregister(
+            &mut self,
+            registry: &mio::Registry,
+            token: Token,
+            interests: mio::Interest,
+        ) -> io::Result<()> {
+            self.0.reregister(registry, token, interests)?;
+            self.1.reregister(registry, token, interests)
+        }
+
+        fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+            self.0.deregister(registry)?;
+            self.1.deregister(registry)
+        }
+    }
+
+    #[test]
+    fn mio_source_rejects_multi_fd_sources() {
+        let addr = "127.0.0.1:0".parse().unwrap();
+        let both = Both(
+            UdpSocket::bind(addr).unwrap(),
+            UdpSocket::bind(addr).unwrap(),
+        );
+        let err = MioSource::new(both).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn interest_from_mio_is_edge_triggered() {
+        let interest = Interest::from(mio::Interest::READABLE | mio::Interest::WRITABLE);
+        assert_eq!(
+            interest.bitflags(),
+            EpollFlags::EPOLLET
+                | EpollFlags::EPOLLIN
+                | EpollFlags::EPOLLRDHUP
+                | EpollFlags::EPOLLOUT
+        );
+    }
+
+    #[test]
+    fn token_subscriber_passes_its_token() {
+        let listener = TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let seen = Rc::new(Cell::new(None));
+
+        let mut ep = Eventp::default();
+        let s = seen.clone();
+        TokenSubscriber::new(
+            Token(7),
+            MioSource::new(listener).unwrap(),
+            mio::Interest::READABLE.into(),
+            move |token, event: Event, _: Pinned<'_, Eventp>| {
+                assert!(event.is_readable());
+                s.set(Some(token));
+            },
+        )
+        .register_into(&mut ep)
+        .unwrap();
+
+        let mut client = std::net::TcpStream::connect(addr).unwrap();
+        client.write_all(b"hi").unwrap();
+        ep.run_once_with_timeout(EpollTimeout::from(500u16))
+            .unwrap();
+        assert_eq!(seen.get(), Some(Token(7)));
+    }
+}
@@ -27,12 +27,166 @@
 //! // When `mock` goes out of scope at the end of this block, mockall will
 //! // verify that all expectations were met (e.g., that `modify` was called exactly once).
 //! ```
+//!
+//! Handlers deferring work with [`Pinned::defer`](crate::Pinned::defer) are checked
+//! with `expect_defer`, whose argument is the boxed closure:
+//!
+//! ```rust
+//! use eventp::{pinned, EventpOps, MockEventp, Pinned};
+//!
+//! fn on_close(mut eventp: Pinned<'_, impl EventpOps>) {
+//!     eventp.defer(|_eventp| println!("flushing stats"));
+//! }
+//!
+//! let mut mock = MockEventp::new();
+//! mock.expect_defer().times(1).return_const(());
+//! on_close(pinned!(mock));
+//! ```
+//!
+//! The subscribers given to `add` can be matched with [`added_with_fd`] and
+//! [`added_with_interest`], combined with
+//! [`PredicateBooleanExt`](mockall::PredicateBooleanExt):
+//!
+//! ```rust
+//! use mockall::PredicateBooleanExt;
+//! use eventp::mock::{added_with_fd, added_with_interest, MockEventp};
+//! use eventp::{tri_subscriber::WithHandler, Interest, Subscriber};
+//! use nix::sys::eventfd::EventFd;
+//! # use std::os::fd::{AsFd, AsRawFd};
+//!
+//! let efd = EventFd::new().unwrap();
+//! let raw_fd = efd.as_fd().as_raw_fd();
+//!
+//! let mut mock = MockEventp::new();
+//! mock.expect_add()
+//!     .with(added_with_fd(raw_fd).and(added_with_interest(Interest::stream_read_et())))
+//!     .times(1)
+//!     .returning(|_| Ok(()));
+//!
+//! Interest::stream_read_et()
+//!     .with_fd(efd)
+//!     .with_handler(|_efd: &mut EventFd| {})
+//!     .register_into(&mut mock)
+//!     .unwrap();
+//! ```
+//!
+//! # Accepting connections
+//!
+//! Handlers taking their listener as an [`Accept`], and their connections as
+//! `Read + Write + AsFd`, are tested with [`MockAccept`] and [`MockStream`], without
+//! sockets. The fd of a mock is made up, as the mock loop does not use it:
+//!
+//! ```rust
+//! use std::io::{self, Read, Write};
+//! use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+//!
+//! use eventp::net::Accept;
+//! use eventp::mock::{added_with_fd, MockAccept, MockEventp, MockStream};
+//! use eventp::tri_subscriber::WithHandler;
+//! use eventp::{pinned, EventpOps, Interest, Pinned, Subscriber};
+//! use mockall::predicate::eq;
+//!
+//! fn on_connection<L>(listener: &mut L, mut eventp: Pinned<'_, impl EventpOps>) -> io::Result<()>
+//! where
+//!     L: Accept,
+//!     L::Stream: 'static + Read + Write,
+//! {
+//!     let (stream, _addr) = listener.accept()?;
+//!     Interest::stream_read()
+//!         .with_fd(stream)
+//!         .with_handler(on_data)
+//!         .register_into(&mut eventp)
+//! }
+//!
+//! fn on_data(
+//!     stream: &mut (impl Read + Write + AsFd),
+//!     mut eventp: Pinned<'_, impl EventpOps>,
+//! ) -> io::Result<()> {
+//!     let mut buf = [0; 512];
+//!     match stream.read(&mut buf)? {
+//!         0 => eventp.delete(stream.as_fd().as_raw_fd()),
+//!         n => stream.write_all(&buf[..n]),
+//!     }
+//! }
+//!
+//! // The connection is accepted and registered.
+//! let mut listener = MockAccept::new();
+//! listener.expect_accept().times(1).returning(|| {
+//!     let mut stream = MockStream::new();
+//!     stream
+//!         .expect_as_fd()
+//!         .returning(|| unsafe { BorrowedFd::borrow_raw(42) });
+//!     Ok((stream, "127.0.0.1:4242".parse().unwrap()))
+//! });
+//! let mut eventp = MockEventp::new();
+//! eventp
+//!     .expect_add()
+//!     .with(added_with_fd(42))
+//!     .times(1)
+//!     .returning(|_| Ok(()));
+//! on_connection(&mut listener, pinned!(eventp)).unwrap();
+//!
+//! // Its data is echoed, and it is deleted once closed.
+//! let mut stream = MockStream::new();
+//! stream
+//!     .expect_as_fd()
+//!     .returning(|| unsafe { BorrowedFd::borrow_raw(42) });
+//! stream.expect_read().times(1).returning(|buf| {
+//!     buf[..4].copy_from_slice(b"ping");
+//!     Ok(4)
+//! });
+//! stream
+//!     .expect_write()
+//!     .with(eq(&b"ping"[..]))
+//!     .times(1)
+//!     .returning(|buf| Ok(buf.len()));
+//! on_data(&mut stream, pinned!(MockEventp::new())).unwrap();
+//!
+//! stream.expect_read().times(1).returning(|_| Ok(0));
+//! let mut eventp = MockEventp::new();
+//! eventp.expect_delete().with(eq(42)).times(1).returning(|_| Ok(()));
+//! on_data(&mut stream, pinned!(eventp)).unwrap();
+//! ```
+//!
+//! # Capturing the added subscribers
+//!
+//! Rather than calling a handler by itself, a test can keep the subscribers a
+//! handler adds with [`capture_add`], and [fire](CaptureAdd::fire) them as the
+//! loop would, to check how the handler and the subscriber fit together:
+//!
+//! ```rust
+//! use eventp::epoll::EpollFlags;
+//! use eventp::mock::{self, MockEventp};
+//! use eventp::tri_subscriber::WithHandler;
+//! use eventp::{interest, pinned, Event, Subscriber};
+//! use nix::sys::eventfd::EventFd;
+//!
+//! let mut eventp = MockEventp::new();
+//! let captured = mock::capture_add(&mut eventp);
+//! interest()
+//!     .read()
+//!     .with_fd(EventFd::new().unwrap())
+//!     .with_handler(|ev: Event| assert!(ev.is_readable()))
+//!     .register_into(&mut eventp)
+//!     .unwrap();
+//! assert_eq!(captured.len(), 1);
+//!
+//! let event = Event::from(EpollFlags::EPOLLIN);
+//! captured.fire(0, event, pinned!(MockEventp::new())).unwrap();
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::os::fd::{AsFd, BorrowedFd, RawFd};
+use std::rc::Rc;
 
-use std::io;
-use std::os::fd::RawFd;
+use mockall::{predicate, Predicate};
 
+use crate::net::Accept;
 use crate::thin::ThinBoxSubscriber;
-use crate::{EventpOps, EventpOpsAdd, Interest};
+use crate::{AddError, Deferred, Event, EventpOps, EventpOpsAdd, Interest, Pinned};
 
 mockall::mock! {
     /// See [module level docs](self) for more information.
@@ -40,10 +194,166 @@ mockall::mock! {
 
     impl EventpOpsAdd<Self> for Eventp {
         fn add(&mut self, subscriber: ThinBoxSubscriber<Self>) -> io::Result<()>;
+        fn try_add(&mut self, subscriber: ThinBoxSubscriber<Self>) -> Result<(), AddError<Self>>;
     }
 
     impl EventpOps for Eventp {
         fn modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<()>;
+        fn try_modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<bool>;
         fn delete(&mut self, fd: RawFd) -> io::Result<()>;
+        fn defer(&mut self, f: Deferred<Self>);
+        fn set_user_data(&mut self, fd: RawFd, data: u64) -> io::Result<()>;
+    }
+}
+
+mockall::mock! {
+    /// A listener accepting [`MockStream`]s, for connection handlers generic over
+    /// [`Accept`]. See [module level docs](self#accepting-connections).
+    pub Accept {}
+
+    impl Accept for Accept {
+        type Stream = MockStream;
+        type Addr = SocketAddr;
+
+        fn accept(&self) -> io::Result<(MockStream, SocketAddr)>;
+    }
+
+    impl AsFd for Accept {
+        fn as_fd(&self) -> BorrowedFd<'_>;
+    }
+}
+
+mockall::mock! {
+    /// A connection, as accepted by [`MockAccept`], for handlers generic over
+    /// `Read + Write + AsFd`. See [module level docs](self#accepting-connections).
+    pub Stream {}
+
+    impl Read for Stream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    }
+
+    impl Write for Stream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
+        fn flush(&mut self) -> io::Result<()>;
+    }
+
+    impl AsFd for Stream {
+        fn as_fd(&self) -> BorrowedFd<'_>;
+    }
+}
+
+/// Matches the subscribers given to `add` whose fd is `raw_fd`.
+///
+/// See [`ThinBoxSubscriber::raw_fd`].
+pub fn added_with_fd(raw_fd: RawFd) -> impl Predicate<ThinBoxSubscriber<MockEventp>> + Send {
+    predicate::function(move |subscriber: &ThinBoxSubscriber<MockEventp>| {
+        subscriber.raw_fd() == raw_fd
+    })
+    .fn_name("added_with_fd")
+}
+
+/// Matches the subscribers given to `add` with exactly `interest`.
+///
+/// See [`ThinBoxSubscriber::interest`].
+pub fn added_with_interest(
+    interest: Interest,
+) -> impl Predicate<ThinBoxSubscriber<MockEventp>> + Send {
+    predicate::function(move |subscriber: &ThinBoxSubscriber<MockEventp>| {
+        subscriber.interest() == interest
+    })
+    .fn_name("added_with_interest")
+}
+
+/// Makes every `add` of `mock` succeed, keeping the subscriber in the returned
+/// handle, to [fire](CaptureAdd::fire) it later.
+///
+/// Only `add` is expected, any number of times; `try_add` is left to the test.
+/// See [module level docs](self#capturing-the-added-subscribers).
+pub fn capture_add(mock: &mut MockEventp) -> CaptureAdd {
+    let captured = CaptureAdd::default();
+    let subscribers = Rc::clone(&captured.subscribers);
+    mock.expect_add().returning_st(move |subscriber| {
+        subscribers.borrow_mut().push(Some(subscriber));
+        Ok(())
+    });
+    captured
+}
+
+/// The subscribers added to a [`MockEventp`], in the order of the calls, as kept
+/// by [`capture_add`].
+///
+/// Clones share the same subscribers.
+#[derive(Clone, Default)]
+pub struct CaptureAdd {
+    /// `None` while the subscriber is being fired, so that its handler can add to
+    /// the mock that captured it.
+    subscribers: Rc<RefCell<Vec<Option<ThinBoxSubscriber<MockEventp>>>>>,
+}
+
+impl CaptureAdd {
+    /// Returns the number of subscribers added so far.
+    pub fn len(&self) -> usize {
+        self.subscribers.borrow().len()
+    }
+
+    /// Returns `true` if nothing was added yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the fd of the `index`-th added subscriber.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds, or the subscriber is being fired.
+    pub fn raw_fd(&self, index: usize) -> RawFd {
+        self.with(index, |subscriber| subscriber.raw_fd())
+    }
+
+    /// Returns the interest the `index`-th subscriber was added with.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds, or the subscriber is being fired.
+    pub fn interest(&self, index: usize) -> Interest {
+        self.with(index, |subscriber| subscriber.interest())
+    }
+
+    /// Calls the handler of the `index`-th added subscriber with `event`, as the
+    /// loop would, and returns its result. See [`ThinBoxSubscriber::try_handle`].
+    ///
+    /// The handler may add to any mock, including the one that captured it.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds, or the subscriber is already being fired.
+    pub fn fire(
+        &self,
+        index: usize,
+        event: Event,
+        eventp: Pinned<'_, MockEventp>,
+    ) -> io::Result<()> {
+        let mut subscriber = self.subscribers.borrow_mut()[index]
+            .take()
+            .expect("the subscriber is already being fired");
+        let result = subscriber.try_handle(event, eventp);
+        self.subscribers.borrow_mut()[index] = Some(subscriber);
+        result
+    }
+
+    fn with<T>(&self, index: usize, f: impl FnOnce(&ThinBoxSubscriber<MockEventp>) -> T) -> T {
+        let subscribers = self.subscribers.borrow();
+        let subscriber = subscribers[index]
+            .as_ref()
+            .expect("the subscriber is being fired");
+        f(subscriber)
+    }
+}
+
+impl fmt::Debug for CaptureAdd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CaptureAdd")
+            .field("len", &self.len())
+            .finish()
     }
 }
@@ -0,0 +1,362 @@
+//! Subscribers that watch several file descriptors with one handler object.
+//!
+//! A [`MultiFdSubscriber`] owns a fixed set of fds and is registered with
+//! [`Eventp::add_group`](crate::Eventp::add_group). Each fd gets its own epoll
+//! registration, but all of them dispatch into the same object, telling it which
+//! fd fired by its index in [`fds`](MultiFdSubscriber::fds).
+//!
+//! # Deleting
+//!
+//! - [`delete`](crate::EventpOps::delete) on one fd removes only that fd from the
+//!   loop. The other fds keep dispatching into the group.
+//! - [`delete_group`](crate::Eventp::delete_group) removes every fd of the group.
+//!
+//! The group object is dropped once its last fd has been removed, including when
+//! that happens from inside its own handler.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::os::fd::{AsFd, BorrowedFd};
+//!
+//! use eventp::multi_fd::MultiFdSubscriber;
+//! use eventp::{Event, Eventp, Interest, Pinned};
+//! use nix::sys::eventfd::EventFd;
+//!
+//! struct Device {
+//!     control: EventFd,
+//!     data: EventFd,
+//! }
+//!
+//! impl MultiFdSubscriber<Eventp> for Device {
+//!     fn fds(&self) -> Vec<(BorrowedFd<'_>, Interest)> {
+//!         vec![
+//!             (self.control.as_fd(), eventp::interest().read()),
+//!             (self.data.as_fd(), eventp::interest().read()),
+//!         ]
+//!     }
+//!
+//!     fn handle(&mut self, index: usize, _event: Event, _eventp: Pinned<'_, Eventp>) {
+//!         match index {
+//!             0 => { let _ = self.control.read(); }
+//!             _ => { let _ = self.data.read(); }
+//!         }
+//!     }
+//! }
+//!
+//! fn thread_main(device: Device) -> io::Result<()> {
+//!     let mut reactor = Eventp::default();
+//!     reactor.add_group(device)?;
+//!     reactor.run_forever()
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd, RawFd};
+use std::rc::Rc;
+
+use crate::subscriber::Handler;
+use crate::{Event, EventpOps, Interest, Pinned};
+
+/// A handler object shared by several file descriptors.
+///
+/// See the [module level docs](self) for more information.
+pub trait MultiFdSubscriber<Ep: EventpOps>: 'static {
+    /// Returns the fds to watch, paired with their initial interest.
+    ///
+    /// Called once, when the group is added. The position of an fd in the returned
+    /// list is the `index` later passed to [`handle`](Self::handle). The fds must
+    /// stay open for as long as they are registered, which holds naturally when the
+    /// group owns them.
+    fn fds(&self) -> Vec<(BorrowedFd<'_>, Interest)>;
+
+    /// Handles an event on the fd at position `index` of [`fds`](Self::fds).
+    fn handle(&mut self, index: usize, event: Event, eventp: Pinned<'_, Ep>);
+
+    /// Same as [`Handler::try_handle`], for a group.
+    fn try_handle(&mut self, index: usize, event: Event, eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        self.handle(index, event, eventp);
+        Ok(())
+    }
+}
+
+/// The raw fds of a group that are still registered, shared by all of them.
+pub(crate) type GroupMembers = Rc<RefCell<Vec<RawFd>>>;
+
+/// The subscriber registered for one fd of a group.
+pub(crate) struct Member<G> {
+    group: Rc<RefCell<G>>,
+    index: usize,
+    raw_fd: RawFd,
+}
+
+impl<G> Member<G> {
+    pub(crate) fn new(group: Rc<RefCell<G>>, index: usize, raw_fd: RawFd) -> Self {
+        Self {
+            group,
+            index,
+            raw_fd,
+        }
+    }
+}
+
+impl<G> AsFd for Member<G> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: `raw_fd` was borrowed from the group, which `fds()` requires to keep
+        // it open while registered. The group outlives `self` through the `Rc`.
+        unsafe { BorrowedFd::borrow_raw(self.raw_fd) }
+    }
+}
+
+impl<Ep, G> Handler<Ep> for Member<G>
+where
+    Ep: EventpOps,
+    G: MultiFdSubscriber<Ep>,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        // Not already borrowed: the loop dispatches one event at a time and refuses
+        // to recurse, and dropping a sibling member only releases its `Rc`. A sibling
+        // lent out by `Pinned::with_subscriber_mut` and handled by hand would panic
+        // here rather than alias the group.
+        self.group
+            .borrow_mut()
+            .try_handle(self.index, event, eventp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::os::fd::AsRawFd;
+
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{ErrorPolicy, Eventp};
+
+    fn new_eventfd() -> EventFd {
+        EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap()
+    }
+
+    fn writer_for(efd: &EventFd) -> EventFd {
+        let dup = efd.as_fd().try_clone_to_owned().unwrap();
+        unsafe { EventFd::from_owned_fd(dup) }
+    }
+
+    fn poll_timeout() -> EpollTimeout {
+        EpollTimeout::from(500u16)
+    }
+
+    type OnEvent = Box<dyn FnMut(usize, &[RawFd], Pinned<'_, Eventp>) -> io::Result<()>>;
+
+    /// A group of two eventfds that records every dispatched index.
+    struct Pair {
+        efds: [EventFd; 2],
+        seen: Rc<RefCell<Vec<usize>>>,
+        dropped: Rc<Cell<u32>>,
+        on_event: OnEvent,
+    }
+
+    impl Drop for Pair {
+        fn drop(&mut self) {
+            self.dropped.set(self.dropped.get() + 1);
+        }
+    }
+
+    impl MultiFdSubscriber<Eventp> for Pair {
+        fn fds(&self) -> Vec<(BorrowedFd<'_>, Interest)> {
+            self.efds
+                .iter()
+                .map(|efd| (efd.as_fd(), crate::interest().read()))
+                .collect()
+        }
+
+        fn handle(&mut self, _: usize, _: Event, _: Pinned<'_, Eventp>) {
+            unreachable!("`try_handle` is overridden")
+        }
+
+        fn try_handle(
+            &mut self,
+            index: usize,
+            _: Event,
+            eventp: Pinned<'_, Eventp>,
+        ) -> io::Result<()> {
+            let _ = self.efds[index].read();
+            self.seen.borrow_mut().push(index);
+            let raw = [self.efds[0].as_raw_fd(), self.efds[1].as_raw_fd()];
+            (self.on_event)(index, &raw, eventp)
+        }
+    }
+
+    struct Fixture {
+        raw: [RawFd; 2],
+        writers: [EventFd; 2],
+        seen: Rc<RefCell<Vec<usize>>>,
+        dropped: Rc<Cell<u32>>,
+    }
+
+    fn add_pair(
+        ep: &mut Eventp,
+        on_event: impl FnMut(usize, &[RawFd], Pinned<'_, Eventp>) -> io::Result<()> + 'static,
+    ) -> Fixture {
+        let efds = [new_eventfd(), new_eventfd()];
+        let fixture = Fixture {
+            raw: [efds[0].as_raw_fd(), efds[1].as_raw_fd()],
+            writers: [writer_for(&efds[0]), writer_for(&efds[1])],
+            seen: Default::default(),
+            dropped: Default::default(),
+        };
+        ep.add_group(Pair {
+            efds,
+            seen: fixture.seen.clone(),
+            dropped: fixture.dropped.clone(),
+            on_event: Box::new(on_event),
+        })
+        .unwrap();
+        fixture
+    }
+
+    #[test]
+    fn every_fd_dispatches_into_the_same_object() {
+        let mut ep = Eventp::default();
+        let f = add_pair(&mut ep, |_, _, _| Ok(()));
+
+        f.writers[1].write(1).unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        f.writers[0].write(1).unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        assert_eq!(*f.seen.borrow(), [1, 0]);
+        assert_eq!(f.dropped.get(), 0);
+    }
+
+    #[test]
+    fn deleting_one_fd_keeps_the_rest_of_the_group() {
+        let mut ep = Eventp::default();
+        let f = add_pair(&mut ep, |_, _, _| Ok(()));
+
+        ep.delete(f.raw[0]).unwrap();
+        assert!(!ep.registered.contains_key(&f.raw[0]));
+        assert_eq!(f.dropped.get(), 0);
+
+        f.writers[1].write(1).unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(*f.seen.borrow(), [1]);
+
+        // Removing the last fd drops the group.
+        ep.delete(f.raw[1]).unwrap();
+        assert_eq!(f.dropped.get(), 1);
+    }
+
+    #[test]
+    fn delete_group_removes_every_fd() {
+        let mut ep = Eventp::default();
+        let f = add_pair(&mut ep, |_, _, _| Ok(()));
+
+        ep.delete_group(f.raw[1]).unwrap();
+        assert!(!ep.registered.contains_key(&f.raw[0]));
+        assert!(!ep.registered.contains_key(&f.raw[1]));
+        assert!(ep.groups.is_empty());
+        assert_eq!(f.dropped.get(), 1);
+    }
+
+    #[test]
+    fn delete_group_skips_fds_already_deleted() {
+        let mut ep = Eventp::default();
+        let f = add_pair(&mut ep, |_, _, _| Ok(()));
+
+        ep.delete(f.raw[0]).unwrap();
+        ep.delete_group(f.raw[1]).unwrap();
+        assert_eq!(f.dropped.get(), 1);
+    }
+
+    #[test]
+    fn delete_group_on_a_plain_subscriber_deletes_it() {
+        use crate::tri_subscriber::WithHandler;
+        use crate::Subscriber;
+
+        let mut ep = Eventp::default();
+        let efd = new_eventfd();
+        let raw = efd.as_raw_fd();
+        crate::interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|| {})
+            .register_into(&mut ep)
+            .unwrap();
+
+        ep.delete_group(raw).unwrap();
+        assert!(!ep.registered.contains_key(&raw));
+    }
+
+    #[test]
+    fn delete_group_from_own_handler_drops_after_dispatch() {
+        let mut ep = Eventp::default();
+        let f = add_pair(&mut ep, |_, raw, mut ep| ep.delete_group(raw[0]));
+
+        f.writers[0].write(1).unwrap();
+        f.writers[1].write(1).unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+
+        // Whichever fd came first deleted both, so the other one was never served.
+        assert_eq!(f.seen.borrow().len(), 1);
+        assert!(ep.registered.is_empty());
+        assert_eq!(f.dropped.get(), 1);
+    }
+
+    #[test]
+    fn add_group_rolls_back_on_failure() {
+        struct Twice(EventFd, Rc<Cell<u32>>);
+
+        impl Drop for Twice {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        impl MultiFdSubscriber<Eventp> for Twice {
+            fn fds(&self) -> Vec<(BorrowedFd<'_>, Interest)> {
+                vec![
+                    (self.0.as_fd(), crate::interest().read()),
+                    (self.0.as_fd(), crate::interest().write()),
+                ]
+            }
+
+            fn handle(&mut self, _: usize, _: Event, _: Pinned<'_, Eventp>) {}
+        }
+
+        let mut ep = Eventp::default();
+        let dropped = Rc::new(Cell::new(0));
+        let err = ep
+            .add_group(Twice(new_eventfd(), dropped.clone()))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(ep.registered.is_empty());
+        assert!(ep.groups.is_empty());
+        assert_eq!(dropped.get(), 1);
+    }
+
+    #[test]
+    fn remove_policy_removes_the_whole_group() {
+        let mut ep = Eventp::builder()
+            .error_policy(ErrorPolicy::Remove)
+            .build()
+            .unwrap();
+        let f = add_pair(&mut ep, |_, _, _| {
+            Err(io::Error::new(io::ErrorKind::Other, "device failed"))
+        });
+
+        f.writers[1].write(1).unwrap();
+        ep.run_once_with_timeout(poll_timeout()).unwrap();
+        assert!(ep.registered.is_empty());
+        assert_eq!(f.dropped.get(), 1);
+    }
+}
@@ -0,0 +1,54 @@
+//! The listeners connections are accepted from, for the [`acceptor`](crate::acceptor())
+//! subscriber and the handlers it builds.
+//!
+//! [`Accept`] is implemented for [`TcpListener`] and [`UnixListener`], and with the
+//! `vsock` feature, for `vsock::VsockListener`. Handlers written against it serve
+//! any of them, and are tested with `mock::MockAccept` of the `mock` feature.
+//! [`acceptor::Accept`](crate::acceptor::Accept) is the same trait.
+
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::fd::AsFd;
+use std::os::unix::net::{self, UnixListener, UnixStream};
+
+/// A listener the [`acceptor`](crate::acceptor()) subscriber can accept
+/// connections from.
+///
+/// Implement it to accept from other kinds of listeners. In tests, `mock::MockAccept`
+/// of the `mock` feature accepts `mock::MockStream`s.
+pub trait Accept: AsFd {
+    /// The connection type.
+    type Stream: AsFd;
+
+    /// The address of the peer.
+    type Addr;
+
+    /// Accepts a connection without blocking, and returns it nonblocking.
+    ///
+    /// # Errors
+    ///
+    /// [`io::ErrorKind::WouldBlock`] once no connection is pending.
+    fn accept(&self) -> io::Result<(Self::Stream, Self::Addr)>;
+}
+
+impl Accept for TcpListener {
+    type Stream = TcpStream;
+    type Addr = SocketAddr;
+
+    fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (stream, addr) = TcpListener::accept(self)?;
+        stream.set_nonblocking(true)?;
+        Ok((stream, addr))
+    }
+}
+
+impl Accept for UnixListener {
+    type Stream = UnixStream;
+    type Addr = net::SocketAddr;
+
+    fn accept(&self) -> io::Result<(UnixStream, net::SocketAddr)> {
+        let (stream, addr) = UnixListener::accept(self)?;
+        stream.set_nonblocking(true)?;
+        Ok((stream, addr))
+    }
+}
@@ -0,0 +1,745 @@
+//! A subscriber monitoring network interfaces and their addresses, with a
+//! `NETLINK_ROUTE` socket.
+//!
+//! [`monitor()`] creates the socket, bound to the multicast [`Groups`] to join,
+//! and [`Monitor::with_handler`] the subscriber. When the socket is readable, the subscriber receives until it would block, and calls the handler
+//! once per message, decoded as a [`RouteEvent`]: a link going up or down, an
+//! address added or removed, and so on. Messages of other kinds are handed out as
+//! [`RouteEvent::Other`], undecoded.
+//!
+//! The kernel only sends changes. The current state is requested with
+//! [`Socket::request_dump`], whose replies are handed out as the same events, then
+//! [`RouteEvent::DumpDone`].
+//!
+//! # Overflow
+//!
+//! When changes come in faster than the loop receives them, the kernel drops them,
+//! and the socket fails with `ENOBUFS`, handed out as [`RouteEvent::Overflow`]. So
+//! is a message too large for the buffer of the subscriber. The interfaces may have
+//! changed in any way since, so request a dump to resync.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use eventp::netlink::{self, Dump, Groups, RouteEvent};
+//! use eventp::{Eventp, Pinned, Subscriber};
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut eventp = Eventp::default();
+//! let monitor = netlink::monitor(Groups::LINK | Groups::IPV4_ADDR)?;
+//! let socket = monitor.socket();
+//! socket.request_dump(Dump::Links)?;
+//! monitor
+//!     .with_handler(move |event: RouteEvent, _eventp: Pinned<'_, Eventp>| {
+//!         match event {
+//!             RouteEvent::LinkUp { name, .. } => println!("{name:?} is up"),
+//!             RouteEvent::LinkDown { name, .. } => println!("{name:?} is down"),
+//!             RouteEvent::Overflow => return socket.request_dump(Dump::Links),
+//!             _ => {}
+//!         }
+//!         Ok(())
+//!     })
+//!     .register_into(&mut eventp)?;
+//! eventp.run_once()?;
+//! # Ok(()) }
+//! ```
+//!
+//! See [examples/netlink-monitor.rs](https://github.com/FuuuOverclocking/eventp/blob/main/examples/netlink-monitor.rs)
+//! for a program printing every change.
+
+use std::cell::Cell;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::{BitOr, BitOrAssign};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::rc::Rc;
+use std::{io, mem};
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::tri_subscriber::HandlerReturn;
+use crate::{interest, Event, EventpOps, Interest, Pinned};
+
+/// The size of `struct nlmsghdr`.
+const HEADER_LEN: usize = 16;
+/// The size of `struct ifinfomsg`.
+const IFINFOMSG_LEN: usize = 16;
+/// The size of `struct ifaddrmsg`.
+const IFADDRMSG_LEN: usize = 8;
+/// Room for the largest messages of a dump, which the kernel sizes after the page
+/// size, up to 32KiB.
+const BUF_LEN: usize = 32 * 1024;
+
+/// The multicast groups of `NETLINK_ROUTE` a [`monitor`] joins, see rtnetlink(7).
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Groups(u32);
+
+impl Groups {
+    /// No group: only the replies to [`Socket::request_dump`] are received.
+    pub const NONE: Self = Self(0);
+    /// `RTMGRP_LINK`: interfaces added, removed, going up or down.
+    pub const LINK: Self = Self(libc::RTMGRP_LINK as u32);
+    /// `RTMGRP_IPV4_IFADDR`: IPv4 addresses added or removed.
+    pub const IPV4_ADDR: Self = Self(libc::RTMGRP_IPV4_IFADDR as u32);
+    /// `RTMGRP_IPV6_IFADDR`: IPv6 addresses added or removed.
+    pub const IPV6_ADDR: Self = Self(libc::RTMGRP_IPV6_IFADDR as u32);
+
+    /// Creates the groups of a `RTMGRP_*` mask, e.g. for groups without a constant
+    /// here, such as `RTMGRP_IPV4_ROUTE`.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the `RTMGRP_*` mask.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for Groups {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Groups {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Creates a `NETLINK_ROUTE` socket joining `groups`, to be completed with a
+/// handler by [`Monitor::with_handler`].
+///
+/// For more information, see the [mod-level documentation](self).
+///
+/// # Errors
+///
+/// Forwards any error from creating or binding the socket.
+pub fn monitor(groups: Groups) -> io::Result<Monitor> {
+    // SAFETY: `socket` takes no pointer.
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: Just opened, and owned by nothing else.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // SAFETY: All zeroes is a valid `sockaddr_nl`, with the kernel picking the port
+    // id.
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = groups.bits();
+    // SAFETY: `addr` is a valid `sockaddr_nl` of the given length.
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            (&addr as *const libc::sockaddr_nl).cast(),
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Monitor {
+        socket: Socket {
+            shared: Rc::new(Shared {
+                fd,
+                seq: Cell::new(0),
+            }),
+        },
+        groups,
+    })
+}
+
+/// A netlink socket, waiting for its handler.
+pub struct Monitor {
+    socket: Socket,
+    groups: Groups,
+}
+
+impl Monitor {
+    /// Returns a handle to send requests on the socket, e.g. for the handler to
+    /// request a dump after an overflow.
+    pub fn socket(&self) -> Socket {
+        self.socket.clone()
+    }
+
+    /// Creates the subscriber calling `handler` with every message.
+    ///
+    /// The handler returns either `()` or `io::Result<()>`. After an error, the
+    /// messages of the datagram already received are still handed out, and the
+    /// following ones are left for the next time the loop polls.
+    pub fn with_handler<F>(self, handler: F) -> Subscriber<F> {
+        Subscriber {
+            socket: self.socket,
+            groups: self.groups,
+            interest: Cell::new(interest().read()),
+            buf: vec![0; BUF_LEN].into_boxed_slice(),
+            handler,
+        }
+    }
+}
+
+/// A message received from `NETLINK_ROUTE`, see rtnetlink(7).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RouteEvent {
+    /// An interface is up and running (`IFF_RUNNING`), e.g. up with a carrier.
+    LinkUp {
+        /// The index of the interface.
+        ifindex: u32,
+        /// The name of the interface, e.g. `eth0`, unless the kernel left it out.
+        name: Option<String>,
+    },
+    /// An interface is there, but not running.
+    LinkDown {
+        /// The index of the interface.
+        ifindex: u32,
+        /// The name of the interface, unless the kernel left it out.
+        name: Option<String>,
+    },
+    /// An interface was removed.
+    LinkRemoved {
+        /// The index of the interface.
+        ifindex: u32,
+        /// The name of the interface, unless the kernel left it out.
+        name: Option<String>,
+    },
+    /// An address was added to an interface.
+    AddrAdded {
+        /// The index of the interface.
+        ifindex: u32,
+        /// The address of the interface, its local end for a point-to-point one.
+        addr: IpAddr,
+        /// The length of the prefix of the network, e.g. 24 for a `/24`.
+        prefix_len: u8,
+    },
+    /// An address was removed from an interface.
+    AddrRemoved {
+        /// The index of the interface.
+        ifindex: u32,
+        /// The address of the interface.
+        addr: IpAddr,
+        /// The length of the prefix of the network.
+        prefix_len: u8,
+    },
+    /// The replies to a [`Socket::request_dump`] are over.
+    DumpDone,
+    /// Messages were lost, see [Overflow](self#overflow).
+    Overflow,
+    /// A message of another kind, or one that could not be decoded.
+    Other(RawMessage),
+}
+
+/// A netlink message, as received.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawMessage {
+    /// The type of the message, e.g. `RTM_NEWROUTE`.
+    pub kind: u16,
+    /// The `NLM_F_*` flags of the message.
+    pub flags: u16,
+    /// The message after its `struct nlmsghdr`.
+    pub payload: Vec<u8>,
+}
+
+/// The state a [`Socket::request_dump`] asks for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Dump {
+    /// Every interface, handed out as [`RouteEvent::LinkUp`] or
+    /// [`RouteEvent::LinkDown`].
+    Links,
+    /// Every address, handed out as [`RouteEvent::AddrAdded`].
+    Addrs,
+}
+
+/// Sends requests on the socket of a [`Subscriber`], see [`Monitor::socket`].
+///
+/// Cheap to clone, and usable from the handlers of the loop the subscriber is
+/// registered with.
+#[derive(Clone)]
+pub struct Socket {
+    shared: Rc<Shared>,
+}
+
+struct Shared {
+    fd: OwnedFd,
+    /// The sequence number of the last request.
+    seq: Cell<u32>,
+}
+
+impl Socket {
+    /// Asks the kernel for the current state of every interface or address, handed
+    /// out by the subscriber as changes would be, then as a
+    /// [`RouteEvent::DumpDone`].
+    ///
+    /// Only one dump runs at a time: to request both, request the second one from
+    /// the handler, on the `DumpDone` of the first.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error of send(2). A dump requested while another one is running
+    /// fails with `EBUSY`, returned by the handling of the reply.
+    pub fn request_dump(&self, dump: Dump) -> io::Result<()> {
+        let seq = self.shared.seq.get().wrapping_add(1);
+        self.shared.seq.set(seq);
+        let (kind, body_len) = match dump {
+            Dump::Links => (libc::RTM_GETLINK, IFINFOMSG_LEN),
+            Dump::Addrs => (libc::RTM_GETADDR, IFADDRMSG_LEN),
+        };
+        let flags = (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16;
+        // An `ifinfomsg` or `ifaddrmsg` of family `AF_UNSPEC`, for every interface
+        // or address.
+        let mut request = encode_header(HEADER_LEN + body_len, kind, flags, seq);
+        request.resize(HEADER_LEN + body_len, 0);
+
+        loop {
+            // SAFETY: `request` is valid for reads of its length.
+            let n = unsafe {
+                libc::send(
+                    self.shared.fd.as_raw_fd(),
+                    request.as_ptr().cast(),
+                    request.len(),
+                    0,
+                )
+            };
+            if n >= 0 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// The subscriber created by [`Monitor::with_handler`].
+pub struct Subscriber<F> {
+    socket: Socket,
+    groups: Groups,
+    interest: Cell<Interest>,
+    buf: Box<[u8]>,
+    handler: F,
+}
+
+impl<F> Subscriber<F> {
+    /// Returns a handle to send requests on the socket.
+    pub fn socket(&self) -> Socket {
+        self.socket.clone()
+    }
+
+    /// Returns the groups the socket joined.
+    pub fn groups(&self) -> Groups {
+        self.groups
+    }
+
+    /// Receives a datagram into `buf`, and returns its length, beyond that of
+    /// `buf` if it was truncated, or `None` once the socket would block.
+    fn recv(&mut self) -> io::Result<Option<usize>> {
+        loop {
+            // SAFETY: `buf` is valid for writes of its length.
+            let n = unsafe {
+                libc::recv(
+                    self.socket.shared.fd.as_raw_fd(),
+                    self.buf.as_mut_ptr().cast(),
+                    self.buf.len(),
+                    libc::MSG_TRUNC,
+                )
+            };
+            if n >= 0 {
+                return Ok(Some(n as usize));
+            }
+            let err = io::Error::last_os_error();
+            match err.kind() {
+                io::ErrorKind::Interrupted => continue,
+                io::ErrorKind::WouldBlock => return Ok(None),
+                _ => return Err(err),
+            }
+        }
+    }
+}
+
+impl<F> AsFd for Subscriber<F> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.shared.fd.as_fd()
+    }
+}
+
+impl<F> HasInterest for Subscriber<F> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, F, R> Handler<Ep> for Subscriber<F>
+where
+    Ep: EventpOps,
+    F: FnMut(RouteEvent, Pinned<'_, Ep>) -> R,
+    R: HandlerReturn,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    /// Receives until the socket would block, and hands out every message.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error of the handler, or of recv(2) but `ENOBUFS`. A reply
+    /// reporting the failure of a request, e.g. a dump refused with `EBUSY`, is
+    /// returned as the error it carries.
+    fn try_handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        loop {
+            let len = match self.recv() {
+                Ok(Some(len)) => len,
+                Ok(None) => return Ok(()),
+                Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                    (self.handler)(RouteEvent::Overflow, eventp.as_mut()).into_result()?;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let mut result = Ok(());
+            // The tail of a datagram too large for the buffer is lost.
+            let truncated = len > self.buf.len();
+            let mut offset = 0;
+            while let Some((message, msg_len)) = decode(&self.buf[offset..len.min(self.buf.len())])
+            {
+                offset += msg_len;
+                let r = match message.parse() {
+                    Parsed::Event(event) => (self.handler)(event, eventp.as_mut()).into_result(),
+                    Parsed::Error(e) => Err(e),
+                    Parsed::Skip => Ok(()),
+                };
+                result = result.and(r);
+            }
+            if truncated {
+                let r = (self.handler)(RouteEvent::Overflow, eventp.as_mut()).into_result();
+                result = result.and(r);
+            }
+            result?;
+        }
+    }
+}
+
+/// A message, borrowed from the datagram it was received in.
+struct Message<'a> {
+    kind: u16,
+    flags: u16,
+    payload: &'a [u8],
+}
+
+enum Parsed {
+    Event(RouteEvent),
+    /// The failure of a request.
+    Error(io::Error),
+    /// Nothing to hand out, e.g. an acknowledgement.
+    Skip,
+}
+
+/// Decodes the message at the start of `buf`, if it is complete, and returns it
+/// with its length, padding included.
+fn decode(buf: &[u8]) -> Option<(Message<'_>, usize)> {
+    let len = u32::from_ne_bytes(buf.get(0..4)?.try_into().unwrap()) as usize;
+    let payload = buf.get(HEADER_LEN..len.max(HEADER_LEN))?;
+    if len < HEADER_LEN {
+        return None;
+    }
+    let message = Message {
+        kind: u16::from_ne_bytes(buf[4..6].try_into().unwrap()),
+        flags: u16::from_ne_bytes(buf[6..8].try_into().unwrap()),
+        payload,
+    };
+    Some((message, align(len).min(buf.len())))
+}
+
+impl Message<'_> {
+    fn parse(&self) -> Parsed {
+        let event = match self.kind as libc::c_int {
+            libc::NLMSG_NOOP => return Parsed::Skip,
+            libc::NLMSG_DONE => RouteEvent::DumpDone,
+            libc::NLMSG_OVERRUN => RouteEvent::Overflow,
+            libc::NLMSG_ERROR => {
+                let error = self
+                    .payload
+                    .get(0..4)
+                    .map_or(0, |b| i32::from_ne_bytes(b.try_into().unwrap()));
+                return match error {
+                    // An acknowledgement.
+                    0 => Parsed::Skip,
+                    e => Parsed::Error(io::Error::from_raw_os_error(-e)),
+                };
+            }
+            _ => match self.kind {
+                libc::RTM_NEWLINK | libc::RTM_DELLINK => self.parse_link(),
+                libc::RTM_NEWADDR | libc::RTM_DELADDR => self.parse_addr(),
+                _ => None,
+            }
+            .unwrap_or_else(|| RouteEvent::Other(self.to_raw())),
+        };
+        Parsed::Event(event)
+    }
+
+    fn parse_link(&self) -> Option<RouteEvent> {
+        let header = self.payload.get(..IFINFOMSG_LEN)?;
+        let ifindex = u32::from_ne_bytes(header[4..8].try_into().unwrap());
+        let flags = u32::from_ne_bytes(header[8..12].try_into().unwrap());
+        let name = attrs(&self.payload[IFINFOMSG_LEN..])
+            .find(|&(kind, _)| kind == libc::IFLA_IFNAME)
+            .map(|(_, value)| {
+                let name = value.split(|&b| b == 0).next().unwrap_or_default();
+                String::from_utf8_lossy(name).into_owned()
+            });
+        Some(if self.kind == libc::RTM_DELLINK {
+            RouteEvent::LinkRemoved { ifindex, name }
+        } else if flags & libc::IFF_RUNNING as u32 != 0 {
+            RouteEvent::LinkUp { ifindex, name }
+        } else {
+            RouteEvent::LinkDown { ifindex, name }
+        })
+    }
+
+    fn parse_addr(&self) -> Option<RouteEvent> {
+        let header = self.payload.get(..IFADDRMSG_LEN)?;
+        let (family, prefix_len) = (header[0], header[1]);
+        let ifindex = u32::from_ne_bytes(header[4..8].try_into().unwrap());
+        let addr = |kind| {
+            attrs(&self.payload[IFADDRMSG_LEN..])
+                .find(|&(k, _)| k == kind)
+                .and_then(|(_, value)| match family as libc::c_int {
+                    libc::AF_INET => <[u8; 4]>::try_from(value)
+                        .ok()
+                        .map(Ipv4Addr::from)
+                        .map(IpAddr::V4),
+                    libc::AF_INET6 => <[u8; 16]>::try_from(value)
+                        .ok()
+                        .map(Ipv6Addr::from)
+                        .map(IpAddr::V6),
+                    _ => None,
+                })
+        };
+        // `IFA_ADDRESS` is the peer of a point-to-point interface, and `IFA_LOCAL`
+        // only sent for IPv4.
+        let addr = addr(libc::IFA_LOCAL).or_else(|| addr(libc::IFA_ADDRESS))?;
+        Some(if self.kind == libc::RTM_DELADDR {
+            RouteEvent::AddrRemoved {
+                ifindex,
+                addr,
+                prefix_len,
+            }
+        } else {
+            RouteEvent::AddrAdded {
+                ifindex,
+                addr,
+                prefix_len,
+            }
+        })
+    }
+
+    fn to_raw(&self) -> RawMessage {
+        RawMessage {
+            kind: self.kind,
+            flags: self.flags,
+            payload: self.payload.to_vec(),
+        }
+    }
+}
+
+/// Iterates over the `struct rtattr` in `buf`, as their type and value, up to the
+/// first incomplete one.
+fn attrs(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        let len = u16::from_ne_bytes(buf.get(0..2)?.try_into().unwrap()) as usize;
+        let kind = u16::from_ne_bytes(buf.get(2..4)?.try_into().unwrap());
+        if len < 4 {
+            return None;
+        }
+        let value = buf.get(4..len)?;
+        buf = buf.get(align(len)..).unwrap_or_default();
+        Some((kind, value))
+    })
+}
+
+/// Rounds `len` up to the 4 bytes alignment of netlink messages and attributes.
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn encode_header(len: usize, kind: u16, flags: u16, seq: u32) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(len);
+    buf.extend_from_slice(&(len as u32).to_ne_bytes());
+    buf.extend_from_slice(&kind.to_ne_bytes());
+    buf.extend_from_slice(&flags.to_ne_bytes());
+    buf.extend_from_slice(&seq.to_ne_bytes());
+    // The kernel.
+    buf.extend_from_slice(&0u32.to_ne_bytes());
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{Eventp, Subscriber as _};
+
+    /// An `RTM_NEWLINK` for `lo`, up and running, as received on x86_64, trimmed
+    /// to its `IFLA_IFNAME` and `IFLA_MTU`.
+    const NEWLINK_LO: &[u8] = &[
+        0x30, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // nlmsghdr: len 48, RTM_NEWLINK
+        0x00, 0x00, 0x04, 0x03, 0x01, 0x00, 0x00, 0x00, 0x49, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x00, // ifinfomsg: ARPHRD_LOOPBACK, index 1, UP|LOOPBACK|RUNNING|LOWER_UP
+        0x07, 0x00, 0x03, 0x00, b'l', b'o', 0x00, 0x00, // IFLA_IFNAME "lo", padded
+        0x08, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, // IFLA_MTU 65536
+    ];
+
+    /// An `RTM_NEWADDR` for `127.0.0.1/8` on `lo`, trimmed to its `IFA_ADDRESS`,
+    /// `IFA_LOCAL` and `IFA_LABEL`.
+    const NEWADDR_LO: &[u8] = &[
+        0x30, 0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // nlmsghdr: len 48, RTM_NEWADDR
+        0x02, 0x08, 0x80, 0xfe, 0x01, 0x00, 0x00, 0x00, // ifaddrmsg: AF_INET, /8, index 1
+        0x08, 0x00, 0x01, 0x00, 127, 0, 0, 1, // IFA_ADDRESS
+        0x08, 0x00, 0x02, 0x00, 127, 0, 0, 1, // IFA_LOCAL
+        0x07, 0x00, 0x03, 0x00, b'l', b'o', 0x00, 0x00, // IFA_LABEL "lo", padded
+    ];
+
+    /// The `NLMSG_DONE` ending a dump, with its `int` of 0.
+    const DONE: &[u8] = &[
+        0x14, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn parse(buf: &[u8]) -> Vec<RouteEvent> {
+        let mut events = Vec::new();
+        let mut offset = 0;
+        while let Some((message, len)) = decode(&buf[offset..]) {
+            offset += len;
+            if let Parsed::Event(event) = message.parse() {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn decodes_captured_messages() {
+        assert_eq!(
+            parse(NEWLINK_LO),
+            [RouteEvent::LinkUp {
+                ifindex: 1,
+                name: Some("lo".into())
+            }]
+        );
+        assert_eq!(
+            parse(NEWADDR_LO),
+            [RouteEvent::AddrAdded {
+                ifindex: 1,
+                addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                prefix_len: 8,
+            }]
+        );
+    }
+
+    #[test]
+    fn decodes_every_message_of_a_multipart_datagram() {
+        let mut link_down = NEWLINK_LO.to_vec();
+        // Flags of `lo` once down, and `NLM_F_MULTI`.
+        link_down[24] = 0x08;
+        link_down[6] = 0x02;
+        let mut deleted = NEWADDR_LO.to_vec();
+        deleted[4] = libc::RTM_DELADDR as u8;
+        let mut route = NEWADDR_LO.to_vec();
+        route[4] = libc::RTM_NEWROUTE as u8;
+
+        let datagram = [&link_down[..], &deleted, &route, DONE].concat();
+        let events = parse(&datagram);
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            RouteEvent::LinkDown {
+                ifindex: 1,
+                name: Some("lo".into())
+            }
+        );
+        assert!(matches!(
+            events[1],
+            RouteEvent::AddrRemoved { prefix_len: 8, .. }
+        ));
+        assert_eq!(
+            events[2],
+            RouteEvent::Other(RawMessage {
+                kind: libc::RTM_NEWROUTE,
+                flags: 0,
+                payload: NEWADDR_LO[HEADER_LEN..].to_vec(),
+            })
+        );
+        assert_eq!(events[3], RouteEvent::DumpDone);
+    }
+
+    #[test]
+    fn incomplete_messages_are_not_decoded() {
+        for cut in 0..NEWLINK_LO.len() {
+            assert!(parse(&NEWLINK_LO[..cut]).is_empty(), "cut at {cut}");
+        }
+        // Too short for its `ifinfomsg`.
+        let mut short = NEWLINK_LO[..HEADER_LEN + 8].to_vec();
+        short[0] = short.len() as u8;
+        assert!(matches!(parse(&short)[..], [RouteEvent::Other(_)]));
+    }
+
+    #[test]
+    fn error_replies_are_errors_and_acks_skipped() {
+        let mut error = encode_header(36, libc::NLMSG_ERROR as u16, 0, 1);
+        error.extend_from_slice(&(-libc::EBUSY).to_ne_bytes());
+        error.extend_from_slice(&[0; 16]);
+        let (message, _) = decode(&error).unwrap();
+        let Parsed::Error(e) = message.parse() else {
+            panic!("not an error");
+        };
+        assert_eq!(e.raw_os_error(), Some(libc::EBUSY));
+
+        error[HEADER_LEN..HEADER_LEN + 4].copy_from_slice(&0i32.to_ne_bytes());
+        let (message, _) = decode(&error).unwrap();
+        assert!(matches!(message.parse(), Parsed::Skip));
+    }
+
+    #[test]
+    fn dump_of_the_links_ends_with_dump_done() {
+        let mut eventp = Eventp::default();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let e = events.clone();
+        let monitor = monitor(Groups::NONE).unwrap();
+        let socket = monitor.socket();
+        monitor
+            .with_handler(move |event: RouteEvent, _: Pinned<'_, Eventp>| {
+                e.borrow_mut().push(event)
+            })
+            .register_into(&mut eventp)
+            .unwrap();
+
+        socket.request_dump(Dump::Links).unwrap();
+        let timeout = EpollTimeout::try_from(1000).unwrap();
+        while events.borrow().last() != Some(&RouteEvent::DumpDone) {
+            eventp.run_once_with_timeout(timeout).unwrap();
+        }
+        // Every network namespace has a loopback interface.
+        assert!(events.borrow().iter().any(|event| matches!(
+            event,
+            RouteEvent::LinkUp { name: Some(name), .. } | RouteEvent::LinkDown { name: Some(name), .. }
+                if name == "lo"
+        )));
+    }
+}
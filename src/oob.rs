@@ -0,0 +1,212 @@
+//! TCP urgent data, its out-of-band byte reported as `EPOLLPRI`.
+//!
+//! A peer sending with `MSG_OOB` marks the last byte sent as urgent. Unless
+//! `SO_OOBINLINE` is set, that byte is taken out of the stream, and the socket is
+//! reported with [`EPOLLPRI`](crate::Event::is_priority) until [`recv_oob`] reads it,
+//! for interests with [`priority`](crate::Interest::priority). [`at_mark`] then tells
+//! whether the in-band data read so far reached the place the byte was sent at. Only
+//! one urgent byte is kept: a later one replaces it.
+//!
+//! # Examples
+//!
+//! Handling the urgent byte before the in-band data of the same event:
+//!
+//! ```rust
+//! # use std::io::{self, Read};
+//! # use std::net::TcpStream;
+//! use std::os::fd::AsFd;
+//!
+//! use eventp::{oob, Event};
+//!
+//! fn on_data(stream: &mut TcpStream, event: Event) -> io::Result<()> {
+//!     if event.is_priority() {
+//!         let mut urgent = [0];
+//!         oob::recv_oob(stream.as_fd(), &mut urgent)?;
+//!         println!("urgent byte {:?}", urgent[0] as char);
+//!     }
+//!     if event.is_readable() {
+//!         let mut buf = [0; 512];
+//!         let _ = stream.read(&mut buf)?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! See [examples/urgent-data.rs](https://github.com/FuuuOverclocking/eventp/blob/main/examples/urgent-data.rs)
+//! for a server of a protocol interrupting with urgent bytes.
+
+use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd};
+
+/// The `ioctl` of `<asm-generic/sockios.h>` telling whether a socket is at the mark.
+#[cfg(not(any(target_arch = "mips", target_arch = "mips64")))]
+const SIOCATMARK: libc::Ioctl = 0x8905;
+/// `_IOR('s', 7, int)` on mips.
+#[cfg(any(target_arch = "mips", target_arch = "mips64"))]
+const SIOCATMARK: libc::Ioctl = 0x4004_7307;
+
+/// Reads the urgent byte of the socket `fd` into `buf`, and returns the number of
+/// bytes read, 1 unless `buf` is empty.
+///
+/// # Errors
+///
+/// Forwards any error of recv(2) with `MSG_OOB` but `EINTR`, which is retried:
+///
+/// - `EINVAL` if there is no urgent byte, e.g. it was already read, or
+///   `SO_OOBINLINE` is set.
+/// - [`io::ErrorKind::WouldBlock`] if the peer announced an urgent byte that has not
+///   arrived yet.
+pub fn recv_oob(fd: BorrowedFd<'_>, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        // SAFETY: `buf` is valid for writes of its length.
+        let n = unsafe {
+            libc::recv(
+                fd.as_raw_fd(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+                libc::MSG_OOB,
+            )
+        };
+        if n >= 0 {
+            return Ok(n as usize);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Sends `buf` on the socket `fd`, its last byte as urgent, and returns the number
+/// of bytes sent.
+///
+/// # Errors
+///
+/// Forwards any error of send(2) with `MSG_OOB` but `EINTR`, which is retried.
+pub fn send_oob(fd: BorrowedFd<'_>, buf: &[u8]) -> io::Result<usize> {
+    loop {
+        // SAFETY: `buf` is valid for reads of its length.
+        let n = unsafe {
+            libc::send(
+                fd.as_raw_fd(),
+                buf.as_ptr().cast(),
+                buf.len(),
+                libc::MSG_OOB | libc::MSG_NOSIGNAL,
+            )
+        };
+        if n >= 0 {
+            return Ok(n as usize);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Returns whether the in-band data of the socket `fd` was read up to the urgent
+/// byte, so that the next read returns what was sent after it.
+///
+/// # Errors
+///
+/// Forwards any error of the `SIOCATMARK` ioctl(2), e.g. `ENOTTY` for an fd that is
+/// not a socket.
+pub fn at_mark(fd: BorrowedFd<'_>) -> io::Result<bool> {
+    let mut at_mark: libc::c_int = 0;
+    // SAFETY: `SIOCATMARK` writes an `int` into `at_mark`.
+    if unsafe { libc::ioctl(fd.as_raw_fd(), SIOCATMARK, &mut at_mark) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(at_mark != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::os::fd::AsFd;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::tri_subscriber::WithHandler;
+    use crate::{interest, Event, Eventp, Subscriber};
+
+    /// Returns both ends of a loopback TCP connection.
+    fn connection() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (client, server)
+    }
+
+    #[test]
+    fn handler_reads_the_urgent_byte_on_epollpri() {
+        let (mut client, server) = connection();
+        server.set_nonblocking(true).unwrap();
+        let mut eventp = Eventp::default();
+        let seen = Rc::new(Cell::new(Vec::new()));
+        let s = seen.clone();
+        interest()
+            .read()
+            .priority()
+            .with_fd(server)
+            .with_handler(move |stream: &mut TcpStream, event: Event| {
+                let mut all = s.take();
+                if event.is_priority() {
+                    let mut urgent = [0];
+                    assert_eq!(recv_oob(stream.as_fd(), &mut urgent).unwrap(), 1);
+                    all.push(format!("urgent {}", urgent[0] as char));
+                }
+                if event.is_readable() {
+                    let mut buf = [0; 16];
+                    if let Ok(n) = stream.read(&mut buf) {
+                        all.push(String::from_utf8_lossy(&buf[..n]).into_owned());
+                    }
+                }
+                s.set(all);
+            })
+            .register_into(&mut eventp)
+            .unwrap();
+
+        client.write_all(b"abc").unwrap();
+        assert_eq!(send_oob(client.as_fd(), b"!").unwrap(), 1);
+        // Both segments are delivered by loopback before the wait, and reported at
+        // once, as `EPOLLIN | EPOLLPRI`.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let timeout = EpollTimeout::from(500u16);
+        eventp.run_once_with_timeout(timeout).unwrap();
+        assert_eq!(seen.take(), ["urgent !", "abc"]);
+
+        // Read, so no longer reported.
+        client.write_all(b"d").unwrap();
+        eventp.run_once_with_timeout(timeout).unwrap();
+        assert_eq!(seen.take(), ["d"]);
+    }
+
+    #[test]
+    fn at_mark_once_the_data_before_the_urgent_byte_is_read() {
+        let (mut client, mut server) = connection();
+        client.write_all(b"ab").unwrap();
+        send_oob(client.as_fd(), b"c!").unwrap();
+        client.write_all(b"d").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        let mut urgent = [0];
+        recv_oob(server.as_fd(), &mut urgent).unwrap();
+        assert_eq!(urgent, *b"!");
+        let err = recv_oob(server.as_fd(), &mut urgent).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+
+        // A read stops at the mark.
+        assert!(!at_mark(server.as_fd()).unwrap());
+        let mut buf = [0; 8];
+        let n = server.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"abc");
+        assert!(at_mark(server.as_fd()).unwrap());
+        let n = server.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"d");
+        assert!(!at_mark(server.as_fd()).unwrap());
+    }
+}
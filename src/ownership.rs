@@ -0,0 +1,164 @@
+//! Detection of one fd registered with several `Eventp`s, behind the
+//! `debug-ownership` feature.
+//!
+//! Every loop claims the fds it registers in a process-global table, keyed by fd
+//! number and by the device and inode of the file, so a closed and reused fd
+//! number is not mistaken for the old one. Claims are released on delete, and all
+//! at once when the loop is dropped. Duplicates of an fd, as used by
+//! [`exclusive`](crate::exclusive), are different fd numbers and do not conflict.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::Error;
+
+/// `(dev, ino, fd)` → the id of the claiming loop.
+static CLAIMS: Mutex<BTreeMap<(u64, u64, RawFd), u64>> = Mutex::new(BTreeMap::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn claims() -> MutexGuard<'static, BTreeMap<(u64, u64, RawFd), u64>> {
+    // The table stays consistent even if a holder panicked.
+    CLAIMS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The identity of one loop in the table. Releases its claims when dropped.
+#[derive(Debug)]
+pub(crate) struct Owner {
+    id: u64,
+}
+
+impl Owner {
+    pub(crate) fn new() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Claims `fd` for this loop.
+    ///
+    /// Fails with [`Error::RegisteredElsewhere`] if another loop has claimed it.
+    pub(crate) fn claim(&self, fd: BorrowedFd<'_>) -> io::Result<()> {
+        let key = key(fd)?;
+        let mut claims = claims();
+        match claims.get(&key) {
+            Some(&owner) if owner != self.id => {
+                Err(Error::RegisteredElsewhere { fd: key.2 }.into())
+            }
+            _ => {
+                claims.insert(key, self.id);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the id of this loop in the table.
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Releases the claim of this loop on `fd`, if any.
+    pub(crate) fn release(&self, fd: RawFd) {
+        claims().retain(|&(_, _, claimed), &mut owner| claimed != fd || owner != self.id);
+    }
+}
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        release_all(self.id);
+    }
+}
+
+/// Releases every claim of the loop `id`, such as one forgotten in a forked child.
+pub(crate) fn release_all(id: u64) {
+    claims().retain(|_, &mut owner| owner != id);
+}
+
+fn key(fd: BorrowedFd<'_>) -> io::Result<(u64, u64, RawFd)> {
+    let mut stat = MaybeUninit::<libc::stat>::uninit();
+    // SAFETY: `stat` is valid for writes of a `libc::stat`, and initialized by a
+    // successful call.
+    let stat = unsafe {
+        if libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    Ok((stat.st_dev as u64, stat.st_ino as u64, fd.as_raw_fd()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use crate::tri_subscriber::WithHandler;
+    use crate::{interest, Error, Eventp, EventpOps, Subscriber};
+
+    fn new_eventfd() -> EventFd {
+        EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap()
+    }
+
+    /// Registers a subscriber borrowing `fd`, so the test keeps it.
+    fn register(fd: &impl AsFd, eventp: &mut Eventp) -> std::io::Result<()> {
+        // SAFETY: `fd` outlives every loop of the tests.
+        let fd = unsafe { BorrowedFd::borrow_raw(fd.as_fd().as_raw_fd()) };
+        interest()
+            .read()
+            .with_fd(fd)
+            .with_handler(|| {})
+            .register_into(eventp)
+    }
+
+    #[test]
+    fn second_loop_is_refused() {
+        let efd = new_eventfd();
+        let (mut first, mut second) = (Eventp::default(), Eventp::default());
+
+        register(&efd, &mut first).unwrap();
+        let err = register(&efd, &mut second).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::RegisteredElsewhere {
+                fd: efd.as_fd().as_raw_fd()
+            })
+        );
+        assert!(!second.contains(efd.as_fd().as_raw_fd()));
+    }
+
+    #[test]
+    fn delete_releases_the_claim() {
+        let efd = new_eventfd();
+        let (mut first, mut second) = (Eventp::default(), Eventp::default());
+
+        register(&efd, &mut first).unwrap();
+        first.delete(efd.as_fd().as_raw_fd()).unwrap();
+        register(&efd, &mut second).unwrap();
+    }
+
+    #[test]
+    fn dropping_the_loop_releases_its_claims() {
+        let efd = new_eventfd();
+        let mut first = Eventp::default();
+        register(&efd, &mut first).unwrap();
+        drop(first);
+
+        register(&efd, &mut Eventp::default()).unwrap();
+    }
+
+    #[test]
+    fn duplicated_fds_do_not_conflict() {
+        let efd = new_eventfd();
+        let dup = efd.as_fd().try_clone_to_owned().unwrap();
+        let (mut first, mut second) = (Eventp::default(), Eventp::default());
+
+        register(&efd, &mut first).unwrap();
+        register(&dup, &mut second).unwrap();
+    }
+}
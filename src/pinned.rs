@@ -1,9 +1,14 @@
 use std::io;
 use std::os::fd::RawFd;
 use std::pin::Pin;
+use std::time::Duration;
 
+#[cfg(target_os = "linux")]
+use crate::multi_fd::MultiFdSubscriber;
 use crate::thin::ThinBoxSubscriber;
-use crate::{EventpOps, EventpOpsAdd, Interest};
+use crate::{AddError, BatchInfo, EventDelta, EventpOps, EventpOpsAdd, Interest};
+#[cfg(target_os = "linux")]
+use crate::{Error, Eventp, Stats, Subscriber};
 
 /// A deliberately narrowed view of `Pin<&mut Ep>` exposing only `add`,
 /// `modify`, and `delete`.
@@ -29,6 +34,12 @@ impl<'a, Ep> Pinned<'a, Ep> {
     pub fn as_mut(&mut self) -> Pinned<'_, Ep> {
         Pinned(self.0.as_mut())
     }
+
+    /// Returns a [`ViewOps`] of the loop, e.g. to call a read-only handler from
+    /// another handler.
+    pub fn view(&self) -> ViewOps<'_, Ep> {
+        ViewOps(self.0.as_ref().get_ref())
+    }
 }
 
 impl<'a, Ep: EventpOps> EventpOpsAdd<Ep> for Pinned<'a, Ep> {
@@ -36,6 +47,17 @@ impl<'a, Ep: EventpOps> EventpOpsAdd<Ep> for Pinned<'a, Ep> {
     fn add(&mut self, subscriber: ThinBoxSubscriber<Ep>) -> io::Result<()> {
         unsafe { self.0.as_mut().get_unchecked_mut().add(subscriber) }
     }
+
+    fn try_add(&mut self, subscriber: ThinBoxSubscriber<Ep>) -> Result<(), AddError<Ep>> {
+        unsafe { self.0.as_mut().get_unchecked_mut().try_add(subscriber) }
+    }
+
+    fn add_all<I>(&mut self, subscribers: I) -> Vec<io::Result<RawFd>>
+    where
+        I: IntoIterator<Item = ThinBoxSubscriber<Ep>>,
+    {
+        unsafe { self.0.as_mut().get_unchecked_mut().add_all(subscribers) }
+    }
 }
 
 impl<'a, Ep> Pinned<'a, Ep>
@@ -47,10 +69,291 @@ where
         unsafe { self.0.as_mut().get_unchecked_mut().modify(fd, interest) }
     }
 
+    /// See [`EventpOps::try_modify`].
+    pub fn try_modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<bool> {
+        unsafe { self.0.as_mut().get_unchecked_mut().try_modify(fd, interest) }
+    }
+
     #[doc = include_str!("../docs/eventp-ops.delete.md")]
     pub fn delete(&mut self, fd: RawFd) -> io::Result<()> {
         unsafe { self.0.as_mut().get_unchecked_mut().delete(fd) }
     }
+
+    /// See [`EventpOps::defer`].
+    pub fn defer(&mut self, f: impl FnOnce(Pinned<'_, Ep>) + 'static) {
+        unsafe { self.0.as_mut().get_unchecked_mut().defer(Box::new(f)) }
+    }
+
+    /// See [`EventpOps::delete_all`].
+    pub fn delete_all(&mut self, fds: &[RawFd]) -> Vec<io::Result<()>> {
+        unsafe { self.0.as_mut().get_unchecked_mut().delete_all(fds) }
+    }
+
+    /// See [`EventpOps::current_interest`].
+    pub fn current_interest(&self) -> Option<Interest> {
+        self.0.current_interest()
+    }
+
+    /// See [`EventpOps::current_delta`].
+    pub fn current_delta(&self) -> Option<EventDelta> {
+        self.0.current_delta()
+    }
+
+    /// See [`EventpOps::since_last_event`].
+    pub fn since_last_event(&self) -> Option<Duration> {
+        self.0.since_last_event()
+    }
+
+    /// See [`EventpOps::batch_info`].
+    pub fn batch_info(&self) -> Option<BatchInfo> {
+        self.0.batch_info()
+    }
+
+    /// See [`EventpOps::user_data_of`].
+    pub fn user_data_of(&self, fd: RawFd) -> Option<u64> {
+        self.0.user_data_of(fd)
+    }
+
+    /// See [`EventpOps::set_user_data`].
+    pub fn set_user_data(&mut self, fd: RawFd, data: u64) -> io::Result<()> {
+        unsafe { self.0.as_mut().get_unchecked_mut().set_user_data(fd, data) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<'a> Pinned<'a, Eventp> {
+    /// See [`Eventp::is_armed`].
+    pub fn is_armed(&self, raw_fd: &RawFd) -> Option<bool> {
+        self.0.is_armed(raw_fd)
+    }
+
+    /// See [`Eventp::add_group`].
+    pub fn add_group<G: MultiFdSubscriber<Eventp>>(&mut self, group: G) -> io::Result<()> {
+        unsafe { self.0.as_mut().get_unchecked_mut().add_group(group) }
+    }
+
+    /// See [`Eventp::delete_group`].
+    pub fn delete_group(&mut self, fd: RawFd) -> io::Result<()> {
+        unsafe { self.0.as_mut().get_unchecked_mut().delete_group(fd) }
+    }
+
+    /// See [`Eventp::stop`].
+    pub fn stop(&mut self) {
+        unsafe { self.0.as_mut().get_unchecked_mut().stop() }
+    }
+
+    /// See [`Eventp::unquarantine`].
+    pub fn unquarantine(&mut self, fd: RawFd) -> io::Result<()> {
+        unsafe { self.0.as_mut().get_unchecked_mut().unquarantine(fd) }
+    }
+
+    /// See [`Eventp::capacity_remaining`].
+    pub fn capacity_remaining(&self) -> Option<usize> {
+        self.0.capacity_remaining()
+    }
+
+    /// See [`Eventp::subscriber_bytes`].
+    pub fn subscriber_bytes(&self) -> usize {
+        self.0.subscriber_bytes()
+    }
+
+    /// See [`Eventp::set_idle_callback`]. Takes effect at the end of the batch.
+    pub fn set_idle_callback(&mut self, callback: impl FnMut(Pinned<'_, Eventp>) + 'static) {
+        unsafe {
+            self.0
+                .as_mut()
+                .get_unchecked_mut()
+                .set_idle_callback(callback)
+        }
+    }
+
+    /// See [`Eventp::clear_idle_callback`]. Takes effect at the end of the batch.
+    pub fn clear_idle_callback(&mut self) {
+        unsafe { self.0.as_mut().get_unchecked_mut().clear_idle_callback() }
+    }
+
+    /// Lends `f` the subscriber registered with `fd`, to reach the state of another
+    /// subscriber from a handler, e.g. with [`downcast_mut`].
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// # use std::os::fd::RawFd;
+    /// use eventp::{Eventp, Pinned};
+    ///
+    /// struct Connection {
+    ///     # fd: std::os::fd::OwnedFd,
+    ///     // ...
+    /// }
+    /// # impl std::os::fd::AsFd for Connection {
+    /// #     fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> { self.fd.as_fd() }
+    /// # }
+    /// # impl eventp::subscriber::Handler<Eventp> for Connection {
+    /// #     fn handle(&mut self, _: eventp::Event, _: Pinned<'_, Eventp>) {}
+    /// # }
+    ///
+    /// impl Connection {
+    ///     fn flush(&mut self) { /* ... */ }
+    /// }
+    ///
+    /// fn on_control(data_fd: RawFd, mut eventp: Pinned<'_, Eventp>) -> io::Result<()> {
+    ///     eventp.with_subscriber_mut(data_fd, |subscriber| {
+    ///         if let Some(connection) = subscriber.downcast_mut::<Connection>() {
+    ///             connection.flush();
+    ///         }
+    ///     })
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::CurrentlyHandled`], as the payload of an
+    ///   [`io::ErrorKind::InvalidInput`] error, if `fd` is the fd of the running
+    ///   handler, which already holds its subscriber mutably.
+    /// - [`io::ErrorKind::NotFound`] if `fd` is not registered, or was deleted
+    ///   during this dispatch.
+    ///
+    /// [`downcast_mut`]: crate::Subscriber#method.downcast_mut
+    pub fn with_subscriber_mut<R>(
+        &mut self,
+        fd: RawFd,
+        f: impl FnOnce(&mut dyn Subscriber<Eventp>) -> R,
+    ) -> io::Result<R> {
+        // SAFETY: Nothing is moved out of the loop.
+        let eventp = unsafe { self.0.as_mut().get_unchecked_mut() };
+        if eventp.handled_fd() == Some(fd) {
+            return Err(Error::CurrentlyHandled { fd }.into());
+        }
+        let subscriber = eventp
+            .get_mut(&fd)
+            .ok_or(io::Error::new(io::ErrorKind::NotFound, "fd not registered"))?;
+        Ok(f(subscriber))
+    }
+}
+
+/// A read-only view of the event loop, taken by handlers in place of
+/// [`Pinned`] to only observe it, e.g. for metrics.
+///
+/// Nothing can be added, modified or deleted through it, so a handler taking it
+/// is known not to change any registration, its own included:
+///
+/// ```rust
+/// # use std::io;
+/// use eventp::{tri_subscriber::WithHandler, Eventp, Subscriber, ViewOps};
+/// use nix::sys::eventfd::{EfdFlags, EventFd};
+///
+/// fn report(efd: &mut EventFd, eventp: ViewOps<'_, Eventp>) -> io::Result<()> {
+///     efd.read()?;
+///     println!("{} fds registered", eventp.len());
+///     Ok(())
+/// }
+///
+/// # fn main() -> io::Result<()> {
+/// let mut eventp = Eventp::default();
+/// eventp::interest()
+///     .read()
+///     .with_fd(EventFd::from_flags(EfdFlags::EFD_NONBLOCK)?)
+///     .with_handler(report)
+///     .register_into(&mut eventp)?;
+/// # Ok(()) }
+/// ```
+///
+/// ```compile_fail
+/// # use std::os::fd::RawFd;
+/// use eventp::{Eventp, ViewOps};
+///
+/// fn sneaky(fd: RawFd, mut eventp: ViewOps<'_, Eventp>) {
+///     eventp.delete(fd);
+/// }
+/// ```
+///
+/// Handlers taking it are called directly in tests with [`ViewOps::new`], or
+/// [`Pinned::view`] of a [`pinned!`](crate::pinned) [`MockEventp`](crate::MockEventp).
+pub struct ViewOps<'a, Ep>(&'a Ep);
+
+impl<'a, Ep> ViewOps<'a, Ep> {
+    /// Views `eventp`.
+    pub fn new(eventp: &'a Ep) -> Self {
+        Self(eventp)
+    }
+}
+
+impl<Ep> Clone for ViewOps<'_, Ep> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Ep> Copy for ViewOps<'_, Ep> {}
+
+impl<'a, Ep> From<Pinned<'a, Ep>> for ViewOps<'a, Ep> {
+    fn from(pinned: Pinned<'a, Ep>) -> Self {
+        Self(pinned.0.into_ref().get_ref())
+    }
+}
+
+impl<Ep: EventpOps> ViewOps<'_, Ep> {
+    /// See [`EventpOps::current_interest`].
+    pub fn current_interest(&self) -> Option<Interest> {
+        self.0.current_interest()
+    }
+
+    /// See [`EventpOps::current_delta`].
+    pub fn current_delta(&self) -> Option<EventDelta> {
+        self.0.current_delta()
+    }
+
+    /// See [`EventpOps::since_last_event`].
+    pub fn since_last_event(&self) -> Option<Duration> {
+        self.0.since_last_event()
+    }
+
+    /// See [`EventpOps::batch_info`].
+    pub fn batch_info(&self) -> Option<BatchInfo> {
+        self.0.batch_info()
+    }
+
+    /// See [`EventpOps::user_data_of`].
+    pub fn user_data_of(&self, fd: RawFd) -> Option<u64> {
+        self.0.user_data_of(fd)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ViewOps<'_, Eventp> {
+    /// See [`Eventp::len`].
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// See [`Eventp::is_empty`].
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// See [`Eventp::interest`].
+    pub fn interest(&self, raw_fd: &RawFd) -> Option<Interest> {
+        self.0.interest(raw_fd)
+    }
+
+    /// See [`Eventp::is_armed`].
+    pub fn is_armed(&self, raw_fd: &RawFd) -> Option<bool> {
+        self.0.is_armed(raw_fd)
+    }
+
+    /// See [`Eventp::capacity_remaining`].
+    pub fn capacity_remaining(&self) -> Option<usize> {
+        self.0.capacity_remaining()
+    }
+
+    /// See [`Eventp::subscriber_bytes`].
+    pub fn subscriber_bytes(&self) -> usize {
+        self.0.subscriber_bytes()
+    }
+
+    /// See [`Eventp::stats`].
+    pub fn stats(&self) -> Stats {
+        self.0.stats()
+    }
 }
 
 /// This macro is primarily used in tests with [MockEventp](crate::MockEventp) to
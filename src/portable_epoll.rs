@@ -0,0 +1,92 @@
+//! The epoll types of the portable core, on other platforms than Linux.
+//!
+//! They mirror those of `nix::sys::epoll`, with the same names, values and methods,
+//! so [`Interest`](crate::Interest) and [`Event`](crate::Event) are the same types
+//! everywhere. Nothing here polls: they exist to build and test handlers.
+
+use std::os::raw::c_int;
+
+bitflags::bitflags! {
+    /// The flags of an epoll interest or event, with their values on Linux.
+    #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    #[repr(transparent)]
+    pub struct EpollFlags: c_int {
+        /// The fd is readable.
+        const EPOLLIN = 0x001;
+        /// An exceptional condition, such as out-of-band data.
+        const EPOLLPRI = 0x002;
+        /// The fd is writable.
+        const EPOLLOUT = 0x004;
+        /// Normal data is readable.
+        const EPOLLRDNORM = 0x040;
+        /// Priority data is readable.
+        const EPOLLRDBAND = 0x080;
+        /// Normal data is writable.
+        const EPOLLWRNORM = 0x100;
+        /// Priority data is writable.
+        const EPOLLWRBAND = 0x200;
+        /// Unused.
+        const EPOLLMSG = 0x400;
+        /// An error condition, reported whatever the interest.
+        const EPOLLERR = 0x008;
+        /// A hang up, reported whatever the interest.
+        const EPOLLHUP = 0x010;
+        /// The peer shut down its writing half.
+        const EPOLLRDHUP = 0x2000;
+        /// Wakes only some of the epolls waiting for the fd.
+        const EPOLLEXCLUSIVE = 1 << 28;
+        /// Keeps the system from suspending while the event is pending.
+        const EPOLLWAKEUP = 1 << 29;
+        /// Disables the fd after its next event.
+        const EPOLLONESHOT = 1 << 30;
+        /// Edge-triggered.
+        const EPOLLET = 1 << 31;
+    }
+}
+
+/// An event as returned by `epoll_wait`: its flags, and the data of its
+/// registration.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+impl EpollEvent {
+    /// Creates an event with `events` and `data`.
+    pub const fn new(events: EpollFlags, data: u64) -> Self {
+        Self {
+            events: events.bits() as u32,
+            data,
+        }
+    }
+
+    /// Creates an event without flags, and with a data of 0.
+    pub fn empty() -> Self {
+        Self::new(EpollFlags::empty(), 0)
+    }
+
+    /// Returns the flags of the event.
+    pub fn events(&self) -> EpollFlags {
+        EpollFlags::from_bits_retain(self.events as c_int)
+    }
+
+    /// Returns the data of the event.
+    pub const fn data(&self) -> u64 {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_keep_their_flags_and_data() {
+        let flags = EpollFlags::EPOLLIN | EpollFlags::EPOLLET;
+        let event = EpollEvent::new(flags, 42);
+        assert_eq!(event.events(), flags);
+        assert_eq!(event.data(), 42);
+        assert_eq!(EpollEvent::empty().events(), EpollFlags::empty());
+    }
+}
@@ -0,0 +1,318 @@
+//! A subscriber waiting for a child process to exit, through its pidfd.
+//!
+//! [`watch_child()`] opens a pidfd for the child, see pidfd_open(2), which becomes
+//! readable once the child exits. The subscriber then reaps the child, calls the
+//! handler once with its exit status, and deletes itself from the loop. No
+//! `SIGCHLD` handler is involved, and other children are left alone.
+//!
+//! A child that exited before being watched is still a zombie, since nothing
+//! reaped it: its pidfd is readable at once, and the handler is called by the next
+//! dispatch.
+//!
+//! The child is reaped by the subscriber, so it must not be waited for elsewhere,
+//! e.g. with [`Child::wait`], which fails afterwards.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::process::{Command, ExitStatus};
+//!
+//! use eventp::{process, Eventp, Pinned, Subscriber};
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut eventp = Eventp::default();
+//! let child = Command::new("true").spawn()?;
+//!
+//! process::watch_child(&child)?
+//!     .with_handler(|status: ExitStatus, _eventp: Pinned<'_, Eventp>| {
+//!         println!("child exited with {status}");
+//!     })
+//!     .register_into(&mut eventp)?;
+//! eventp.run_once()?;
+//! # Ok(()) }
+//! ```
+//!
+//! See [examples/supervisor.rs](https://github.com/FuuuOverclocking/eventp/blob/main/examples/supervisor.rs)
+//! for a worker respawned whenever it crashes.
+
+use std::cell::Cell;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ExitStatus};
+
+pub use nix::unistd::Pid;
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::tri_subscriber::HandlerReturn;
+use crate::{interest, Event, EventpOps, Interest, Pinned};
+
+/// A child process [`watch_child`] can take: a [`Child`], or the [`Pid`] of one.
+pub trait ChildPid {
+    /// Returns the pid of the child.
+    fn pid(&self) -> Pid;
+}
+
+impl ChildPid for &Child {
+    fn pid(&self) -> Pid {
+        Pid::from_raw(self.id() as libc::pid_t)
+    }
+}
+
+impl ChildPid for Pid {
+    fn pid(&self) -> Pid {
+        *self
+    }
+}
+
+/// Opens a pidfd for `child`, to be completed with a handler by
+/// [`ChildWatch::with_handler`].
+///
+/// For more information, see the [mod-level documentation](self).
+///
+/// # Errors
+///
+/// Forwards any error from `pidfd_open`, e.g. `ESRCH` for a child already reaped,
+/// or `ENOSYS` before Linux 5.3.
+pub fn watch_child<C: ChildPid>(child: C) -> io::Result<ChildWatch> {
+    let pid = child.pid();
+    // SAFETY: `pidfd_open` takes no pointer. The pidfd is close-on-exec.
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ChildWatch {
+        pid,
+        // SAFETY: Just opened, and owned by nothing else.
+        pidfd: unsafe { OwnedFd::from_raw_fd(fd as i32) },
+    })
+}
+
+/// A watched child process, waiting for its handler.
+pub struct ChildWatch {
+    pid: Pid,
+    pidfd: OwnedFd,
+}
+
+impl ChildWatch {
+    /// Creates the subscriber calling `handler` with the exit status of the child.
+    ///
+    /// The handler returns either `()` or `io::Result<()>`. It is called once, after
+    /// the subscriber is deleted from the loop, so it may watch a new child right
+    /// away.
+    pub fn with_handler<F>(self, handler: F) -> Subscriber<F> {
+        Subscriber {
+            pid: self.pid,
+            pidfd: self.pidfd,
+            interest: Cell::new(interest().read()),
+            handler: Some(handler),
+        }
+    }
+}
+
+/// The subscriber created by [`ChildWatch::with_handler`].
+pub struct Subscriber<F> {
+    pid: Pid,
+    pidfd: OwnedFd,
+    interest: Cell<Interest>,
+    /// Taken by the one call.
+    handler: Option<F>,
+}
+
+impl<F> Subscriber<F> {
+    /// Returns the pid of the watched child.
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// Reaps the child, and returns its exit status, or `None` if it is still
+    /// running.
+    fn try_wait(&self) -> io::Result<Option<ExitStatus>> {
+        // SAFETY: All zeroes is a valid `siginfo_t`, and `si_pid` stays 0 when there
+        // is no child to reap yet.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: `info` is a valid `siginfo_t` to write into.
+            let ret = unsafe {
+                libc::waitid(
+                    libc::P_PIDFD,
+                    self.pidfd.as_raw_fd() as libc::id_t,
+                    &mut info,
+                    libc::WEXITED | libc::WNOHANG,
+                )
+            };
+            if ret == 0 {
+                break;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+
+        // SAFETY: Filled in by `waitid` for the `SIGCHLD` of a child.
+        let (pid, status) = unsafe { (info.si_pid(), info.si_status()) };
+        if pid == 0 {
+            return Ok(None);
+        }
+        // Rebuilt as the `wait` status the fields were decoded from.
+        let raw = match info.si_code {
+            libc::CLD_EXITED => (status & 0xff) << 8,
+            libc::CLD_DUMPED => status | 0x80,
+            _ => status,
+        };
+        Ok(Some(ExitStatus::from_raw(raw)))
+    }
+}
+
+impl<F> AsFd for Subscriber<F> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.pidfd.as_fd()
+    }
+}
+
+impl<F> HasInterest for Subscriber<F> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, F, R> Handler<Ep> for Subscriber<F>
+where
+    Ep: EventpOps,
+    F: FnOnce(ExitStatus, Pinned<'_, Ep>) -> R,
+    R: HandlerReturn,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    /// Reaps the child, deletes the subscriber and calls the handler, once the
+    /// child exited.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error of the handler. If the child cannot be reaped, e.g. with
+    /// `ECHILD` because it was waited for elsewhere, the subscriber is deleted
+    /// without calling the handler, and the error returned.
+    fn try_handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        let fd = self.pidfd.as_raw_fd();
+        let status = match self.try_wait() {
+            Ok(Some(status)) => status,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                let _ = eventp.delete(fd);
+                return Err(e);
+            }
+        };
+        eventp.delete(fd)?;
+        match self.handler.take() {
+            Some(handler) => handler(status, eventp).into_result(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+// The children are reaped by the subscribers under test.
+#[allow(clippy::zombie_processes)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{Command, Stdio};
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{ErrorPolicy, Eventp, Subscriber as _};
+
+    fn spawn(script: &str) -> Child {
+        Command::new("sh")
+            .args(["-c", script])
+            .stdin(Stdio::null())
+            .spawn()
+            .unwrap()
+    }
+
+    /// Registers a watch of `child` into `eventp`, recording every status handed
+    /// out, and returns the pidfd and the recorded statuses.
+    fn record(
+        eventp: &mut Eventp,
+        child: impl ChildPid,
+    ) -> (std::os::fd::RawFd, Rc<Cell<Vec<ExitStatus>>>) {
+        let statuses = Rc::new(Cell::new(Vec::new()));
+        let s = statuses.clone();
+        let subscriber = watch_child(child).unwrap().with_handler(
+            move |status: ExitStatus, _eventp: Pinned<'_, Eventp>| {
+                let mut all = s.take();
+                all.push(status);
+                s.set(all);
+            },
+        );
+        let fd = subscriber.as_fd().as_raw_fd();
+        subscriber.register_into(eventp).unwrap();
+        (fd, statuses)
+    }
+
+    fn timeout() -> EpollTimeout {
+        EpollTimeout::from(5000u16)
+    }
+
+    #[test]
+    fn exit_code_is_handed_out_once() {
+        let mut eventp = Eventp::default();
+        let child = spawn("sleep 0.05; exit 3");
+        let (fd, statuses) = record(&mut eventp, &child);
+
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        let all = statuses.take();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].code(), Some(3));
+        assert!(!eventp.contains(fd));
+    }
+
+    #[test]
+    fn child_exited_before_the_watch_is_handed_out() {
+        let mut eventp = Eventp::default();
+        let child = spawn("exit 0");
+        // A zombie by now, not reaped by anyone.
+        std::thread::sleep(Duration::from_millis(50));
+        let (_, statuses) = record(&mut eventp, Pid::from_raw(child.id() as libc::pid_t));
+
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        let all = statuses.take();
+        assert_eq!(all.len(), 1);
+        assert!(all[0].success());
+    }
+
+    #[test]
+    fn killed_child_reports_the_signal() {
+        let mut eventp = Eventp::default();
+        let mut child = spawn("sleep 10");
+        let (_, statuses) = record(&mut eventp, &child);
+
+        child.kill().unwrap();
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        let all = statuses.take();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].signal(), Some(libc::SIGKILL));
+    }
+
+    #[test]
+    fn child_reaped_elsewhere_is_an_error() {
+        let mut eventp = Eventp::builder()
+            .error_policy(ErrorPolicy::Propagate)
+            .build()
+            .unwrap();
+        let mut child = spawn("exit 0");
+        let (fd, statuses) = record(&mut eventp, &child);
+
+        child.wait().unwrap();
+        let err = eventp.run_once_with_timeout(timeout()).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ECHILD));
+        assert!(statuses.take().is_empty());
+        assert!(!eventp.contains(fd));
+    }
+}
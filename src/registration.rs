@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::os::fd::RawFd;
+use std::rc::{Rc, Weak};
+use std::{io, mem};
+
+#[cfg(feature = "remote-endpoint")]
+use crate::remote_endpoint::RemoteEndpoint;
+use crate::{Eventp, EventpOps, EventpOpsAdd, Pinned};
+
+/// The registrations whose [`Registration`] was dropped, by fd and generation, to
+/// be deleted by the loop at the next batch boundary.
+pub(crate) type Released = Rc<RefCell<Vec<(RawFd, u16)>>>;
+
+/// Deletes a subscriber from its loop when dropped, for subscribers living as long
+/// as some object of their own, e.g. a session or a plugin.
+///
+/// Returned by [`Subscriber::register_guarded`](crate::Subscriber::register_guarded).
+/// Dropping the guard cannot reach the loop, so it leaves the fd in a queue of the
+/// loop, which deletes it before waiting next, or at the end of the batch if the
+/// guard is dropped by a handler. [`deregister`](Self::deregister) deletes it right
+/// away.
+///
+/// The guard only refers to its own registration: once the subscriber is deleted
+/// otherwise, it does nothing, even if the fd number is registered again.
+///
+/// ```rust
+/// # use std::io;
+/// use eventp::{tri_subscriber::WithHandler, Eventp, Registration, Subscriber};
+/// use nix::sys::eventfd::EventFd;
+///
+/// struct Session {
+///     _notifications: Registration,
+/// }
+///
+/// fn open_session(efd: EventFd, eventp: &mut Eventp) -> io::Result<Session> {
+///     let registration = eventp::interest()
+///         .read()
+///         .with_fd(efd)
+///         .with_handler(|efd: &mut EventFd| {
+///             let _ = efd.read();
+///         })
+///         .register_guarded(eventp)?;
+///     Ok(Session {
+///         _notifications: registration,
+///     })
+/// }
+/// ```
+#[derive(Debug)]
+#[must_use = "dropping the guard deletes the subscriber"]
+pub struct Registration {
+    fd: RawFd,
+    generation: u16,
+    released: Weak<RefCell<Vec<(RawFd, u16)>>>,
+}
+
+/// A helper trait that lets [`Subscriber::register_guarded`] accept both
+/// `&mut Eventp` and [`Pinned<'_, Eventp>`].
+///
+/// Its methods are only meant to be called by [`Registration`].
+///
+/// # Sealed
+///
+/// This trait is sealed and cannot be implemented for types outside of this crate.
+///
+/// [`Subscriber::register_guarded`]: crate::Subscriber::register_guarded
+/// [`Pinned<'_, Eventp>`]: crate::Pinned
+pub trait Registry<Ep: EventpOps>: EventpOpsAdd<Ep> {
+    #[doc(hidden)]
+    fn registration(&self, fd: RawFd) -> Registration;
+
+    #[doc(hidden)]
+    fn delete_registration(&mut self, fd: RawFd, generation: u16) -> io::Result<()>;
+}
+
+impl Registry<Eventp> for Eventp {
+    fn registration(&self, fd: RawFd) -> Registration {
+        Registration {
+            fd,
+            generation: self.registered[&fd].generation(),
+            released: Rc::downgrade(&self.released),
+        }
+    }
+
+    fn delete_registration(&mut self, fd: RawFd, generation: u16) -> io::Result<()> {
+        match self.registered.get(&fd) {
+            Some(subscriber) if subscriber.generation() == generation => self.delete(fd),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "fd not registered")),
+        }
+    }
+}
+
+impl Registry<Eventp> for Pinned<'_, Eventp> {
+    fn registration(&self, fd: RawFd) -> Registration {
+        self.0.registration(fd)
+    }
+
+    fn delete_registration(&mut self, fd: RawFd, generation: u16) -> io::Result<()> {
+        unsafe {
+            self.0
+                .as_mut()
+                .get_unchecked_mut()
+                .delete_registration(fd, generation)
+        }
+    }
+}
+
+impl Registration {
+    /// Returns the raw fd of the subscriber.
+    pub fn raw_fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Deletes the subscriber from `eventp` right away, instead of at the next batch
+    /// boundary.
+    ///
+    /// # Errors
+    ///
+    /// - [`io::ErrorKind::NotFound`] if the subscriber was already deleted.
+    /// - Otherwise, same as [`EventpOps::delete`].
+    ///
+    /// The guard is consumed either way.
+    pub fn deregister<R: Registry<Eventp>>(mut self, eventp: &mut R) -> io::Result<()> {
+        mem::take(&mut self.released);
+        eventp.delete_registration(self.fd, self.generation)
+    }
+
+    /// Turns the guard into one that can be sent to other threads, deleting the
+    /// subscriber through `endpoint`, a [`RemoteEndpoint`] of its loop.
+    #[cfg(feature = "remote-endpoint")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "remote-endpoint")))]
+    pub fn into_remote(mut self, endpoint: RemoteEndpoint<Eventp>) -> RemoteRegistration {
+        mem::take(&mut self.released);
+        RemoteRegistration {
+            fd: self.fd,
+            generation: self.generation,
+            endpoint: Some(endpoint),
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        // Gone along with its loop otherwise.
+        if let Some(released) = self.released.upgrade() {
+            released.borrow_mut().push((self.fd, self.generation));
+        }
+    }
+}
+
+/// A [`Registration`] that can be sent to other threads, created by
+/// [`Registration::into_remote`].
+///
+/// Dropping the guard sends the deletion to the loop, without waiting for it.
+#[cfg(feature = "remote-endpoint")]
+#[cfg_attr(docsrs, doc(cfg(feature = "remote-endpoint")))]
+#[must_use = "dropping the guard deletes the subscriber"]
+pub struct RemoteRegistration {
+    fd: RawFd,
+    generation: u16,
+    /// Taken by `deregister`.
+    endpoint: Option<RemoteEndpoint<Eventp>>,
+}
+
+#[cfg(feature = "remote-endpoint")]
+impl RemoteRegistration {
+    /// Returns the raw fd of the subscriber.
+    pub fn raw_fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Deletes the subscriber, and blocks until the loop did.
+    ///
+    /// Must not be called from the thread of the loop, which would then never get
+    /// to the deletion.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Registration::deregister`], or as
+    /// [`RemoteEndpoint::call_blocking`].
+    pub fn deregister(mut self) -> io::Result<()> {
+        let (fd, generation) = (self.fd, self.generation);
+        let endpoint = self.endpoint.take().expect("taken only once");
+        endpoint.call_blocking(move |mut eventp| eventp.delete_registration(fd, generation))
+    }
+}
+
+#[cfg(feature = "remote-endpoint")]
+impl Drop for RemoteRegistration {
+    fn drop(&mut self) {
+        let (fd, generation) = (self.fd, self.generation);
+        if let Some(endpoint) = self.endpoint.take() {
+            // Fails only if the loop is gone, along with the subscriber.
+            let _ = endpoint.call_nonblocking(move |mut eventp| {
+                let _ = eventp.delete_registration(fd, generation);
+            });
+        }
+    }
+}
+
+impl Eventp {
+    /// Deletes the registrations whose guard was dropped.
+    pub(crate) fn delete_released(&mut self) {
+        // Deleting a subscriber may drop the guards of others, which are taken by
+        // the next round.
+        loop {
+            let released = mem::take(&mut *self.released.borrow_mut());
+            if released.is_empty() {
+                return;
+            }
+            for (fd, generation) in released {
+                // Already deleted otherwise, or refused by the kernel, with nowhere
+                // better for the error to go.
+                let _ = self.delete_registration(fd, generation);
+            }
+        }
+    }
+}
@@ -14,7 +14,29 @@
 //! When a method like [`RemoteEndpoint::call_blocking`] is called, it sends a closure
 //! over an MPSC channel to the `Subscriber` and then writes to the `eventfd` to wake
 //! up the event loop. The `Subscriber`'s handler then drains the channel and executes
-//! the received closures.
+//! the received closures. It clears the `eventfd` before draining, so the subscriber
+//! may also be registered, or modified to be, edge-triggered.
+//!
+//! The most common operations, [`delete_remote`](RemoteEndpoint::delete_remote),
+//! [`modify_remote`](RemoteEndpoint::modify_remote) and
+//! [`wake`](RemoteEndpoint::wake), are sent without a closure, so without an
+//! allocation per call.
+//!
+//! # Observability
+//!
+//! The endpoints of a pair and its subscriber share their [`EndpointStats`]: the
+//! calls sent and those executed, and the difference, the calls
+//! [`pending`](RemoteEndpoint::pending) in the channel. A producer can tell from it
+//! that the loop falls behind, to back off or to raise an alert. The counters are
+//! relaxed atomics, updated once per call.
+//!
+//! # Ordering
+//!
+//! The closures sent through one endpoint, or its clones, from one thread run in the
+//! order they were sent. Those of different threads may interleave, one closure at a
+//! time. The operations sent without a closure are ordered along with the closures.
+//! A [`Batch`] is sent as a single closure, so the closures it chains run back
+//! to back, with no closure of another sender in between.
 //!
 //! # Examples
 //!
@@ -45,7 +67,8 @@
 
 use std::cell::Cell;
 use std::io;
-use std::os::fd::{AsFd, BorrowedFd};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{mpsc, Arc};
 use std::time::Duration;
 
@@ -57,6 +80,18 @@ use crate::{interest, Event, EventpOps, EventpOpsAdd, Interest, Pinned};
 
 type BoxFn<Ep> = Box<dyn FnOnce(Pinned<Ep>) + Send>;
 
+/// Where the result of an operation sent without a closure goes, e.g. with
+/// [`RemoteEndpoint::delete_remote`]. Made by `oneshot::channel`.
+pub type Reply = oneshot::Sender<io::Result<()>>;
+
+/// What goes through the channel: a closure, or one of the operations that need
+/// none.
+enum Message<Ep> {
+    Call(BoxFn<Ep>),
+    Delete(RawFd, Option<Reply>),
+    Modify(RawFd, Interest, Option<Reply>),
+}
+
 /// Creates a [`Pair`] of [`RemoteEndpoint`] and [`Subscriber`].
 ///
 /// For more information, see the [mod-level documentation](self).
@@ -66,13 +101,15 @@ pub fn remote_endpoint<Ep>() -> io::Result<Pair<Ep>> {
     let eventfd = Arc::new(eventfd);
 
     let (tx, rx) = mpsc::channel();
+    let stats = Arc::new(EndpointStats::default());
 
     let subscriber = Subscriber {
         eventfd: Arc::clone(&eventfd),
         interest: Cell::new(interest().read()),
         rx,
+        stats: Arc::clone(&stats),
     };
-    let endpoint = RemoteEndpoint { eventfd, tx };
+    let endpoint = RemoteEndpoint { eventfd, tx, stats };
 
     Ok(Pair {
         subscriber,
@@ -99,7 +136,8 @@ pub struct Pair<Ep> {
 pub struct Subscriber<Ep> {
     eventfd: Arc<EventFd>,
     interest: Cell<Interest>,
-    rx: mpsc::Receiver<BoxFn<Ep>>,
+    rx: mpsc::Receiver<Message<Ep>>,
+    stats: Arc<EndpointStats>,
 }
 
 /// A remote control for an `Eventp` instance running on another thread.
@@ -111,7 +149,41 @@ pub struct Subscriber<Ep> {
 /// `RemoteEndpoint` is cheap to clone and is both `Send` and `Sync`.
 pub struct RemoteEndpoint<Ep> {
     eventfd: Arc<EventFd>,
-    tx: mpsc::Sender<BoxFn<Ep>>,
+    tx: mpsc::Sender<Message<Ep>>,
+    stats: Arc<EndpointStats>,
+}
+
+/// The counters of the calls sent through the endpoints of a [`Pair`], and executed
+/// by its [`Subscriber`], see [Observability](self#observability).
+///
+/// A call is a closure, a [`Batch`] or an operation sent without a closure, such as
+/// [`delete_remote`](RemoteEndpoint::delete_remote); [`wake`](RemoteEndpoint::wake)
+/// sends none. The calls left in the channel when the subscriber is dropped are
+/// never executed, and stay pending.
+#[derive(Debug, Default)]
+pub struct EndpointStats {
+    sent: AtomicU64,
+    executed: AtomicU64,
+}
+
+impl EndpointStats {
+    /// Returns the number of calls sent so far.
+    pub fn calls_sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of calls executed so far, on the thread of the loop,
+    /// counting the one running.
+    pub fn calls_executed(&self) -> u64 {
+        self.executed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of calls sent but not executed yet, approximately, as the
+    /// two counters are read one after the other while they may change.
+    pub fn pending(&self) -> usize {
+        let executed = self.calls_executed();
+        self.calls_sent().saturating_sub(executed) as usize
+    }
 }
 
 impl<Ep: EventpOps> Pair<Ep> {
@@ -139,12 +211,40 @@ impl<Ep> HasInterest for Subscriber<Ep> {
     }
 }
 
+impl<Ep> Subscriber<Ep> {
+    /// Returns the counters this subscriber shares with its endpoints, which stay
+    /// reachable once it is registered.
+    pub fn stats(&self) -> &Arc<EndpointStats> {
+        &self.stats
+    }
+}
+
 impl<Ep: EventpOps> Handler<Ep> for Subscriber<Ep> {
+    /// Clears the eventfd, then runs every queued closure and operation.
+    ///
+    /// Senders queue a closure before writing to the eventfd, so a closure left in
+    /// the channel by the drain comes with a write after the clear, which the loop
+    /// reports again. This holds with an edge-triggered interest too: nothing is
+    /// stranded until the next, unrelated, wakeup.
     fn handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) {
-        let _ = self.eventfd.read();
-
-        while let Ok(f) = self.rx.try_recv() {
-            (f)(eventp.as_mut())
+        // One read resets the counter; more only succeed if a sender wrote since,
+        // and stop at `EAGAIN`.
+        while self.eventfd.read().is_ok() {}
+
+        while let Ok(message) = self.rx.try_recv() {
+            // Counted first, so that a caller woken by the call sees it executed.
+            self.stats.executed.fetch_add(1, Ordering::Relaxed);
+            let (result, reply) = match message {
+                Message::Call(f) => {
+                    f(eventp.as_mut());
+                    continue;
+                }
+                Message::Delete(fd, reply) => (eventp.delete(fd), reply),
+                Message::Modify(fd, interest, reply) => (eventp.modify(fd, interest), reply),
+            };
+            if let Some(reply) = reply {
+                let _ = reply.send(result);
+            }
         }
     }
 }
@@ -160,13 +260,9 @@ macro_rules! call_variant {
     ($self:ident, $f:ident, |$rx:ident| $rx_expr:expr, |$rx_err:ident| $err_map:expr) => {{
         let (tx, $rx) = oneshot::channel();
 
-        $self
-            .tx
-            .send(Box::new(move |ep| {
-                let _ = tx.send($f(ep));
-            }))
-            .map_err(|_| err_subscriber_dropped())?;
-        $self.eventfd.write(1).map_err(io::Error::from)?;
+        $self.send(Message::Call(Box::new(move |ep| {
+            let _ = tx.send($f(ep));
+        })))?;
 
         match $rx_expr {
             Ok(v) => v,
@@ -260,13 +356,194 @@ impl<Ep> RemoteEndpoint<Ep> {
     where
         F: 'static + FnOnce(Pinned<'_, Ep>) + Send,
     {
-        self.tx
-            .send(Box::new(f))
-            .map_err(|_| err_subscriber_dropped())?;
-        self.eventfd.write(1).map_err(io::Error::from)?;
+        self.send(Message::Call(Box::new(f)))
+    }
 
+    /// Deletes the subscriber registered with `fd` on the `Eventp` thread, see
+    /// [`EventpOps::delete`], without waiting for it.
+    ///
+    /// The same as [`call_nonblocking`](Self::call_nonblocking) with a closure
+    /// calling `delete`, but without allocating. Its result is sent to `reply`, if
+    /// given.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`call_nonblocking`](Self::call_nonblocking). Those of `delete` go
+    /// to `reply`.
+    pub fn delete_remote(&self, fd: RawFd, reply: Option<Reply>) -> io::Result<()> {
+        self.send(Message::Delete(fd, reply))
+    }
+
+    /// Modifies the interest of the subscriber registered with `fd` on the `Eventp`
+    /// thread, see [`EventpOps::modify`], without waiting for it.
+    ///
+    /// The same as [`call_nonblocking`](Self::call_nonblocking) with a closure
+    /// calling `modify`, but without allocating. Its result is sent to `reply`, if
+    /// given.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`call_nonblocking`](Self::call_nonblocking). Those of `modify` go
+    /// to `reply`.
+    pub fn modify_remote(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        reply: Option<Reply>,
+    ) -> io::Result<()> {
+        self.send(Message::Modify(fd, interest, reply))
+    }
+
+    /// Wakes the `Eventp` thread up, returning from its wait with nothing to run,
+    /// e.g. for it to check a flag.
+    ///
+    /// Nothing goes through the channel, so this succeeds even once the
+    /// [`Subscriber`] is dropped, waking nobody.
+    ///
+    /// # Errors
+    ///
+    /// The [`io::Error`] returned by the underlying `eventfd` write.
+    pub fn wake(&self) -> io::Result<()> {
+        self.eventfd.write(1).map_err(io::Error::from)?;
         Ok(())
     }
+
+    /// Returns the number of calls sent through this endpoint and its clones, but
+    /// not executed yet, see [`EndpointStats::pending`].
+    pub fn pending(&self) -> usize {
+        self.stats.pending()
+    }
+
+    /// Returns the number of calls sent through this endpoint and its clones, see
+    /// [`EndpointStats::calls_sent`].
+    pub fn calls_sent(&self) -> u64 {
+        self.stats.calls_sent()
+    }
+
+    /// Returns the counters this endpoint shares with its clones and its
+    /// [`Subscriber`].
+    pub fn stats(&self) -> &Arc<EndpointStats> {
+        &self.stats
+    }
+
+    /// Queues `message`, then wakes the `Eventp` thread up.
+    fn send(&self, message: Message<Ep>) -> io::Result<()> {
+        // Counted first, so that it is never executed before it is sent.
+        self.stats.sent.fetch_add(1, Ordering::Relaxed);
+        if self.tx.send(message).is_err() {
+            self.stats.sent.fetch_sub(1, Ordering::Relaxed);
+            return Err(err_subscriber_dropped());
+        }
+        self.wake()
+    }
+}
+
+impl<Ep: 'static> RemoteEndpoint<Ep> {
+    /// Sends the closures of `batch` to the `Eventp` thread as one, without waiting
+    /// for them to run. See [`Batch`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`call_nonblocking`](Self::call_nonblocking).
+    pub fn call_batch<S: 'static + Send>(&self, batch: Batch<Ep, S>) -> io::Result<()> {
+        self.call_nonblocking(move |eventp| {
+            batch.run(eventp);
+        })
+    }
+
+    /// Sends the closures of `batch` to the `Eventp` thread as one, and blocks until
+    /// they ran, returning the scratch state they left.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`call_blocking`](Self::call_blocking).
+    pub fn call_batch_blocking<S: 'static + Send>(&self, batch: Batch<Ep, S>) -> io::Result<S> {
+        self.call_blocking(move |eventp| Ok(batch.run(eventp)))
+    }
+}
+
+type BatchFn<Ep, S> = Box<dyn FnOnce(Pinned<'_, Ep>, &mut S) + Send>;
+
+/// Closures run back to back on the `Eventp` thread, sharing a scratch state `S`,
+/// sent by [`RemoteEndpoint::call_batch`] or
+/// [`call_batch_blocking`](RemoteEndpoint::call_batch_blocking).
+///
+/// No closure of another sender runs between them, see [Ordering](self#ordering),
+/// and each gets the state left by the previous one, without a lock.
+///
+/// ```rust
+/// # use std::io;
+/// use std::os::fd::RawFd;
+///
+/// use eventp::remote_endpoint::{Batch, RemoteEndpoint};
+/// use eventp::{Eventp, EventpOps};
+///
+/// /// Deletes `fds`, and returns how many were registered.
+/// fn delete_all(endpoint: &RemoteEndpoint<Eventp>, fds: &[RawFd]) -> io::Result<usize> {
+///     let mut batch = Batch::with_scratch(0);
+///     for &fd in fds {
+///         batch = batch.then(move |mut eventp, deleted: &mut usize| {
+///             if eventp.delete(fd).is_ok() {
+///                 *deleted += 1;
+///             }
+///         });
+///     }
+///     endpoint.call_batch_blocking(batch)
+/// }
+/// ```
+pub struct Batch<Ep, S = ()> {
+    scratch: S,
+    calls: Vec<BatchFn<Ep, S>>,
+}
+
+impl<Ep> Batch<Ep> {
+    /// Creates an empty batch, without scratch state.
+    pub fn new() -> Self {
+        Self::with_scratch(())
+    }
+}
+
+impl<Ep> Default for Batch<Ep> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Ep, S> Batch<Ep, S> {
+    /// Creates an empty batch, whose closures share `scratch`.
+    pub fn with_scratch(scratch: S) -> Self {
+        Self {
+            scratch,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Chains `f`, to be run after the closures already in the batch.
+    pub fn then<F>(mut self, f: F) -> Self
+    where
+        F: 'static + FnOnce(Pinned<'_, Ep>, &mut S) + Send,
+    {
+        self.calls.push(Box::new(f));
+        self
+    }
+
+    /// Returns the number of closures in the batch.
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Returns whether the batch has no closure.
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    fn run(self, mut eventp: Pinned<'_, Ep>) -> S {
+        let mut scratch = self.scratch;
+        for f in self.calls {
+            f(eventp.as_mut(), &mut scratch);
+        }
+        scratch
+    }
 }
 
 impl<Ep> Clone for RemoteEndpoint<Ep> {
@@ -274,12 +551,112 @@ impl<Ep> Clone for RemoteEndpoint<Ep> {
         Self {
             eventfd: self.eventfd.clone(),
             tx: self.tx.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+/// Registers subscribers into an `Eventp` running on another thread, through a
+/// [`RemoteEndpoint`] of it.
+///
+/// The subscriber is built on the calling thread, and sent to the thread of the loop
+/// once, so it must be `Send`. The registration is then known by its fd, for
+/// [`modify_remote`](Self::modify_remote) and
+/// [`delete_remote`](Self::delete_remote).
+///
+/// Every method blocks until the loop ran it, so none may be called from the
+/// thread of the loop, which would then never get to it.
+///
+/// ```rust
+/// # use std::io;
+/// use std::os::fd::RawFd;
+///
+/// use eventp::remote_endpoint::RemoteRegistry;
+/// use eventp::{interest, tri_subscriber::WithHandler, Eventp};
+/// use nix::sys::eventfd::EventFd;
+///
+/// fn watch_shutdown(registry: &RemoteRegistry<Eventp>, efd: EventFd) -> io::Result<RawFd> {
+///     registry.register_remote(interest().read().with_fd(efd).with_handler(
+///         |efd: &mut EventFd| {
+///             let _ = efd.read();
+///         },
+///     ))
+/// }
+/// ```
+pub struct RemoteRegistry<Ep> {
+    endpoint: RemoteEndpoint<Ep>,
+}
+
+impl<Ep: EventpOps> RemoteRegistry<Ep> {
+    /// Creates a registry registering through `endpoint`.
+    pub fn new(endpoint: RemoteEndpoint<Ep>) -> Self {
+        Self { endpoint }
+    }
+
+    /// Returns the endpoint registrations go through.
+    pub fn endpoint(&self) -> &RemoteEndpoint<Ep> {
+        &self.endpoint
+    }
+
+    /// Registers `subscriber` on the thread of the loop, as
+    /// [`Subscriber::register_into`](crate::Subscriber::register_into) would, and
+    /// returns its fd.
+    ///
+    /// # Errors
+    ///
+    /// The error of the registration on the loop, or of
+    /// [`RemoteEndpoint::call_blocking`].
+    pub fn register_remote<S>(&self, subscriber: S) -> io::Result<RawFd>
+    where
+        S: crate::Subscriber<Ep> + HasInterest + Send,
+    {
+        let fd = subscriber.as_fd().as_raw_fd();
+        self.endpoint
+            .call_blocking(move |mut eventp| eventp.add(ThinBoxSubscriber::new(subscriber)))?;
+        Ok(fd)
+    }
+
+    /// Modifies the interest of the subscriber registered with `fd`, see
+    /// [`EventpOps::modify`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`EventpOps::modify`], or as [`RemoteEndpoint::call_blocking`].
+    pub fn modify_remote(&self, fd: RawFd, interest: Interest) -> io::Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.endpoint.modify_remote(fd, interest, Some(tx))?;
+        rx.recv().map_err(|_| err_subscriber_dropped())?
+    }
+
+    /// Deletes the subscriber registered with `fd`, see [`EventpOps::delete`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`EventpOps::delete`], or as [`RemoteEndpoint::call_blocking`].
+    pub fn delete_remote(&self, fd: RawFd) -> io::Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.endpoint.delete_remote(fd, Some(tx))?;
+        rx.recv().map_err(|_| err_subscriber_dropped())?
+    }
+}
+
+impl<Ep> From<RemoteEndpoint<Ep>> for RemoteRegistry<Ep> {
+    fn from(endpoint: RemoteEndpoint<Ep>) -> Self {
+        Self { endpoint }
+    }
+}
+
+impl<Ep> Clone for RemoteRegistry<Ep> {
+    fn clone(&self) -> Self {
+        Self {
+            endpoint: self.endpoint.clone(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::os::fd::AsRawFd;
     use std::sync::atomic::{AtomicU32, Ordering};
     use std::sync::{Arc as StdArc, Barrier};
     use std::thread;
@@ -297,6 +674,9 @@ mod tests {
     const _: () = {
         assert_send::<RemoteEndpoint<Eventp>>();
         assert_sync::<RemoteEndpoint<Eventp>>();
+        assert_send::<crate::RemoteRegistration>();
+        assert_send::<RemoteRegistry<Eventp>>();
+        assert_sync::<RemoteRegistry<Eventp>>();
 
         #[cfg(feature = "mock")]
         assert_send::<RemoteEndpoint<MockEventp>>();
@@ -371,6 +751,27 @@ mod tests {
         shutdown(stop, handle);
     }
 
+    #[test]
+    fn try_run_once_inside_a_call_reports_the_loop_running() {
+        let (endpoint, handle, stop) = spawn_reactor();
+
+        let tried = endpoint
+            .call_blocking(|mut ep| {
+                // SAFETY: Deliberately bypassing `Pinned`, as a helper handed the
+                // loop by such a closure would.
+                let eventp: &mut Eventp = unsafe { ep.0.as_mut().get_unchecked_mut() };
+                Ok(eventp
+                    .try_run_once_with_timeout(EpollTimeout::ZERO)
+                    .map(drop))
+            })
+            .unwrap();
+        assert!(matches!(tried, Err(crate::AlreadyRunning { fd }) if fd >= 0));
+
+        // The loop is unharmed, and runs the next call.
+        assert_eq!(endpoint.call_blocking(|_| Ok(7)).unwrap(), 7);
+        shutdown(stop, handle);
+    }
+
     #[test]
     fn call_blocking_with_timeout_elapses_when_reactor_idle() {
         // Build a Pair but never register the subscriber and never run the
@@ -390,6 +791,80 @@ mod tests {
         assert_eq!(err.kind(), io::ErrorKind::TimedOut);
     }
 
+    #[test]
+    fn user_data_is_set_from_a_remote_closure() {
+        use crate::tri_subscriber::{UserData, WithHandler};
+        use crate::Subscriber as _;
+
+        let (endpoint, handle, stop) = spawn_reactor();
+        let seen = StdArc::new(AtomicU32::new(0));
+        let s = seen.clone();
+        let raw = endpoint
+            .call_blocking(move |mut eventp| {
+                let efd = EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+                let raw = efd.as_fd().as_raw_fd();
+                interest()
+                    .read()
+                    .user_data(1)
+                    .with_fd(efd)
+                    .with_handler(move |efd: &mut EventFd, UserData(data)| {
+                        let _ = efd.read();
+                        s.store(data as u32, Ordering::Release);
+                    })
+                    .register_into(&mut eventp)?;
+                Ok(raw)
+            })
+            .unwrap();
+
+        let data = endpoint
+            .call_blocking(move |mut eventp| {
+                let before = eventp.user_data_of(raw);
+                eventp.set_user_data(raw, 42)?;
+                // SAFETY: The eventfd is owned by its subscriber, registered above.
+                let efd = unsafe { BorrowedFd::borrow_raw(raw) };
+                nix::unistd::write(efd, &1u64.to_ne_bytes())?;
+                Ok((before, eventp.user_data_of(raw)))
+            })
+            .unwrap();
+        assert_eq!(data, (Some(1), Some(42)));
+        // A fence, once the eventfd has been handled.
+        while seen.load(Ordering::Acquire) == 0 {
+            endpoint.call_blocking(|_| Ok(())).unwrap();
+        }
+        assert_eq!(seen.load(Ordering::Acquire), 42);
+
+        shutdown(stop, handle);
+    }
+
+    #[test]
+    fn pending_calls_pile_up_until_the_loop_runs() {
+        let mut eventp = Eventp::default();
+        let pair = remote_endpoint::<Eventp>().unwrap();
+        let stats = StdArc::clone(pair.subscriber.stats());
+        let endpoint = pair.register_into(&mut eventp).unwrap();
+        let clone = endpoint.clone();
+
+        for _ in 0..3 {
+            endpoint.call_nonblocking(|_| {}).unwrap();
+        }
+        clone.delete_remote(-1, None).unwrap();
+        // Sends nothing.
+        clone.wake().unwrap();
+        assert_eq!(endpoint.pending(), 4);
+        assert_eq!(clone.calls_sent(), 4);
+        assert_eq!(stats.calls_executed(), 0);
+        assert!(StdArc::ptr_eq(endpoint.stats(), &stats));
+
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(endpoint.pending(), 0);
+        assert_eq!(stats.calls_executed(), 4);
+
+        // Not counted once the subscriber is gone.
+        drop(eventp);
+        assert!(endpoint.call_nonblocking(|_| {}).is_err());
+        assert_eq!(endpoint.calls_sent(), 4);
+    }
+
     #[test]
     fn call_nonblocking_executes_and_drains_batch() {
         let (endpoint, handle, stop) = spawn_reactor();
@@ -471,6 +946,48 @@ mod tests {
         shutdown(stop, handle);
     }
 
+    #[test]
+    fn edge_triggered_wakeups_strand_no_closure() {
+        const THREADS: u32 = 8;
+        const CALLS: u32 = 2000;
+
+        let mut eventp = Eventp::default();
+        let pair = remote_endpoint().unwrap();
+        let raw_fd = pair.subscriber.as_fd().as_raw_fd();
+        let endpoint = pair.register_into(&mut eventp).unwrap();
+        eventp
+            .modify(raw_fd, interest().read().edge_triggered())
+            .unwrap();
+
+        let counter = StdArc::new(AtomicU32::new(0));
+        let producers: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let (ep, c) = (endpoint.clone(), counter.clone());
+                thread::spawn(move || {
+                    for _ in 0..CALLS {
+                        let c = c.clone();
+                        ep.call_nonblocking(move |_| {
+                            c.fetch_add(1, Ordering::Relaxed);
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        while !producers.iter().all(|p| p.is_finished()) {
+            eventp.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        }
+        producers.into_iter().for_each(|p| p.join().unwrap());
+
+        // Whatever is left was written after the last clear, so one more wakeup
+        // must run it all.
+        if counter.load(Ordering::Relaxed) < THREADS * CALLS {
+            eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        }
+        assert_eq!(counter.load(Ordering::Relaxed), THREADS * CALLS);
+    }
+
     #[test]
     fn closure_can_mutate_reactor_state() {
         // The whole point of `RemoteEndpoint` is to give external threads a
@@ -486,4 +1003,236 @@ mod tests {
 
         shutdown(stop, handle);
     }
+
+    #[test]
+    fn operations_without_closures_match_their_closures() {
+        use crate::tri_subscriber::WithHandler;
+        use crate::Subscriber as _;
+
+        let mut eventp = Eventp::default();
+        let endpoint = remote_endpoint()
+            .unwrap()
+            .register_into(&mut eventp)
+            .unwrap();
+        let efd = EventFd::from_flags(EfdFlags::EFD_NONBLOCK).unwrap();
+        let fd = efd.as_raw_fd();
+        interest()
+            .read()
+            .with_fd(efd)
+            .with_handler(|| {})
+            .register_into(&mut eventp)
+            .unwrap();
+
+        let (reply, modified) = oneshot::channel();
+        let edge = interest().read().edge_triggered();
+        endpoint.modify_remote(fd, edge, Some(reply)).unwrap();
+        // Ordered along with the closures.
+        let (tx, seen) = mpsc::channel();
+        endpoint
+            .call_nonblocking(move |ep| tx.send(ep.0.interest(&fd)).unwrap())
+            .unwrap();
+        endpoint.delete_remote(fd, None).unwrap();
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        modified.recv().unwrap().unwrap();
+        assert_eq!(seen.recv().unwrap(), Some(edge));
+        assert!(!eventp.contains(fd));
+
+        // The errors are those of the closures.
+        let (reply, deleted) = oneshot::channel();
+        endpoint.delete_remote(fd, Some(reply)).unwrap();
+        let (reply, modified) = oneshot::channel();
+        endpoint.modify_remote(fd, edge, Some(reply)).unwrap();
+        let (tx, closures) = mpsc::channel();
+        endpoint
+            .call_nonblocking(move |mut ep| {
+                tx.send((ep.delete(fd), ep.modify(fd, edge))).unwrap();
+            })
+            .unwrap();
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        let (delete, modify) = closures.recv().unwrap();
+        assert_eq!(
+            deleted.recv().unwrap().unwrap_err().kind(),
+            delete.unwrap_err().kind()
+        );
+        assert_eq!(
+            modified.recv().unwrap().unwrap_err().kind(),
+            modify.unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn wake_sends_nothing() {
+        let mut eventp = Eventp::default();
+        let pair = remote_endpoint().unwrap();
+        let rx_fd = pair.subscriber.as_fd().as_raw_fd();
+        let endpoint = pair.register_into(&mut eventp).unwrap();
+
+        endpoint.wake().unwrap();
+        eventp.run_once_with_timeout(poll_timeout()).unwrap();
+        assert_eq!(eventp.stats().events_dispatched, 1);
+        assert!(eventp.contains(rx_fd));
+
+        // Unlike the other methods, it does not need the subscriber.
+        eventp.delete(rx_fd).unwrap();
+        endpoint.wake().unwrap();
+        let err = endpoint.delete_remote(rx_fd, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn remote_registration_deletes_from_another_thread() {
+        use crate::tri_subscriber::WithHandler;
+        use crate::{RemoteRegistration, Subscriber as _};
+
+        let (endpoint, handle, stop) = spawn_reactor();
+        let register = || -> (std::os::fd::RawFd, RemoteRegistration) {
+            let remote = endpoint.clone();
+            endpoint
+                .call_blocking(move |mut ep| {
+                    let efd = EventFd::from_flags(EfdFlags::EFD_NONBLOCK)?;
+                    let fd = efd.as_raw_fd();
+                    let registration = crate::interest()
+                        .read()
+                        .with_fd(efd)
+                        .with_handler(|| {})
+                        .register_guarded(&mut ep)?;
+                    Ok((fd, registration.into_remote(remote)))
+                })
+                .unwrap()
+        };
+        let contains = |fd| endpoint.call_blocking(move |ep| Ok(ep.0.contains(fd)));
+
+        let (fd, registration) = register();
+        assert!(contains(fd).unwrap());
+        // Sent before the call of `contains`, so run before it.
+        drop(registration);
+        assert!(!contains(fd).unwrap());
+
+        let (fd, registration) = register();
+        registration.deregister().unwrap();
+        assert!(!contains(fd).unwrap());
+
+        shutdown(stop, handle);
+    }
+
+    #[test]
+    fn batch_threads_its_scratch_state_through_the_closures() {
+        let (endpoint, handle, stop) = spawn_reactor();
+
+        let batch = Batch::with_scratch(Vec::new())
+            .then(|_, seen: &mut Vec<u32>| seen.push(1))
+            .then(|mut ep, seen| {
+                if ep.delete(424242).is_err() {
+                    seen.push(2);
+                }
+            })
+            .then(|_, seen| seen.push(seen.len() as u32 + 1));
+        assert_eq!(batch.len(), 3);
+        assert_eq!(endpoint.call_batch_blocking(batch).unwrap(), [1, 2, 3]);
+        assert_eq!(endpoint.call_batch_blocking(Batch::new()).unwrap(), ());
+
+        shutdown(stop, handle);
+    }
+
+    #[test]
+    fn batches_of_concurrent_senders_do_not_interleave() {
+        const THREADS: u32 = 4;
+        const BATCHES: u32 = 50;
+        const CALLS: u32 = 8;
+
+        let (endpoint, handle, stop) = spawn_reactor();
+        // Only ever locked on the reactor thread, one closure at a time.
+        let order = StdArc::new(std::sync::Mutex::new(Vec::new()));
+        let barrier = StdArc::new(Barrier::new(THREADS as usize));
+
+        let senders: Vec<_> = (0..THREADS)
+            .map(|sender| {
+                let (endpoint, order, barrier) = (endpoint.clone(), order.clone(), barrier.clone());
+                thread::spawn(move || {
+                    barrier.wait();
+                    for batch in 0..BATCHES {
+                        let calls = (0..CALLS).fold(Batch::new(), |calls, call| {
+                            let order = order.clone();
+                            calls.then(move |_, _| {
+                                order.lock().unwrap().push((sender, batch, call));
+                            })
+                        });
+                        endpoint.call_batch(calls).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for sender in senders {
+            sender.join().unwrap();
+        }
+        // A fence: the batches were all sent before it.
+        endpoint.call_blocking(|_| Ok(())).unwrap();
+
+        let order = order.lock().unwrap();
+        assert_eq!(order.len() as u32, THREADS * BATCHES * CALLS);
+        let mut next_batch = [0; THREADS as usize];
+        for run in order.chunks(CALLS as usize) {
+            let (sender, batch, _) = run[0];
+            // Each batch runs whole, and those of a sender in the order sent.
+            assert_eq!(batch, next_batch[sender as usize]);
+            next_batch[sender as usize] += 1;
+            for (call, &entry) in run.iter().enumerate() {
+                assert_eq!(entry, (sender, batch, call as u32));
+            }
+        }
+
+        shutdown(stop, handle);
+    }
+
+    #[test]
+    fn remote_registry_registers_a_pipe_from_another_thread() {
+        use std::io::Write;
+        use std::os::fd::OwnedFd;
+
+        use crate::tri_subscriber::WithHandler;
+
+        let (endpoint, handle, stop) = spawn_reactor();
+        let registry = RemoteRegistry::new(endpoint);
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let mut write = std::fs::File::from(write);
+        let (tx, rx) = mpsc::channel();
+
+        let registrar = registry.clone();
+        let fd =
+            thread::spawn(move || {
+                let subscriber = crate::interest().read().with_fd(read).with_handler(
+                    move |pipe: &mut OwnedFd| {
+                        let mut buf = [0; 16];
+                        let n = nix::unistd::read(&*pipe, &mut buf).unwrap();
+                        tx.send((buf[..n].to_vec(), thread::current().id()))
+                            .unwrap();
+                    },
+                );
+                registrar.register_remote(subscriber).unwrap()
+            })
+            .join()
+            .unwrap();
+
+        write.write_all(b"ping").unwrap();
+        let (data, tid) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(data, b"ping");
+        assert_eq!(tid, handle.thread().id());
+
+        // The error of the add on the loop comes back: regular files cannot be
+        // polled.
+        let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+        let err = registry
+            .register_remote(crate::interest().read().with_fd(file).with_handler(|| {}))
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        registry
+            .modify_remote(fd, crate::interest().read().edge_triggered())
+            .unwrap();
+        registry.delete_remote(fd).unwrap();
+        let err = registry.delete_remote(fd).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        shutdown(stop, handle);
+    }
 }
@@ -0,0 +1,474 @@
+//! Replays the batches of events captured by
+//! [`Eventp::enable_capture`](crate::Eventp::enable_capture), to reproduce a
+//! dispatch order in a test.
+//!
+//! Raw fds do not match from one run to the next, so the capture keys every event
+//! by the registration of its subscriber: the first subscriber added once the
+//! capture is enabled is 0, the next one 1, and so on, counting the subscribers
+//! added by handlers as well. [`run`] dispatches every batch to the subscribers of
+//! another loop, numbered the same way from the call to
+//! [`Eventp::enable_replay`](crate::Eventp::enable_replay). Registering the same
+//! subscribers, in the same order, replays the events to the same handlers.
+//! Events of subscribers registered before the capture was enabled are not
+//! captured.
+//!
+//! The handlers run as in the captured session, but the fds are not ready: a
+//! handler reading its nonblocking fd gets `EAGAIN`, or whatever the fd holds in the test.
+//! Replay is meant for handlers whose behaviour depends on the events, such as
+//! state machines, and on the order they come in.
+//!
+//! # Format
+//!
+//! A capture starts with the magic `b"EVPCAP"` and the format version, a
+//! little-endian `u16`, currently [`FORMAT_VERSION`]. Each batch follows as the
+//! little-endian `u32` length in bytes of its events, then each event as its
+//! registration number, a little-endian `u64`, and its raw
+//! [`events`](crate::Event::as_raw) mask, a little-endian `u32`. A batch is written
+//! before it is dispatched, so a capture cut short, e.g. by a crash in a handler,
+//! ends with whole batches, or a truncated one, which is not replayed.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::cell::RefCell;
+//! use std::fs::File;
+//! use std::rc::Rc;
+//!
+//! use eventp::{interest, replay, tri_subscriber::WithHandler, Event, Eventp, Subscriber};
+//! use nix::sys::eventfd::{EfdFlags, EventFd};
+//!
+//! /// Registers the subscriber under test, logging the events of its handler.
+//! fn register(eventp: &mut Eventp, efd: EventFd) -> io::Result<Rc<RefCell<Vec<Event>>>> {
+//!     let seen = Rc::new(RefCell::new(Vec::new()));
+//!     let log = seen.clone();
+//!     interest()
+//!         .read()
+//!         .with_fd(efd)
+//!         .with_handler(move |efd: &mut EventFd, event: Event| {
+//!             let _ = efd.read();
+//!             log.borrow_mut().push(event);
+//!         })
+//!         .register_into(eventp)?;
+//!     Ok(seen)
+//! }
+//!
+//! # fn main() -> io::Result<()> {
+//! let path = std::env::temp_dir().join("eventp-replay-example.capture");
+//! let mut eventp = Eventp::default();
+//! eventp.enable_capture(File::create(&path)?)?;
+//! let seen = register(&mut eventp, EventFd::from_value_and_flags(1, EfdFlags::EFD_NONBLOCK)?)?;
+//! eventp.run_once()?;
+//! eventp.disable_capture()?;
+//!
+//! // Later, in a test.
+//! let mut eventp = Eventp::default();
+//! eventp.enable_replay();
+//! // Nonblocking, as it is never ready.
+//! let replayed = register(&mut eventp, EventFd::from_flags(EfdFlags::EFD_NONBLOCK)?)?;
+//! let summary = replay::run(File::open(&path)?, &mut eventp)?;
+//! assert_eq!(summary.batches, 1);
+//! assert_eq!(*replayed.borrow(), *seen.borrow());
+//! # std::fs::remove_file(&path)?;
+//! # Ok(()) }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::os::fd::RawFd;
+
+use rustc_hash::FxHashMap;
+
+use crate::epoll::{EpollEvent, EpollTimeout};
+use crate::thin::ThinBoxSubscriber;
+use crate::{Event, Eventp};
+
+/// The version of the capture format written by this crate.
+pub const FORMAT_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 6] = b"EVPCAP";
+
+/// The length of a captured event: its registration number and events mask.
+const EVENT_LEN: usize = 12;
+
+/// What [`run`] replayed.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct Replayed {
+    /// The batches read from the capture, including those left without any event to
+    /// dispatch.
+    pub batches: u64,
+
+    /// The events dispatched to a handler.
+    pub events: u64,
+
+    /// The events whose subscriber was never registered, or already deleted.
+    pub skipped: u64,
+
+    /// Whether the capture ended with a truncated batch, which was not replayed.
+    pub truncated: bool,
+}
+
+/// Dispatches every batch of the capture read from `reader` to the subscribers of
+/// `eventp`, found by their registration number, without waiting on the epoll.
+///
+/// For more information, see the [mod-level documentation](self).
+///
+/// # Errors
+///
+/// [`io::ErrorKind::InvalidData`] if `reader` does not start with a capture of
+/// [`FORMAT_VERSION`], or holds a malformed batch. Also forwards any error from
+/// `reader`, or returned by a handler under
+/// [`ErrorPolicy::Propagate`](crate::ErrorPolicy::Propagate), ending the replay.
+///
+/// # Panics
+///
+/// Panics if [`Eventp::enable_replay`] was not called on `eventp`, or if called
+/// from within an event handler.
+pub fn run<R: Read>(mut reader: R, eventp: &mut Eventp) -> io::Result<Replayed> {
+    assert!(
+        eventp.keys.is_some(),
+        "Call to `replay::run` without `Eventp::enable_replay`"
+    );
+    read_header(&mut reader)?;
+
+    let mut replayed = Replayed::default();
+    let mut buf = Vec::new();
+    loop {
+        let mut len = [0; 4];
+        match read_full(&mut reader, &mut len)? {
+            0 => return Ok(replayed),
+            4 => {}
+            _ => {
+                replayed.truncated = true;
+                return Ok(replayed);
+            }
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len % EVENT_LEN != 0 {
+            return Err(invalid_data(format!(
+                "batch of {len} bytes, not a multiple of {EVENT_LEN}"
+            )));
+        }
+        buf.resize(len, 0);
+        if read_full(&mut reader, &mut buf)? < len {
+            replayed.truncated = true;
+            return Ok(replayed);
+        }
+        replayed.batches += 1;
+
+        let keys = eventp.keys.as_ref().unwrap();
+        let mut batch = Vec::with_capacity(len / EVENT_LEN);
+        for entry in buf.chunks_exact(EVENT_LEN) {
+            let key = u64::from_le_bytes(entry[..8].try_into().unwrap());
+            let event = Event::from_raw(u32::from_le_bytes(entry[8..].try_into().unwrap()));
+            match keys.fds.get(&key) {
+                Some(fd) => {
+                    let data = eventp.registered[fd].to_data();
+                    batch.push(EpollEvent::new(event.bitflags(), data));
+                }
+                None => replayed.skipped += 1,
+            }
+        }
+        if batch.is_empty() {
+            continue;
+        }
+
+        // Dispatched as events left over by a budget, which are never captured, and
+        // after those of an earlier budgeted run, if any.
+        eventp.pending.extend(batch);
+        replayed.events += eventp.wait_and_dispatch(EpollTimeout::ZERO, usize::MAX)? as u64;
+    }
+}
+
+fn read_header(reader: &mut impl Read) -> io::Result<()> {
+    let mut header = [0; 8];
+    if read_full(reader, &mut header)? < header.len() || &header[..6] != MAGIC {
+        return Err(invalid_data("not an eventp capture".to_string()));
+    }
+    let version = u16::from_le_bytes([header[6], header[7]]);
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "capture format version {version}, only {FORMAT_VERSION} is supported"
+        )));
+    }
+    Ok(())
+}
+
+/// Reads into `buf` until it is full or the end of `reader`, returning how many
+/// bytes were read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The registration numbers of the subscribers added since the capture or the
+/// replay was enabled.
+#[derive(Default)]
+pub(crate) struct Keys {
+    by_fd: FxHashMap<RawFd, u64>,
+    fds: FxHashMap<u64, RawFd>,
+    next: u64,
+}
+
+impl Keys {
+    pub(crate) fn added(&mut self, fd: RawFd) {
+        self.by_fd.insert(fd, self.next);
+        self.fds.insert(self.next, fd);
+        self.next += 1;
+    }
+
+    pub(crate) fn deleted(&mut self, fd: RawFd) {
+        if let Some(key) = self.by_fd.remove(&fd) {
+            self.fds.remove(&key);
+        }
+    }
+}
+
+/// Writes the batches of [`Eventp::enable_capture`].
+pub(crate) struct Capture {
+    writer: Box<dyn Write>,
+    buf: Vec<u8>,
+    /// The first write error, after which nothing more is written.
+    error: Option<io::Error>,
+}
+
+impl Capture {
+    /// Writes the header of a capture into `writer`.
+    pub(crate) fn new(mut writer: Box<dyn Write>) -> io::Result<Self> {
+        let mut header = [0; 8];
+        header[..6].copy_from_slice(MAGIC);
+        header[6..].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        writer.write_all(&header)?;
+        Ok(Self {
+            writer,
+            buf: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Writes the events of `batch` whose subscriber has a registration number.
+    pub(crate) fn write(&mut self, keys: &Keys, batch: &[EpollEvent]) {
+        if self.error.is_some() {
+            return;
+        }
+        self.buf.clear();
+        self.buf.extend_from_slice(&[0; 4]);
+        for ev in batch {
+            // SAFETY: As for the dispatched events in `wait_and_dispatch`, which
+            // calls this before any handler runs.
+            let subscriber = unsafe { ThinBoxSubscriber::<Eventp>::from_data(ev.data()) };
+            // Evicted subscribers keep their header, and the fd may be reused.
+            if !subscriber.is_current(ev.data()) || subscriber.try_deref().is_none() {
+                continue;
+            }
+            let Some(key) = keys.by_fd.get(subscriber.raw_fd_ref()) else {
+                continue;
+            };
+            self.buf.extend_from_slice(&key.to_le_bytes());
+            self.buf
+                .extend_from_slice(&ev.events().bits().to_le_bytes());
+        }
+        let len = (self.buf.len() - 4) as u32;
+        self.buf[..4].copy_from_slice(&len.to_le_bytes());
+        if let Err(e) = self.writer.write_all(&self.buf) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Flushes the writer, and returns the first error, if any.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs::File;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::tri_subscriber::WithHandler;
+    use crate::{interest, Pinned, Subscriber};
+
+    type Effects = Rc<RefCell<Vec<(&'static str, u32)>>>;
+
+    /// A writer into a buffer the test keeps.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A pipe whose read end never blocks.
+    fn pipe() -> (OwnedFd, File) {
+        let mut fds = [0; 2];
+        // SAFETY: `fds` is writable for two fds.
+        let ret = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) };
+        assert_eq!(ret, 0, "{}", io::Error::last_os_error());
+        // SAFETY: Just opened, and owned by nothing else.
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    /// Registers a reader logging every event, and deleting itself on hangup.
+    fn register(eventp: &mut Eventp, label: &'static str, fd: OwnedFd, effects: &Effects) {
+        let effects = effects.clone();
+        interest()
+            .read()
+            .with_fd(File::from(fd))
+            .with_handler(
+                move |file: &mut File, event: Event, mut eventp: Pinned<'_, Eventp>| {
+                    effects.borrow_mut().push((label, event.as_raw()));
+                    let mut buf = [0; 64];
+                    while let Ok(1..) = file.read(&mut buf) {}
+                    if event.is_hangup() {
+                        eventp.delete(file.as_raw_fd()).unwrap();
+                    }
+                },
+            )
+            .register_into(eventp)
+            .unwrap();
+    }
+
+    /// Captures a session over two pipes, returning the capture and the effects.
+    fn capture_session() -> (Vec<u8>, Vec<(&'static str, u32)>) {
+        let effects = Effects::default();
+        let writer = Shared::default();
+        let mut eventp = Eventp::default();
+        let (unkeyed, mut unkeyed_writer) = pipe();
+        register(&mut eventp, "unkeyed", unkeyed, &effects);
+        eventp.enable_capture(writer.clone()).unwrap();
+
+        let (a, mut a_writer) = pipe();
+        let (b, b_writer) = pipe();
+        register(&mut eventp, "a", a, &effects);
+        register(&mut eventp, "b", b, &effects);
+        unkeyed_writer.write_all(b"x").unwrap();
+        a_writer.write_all(b"1").unwrap();
+        eventp.run_once().unwrap();
+        drop(b_writer);
+        a_writer.write_all(b"2").unwrap();
+        eventp.run_once().unwrap();
+        eventp.disable_capture().unwrap();
+
+        let effects = effects.borrow().clone();
+        let capture = writer.0.borrow().clone();
+        (capture, effects)
+    }
+
+    fn replay(capture: &[u8]) -> io::Result<(Replayed, Vec<(&'static str, u32)>)> {
+        let effects = Effects::default();
+        let mut eventp = Eventp::default();
+        eventp.enable_replay();
+        // Never ready: every event comes from the capture.
+        let (a, _a_writer) = pipe();
+        let (b, _b_writer) = pipe();
+        register(&mut eventp, "a", a, &effects);
+        register(&mut eventp, "b", b, &effects);
+        let replayed = run(capture, &mut eventp)?;
+        let effects = effects.borrow().clone();
+        Ok((replayed, effects))
+    }
+
+    #[test]
+    fn replay_of_a_pipe_session_has_the_same_effects() {
+        let (capture, captured) = capture_session();
+        let keyed: Vec<_> = captured
+            .iter()
+            .copied()
+            .filter(|&(label, _)| label != "unkeyed")
+            .collect();
+        assert_eq!(keyed.len(), 3);
+        assert!(keyed
+            .iter()
+            .any(|&(label, raw)| { label == "b" && Event::from_raw(raw).is_hangup() }));
+
+        let (replayed, effects) = replay(&capture).unwrap();
+        assert_eq!(effects, keyed);
+        assert_eq!(
+            replayed,
+            Replayed {
+                batches: 2,
+                events: 3,
+                skipped: 0,
+                truncated: false,
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_batch_is_not_replayed() {
+        let (capture, _) = capture_session();
+        let (replayed, effects) = replay(&capture[..capture.len() - 1]).unwrap();
+        assert!(replayed.truncated);
+        assert_eq!(replayed.batches, 1);
+        assert_eq!(effects, [("a", libc::EPOLLIN as u32)]);
+
+        // Cut in the length of the second batch.
+        let first = 8 + 4 + EVENT_LEN;
+        let (replayed, _) = replay(&capture[..first + 2]).unwrap();
+        assert_eq!((replayed.batches, replayed.truncated), (1, true));
+    }
+
+    #[test]
+    fn deleted_subscribers_are_skipped() {
+        let (capture, _) = capture_session();
+        let effects = Effects::default();
+        let mut eventp = Eventp::default();
+        eventp.enable_replay();
+        let (a, _a_writer) = pipe();
+        register(&mut eventp, "a", a, &effects);
+
+        let replayed = run(&capture[..], &mut eventp).unwrap();
+        assert_eq!((replayed.events, replayed.skipped), (2, 1));
+    }
+
+    #[test]
+    fn other_files_and_versions_are_rejected() {
+        let mut eventp = Eventp::default();
+        eventp.enable_replay();
+        let err = run(&b"not a capture"[..], &mut eventp).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let err = run(&header[..], &mut eventp).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        header[6..].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header.extend_from_slice(&5u32.to_le_bytes());
+        let err = run(&header[..], &mut eventp).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[should_panic(expected = "without `Eventp::enable_replay`")]
+    fn replay_needs_numbered_registrations() {
+        let mut eventp = Eventp::default();
+        let _ = run(&b""[..], &mut eventp);
+    }
+}
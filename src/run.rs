@@ -0,0 +1,101 @@
+use std::any::Any;
+use std::os::fd::RawFd;
+use std::{fmt, io};
+
+/// Why [`Eventp::run`](crate::Eventp::run) returned.
+#[non_exhaustive]
+pub enum RunOutcome {
+    /// [`Eventp::stop`](crate::Eventp::stop) was called, by a handler or between
+    /// two batches.
+    Stopped,
+
+    /// `epoll_wait` failed, with another error than `EINTR`, which is retried.
+    EpollError(io::Error),
+
+    /// The handler of `fd` failed under
+    /// [`ErrorPolicy::Propagate`](crate::ErrorPolicy::Propagate), the default. The
+    /// rest of its batch was still dispatched, and later errors of the batch were
+    /// dropped. Never returned under the other policies.
+    HandlerError {
+        /// The fd of the failed handler.
+        fd: RawFd,
+        /// The error it returned.
+        error: io::Error,
+    },
+
+    /// The handler of `fd` panicked, or with `fd` of `None`, a closure run by the
+    /// loop outside of any handler, such as a [deferred](crate::EventpOps::defer)
+    /// one. The rest of its batch was not dispatched, but is kept for the next run.
+    Panicked {
+        /// The fd of the handler that panicked, if any.
+        fd: Option<RawFd>,
+        /// The payload of the panic, to resume it with
+        /// [`resume_unwind`](std::panic::resume_unwind).
+        payload: Box<dyn Any + Send>,
+    },
+}
+
+impl fmt::Debug for RunOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stopped => f.write_str("Stopped"),
+            Self::EpollError(e) => f.debug_tuple("EpollError").field(e).finish(),
+            Self::HandlerError { fd, error } => f
+                .debug_struct("HandlerError")
+                .field("fd", fd)
+                .field("error", error)
+                .finish(),
+            Self::Panicked { fd, .. } => f
+                .debug_struct("Panicked")
+                .field("fd", fd)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// The loop is already dispatching a batch, see
+/// [`Eventp::try_run_once_with_timeout`](crate::Eventp::try_run_once_with_timeout).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct AlreadyRunning {
+    /// The fd whose handler is running, or -1 between two handlers of the batch.
+    pub fd: RawFd,
+}
+
+impl fmt::Display for AlreadyRunning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the loop is already running, handling fd {}", self.fd)
+    }
+}
+
+impl std::error::Error for AlreadyRunning {}
+
+/// What [`Eventp::drain`](crate::Eventp::drain) dispatched.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct DrainSummary {
+    /// The batches dispatched, not counting the last wait, which found none ready.
+    pub batches: u64,
+
+    /// The events dispatched to a handler.
+    pub events: u64,
+
+    /// Whether the drain stopped at its cap on batches, before finding the kernel
+    /// with nothing ready.
+    pub more_may_remain: bool,
+}
+
+/// What ended a batch early, told apart for [`RunOutcome`], and merged into the
+/// `io::Error` of the `run_*` methods otherwise.
+pub(crate) enum Exit {
+    Epoll(io::Error),
+    Handler { fd: RawFd, error: io::Error },
+}
+
+impl From<Exit> for io::Error {
+    fn from(exit: Exit) -> Self {
+        match exit {
+            Exit::Epoll(error) | Exit::Handler { error, .. } => error,
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Registering subscribers that borrow from their environment, for the duration of
+//! a [`scope`].
+//!
+//! A subscriber added with [`EventpOpsAdd::add`](crate::EventpOpsAdd::add) may stay
+//! registered for as long as the loop lives, so it must be `'static`. Within a
+//! scope, [`Scope::register`] also takes subscribers borrowing data that outlives
+//! the call to `scope`, and every one of them still registered is deleted and
+//! dropped before `scope` returns, also when it unwinds.
+
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::pin::Pin;
+use std::time::Instant;
+use std::{io, mem};
+
+use crate::epoll::EpollTimeout;
+use crate::subscriber::{Handler, HasInterest};
+use crate::thin::ThinBoxSubscriber;
+use crate::{Event, Eventp, EventpOps, EventpOpsAdd, Interest, Pinned};
+
+/// Runs `f` with a [`Scope`] of `eventp`, to register subscribers borrowing from
+/// the environment. Those still registered when `f` returns, or panics, are
+/// deleted and dropped before `scope` does.
+///
+/// ```rust
+/// # use std::io;
+/// use std::cell::Cell;
+/// use std::time::{Duration, Instant};
+///
+/// use eventp::{tri_subscriber::WithHandler, Eventp};
+/// use nix::sys::eventfd::EventFd;
+///
+/// # fn main() -> io::Result<()> {
+/// let mut eventp = Eventp::default();
+/// let wakeups = Cell::new(0);
+///
+/// eventp::scope(&mut eventp, |scope| {
+///     let subscriber = eventp::interest()
+///         .read()
+///         .with_fd(EventFd::from_value(1)?)
+///         .with_handler(|eventfd: &mut EventFd| {
+///             let _ = eventfd.read();
+///             // Borrowed, not `Rc`-shared.
+///             wakeups.set(wakeups.get() + 1);
+///         });
+///     scope.register(subscriber)?;
+///     scope.run_until(Instant::now() + Duration::from_millis(10))
+/// })?;
+///
+/// assert_eq!(wakeups.get(), 1);
+/// # Ok(()) }
+/// ```
+///
+/// Subscribers cannot borrow what does not outlive `scope`:
+///
+/// ```rust,compile_fail
+/// # use std::cell::Cell;
+/// # use eventp::{tri_subscriber::WithHandler, Eventp};
+/// # use nix::sys::eventfd::EventFd;
+/// # let mut eventp = Eventp::default();
+/// eventp::scope(&mut eventp, |scope| {
+///     let wakeups = Cell::new(0);
+///     let subscriber = eventp::interest()
+///         .read()
+///         .with_fd(EventFd::new().unwrap())
+///         .with_handler(|| wakeups.set(wakeups.get() + 1));
+///     // `wakeups` does not live long enough.
+///     scope.register(subscriber).unwrap();
+/// });
+/// ```
+///
+/// nor give up what they borrow while it is running:
+///
+/// ```rust,compile_fail
+/// # use std::cell::Cell;
+/// # use eventp::{tri_subscriber::WithHandler, Eventp};
+/// # use nix::sys::eventfd::EventFd;
+/// # let mut eventp = Eventp::default();
+/// let wakeups = Cell::new(0);
+/// eventp::scope(&mut eventp, |scope| {
+///     let subscriber = eventp::interest()
+///         .read()
+///         .with_fd(EventFd::new().unwrap())
+///         .with_handler(|| wakeups.set(wakeups.get() + 1));
+///     scope.register(subscriber).unwrap();
+///     // `wakeups` is still borrowed.
+///     drop(wakeups);
+/// });
+/// ```
+///
+/// And the scope itself cannot escape:
+///
+/// ```rust,compile_fail
+/// # use eventp::Eventp;
+/// # let mut eventp = Eventp::default();
+/// let mut escaped = None;
+/// eventp::scope(&mut eventp, |scope| escaped = Some(scope));
+/// ```
+pub fn scope<'env, F, T>(eventp: &mut Eventp, f: F) -> T
+where
+    F: FnOnce(&mut Scope<'_, 'env>) -> T,
+{
+    let mut scope = Scope {
+        eventp,
+        registered: Vec::new(),
+        _env: PhantomData,
+    };
+    f(&mut scope)
+}
+
+/// The subscribers registered within a [`scope`], which derefs to its `Eventp`.
+pub struct Scope<'a, 'env> {
+    eventp: &'a mut Eventp,
+    /// The fd of every subscriber registered by the scope.
+    registered: Vec<RawFd>,
+    /// Invariant, so `'env` cannot be shortened to fit a borrow.
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'a, 'env> Scope<'a, 'env> {
+    /// Registers `subscriber` until the end of the scope at the latest, with the
+    /// interest from [`HasInterest`], and returns its raw fd.
+    ///
+    /// Like a `'static` subscriber, it can be modified or deleted earlier, from
+    /// any handler of the loop.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`EventpOpsAdd::add`].
+    pub fn register<S>(&mut self, subscriber: S) -> io::Result<RawFd>
+    where
+        S: AsFd + Handler<Eventp> + HasInterest + 'env,
+    {
+        let interest = subscriber.interest().get();
+        self.register_with_interest(subscriber, interest)
+    }
+
+    /// Same as [`register`](Self::register), but with an explicit interest, so `S`
+    /// does not need to implement [`HasInterest`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`EventpOpsAdd::add`].
+    pub fn register_with_interest<S>(
+        &mut self,
+        subscriber: S,
+        interest: Interest,
+    ) -> io::Result<RawFd>
+    where
+        S: AsFd + Handler<Eventp> + 'env,
+    {
+        let raw_fd = subscriber.as_fd().as_raw_fd();
+        let inner: Box<dyn ScopedSubscriber + 'env> = Box::new(subscriber);
+        // SAFETY: Only the lifetime changes. The subscriber is dropped before the
+        //         scope ends, when it is deleted or by `Scope::drop`, and `'env`
+        //         outlives the scope.
+        let inner: Box<dyn ScopedSubscriber> = unsafe { mem::transmute(inner) };
+
+        let subscriber = ThinBoxSubscriber::with_interest(Erased(inner), interest);
+        self.eventp.add(subscriber)?;
+        self.registered.push(raw_fd);
+        Ok(raw_fd)
+    }
+
+    /// Returns the loop, to add, modify or delete subscribers as a handler would.
+    pub fn eventp(&mut self) -> Pinned<'_, Eventp> {
+        // SAFETY: `Pinned` only exposes operations that do not move the loop.
+        Pinned(unsafe { Pin::new_unchecked(&mut *self.eventp) })
+    }
+
+    /// See [`Eventp::run_once`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Eventp::run_once`].
+    pub fn run_once(&mut self) -> io::Result<()> {
+        self.eventp.run_once()
+    }
+
+    /// See [`Eventp::run_once_with_timeout`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Eventp::run_once_with_timeout`].
+    pub fn run_once_with_timeout(&mut self, timeout: EpollTimeout) -> io::Result<()> {
+        self.eventp.run_once_with_timeout(timeout)
+    }
+
+    /// See [`Eventp::run_until`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Eventp::run_until`].
+    pub fn run_until(&mut self, deadline: Instant) -> io::Result<()> {
+        self.eventp.run_until(deadline)
+    }
+}
+
+impl Deref for Scope<'_, '_> {
+    type Target = Eventp;
+
+    fn deref(&self) -> &Eventp {
+        self.eventp
+    }
+}
+
+impl Drop for Scope<'_, '_> {
+    fn drop(&mut self) {
+        // A handler may have panicked out of a batch, with subscribers still
+        // waiting to be dropped.
+        self.eventp.end_abandoned_batch();
+
+        for fd in self.registered.drain(..) {
+            // Unless deleted already, and maybe replaced by a `'static` subscriber.
+            // Any scoped one is from this scope, the only one of the loop.
+            let scoped = self.eventp.registered.get(&fd).and_then(|s| s.try_deref());
+            if scoped.and_then(|s| s.downcast_ref::<Erased>()).is_none() {
+                continue;
+            }
+            if self.eventp.delete(fd).is_err() {
+                // The kernel refused, but the borrows must end anyway. Kept
+                // allocated, as the registration may be left in the kernel.
+                self.eventp
+                    .unregister(fd, true, crate::DeregisterReason::ExplicitDelete);
+            }
+        }
+    }
+}
+
+/// The subscriber traits without `Any`, which requires `'static`.
+trait ScopedSubscriber: AsFd + Handler<Eventp> {}
+
+impl<S: AsFd + Handler<Eventp>> ScopedSubscriber for S {}
+
+/// A scoped subscriber, with the lifetime of its borrows erased.
+struct Erased(Box<dyn ScopedSubscriber>);
+
+impl AsFd for Erased {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl Handler<Eventp> for Erased {
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Eventp>) {
+        self.0.handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, event: Event, eventp: Pinned<'_, Eventp>) -> io::Result<()> {
+        self.0.try_handle(event, eventp)
+    }
+
+    fn on_idle(&mut self, eventp: Pinned<'_, Eventp>) -> bool {
+        self.0.on_idle(eventp)
+    }
+
+    fn on_shutdown(&mut self, eventp: Pinned<'_, Eventp>) {
+        self.0.on_shutdown(eventp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use super::*;
+    use crate::tri_subscriber::WithHandler;
+    use crate::{interest, Subscriber};
+
+    fn ready_eventfd() -> EventFd {
+        EventFd::from_value_and_flags(1, EfdFlags::EFD_NONBLOCK).unwrap()
+    }
+
+    fn poll_timeout() -> EpollTimeout {
+        EpollTimeout::try_from(100).unwrap()
+    }
+
+    /// Sets its flag when dropped.
+    struct DropFlag<'a>(&'a Cell<bool>);
+
+    impl Drop for DropFlag<'_> {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    #[test]
+    fn borrowing_subscribers_are_gone_after_the_scope() {
+        let mut eventp = Eventp::default();
+        let seen = RefCell::new(Vec::new());
+        let dropped = Cell::new(false);
+
+        let fd = scope(&mut eventp, |scope| {
+            let (flag, seen) = (DropFlag(&dropped), &seen);
+            let fd = scope
+                .register(interest().read().with_fd(ready_eventfd()).with_handler(
+                    move |eventfd: &mut EventFd| {
+                        let _ = &flag;
+                        seen.borrow_mut().push(eventfd.read().unwrap());
+                    },
+                ))
+                .unwrap();
+            scope.run_once_with_timeout(poll_timeout()).unwrap();
+            assert!(scope.contains(fd));
+            assert!(!dropped.get());
+            fd
+        });
+
+        assert!(!eventp.contains(fd));
+        assert!(dropped.get());
+        assert_eq!(*seen.borrow(), [1]);
+    }
+
+    #[test]
+    fn subscribers_are_gone_after_a_panic_in_the_scope() {
+        let mut eventp = Eventp::default();
+        let dropped = Cell::new(false);
+        let fd = Cell::new(-1);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let _: () = scope(&mut eventp, |scope| {
+                let flag = DropFlag(&dropped);
+                let subscriber =
+                    interest()
+                        .read()
+                        .with_fd(ready_eventfd())
+                        .with_handler(move || {
+                            let _ = &flag;
+                        });
+                fd.set(scope.register(subscriber).unwrap());
+                panic!("scope panics");
+            });
+        }));
+        assert!(result.is_err());
+        assert!(!eventp.contains(fd.get()));
+        assert!(dropped.get());
+    }
+
+    #[test]
+    fn subscribers_are_gone_after_a_panic_in_their_handler() {
+        let mut eventp = Eventp::default();
+        let dropped = Cell::new(false);
+        let fd = Cell::new(-1);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            scope(&mut eventp, |scope| {
+                let flag = DropFlag(&dropped);
+                let subscriber = interest().read().with_fd(ready_eventfd()).with_handler(
+                    move |eventfd: &mut EventFd,
+                          mut eventp: Pinned<'_, Eventp>|
+                          -> io::Result<()> {
+                        let _ = &flag;
+                        // Left for the end of the batch, which the panic skips.
+                        eventp.delete(eventfd.as_fd().as_raw_fd()).unwrap();
+                        panic!("handler panics");
+                    },
+                );
+                fd.set(scope.register(subscriber).unwrap());
+                scope.run_once_with_timeout(poll_timeout())
+            })
+        }));
+        assert!(result.is_err());
+        assert!(!eventp.contains(fd.get()));
+        assert!(dropped.get());
+
+        // And the loop runs again.
+        eventp.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+    }
+
+    #[test]
+    fn subscribers_deleted_and_replaced_are_left_alone() {
+        // Declared first, to outlive the loop.
+        let eventfd = ready_eventfd();
+        let mut eventp = Eventp::default();
+        let raw = eventfd.as_fd().as_raw_fd();
+
+        scope(&mut eventp, |scope| {
+            // Only a borrowed fd, so deleting the subscriber does not close it.
+            let subscriber = interest()
+                .read()
+                .with_fd(eventfd.as_fd())
+                .with_handler(|| {});
+            scope.register(subscriber).unwrap();
+            scope.eventp().delete(raw).unwrap();
+
+            // SAFETY: `eventfd` outlives the loop.
+            let fd = unsafe { BorrowedFd::borrow_raw(raw) };
+            interest()
+                .read()
+                .with_fd(fd)
+                .with_handler(|| {})
+                .register_into(&mut scope.eventp())
+                .unwrap();
+        });
+        assert!(eventp.contains(raw));
+    }
+}
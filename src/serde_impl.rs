@@ -0,0 +1,158 @@
+//! `Serialize` and `Deserialize` for [`Event`] and [`Interest`], as lists of flag
+//! names such as `["EPOLLIN", "EPOLLET"]`.
+//!
+//! Bits without a name are kept as one hexadecimal string, e.g. `"0x4000000"`, so
+//! that any raw mask round-trips. An unknown name fails to deserialize. An
+//! [`Interest`] is written as its [`as_raw`](Interest::as_raw) mask, without the
+//! settings kept by the loop.
+
+use std::fmt;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::epoll::EpollFlags;
+use crate::{Event, Interest};
+
+impl Serialize for Event {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_mask(self.as_raw(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_seq(MaskVisitor)
+            .map(Event::from_raw)
+    }
+}
+
+impl Serialize for Interest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_mask(self.as_raw(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_seq(MaskVisitor)
+            .map(Interest::from_raw)
+    }
+}
+
+fn serialize_mask<S: Serializer>(mask: u32, serializer: S) -> Result<S::Ok, S::Error> {
+    let flags = EpollFlags::from_bits_retain(mask as i32);
+    let unnamed = flags.difference(EpollFlags::all()).bits() as u32;
+
+    let len = flags.iter_names().count() + usize::from(unnamed != 0);
+    let mut seq = serializer.serialize_seq(Some(len))?;
+    for (name, _) in flags.iter_names() {
+        seq.serialize_element(name)?;
+    }
+    if unnamed != 0 {
+        seq.serialize_element(&format_args!("{unnamed:#x}"))?;
+    }
+    seq.end()
+}
+
+struct MaskVisitor;
+
+impl<'de> Visitor<'de> for MaskVisitor {
+    type Value = u32;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of epoll flag names")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<u32, A::Error> {
+        let mut mask = 0;
+        while let Some(name) = seq.next_element::<String>()? {
+            mask |= parse_flag(&name)
+                .ok_or_else(|| de::Error::custom(format_args!("unknown epoll flag `{name}`")))?;
+        }
+        Ok(mask)
+    }
+}
+
+/// Parses a flag name, or the hexadecimal bits written for those without one.
+fn parse_flag(name: &str) -> Option<u32> {
+    match name.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => EpollFlags::from_name(name).map(|flag| flag.bits() as u32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T>(value: T, json: &str)
+    where
+        T: Serialize + for<'de> Deserialize<'de> + PartialEq + fmt::Debug,
+    {
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+        assert_eq!(serde_json::from_str::<T>(json).unwrap(), value);
+    }
+
+    #[test]
+    fn every_flag_round_trips_by_name() {
+        for (name, flag) in EpollFlags::all().iter_names() {
+            let json = format!(r#"["{name}"]"#);
+            round_trip(Event::new(flag), &json);
+            round_trip(Interest::new(flag), &json);
+        }
+        round_trip(Event::from_raw(0), "[]");
+    }
+
+    #[test]
+    fn mixed_flags_round_trip() {
+        round_trip(
+            Interest::stream_read_et(),
+            r#"["EPOLLIN","EPOLLRDHUP","EPOLLET"]"#,
+        );
+        round_trip(
+            Event::new(EpollFlags::EPOLLOUT | EpollFlags::EPOLLHUP),
+            r#"["EPOLLOUT","EPOLLHUP"]"#,
+        );
+    }
+
+    #[test]
+    fn unnamed_bits_round_trip_as_hex() {
+        let event = Event::from_raw(EpollFlags::EPOLLIN.bits() as u32 | 1 << 26);
+        round_trip(event, r#"["EPOLLIN","0x4000000"]"#);
+        round_trip(
+            crate::interest().read().with_custom(1 << 14),
+            r#"["EPOLLIN","0x4000"]"#,
+        );
+    }
+
+    #[test]
+    fn interest_is_written_without_the_settings_of_the_loop() {
+        let interest = crate::interest().read().track_deltas().dispatch_priority(3);
+        let json = serde_json::to_string(&interest).unwrap();
+        assert_eq!(json, r#"["EPOLLIN"]"#);
+        assert_eq!(
+            serde_json::from_str::<Interest>(&json).unwrap(),
+            crate::interest().read()
+        );
+        // Bits given as hex are the kernel's, never settings of the loop.
+        assert_eq!(
+            serde_json::from_str::<Interest>(r#"["0x4000000"]"#).unwrap(),
+            crate::interest().with_custom(1 << 26)
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        let err = serde_json::from_str::<Event>(r#"["EPOLLIN","EPOLLFOO"]"#).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown epoll flag `EPOLLFOO`"),
+            "{err}"
+        );
+        assert!(serde_json::from_str::<Interest>(r#"["0xzz"]"#).is_err());
+        assert!(serde_json::from_str::<Interest>("1").is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::{fmt, io, panic};
+
+use crate::remote_endpoint::{remote_endpoint, RemoteEndpoint};
+use crate::Eventp;
+
+/// Runs an `Eventp` on a new thread named `name`, and returns a [`LoopHandle`] to
+/// reach, stop and join it.
+///
+/// `setup` runs on the new thread, before the loop starts, so the subscribers and
+/// fds it creates belong to the loop thread from the beginning, e.g. `!Send` ones
+/// sharing an `Rc`. This returns once `setup` has, so they are registered by then.
+/// The loop then runs until [`LoopHandle::stop`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::io;
+/// use std::cell::Cell;
+///
+/// use eventp::spawn_loop;
+///
+/// thread_local!(static REQUESTS: Cell<u32> = const { Cell::new(0) });
+///
+/// # fn main() -> io::Result<()> {
+/// let handle = spawn_loop("worker", |_eventp| {
+///     // Register the subscribers of the worker here.
+///     Ok(())
+/// })?;
+///
+/// let served = handle.endpoint().call_blocking(|_eventp| {
+///     REQUESTS.with(|requests| requests.set(requests.get() + 1));
+///     Ok(REQUESTS.with(Cell::get))
+/// })?;
+/// assert_eq!(served, 1);
+///
+/// handle.stop()?;
+/// handle.join()
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// - The error of creating the `Eventp` or its remote endpoint, on the new thread.
+/// - The error returned by `setup`, once the thread has ended.
+/// - Otherwise, the [`io::Error`] of spawning the thread.
+///
+/// # Panics
+///
+/// Resumes the panic of `setup`, once the thread has ended.
+pub fn spawn_loop<F>(name: &str, setup: F) -> io::Result<LoopHandle>
+where
+    F: FnOnce(&mut Eventp) -> io::Result<()> + Send + 'static,
+{
+    let stopped = Arc::new(AtomicBool::new(false));
+    let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+
+    let s = Arc::clone(&stopped);
+    let thread = thread::Builder::new()
+        .name(name.to_owned())
+        .spawn(move || {
+            let started = (|| {
+                let mut eventp = Eventp::try_default()?;
+                let endpoint = remote_endpoint()?.register_into(&mut eventp)?;
+                setup(&mut eventp)?;
+                Ok((eventp, endpoint))
+            })();
+            let mut eventp = match started {
+                Ok((eventp, endpoint)) => {
+                    let _ = ready_tx.send(Ok(endpoint));
+                    eventp
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return Ok(());
+                }
+            };
+            drop(ready_tx);
+
+            while !s.load(Ordering::Acquire) {
+                match eventp.run_once() {
+                    Err(e) if e.kind() != io::ErrorKind::Interrupted => return Err(e),
+                    _ => {}
+                }
+            }
+            Ok(())
+        })?;
+
+    match ready_rx.recv() {
+        Ok(Ok(endpoint)) => Ok(LoopHandle {
+            endpoint,
+            stopped,
+            thread: Some(thread),
+        }),
+        // The thread ended without running the loop, failing or panicking.
+        Ok(Err(e)) => {
+            let _ = join(thread);
+            Err(e)
+        }
+        // The thread only drops the sender unused by panicking.
+        Err(_) => match join(thread) {
+            Ok(()) => unreachable!("the loop thread ended before its loop started"),
+            Err(e) => Err(e),
+        },
+    }
+}
+
+/// A loop running on its own thread, returned by [`spawn_loop`].
+///
+/// Dropping it stops the loop and waits for its thread, discarding how it ended.
+/// Call [`join`](Self::join) instead to get it.
+pub struct LoopHandle {
+    endpoint: RemoteEndpoint<Eventp>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl LoopHandle {
+    /// Returns the endpoint of the loop, to send it closures and operations. It can
+    /// be cloned to outlive the handle, then failing once the loop has stopped.
+    pub fn endpoint(&self) -> &RemoteEndpoint<Eventp> {
+        &self.endpoint
+    }
+
+    /// Makes the loop return once the batch it dispatches, if any, is over.
+    ///
+    /// # Errors
+    ///
+    /// The [`io::Error`] of waking the loop up, see [`RemoteEndpoint::wake`].
+    pub fn stop(&self) -> io::Result<()> {
+        self.stopped.store(true, Ordering::Release);
+        self.endpoint.wake()
+    }
+
+    /// Waits for the loop to return, after [`stop`](Self::stop) or a failure.
+    ///
+    /// # Errors
+    ///
+    /// The error the loop returned with, i.e. that of `epoll_wait`.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of the loop thread, e.g. of a handler.
+    pub fn join(mut self) -> io::Result<()> {
+        // Only taken here, and in `drop`, which runs after.
+        join(self.thread.take().unwrap())
+    }
+}
+
+impl fmt::Debug for LoopHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoopHandle")
+            .field("stopped", &self.stopped)
+            .field("thread", &self.thread)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for LoopHandle {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = self.stop();
+            // The panic of the loop is not resumed, as there may be one already.
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Joins `thread`, resuming its panic.
+fn join(thread: JoinHandle<io::Result<()>>) -> io::Result<()> {
+    thread
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::{AsFd, AsRawFd};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use super::*;
+    use crate::tri_subscriber::WithHandler;
+    use crate::{interest, Subscriber};
+
+    fn new_eventfd() -> EventFd {
+        EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap()
+    }
+
+    #[test]
+    fn remotely_registered_handler_runs_until_stopped() {
+        let handle = spawn_loop("eventp-test", |_| Ok(())).unwrap();
+
+        let efd = new_eventfd();
+        let writer = efd.as_fd().try_clone_to_owned().unwrap();
+        let writer = unsafe { EventFd::from_owned_fd(writer) };
+        let (tx, rx) = mpsc::channel();
+        handle
+            .endpoint()
+            .call_blocking(move |mut eventp| {
+                interest()
+                    .read()
+                    .with_fd(efd)
+                    .with_handler(move |efd: &mut EventFd| {
+                        let _ = efd.read();
+                        let _ = tx.send(thread::current().name().map(str::to_owned));
+                    })
+                    .register_into(&mut eventp)
+            })
+            .unwrap();
+
+        writer.write(1).unwrap();
+        let name = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(name.as_deref(), Some("eventp-test"));
+
+        let endpoint = handle.endpoint().clone();
+        handle.stop().unwrap();
+        handle.join().unwrap();
+        assert!(endpoint.call_blocking(|_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn setup_runs_on_the_loop_thread_and_its_error_is_returned() {
+        let raw = Arc::new(std::sync::Mutex::new(None));
+        let r = Arc::clone(&raw);
+        let handle = spawn_loop("eventp-setup", move |eventp| {
+            assert_eq!(thread::current().name(), Some("eventp-setup"));
+            let efd = new_eventfd();
+            *r.lock().unwrap() = Some(efd.as_fd().as_raw_fd());
+            interest()
+                .read()
+                .with_fd(efd)
+                .with_handler(|_: &mut EventFd| {})
+                .register_into(eventp)
+        })
+        .unwrap();
+        let raw = raw.lock().unwrap().unwrap();
+        let registered = handle
+            .endpoint()
+            .call_blocking(move |mut eventp| Ok(eventp.try_modify(raw, interest().read())))
+            .unwrap();
+        assert!(!registered.unwrap());
+        drop(handle);
+
+        let err = spawn_loop("eventp-setup", |_| {
+            Err(io::Error::new(io::ErrorKind::AddrInUse, "taken"))
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+    }
+
+    #[test]
+    fn join_resumes_the_panic_of_the_loop() {
+        let handle = spawn_loop("eventp-panic", |_| Ok(())).unwrap();
+        handle
+            .endpoint()
+            .call_nonblocking(|_| panic!("handler panicked"))
+            .unwrap();
+        let payload = panic::catch_unwind(panic::AssertUnwindSafe(|| handle.join())).unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"handler panicked"));
+
+        let payload =
+            panic::catch_unwind(|| spawn_loop("eventp-panic", |_| panic!("setup panicked")))
+                .unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"setup panicked"));
+    }
+}
@@ -0,0 +1,41 @@
+/// Counters of the dispatch loop, see [`Eventp::stats`](crate::Eventp::stats).
+///
+/// Maintained unconditionally, as a few integer additions per batch. All counters
+/// start at zero, and restart from it on [`Eventp::reset_stats`](crate::Eventp::reset_stats).
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Events handed to a handler. Events for subscribers deleted earlier in the
+    /// same batch are not counted.
+    pub events_dispatched: u64,
+
+    /// Calls to `epoll_wait` that returned at least one event.
+    pub wakeups: u64,
+
+    /// The largest number of events returned by one `epoll_wait`.
+    pub max_batch: u64,
+
+    /// Handler invocations that panicked. The panic still propagates out of the
+    /// `run_*` method.
+    pub handler_panics: u64,
+
+    /// Events dropped because they were meant for an earlier registration whose
+    /// allocation was reused, see [`ThinBoxSubscriber`](crate::thin::ThinBoxSubscriber#generations).
+    /// Expected to stay zero, and always zero but on x86_64, which alone has the
+    /// check.
+    pub stale_events: u64,
+
+    /// Subscribers evicted because their fd was closed while registered, by
+    /// [`Eventp::sweep`](crate::Eventp::sweep) or [`delete`](crate::EventpOps::delete).
+    /// Expected to stay zero.
+    pub fds_evicted: u64,
+
+    /// Oneshot subscribers found disarmed for too long, see
+    /// [`Builder::warn_disarmed_after`](crate::Builder::warn_disarmed_after).
+    /// Expected to stay zero.
+    pub disarmed_leaks: u64,
+
+    /// Fds caught in an error storm, see
+    /// [`Builder::error_storm_policy`](crate::Builder::error_storm_policy).
+    pub error_storms: u64,
+}
@@ -0,0 +1,385 @@
+//! The storage of the subscribers of an `Eventp`, by fd.
+//!
+//! An `FxHashMap` by default, or for [`Builder::fixed_capacity`], a table of
+//! slots allocated once on the heap, which never grows nor rehashes. It is not
+//! inline in the `Eventp`, which would take a const generic parameter on `Eventp`
+//! itself, and so on every type naming it. Both sit behind
+//! [`Storage`], and the loop only sees the [`Registered`] enum picking one of them,
+//! so the dispatch, the deferred removals and the re-entrancy rules are the same
+//! code for either.
+//!
+//! [`Builder::fixed_capacity`]: crate::Builder::fixed_capacity
+
+use std::collections::hash_map;
+use std::ops::Index;
+use std::os::fd::RawFd;
+use std::slice;
+
+use rustc_hash::FxHashMap;
+
+/// A map from fds to `V`, the operations of [`Registered`] apart from its iterators.
+pub(crate) trait Storage<V> {
+    fn get(&self, fd: &RawFd) -> Option<&V>;
+
+    fn get_mut(&mut self, fd: &RawFd) -> Option<&mut V>;
+
+    /// Stores `value` for `fd`, which is not stored yet, or gives it back if there
+    /// is no room for it.
+    fn insert(&mut self, fd: RawFd, value: V) -> Result<(), V>;
+
+    fn remove(&mut self, fd: &RawFd) -> Option<V>;
+
+    fn len(&self) -> usize;
+
+    /// The number of fds that can be stored at all, if bounded.
+    fn capacity(&self) -> Option<usize>;
+}
+
+impl<V> Storage<V> for FxHashMap<RawFd, V> {
+    fn get(&self, fd: &RawFd) -> Option<&V> {
+        hash_map::HashMap::get(self, fd)
+    }
+
+    fn get_mut(&mut self, fd: &RawFd) -> Option<&mut V> {
+        hash_map::HashMap::get_mut(self, fd)
+    }
+
+    fn insert(&mut self, fd: RawFd, value: V) -> Result<(), V> {
+        let previous = hash_map::HashMap::insert(self, fd, value);
+        debug_assert!(previous.is_none(), "fd {fd} stored twice");
+        Ok(())
+    }
+
+    fn remove(&mut self, fd: &RawFd) -> Option<V> {
+        hash_map::HashMap::remove(self, fd)
+    }
+
+    fn len(&self) -> usize {
+        hash_map::HashMap::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// An open-addressing table of a fixed number of slots, probed linearly from the
+/// fd modulo that number.
+///
+/// Removals shift the entries that follow back into the hole, rather than leaving
+/// a tombstone, so that a lookup stops at the first empty slot however long the
+/// table has churned.
+///
+/// Entries thus move between slots, as those of the map do when it rehashes. For
+/// the loop, they are only thin pointers: the subscribers a running handler
+/// borrows stay where they are.
+pub(crate) struct FixedSlots<V> {
+    slots: Box<[Option<(RawFd, V)>]>,
+    len: usize,
+}
+
+impl<V> FixedSlots<V> {
+    /// `capacity` is at least 1.
+    pub(crate) fn new(capacity: usize) -> Self {
+        debug_assert!(capacity > 0);
+        Self {
+            slots: (0..capacity).map(|_| None).collect(),
+            len: 0,
+        }
+    }
+
+    fn home(&self, fd: RawFd) -> usize {
+        fd as u32 as usize % self.slots.len()
+    }
+
+    fn next(&self, slot: usize) -> usize {
+        if slot + 1 == self.slots.len() {
+            0
+        } else {
+            slot + 1
+        }
+    }
+
+    /// Returns the slot of `fd`, if stored.
+    fn find(&self, fd: RawFd) -> Option<usize> {
+        let mut slot = self.home(fd);
+        for _ in 0..self.slots.len() {
+            match &self.slots[slot] {
+                Some((stored, _)) if *stored == fd => return Some(slot),
+                Some(_) => slot = self.next(slot),
+                None => return None,
+            }
+        }
+        None
+    }
+}
+
+impl<V> Storage<V> for FixedSlots<V> {
+    fn get(&self, fd: &RawFd) -> Option<&V> {
+        let slot = self.find(*fd)?;
+        self.slots[slot].as_ref().map(|(_, value)| value)
+    }
+
+    fn get_mut(&mut self, fd: &RawFd) -> Option<&mut V> {
+        let slot = self.find(*fd)?;
+        self.slots[slot].as_mut().map(|(_, value)| value)
+    }
+
+    fn insert(&mut self, fd: RawFd, value: V) -> Result<(), V> {
+        debug_assert!(self.find(fd).is_none(), "fd {fd} stored twice");
+        if self.len == self.slots.len() {
+            return Err(value);
+        }
+        let mut slot = self.home(fd);
+        while self.slots[slot].is_some() {
+            slot = self.next(slot);
+        }
+        self.slots[slot] = Some((fd, value));
+        self.len += 1;
+        Ok(())
+    }
+
+    fn remove(&mut self, fd: &RawFd) -> Option<V> {
+        let mut hole = self.find(*fd)?;
+        let (_, value) = self.slots[hole].take()?;
+        self.len -= 1;
+
+        // Every entry up to the next empty slot was probed past `hole`, and moves
+        // into it unless that would put it before its home slot.
+        let n = self.slots.len();
+        let mut slot = self.next(hole);
+        while let Some((stored, _)) = &self.slots[slot] {
+            let home = self.home(*stored);
+            if (slot + n - home) % n >= (slot + n - hole) % n {
+                self.slots[hole] = self.slots[slot].take();
+                hole = slot;
+            }
+            slot = self.next(slot);
+        }
+        Some(value)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.slots.len())
+    }
+}
+
+/// The storage of the subscribers of an `Eventp`, with the subset of the
+/// `HashMap` API the loop uses.
+pub(crate) enum Registered<V> {
+    Map(FxHashMap<RawFd, V>),
+    Fixed(FixedSlots<V>),
+}
+
+impl<V> Default for Registered<V> {
+    fn default() -> Self {
+        Self::Map(FxHashMap::default())
+    }
+}
+
+/// Calls the same [`Storage`] method on either backend.
+macro_rules! delegate {
+    ($self:ident, $storage:ident => $call:expr) => {
+        match $self {
+            Registered::Map($storage) => $call,
+            Registered::Fixed($storage) => $call,
+        }
+    };
+}
+
+impl<V> Registered<V> {
+    /// A registry of `capacity` slots, at least 1, see [`FixedSlots`].
+    pub(crate) fn fixed(capacity: usize) -> Self {
+        Self::Fixed(FixedSlots::new(capacity))
+    }
+
+    pub(crate) fn get(&self, fd: &RawFd) -> Option<&V> {
+        delegate!(self, storage => Storage::get(storage, fd))
+    }
+
+    pub(crate) fn get_mut(&mut self, fd: &RawFd) -> Option<&mut V> {
+        delegate!(self, storage => Storage::get_mut(storage, fd))
+    }
+
+    pub(crate) fn contains_key(&self, fd: &RawFd) -> bool {
+        self.get(fd).is_some()
+    }
+
+    /// Same as [`Storage::insert`].
+    pub(crate) fn insert(&mut self, fd: RawFd, value: V) -> Result<(), V> {
+        delegate!(self, storage => Storage::insert(storage, fd, value))
+    }
+
+    pub(crate) fn remove(&mut self, fd: &RawFd) -> Option<V> {
+        delegate!(self, storage => Storage::remove(storage, fd))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        delegate!(self, storage => Storage::len(storage))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Same as [`Storage::capacity`].
+    pub(crate) fn capacity(&self) -> Option<usize> {
+        delegate!(self, storage => Storage::capacity(storage))
+    }
+
+    /// Whether there is no room for another fd.
+    pub(crate) fn is_full(&self) -> bool {
+        self.capacity() == Some(self.len())
+    }
+
+    /// Makes room for `additional` more fds in the map, and does nothing for fixed
+    /// slots.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        if let Self::Map(map) = self {
+            map.reserve(additional);
+        }
+    }
+
+    /// Iterates over the fds and their values, in no particular order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&RawFd, &V)> {
+        match self {
+            Self::Map(map) => Either::Left(map.iter()),
+            Self::Fixed(fixed) => Either::Right(SlotsIter(fixed.slots.iter())),
+        }
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &RawFd> {
+        self.iter().map(|(fd, _)| fd)
+    }
+
+    pub(crate) fn into_values(self) -> impl Iterator<Item = V> {
+        match self {
+            Self::Map(map) => Either::Left(map.into_values()),
+            Self::Fixed(fixed) => Either::Right(
+                Vec::from(fixed.slots)
+                    .into_iter()
+                    .flatten()
+                    .map(|(_, value)| value),
+            ),
+        }
+    }
+}
+
+impl<V> Index<&RawFd> for Registered<V> {
+    type Output = V;
+
+    /// # Panics
+    ///
+    /// Panics if `fd` is not stored.
+    fn index(&self, fd: &RawFd) -> &V {
+        self.get(fd).expect("fd not registered")
+    }
+}
+
+struct SlotsIter<'a, V>(slice::Iter<'a, Option<(RawFd, V)>>);
+
+impl<'a, V> Iterator for SlotsIter<'a, V> {
+    type Item = (&'a RawFd, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .by_ref()
+            .find_map(|slot| slot.as_ref().map(|(fd, value)| (fd, value)))
+    }
+}
+
+/// One of two iterators of the same items.
+enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+impl<L, R> Iterator for Either<L, R>
+where
+    L: Iterator,
+    R: Iterator<Item = L::Item>,
+{
+    type Item = L::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Left(left) => left.next(),
+            Self::Right(right) => right.next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(registry: &Registered<u32>) -> Vec<(RawFd, u32)> {
+        let mut entries: Vec<_> = registry.iter().map(|(&fd, &v)| (fd, v)).collect();
+        entries.sort_unstable();
+        entries
+    }
+
+    #[test]
+    fn fixed_slots_refuse_more_fds_than_slots() {
+        let mut registry = Registered::fixed(3);
+        for fd in [3, 6, 4] {
+            assert!(registry.insert(fd, fd as u32).is_ok());
+        }
+        assert!(registry.is_full());
+        assert_eq!(registry.insert(5, 5), Err(5));
+
+        assert_eq!(registry.remove(&6), Some(6));
+        assert!(registry.insert(5, 5).is_ok());
+        assert_eq!(sorted(&registry), [(3, 3), (4, 4), (5, 5)]);
+    }
+
+    #[test]
+    fn fixed_slots_find_every_fd_after_removals_in_a_collision_chain() {
+        // 4 slots: 0, 4, 8 and 12 share the home slot 0, and 1 is displaced by them.
+        let mut registry = Registered::fixed(4);
+        for fd in [0, 4, 1, 8] {
+            registry.insert(fd, fd as u32).unwrap();
+        }
+        assert_eq!(registry.remove(&0), Some(0));
+        assert_eq!(registry.get(&4), Some(&4));
+        assert_eq!(registry.get(&1), Some(&1));
+        assert_eq!(registry.get(&8), Some(&8));
+        assert_eq!(registry.get(&0), None);
+
+        // The chain wraps around the last slot.
+        registry.insert(3, 3).unwrap();
+        assert_eq!(registry.remove(&4), Some(4));
+        registry.insert(12, 12).unwrap();
+        assert_eq!(sorted(&registry), [(1, 1), (3, 3), (8, 8), (12, 12)]);
+        for fd in [1, 3, 8, 12] {
+            assert_eq!(registry.remove(&fd), Some(fd as u32));
+        }
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn fixed_slots_match_the_map_under_churn() {
+        let mut fixed = Registered::fixed(8);
+        let mut map = Registered::default();
+        // A deterministic walk over fds 0..24, adding those absent and removing
+        // those present, while there is room.
+        let mut x = 7u32;
+        for _ in 0..2_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let fd = (x >> 16) as RawFd % 24;
+            if map.contains_key(&fd) {
+                assert_eq!(fixed.remove(&fd), map.remove(&fd));
+            } else if !fixed.is_full() {
+                assert!(fixed.insert(fd, x).is_ok());
+                assert!(map.insert(fd, x).is_ok());
+            }
+            assert_eq!(fixed.len(), map.len());
+            assert_eq!(fixed.get(&fd), map.get(&fd));
+        }
+        assert_eq!(sorted(&fixed), sorted(&map));
+    }
+}
@@ -0,0 +1,105 @@
+//! The counters of [`Builder::error_storm_policy`](crate::Builder::error_storm_policy).
+//!
+//! `EPOLLERR` is reported whatever the interest, and as long as the error lasts, so
+//! a handler unable to clear it is called on every wait. Only the fds whose last
+//! events reported an error have an entry, removed by the next event without one.
+
+use std::os::fd::RawFd;
+use std::time::{Duration, Instant};
+
+use rustc_hash::FxHashMap;
+
+use crate::{Event, StormAction};
+
+#[derive(Clone, Debug)]
+pub(crate) struct ErrorStorms {
+    threshold: u32,
+    window: Duration,
+    action: StormAction,
+    /// `fd -> (consecutive errors, when the first of them was reported)`.
+    counts: FxHashMap<RawFd, (u32, Instant)>,
+}
+
+impl ErrorStorms {
+    pub(crate) fn new(threshold: u32, window: Duration, action: StormAction) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            window,
+            action,
+            counts: FxHashMap::default(),
+        }
+    }
+
+    /// Counts `event`, dispatched for `fd`, and returns the action to take once
+    /// `threshold` consecutive events reported `EPOLLERR` within `window`.
+    pub(crate) fn record(&mut self, fd: RawFd, event: Event) -> Option<StormAction> {
+        if event.is_error() {
+            self.record_error(fd, Instant::now())
+        } else {
+            if !self.counts.is_empty() {
+                self.counts.remove(&fd);
+            }
+            None
+        }
+    }
+
+    fn record_error(&mut self, fd: RawFd, now: Instant) -> Option<StormAction> {
+        let (count, since) = self.counts.entry(fd).or_insert((0, now));
+        if now.duration_since(*since) > self.window {
+            (*count, *since) = (0, now);
+        }
+        *count += 1;
+        if *count < self.threshold {
+            return None;
+        }
+        self.counts.remove(&fd);
+        Some(self.action)
+    }
+
+    /// Forgets the errors of `fd`, deleted or quarantined.
+    pub(crate) fn forget(&mut self, fd: RawFd) {
+        if !self.counts.is_empty() {
+            self.counts.remove(&fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoll::EpollFlags;
+
+    fn storms(threshold: u32) -> ErrorStorms {
+        ErrorStorms::new(threshold, Duration::from_secs(1), StormAction::Quarantine)
+    }
+
+    #[test]
+    fn consecutive_errors_within_the_window_trigger_the_action() {
+        let mut storms = storms(3);
+        let error = Event::new(EpollFlags::EPOLLERR | EpollFlags::EPOLLOUT);
+        assert!(storms.record(5, error).is_none());
+        assert!(storms.record(5, error).is_none());
+        // Other fds are counted apart.
+        assert!(storms.record(6, error).is_none());
+        assert!(matches!(
+            storms.record(5, error),
+            Some(StormAction::Quarantine)
+        ));
+        // Counting starts over after the action.
+        assert!(storms.record(5, error).is_none());
+    }
+
+    #[test]
+    fn an_event_without_error_or_a_late_error_starts_over() {
+        let mut storms = storms(2);
+        let start = Instant::now();
+        assert!(storms.record_error(5, start).is_none());
+        assert!(storms.record(5, Event::new(EpollFlags::EPOLLIN)).is_none());
+        assert!(storms.counts.is_empty());
+
+        assert!(storms.record_error(5, start).is_none());
+        let late = start + Duration::from_secs(2);
+        assert!(storms.record_error(5, late).is_none());
+        assert!(storms.record_error(5, late).is_some());
+    }
+}
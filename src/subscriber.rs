@@ -1,58 +1,286 @@
 //! [`Subscriber`] represents types that can be registered with an [`Eventp`](crate::Eventp)
 //! to receive and handle I/O events.
 //!
-//! It is composed of 3 parts:
+//! It is composed of 2 parts:
 //!
 //! - [`AsFd`] - Can borrow a file descriptor.
-//! - [`HasInterest`] - Provides the interest in I/O readiness events for the fd, held in a `Cell`.
 //! - [`Handler`] - Can handle the triggered events.
 //!
 //! You do not have to implement [`Subscriber`] manually. It is automatically implemented
-//! for any type that implements these three traits.
+//! for any type that implements these two traits.
+//!
+//! The interest in I/O readiness events is kept by the event loop itself, next to the
+//! subscriber. It is given at registration, either explicitly with
+//! [`register_with_interest`](Subscriber::register_with_interest), or by implementing
+//! the optional [`HasInterest`] to use [`register_into`](Subscriber::register_into).
 //!
 //! The **most common** approach is not to create a new type and implement them, but to start
 //! a method chain with [`interest()`](crate::interest), which would be simpler and more
 //! testable.
 
-use std::any::Any;
+use std::any::{Any, TypeId};
 use std::cell::Cell;
 use std::io;
-use std::os::fd::AsFd;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
 
 use crate::thin::ThinBoxSubscriber;
-use crate::{Event, EventpOps, EventpOpsAdd, Interest, Pinned};
+use crate::{AddError, Event, EventpOps, EventpOpsAdd, EventpOpsCtl, Interest, Pinned};
+#[cfg(target_os = "linux")]
+use crate::{Registration, Registry};
 
 /// See [module level docs](self) for more information.
-pub trait Subscriber<Ep: EventpOps>: AsFd + HasInterest + Handler<Ep> + Any {
-    /// Boxes `self` into a [`ThinBoxSubscriber`] and registers it with the given reactor.
+pub trait Subscriber<Ep: EventpOps>: AsFd + Handler<Ep> + Any {
+    /// Boxes `self` into a [`ThinBoxSubscriber`] and registers it with the given reactor,
+    /// with the interest from [`HasInterest`].
     ///
     /// This is a convenience wrapper around [`EventpOpsAdd::add`] that handles the
     /// thin-pointer boxing for the caller. Equivalent to
     /// `eventp.add(ThinBoxSubscriber::new(self))`.
     fn register_into<R>(self, eventp: &mut R) -> io::Result<()>
     where
-        Self: Sized,
+        Self: Sized + HasInterest,
         R: EventpOpsAdd<Ep>,
     {
         eventp.add(ThinBoxSubscriber::new(self))
     }
+
+    /// Same as [`register_into`](Self::register_into), but returns a
+    /// [`SubscriberHandle`] to modify or delete the registration later, without
+    /// keeping the fd around.
+    fn register_into_with<R>(self, eventp: &mut R) -> io::Result<SubscriberHandle>
+    where
+        Self: Sized + HasInterest,
+        R: EventpOpsAdd<Ep>,
+    {
+        let handle = SubscriberHandle::new(self.as_fd().as_raw_fd());
+        self.register_into(eventp)?;
+        Ok(handle)
+    }
+
+    /// Same as [`register_into`](Self::register_into), but returns a
+    /// [`Registration`] deleting the subscriber once dropped, to tie it to the
+    /// lifetime of another object.
+    #[cfg(target_os = "linux")]
+    fn register_guarded<R>(self, eventp: &mut R) -> io::Result<Registration>
+    where
+        Self: Sized + HasInterest,
+        R: Registry<Ep>,
+    {
+        let fd = self.as_fd().as_raw_fd();
+        self.register_into(eventp)?;
+        Ok(eventp.registration(fd))
+    }
+
+    /// Same as [`register_into`](Self::register_into), but gives `self` back on
+    /// failure, e.g. to shut a connection down gracefully when the loop refuses it.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::net::{Shutdown, TcpStream};
+    ///
+    /// use eventp::{tri_subscriber::WithHandler, Eventp, Subscriber};
+    ///
+    /// fn on_accept(stream: TcpStream, eventp: &mut Eventp) {
+    ///     let subscriber = eventp::Interest::stream_read()
+    ///         .with_fd(stream)
+    ///         .with_handler(|_stream: &mut TcpStream| {});
+    ///     if let Err((_, subscriber)) = subscriber.try_register(eventp) {
+    ///         let _ = subscriber.into_fd().shutdown(Shutdown::Both);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The error [`register_into`](Self::register_into) would have returned,
+    /// with `self`.
+    fn try_register<R>(self, eventp: &mut R) -> Result<(), (io::Error, Self)>
+    where
+        Self: Sized + HasInterest,
+        R: EventpOpsAdd<Ep>,
+    {
+        match eventp.try_add(ThinBoxSubscriber::new(self)) {
+            Ok(()) => Ok(()),
+            Err(AddError { error, subscriber }) => match subscriber.downcast() {
+                Ok(subscriber) => Err((error, subscriber)),
+                Err(_) => unreachable!("the subscriber given back is the one added"),
+            },
+        }
+    }
+
+    /// Same as [`register_into`](Self::register_into), but with an explicit interest,
+    /// so `Self` does not need to implement [`HasInterest`].
+    fn register_with_interest<R>(self, interest: Interest, eventp: &mut R) -> io::Result<()>
+    where
+        Self: Sized,
+        R: EventpOpsAdd<Ep>,
+    {
+        eventp.add(ThinBoxSubscriber::with_interest(self, interest))
+    }
 }
 
 impl<S, Ep> Subscriber<Ep> for S
 where
-    S: 'static + AsFd + HasInterest + Handler<Ep>,
+    S: 'static + AsFd + Handler<Ep>,
     Ep: EventpOps,
 {
 }
 
-/// See [module level docs](self) for more information.
+impl<Ep: EventpOps> dyn Subscriber<Ep> {
+    /// Returns the subscriber as a `T`, if it is one.
+    pub fn downcast_ref<T: Subscriber<Ep>>(&self) -> Option<&T> {
+        if <dyn Subscriber<Ep> as Any>::type_id(self) == TypeId::of::<T>() {
+            // SAFETY: The type id was just checked, as in `<dyn Any>::downcast_ref`.
+            Some(unsafe { &*(self as *const dyn Subscriber<Ep> as *const T) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the subscriber as a mutable `T`, if it is one.
+    pub fn downcast_mut<T: Subscriber<Ep>>(&mut self) -> Option<&mut T> {
+        if <dyn Subscriber<Ep> as Any>::type_id(self) == TypeId::of::<T>() {
+            // SAFETY: Same as in `downcast_ref`.
+            Some(unsafe { &mut *(self as *mut dyn Subscriber<Ep> as *mut T) })
+        } else {
+            None
+        }
+    }
+}
+
+/// Identifies a registered subscriber, by its raw fd.
+///
+/// Returned by [`Subscriber::register_into_with`], and injected into
+/// [`tri_subscriber`](crate::tri_subscriber) handlers taking a `SubscriberHandle`
+/// parameter, so a handler can delete itself without keeping its fd around:
+///
+/// ```rust
+/// # use std::io;
+/// use eventp::{tri_subscriber::WithHandler, Eventp, Pinned, Subscriber, SubscriberHandle};
+/// use nix::sys::eventfd::EventFd;
+///
+/// fn register(efd: EventFd, eventp: &mut Eventp) -> io::Result<()> {
+///     eventp::interest()
+///         .read()
+///         .with_fd(efd)
+///         .with_handler(|handle: SubscriberHandle, mut eventp: Pinned<'_, Eventp>| {
+///             handle.delete(&mut eventp)
+///         })
+///         .register_into(eventp)
+/// }
+/// ```
+///
+/// The handle is only the fd number: once the registration is deleted, and the fd
+/// closed and reused, it designates whatever is registered with that number.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SubscriberHandle {
+    raw_fd: RawFd,
+}
+
+impl SubscriberHandle {
+    /// Creates a handle for the subscriber registered with `raw_fd`.
+    pub const fn new(raw_fd: RawFd) -> Self {
+        Self { raw_fd }
+    }
+
+    /// Returns the raw fd of the subscriber.
+    pub const fn raw_fd(&self) -> RawFd {
+        self.raw_fd
+    }
+
+    /// Modifies the interest of the subscriber, from outside a handler with
+    /// `&mut Ep`, or from inside one with [`Pinned`].
+    ///
+    /// See [`EventpOps::modify`].
+    pub fn modify<Ep, R>(&self, eventp: &mut R, interest: Interest) -> io::Result<()>
+    where
+        Ep: EventpOps,
+        R: EventpOpsCtl<Ep>,
+    {
+        eventp.ctl_modify(self.raw_fd, interest)
+    }
+
+    /// Deletes the subscriber, from outside a handler with `&mut Ep`, or from
+    /// inside one with [`Pinned`].
+    ///
+    /// See [`EventpOps::delete`].
+    pub fn delete<Ep, R>(&self, eventp: &mut R) -> io::Result<()>
+    where
+        Ep: EventpOps,
+        R: EventpOpsCtl<Ep>,
+    {
+        eventp.ctl_delete(self.raw_fd)
+    }
+}
+
+/// A [`Subscriber`] that can be sent to another thread, e.g. to be built by a pool
+/// of threads and registered by the one running the loop, boxed in a
+/// [`SendThinBox`](crate::thin::SendThinBox).
+///
+/// Implemented for every `Send` subscriber.
+#[cfg(feature = "send-subscribers")]
+pub trait SendSubscriber<Ep: EventpOps>: Subscriber<Ep> + Send {}
+
+#[cfg(feature = "send-subscribers")]
+impl<S, Ep> SendSubscriber<Ep> for S
+where
+    S: Subscriber<Ep> + Send,
+    Ep: EventpOps,
+{
+}
+
+/// Provides the interest a subscriber is registered with by
+/// [`register_into`](Subscriber::register_into).
+///
+/// Optional. The `Cell` is only read once, at registration; afterwards the event loop
+/// keeps the current interest itself and [`modify`](crate::EventpOps::modify) does
+/// not write back to it. Read the current value with
+/// [`Pinned::current_interest`](crate::Pinned::current_interest) from a handler, or
+/// [`Eventp::interest`](crate::Eventp::interest) from outside.
 pub trait HasInterest {
     /// Returns the interest in IO-readiness event.
     fn interest(&self) -> &Cell<Interest>;
+
+    /// Returns the name the subscriber is registered with, shown next to its fd in
+    /// diagnostics, see [`ThinBoxSubscriber::name`]. Also only read at registration.
+    fn name(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 /// See [module level docs](self) for more information.
 pub trait Handler<Ep: EventpOps> {
     /// Handle the triggered event
     fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>);
+
+    /// Fallible counterpart of [`handle`](Self::handle), and the method the event loop
+    /// actually invokes on dispatch.
+    ///
+    /// The default implementation forwards to `handle` and returns `Ok(())`. Override it
+    /// to report failures back to the loop, which then applies its
+    /// [`ErrorPolicy`](crate::ErrorPolicy).
+    fn try_handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        self.handle(event, eventp);
+        Ok(())
+    }
+
+    /// Called once no event has been dispatched to the subscriber for its
+    /// [`idle_timeout`](Interest::idle_timeout), and returns whether the loop should
+    /// delete it, as by default. If not, it gets another full timeout.
+    ///
+    /// The subscriber may also delete itself, or another one, through `eventp`.
+    fn on_idle(&mut self, eventp: Pinned<'_, Ep>) -> bool {
+        let _ = eventp;
+        true
+    }
+
+    /// Called once by [`Eventp::shutdown`](crate::Eventp::shutdown), to flush state
+    /// before the loop goes away, e.g. write a last record or shut a connection
+    /// down. Does nothing by default.
+    ///
+    /// The loop keeps running until the timeout of the shutdown, so the subscriber
+    /// may finish its work in later events, and delete itself once done.
+    fn on_shutdown(&mut self, eventp: Pinned<'_, Ep>) {
+        let _ = eventp;
+    }
 }
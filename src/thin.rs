@@ -1,16 +1,25 @@
 //! Thin pointer implementations.
 
 use std::alloc::{self, Layout};
+use std::io;
 use std::marker::PhantomData;
-use std::mem::{self, size_of};
+use std::mem::{self, size_of, ManuallyDrop};
 use std::ops::Deref;
 use std::os::fd::{AsRawFd, RawFd};
 use std::ptr::{self, NonNull};
+use std::time::Duration;
 
+use crate::epoll::EpollFlags;
 #[cfg(feature = "mock")]
 use crate::mock::MockEventp;
+use crate::subscriber::HasInterest;
+#[cfg(feature = "send-subscribers")]
+use crate::subscriber::SendSubscriber;
+use crate::tri_subscriber::{QuadSubscriber, TriSubscriber};
 use crate::utils::unlikely;
-use crate::{Eventp, EventpOps, Subscriber};
+#[cfg(target_os = "linux")]
+use crate::Eventp;
+use crate::{Event, EventDelta, EventpOps, Interest, Pinned, Subscriber};
 
 /// Similar to `Box<dyn Subscriber<Ep>>`, but the size of this type is only one usize.
 ///
@@ -20,37 +29,143 @@ use crate::{Eventp, EventpOps, Subscriber};
 /// # Memory layout
 ///
 /// ```text
-/// +---------+---------+---------+-----------------+--------------------+
-/// |  _pad_  |  raw fd |  _pad_  |       vptr      | dyn Subscriber<Ep> |
-/// +---------+---------+---------+-----------------+--------------------+
-/// ??      ptr-16    ptr-12    ptr-8               ↑                    ??
-///                                                 |
-///                            ThinBoxSubscriber { ptr }
+/// +-------+------+------------+----------+--------+----------+------------+-------+---------------+---------------+---------+--------------------+
+/// | _pad_ | name | generation | out seen | raw fd | interest | last event | order | idle deadline | last dispatch |  vptr   | dyn Subscriber<Ep> |
+/// +-------+------+------------+----------+--------+----------+------------+-------+---------------+---------------+---------+--------------------+
+/// ??    ptr-72 ptr-56       ptr-54     ptr-52   ptr-48     ptr-32       ptr-28  ptr-24          ptr-16          ptr-8     ↑                    ??
+///                                                                                                                         |
+///                                                                                                   ThinBoxSubscriber { ptr }
 /// ```
 ///
+/// The name, generation, out seen, raw fd, interest, last event, order, idle
+/// deadline, last dispatch and vptr form the `Header`. The name is the one given with
+/// [`named`](crate::tri_subscriber::TriSubscriber::named), if any, for diagnostics. The interest is the one the fd is currently registered with;
+/// it is owned by the loop, which reads it on `add` and updates it on `modify`
+/// without going through the vtable, and holds the
+/// [`user_data`](Interest::user_data) of the registration. The last event is only kept for interests with
+/// [`track_deltas`](Interest::track_deltas), out seen for those with
+/// [`writable_edge_emulation`](Interest::writable_edge_emulation), and the idle
+/// deadline for those with an [`idle_timeout`](Interest::idle_timeout), and the last
+/// dispatch for those with [`track_last_event`](Interest::track_last_event). The order
+/// is that of the registrations, for [`Eventp::shutdown`].
+///
+/// # Generations
+///
+/// [`Eventp`] numbers its registrations, and on x86_64 hands the number to the
+/// kernel in the 16 bits of the epoll data word above the 48-bit address. An event
+/// whose number differs from the header's belongs to an earlier registration whose
+/// allocation was reused, and is dropped rather than dispatched. The loop already
+/// keeps deleted subscribers allocated until no event can refer to them; the check
+/// guards that invariant, within limits:
+///
+/// - The number wraps around after 65536 registrations, so an event of the
+///   registration exactly a multiple of that many earlier goes through.
+/// - The address must fit in 48 bits, which Linux only breaks for mappings asking
+///   for higher addresses under 5-level paging; such a subscriber panics on `add`.
+/// - Other architectures may use these bits of the address, and skip the check:
+///   every event is taken as current, and [`Stats::stale_events`] stays zero.
+///
+/// [`Stats::stale_events`]: crate::Stats::stale_events
+///
 /// See [technical](crate::_technical) for more information.
 pub struct ThinBoxSubscriber<Ep: EventpOps> {
     ptr: NonNull<u8>,
     _marker: PhantomData<dyn Subscriber<Ep>>,
 }
 
+/// The words right before the value of a [`ThinBoxSubscriber`].
+#[repr(C)]
+struct Header {
+    /// See [`ThinBoxSubscriber::name`].
+    name: Option<&'static str>,
+    /// Set by the loop on `add`, see [Generations](ThinBoxSubscriber#generations).
+    generation: u16,
+    /// See [`WriteReadiness`], reset by `modify`.
+    out: WriteReadiness,
+    /// Whether an event was dispatched since the fd was last armed with
+    /// `EPOLLONESHOT`, see [`ThinBoxSubscriber::is_armed`]. Cleared by `modify`.
+    disarmed: bool,
+    raw_fd: RawFd,
+    interest: Interest,
+    /// The flags of the last event; dispatched events carry no data word.
+    last_event: EpollFlags,
+    /// Set by the loop on `add`, wrapping around. Fills the padding before
+    /// `idle_deadline`.
+    order: u32,
+    /// In the milliseconds of the loop's idle timers.
+    idle_deadline: u64,
+    /// `CLOCK_MONOTONIC` in nanoseconds when the last event was dispatched, 0 before
+    /// the first.
+    last_dispatch: u64,
+    vptr: *const (),
+}
+
+const _: () = assert!(size_of::<Header>() == size_of::<[usize; 10]>());
+
+/// What [`writable_edge_emulation`](Interest::writable_edge_emulation) knows of
+/// the write readiness of the fd.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum WriteReadiness {
+    /// The last event dispatched did not report `EPOLLOUT`.
+    Unseen,
+    /// The last event dispatched reported `EPOLLOUT`.
+    Seen,
+    /// A repeated `EPOLLOUT` was dropped for a level-triggered interest, which the
+    /// kernel then stops watching for it.
+    Parked,
+}
+
+/// Reads `CLOCK_MONOTONIC`, in nanoseconds, which is past 0 by the time any process
+/// runs.
+fn monotonic_nanos() -> u64 {
+    // SAFETY: All zeroes is a valid `timespec`.
+    let mut now: libc::timespec = unsafe { mem::zeroed() };
+    // SAFETY: `now` is a valid `timespec` to write into. `CLOCK_MONOTONIC` is
+    // always supported, so the call cannot fail.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64
+}
+
+/// Where the generation starts in the data word, above the address.
+#[cfg(target_arch = "x86_64")]
+const GENERATION_SHIFT: u32 = 48;
+
 impl<Ep> ThinBoxSubscriber<Ep>
 where
     Ep: EventpOps,
 {
-    /// Allocates memory on the heap and then places `value` into it.
+    /// Allocates memory on the heap and then places `value` into it, taking the
+    /// initial interest and the name from [`HasInterest`].
+    ///
+    /// # Panics
+    ///
+    /// See [`with_interest`](Self::with_interest).
+    pub fn new<T: Subscriber<Ep> + HasInterest>(value: T) -> Self {
+        let interest = value.interest().get();
+        let name = value.name();
+        let mut ret = Self::with_interest(value, interest);
+        ret.header_mut().name = name;
+        ret
+    }
+
+    /// Allocates memory on the heap and then places `value` into it, to be
+    /// registered with `interest`.
+    ///
+    /// Zero-sized subscribers are supported: the allocation then holds just the
+    /// header, with the value right after it at its own alignment.
     ///
     /// # Panics
     ///
-    /// - if combining the `(usize, usize)` header layout with `T`'s layout
-    ///   overflows (`Layout::extend` returns `Err`);
+    /// - if combining the header layout with `T`'s layout overflows
+    ///   (`Layout::extend` returns `Err`);
     /// - if the heap allocation fails (via [`alloc::handle_alloc_error`]).
-    pub fn new<T: Subscriber<Ep>>(value: T) -> Self {
+    pub fn with_interest<T: Subscriber<Ep>>(value: T, interest: Interest) -> Self {
         #[cfg(not(target_pointer_width = "64"))]
         compile_error!("Platforms with pointer width other than 64 are not supported.");
 
         // Verify trait object layout: first 8 bytes for the data pointer,
         // next 8 bytes for the vtable pointer.
+        #[cfg(target_os = "linux")]
         const _: () = assert!(size_of::<&dyn Subscriber<Eventp>>() == 16);
         #[cfg(feature = "mock")]
         const _: () = assert!(size_of::<&dyn Subscriber<MockEventp>>() == 16);
@@ -64,14 +179,13 @@ where
         // user-provided `AsFd` impl cannot leave a partially-initialized heap.
         let raw_fd = value.as_fd().as_raw_fd();
 
-        // Create a new layout for the raw fd, vptr and data T.
-        let (layout, value_offset) = Layout::new::<(usize, usize)>()
+        // Create a new layout for the header and data T.
+        let (layout, value_offset) = Layout::new::<Header>()
             .extend(Layout::new::<T>())
             .expect("Failed to create combined layout");
 
         let ptr = {
-            // SAFETY: Layout has a non-zero size, because it contains
-            // at least two usizes.
+            // SAFETY: Layout has a non-zero size, because it contains the header.
             let ptr = unsafe { alloc::alloc(layout) };
             if ptr.is_null() {
                 alloc::handle_alloc_error(layout);
@@ -89,8 +203,19 @@ where
 
         // Fill it with the data. No operation may unwind.
 
-        *ret.raw_fd_mut() = raw_fd;
-        *ret.vptr_mut() = vptr;
+        ret.write_header(Header {
+            name: None,
+            generation: 0,
+            out: WriteReadiness::Unseen,
+            disarmed: false,
+            raw_fd,
+            interest,
+            last_event: EpollFlags::empty(),
+            order: 0,
+            idle_deadline: 0,
+            last_dispatch: 0,
+            vptr,
+        });
 
         // Move the value into the allocated location. No drop occurs.
         // SAFETY: data_ptr is valid and aligned for writes.
@@ -99,15 +224,20 @@ where
         ret
     }
 
-    /// Allocates memory on the heap and then moves `value` into it.
+    /// Moves `value` into a thin allocation, to be registered with `interest`.
     /// The original [Box] will be consumed.
     ///
+    /// When the value is aligned to at least 8 bytes, the `Box`'s own allocation is
+    /// grown in place with `realloc` and the value is shifted up to make room for the
+    /// header, so no second allocation is made. Otherwise the value is copied into a
+    /// new allocation, as the `Box`'s alignment is too small for the header.
+    ///
     /// # Panics
     ///
-    /// - if combining the `(usize, usize)` header layout with the value's layout
-    ///   overflows (`Layout::extend` returns `Err`);
+    /// - if combining the header layout with the value's layout overflows
+    ///   (`Layout::extend` returns `Err`);
     /// - if the heap allocation fails (via [`alloc::handle_alloc_error`]).
-    pub fn from_box_dyn(value: Box<dyn Subscriber<Ep>>) -> Self {
+    pub fn from_box_dyn(value: Box<dyn Subscriber<Ep>>, interest: Interest) -> Self {
         // Obtain the fat pointer and extract the vtable address.
         let fat_ptr = value.deref();
         let (_data_ptr, vptr) =
@@ -120,22 +250,50 @@ where
         // user-provided `AsFd` impl cannot leave a partially-initialized heap.
         let raw_fd = value.as_fd().as_raw_fd();
 
-        // Create a new layout for the raw fd, vptr and data.
-        let (layout, value_offset) = Layout::new::<(usize, usize)>()
+        // Create a new layout for the header and data.
+        let (layout, value_offset) = Layout::new::<Header>()
             .extend(value_layout)
             .expect("Failed to create combined layout");
 
-        let ptr = {
-            // SAFETY: Layout has a non-zero size, because it contains
-            // at least two usizes.
-            let ptr = unsafe { alloc::alloc(layout) };
-            if ptr.is_null() {
+        // From here on, nothing may unwind: the value is owned by a raw pointer.
+        let value = Box::into_raw(value) as *mut u8;
+
+        // `realloc` must keep the alignment the `Box` was allocated with, which only
+        // fits the combined layout if the value is at least as aligned as the header.
+        let ptr = if value_layout.size() != 0 && value_layout.align() == layout.align() {
+            // SAFETY: `value` was allocated by the global allocator with `value_layout`
+            // (non-zero size, so it is a real allocation), and `layout.size()` is
+            // non-zero and does not overflow `isize` once rounded, as `Layout` checked.
+            let base = unsafe { alloc::realloc(value, value_layout, layout.size()) };
+            if base.is_null() {
                 alloc::handle_alloc_error(layout);
             }
-
-            // SAFETY: Points to a valid location because the previous allocation succeeded.
-            let ptr = unsafe { ptr.add(value_offset) };
-            unsafe { NonNull::new_unchecked(ptr) }
+            // SAFETY: The block now spans `layout.size() >= value_offset + size`
+            // bytes, and the value sits at its start. `ptr::copy` handles the overlap.
+            unsafe {
+                let ptr = base.add(value_offset);
+                ptr::copy(base, ptr, value_layout.size());
+                NonNull::new_unchecked(ptr)
+            }
+        } else {
+            // SAFETY: Layout has a non-zero size, because it contains the header.
+            let base = unsafe { alloc::alloc(layout) };
+            if base.is_null() {
+                alloc::handle_alloc_error(layout);
+            }
+            // SAFETY: Points to a valid location because the allocation succeeded.
+            // `src` and `dst` are valid and aligned, and from different allocations,
+            // so not overlapped. For a non-ZST, `GlobalAlloc` is the allocator of the
+            // value and `value_layout` is valid; a ZST `Box` holds a dangling pointer
+            // that was never allocated, so it must not be deallocated.
+            unsafe {
+                let ptr = base.add(value_offset);
+                ptr.copy_from_nonoverlapping(value, value_layout.size());
+                if value_layout.size() != 0 {
+                    alloc::dealloc(value, value_layout);
+                }
+                NonNull::new_unchecked(ptr)
+            }
         };
 
         let mut ret = Self {
@@ -143,45 +301,357 @@ where
             _marker: PhantomData,
         };
 
-        // Fill it with the data. No operation may unwind.
+        ret.write_header(Header {
+            name: None,
+            generation: 0,
+            out: WriteReadiness::Unseen,
+            disarmed: false,
+            raw_fd,
+            interest,
+            last_event: EpollFlags::empty(),
+            order: 0,
+            idle_deadline: 0,
+            last_dispatch: 0,
+            vptr,
+        });
 
-        *ret.raw_fd_mut() = raw_fd;
-        *ret.vptr_mut() = vptr;
+        ret
+    }
 
-        // Move the value into the allocated location. No drop occurs.
-        let value = Box::into_raw(value) as *mut u8;
-        // SAFETY: `src` and `dst` are valid and aligned. Because they are from
-        // different allocation, so not overlapped.
-        unsafe {
-            ret.ptr
-                .as_ptr()
-                .cast::<u8>()
-                .copy_from_nonoverlapping(value, value_layout.size())
-        };
-        // SAFETY: `GlobalAlloc` is the allocator of this value and `value_layout` is valid.
-        unsafe { alloc::dealloc(value, value_layout) };
+    /// Consumes the `ThinBoxSubscriber`, returning the thin pointer to its value.
+    ///
+    /// The subscriber is leaked until the pointer is given back to
+    /// [`from_raw`](Self::from_raw).
+    pub fn into_raw(self) -> *mut u8 {
+        ManuallyDrop::new(self).ptr.as_ptr()
+    }
 
-        ret
+    /// Returns the thin pointer to the value, without giving up ownership.
+    pub fn as_raw(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Takes back the ownership of a subscriber from its thin pointer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`into_raw`](Self::into_raw) on a
+    /// `ThinBoxSubscriber<Ep>` of the same `Ep`, and not have been given back yet.
+    /// A pointer returned by [`as_raw`](Self::as_raw) may be given back as well, as
+    /// long as the result is not dropped while the original is alive, e.g. by
+    /// keeping it in a [`ManuallyDrop`].
+    pub unsafe fn from_raw(ptr: *mut u8) -> Self {
+        Self {
+            // SAFETY: The pointer of a `ThinBoxSubscriber` is never null.
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the thin pointer as the `u64` handed to the kernel, as the data of an
+    /// epoll event or the user data of an io_uring request, exposing its provenance.
+    /// On x86_64, the [generation](Self#generations) is packed above the address.
+    ///
+    /// This cast and the one in [`from_data`](Self::from_data) are the only round
+    /// trip of the pointer through an integer. They have the semantics of
+    /// `expose_provenance` and `with_exposed_provenance`, and can be replaced with
+    /// them once MSRV reaches 1.84.
+    ///
+    /// # Panics
+    ///
+    /// On x86_64, if the address does not fit in 48 bits, which Linux only hands out
+    /// to mappings asking for it.
+    pub(crate) fn to_data(&self) -> u64 {
+        let addr = self.as_raw() as usize as u64;
+        #[cfg(target_arch = "x86_64")]
+        {
+            assert!(
+                addr >> GENERATION_SHIFT == 0,
+                "subscriber address {addr:#x} does not fit in 48 bits"
+            );
+            addr | u64::from(self.header_ref().generation) << GENERATION_SHIFT
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        addr
+    }
+
+    /// Borrows the subscriber whose [`to_data`](Self::to_data) is `data`, as handed
+    /// back by the kernel.
+    ///
+    /// # Safety
+    ///
+    /// The subscriber must still be allocated, and owned elsewhere: the result must
+    /// not be taken out of the `ManuallyDrop`, nor outlive the owner.
+    pub(crate) unsafe fn from_data(data: u64) -> ManuallyDrop<Self> {
+        #[cfg(target_arch = "x86_64")]
+        let data = data & ((1 << GENERATION_SHIFT) - 1);
+        // SAFETY: `data` is a pointer exposed by `to_data`, per the contract above.
+        ManuallyDrop::new(unsafe { Self::from_raw(data as usize as *mut u8) })
+    }
+
+    /// Sets the generation packed into [`to_data`](Self::to_data). Must not change
+    /// while registered, as the kernel keeps the data word.
+    pub(crate) fn set_generation(&mut self, generation: u16) {
+        self.header_mut().generation = generation;
+    }
+
+    /// Returns the generation set by [`set_generation`](Self::set_generation).
+    pub(crate) fn generation(&self) -> u16 {
+        self.header_ref().generation
+    }
+
+    /// Sets the registration order, see [`Eventp::shutdown`].
+    pub(crate) fn set_order(&mut self, order: u32) {
+        self.header_mut().order = order;
+    }
+
+    /// Returns the order set by [`set_order`](Self::set_order).
+    pub(crate) fn order(&self) -> u32 {
+        self.header_ref().order
+    }
+
+    /// Returns `false` if `data`, from which `self` was borrowed with
+    /// [`from_data`](Self::from_data), carries another generation than `self`:
+    /// the event was meant for an earlier registration at the same address.
+    pub(crate) fn is_current(&self, data: u64) -> bool {
+        #[cfg(target_arch = "x86_64")]
+        {
+            data >> GENERATION_SHIFT == u64::from(self.header_ref().generation)
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            let _ = data;
+            true
+        }
+    }
+
+    fn header_ptr(&self) -> *mut Header {
+        // SAFETY: See memory layout of docs of this type. The value offset is a
+        // multiple of `align_of::<Header>()` and at least `size_of::<Header>()`,
+        // so the header always fits, aligned, right before the value.
+        unsafe { self.ptr.as_ptr().sub(size_of::<Header>()).cast() }
+    }
+
+    fn header_ref(&self) -> &Header {
+        // SAFETY: Written in `with_interest` / `from_box_dyn` before `self` is
+        // handed out, and only freed in `Drop`.
+        unsafe { &*self.header_ptr() }
+    }
+
+    fn header_mut(&mut self) -> &mut Header {
+        // SAFETY: Same as `header_ref`; uniqueness is enforced by `&mut self`.
+        unsafe { &mut *self.header_ptr() }
+    }
+
+    fn write_header(&mut self, header: Header) {
+        // SAFETY: The header slot is allocated and aligned, but may be uninit,
+        // so it must be written without reading (dropping) the old value.
+        unsafe { self.header_ptr().write(header) }
     }
 
     pub(crate) fn raw_fd_ref(&self) -> &RawFd {
-        // SAFETY: See memory layout of docs of this type.
-        unsafe { &*self.ptr.as_ptr().sub(2 * size_of::<usize>()).cast() }
+        &self.header_ref().raw_fd
     }
 
     fn raw_fd_mut(&mut self) -> &mut RawFd {
-        // SAFETY: See memory layout of docs of this type.
-        unsafe { &mut *self.ptr.as_ptr().sub(2 * size_of::<usize>()).cast() }
+        &mut self.header_mut().raw_fd
     }
 
     fn vptr_ref(&self) -> &*const () {
-        // SAFETY: See memory layout of docs of this type.
-        unsafe { &*self.ptr.as_ptr().sub(size_of::<usize>()).cast() }
+        &self.header_ref().vptr
+    }
+
+    /// Returns the raw fd of the subscriber, read into the header when it was boxed,
+    /// so without calling [`AsFd::as_fd`](std::os::fd::AsFd::as_fd). It is -1 once
+    /// the subscriber has been dropped in place.
+    pub fn raw_fd(&self) -> RawFd {
+        *self.raw_fd_ref()
+    }
+
+    /// Returns the interest the subscriber is, or is to be, registered with.
+    ///
+    /// This is read from the header, not from the subscriber, so it is available
+    /// even after the subscriber has been dropped in place.
+    pub fn interest(&self) -> Interest {
+        self.header_ref().interest
+    }
+
+    /// Returns `false` once an event has been dispatched for an
+    /// [`oneshot`](Interest::oneshot) interest, which the kernel then disarms, until
+    /// the fd is armed again by [`modify`](EventpOps::modify). Always `true` without
+    /// `EPOLLONESHOT`.
+    ///
+    /// The [`interest`](Self::interest) is still the one last registered, so that
+    /// handlers can rearm with it.
+    pub fn is_armed(&self) -> bool {
+        !self.header_ref().disarmed
+    }
+
+    /// Returns the name of the subscriber, given with
+    /// [`named`](crate::tri_subscriber::TriSubscriber::named) or by
+    /// [`HasInterest::name`], to tell it apart in diagnostics.
+    pub fn name(&self) -> Option<&'static str> {
+        self.header_ref().name
     }
 
-    fn vptr_mut(&mut self) -> &mut *const () {
-        // SAFETY: See memory layout of docs of this type.
-        unsafe { &mut *self.ptr.as_ptr().sub(size_of::<usize>()).cast() }
+    pub(crate) fn set_name(&mut self, name: Option<&'static str>) {
+        self.header_mut().name = name;
+    }
+
+    /// Returns the size of the allocation holding the header and the subscriber,
+    /// as passed to the allocator, e.g. to bound the memory of a loop with
+    /// [`Builder::max_subscriber_bytes`](crate::Builder::max_subscriber_bytes).
+    ///
+    /// It is derived from the vtable rather than stored, so it stays the same after
+    /// the subscriber has been dropped in place, until the box itself is dropped.
+    pub fn allocated_bytes(&self) -> usize {
+        // SAFETY: Only the layout of the trait object is inspected, which is valid
+        // even after the subscriber has been dropped in place, see `deref`.
+        let value_layout = Layout::for_value(unsafe { self.deref() });
+        // SAFETY: The same layout was computed, and succeeded, when boxing.
+        let (layout, _) = unsafe {
+            Layout::new::<Header>()
+                .extend(value_layout)
+                .unwrap_unchecked()
+        };
+        layout.size()
+    }
+
+    /// Calls the handler of the subscriber with `event`, as the loop does, e.g. to
+    /// test a boxed subscriber, or to dispatch events from a reactor of one's own.
+    /// Does nothing if the subscriber has been dropped in place.
+    ///
+    /// ```rust
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// use eventp::epoll::EpollFlags;
+    /// use eventp::thin::ThinBoxSubscriber;
+    /// use eventp::tri_subscriber::WithHandler;
+    /// use eventp::{pinned, Event, Eventp};
+    /// use nix::sys::eventfd::EventFd;
+    ///
+    /// let called = Rc::new(Cell::new(false));
+    /// let c = called.clone();
+    /// let subscriber = eventp::interest()
+    ///     .read()
+    ///     .with_fd(EventFd::new().unwrap())
+    ///     .with_handler(move |event: Event| c.set(event.is_readable()));
+    ///
+    /// let mut thin = ThinBoxSubscriber::<Eventp>::from(subscriber);
+    /// assert!(thin.interest().bitflags().contains(EpollFlags::EPOLLIN));
+    /// thin.handle(Event::new(EpollFlags::EPOLLIN), pinned!(Eventp::default()));
+    /// assert!(called.get());
+    /// ```
+    pub fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        if let Some(subscriber) = self.try_deref_mut() {
+            subscriber.handle(event, eventp);
+        }
+    }
+
+    /// Same as [`handle`](Self::handle), but returns the error of the handler, see
+    /// [`Handler::try_handle`](crate::subscriber::Handler::try_handle).
+    pub fn try_handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        match self.try_deref_mut() {
+            Some(subscriber) => subscriber.try_handle(event, eventp),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn set_interest(&mut self, interest: Interest) {
+        let header = self.header_mut();
+        header.interest = interest;
+        header.out = WriteReadiness::Unseen;
+        header.disarmed = false;
+    }
+
+    /// Sets the user data of the interest, leaving the rest of the header intact.
+    pub(crate) fn set_user_data(&mut self, data: u64) {
+        let header = self.header_mut();
+        header.interest = header.interest.user_data(data);
+    }
+
+    pub(crate) fn idle_deadline(&self) -> u64 {
+        self.header_ref().idle_deadline
+    }
+
+    /// Pushes the idle deadline back to a full timeout after `now`, and returns it,
+    /// if the interest has an [`idle_timeout`](Interest::idle_timeout).
+    pub(crate) fn refresh_idle_deadline(&mut self, now: u64) -> Option<u64> {
+        let header = self.header_mut();
+        match header.interest.idle_millis() {
+            0 => None,
+            millis => {
+                header.idle_deadline = now + u64::from(millis);
+                Some(header.idle_deadline)
+            }
+        }
+    }
+
+    /// Records `event` as the last one dispatched, and returns how it differs from
+    /// the previous one, if the interest has [`track_deltas`](Interest::track_deltas).
+    ///
+    /// With `EPOLLONESHOT`, the kernel has disarmed the fd by now, so it is marked as
+    /// such.
+    pub(crate) fn record_event(&mut self, event: Event) -> Option<EventDelta> {
+        let header = self.header_mut();
+        if header
+            .interest
+            .bitflags()
+            .contains(EpollFlags::EPOLLONESHOT)
+        {
+            header.disarmed = true;
+        }
+        if !header.interest.tracks_deltas() {
+            return None;
+        }
+        let previous = mem::replace(&mut header.last_event, event.bitflags());
+        Some(EventDelta::between(Event::new(previous), event))
+    }
+
+    /// Records the time of the event being dispatched, and returns the time since
+    /// the previous one, if the interest has
+    /// [`track_last_event`](Interest::track_last_event) and this is not the first.
+    /// The clock is not read otherwise.
+    pub(crate) fn record_dispatch_time(&mut self) -> Option<Duration> {
+        let header = self.header_mut();
+        if !header.interest.tracks_last_event() {
+            return None;
+        }
+        let now = monotonic_nanos();
+        let previous = mem::replace(&mut header.last_dispatch, now);
+        (previous != 0).then(|| Duration::from_nanos(now.saturating_sub(previous)))
+    }
+
+    /// Returns `true` if `event` is to be dropped by the
+    /// [`writable_edge_emulation`](Interest::writable_edge_emulation) of the
+    /// interest, and otherwise records whether it reports `EPOLLOUT`.
+    pub(crate) fn suppresses(&mut self, event: Event) -> bool {
+        let header = self.header_mut();
+        if !header.interest.emulates_writable_edge() {
+            return false;
+        }
+        let flags = event.bitflags();
+        if flags == EpollFlags::EPOLLOUT && header.out != WriteReadiness::Unseen {
+            let modes = EpollFlags::EPOLLET | EpollFlags::EPOLLONESHOT;
+            if !header.interest.bitflags().intersects(modes) {
+                header.out = WriteReadiness::Parked;
+            }
+            return true;
+        }
+        if header.out != WriteReadiness::Parked {
+            header.out = match flags.contains(EpollFlags::EPOLLOUT) {
+                true => WriteReadiness::Seen,
+                false => WriteReadiness::Unseen,
+            };
+        }
+        false
+    }
+
+    /// Returns `true` if the kernel is to stop watching `EPOLLOUT` for the fd, see
+    /// [`suppresses`](Self::suppresses), until `modify`.
+    pub(crate) fn is_writable_parked(&self) -> bool {
+        self.header_ref().out == WriteReadiness::Parked
     }
 
     fn is_subscriber_dropped(&self) -> bool {
@@ -342,6 +812,20 @@ where
         Some(unsafe { Box::from_raw(fat_ptr) })
     }
 
+    /// Moves the subscriber out as a `T`, or returns `self` back if it is not one,
+    /// or has already been dropped in place.
+    pub fn downcast<T: Subscriber<Ep>>(mut self) -> Result<T, Self> {
+        match self.try_deref() {
+            Some(s) if s.downcast_ref::<T>().is_some() => {}
+            _ => return Err(self),
+        }
+        // SAFETY: The value is a live `T`, as just checked. Marking it dropped
+        // right after reading it makes `Drop` free the slot without dropping it.
+        let value = unsafe { self.ptr.as_ptr().cast::<T>().read() };
+        self.mark_subscriber_dropped();
+        Ok(value)
+    }
+
     /// Drops the subscriber in place and marks the slot so subsequent
     /// `try_deref_mut` calls return `None`.
     pub(crate) fn drop_in_place(&mut self) {
@@ -366,12 +850,118 @@ where
     }
 }
 
-impl<Ep> From<Box<dyn Subscriber<Ep>>> for ThinBoxSubscriber<Ep>
+/// A [`ThinBoxSubscriber`] known to hold a [`SendSubscriber`], and so `Send` itself.
+///
+/// A `ThinBoxSubscriber` is not `Send`, as it erases whether its value is. This
+/// keeps the proof until the subscriber reaches the thread of its loop:
+///
+/// ```rust
+/// use std::sync::mpsc;
+/// use std::thread;
+///
+/// use eventp::thin::SendThinBox;
+/// use eventp::{interest, tri_subscriber::WithHandler, Eventp, EventpOpsAdd};
+/// use nix::sys::eventfd::EventFd;
+///
+/// let (tx, rx) = mpsc::channel();
+/// thread::spawn(move || {
+///     let subscriber = interest()
+///         .read()
+///         .with_fd(EventFd::new().unwrap())
+///         .with_handler(|efd: &mut EventFd| drop(efd.read()));
+///     tx.send(SendThinBox::new(subscriber)).unwrap();
+/// })
+/// .join()
+/// .unwrap();
+///
+/// let mut eventp = Eventp::default();
+/// eventp.add(rx.recv().unwrap().into()).unwrap();
+/// ```
+///
+/// Subscribers that are not `Send` cannot be boxed this way:
+///
+/// ```compile_fail
+/// use std::rc::Rc;
+///
+/// use eventp::thin::SendThinBox;
+/// use eventp::{interest, tri_subscriber::WithHandler, Eventp};
+/// use nix::sys::eventfd::EventFd;
+///
+/// let shared = Rc::new(());
+/// let subscriber = interest()
+///     .read()
+///     .with_fd(EventFd::new().unwrap())
+///     .with_handler(move |_efd: &mut EventFd| drop(shared.clone()));
+/// let _: SendThinBox<Eventp> = SendThinBox::new(subscriber);
+/// ```
+///
+/// Nor can a plain `ThinBoxSubscriber` be sent:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<eventp::thin::ThinBoxSubscriber<eventp::Eventp>>();
+/// ```
+#[cfg(feature = "send-subscribers")]
+pub struct SendThinBox<Ep: EventpOps>(ThinBoxSubscriber<Ep>);
+
+// SAFETY: The value is `Send`, as required by every constructor. The rest of the
+// allocation is the header, plain data and a pointer to a `'static` vtable.
+#[cfg(feature = "send-subscribers")]
+unsafe impl<Ep: EventpOps> Send for SendThinBox<Ep> {}
+
+#[cfg(feature = "send-subscribers")]
+impl<Ep: EventpOps> SendThinBox<Ep> {
+    /// Same as [`ThinBoxSubscriber::new`], for a `Send` value.
+    pub fn new<T: SendSubscriber<Ep> + HasInterest>(value: T) -> Self {
+        Self(ThinBoxSubscriber::new(value))
+    }
+
+    /// Same as [`ThinBoxSubscriber::with_interest`], for a `Send` value.
+    pub fn with_interest<T: SendSubscriber<Ep>>(value: T, interest: Interest) -> Self {
+        Self(ThinBoxSubscriber::with_interest(value, interest))
+    }
+
+    /// Returns the subscriber, to be registered, forgetting that it is `Send`.
+    pub fn into_inner(self) -> ThinBoxSubscriber<Ep> {
+        self.0
+    }
+}
+
+#[cfg(feature = "send-subscribers")]
+impl<Ep: EventpOps> From<SendThinBox<Ep>> for ThinBoxSubscriber<Ep> {
+    fn from(value: SendThinBox<Ep>) -> Self {
+        value.into_inner()
+    }
+}
+
+impl<Ep, Fd, Args, F, Fl> From<TriSubscriber<Fd, Args, F, Fl>> for ThinBoxSubscriber<Ep>
+where
+    Ep: EventpOps,
+    TriSubscriber<Fd, Args, F, Fl>: Subscriber<Ep>,
+{
+    /// Same as [`ThinBoxSubscriber::new`].
+    fn from(value: TriSubscriber<Fd, Args, F, Fl>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<Ep, Fd, St, Args, F, Fl> From<QuadSubscriber<Fd, St, Args, F, Fl>> for ThinBoxSubscriber<Ep>
+where
+    Ep: EventpOps,
+    QuadSubscriber<Fd, St, Args, F, Fl>: Subscriber<Ep>,
+{
+    /// Same as [`ThinBoxSubscriber::new`].
+    fn from(value: QuadSubscriber<Fd, St, Args, F, Fl>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<Ep> From<(Interest, Box<dyn Subscriber<Ep>>)> for ThinBoxSubscriber<Ep>
 where
     Ep: EventpOps,
 {
-    fn from(value: Box<dyn Subscriber<Ep>>) -> Self {
-        Self::from_box_dyn(value)
+    fn from((interest, value): (Interest, Box<dyn Subscriber<Ep>>)) -> Self {
+        Self::from_box_dyn(value, interest)
     }
 }
 
@@ -382,6 +972,8 @@ where
     type Error = ThinBoxSubscriber<Ep>;
 
     /// Converts a [`ThinBoxSubscriber`] back into a `Box<dyn Subscriber<Ep>>`.
+    /// The interest in the header is not carried over; read it with
+    /// [`ThinBoxSubscriber::interest`] first if it is still needed.
     ///
     /// Returns the original `ThinBoxSubscriber` as the error if the
     /// subscriber has already been dropped in place and is no longer
@@ -408,7 +1000,7 @@ impl<Ep: EventpOps> Drop for ThinBoxSubscriber<Ep> {
             fn drop(&mut self) {
                 unsafe {
                     // SAFETY: Layout must have been computable if we're in drop.
-                    let (layout, value_offset) = Layout::new::<(usize, usize)>()
+                    let (layout, value_offset) = Layout::new::<Header>()
                         .extend(self.value_layout)
                         .unwrap_unchecked();
 
@@ -443,7 +1035,7 @@ impl<Ep: EventpOps> Drop for ThinBoxSubscriber<Ep> {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, target_os = "linux"))]
 mod tests {
     use std::cell::Cell;
     use std::os::fd::{AsFd, BorrowedFd};
@@ -461,8 +1053,7 @@ mod tests {
     /// a zero-sized `repr(align(N))` tag and an arbitrary payload `P`.
     ///
     /// `Align` controls the resulting `align_of::<TestSub<_, _>>()` so we can
-    /// exercise the offset-padding logic between the (raw_fd, vptr) header and
-    /// the value slot.
+    /// exercise the offset-padding logic between the header and the value slot.
     #[repr(C)]
     struct TestSub<Align: Copy, P> {
         _align: Align,
@@ -530,6 +1121,12 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "send-subscribers")]
+    const _: () = {
+        const fn assert_send<T: Send>() {}
+        assert_send::<SendThinBox<Eventp>>();
+    };
+
     /// Counter helper: each test uses its own static so tests can run in
     /// parallel without stepping on each other.
     macro_rules! drop_counter {
@@ -552,6 +1149,48 @@ mod tests {
         assert_eq!(counter.load(Ordering::SeqCst), 1);
     }
 
+    #[test]
+    fn handle_calls_the_handler_until_dropped_in_place() {
+        let counter = drop_counter!();
+        let calls = std::rc::Rc::new(Cell::new(0));
+        let c = calls.clone();
+        let sub = make_sub::<(), _>(move || c.set(c.get() + 1), counter);
+        let event = Event::new(EpollFlags::EPOLLIN);
+
+        let mut thin = ThinBoxSubscriber::<Eventp>::new(sub);
+        thin.handle(event, crate::pinned!(Eventp::default()));
+        thin.try_handle(event, crate::pinned!(Eventp::default()))
+            .unwrap();
+        assert_eq!(calls.get(), 2);
+
+        thin.drop_in_place();
+        thin.handle(event, crate::pinned!(Eventp::default()));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn raw_pointer_round_trips_ownership() {
+        let counter = drop_counter!();
+        let sub = make_sub::<(), _>(|| {}, counter);
+        let expected_fd = sub.eventfd.as_fd().as_raw_fd();
+
+        let ptr = ThinBoxSubscriber::<Eventp>::new(sub).into_raw();
+        assert_eq!(counter.load(Ordering::SeqCst), 0);
+
+        // SAFETY: `ptr` comes from `into_raw` and is given back once.
+        let thin = unsafe { ThinBoxSubscriber::<Eventp>::from_raw(ptr) };
+        assert_eq!(thin.as_raw(), ptr);
+        {
+            // SAFETY: `thin` owns the subscriber and outlives the borrow.
+            let borrowed = unsafe { ThinBoxSubscriber::<Eventp>::from_data(thin.to_data()) };
+            assert_eq!(*borrowed.raw_fd_ref(), expected_fd);
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 0);
+
+        drop(thin);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn raw_fd_ref_returns_subscribers_fd() {
         let counter = drop_counter!();
@@ -562,6 +1201,24 @@ mod tests {
         assert_eq!(*thin.raw_fd_ref(), expected_fd);
     }
 
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn generation_is_packed_above_the_address() {
+        let sub = make_sub::<(), _>(|| {}, drop_counter!());
+        let mut thin = ThinBoxSubscriber::<Eventp>::new(sub);
+        let addr = thin.as_raw() as usize as u64;
+        assert_eq!(thin.to_data(), addr);
+
+        thin.set_generation(u16::MAX);
+        let data = thin.to_data();
+        assert_eq!(data, addr | 0xffff << 48);
+        // SAFETY: `thin` owns the subscriber and outlives the borrow.
+        let borrowed = unsafe { ThinBoxSubscriber::<Eventp>::from_data(data) };
+        assert_eq!(borrowed.as_raw(), thin.as_raw());
+        assert!(borrowed.is_current(data));
+        assert!(!borrowed.is_current(addr));
+    }
+
     #[test]
     fn try_deref_mut_dispatches_to_handler() {
         let counter = drop_counter!();
@@ -582,6 +1239,58 @@ mod tests {
         assert_eq!(call_count.get(), 1);
     }
 
+    #[test]
+    fn interest_is_kept_in_header() {
+        let counter = drop_counter!();
+        let sub = make_sub::<(), _>(|| {}, counter);
+        let read = Interest::new(EpollFlags::EPOLLIN);
+
+        let mut thin = ThinBoxSubscriber::<Eventp>::with_interest(sub, read);
+        assert_eq!(thin.interest(), read);
+
+        // Updating the interest must not disturb the neighbouring header fields.
+        let expected_fd = *thin.raw_fd_ref();
+        let edge = Interest::new(EpollFlags::EPOLLIN | EpollFlags::EPOLLET);
+        thin.set_interest(edge);
+        assert_eq!(thin.interest(), edge);
+        assert_eq!(*thin.raw_fd_ref(), expected_fd);
+        assert!(thin.try_deref_mut().is_some());
+
+        // It outlives the value, as the loop may still read it for a deferred drop.
+        thin.drop_in_place();
+        assert_eq!(thin.interest(), edge);
+    }
+
+    #[test]
+    fn record_event_diffs_against_the_previous_event() {
+        let counter = drop_counter!();
+        let sub = make_sub::<(), _>(|| {}, counter);
+        let read = Event::new(EpollFlags::EPOLLIN);
+        let read_write = Event::new(EpollFlags::EPOLLIN | EpollFlags::EPOLLOUT);
+
+        let mut thin = ThinBoxSubscriber::<Eventp>::with_interest(sub, Interest::default());
+        assert_eq!(thin.record_event(read), None);
+
+        thin.set_interest(Interest::default().track_deltas());
+        let delta = thin.record_event(read).unwrap();
+        assert!(delta.newly_readable() && !delta.newly_writable());
+        let delta = thin.record_event(read_write).unwrap();
+        assert!(!delta.newly_readable() && delta.newly_writable());
+        assert_eq!(delta.previous(), read);
+        assert!(thin.try_deref_mut().is_some());
+    }
+
+    #[test]
+    fn new_reads_interest_from_has_interest() {
+        let counter = drop_counter!();
+        let sub = make_sub::<(), _>(|| {}, counter);
+        let read = Interest::new(EpollFlags::EPOLLIN);
+        sub.interest.set(read);
+
+        let thin = ThinBoxSubscriber::<Eventp>::new(sub);
+        assert_eq!(thin.interest(), read);
+    }
+
     #[test]
     fn drop_in_place_marks_slot_and_runs_destructor() {
         let counter = drop_counter!();
@@ -639,8 +1348,8 @@ mod tests {
     }
 
     /// Builds and round-trips a subscriber whose alignment is `$align`.
-    /// Exercises the padding the layout code inserts between the (raw_fd,
-    /// vptr) header and the value when `align_of::<T>() > align_of::<usize>()`.
+    /// Exercises the padding the layout code inserts between the header and the
+    /// value when `align_of::<T>() > align_of::<usize>()`.
     macro_rules! align_roundtrip_test {
         ($name:ident, $align:literal) => {
             #[test]
@@ -677,6 +1386,113 @@ mod tests {
     align_roundtrip_test!(roundtrip_align_32, 32);
     align_roundtrip_test!(roundtrip_align_64, 64);
 
+    /// Same as `align_roundtrip_test`, but through `from_box_dyn`, which takes the
+    /// `realloc` path for alignments of at least 8 and copies otherwise. The
+    /// payload checks that the value's bytes survive being shifted in place.
+    macro_rules! box_dyn_align_test {
+        ($name:ident, $align:literal) => {
+            #[test]
+            fn $name() {
+                #[derive(Copy, Clone, Default)]
+                #[repr(align($align))]
+                struct A;
+
+                let counter = drop_counter!();
+                let payload: [u64; 8] = std::array::from_fn(|i| i as u64 * 0x0101_0101);
+                let handled = std::rc::Rc::new(Cell::new(false));
+                let h = handled.clone();
+                let sub = make_sub::<A, _>(
+                    move || {
+                        assert_eq!(payload, std::array::from_fn(|i| i as u64 * 0x0101_0101));
+                        h.set(true);
+                    },
+                    counter,
+                );
+                let expected_fd = sub.eventfd.as_fd().as_raw_fd();
+                let read = Interest::new(EpollFlags::EPOLLIN);
+
+                let boxed: Box<dyn Subscriber<Eventp>> = Box::new(sub);
+                let mut thin = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed, read);
+                assert_eq!(*thin.raw_fd_ref(), expected_fd);
+                assert_eq!(thin.interest(), read);
+                assert_eq!(thin.ptr.as_ptr() as usize % $align, 0);
+
+                let mut ep = Eventp::default();
+                // SAFETY: `ep` lives until end of scope and is never moved.
+                let pinned = Pinned(unsafe { std::pin::Pin::new_unchecked(&mut ep) });
+                let s = thin.try_deref_mut().unwrap();
+                s.handle(Event::new(EpollFlags::empty()), pinned);
+                assert!(handled.get());
+
+                drop(thin);
+                assert_eq!(counter.load(Ordering::SeqCst), 1);
+            }
+        };
+    }
+
+    box_dyn_align_test!(box_dyn_align_8, 8);
+    box_dyn_align_test!(box_dyn_align_16, 16);
+    box_dyn_align_test!(box_dyn_align_64, 64);
+
+    /// A subscriber aligned to 4 bytes only, which `from_box_dyn` cannot grow in
+    /// place and must copy.
+    struct FdOnly(EventFd);
+
+    impl AsFd for FdOnly {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.0.as_fd()
+        }
+    }
+
+    impl Handler<Eventp> for FdOnly {
+        fn handle(&mut self, _event: Event, _eventp: Pinned<'_, Eventp>) {}
+    }
+
+    #[test]
+    fn from_box_dyn_copies_values_less_aligned_than_header() {
+        assert!(std::mem::align_of::<FdOnly>() < std::mem::align_of::<Header>());
+
+        let efd = new_eventfd();
+        let expected_fd = efd.as_fd().as_raw_fd();
+        let boxed: Box<dyn Subscriber<Eventp>> = Box::new(FdOnly(efd));
+        let thin = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed, Interest::default());
+        assert_eq!(*thin.raw_fd_ref(), expected_fd);
+
+        let boxed = thin.into_box_dyn().unwrap();
+        assert_eq!(boxed.as_fd().as_raw_fd(), expected_fd);
+    }
+
+    #[test]
+    fn allocated_bytes_counts_header_padding_and_value() {
+        let header = std::mem::size_of::<Header>();
+        let mut thin =
+            ThinBoxSubscriber::<Eventp>::with_interest(FdOnly(new_eventfd()), Interest::default());
+        assert_eq!(
+            thin.allocated_bytes(),
+            header + std::mem::size_of::<FdOnly>()
+        );
+        thin.drop_in_place();
+        assert_eq!(
+            thin.allocated_bytes(),
+            header + std::mem::size_of::<FdOnly>()
+        );
+
+        #[repr(C, align(64))]
+        struct Aligned(EventFd);
+        impl AsFd for Aligned {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.0.as_fd()
+            }
+        }
+        impl Handler<Eventp> for Aligned {
+            fn handle(&mut self, _event: Event, _eventp: Pinned<'_, Eventp>) {}
+        }
+        let boxed: Box<dyn Subscriber<Eventp>> = Box::new(Aligned(new_eventfd()));
+        let thin = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed, Interest::default());
+        // The header, of 80 bytes, is padded up to the alignment of the value.
+        assert_eq!(thin.allocated_bytes(), 128 + 64);
+    }
+
     #[test]
     fn from_box_dyn_matches_new() {
         let counter = drop_counter!();
@@ -684,7 +1500,7 @@ mod tests {
         let expected_fd = sub.eventfd.as_fd().as_raw_fd();
 
         let boxed: Box<dyn Subscriber<Eventp>> = Box::new(sub);
-        let thin = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed);
+        let thin = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed, Interest::default());
         assert_eq!(*thin.raw_fd_ref(), expected_fd);
 
         drop(thin);
@@ -697,7 +1513,7 @@ mod tests {
         let sub = make_sub::<(), _>(|| {}, counter);
 
         let boxed: Box<dyn Subscriber<Eventp>> = Box::new(sub);
-        let thin: ThinBoxSubscriber<Eventp> = boxed.into();
+        let thin: ThinBoxSubscriber<Eventp> = (Interest::default(), boxed).into();
         drop(thin);
         assert_eq!(counter.load(Ordering::SeqCst), 1);
     }
@@ -765,6 +1581,24 @@ mod tests {
         assert_eq!(drops.load(Ordering::SeqCst), 1);
     }
 
+    #[test]
+    fn downcast_moves_the_value_out_once() {
+        let counter = drop_counter!();
+        let sub = make_sub::<(), fn()>(|| {}, counter);
+        let expected_fd = sub.eventfd.as_fd().as_raw_fd();
+
+        let thin = ThinBoxSubscriber::<Eventp>::new(sub);
+        let Err(thin) = thin.downcast::<FdOnly>() else {
+            panic!("not an `FdOnly`");
+        };
+        let sub = thin.downcast::<TestSub<(), fn()>>().ok().unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 0);
+        assert_eq!(sub.eventfd.as_fd().as_raw_fd(), expected_fd);
+
+        drop(sub);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn into_box_dyn_round_trips_value_and_runs_destructor_once() {
         let counter = drop_counter!();
@@ -786,8 +1620,7 @@ mod tests {
     /// produce a `Box` whose deallocation path matches `Box::<Self>::new`.
     ///
     /// The subscriber holds no state, so it cannot own an `EventFd`; we use
-    /// stdin as a borrowed fd just to satisfy `AsFd`, and a leaked
-    /// `Cell<Interest>` to satisfy `HasInterest`. Neither is exercised here.
+    /// stdin as a borrowed fd just to satisfy `AsFd`. It is never exercised here.
     struct ZstSub;
 
     impl AsFd for ZstSub {
@@ -798,27 +1631,6 @@ mod tests {
         }
     }
 
-    impl HasInterest for ZstSub {
-        fn interest(&self) -> &Cell<Interest> {
-            // `Cell` is not `Sync`, so it cannot live in a `static`. Leak a
-            // `Box` once instead; the resulting `&'static Cell<Interest>` is
-            // good for the rest of the process. Per-call leaks would be
-            // wasteful, so cache the pointer in a `OnceLock<usize>`.
-            use std::sync::OnceLock;
-            static SLOT: OnceLock<usize> = OnceLock::new();
-            let addr = *SLOT.get_or_init(|| {
-                let leaked: &'static Cell<Interest> =
-                    Box::leak(Box::new(Cell::new(Interest::new(EpollFlags::empty()))));
-                leaked as *const Cell<Interest> as usize
-            });
-            // SAFETY: `addr` was produced from `Box::leak`, which yields a
-            // valid pointer with `'static` lifetime. `Cell` is `!Sync` but
-            // we only ever read `addr` here, not the cell's contents, and
-            // the test never accesses it concurrently.
-            unsafe { &*(addr as *const Cell<Interest>) }
-        }
-    }
-
     impl Handler<Eventp> for ZstSub {
         fn handle(&mut self, _event: Event, _eventp: Pinned<'_, Eventp>) {}
     }
@@ -827,13 +1639,48 @@ mod tests {
     fn into_box_dyn_handles_zst_subscriber() {
         assert_eq!(std::mem::size_of::<ZstSub>(), 0);
 
-        let thin = ThinBoxSubscriber::<Eventp>::new(ZstSub);
+        let thin = ThinBoxSubscriber::<Eventp>::with_interest(ZstSub, Interest::default());
         let boxed = thin.into_box_dyn().expect("alive ZST must convert");
         // Dropping the `Box` must not call the global allocator with a
         // zero-sized layout. If it does, Miri / a sanitizer will flag it.
         drop(boxed);
     }
 
+    #[test]
+    fn from_box_dyn_handles_zst_subscriber() {
+        let boxed: Box<dyn Subscriber<Eventp>> = Box::new(ZstSub);
+        // A ZST `Box` owns no allocation; freeing it here would be UB.
+        let thin = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed, Interest::default());
+        assert_eq!(*thin.raw_fd_ref(), 0);
+        drop(thin);
+    }
+
+    #[test]
+    fn over_aligned_zst_subscriber_round_trips() {
+        #[repr(align(64))]
+        struct AlignedZst;
+
+        impl AsFd for AlignedZst {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                // SAFETY: Same as `ZstSub`.
+                unsafe { BorrowedFd::borrow_raw(0) }
+            }
+        }
+
+        impl Handler<Eventp> for AlignedZst {
+            fn handle(&mut self, _event: Event, _eventp: Pinned<'_, Eventp>) {}
+        }
+
+        assert_eq!(std::mem::size_of::<AlignedZst>(), 0);
+
+        let thin = ThinBoxSubscriber::<Eventp>::with_interest(AlignedZst, Interest::default());
+        assert_eq!(thin.ptr.as_ptr() as usize % 64, 0);
+        let boxed = thin.into_box_dyn().unwrap();
+        let thin = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed, Interest::default());
+        assert_eq!(thin.ptr.as_ptr() as usize % 64, 0);
+        assert_eq!(*thin.raw_fd_ref(), 0);
+    }
+
     #[test]
     fn into_box_dyn_round_trips_through_thin_again() {
         let counter = drop_counter!();
@@ -842,7 +1689,7 @@ mod tests {
 
         let thin1 = ThinBoxSubscriber::<Eventp>::new(sub);
         let boxed = thin1.into_box_dyn().unwrap();
-        let thin2 = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed);
+        let thin2 = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed, Interest::default());
         assert_eq!(*thin2.raw_fd_ref(), expected_fd);
 
         drop(thin2);
@@ -884,7 +1731,7 @@ mod tests {
         let boxed: Box<dyn Subscriber<Eventp>> = Box::new(PanickingFdSub::new(drops));
 
         let result = catch_unwind(AssertUnwindSafe(|| {
-            let _ = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed);
+            let _ = ThinBoxSubscriber::<Eventp>::from_box_dyn(boxed, Interest::default());
         }));
         assert!(result.is_err(), "as_fd panic must propagate");
         assert_eq!(drops.load(Ordering::SeqCst), 1);
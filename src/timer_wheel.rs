@@ -0,0 +1,885 @@
+//! Any number of cheap timeouts on a single `timerfd`, such as one per connection.
+//!
+//! A [`TimerWheelSubscriber`] keeps its timers in a hierarchical timing wheel, and
+//! arms its `timerfd` for the earliest of them only. Each time it fires, the due
+//! callbacks run in deadline order, and the `timerfd` is armed again for the next
+//! one. Scheduling, rescheduling and cancelling a timer are constant time, and only
+//! make a syscall when the earliest deadline moves forward.
+//!
+//! Timers are scheduled through a [`TimerHandle`], which is cheap to clone and can
+//! be kept by the other handlers of the same loop. It is not `Send`: the timers run
+//! on the loop thread, and are only scheduled from it.
+//!
+//! Deadlines are rounded up to the tick of the wheel, 1 ms by default, so a timer
+//! never runs early, and runs at most about one tick late on an idle loop.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::io;
+//! use std::time::Duration;
+//!
+//! use eventp::timer_wheel::TimerWheelSubscriber;
+//! use eventp::{Eventp, Pinned, Subscriber};
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut eventp = Eventp::default();
+//! let wheel = TimerWheelSubscriber::new()?;
+//! let timers = wheel.handle();
+//! wheel.register_into(&mut eventp)?;
+//!
+//! let idle = timers.schedule(Duration::from_secs(30), |_: Pinned<'_, Eventp>| {
+//!     println!("connection idle, closing it");
+//! })?;
+//! // Some data came in: push the timeout back.
+//! timers.reschedule(idle, Duration::from_secs(30))?;
+//! // The connection was closed: forget about it.
+//! assert!(timers.cancel(idle));
+//!
+//! timers.schedule(Duration::from_millis(1), |_: Pinned<'_, Eventp>| {})?;
+//! eventp.run_once()?;
+//! assert!(timers.is_empty());
+//! # Ok(()) }
+//! ```
+
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use nix::errno::Errno;
+use nix::sys::time::TimeSpec;
+use nix::sys::timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags};
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::{interest, Event, EventpOps, Interest, Pinned};
+
+/// A subscriber running the timers of its [`TimerHandle`]s.
+///
+/// See the [module level docs](self).
+pub struct TimerWheelSubscriber<Ep> {
+    shared: Rc<Shared<Ep>>,
+    interest: Cell<Interest>,
+}
+
+/// Schedules timers on a [`TimerWheelSubscriber`], see the [module level docs](self).
+///
+/// Timers scheduled after the subscriber is deleted from its loop never run.
+pub struct TimerHandle<Ep> {
+    shared: Rc<Shared<Ep>>,
+}
+
+/// Identifies a timer scheduled with [`TimerHandle::schedule`].
+///
+/// An id is not reused once its timer has run or been cancelled, so using a stale
+/// one is a no-op.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TimerId {
+    index: u32,
+    generation: u32,
+}
+
+type Callback<Ep> = Box<dyn FnOnce(Pinned<'_, Ep>)>;
+
+struct Shared<Ep> {
+    timerfd: TimerFd,
+    epoch: Instant,
+    tick_nanos: u64,
+    wheel: RefCell<Wheel<Callback<Ep>>>,
+    /// The tick the `timerfd` is armed for, if any.
+    armed: Cell<Option<u64>>,
+}
+
+impl<Ep> TimerWheelSubscriber<Ep> {
+    /// Creates a wheel with a tick of 1 ms, to be registered into a loop.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from creating the `timerfd`.
+    pub fn new() -> io::Result<Self> {
+        Self::with_tick(Duration::from_millis(1))
+    }
+
+    /// Creates a wheel whose deadlines are rounded up to a multiple of `tick`.
+    ///
+    /// A finer tick runs timers closer to their deadline, at the cost of more
+    /// wakeups when many deadlines are close to each other.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`new`](Self::new).
+    ///
+    /// # Panics
+    ///
+    /// If `tick` is zero.
+    pub fn with_tick(tick: Duration) -> io::Result<Self> {
+        assert!(
+            !tick.is_zero(),
+            "the tick of a timer wheel must not be zero"
+        );
+        let timerfd = TimerFd::new(
+            ClockId::CLOCK_MONOTONIC,
+            TimerFlags::TFD_NONBLOCK | TimerFlags::TFD_CLOEXEC,
+        )
+        .map_err(io::Error::from)?;
+
+        Ok(Self {
+            shared: Rc::new(Shared {
+                timerfd,
+                epoch: Instant::now(),
+                tick_nanos: u64::try_from(tick.as_nanos()).unwrap_or(u64::MAX),
+                wheel: RefCell::new(Wheel::new()),
+                armed: Cell::new(None),
+            }),
+            interest: Cell::new(interest().read()),
+        })
+    }
+
+    /// Returns a handle to schedule timers on this wheel.
+    pub fn handle(&self) -> TimerHandle<Ep> {
+        TimerHandle {
+            shared: Rc::clone(&self.shared),
+        }
+    }
+}
+
+impl<Ep> TimerHandle<Ep> {
+    /// Runs `callback` on the loop thread once `after` has elapsed, and returns the
+    /// id to reschedule or cancel it.
+    ///
+    /// Timers due in the same tick run in the order they were scheduled.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from arming the `timerfd`, in which case the timer is
+    /// not scheduled.
+    pub fn schedule<F>(&self, after: Duration, callback: F) -> io::Result<TimerId>
+    where
+        F: FnOnce(Pinned<'_, Ep>) + 'static,
+    {
+        let deadline = self.shared.deadline_after(after);
+        let id = self
+            .shared
+            .wheel
+            .borrow_mut()
+            .insert(deadline, Box::new(callback));
+        if let Err(e) = self.shared.arm_if_earlier() {
+            self.shared.wheel.borrow_mut().remove(id);
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    /// Moves the deadline of the timer `id` to `after` from now, and returns
+    /// whether it was still pending. If it has already run or been cancelled, this
+    /// is a no-op.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from arming the `timerfd`. The timer is then left at its
+    /// new deadline, and runs with the next batch of timers that does.
+    pub fn reschedule(&self, id: TimerId, after: Duration) -> io::Result<bool> {
+        let deadline = self.shared.deadline_after(after);
+        if !self.shared.wheel.borrow_mut().reschedule(id, deadline) {
+            return Ok(false);
+        }
+        self.shared.arm_if_earlier()?;
+        Ok(true)
+    }
+
+    /// Cancels the timer `id`, and returns whether it was still pending. If it has
+    /// already run or been cancelled, this is a no-op.
+    ///
+    /// A timer can be cancelled by another one due in the same batch, until its
+    /// own callback starts.
+    pub fn cancel(&self, id: TimerId) -> bool {
+        // The `timerfd` is left as it is: waking up early for nothing is cheaper
+        // than a syscall per cancellation.
+        self.shared.wheel.borrow_mut().remove(id).is_some()
+    }
+
+    /// Returns the number of pending timers.
+    pub fn len(&self) -> usize {
+        self.shared.wheel.borrow().len()
+    }
+
+    /// Returns whether no timer is pending.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<Ep> Clone for TimerHandle<Ep> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Rc::clone(&self.shared),
+        }
+    }
+}
+
+impl<Ep> Shared<Ep> {
+    fn elapsed_nanos(&self) -> u128 {
+        self.epoch.elapsed().as_nanos()
+    }
+
+    /// Returns the current tick, rounded down.
+    fn now(&self) -> u64 {
+        to_u64(self.elapsed_nanos() / u128::from(self.tick_nanos))
+    }
+
+    /// Returns the tick `after` from now, rounded up so the timer never runs early.
+    fn deadline_after(&self, after: Duration) -> u64 {
+        let tick = u128::from(self.tick_nanos);
+        to_u64((self.elapsed_nanos() + after.as_nanos() + tick - 1) / tick)
+    }
+
+    /// Arms the `timerfd` for the earliest deadline, unless it is already armed
+    /// for an earlier one.
+    fn arm_if_earlier(&self) -> io::Result<()> {
+        let next = self.wheel.borrow().next_deadline();
+        match (next, self.armed.get()) {
+            (Some(next), Some(armed)) if armed <= next => Ok(()),
+            (None, _) => Ok(()),
+            _ => self.arm(next),
+        }
+    }
+
+    /// Arms the `timerfd` for `deadline`, or disarms it.
+    fn arm(&self, deadline: Option<u64>) -> io::Result<()> {
+        match deadline {
+            Some(deadline) => {
+                let at = u128::from(deadline) * u128::from(self.tick_nanos);
+                let remaining = at.saturating_sub(self.elapsed_nanos());
+                // A zero expiration would disarm it instead.
+                let remaining = Duration::from_nanos(to_u64(remaining).max(1));
+                self.timerfd.set(
+                    Expiration::OneShot(TimeSpec::from_duration(remaining)),
+                    TimerSetTimeFlags::empty(),
+                )
+            }
+            None => self.timerfd.unset(),
+        }
+        .map_err(io::Error::from)?;
+        self.armed.set(deadline);
+        Ok(())
+    }
+}
+
+fn to_u64(n: u128) -> u64 {
+    u64::try_from(n).unwrap_or(u64::MAX)
+}
+
+impl<Ep> AsFd for TimerWheelSubscriber<Ep> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.shared.timerfd.as_fd()
+    }
+}
+
+impl<Ep> HasInterest for TimerWheelSubscriber<Ep> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep: EventpOps> Handler<Ep> for TimerWheelSubscriber<Ep> {
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, _event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        let shared = &self.shared;
+        match shared.timerfd.wait() {
+            // Not expired yet, if it was armed again since it became readable.
+            Ok(()) | Err(Errno::EAGAIN) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let due = shared.wheel.borrow_mut().advance(shared.now());
+        // Armed for what is left, before the callbacks schedule more.
+        let armed = shared.arm(shared.wheel.borrow().next_deadline());
+
+        for id in due {
+            // Taken one at a time, as an earlier callback may cancel a later one.
+            let callback = shared.wheel.borrow_mut().take_due(id);
+            if let Some(callback) = callback {
+                callback(eventp.as_mut());
+            }
+        }
+        armed
+    }
+}
+
+const LEVELS: usize = 6;
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+/// The furthest a deadline can be from the current tick, beyond which it is
+/// clamped. About 2.2 years with a 1 ms tick.
+const MAX_TICKS: u64 = 1 << (SLOT_BITS * LEVELS as u32);
+
+/// The list of the entries whose deadline is already reached, after the slots.
+const OVERDUE: usize = LEVELS * SLOTS;
+/// The list of an entry taken out of the wheel by [`Wheel::advance`].
+const DUE: usize = usize::MAX;
+
+/// Six levels of 64 slots. A slot of level `n` spans `64^n` ticks, and a deadline
+/// is kept at the lowest level whose current slot range does not contain it. Once
+/// the slot of a higher level comes due, its entries move down to a lower one.
+struct Wheel<T> {
+    /// The tick up to which the wheel has advanced.
+    elapsed: u64,
+    /// The non-empty slots, one bit per slot, per level.
+    occupied: [u64; LEVELS],
+    /// The indices of the entries in each slot, followed by the overdue list.
+    lists: Vec<Vec<u32>>,
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    next_seq: u64,
+    len: usize,
+}
+
+struct Slot<T> {
+    generation: u32,
+    entry: Option<Entry<T>>,
+}
+
+struct Entry<T> {
+    deadline: u64,
+    /// Orders the entries of the same deadline.
+    seq: u64,
+    list: usize,
+    pos: usize,
+    payload: T,
+}
+
+impl<T> Wheel<T> {
+    fn new() -> Self {
+        Self {
+            elapsed: 0,
+            occupied: [0; LEVELS],
+            lists: (0..=OVERDUE).map(|_| Vec::new()).collect(),
+            slots: Vec::new(),
+            free: Vec::new(),
+            next_seq: 0,
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn insert(&mut self, deadline: u64, payload: T) -> TimerId {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let entry = Entry {
+            deadline: self.clamp(deadline),
+            seq,
+            list: DUE,
+            pos: 0,
+            payload,
+        };
+
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize].entry = Some(entry);
+                index
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("too many timers");
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry: Some(entry),
+                });
+                index
+            }
+        };
+        self.len += 1;
+        self.link(index);
+
+        TimerId {
+            index,
+            generation: self.slots[index as usize].generation,
+        }
+    }
+
+    fn remove(&mut self, id: TimerId) -> Option<T> {
+        self.entry(id)?;
+        self.unlink(id.index);
+        Some(self.release(id.index))
+    }
+
+    fn reschedule(&mut self, id: TimerId, deadline: u64) -> bool {
+        if self.entry(id).is_none() {
+            return false;
+        }
+        self.unlink(id.index);
+        let deadline = self.clamp(deadline);
+        let entry = self.slots[id.index as usize].entry.as_mut().unwrap();
+        entry.deadline = deadline;
+        entry.seq = self.next_seq;
+        self.next_seq += 1;
+        self.link(id.index);
+        true
+    }
+
+    /// Takes out every entry due at `now`, and returns their ids in deadline order.
+    /// Their payloads are then removed with [`take_due`](Self::take_due).
+    fn advance(&mut self, now: u64) -> Vec<TimerId> {
+        let mut due = Vec::new();
+        self.take_list(OVERDUE, &mut due);
+
+        while let Some((level, slot, start)) = self.next_expiration() {
+            if start > now {
+                break;
+            }
+            self.elapsed = start;
+            self.occupied[level] &= !(1 << slot);
+            let list = std::mem::take(&mut self.lists[level * SLOTS + slot]);
+            for index in list {
+                let entry = self.slots[index as usize].entry.as_mut().unwrap();
+                if entry.deadline <= start {
+                    entry.list = DUE;
+                    due.push((entry.deadline, entry.seq, index));
+                } else {
+                    self.link(index);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+
+        due.sort_unstable();
+        due.into_iter()
+            .map(|(_, _, index)| TimerId {
+                index,
+                generation: self.slots[index as usize].generation,
+            })
+            .collect()
+    }
+
+    /// Removes the entry `id` returned by [`advance`](Self::advance), unless it
+    /// has been removed or rescheduled since.
+    fn take_due(&mut self, id: TimerId) -> Option<T> {
+        if self.entry(id)?.list != DUE {
+            return None;
+        }
+        Some(self.release(id.index))
+    }
+
+    /// Returns the tick the next entry comes due at, which only is the earliest
+    /// deadline when it is in the lowest level.
+    fn next_deadline(&self) -> Option<u64> {
+        if !self.lists[OVERDUE].is_empty() {
+            return Some(self.elapsed);
+        }
+        self.next_expiration().map(|(_, _, start)| start)
+    }
+
+    /// Returns the level, slot and starting tick of the next non-empty slot.
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        let level = self.occupied.iter().position(|&occupied| occupied != 0)?;
+        let slot_range = 1u64 << (SLOT_BITS * level as u32);
+        let level_range = slot_range << SLOT_BITS;
+
+        let now_slot = ((self.elapsed >> (SLOT_BITS * level as u32)) % SLOTS as u64) as u32;
+        let rotated = self.occupied[level].rotate_right(now_slot);
+        let slot = (rotated.trailing_zeros() + now_slot) as usize % SLOTS;
+
+        let mut start = (self.elapsed & !(level_range - 1)) + slot as u64 * slot_range;
+        if start <= self.elapsed {
+            // Only at the top level, whose slots wrap around.
+            start += level_range;
+        }
+        Some((level, slot, start))
+    }
+
+    fn entry(&self, id: TimerId) -> Option<&Entry<T>> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.entry.as_ref()
+    }
+
+    fn clamp(&self, deadline: u64) -> u64 {
+        deadline.min(self.elapsed + (MAX_TICKS - 1))
+    }
+
+    fn link(&mut self, index: u32) {
+        let entry = self.slots[index as usize].entry.as_mut().unwrap();
+        let list = if entry.deadline <= self.elapsed {
+            OVERDUE
+        } else {
+            let level = level_for(self.elapsed, entry.deadline);
+            let slot = (entry.deadline >> (SLOT_BITS * level as u32)) as usize % SLOTS;
+            self.occupied[level] |= 1 << slot;
+            level * SLOTS + slot
+        };
+        entry.list = list;
+        entry.pos = self.lists[list].len();
+        self.lists[list].push(index);
+    }
+
+    fn unlink(&mut self, index: u32) {
+        let entry = self.slots[index as usize].entry.as_ref().unwrap();
+        let (list, pos) = (entry.list, entry.pos);
+        if list == DUE {
+            return;
+        }
+
+        self.lists[list].swap_remove(pos);
+        if let Some(&moved) = self.lists[list].get(pos) {
+            self.slots[moved as usize].entry.as_mut().unwrap().pos = pos;
+        }
+        if list != OVERDUE && self.lists[list].is_empty() {
+            self.occupied[list / SLOTS] &= !(1 << (list % SLOTS));
+        }
+    }
+
+    fn take_list(&mut self, list: usize, due: &mut Vec<(u64, u64, u32)>) {
+        for index in std::mem::take(&mut self.lists[list]) {
+            let entry = self.slots[index as usize].entry.as_mut().unwrap();
+            entry.list = DUE;
+            due.push((entry.deadline, entry.seq, index));
+        }
+    }
+
+    /// Frees the slot of an unlinked entry, and returns its payload.
+    fn release(&mut self, index: u32) -> T {
+        let slot = &mut self.slots[index as usize];
+        let entry = slot.entry.take().unwrap();
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index);
+        self.len -= 1;
+        entry.payload
+    }
+}
+
+/// Returns the lowest level whose slots are wide enough that `deadline` is not in
+/// the current one.
+fn level_for(elapsed: u64, deadline: u64) -> usize {
+    let masked = ((elapsed ^ deadline) | (SLOTS as u64 - 1)).min(MAX_TICKS - 1);
+    let significant = (u64::BITS - 1 - masked.leading_zeros()) as usize;
+    significant / SLOT_BITS as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsRawFd;
+
+    use nix::sys::eventfd::{EfdFlags, EventFd};
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::tri_subscriber::WithHandler;
+    use crate::{Eventp, Subscriber};
+
+    /// Advances `wheel` one tick at a time up to `until`, recording when each
+    /// payload comes due.
+    fn drain(wheel: &mut Wheel<u32>, until: u64) -> Vec<(u64, u32)> {
+        let mut fired = Vec::new();
+        let mut now = 0;
+        while now <= until {
+            for id in wheel.advance(now) {
+                fired.push((now, wheel.take_due(id).unwrap()));
+            }
+            // Jumps straight to the next slot, as the `timerfd` would.
+            now = match wheel.next_deadline() {
+                Some(next) => next.max(now + 1),
+                None => break,
+            };
+        }
+        fired
+    }
+
+    #[test]
+    fn entries_come_due_on_time_across_levels() {
+        let mut wheel = Wheel::new();
+        let deadlines = [5, 70, 4_100, 3, 70, 300_000, 64, 63, 1 << 30];
+        for (payload, &deadline) in deadlines.iter().enumerate() {
+            wheel.insert(deadline, payload as u32);
+        }
+
+        let fired = drain(&mut wheel, u64::MAX);
+        let mut expected: Vec<_> = deadlines.iter().copied().zip(0..).collect();
+        expected.sort_by_key(|&(deadline, payload)| (deadline, payload));
+        assert_eq!(fired, expected);
+        assert_eq!(wheel.len(), 0);
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn a_jump_past_several_deadlines_returns_them_in_order() {
+        let mut wheel = Wheel::new();
+        for (payload, deadline) in [(0, 9_000), (1, 10), (2, 700), (3, 10)] {
+            wheel.insert(deadline, payload);
+        }
+        let due = wheel.advance(100_000);
+        let due: Vec<_> = due
+            .into_iter()
+            .map(|id| wheel.take_due(id).unwrap())
+            .collect();
+        assert_eq!(due, [1, 3, 2, 0]);
+
+        // Already reached: due with the next advance.
+        wheel.insert(50, 4);
+        assert_eq!(wheel.next_deadline(), Some(100_000));
+        assert_eq!(wheel.advance(100_000).len(), 1);
+    }
+
+    #[test]
+    fn removed_and_rescheduled_entries() {
+        let mut wheel = Wheel::new();
+        let a = wheel.insert(100, 0);
+        let b = wheel.insert(200, 1);
+        let c = wheel.insert(200, 2);
+
+        assert_eq!(wheel.remove(b), Some(1));
+        assert_eq!(wheel.remove(b), None);
+        assert!(wheel.reschedule(a, 300));
+        assert!(!wheel.reschedule(b, 300));
+
+        // The freed slot is reused under a new generation.
+        let d = wheel.insert(250, 3);
+        assert_eq!(d.index, b.index);
+        assert_eq!(wheel.remove(b), None);
+
+        assert_eq!(drain(&mut wheel, u64::MAX), [(200, 2), (250, 3), (300, 0)]);
+        assert_eq!(wheel.remove(c), None);
+        assert!(wheel.occupied.iter().all(|&occupied| occupied == 0));
+    }
+
+    #[test]
+    fn due_entries_can_still_be_removed() {
+        let mut wheel = Wheel::new();
+        let a = wheel.insert(10, 0);
+        let b = wheel.insert(10, 1);
+        let due = wheel.advance(10);
+        assert_eq!(due, [a, b]);
+
+        assert_eq!(wheel.take_due(a), Some(0));
+        assert_eq!(wheel.remove(b), Some(1));
+        assert_eq!(wheel.take_due(b), None);
+        assert_eq!(wheel.len(), 0);
+    }
+
+    #[test]
+    fn far_deadlines_are_clamped() {
+        let mut wheel = Wheel::new();
+        wheel.insert(u64::MAX, 0);
+        assert_eq!(drain(&mut wheel, u64::MAX), [(MAX_TICKS - 1, 0)]);
+    }
+
+    fn new_wheel(eventp: &mut Eventp, tick: Duration) -> TimerHandle<Eventp> {
+        let wheel = TimerWheelSubscriber::with_tick(tick).unwrap();
+        let timers = wheel.handle();
+        wheel.register_into(eventp).unwrap();
+        timers
+    }
+
+    fn run_until_empty(eventp: &mut Eventp, timers: &TimerHandle<Eventp>) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !timers.is_empty() {
+            assert!(Instant::now() < deadline, "{} timers lost", timers.len());
+            eventp
+                .run_once_with_timeout(EpollTimeout::try_from(100).unwrap())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn callbacks_run_in_deadline_order() {
+        let mut eventp = Eventp::default();
+        let timers = new_wheel(&mut eventp, Duration::from_millis(1));
+        let fired = Rc::new(RefCell::new(Vec::new()));
+
+        let start = Instant::now();
+        for (n, millis) in [(0, 30), (1, 10), (2, 20), (3, 10)] {
+            let fired = fired.clone();
+            timers
+                .schedule(
+                    Duration::from_millis(millis),
+                    move |_: Pinned<'_, Eventp>| {
+                        assert!(start.elapsed() >= Duration::from_millis(millis));
+                        fired.borrow_mut().push(n);
+                    },
+                )
+                .unwrap();
+        }
+        run_until_empty(&mut eventp, &timers);
+        assert_eq!(*fired.borrow(), [1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn cancel_and_reschedule() {
+        let mut eventp = Eventp::default();
+        let timers = new_wheel(&mut eventp, Duration::from_millis(1));
+        let fired = Rc::new(Cell::new(0));
+
+        let f = fired.clone();
+        let id = timers
+            .schedule(Duration::ZERO, move |_: Pinned<'_, Eventp>| {
+                f.set(f.get() + 1)
+            })
+            .unwrap();
+        run_until_empty(&mut eventp, &timers);
+        assert_eq!(fired.get(), 1);
+        // A no-op once fired.
+        assert!(!timers.cancel(id));
+        assert!(!timers.reschedule(id, Duration::ZERO).unwrap());
+
+        let f = fired.clone();
+        let cancelled = timers
+            .schedule(Duration::from_millis(5), move |_: Pinned<'_, Eventp>| {
+                f.set(f.get() + 10)
+            })
+            .unwrap();
+        let rescheduled = timers
+            .schedule(Duration::from_secs(60), |_: Pinned<'_, Eventp>| {})
+            .unwrap();
+        assert!(timers.reschedule(rescheduled, Duration::ZERO).unwrap());
+        assert!(timers.cancel(cancelled));
+        assert!(!timers.cancel(cancelled));
+        run_until_empty(&mut eventp, &timers);
+        assert_eq!(fired.get(), 1);
+    }
+
+    #[test]
+    fn timers_cancel_timers_of_the_same_batch() {
+        let mut eventp = Eventp::default();
+        let timers = new_wheel(&mut eventp, Duration::from_millis(1));
+        let fired = Rc::new(Cell::new(false));
+
+        let later = Rc::new(Cell::new(None));
+        let (t, l) = (timers.clone(), later.clone());
+        timers
+            .schedule(Duration::ZERO, move |_: Pinned<'_, Eventp>| {
+                assert!(t.cancel(l.get().unwrap()));
+            })
+            .unwrap();
+        let f = fired.clone();
+        later.set(Some(
+            timers
+                .schedule(Duration::ZERO, move |_: Pinned<'_, Eventp>| f.set(true))
+                .unwrap(),
+        ));
+        run_until_empty(&mut eventp, &timers);
+        assert!(!fired.get());
+    }
+
+    #[test]
+    fn handles_are_usable_from_other_handlers() {
+        let mut eventp = Eventp::default();
+        let timers = new_wheel(&mut eventp, Duration::from_millis(1));
+        let fired = Rc::new(Cell::new(0));
+
+        let eventfd = EventFd::from_flags(EfdFlags::EFD_NONBLOCK).unwrap();
+        eventfd.write(1).unwrap();
+        let (t, f) = (timers.clone(), fired.clone());
+        interest()
+            .read()
+            .with_fd(eventfd)
+            .with_handler(move |eventfd: &mut EventFd| {
+                let _ = eventfd.read();
+                let f = f.clone();
+                let t2 = t.clone();
+                t.schedule(Duration::from_millis(2), move |_: Pinned<'_, Eventp>| {
+                    f.set(f.get() + 1);
+                    // And from the callbacks themselves.
+                    let f = f.clone();
+                    t2.schedule(Duration::ZERO, move |_: Pinned<'_, Eventp>| {
+                        f.set(f.get() + 1)
+                    })
+                    .unwrap();
+                })
+                .unwrap();
+            })
+            .register_into(&mut eventp)
+            .unwrap();
+
+        eventp.run_once().unwrap();
+        assert_eq!(timers.len(), 1);
+        run_until_empty(&mut eventp, &timers);
+        assert_eq!(fired.get(), 2);
+    }
+
+    #[test]
+    fn callbacks_can_delete_the_wheel() {
+        let mut eventp = Eventp::default();
+        let wheel = TimerWheelSubscriber::new().unwrap();
+        let timers = wheel.handle();
+        let raw_fd = wheel.as_fd().as_raw_fd();
+        wheel.register_into(&mut eventp).unwrap();
+
+        timers
+            .schedule(Duration::ZERO, move |mut eventp: Pinned<'_, Eventp>| {
+                eventp.delete(raw_fd).unwrap();
+            })
+            .unwrap();
+        timers
+            .schedule(Duration::from_secs(60), |_: Pinned<'_, Eventp>| {})
+            .unwrap();
+        eventp.run_once().unwrap();
+        assert!(eventp.interest(&raw_fd).is_none());
+        assert_eq!(timers.len(), 1);
+    }
+
+    #[test]
+    fn stress_random_durations() {
+        const TIMERS: usize = 50_000;
+        // Deadlines up to 6000 ticks, spread over the three lowest levels.
+        const TICK: Duration = Duration::from_micros(50);
+        const MAX_MILLIS: u64 = 300;
+
+        let mut eventp = Eventp::default();
+        let timers = new_wheel(&mut eventp, TICK);
+        let fired = Rc::new(RefCell::new(Vec::with_capacity(TIMERS)));
+
+        // A xorshift generator is random enough, with a fixed seed to reproduce.
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut cancelled = Vec::new();
+        for n in 0..TIMERS {
+            let after = Duration::from_micros(random() % (MAX_MILLIS * 1000));
+            let due = Instant::now() + after;
+            let fired = fired.clone();
+            let id = timers
+                .schedule(after, move |_: Pinned<'_, Eventp>| {
+                    fired.borrow_mut().push((n, due, Instant::now()));
+                })
+                .unwrap();
+            if random() % 10 == 0 {
+                cancelled.push(id);
+            }
+        }
+        for &id in &cancelled {
+            assert!(timers.cancel(id));
+        }
+        run_until_empty(&mut eventp, &timers);
+
+        let fired = fired.borrow();
+        assert_eq!(fired.len(), TIMERS - cancelled.len());
+        let mut seen = vec![false; TIMERS];
+        for &(n, due, at) in fired.iter() {
+            assert!(
+                !std::mem::replace(&mut seen[n], true),
+                "timer {n} ran twice"
+            );
+            assert!(at >= due, "timer {n} ran early");
+            assert!(at - due < Duration::from_secs(1), "timer {n} ran late");
+        }
+        // Deadlines are rounded to ticks, computed a little after `due`.
+        let tolerance = TICK * 2 + Duration::from_millis(5);
+        for pair in fired.windows(2) {
+            let ((a, a_due, _), (b, b_due, _)) = (pair[0], pair[1]);
+            assert!(a_due <= b_due + tolerance, "timer {a} ran before {b}");
+        }
+    }
+}
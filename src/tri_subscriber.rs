@@ -1,11 +1,115 @@
 //! A ternary subscriber, composed of a file descriptor, interest, and a handler.
+//!
+//! # Handler parameters
+//!
+//! Handler closures take any of the following parameters, in any order:
+//!
+//! - `&mut Fd`, the watched fd, e.g. `&mut OwnedFd` for
+//!   [`with_owned_fd`](Interest::with_owned_fd).
+//! - `&mut St`, the state of a [`QuadSubscriber`], see [State](#state).
+//! - [`RawFd`], the number of the watched fd.
+//! - [`Event`], the event being dispatched.
+//! - [`Interest`], the interest the fd is currently registered with.
+//! - [`UserData`], the [user data](Interest::user_data) of the registration.
+//! - [`EventDelta`], how the event differs from the previous one, for interests with
+//!   [`track_deltas`](Interest::track_deltas).
+//! - [`SubscriberHandle`], to modify or delete the registration.
+//! - [`BatchInfo`], where the event stands in its batch.
+//! - [`SinceLast`], the time since the previous event, for interests with
+//!   [`track_last_event`](Interest::track_last_event).
+//! - [`Pinned<'_, Ep>`](Pinned), the event loop.
+//! - [`ViewOps<'_, Ep>`](ViewOps), the event loop, read-only, in place of `Pinned`.
+//!
+//! # Fallible handlers
+//!
+//! Handler closures may return either `()` or `io::Result<()>`, for every parameter
+//! permutation. An `Err` is reported to the event loop, which deals with it according
+//! to its [`ErrorPolicy`](crate::ErrorPolicy).
+//!
+//! ```rust
+//! # use std::io;
+//! use eventp::{tri_subscriber::WithHandler, ErrorPolicy, Eventp, Subscriber};
+//! use nix::sys::eventfd::EventFd;
+//!
+//! fn thread_main(eventfd: EventFd) -> io::Result<()> {
+//!     let mut reactor = Eventp::builder().error_policy(ErrorPolicy::Remove).build()?;
+//!
+//!     eventp::interest()
+//!         .read()
+//!         .with_fd(eventfd)
+//!         .with_handler(|eventfd: &mut EventFd| -> io::Result<()> {
+//!             eventfd.read()?;
+//!             Ok(())
+//!         })
+//!         .register_into(&mut reactor)?;
+//!
+//!     reactor.run_forever()
+//! }
+//! ```
+//!
+//! # Filters
+//!
+//! [`filter`](TriSubscriber::filter) drops the events its function returns `false`
+//! for before they reach the handler, and [`on_filtered`](TriSubscriber::on_filtered)
+//! hands them to a fallback instead, which many subscribers may share.
+//!
+//! # State
+//!
+//! State kept next to the fd, such as a buffer or a parser, is given with
+//! [`with_state`](WithState::with_state), for a [`QuadSubscriber`] whose handler
+//! takes it as `&mut St`, apart from the fd. Handlers remain plain functions, and
+//! can be called directly in tests:
+//!
+//! ```rust
+//! # use std::io::{self, Read};
+//! # use std::os::fd::{AsFd, AsRawFd};
+//! use std::os::unix::net::UnixStream;
+//!
+//! use eventp::tri_subscriber::{WithHandler, WithState};
+//! use eventp::{EventpOps, Pinned};
+//!
+//! #[derive(Default)]
+//! struct Connection {
+//!     received: usize,
+//! }
+//!
+//! fn on_data(
+//!     stream: &mut UnixStream,
+//!     conn: &mut Connection,
+//!     mut eventp: Pinned<'_, impl EventpOps>,
+//! ) -> io::Result<()> {
+//!     let mut buf = [0; 512];
+//!     match stream.read(&mut buf)? {
+//!         0 => eventp.delete(stream.as_fd().as_raw_fd()),
+//!         n => {
+//!             conn.received += n;
+//!             Ok(())
+//!         }
+//!     }
+//! }
+//!
+//! # fn main() -> io::Result<()> {
+//! # let (stream, _peer) = UnixStream::pair()?;
+//! # let mut eventp = eventp::Eventp::default();
+//! # use eventp::Subscriber;
+//! eventp::interest()
+//!     .read()
+//!     .with_fd(stream)
+//!     .with_state(Connection::default())
+//!     .with_handler(on_data)
+//!     .register_into(&mut eventp)?;
+//! # Ok(()) }
+//! ```
 
 use std::cell::Cell;
+use std::io;
 use std::marker::PhantomData;
-use std::os::fd::{AsFd, BorrowedFd};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::time::Duration;
 
+use crate::epoll::EpollFlags;
 use crate::subscriber::{Handler, HasInterest};
-use crate::{Event, EventpOps, Interest, Pinned};
+use crate::{BatchInfo, Event, EventDelta, EventpOps, Interest, Pinned, SubscriberHandle, ViewOps};
 
 /// A ternary subscriber, composed of a file descriptor, interest, and a handler.
 ///
@@ -21,18 +125,58 @@ use crate::{Event, EventpOps, Interest, Pinned};
 /// ```
 ///
 /// It is rarely constructed manually.
-pub struct TriSubscriber<Fd, Args, F> {
+pub struct TriSubscriber<Fd, Args, F, Fl = NoFilter> {
     /// The file descriptor being watched.
     pub fd: Fd,
 
-    /// The set of I/O readiness events this subscriber is interested in.
+    /// The set of I/O readiness events this subscriber is registered with.
     ///
-    /// Wrapped in a `Cell` so that handlers can adjust the interest in place
-    /// without needing `&mut` access to the subscriber.
+    /// Only read at registration. Afterwards the loop keeps the current interest, and
+    /// that is what an `Interest` handler parameter receives. It falls back to this
+    /// value when the loop does not keep one, as with [`MockEventp`](crate::MockEventp).
     pub interest: Cell<Interest>,
 
     /// The closure invoked when one of the interested events fires.
     pub handler: FnHandler<Args, F>,
+
+    /// The name of the subscriber in diagnostics, set by [`named`](Self::named).
+    pub name: Option<&'static str>,
+
+    /// Which events reach the handler, set by [`filter`](Self::filter).
+    pub filter: Fl,
+}
+
+/// A [`TriSubscriber`] with some state of the handler, which it takes as a separate
+/// `&mut St` parameter. See [State](self#state).
+///
+/// Created with:
+///
+/// ```rust,ignore
+/// interest()
+///     .read()
+///     .with_fd(fd)
+///     .with_state(state)
+///     .with_handler(handler)
+/// ```
+pub struct QuadSubscriber<Fd, St, Args, F, Fl = NoFilter> {
+    /// The file descriptor being watched.
+    pub fd: Fd,
+
+    /// The state handed to the handler.
+    pub state: St,
+
+    /// The set of I/O readiness events this subscriber is registered with, as for
+    /// [`TriSubscriber::interest`].
+    pub interest: Cell<Interest>,
+
+    /// The closure invoked when one of the interested events fires.
+    pub handler: FnHandler<Args, F>,
+
+    /// The name of the subscriber in diagnostics, set by [`named`](Self::named).
+    pub name: Option<&'static str>,
+
+    /// Which events reach the handler, as for [`TriSubscriber::filter`].
+    pub filter: Fl,
 }
 
 /// A wrapper for `FnMut` closures.
@@ -47,7 +191,172 @@ pub struct FnHandler<Args, F> {
     _marker: PhantomData<fn(Args)>,
 }
 
-impl<Fd, Args, F> AsFd for TriSubscriber<Fd, Args, F>
+impl<Fd, Args, F, Fl> TriSubscriber<Fd, Args, F, Fl> {
+    /// Returns the fd, dropping the interest and the handler, e.g. after
+    /// [`try_register`](crate::Subscriber::try_register) gave the subscriber back.
+    pub fn into_fd(self) -> Fd {
+        self.fd
+    }
+
+    /// Names the subscriber, e.g. after what the fd is for, so the loop's
+    /// diagnostics show the name next to the fd: the log of a failing or panicking
+    /// handler, the [event log](crate::Eventp::enable_event_log) and
+    /// [`Eventp::dump`](crate::Eventp::dump).
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use eventp::{tri_subscriber::WithHandler, Eventp, Subscriber};
+    /// use nix::sys::eventfd::EventFd;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// eventp::interest()
+    ///     .read()
+    ///     .with_fd(EventFd::new()?)
+    ///     .with_handler(|efd: &mut EventFd| {
+    ///         let _ = efd.read();
+    ///     })
+    ///     .named("shutdown notifier")
+    ///     .register_into(&mut eventp)?;
+    /// assert_eq!(eventp.dump()[0].2, Some("shutdown notifier"));
+    /// # Ok(()) }
+    /// ```
+    pub fn named(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+}
+
+impl<Fd, Args, F> TriSubscriber<Fd, Args, F> {
+    /// Calls the handler only for the events `keep` returns `true` for, dropping
+    /// the others, e.g. the hangups of a connection whose handler only reads.
+    /// Dropped events go to [`on_filtered`](Self::on_filtered), if any.
+    ///
+    /// `keep` is a function pointer, so the subscriber only grows by a word, and
+    /// subscribers without a filter not at all.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::os::unix::net::UnixStream;
+    ///
+    /// use eventp::{tri_subscriber::WithHandler, Event, Eventp, Subscriber};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// # let (stream, _peer) = UnixStream::pair()?;
+    /// let mut eventp = Eventp::default();
+    /// eventp::interest()
+    ///     .read()
+    ///     .read_hangup()
+    ///     .with_fd(stream)
+    ///     .with_handler(|_stream: &mut UnixStream| {
+    ///         // Only called with data to read.
+    ///     })
+    ///     .filter(|event: Event| !event.is_closed())
+    ///     .register_into(&mut eventp)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn filter(self, keep: fn(Event) -> bool) -> TriSubscriber<Fd, Args, F, Filter> {
+        TriSubscriber {
+            fd: self.fd,
+            interest: self.interest,
+            handler: self.handler,
+            name: self.name,
+            filter: Filter {
+                keep,
+                on_filtered: NoFallback,
+            },
+        }
+    }
+}
+
+impl<Fd, Args, F> TriSubscriber<Fd, Args, F, Filter> {
+    /// Calls `on_filtered` with the events dropped by the [filter](Self::filter),
+    /// and the raw fd, instead of nothing. Its result is reported to the loop, as
+    /// that of the handler. One function can serve many subscribers, e.g. deleting
+    /// any closed connection.
+    pub fn on_filtered<G>(self, on_filtered: G) -> TriSubscriber<Fd, Args, F, Filter<G>> {
+        TriSubscriber {
+            fd: self.fd,
+            interest: self.interest,
+            handler: self.handler,
+            name: self.name,
+            filter: Filter {
+                keep: self.filter.keep,
+                on_filtered,
+            },
+        }
+    }
+}
+
+impl<Fd, Args, F, Fl> AsFd for TriSubscriber<Fd, Args, F, Fl>
+where
+    Fd: AsFd,
+{
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl<Fd, Args, F, Fl> HasInterest for TriSubscriber<Fd, Args, F, Fl> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+}
+
+impl<Fd, St, Args, F, Fl> QuadSubscriber<Fd, St, Args, F, Fl> {
+    /// Returns the fd and the state, dropping the interest and the handler.
+    pub fn into_parts(self) -> (Fd, St) {
+        (self.fd, self.state)
+    }
+
+    /// Names the subscriber, as [`TriSubscriber::named`].
+    pub fn named(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+}
+
+impl<Fd, St, Args, F> QuadSubscriber<Fd, St, Args, F> {
+    /// Calls the handler only for the events `keep` returns `true` for, as
+    /// [`TriSubscriber::filter`].
+    pub fn filter(self, keep: fn(Event) -> bool) -> QuadSubscriber<Fd, St, Args, F, Filter> {
+        QuadSubscriber {
+            fd: self.fd,
+            state: self.state,
+            interest: self.interest,
+            handler: self.handler,
+            name: self.name,
+            filter: Filter {
+                keep,
+                on_filtered: NoFallback,
+            },
+        }
+    }
+}
+
+impl<Fd, St, Args, F> QuadSubscriber<Fd, St, Args, F, Filter> {
+    /// Calls `on_filtered` with the events dropped by the [filter](Self::filter), as
+    /// [`TriSubscriber::on_filtered`].
+    pub fn on_filtered<G>(self, on_filtered: G) -> QuadSubscriber<Fd, St, Args, F, Filter<G>> {
+        QuadSubscriber {
+            fd: self.fd,
+            state: self.state,
+            interest: self.interest,
+            handler: self.handler,
+            name: self.name,
+            filter: Filter {
+                keep: self.filter.keep,
+                on_filtered,
+            },
+        }
+    }
+}
+
+impl<Fd, St, Args, F, Fl> AsFd for QuadSubscriber<Fd, St, Args, F, Fl>
 where
     Fd: AsFd,
 {
@@ -56,10 +365,14 @@ where
     }
 }
 
-impl<Fd, Args, F> HasInterest for TriSubscriber<Fd, Args, F> {
+impl<Fd, St, Args, F, Fl> HasInterest for QuadSubscriber<Fd, St, Args, F, Fl> {
     fn interest(&self) -> &Cell<Interest> {
         &self.interest
     }
+
+    fn name(&self) -> Option<&'static str> {
+        self.name
+    }
 }
 
 impl Interest {
@@ -70,6 +383,61 @@ impl Interest {
         (self, fd)
     }
 
+    /// Same as [`with_fd`](Self::with_fd), but sets `O_NONBLOCK` on `fd` first, so
+    /// that its handler does not block the loop, e.g. for a
+    /// [`TcpListener`](std::net::TcpListener) and the streams it accepts. Not only
+    /// sockets: any fd, such as a pipe.
+    ///
+    /// ```rust
+    /// # use std::io;
+    /// use std::net::TcpListener;
+    ///
+    /// use eventp::tri_subscriber::WithHandler;
+    /// use eventp::{Eventp, Subscriber};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut eventp = Eventp::default();
+    /// eventp::interest()
+    ///     .read()
+    ///     .with_fd_nonblocking(TcpListener::bind("127.0.0.1:0")?)?
+    ///     .with_handler(|listener: &mut TcpListener| {
+    ///         while let Ok((_stream, _)) = listener.accept() {}
+    ///     })
+    ///     .register_into(&mut eventp)?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The [`io::Error`] of `fcntl(F_GETFL)` or `fcntl(F_SETFL)`, in which case `fd`
+    /// is dropped.
+    pub fn with_fd_nonblocking<Fd: AsFd>(self, fd: Fd) -> io::Result<(Self, Fd)> {
+        crate::utils::set_nonblocking(fd.as_fd())?;
+        Ok((self, fd))
+    }
+
+    /// Combines this `Interest` with an owned file descriptor, e.g. one handed over
+    /// by C code, closed when the subscriber is dropped.
+    ///
+    /// The same as [`with_fd`](Self::with_fd), spelled out for fds without a more
+    /// specific type. Handlers can take it as `&mut OwnedFd`.
+    pub const fn with_owned_fd(self, fd: OwnedFd) -> (Self, OwnedFd) {
+        (self, fd)
+    }
+
+    /// Combines this `Interest` with a raw file descriptor owned elsewhere, which
+    /// is not closed when the subscriber is dropped.
+    ///
+    /// Handlers can take it as `&mut RawFdSource`, or as a plain [`RawFd`].
+    ///
+    /// # Safety
+    ///
+    /// See [`RawFdSource::new`].
+    pub const unsafe fn with_raw_fd(self, fd: RawFd) -> (Self, RawFdSource) {
+        // SAFETY: Forwarded to the caller.
+        (self, unsafe { RawFdSource::new(fd) })
+    }
+
     /// Combines this Interest with the event handler.
     ///
     /// This is a convenience method for chaining calls.
@@ -84,6 +452,39 @@ impl Interest {
     }
 }
 
+/// A file descriptor borrowed by number, for sources that only come as a [`RawFd`],
+/// created by [`Interest::with_raw_fd`].
+///
+/// Dropping it does not close the fd.
+#[derive(Debug)]
+pub struct RawFdSource(RawFd);
+
+impl RawFdSource {
+    /// Wraps `fd` as a source.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be open, and stay open until the `RawFdSource` is dropped, that is
+    /// until the subscriber holding it is deleted from its loop, or dropped
+    /// unregistered. The caller keeps the ownership, and closes the fd afterwards.
+    pub const unsafe fn new(fd: RawFd) -> Self {
+        Self(fd)
+    }
+}
+
+impl AsFd for RawFdSource {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: Open for as long as `self` is alive, per the contract of `new`.
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+impl AsRawFd for RawFdSource {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
 /// A trait for types that can be combined with a file descriptor.
 pub trait WithFd {
     /// The resulting output type after combining with a file descriptor.
@@ -101,6 +502,8 @@ impl<Args, F> WithFd for (Interest, FnHandler<Args, F>) {
             fd,
             interest: Cell::new(self.0),
             handler: self.1,
+            name: None,
+            filter: NoFilter,
         }
     }
 }
@@ -125,128 +528,922 @@ impl<Fd: AsFd> WithHandler for (Interest, Fd) {
                 f,
                 _marker: PhantomData,
             },
+            name: None,
+            filter: NoFilter,
         }
     }
 }
 
-impl<Ep, Fd, F> Handler<Ep> for TriSubscriber<Fd, (), F>
+impl<Fd: AsFd, St> WithHandler for (Interest, Fd, St) {
+    type Out<Args, F> = QuadSubscriber<Fd, St, Args, F>;
+
+    fn with_handler<Args, F>(self, f: F) -> Self::Out<Args, F> {
+        QuadSubscriber {
+            fd: self.1,
+            state: self.2,
+            interest: Cell::new(self.0),
+            handler: FnHandler {
+                f,
+                _marker: PhantomData,
+            },
+            name: None,
+            filter: NoFilter,
+        }
+    }
+}
+
+/// A trait for types that can be combined with the state of their handler.
+pub trait WithState {
+    /// The resulting output type after combining with a state.
+    type Out<St>;
+
+    /// Combines `self` with the state of the handler, see [State](self#state).
+    fn with_state<St>(self, state: St) -> Self::Out<St>;
+}
+
+impl<Fd: AsFd> WithState for (Interest, Fd) {
+    type Out<St> = (Interest, Fd, St);
+
+    fn with_state<St>(self, state: St) -> Self::Out<St> {
+        (self.0, self.1, state)
+    }
+}
+
+/// The return type of a handler closure: either `()` or `io::Result<()>`.
+///
+/// # Sealed
+///
+/// This trait is sealed and cannot be implemented for types outside of this crate.
+pub trait HandlerReturn: sealed::Sealed {
+    /// Converts the closure's return value into the result reported to the loop.
+    fn into_result(self) -> io::Result<()>;
+}
+
+impl HandlerReturn for () {
+    fn into_result(self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl HandlerReturn for io::Result<()> {
+    fn into_result(self) -> io::Result<()> {
+        self
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for () {}
+    impl Sealed for std::io::Result<()> {}
+
+    impl Sealed for super::NoFilter {}
+    impl<G> Sealed for super::Filter<G> {}
+
+    impl Sealed for crate::BatchInfo {}
+    impl Sealed for crate::Event {}
+    impl Sealed for crate::EventDelta {}
+    impl Sealed for crate::Interest {}
+    impl Sealed for crate::SubscriberHandle {}
+    impl Sealed for std::os::fd::RawFd {}
+    impl Sealed for super::SinceLast {}
+    impl Sealed for super::UserData {}
+}
+
+/// A handler parameter passed by value: [`Event`], [`EventDelta`], [`Interest`],
+/// [`UserData`], [`SubscriberHandle`], [`RawFd`], [`BatchInfo`] or [`SinceLast`].
+///
+/// # Sealed
+///
+/// This trait is sealed and cannot be implemented for types outside of this crate.
+pub trait Inject: sealed::Sealed + Sized {
+    /// Picks the parameter out of what the loop knows about the dispatched event.
+    fn inject(
+        event: Event,
+        delta: EventDelta,
+        interest: Interest,
+        handle: SubscriberHandle,
+        batch: BatchInfo,
+        since_last: Option<Duration>,
+    ) -> Self;
+}
+
+impl Inject for Event {
+    fn inject(
+        event: Event,
+        _delta: EventDelta,
+        _interest: Interest,
+        _handle: SubscriberHandle,
+        _batch: BatchInfo,
+        _since_last: Option<Duration>,
+    ) -> Self {
+        event
+    }
+}
+
+/// Without [`track_deltas`](Interest::track_deltas), or with a loop that does not
+/// keep deltas, as [`MockEventp`](crate::MockEventp), the delta is taken from an
+/// empty event: everything the event reports is new.
+impl Inject for EventDelta {
+    fn inject(
+        _event: Event,
+        delta: EventDelta,
+        _interest: Interest,
+        _handle: SubscriberHandle,
+        _batch: BatchInfo,
+        _since_last: Option<Duration>,
+    ) -> Self {
+        delta
+    }
+}
+
+impl Inject for Interest {
+    fn inject(
+        _event: Event,
+        _delta: EventDelta,
+        interest: Interest,
+        _handle: SubscriberHandle,
+        _batch: BatchInfo,
+        _since_last: Option<Duration>,
+    ) -> Self {
+        interest
+    }
+}
+
+impl Inject for SubscriberHandle {
+    fn inject(
+        _event: Event,
+        _delta: EventDelta,
+        _interest: Interest,
+        handle: SubscriberHandle,
+        _batch: BatchInfo,
+        _since_last: Option<Duration>,
+    ) -> Self {
+        handle
+    }
+}
+
+/// Outside of [`Eventp`](crate::Eventp), as with [`MockEventp`](crate::MockEventp),
+/// the event is taken as the only one of the first batch.
+impl Inject for BatchInfo {
+    fn inject(
+        _event: Event,
+        _delta: EventDelta,
+        _interest: Interest,
+        _handle: SubscriberHandle,
+        batch: BatchInfo,
+        _since_last: Option<Duration>,
+    ) -> Self {
+        batch
+    }
+}
+
+impl Inject for RawFd {
+    fn inject(
+        _event: Event,
+        _delta: EventDelta,
+        _interest: Interest,
+        handle: SubscriberHandle,
+        _batch: BatchInfo,
+        _since_last: Option<Duration>,
+    ) -> Self {
+        handle.raw_fd()
+    }
+}
+
+/// The [user data](Interest::user_data) of the registration, as a handler
+/// parameter, 0 if it has none:
+///
+/// ```rust
+/// # use eventp::tri_subscriber::{UserData, WithHandler};
+/// # use eventp::Subscriber;
+/// # use nix::sys::eventfd::EventFd;
+/// # let mut eventp = eventp::Eventp::default();
+/// # let tenants = [(); 4];
+/// # let efd = EventFd::new().unwrap();
+/// eventp::interest()
+///     .read()
+///     .user_data(3)
+///     .with_fd(efd)
+///     .with_handler(move |UserData(tenant): UserData| {
+///         let _tenant = &tenants[tenant as usize];
+///     })
+///     .register_into(&mut eventp)
+///     .unwrap();
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct UserData(pub u64);
+
+impl Inject for UserData {
+    fn inject(
+        _event: Event,
+        _delta: EventDelta,
+        interest: Interest,
+        _handle: SubscriberHandle,
+        _batch: BatchInfo,
+        _since_last: Option<Duration>,
+    ) -> Self {
+        Self(interest.user_data_value())
+    }
+}
+
+/// The time since the previous event dispatched to the subscriber, as a handler
+/// parameter, for interests with [`track_last_event`](Interest::track_last_event).
+/// `None` for the first event, and always without the setting, or with a loop that
+/// does not keep the time, as [`MockEventp`](crate::MockEventp):
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use eventp::tri_subscriber::{SinceLast, WithHandler};
+/// # use eventp::Subscriber;
+/// # use nix::sys::eventfd::EventFd;
+/// # let mut eventp = eventp::Eventp::default();
+/// # let efd = EventFd::new().unwrap();
+/// const KEEPALIVE: Duration = Duration::from_secs(30);
+/// eventp::interest()
+///     .read()
+///     .track_last_event()
+///     .with_fd(efd)
+///     .with_handler(move |SinceLast(since): SinceLast| {
+///         if since.is_some_and(|since| since > KEEPALIVE) {
+///             // The peer was silent for longer than allowed.
+///         }
+///     })
+///     .register_into(&mut eventp)
+///     .unwrap();
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SinceLast(pub Option<Duration>);
+
+impl Inject for SinceLast {
+    fn inject(
+        _event: Event,
+        _delta: EventDelta,
+        _interest: Interest,
+        _handle: SubscriberHandle,
+        _batch: BatchInfo,
+        since_last: Option<Duration>,
+    ) -> Self {
+        Self(since_last)
+    }
+}
+
+/// Stands for an [`Inject`] parameter `T` in the `Args` of [`FnHandler`].
+///
+/// Unlike `&mut Fd` and `Pinned`, these parameters are generic in the `Handler`
+/// impls, and the marker keeps those impls from overlapping.
+pub struct Injected<T>(PhantomData<T>);
+
+/// Stands for the `&mut St` parameter of a [`QuadSubscriber`] in the `Args` of
+/// [`FnHandler`], which `&mut Fd` could be confused with.
+pub struct State<T>(PhantomData<T>);
+
+/// The filter of a subscriber without one, letting every event through.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoFilter;
+
+/// The filter set by [`TriSubscriber::filter`], with the fallback set by
+/// [`on_filtered`](TriSubscriber::on_filtered), if any.
+#[derive(Clone, Copy, Debug)]
+pub struct Filter<G = NoFallback> {
+    /// Whether the handler is called with the event.
+    pub keep: fn(Event) -> bool,
+    /// What the events not kept are handed to.
+    pub on_filtered: G,
+}
+
+/// The fallback of a [`Filter`] without one, dropping the events not kept.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoFallback;
+
+/// Decides whether an event reaches the handler of a [`TriSubscriber`] or a
+/// [`QuadSubscriber`]: [`NoFilter`] or a [`Filter`].
+///
+/// # Sealed
+///
+/// This trait is sealed and cannot be implemented for types outside of this crate.
+pub trait EventFilter<Ep>: sealed::Sealed {
+    /// Returns `None` to call the handler with `event`, otherwise the result of the
+    /// fallback, if any, which the handler's stands for.
+    fn apply<Fd: AsFd>(
+        &mut self,
+        event: Event,
+        fd: &Fd,
+        eventp: Pinned<'_, Ep>,
+    ) -> Option<io::Result<()>>;
+}
+
+impl<Ep> EventFilter<Ep> for NoFilter {
+    #[inline(always)]
+    fn apply<Fd: AsFd>(&mut self, _: Event, _: &Fd, _: Pinned<'_, Ep>) -> Option<io::Result<()>> {
+        None
+    }
+}
+
+impl<Ep> EventFilter<Ep> for Filter {
+    fn apply<Fd: AsFd>(
+        &mut self,
+        event: Event,
+        _: &Fd,
+        _: Pinned<'_, Ep>,
+    ) -> Option<io::Result<()>> {
+        (!(self.keep)(event)).then_some(Ok(()))
+    }
+}
+
+impl<Ep, G, R> EventFilter<Ep> for Filter<G>
+where
+    G: FnMut(Event, RawFd, Pinned<'_, Ep>) -> R,
+    R: HandlerReturn,
+{
+    fn apply<Fd: AsFd>(
+        &mut self,
+        event: Event,
+        fd: &Fd,
+        eventp: Pinned<'_, Ep>,
+    ) -> Option<io::Result<()>> {
+        if (self.keep)(event) {
+            return None;
+        }
+        let raw_fd = fd.as_fd().as_raw_fd();
+        Some((self.on_filtered)(event, raw_fd, eventp).into_result())
+    }
+}
+
+impl<Ep, Fd, F, Fl, R> Handler<Ep> for TriSubscriber<Fd, (), F, Fl>
 where
     Ep: EventpOps,
     Fd: AsFd,
-    F: FnMut(),
+    F: FnMut() -> R,
+    Fl: EventFilter<Ep>,
+    R: HandlerReturn,
 {
-    fn handle(&mut self, _event: Event, _eventp: Pinned<'_, Ep>) {
-        (self.handler.f)()
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    fn try_handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        if let Some(result) = self.filter.apply(event, &self.fd, eventp) {
+            return result;
+        }
+        (self.handler.f)().into_result()
     }
 }
 
 macro_rules! expand_param_type {
     (fd) => { &mut Fd };
-    (event) => { crate::Event };
-    (interest) => { crate::Interest };
+    (state) => { &mut St };
     (eventp) => { Pinned<'_, Ep> };
+    (view) => { ViewOps<'_, Ep> };
+    ($value:ident) => { $value };
+}
+
+macro_rules! expand_arg_type {
+    (fd) => { &mut Fd };
+    (state) => { State<St> };
+    (eventp) => { Pinned<'_, Ep> };
+    (view) => { ViewOps<'_, Ep> };
+    ($value:ident) => { Injected<$value> };
 }
 
 macro_rules! impl_handler {
-    (@build_call ($s:ident, $e:ident, $i:ident, $ep:ident) -> @args( $($processed:expr,)* ) fd, $($tail:ident,)*) => {
-        impl_handler!(@build_call ($s, $e, $i, $ep) -> @args( $($processed,)* &mut $s.fd, ) $($tail,)*)
+    (@build_call ($s:ident, $e:ident, $d:ident, $i:ident, $h:ident, $b:ident, $t:ident, $ep:ident) -> @args( $($processed:expr,)* ) fd, $($tail:ident,)*) => {
+        impl_handler!(@build_call ($s, $e, $d, $i, $h, $b, $t, $ep) -> @args( $($processed,)* &mut $s.fd, ) $($tail,)*)
+    };
+    (@build_call ($s:ident, $e:ident, $d:ident, $i:ident, $h:ident, $b:ident, $t:ident, $ep:ident) -> @args( $($processed:expr,)* ) state, $($tail:ident,)*) => {
+        impl_handler!(@build_call ($s, $e, $d, $i, $h, $b, $t, $ep) -> @args( $($processed,)* &mut $s.state, ) $($tail,)*)
+    };
+    (@build_call ($s:ident, $e:ident, $d:ident, $i:ident, $h:ident, $b:ident, $t:ident, $ep:ident) -> @args( $($processed:expr,)* ) eventp, $($tail:ident,)*) => {
+        impl_handler!(@build_call ($s, $e, $d, $i, $h, $b, $t, $ep) -> @args( $($processed,)* $ep, ) $($tail,)*)
+    };
+    (@build_call ($s:ident, $e:ident, $d:ident, $i:ident, $h:ident, $b:ident, $t:ident, $ep:ident) -> @args( $($processed:expr,)* ) view, $($tail:ident,)*) => {
+        impl_handler!(@build_call ($s, $e, $d, $i, $h, $b, $t, $ep) -> @args( $($processed,)* ViewOps::from($ep), ) $($tail,)*)
     };
-    (@build_call ($s:ident, $e:ident, $i:ident, $ep:ident) -> @args( $($processed:expr,)* ) event, $($tail:ident,)*) => {
-        impl_handler!(@build_call ($s, $e, $i, $ep) -> @args( $($processed,)* $e, ) $($tail,)*)
+    (@build_call ($s:ident, $e:ident, $d:ident, $i:ident, $h:ident, $b:ident, $t:ident, $ep:ident) -> @args( $($processed:expr,)* ) $value:ident, $($tail:ident,)*) => {
+        impl_handler!(@build_call ($s, $e, $d, $i, $h, $b, $t, $ep) -> @args( $($processed,)* $value::inject($e, $d, $i, $h, $b, $t), ) $($tail,)*)
     };
-    (@build_call ($s:ident, $e:ident, $i:ident, $ep:ident) -> @args( $($processed:expr,)* ) interest, $($tail:ident,)*) => {
-        impl_handler!(@build_call ($s, $e, $i, $ep) -> @args( $($processed,)* $i.interest.get(), ) $($tail,)*)
+    (@build_call ($s:ident, $e:ident, $d:ident, $i:ident, $h:ident, $b:ident, $t:ident, $ep:ident) -> @args( $($processed:expr,)* )) => {
+        ($s.handler.f)($($processed),*).into_result()
     };
-    (@build_call ($s:ident, $e:ident, $i:ident, $ep:ident) -> @args( $($processed:expr,)* ) eventp, $($tail:ident,)*) => {
-        impl_handler!(@build_call ($s, $e, $i, $ep) -> @args( $($processed,)* $ep, ) $($tail,)*)
+
+    (@methods $( $param:ident ),+) => {
+        fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+            // The error, if any, can only be observed through `try_handle`.
+            let _ = self.try_handle(event, eventp);
+        }
+
+        #[allow(unused_variables, unused_mut)]
+        fn try_handle(&mut self, event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+            if let Some(result) = self.filter.apply(event, &self.fd, eventp.as_mut()) {
+                return result;
+            }
+            // Read before `eventp` may be moved into the call. Optimized out when the
+            // handler takes none of `EventDelta`, `Interest`, `UserData`,
+            // `SubscriberHandle`, `RawFd`, `BatchInfo` and `SinceLast`.
+            let delta = eventp
+                .current_delta()
+                .unwrap_or(EventDelta::between(Event::new(EpollFlags::empty()), event));
+            let interest = eventp.current_interest().unwrap_or(self.interest.get());
+            let handle = SubscriberHandle::new(self.fd.as_fd().as_raw_fd());
+            let batch = eventp.batch_info().unwrap_or(BatchInfo::new(0, 1, 0));
+            let since_last = eventp.since_last_event();
+            impl_handler!(@build_call (self, event, delta, interest, handle, batch, since_last, eventp) -> @args() $($param,)*)
+        }
     };
-    (@build_call ($s:ident, $e:ident, $i:ident, $ep:ident) -> @args( $($processed:expr,)* )) => {
-        ($s.handler.f)($($processed),*)
+
+    (@quad [ $( $value:ident ),* ] $( $param:ident ),+ ) => {
+        impl<Ep, Fd, St, F, Fl, R, $( $value, )*> Handler<Ep> for QuadSubscriber<Fd, St, ( $( expand_arg_type!($param), )* ), F, Fl>
+        where
+            Ep: EventpOps,
+            Fd: AsFd,
+            F: FnMut( $( expand_param_type!($param), )* ) -> R,
+            Fl: EventFilter<Ep>,
+            R: HandlerReturn,
+            $( $value: Inject, )*
+        {
+            impl_handler!(@methods $($param),+);
+        }
     };
 
-    ( $( $param:ident ),+ ) => {
-        impl<Ep, Fd, F> Handler<Ep> for TriSubscriber<Fd, ( $( expand_param_type!($param), )* ), F>
+    ( [ $( $value:ident ),* ] $( $param:ident ),+ ) => {
+        impl<Ep, Fd, F, Fl, R, $( $value, )*> Handler<Ep> for TriSubscriber<Fd, ( $( expand_arg_type!($param), )* ), F, Fl>
         where
             Ep: EventpOps,
             Fd: AsFd,
-            F: FnMut( $( expand_param_type!($param), )* ),
+            F: FnMut( $( expand_param_type!($param), )* ) -> R,
+            Fl: EventFilter<Ep>,
+            R: HandlerReturn,
+            $( $value: Inject, )*
         {
-            #[allow(unused_variables)]
-            fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
-                impl_handler!(@build_call (self, event, self, eventp) -> @args() $($param,)*);
-            }
+            impl_handler!(@methods $($param),+);
         }
     };
 }
 
-// 1 parameter (4 variants)
-impl_handler!(fd);
-impl_handler!(event);
-impl_handler!(interest);
-impl_handler!(eventp);
-
-// 2 parameters (4P2 = 12 variants)
-impl_handler!(fd, event);
-impl_handler!(fd, interest);
-impl_handler!(fd, eventp);
-impl_handler!(event, fd);
-impl_handler!(event, interest);
-impl_handler!(event, eventp);
-impl_handler!(interest, fd);
-impl_handler!(interest, event);
-impl_handler!(interest, eventp);
-impl_handler!(eventp, fd);
-impl_handler!(eventp, event);
-impl_handler!(eventp, interest);
-
-// 3 parameters (4P3 = 24 variants)
-impl_handler!(fd, event, interest);
-impl_handler!(fd, event, eventp);
-impl_handler!(fd, interest, event);
-impl_handler!(fd, interest, eventp);
-impl_handler!(fd, eventp, event);
-impl_handler!(fd, eventp, interest);
-impl_handler!(event, fd, interest);
-impl_handler!(event, fd, eventp);
-impl_handler!(event, interest, fd);
-impl_handler!(event, interest, eventp);
-impl_handler!(event, eventp, fd);
-impl_handler!(event, eventp, interest);
-impl_handler!(interest, fd, event);
-impl_handler!(interest, fd, eventp);
-impl_handler!(interest, event, fd);
-impl_handler!(interest, event, eventp);
-impl_handler!(interest, eventp, fd);
-impl_handler!(interest, eventp, event);
-impl_handler!(eventp, fd, event);
-impl_handler!(eventp, fd, interest);
-impl_handler!(eventp, event, fd);
-impl_handler!(eventp, event, interest);
-impl_handler!(eventp, interest, fd);
-impl_handler!(eventp, interest, event);
-
-// 4 parameters (4P4 = 24 variants)
-impl_handler!(fd, event, interest, eventp);
-impl_handler!(fd, event, eventp, interest);
-impl_handler!(fd, interest, event, eventp);
-impl_handler!(fd, interest, eventp, event);
-impl_handler!(fd, eventp, event, interest);
-impl_handler!(fd, eventp, interest, event);
-impl_handler!(event, fd, interest, eventp);
-impl_handler!(event, fd, eventp, interest);
-impl_handler!(event, interest, fd, eventp);
-impl_handler!(event, interest, eventp, fd);
-impl_handler!(event, eventp, fd, interest);
-impl_handler!(event, eventp, interest, fd);
-impl_handler!(interest, fd, event, eventp);
-impl_handler!(interest, fd, eventp, event);
-impl_handler!(interest, event, fd, eventp);
-impl_handler!(interest, event, eventp, fd);
-impl_handler!(interest, eventp, fd, event);
-impl_handler!(interest, eventp, event, fd);
-impl_handler!(eventp, fd, event, interest);
-impl_handler!(eventp, fd, interest, event);
-impl_handler!(eventp, event, fd, interest);
-impl_handler!(eventp, event, interest, fd);
-impl_handler!(eventp, interest, fd, event);
-impl_handler!(eventp, interest, event, fd);
+// 1 parameter (3 variants)
+impl_handler!([] fd);
+impl_handler!([V1] V1);
+impl_handler!([] eventp);
+
+// 2 parameters (7 variants)
+impl_handler!([V1] fd, V1);
+impl_handler!([] fd, eventp);
+impl_handler!([V1] V1, fd);
+impl_handler!([V1, V2] V1, V2);
+impl_handler!([V1] V1, eventp);
+impl_handler!([] eventp, fd);
+impl_handler!([V1] eventp, V1);
+
+// 3 parameters (13 variants)
+impl_handler!([V1, V2] fd, V1, V2);
+impl_handler!([V1] fd, V1, eventp);
+impl_handler!([V1] fd, eventp, V1);
+impl_handler!([V1, V2] V1, fd, V2);
+impl_handler!([V1] V1, fd, eventp);
+impl_handler!([V1, V2] V1, V2, fd);
+impl_handler!([V1, V2, V3] V1, V2, V3);
+impl_handler!([V1, V2] V1, V2, eventp);
+impl_handler!([V1] V1, eventp, fd);
+impl_handler!([V1, V2] V1, eventp, V2);
+impl_handler!([V1] eventp, fd, V1);
+impl_handler!([V1] eventp, V1, fd);
+impl_handler!([V1, V2] eventp, V1, V2);
+
+// 4 parameters (20 variants)
+impl_handler!([V1, V2, V3] fd, V1, V2, V3);
+impl_handler!([V1, V2] fd, V1, V2, eventp);
+impl_handler!([V1, V2] fd, V1, eventp, V2);
+impl_handler!([V1, V2] fd, eventp, V1, V2);
+impl_handler!([V1, V2, V3] V1, fd, V2, V3);
+impl_handler!([V1, V2] V1, fd, V2, eventp);
+impl_handler!([V1, V2] V1, fd, eventp, V2);
+impl_handler!([V1, V2, V3] V1, V2, fd, V3);
+impl_handler!([V1, V2] V1, V2, fd, eventp);
+impl_handler!([V1, V2, V3] V1, V2, V3, fd);
+impl_handler!([V1, V2, V3] V1, V2, V3, eventp);
+impl_handler!([V1, V2] V1, V2, eventp, fd);
+impl_handler!([V1, V2, V3] V1, V2, eventp, V3);
+impl_handler!([V1, V2] V1, eventp, fd, V2);
+impl_handler!([V1, V2] V1, eventp, V2, fd);
+impl_handler!([V1, V2, V3] V1, eventp, V2, V3);
+impl_handler!([V1, V2] eventp, fd, V1, V2);
+impl_handler!([V1, V2] eventp, V1, fd, V2);
+impl_handler!([V1, V2] eventp, V1, V2, fd);
+impl_handler!([V1, V2, V3] eventp, V1, V2, V3);
+
+// 5 parameters (20 variants)
+impl_handler!([V1, V2, V3] fd, V1, V2, V3, eventp);
+impl_handler!([V1, V2, V3] fd, V1, V2, eventp, V3);
+impl_handler!([V1, V2, V3] fd, V1, eventp, V2, V3);
+impl_handler!([V1, V2, V3] fd, eventp, V1, V2, V3);
+impl_handler!([V1, V2, V3] V1, fd, V2, V3, eventp);
+impl_handler!([V1, V2, V3] V1, fd, V2, eventp, V3);
+impl_handler!([V1, V2, V3] V1, fd, eventp, V2, V3);
+impl_handler!([V1, V2, V3] V1, V2, fd, V3, eventp);
+impl_handler!([V1, V2, V3] V1, V2, fd, eventp, V3);
+impl_handler!([V1, V2, V3] V1, V2, V3, fd, eventp);
+impl_handler!([V1, V2, V3] V1, V2, V3, eventp, fd);
+impl_handler!([V1, V2, V3] V1, V2, eventp, fd, V3);
+impl_handler!([V1, V2, V3] V1, V2, eventp, V3, fd);
+impl_handler!([V1, V2, V3] V1, eventp, fd, V2, V3);
+impl_handler!([V1, V2, V3] V1, eventp, V2, fd, V3);
+impl_handler!([V1, V2, V3] V1, eventp, V2, V3, fd);
+impl_handler!([V1, V2, V3] eventp, fd, V1, V2, V3);
+impl_handler!([V1, V2, V3] eventp, V1, fd, V2, V3);
+impl_handler!([V1, V2, V3] eventp, V1, V2, fd, V3);
+impl_handler!([V1, V2, V3] eventp, V1, V2, V3, fd);
+
+// With a state, which every handler of a `QuadSubscriber` takes.
+
+// 1 parameter (1 variant)
+impl_handler!(@quad [] state);
+
+// 2 parameters (6 variants)
+impl_handler!(@quad [] fd, state);
+impl_handler!(@quad [] state, fd);
+impl_handler!(@quad [V1] state, V1);
+impl_handler!(@quad [] state, eventp);
+impl_handler!(@quad [V1] V1, state);
+impl_handler!(@quad [] eventp, state);
+
+// 3 parameters (21 variants)
+impl_handler!(@quad [V1] fd, state, V1);
+impl_handler!(@quad [] fd, state, eventp);
+impl_handler!(@quad [V1] fd, V1, state);
+impl_handler!(@quad [] fd, eventp, state);
+impl_handler!(@quad [V1] state, fd, V1);
+impl_handler!(@quad [] state, fd, eventp);
+impl_handler!(@quad [V1] state, V1, fd);
+impl_handler!(@quad [V1, V2] state, V1, V2);
+impl_handler!(@quad [V1] state, V1, eventp);
+impl_handler!(@quad [] state, eventp, fd);
+impl_handler!(@quad [V1] state, eventp, V1);
+impl_handler!(@quad [V1] V1, fd, state);
+impl_handler!(@quad [V1] V1, state, fd);
+impl_handler!(@quad [V1, V2] V1, state, V2);
+impl_handler!(@quad [V1] V1, state, eventp);
+impl_handler!(@quad [V1, V2] V1, V2, state);
+impl_handler!(@quad [V1] V1, eventp, state);
+impl_handler!(@quad [] eventp, fd, state);
+impl_handler!(@quad [] eventp, state, fd);
+impl_handler!(@quad [V1] eventp, state, V1);
+impl_handler!(@quad [V1] eventp, V1, state);
+
+// 4 parameters (52 variants)
+impl_handler!(@quad [V1, V2] fd, state, V1, V2);
+impl_handler!(@quad [V1] fd, state, V1, eventp);
+impl_handler!(@quad [V1] fd, state, eventp, V1);
+impl_handler!(@quad [V1, V2] fd, V1, state, V2);
+impl_handler!(@quad [V1] fd, V1, state, eventp);
+impl_handler!(@quad [V1, V2] fd, V1, V2, state);
+impl_handler!(@quad [V1] fd, V1, eventp, state);
+impl_handler!(@quad [V1] fd, eventp, state, V1);
+impl_handler!(@quad [V1] fd, eventp, V1, state);
+impl_handler!(@quad [V1, V2] state, fd, V1, V2);
+impl_handler!(@quad [V1] state, fd, V1, eventp);
+impl_handler!(@quad [V1] state, fd, eventp, V1);
+impl_handler!(@quad [V1, V2] state, V1, fd, V2);
+impl_handler!(@quad [V1] state, V1, fd, eventp);
+impl_handler!(@quad [V1, V2] state, V1, V2, fd);
+impl_handler!(@quad [V1, V2, V3] state, V1, V2, V3);
+impl_handler!(@quad [V1, V2] state, V1, V2, eventp);
+impl_handler!(@quad [V1] state, V1, eventp, fd);
+impl_handler!(@quad [V1, V2] state, V1, eventp, V2);
+impl_handler!(@quad [V1] state, eventp, fd, V1);
+impl_handler!(@quad [V1] state, eventp, V1, fd);
+impl_handler!(@quad [V1, V2] state, eventp, V1, V2);
+impl_handler!(@quad [V1, V2] V1, fd, state, V2);
+impl_handler!(@quad [V1] V1, fd, state, eventp);
+impl_handler!(@quad [V1, V2] V1, fd, V2, state);
+impl_handler!(@quad [V1] V1, fd, eventp, state);
+impl_handler!(@quad [V1, V2] V1, state, fd, V2);
+impl_handler!(@quad [V1] V1, state, fd, eventp);
+impl_handler!(@quad [V1, V2] V1, state, V2, fd);
+impl_handler!(@quad [V1, V2, V3] V1, state, V2, V3);
+impl_handler!(@quad [V1, V2] V1, state, V2, eventp);
+impl_handler!(@quad [V1] V1, state, eventp, fd);
+impl_handler!(@quad [V1, V2] V1, state, eventp, V2);
+impl_handler!(@quad [V1, V2] V1, V2, fd, state);
+impl_handler!(@quad [V1, V2] V1, V2, state, fd);
+impl_handler!(@quad [V1, V2, V3] V1, V2, state, V3);
+impl_handler!(@quad [V1, V2] V1, V2, state, eventp);
+impl_handler!(@quad [V1, V2, V3] V1, V2, V3, state);
+impl_handler!(@quad [V1, V2] V1, V2, eventp, state);
+impl_handler!(@quad [V1] V1, eventp, fd, state);
+impl_handler!(@quad [V1] V1, eventp, state, fd);
+impl_handler!(@quad [V1, V2] V1, eventp, state, V2);
+impl_handler!(@quad [V1, V2] V1, eventp, V2, state);
+impl_handler!(@quad [V1] eventp, fd, state, V1);
+impl_handler!(@quad [V1] eventp, fd, V1, state);
+impl_handler!(@quad [V1] eventp, state, fd, V1);
+impl_handler!(@quad [V1] eventp, state, V1, fd);
+impl_handler!(@quad [V1, V2] eventp, state, V1, V2);
+impl_handler!(@quad [V1] eventp, V1, fd, state);
+impl_handler!(@quad [V1] eventp, V1, state, fd);
+impl_handler!(@quad [V1, V2] eventp, V1, state, V2);
+impl_handler!(@quad [V1, V2] eventp, V1, V2, state);
+
+// 5 parameters (100 variants)
+impl_handler!(@quad [V1, V2, V3] fd, state, V1, V2, V3);
+impl_handler!(@quad [V1, V2] fd, state, V1, V2, eventp);
+impl_handler!(@quad [V1, V2] fd, state, V1, eventp, V2);
+impl_handler!(@quad [V1, V2] fd, state, eventp, V1, V2);
+impl_handler!(@quad [V1, V2, V3] fd, V1, state, V2, V3);
+impl_handler!(@quad [V1, V2] fd, V1, state, V2, eventp);
+impl_handler!(@quad [V1, V2] fd, V1, state, eventp, V2);
+impl_handler!(@quad [V1, V2, V3] fd, V1, V2, state, V3);
+impl_handler!(@quad [V1, V2] fd, V1, V2, state, eventp);
+impl_handler!(@quad [V1, V2, V3] fd, V1, V2, V3, state);
+impl_handler!(@quad [V1, V2] fd, V1, V2, eventp, state);
+impl_handler!(@quad [V1, V2] fd, V1, eventp, state, V2);
+impl_handler!(@quad [V1, V2] fd, V1, eventp, V2, state);
+impl_handler!(@quad [V1, V2] fd, eventp, state, V1, V2);
+impl_handler!(@quad [V1, V2] fd, eventp, V1, state, V2);
+impl_handler!(@quad [V1, V2] fd, eventp, V1, V2, state);
+impl_handler!(@quad [V1, V2, V3] state, fd, V1, V2, V3);
+impl_handler!(@quad [V1, V2] state, fd, V1, V2, eventp);
+impl_handler!(@quad [V1, V2] state, fd, V1, eventp, V2);
+impl_handler!(@quad [V1, V2] state, fd, eventp, V1, V2);
+impl_handler!(@quad [V1, V2, V3] state, V1, fd, V2, V3);
+impl_handler!(@quad [V1, V2] state, V1, fd, V2, eventp);
+impl_handler!(@quad [V1, V2] state, V1, fd, eventp, V2);
+impl_handler!(@quad [V1, V2, V3] state, V1, V2, fd, V3);
+impl_handler!(@quad [V1, V2] state, V1, V2, fd, eventp);
+impl_handler!(@quad [V1, V2, V3] state, V1, V2, V3, fd);
+impl_handler!(@quad [V1, V2, V3] state, V1, V2, V3, eventp);
+impl_handler!(@quad [V1, V2] state, V1, V2, eventp, fd);
+impl_handler!(@quad [V1, V2, V3] state, V1, V2, eventp, V3);
+impl_handler!(@quad [V1, V2] state, V1, eventp, fd, V2);
+impl_handler!(@quad [V1, V2] state, V1, eventp, V2, fd);
+impl_handler!(@quad [V1, V2, V3] state, V1, eventp, V2, V3);
+impl_handler!(@quad [V1, V2] state, eventp, fd, V1, V2);
+impl_handler!(@quad [V1, V2] state, eventp, V1, fd, V2);
+impl_handler!(@quad [V1, V2] state, eventp, V1, V2, fd);
+impl_handler!(@quad [V1, V2, V3] state, eventp, V1, V2, V3);
+impl_handler!(@quad [V1, V2, V3] V1, fd, state, V2, V3);
+impl_handler!(@quad [V1, V2] V1, fd, state, V2, eventp);
+impl_handler!(@quad [V1, V2] V1, fd, state, eventp, V2);
+impl_handler!(@quad [V1, V2, V3] V1, fd, V2, state, V3);
+impl_handler!(@quad [V1, V2] V1, fd, V2, state, eventp);
+impl_handler!(@quad [V1, V2, V3] V1, fd, V2, V3, state);
+impl_handler!(@quad [V1, V2] V1, fd, V2, eventp, state);
+impl_handler!(@quad [V1, V2] V1, fd, eventp, state, V2);
+impl_handler!(@quad [V1, V2] V1, fd, eventp, V2, state);
+impl_handler!(@quad [V1, V2, V3] V1, state, fd, V2, V3);
+impl_handler!(@quad [V1, V2] V1, state, fd, V2, eventp);
+impl_handler!(@quad [V1, V2] V1, state, fd, eventp, V2);
+impl_handler!(@quad [V1, V2, V3] V1, state, V2, fd, V3);
+impl_handler!(@quad [V1, V2] V1, state, V2, fd, eventp);
+impl_handler!(@quad [V1, V2, V3] V1, state, V2, V3, fd);
+impl_handler!(@quad [V1, V2, V3] V1, state, V2, V3, eventp);
+impl_handler!(@quad [V1, V2] V1, state, V2, eventp, fd);
+impl_handler!(@quad [V1, V2, V3] V1, state, V2, eventp, V3);
+impl_handler!(@quad [V1, V2] V1, state, eventp, fd, V2);
+impl_handler!(@quad [V1, V2] V1, state, eventp, V2, fd);
+impl_handler!(@quad [V1, V2, V3] V1, state, eventp, V2, V3);
+impl_handler!(@quad [V1, V2, V3] V1, V2, fd, state, V3);
+impl_handler!(@quad [V1, V2] V1, V2, fd, state, eventp);
+impl_handler!(@quad [V1, V2, V3] V1, V2, fd, V3, state);
+impl_handler!(@quad [V1, V2] V1, V2, fd, eventp, state);
+impl_handler!(@quad [V1, V2, V3] V1, V2, state, fd, V3);
+impl_handler!(@quad [V1, V2] V1, V2, state, fd, eventp);
+impl_handler!(@quad [V1, V2, V3] V1, V2, state, V3, fd);
+impl_handler!(@quad [V1, V2, V3] V1, V2, state, V3, eventp);
+impl_handler!(@quad [V1, V2] V1, V2, state, eventp, fd);
+impl_handler!(@quad [V1, V2, V3] V1, V2, state, eventp, V3);
+impl_handler!(@quad [V1, V2, V3] V1, V2, V3, fd, state);
+impl_handler!(@quad [V1, V2, V3] V1, V2, V3, state, fd);
+impl_handler!(@quad [V1, V2, V3] V1, V2, V3, state, eventp);
+impl_handler!(@quad [V1, V2, V3] V1, V2, V3, eventp, state);
+impl_handler!(@quad [V1, V2] V1, V2, eventp, fd, state);
+impl_handler!(@quad [V1, V2] V1, V2, eventp, state, fd);
+impl_handler!(@quad [V1, V2, V3] V1, V2, eventp, state, V3);
+impl_handler!(@quad [V1, V2, V3] V1, V2, eventp, V3, state);
+impl_handler!(@quad [V1, V2] V1, eventp, fd, state, V2);
+impl_handler!(@quad [V1, V2] V1, eventp, fd, V2, state);
+impl_handler!(@quad [V1, V2] V1, eventp, state, fd, V2);
+impl_handler!(@quad [V1, V2] V1, eventp, state, V2, fd);
+impl_handler!(@quad [V1, V2, V3] V1, eventp, state, V2, V3);
+impl_handler!(@quad [V1, V2] V1, eventp, V2, fd, state);
+impl_handler!(@quad [V1, V2] V1, eventp, V2, state, fd);
+impl_handler!(@quad [V1, V2, V3] V1, eventp, V2, state, V3);
+impl_handler!(@quad [V1, V2, V3] V1, eventp, V2, V3, state);
+impl_handler!(@quad [V1, V2] eventp, fd, state, V1, V2);
+impl_handler!(@quad [V1, V2] eventp, fd, V1, state, V2);
+impl_handler!(@quad [V1, V2] eventp, fd, V1, V2, state);
+impl_handler!(@quad [V1, V2] eventp, state, fd, V1, V2);
+impl_handler!(@quad [V1, V2] eventp, state, V1, fd, V2);
+impl_handler!(@quad [V1, V2] eventp, state, V1, V2, fd);
+impl_handler!(@quad [V1, V2, V3] eventp, state, V1, V2, V3);
+impl_handler!(@quad [V1, V2] eventp, V1, fd, state, V2);
+impl_handler!(@quad [V1, V2] eventp, V1, fd, V2, state);
+impl_handler!(@quad [V1, V2] eventp, V1, state, fd, V2);
+impl_handler!(@quad [V1, V2] eventp, V1, state, V2, fd);
+impl_handler!(@quad [V1, V2, V3] eventp, V1, state, V2, V3);
+impl_handler!(@quad [V1, V2] eventp, V1, V2, fd, state);
+impl_handler!(@quad [V1, V2] eventp, V1, V2, state, fd);
+impl_handler!(@quad [V1, V2, V3] eventp, V1, V2, state, V3);
+impl_handler!(@quad [V1, V2, V3] eventp, V1, V2, V3, state);
+
+// With a `ViewOps` in place of `Pinned`, for handlers only observing the loop.
+
+// 1 parameter (1 variant)
+impl_handler!([] view);
+
+// 2 parameters (4 variants)
+impl_handler!([] fd, view);
+impl_handler!([V1] V1, view);
+impl_handler!([] view, fd);
+impl_handler!([V1] view, V1);
+
+// 3 parameters (9 variants)
+impl_handler!([V1] fd, V1, view);
+impl_handler!([V1] fd, view, V1);
+impl_handler!([V1] V1, fd, view);
+impl_handler!([V1, V2] V1, V2, view);
+impl_handler!([V1] V1, view, fd);
+impl_handler!([V1, V2] V1, view, V2);
+impl_handler!([V1] view, fd, V1);
+impl_handler!([V1] view, V1, fd);
+impl_handler!([V1, V2] view, V1, V2);
+
+// 4 parameters (16 variants)
+impl_handler!([V1, V2] fd, V1, V2, view);
+impl_handler!([V1, V2] fd, V1, view, V2);
+impl_handler!([V1, V2] fd, view, V1, V2);
+impl_handler!([V1, V2] V1, fd, V2, view);
+impl_handler!([V1, V2] V1, fd, view, V2);
+impl_handler!([V1, V2] V1, V2, fd, view);
+impl_handler!([V1, V2, V3] V1, V2, V3, view);
+impl_handler!([V1, V2] V1, V2, view, fd);
+impl_handler!([V1, V2, V3] V1, V2, view, V3);
+impl_handler!([V1, V2] V1, view, fd, V2);
+impl_handler!([V1, V2] V1, view, V2, fd);
+impl_handler!([V1, V2, V3] V1, view, V2, V3);
+impl_handler!([V1, V2] view, fd, V1, V2);
+impl_handler!([V1, V2] view, V1, fd, V2);
+impl_handler!([V1, V2] view, V1, V2, fd);
+impl_handler!([V1, V2, V3] view, V1, V2, V3);
+
+// 5 parameters (20 variants)
+impl_handler!([V1, V2, V3] fd, V1, V2, V3, view);
+impl_handler!([V1, V2, V3] fd, V1, V2, view, V3);
+impl_handler!([V1, V2, V3] fd, V1, view, V2, V3);
+impl_handler!([V1, V2, V3] fd, view, V1, V2, V3);
+impl_handler!([V1, V2, V3] V1, fd, V2, V3, view);
+impl_handler!([V1, V2, V3] V1, fd, V2, view, V3);
+impl_handler!([V1, V2, V3] V1, fd, view, V2, V3);
+impl_handler!([V1, V2, V3] V1, V2, fd, V3, view);
+impl_handler!([V1, V2, V3] V1, V2, fd, view, V3);
+impl_handler!([V1, V2, V3] V1, V2, V3, fd, view);
+impl_handler!([V1, V2, V3] V1, V2, V3, view, fd);
+impl_handler!([V1, V2, V3] V1, V2, view, fd, V3);
+impl_handler!([V1, V2, V3] V1, V2, view, V3, fd);
+impl_handler!([V1, V2, V3] V1, view, fd, V2, V3);
+impl_handler!([V1, V2, V3] V1, view, V2, fd, V3);
+impl_handler!([V1, V2, V3] V1, view, V2, V3, fd);
+impl_handler!([V1, V2, V3] view, fd, V1, V2, V3);
+impl_handler!([V1, V2, V3] view, V1, fd, V2, V3);
+impl_handler!([V1, V2, V3] view, V1, V2, fd, V3);
+impl_handler!([V1, V2, V3] view, V1, V2, V3, fd);
+
+// With a state and a `ViewOps`.
+
+// 2 parameters (2 variants)
+impl_handler!(@quad [] state, view);
+impl_handler!(@quad [] view, state);
+
+// 3 parameters (12 variants)
+impl_handler!(@quad [] fd, state, view);
+impl_handler!(@quad [] fd, view, state);
+impl_handler!(@quad [] state, fd, view);
+impl_handler!(@quad [V1] state, V1, view);
+impl_handler!(@quad [] state, view, fd);
+impl_handler!(@quad [V1] state, view, V1);
+impl_handler!(@quad [V1] V1, state, view);
+impl_handler!(@quad [V1] V1, view, state);
+impl_handler!(@quad [] view, fd, state);
+impl_handler!(@quad [] view, state, fd);
+impl_handler!(@quad [V1] view, state, V1);
+impl_handler!(@quad [V1] view, V1, state);
+
+// 4 parameters (36 variants)
+impl_handler!(@quad [V1] fd, state, V1, view);
+impl_handler!(@quad [V1] fd, state, view, V1);
+impl_handler!(@quad [V1] fd, V1, state, view);
+impl_handler!(@quad [V1] fd, V1, view, state);
+impl_handler!(@quad [V1] fd, view, state, V1);
+impl_handler!(@quad [V1] fd, view, V1, state);
+impl_handler!(@quad [V1] state, fd, V1, view);
+impl_handler!(@quad [V1] state, fd, view, V1);
+impl_handler!(@quad [V1] state, V1, fd, view);
+impl_handler!(@quad [V1, V2] state, V1, V2, view);
+impl_handler!(@quad [V1] state, V1, view, fd);
+impl_handler!(@quad [V1, V2] state, V1, view, V2);
+impl_handler!(@quad [V1] state, view, fd, V1);
+impl_handler!(@quad [V1] state, view, V1, fd);
+impl_handler!(@quad [V1, V2] state, view, V1, V2);
+impl_handler!(@quad [V1] V1, fd, state, view);
+impl_handler!(@quad [V1] V1, fd, view, state);
+impl_handler!(@quad [V1] V1, state, fd, view);
+impl_handler!(@quad [V1, V2] V1, state, V2, view);
+impl_handler!(@quad [V1] V1, state, view, fd);
+impl_handler!(@quad [V1, V2] V1, state, view, V2);
+impl_handler!(@quad [V1, V2] V1, V2, state, view);
+impl_handler!(@quad [V1, V2] V1, V2, view, state);
+impl_handler!(@quad [V1] V1, view, fd, state);
+impl_handler!(@quad [V1] V1, view, state, fd);
+impl_handler!(@quad [V1, V2] V1, view, state, V2);
+impl_handler!(@quad [V1, V2] V1, view, V2, state);
+impl_handler!(@quad [V1] view, fd, state, V1);
+impl_handler!(@quad [V1] view, fd, V1, state);
+impl_handler!(@quad [V1] view, state, fd, V1);
+impl_handler!(@quad [V1] view, state, V1, fd);
+impl_handler!(@quad [V1, V2] view, state, V1, V2);
+impl_handler!(@quad [V1] view, V1, fd, state);
+impl_handler!(@quad [V1] view, V1, state, fd);
+impl_handler!(@quad [V1, V2] view, V1, state, V2);
+impl_handler!(@quad [V1, V2] view, V1, V2, state);
+
+// 5 parameters (80 variants)
+impl_handler!(@quad [V1, V2] fd, state, V1, V2, view);
+impl_handler!(@quad [V1, V2] fd, state, V1, view, V2);
+impl_handler!(@quad [V1, V2] fd, state, view, V1, V2);
+impl_handler!(@quad [V1, V2] fd, V1, state, V2, view);
+impl_handler!(@quad [V1, V2] fd, V1, state, view, V2);
+impl_handler!(@quad [V1, V2] fd, V1, V2, state, view);
+impl_handler!(@quad [V1, V2] fd, V1, V2, view, state);
+impl_handler!(@quad [V1, V2] fd, V1, view, state, V2);
+impl_handler!(@quad [V1, V2] fd, V1, view, V2, state);
+impl_handler!(@quad [V1, V2] fd, view, state, V1, V2);
+impl_handler!(@quad [V1, V2] fd, view, V1, state, V2);
+impl_handler!(@quad [V1, V2] fd, view, V1, V2, state);
+impl_handler!(@quad [V1, V2] state, fd, V1, V2, view);
+impl_handler!(@quad [V1, V2] state, fd, V1, view, V2);
+impl_handler!(@quad [V1, V2] state, fd, view, V1, V2);
+impl_handler!(@quad [V1, V2] state, V1, fd, V2, view);
+impl_handler!(@quad [V1, V2] state, V1, fd, view, V2);
+impl_handler!(@quad [V1, V2] state, V1, V2, fd, view);
+impl_handler!(@quad [V1, V2, V3] state, V1, V2, V3, view);
+impl_handler!(@quad [V1, V2] state, V1, V2, view, fd);
+impl_handler!(@quad [V1, V2, V3] state, V1, V2, view, V3);
+impl_handler!(@quad [V1, V2] state, V1, view, fd, V2);
+impl_handler!(@quad [V1, V2] state, V1, view, V2, fd);
+impl_handler!(@quad [V1, V2, V3] state, V1, view, V2, V3);
+impl_handler!(@quad [V1, V2] state, view, fd, V1, V2);
+impl_handler!(@quad [V1, V2] state, view, V1, fd, V2);
+impl_handler!(@quad [V1, V2] state, view, V1, V2, fd);
+impl_handler!(@quad [V1, V2, V3] state, view, V1, V2, V3);
+impl_handler!(@quad [V1, V2] V1, fd, state, V2, view);
+impl_handler!(@quad [V1, V2] V1, fd, state, view, V2);
+impl_handler!(@quad [V1, V2] V1, fd, V2, state, view);
+impl_handler!(@quad [V1, V2] V1, fd, V2, view, state);
+impl_handler!(@quad [V1, V2] V1, fd, view, state, V2);
+impl_handler!(@quad [V1, V2] V1, fd, view, V2, state);
+impl_handler!(@quad [V1, V2] V1, state, fd, V2, view);
+impl_handler!(@quad [V1, V2] V1, state, fd, view, V2);
+impl_handler!(@quad [V1, V2] V1, state, V2, fd, view);
+impl_handler!(@quad [V1, V2, V3] V1, state, V2, V3, view);
+impl_handler!(@quad [V1, V2] V1, state, V2, view, fd);
+impl_handler!(@quad [V1, V2, V3] V1, state, V2, view, V3);
+impl_handler!(@quad [V1, V2] V1, state, view, fd, V2);
+impl_handler!(@quad [V1, V2] V1, state, view, V2, fd);
+impl_handler!(@quad [V1, V2, V3] V1, state, view, V2, V3);
+impl_handler!(@quad [V1, V2] V1, V2, fd, state, view);
+impl_handler!(@quad [V1, V2] V1, V2, fd, view, state);
+impl_handler!(@quad [V1, V2] V1, V2, state, fd, view);
+impl_handler!(@quad [V1, V2, V3] V1, V2, state, V3, view);
+impl_handler!(@quad [V1, V2] V1, V2, state, view, fd);
+impl_handler!(@quad [V1, V2, V3] V1, V2, state, view, V3);
+impl_handler!(@quad [V1, V2, V3] V1, V2, V3, state, view);
+impl_handler!(@quad [V1, V2, V3] V1, V2, V3, view, state);
+impl_handler!(@quad [V1, V2] V1, V2, view, fd, state);
+impl_handler!(@quad [V1, V2] V1, V2, view, state, fd);
+impl_handler!(@quad [V1, V2, V3] V1, V2, view, state, V3);
+impl_handler!(@quad [V1, V2, V3] V1, V2, view, V3, state);
+impl_handler!(@quad [V1, V2] V1, view, fd, state, V2);
+impl_handler!(@quad [V1, V2] V1, view, fd, V2, state);
+impl_handler!(@quad [V1, V2] V1, view, state, fd, V2);
+impl_handler!(@quad [V1, V2] V1, view, state, V2, fd);
+impl_handler!(@quad [V1, V2, V3] V1, view, state, V2, V3);
+impl_handler!(@quad [V1, V2] V1, view, V2, fd, state);
+impl_handler!(@quad [V1, V2] V1, view, V2, state, fd);
+impl_handler!(@quad [V1, V2, V3] V1, view, V2, state, V3);
+impl_handler!(@quad [V1, V2, V3] V1, view, V2, V3, state);
+impl_handler!(@quad [V1, V2] view, fd, state, V1, V2);
+impl_handler!(@quad [V1, V2] view, fd, V1, state, V2);
+impl_handler!(@quad [V1, V2] view, fd, V1, V2, state);
+impl_handler!(@quad [V1, V2] view, state, fd, V1, V2);
+impl_handler!(@quad [V1, V2] view, state, V1, fd, V2);
+impl_handler!(@quad [V1, V2] view, state, V1, V2, fd);
+impl_handler!(@quad [V1, V2, V3] view, state, V1, V2, V3);
+impl_handler!(@quad [V1, V2] view, V1, fd, state, V2);
+impl_handler!(@quad [V1, V2] view, V1, fd, V2, state);
+impl_handler!(@quad [V1, V2] view, V1, state, fd, V2);
+impl_handler!(@quad [V1, V2] view, V1, state, V2, fd);
+impl_handler!(@quad [V1, V2, V3] view, V1, state, V2, V3);
+impl_handler!(@quad [V1, V2] view, V1, V2, fd, state);
+impl_handler!(@quad [V1, V2] view, V1, V2, state, fd);
+impl_handler!(@quad [V1, V2, V3] view, V1, V2, state, V3);
+impl_handler!(@quad [V1, V2, V3] view, V1, V2, V3, state);
@@ -0,0 +1,1049 @@
+//! An experimental reactor waiting with io_uring instead of `epoll_wait`.
+//!
+//! [`UringEventp`] implements [`EventpOps`], so handlers written against
+//! `EventpOps` and [`Pinned`] run on it unchanged. Every subscriber is watched by
+//! an `IORING_OP_POLL_ADD` request whose `user_data` is the thin pointer of the
+//! subscriber, just as the epoll data word is for [`Eventp`](crate::Eventp):
+//!
+//! - Edge-triggered interests use a multishot poll, re-armed if the kernel ends it,
+//!   e.g. when the completion queue overflows.
+//! - Level-triggered interests use a single-shot poll, re-armed after every event,
+//!   so an fd that is still ready is reported again by the next `run_once`.
+//! - `EPOLLONESHOT` interests are not re-armed until they are
+//!   [`modify`](EventpOps::modify)-ed, as with epoll.
+//!
+//! `modify` cancels the poll and adds a new one, and `delete` cancels it. Unlike
+//! `EPOLL_CTL_DEL`, a cancellation completes later, so a deleted subscriber is
+//! dropped in place right away, as with `Eventp`, but its memory is kept until
+//! the last completion pointing to it has been reaped.
+//!
+//! Requests are queued, and submitted by the next `run_*` call together with the
+//! wait, so registering a burst of fds costs one syscall. The flip side is that
+//! errors of a poll request, such as `EBADF`, are not reported by
+//! [`add`](EventpOpsAdd::add) but by the `run_*` call that reaps them, as an
+//! [`Error::Syscall`](crate::Error::Syscall) for `"IORING_OP_POLL_ADD"`. The
+//! subscriber stays registered, but is not polled again until it is
+//! [`modify`](EventpOps::modify)-ed. Requests that do not fit in the submission
+//! queue wait in a backlog.
+//!
+//! Requires Linux 5.13 for multishot polls. Budgets, groups, [`Stats`](crate::Stats)
+//! and error policies are not supported: the first handler error of a batch is
+//! returned once the batch is over, as under
+//! [`ErrorPolicy::Propagate`](crate::ErrorPolicy::Propagate).
+
+use std::collections::hash_map::Entry;
+use std::collections::VecDeque;
+use std::ffi::c_void;
+use std::marker::PhantomPinned;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use std::{io, mem, ptr};
+
+use rustc_hash::FxHashMap;
+
+use crate::epoll::{EpollFlags, EpollTimeout};
+use crate::run::Exit;
+use crate::thin::ThinBoxSubscriber;
+use crate::{AddError, Deferred, Event, EventDelta, EventpOps, EventpOpsAdd, Interest, Pinned};
+
+const DEFAULT_ENTRIES: u32 = 256;
+
+/// The `user_data` of cancellations, whose completions carry nothing of interest.
+/// Thin pointers are never null.
+const CTL_USER_DATA: u64 = 0;
+
+/// An event loop with the semantics of [`Eventp`](crate::Eventp), driven by
+/// io_uring poll requests. See the [module documentation](self).
+///
+/// # Drop
+///
+/// Dropping a `UringEventp` drops every registered subscriber exactly once, and
+/// then closes the ring, which cancels every request still in flight.
+pub struct UringEventp {
+    registered: FxHashMap<RawFd, ThinBoxSubscriber<UringEventp>>,
+    /// The poll requests of each subscriber, by thin-pointer address, that have
+    /// not yet posted their last completion, i.e. one without `IORING_CQE_F_MORE`.
+    in_flight: FxHashMap<u64, u32>,
+    /// Deleted subscribers, dropped in place, whose memory is still referred to by
+    /// requests in flight.
+    zombies: FxHashMap<u64, ThinBoxSubscriber<UringEventp>>,
+    ring: Ring,
+    /// Reused between batches, to avoid an allocation per `run_once`.
+    cqes: Vec<Cqe>,
+    handling: Option<Handling>,
+    /// Deleted subscribers freed at the end of the batch. Reused like `cqes`.
+    deferred_drop: Vec<ThinBoxSubscriber<UringEventp>>,
+    /// See [`EventpOps::defer`].
+    deferred: VecDeque<Deferred<UringEventp>>,
+    _pinned: PhantomPinned,
+}
+
+struct Handling {
+    fd: RawFd,
+    interest: Interest,
+    delta: Option<EventDelta>,
+    since_last: Option<Duration>,
+    drop_current: bool,
+    /// The first error of the batch, returned once it is over.
+    error: Option<(RawFd, io::Error)>,
+}
+
+impl Default for UringEventp {
+    /// Creates a new `UringEventp` with 256 submission queue entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ring cannot be set up. Use [`UringEventp::new`] if you need
+    /// to handle that error.
+    fn default() -> Self {
+        Self::new(DEFAULT_ENTRIES).expect("Failed to create io_uring instance")
+    }
+}
+
+impl UringEventp {
+    /// Creates a new `UringEventp` whose submission queue holds `entries`
+    /// requests, rounded up to a power of two by the kernel. This is not a limit
+    /// on the number of subscribers.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `io::Error` of `io_uring_setup` or `mmap`, or
+    /// [`io::ErrorKind::Unsupported`] if the kernel predates Linux 5.11.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entries` is zero.
+    pub fn new(entries: u32) -> io::Result<Self> {
+        assert!(entries > 0, "Entries must be greater than zero");
+
+        Ok(Self {
+            registered: Default::default(),
+            in_flight: Default::default(),
+            zombies: Default::default(),
+            ring: Ring::new(entries)?,
+            cqes: Vec::new(),
+            handling: None,
+            deferred_drop: Vec::new(),
+            deferred: VecDeque::new(),
+            _pinned: PhantomPinned,
+        })
+    }
+
+    /// Returns whether the raw fd is registered with this `UringEventp`.
+    pub fn contains(&self, raw_fd: RawFd) -> bool {
+        self.registered.contains_key(&raw_fd)
+    }
+
+    /// Runs the event loop until a non-`EINTR` error occurs.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `io::Error` from `io_uring_enter` that is not
+    /// [`io::ErrorKind::Interrupted`], or the first error of a batch, see
+    /// [`run_once_with_timeout`](Self::run_once_with_timeout). The function never
+    /// returns `Ok(())`.
+    pub fn run_forever(&mut self) -> io::Result<()> {
+        loop {
+            match self.dispatch(EpollTimeout::NONE) {
+                Ok(()) => {}
+                Err(Exit::Epoll(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(exit) => return Err(exit.into()),
+            }
+        }
+    }
+
+    /// Waits with no timeout and dispatches every ready event to its handler.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run_once_with_timeout`](Self::run_once_with_timeout).
+    ///
+    /// # Panics
+    ///
+    /// Panics if called recursively from within an event handler.
+    pub fn run_once(&mut self) -> io::Result<()> {
+        self.run_once_with_timeout(EpollTimeout::NONE)
+    }
+
+    /// Dispatches the events that are ready, without waiting for any.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run_once_with_timeout`](Self::run_once_with_timeout).
+    ///
+    /// # Panics
+    ///
+    /// Panics if called recursively from within an event handler.
+    pub fn try_run_once(&mut self) -> io::Result<()> {
+        self.run_once_with_timeout(EpollTimeout::ZERO)
+    }
+
+    /// Submits the queued requests, waits up to `timeout` for a completion, and
+    /// dispatches every ready event to its handler.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any `io::Error` from `io_uring_enter`, including
+    /// [`io::ErrorKind::Interrupted`] when it is interrupted by a signal. An
+    /// expired timeout is not an error.
+    ///
+    /// Once the batch is over, returns the first error of its handlers or of its
+    /// poll requests, the latter as an [`Error::Syscall`](crate::Error::Syscall)
+    /// for `"IORING_OP_POLL_ADD"`, e.g. with `EBADF` for an fd closed while
+    /// registered. Later errors of the batch are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called recursively (i.e. from within an event handler), for the
+    /// same reasons as [`Eventp::run_once_with_timeout`](crate::Eventp::run_once_with_timeout).
+    pub fn run_once_with_timeout(&mut self, timeout: EpollTimeout) -> io::Result<()> {
+        self.dispatch(timeout).map_err(io::Error::from)
+    }
+
+    fn dispatch(&mut self, timeout: EpollTimeout) -> Result<(), Exit> {
+        if let Some(handling) = &self.handling {
+            panic!(
+                "Recursive call to `UringEventp::run_once_with_timeout` while handling fd {}",
+                handling.fd
+            );
+        }
+
+        if !self.deferred.is_empty() {
+            self.run_deferred();
+        }
+
+        self.ring.submit_and_wait(timeout).map_err(Exit::Epoll)?;
+        let mut cqes = mem::take(&mut self.cqes);
+        self.ring.reap(&mut cqes);
+
+        self.handling = Some(Handling {
+            fd: -1, // Invalid fd, will be updated for each event.
+            interest: Interest::default(),
+            delta: None,
+            since_last: None,
+            drop_current: false,
+            error: None,
+        });
+
+        for cqe in &cqes {
+            if cqe.user_data == CTL_USER_DATA {
+                continue;
+            }
+            let addr = cqe.user_data;
+            let last = cqe.flags & IORING_CQE_F_MORE == 0;
+            if last {
+                if let Entry::Occupied(mut count) = self.in_flight.entry(addr) {
+                    *count.get_mut() -= 1;
+                    if *count.get() == 0 {
+                        count.remove();
+                    }
+                }
+            }
+
+            if self.zombies.contains_key(&addr) {
+                if !self.in_flight.contains_key(&addr) {
+                    // That was the last completion pointing to it.
+                    self.zombies.remove(&addr);
+                }
+                continue;
+            }
+
+            // SAFETY: `addr` is the thin pointer of a subscriber with a request in
+            // flight, or up to this completion. Such a subscriber is either in
+            // `self.registered` or in `self.zombies`, which was just ruled out,
+            // so it is allocated and live. `ManuallyDrop` as in `Eventp`: the
+            // owner is `self.registered`.
+            let mut subscriber = unsafe { ThinBoxSubscriber::<UringEventp>::from_data(addr) };
+            let fd = *subscriber.raw_fd_ref();
+            {
+                let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+                handling.fd = fd;
+                handling.interest = subscriber.interest();
+            }
+
+            // A negative result is an error of the request, not an event. Only
+            // `ECANCELED` is expected, for the poll replaced by `modify`.
+            if cqe.res < 0 && cqe.res != -libc::ECANCELED {
+                let error = crate::Error::Syscall {
+                    op: "IORING_OP_POLL_ADD",
+                    fd,
+                    name: subscriber.name(),
+                    errno: -cqe.res,
+                };
+                let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+                handling.error.get_or_insert((fd, error.into()));
+            }
+            let event = Event::new(EpollFlags::from_bits_retain(cqe.res.max(0)));
+            if cqe.res > 0 && !subscriber.suppresses(event) {
+                let delta = subscriber.record_event(event);
+                let since_last = subscriber.record_dispatch_time();
+                let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+                handling.delta = delta;
+                handling.since_last = since_last;
+                if let Some(s) = subscriber.try_deref_mut() {
+                    // SAFETY: Same as in `Eventp::run_once_with_timeout`.
+                    let result =
+                        s.try_handle(event, Pinned(unsafe { Pin::new_unchecked(&mut *self) }));
+                    if let Err(error) = result {
+                        let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+                        handling.error.get_or_insert((fd, error));
+                    }
+                }
+            }
+
+            let handling = unsafe { self.handling.as_mut().unwrap_unchecked() };
+            if handling.drop_current {
+                handling.drop_current = false;
+                let subscriber = self.registered.remove(&fd).unwrap();
+                self.retire(subscriber);
+            } else if cqe.res >= 0 && !self.in_flight.contains_key(&addr) {
+                // The poll has ended; it is re-armed unless it was meant to, or
+                // the handler already did with `modify`. Without `EPOLLOUT` after
+                // a dropped write readiness, as `Eventp` does, until `modify`.
+                let mut interest = subscriber.interest();
+                if subscriber.is_writable_parked() {
+                    interest = interest.remove_write();
+                }
+                if !interest.bitflags().contains(EpollFlags::EPOLLONESHOT) {
+                    self.arm(fd, addr, interest);
+                }
+            }
+        }
+
+        cqes.clear();
+        self.cqes = cqes;
+        // SAFETY: Set above, and only taken here.
+        let handling = unsafe { self.handling.take().unwrap_unchecked() };
+        // Drops the subscribers deleted during the batch.
+        self.deferred_drop.clear();
+        if !self.deferred.is_empty() {
+            self.run_deferred();
+        }
+        match handling.error {
+            Some((fd, error)) => Err(Exit::Handler { fd, error }),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the closures queued by [`EventpOps::defer`], outside of any batch.
+    fn run_deferred(&mut self) {
+        crate::deferred::run_deferred(self, |uring| &mut uring.deferred);
+    }
+
+    /// Queues a poll request for the subscriber at `addr`.
+    fn arm(&mut self, fd: RawFd, addr: u64, interest: Interest) {
+        self.ring.push(Sqe::poll_add(fd, interest, addr));
+        *self.in_flight.entry(addr).or_insert(0) += 1;
+    }
+
+    /// Queues the cancellation of the poll of the subscriber at `addr`, if any.
+    fn cancel(&mut self, addr: u64) {
+        if self.in_flight.contains_key(&addr) {
+            self.ring.push(Sqe::poll_remove(addr));
+        }
+    }
+
+    /// Drops a deleted subscriber in place, and frees it once nothing refers to
+    /// it anymore.
+    fn retire(&mut self, mut subscriber: ThinBoxSubscriber<UringEventp>) {
+        let addr = subscriber.to_data();
+        subscriber.drop_in_place();
+        if self.in_flight.contains_key(&addr) {
+            self.zombies.insert(addr, subscriber);
+        } else if self.handling.is_some() {
+            self.deferred_drop.push(subscriber);
+        }
+    }
+
+    /// Whether `fd` is the subscriber being handled, and has deleted itself.
+    fn is_deleted_current(&self, fd: RawFd) -> bool {
+        matches!(&self.handling, Some(h) if h.fd == fd && h.drop_current)
+    }
+}
+
+impl EventpOpsAdd<Self> for UringEventp {
+    /// Registers a new subscriber with the event loop. Its poll request is
+    /// submitted by the next `run_*` call.
+    ///
+    /// # Errors
+    ///
+    /// [`io::ErrorKind::AlreadyExists`] if a subscriber for the same [`RawFd`]
+    /// is already registered, with the subscriber given back.
+    ///
+    /// # Panics
+    ///
+    /// Same as [`Eventp`](crate::Eventp)'s `add`.
+    fn try_add(&mut self, subscriber: ThinBoxSubscriber<Self>) -> Result<(), AddError<Self>> {
+        let raw_fd = crate::eventp_ops::raw_fd_of(&subscriber);
+        if self.registered.contains_key(&raw_fd) {
+            let error = io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "subscriber with same fd already registered",
+            );
+            return Err(AddError::new(error, subscriber));
+        }
+
+        self.arm(raw_fd, subscriber.to_data(), subscriber.interest());
+        self.registered.insert(raw_fd, subscriber);
+        Ok(())
+    }
+}
+
+impl EventpOps for UringEventp {
+    /// Changes the interest of a registered subscriber, by cancelling its poll
+    /// and queuing a new one.
+    ///
+    /// # Errors
+    ///
+    /// [`io::ErrorKind::NotFound`] if no subscriber is registered for `fd`.
+    fn modify(&mut self, fd: RawFd, interest: Interest) -> io::Result<()> {
+        if self.is_deleted_current(fd) || !self.registered.contains_key(&fd) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "fd not registered"));
+        }
+
+        let subscriber = self.registered.get_mut(&fd).unwrap();
+        let interest = interest.or_user_data_of(subscriber.interest());
+        subscriber.set_interest(interest);
+        let addr = subscriber.to_data();
+        self.cancel(addr);
+        self.arm(fd, addr, interest);
+
+        if let Some(handling) = &mut self.handling {
+            if handling.fd == fd {
+                handling.interest = interest;
+            }
+        }
+        Ok(())
+    }
+
+    fn current_interest(&self) -> Option<Interest> {
+        self.handling.as_ref().map(|handling| handling.interest)
+    }
+
+    fn current_delta(&self) -> Option<EventDelta> {
+        self.handling.as_ref().and_then(|handling| handling.delta)
+    }
+
+    fn since_last_event(&self) -> Option<Duration> {
+        self.handling
+            .as_ref()
+            .and_then(|handling| handling.since_last)
+    }
+
+    fn user_data_of(&self, fd: RawFd) -> Option<u64> {
+        self.registered
+            .get(&fd)
+            .map(|subscriber| subscriber.interest().user_data_value())
+    }
+
+    /// Sets the user data of a registered subscriber, see
+    /// [`Interest::user_data`].
+    ///
+    /// # Errors
+    ///
+    /// [`io::ErrorKind::NotFound`] if no subscriber is registered for `fd`.
+    fn set_user_data(&mut self, fd: RawFd, data: u64) -> io::Result<()> {
+        if self.is_deleted_current(fd) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "fd not registered"));
+        }
+        self.registered
+            .get_mut(&fd)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "fd not registered"))?
+            .set_user_data(data);
+        if let Some(handling) = &mut self.handling {
+            if handling.fd == fd {
+                handling.interest = handling.interest.user_data(data);
+            }
+        }
+        Ok(())
+    }
+
+    fn defer(&mut self, f: Deferred<Self>) {
+        self.deferred.push_back(f);
+    }
+
+    /// Unregisters a subscriber, with the semantics of
+    /// [`Eventp`](crate::Eventp)'s `delete`: the subscriber is dropped right
+    /// away, or after its handler returns if it deletes itself.
+    ///
+    /// # Errors
+    ///
+    /// [`io::ErrorKind::NotFound`] if no subscriber is registered for `fd`.
+    fn delete(&mut self, fd: RawFd) -> io::Result<()> {
+        if self.is_deleted_current(fd) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "fd not registered"));
+        }
+        let addr = match self.registered.get(&fd) {
+            Some(subscriber) => subscriber.to_data(),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "fd not registered")),
+        };
+
+        self.cancel(addr);
+
+        match &mut self.handling {
+            // Dropped after the handler returns, like `Eventp` does.
+            Some(handling) if handling.fd == fd => handling.drop_current = true,
+            _ => {
+                let subscriber = self.registered.remove(&fd).unwrap();
+                self.retire(subscriber);
+            }
+        }
+        Ok(())
+    }
+}
+
+// ---------- the ring ----------
+
+const IORING_OP_POLL_ADD: u8 = 6;
+const IORING_OP_POLL_REMOVE: u8 = 7;
+const IORING_POLL_ADD_MULTI: u32 = 1 << 0;
+const IORING_CQE_F_MORE: u32 = 1 << 1;
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+const IORING_OFF_SQES: libc::off_t = 0x10000000;
+const IORING_ENTER_GETEVENTS: u32 = 1 << 0;
+const IORING_ENTER_EXT_ARG: u32 = 1 << 3;
+const IORING_FEAT_SINGLE_MMAP: u32 = 1 << 0;
+const IORING_FEAT_EXT_ARG: u32 = 1 << 8;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    resv2: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    resv2: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// `struct io_uring_sqe`, with the unions reduced to the fields used here.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    poll32_events: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+const _: () = assert!(mem::size_of::<Sqe>() == 64);
+
+impl Sqe {
+    fn poll_add(fd: RawFd, interest: Interest, user_data: u64) -> Self {
+        let flags = interest.bitflags();
+        let mode = EpollFlags::EPOLLET | EpollFlags::EPOLLONESHOT;
+        // The kernel adds `EPOLLET` itself, and `EPOLLONESHOT` unless multishot.
+        let events = flags.difference(mode).bits() as u32;
+        #[cfg(target_endian = "big")]
+        let events = events.rotate_left(16);
+
+        Self {
+            opcode: IORING_OP_POLL_ADD,
+            fd,
+            len: if flags & mode == EpollFlags::EPOLLET {
+                IORING_POLL_ADD_MULTI
+            } else {
+                0
+            },
+            poll32_events: events,
+            user_data,
+            ..Self::default()
+        }
+    }
+
+    fn poll_remove(target: u64) -> Self {
+        Self {
+            opcode: IORING_OP_POLL_REMOVE,
+            fd: -1,
+            addr: target,
+            user_data: CTL_USER_DATA,
+            ..Self::default()
+        }
+    }
+}
+
+/// `struct io_uring_cqe`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// `struct io_uring_getevents_arg`.
+#[repr(C)]
+struct GeteventsArg {
+    sigmask: u64,
+    sigmask_sz: u32,
+    pad: u32,
+    ts: u64,
+}
+
+struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(fd: &OwnedFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        // SAFETY: A fresh shared mapping of the ring, checked for failure.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd.as_raw_fd(),
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    /// Returns a pointer `offset` bytes into the mapping.
+    fn at<T>(&self, offset: u32) -> *mut T {
+        debug_assert!(offset as usize + mem::size_of::<T>() <= self.len);
+        // SAFETY: In bounds of the mapping, as the kernel computed the offsets.
+        unsafe { self.ptr.cast::<u8>().add(offset as usize).cast() }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: Unmaps exactly what `new` mapped.
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+/// The raw submission and completion queues, shared with the kernel.
+struct Ring {
+    // Declared before `fd`, so the rings are unmapped before it is closed.
+    _sq_map: Mmap,
+    /// `None` if the completion queue shares `_sq_map`.
+    _cq_map: Option<Mmap>,
+    sqes_map: Mmap,
+
+    sq_head: *const AtomicU32,
+    sq_tail: *const AtomicU32,
+    sq_mask: u32,
+    sq_entries: u32,
+    sq_array: *mut u32,
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cq_mask: u32,
+    cqes: *const Cqe,
+    /// Entries pushed but not yet consumed by the kernel.
+    unsubmitted: u32,
+    /// Entries queued while the submission queue was full, in order.
+    backlog: VecDeque<Sqe>,
+
+    fd: OwnedFd,
+}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+        // SAFETY: `params` is a valid `struct io_uring_params` for the call.
+        let ret = unsafe { libc::syscall(libc::SYS_io_uring_setup, entries, &mut params) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: The fd was just created and is owned by nothing else.
+        let fd = unsafe { OwnedFd::from_raw_fd(ret as RawFd) };
+        if params.features & IORING_FEAT_EXT_ARG == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "io_uring lacks IORING_FEAT_EXT_ARG (Linux 5.11)",
+            ));
+        }
+
+        let (sq_off, cq_off) = (&params.sq_off, &params.cq_off);
+        let sq_len = sq_off.array as usize + params.sq_entries as usize * mem::size_of::<u32>();
+        let cq_len = cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let (sq_map, cq_map) = if params.features & IORING_FEAT_SINGLE_MMAP != 0 {
+            (
+                Mmap::new(&fd, sq_len.max(cq_len), IORING_OFF_SQ_RING)?,
+                None,
+            )
+        } else {
+            let sq_map = Mmap::new(&fd, sq_len, IORING_OFF_SQ_RING)?;
+            (sq_map, Some(Mmap::new(&fd, cq_len, IORING_OFF_CQ_RING)?))
+        };
+        let sqes_map = Mmap::new(
+            &fd,
+            params.sq_entries as usize * mem::size_of::<Sqe>(),
+            IORING_OFF_SQES,
+        )?;
+        let cq = cq_map.as_ref().unwrap_or(&sq_map);
+
+        // SAFETY: The masks are plain values the kernel wrote at setup.
+        let (sq_mask, cq_mask) = unsafe {
+            (
+                *sq_map.at::<u32>(sq_off.ring_mask),
+                *cq.at::<u32>(cq_off.ring_mask),
+            )
+        };
+        Ok(Self {
+            sq_head: sq_map.at(sq_off.head),
+            sq_tail: sq_map.at(sq_off.tail),
+            sq_mask,
+            sq_entries: params.sq_entries,
+            sq_array: sq_map.at(sq_off.array),
+            cq_head: cq.at(cq_off.head),
+            cq_tail: cq.at(cq_off.tail),
+            cq_mask,
+            cqes: cq.at(cq_off.cqes),
+            unsubmitted: 0,
+            backlog: VecDeque::new(),
+            _sq_map: sq_map,
+            _cq_map: cq_map,
+            sqes_map,
+            fd,
+        })
+    }
+
+    fn sq_len(&self) -> u32 {
+        // SAFETY: The head and tail are valid for the lifetime of the mapping.
+        // Only we write the tail, the kernel writes the head.
+        unsafe {
+            let tail = (*self.sq_tail).load(Ordering::Relaxed);
+            tail.wrapping_sub((*self.sq_head).load(Ordering::Acquire))
+        }
+    }
+
+    /// Queues `sqe`, in the backlog if the submission queue is full.
+    fn push(&mut self, sqe: Sqe) {
+        if !self.backlog.is_empty() || self.sq_len() == self.sq_entries {
+            self.backlog.push_back(sqe);
+        } else {
+            self.push_sq(sqe);
+        }
+    }
+
+    fn push_sq(&mut self, sqe: Sqe) {
+        debug_assert!(self.sq_len() < self.sq_entries);
+        // SAFETY: The slot at `tail` is free, since the queue is not full, and
+        // only becomes visible to the kernel with the release store of the tail.
+        unsafe {
+            let tail = (*self.sq_tail).load(Ordering::Relaxed);
+            let index = tail & self.sq_mask;
+            self.sqes_map.at::<Sqe>(0).add(index as usize).write(sqe);
+            self.sq_array.add(index as usize).write(index);
+            (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+        self.unsubmitted += 1;
+    }
+
+    fn cq_is_empty(&self) -> bool {
+        // SAFETY: See `sq_len`; here the kernel writes the tail.
+        unsafe {
+            (*self.cq_head).load(Ordering::Relaxed) == (*self.cq_tail).load(Ordering::Acquire)
+        }
+    }
+
+    /// Submits the queued entries and waits up to `timeout` for a completion,
+    /// unless some are already there.
+    fn submit_and_wait(&mut self, timeout: EpollTimeout) -> io::Result<()> {
+        while !self.backlog.is_empty() {
+            while self.sq_len() < self.sq_entries {
+                match self.backlog.pop_front() {
+                    Some(sqe) => self.push_sq(sqe),
+                    None => break,
+                }
+            }
+            if self.backlog.is_empty() {
+                break;
+            }
+            let unsubmitted = self.unsubmitted;
+            self.enter(0, None)?;
+            if self.unsubmitted == unsubmitted {
+                // The kernel consumed nothing, e.g. under CQ overflow. The rest
+                // waits for the next call.
+                break;
+            }
+        }
+
+        let millis = i32::from(timeout);
+        if millis == 0 || !self.cq_is_empty() {
+            return self.enter(0, None);
+        }
+        if millis < 0 {
+            return self.enter(1, None);
+        }
+        let ts = libc::timespec {
+            tv_sec: (millis / 1000) as _,
+            tv_nsec: (millis % 1000) as libc::c_long * 1_000_000,
+        };
+        self.enter(1, Some(&ts))
+    }
+
+    fn enter(&mut self, min_complete: u32, timeout: Option<&libc::timespec>) -> io::Result<()> {
+        // `GETEVENTS` even without waiting, to flush overflowed completions.
+        let mut flags = IORING_ENTER_GETEVENTS;
+        let arg;
+        let (argp, argsz) = match timeout {
+            Some(ts) => {
+                flags |= IORING_ENTER_EXT_ARG;
+                arg = GeteventsArg {
+                    sigmask: 0,
+                    sigmask_sz: 0,
+                    pad: 0,
+                    ts: ts as *const libc::timespec as u64,
+                };
+                (
+                    &arg as *const GeteventsArg as *const c_void,
+                    mem::size_of::<GeteventsArg>(),
+                )
+            }
+            None => (ptr::null(), 0),
+        };
+
+        // SAFETY: `argp` is null or points to `arg`, which outlives the call.
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd.as_raw_fd(),
+                self.unsubmitted,
+                min_complete,
+                flags,
+                argp,
+                argsz,
+            )
+        };
+        if ret < 0 {
+            let error = io::Error::last_os_error();
+            return match error.raw_os_error() {
+                Some(libc::ETIME) => Ok(()),
+                _ => Err(error),
+            };
+        }
+        self.unsubmitted -= (ret as u32).min(self.unsubmitted);
+        Ok(())
+    }
+
+    /// Moves every completion out of the completion queue into `out`.
+    fn reap(&mut self, out: &mut Vec<Cqe>) {
+        // SAFETY: The entries between head and tail were written by the kernel,
+        // and are ours until the release store of the new head.
+        unsafe {
+            let head = (*self.cq_head).load(Ordering::Relaxed);
+            let tail = (*self.cq_tail).load(Ordering::Acquire);
+            let mut i = head;
+            while i != tail {
+                out.push(self.cqes.add((i & self.cq_mask) as usize).read());
+                i = i.wrapping_add(1);
+            }
+            (*self.cq_head).store(tail, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqe_layout_matches_the_kernel() {
+        assert_eq!(mem::size_of::<Params>(), 120);
+        assert_eq!(mem::size_of::<Cqe>(), 16);
+        assert_eq!(mem::size_of::<GeteventsArg>(), 24);
+    }
+
+    #[test]
+    fn poll_mode_follows_the_interest() {
+        let fd = 3;
+        let multishot = |interest: Interest| Sqe::poll_add(fd, interest, 8).len;
+        assert_eq!(multishot(crate::interest().read()), 0);
+        assert_eq!(
+            multishot(crate::interest().read().edge_triggered()),
+            IORING_POLL_ADD_MULTI
+        );
+        assert_eq!(
+            multishot(crate::interest().read().edge_triggered().oneshot()),
+            0
+        );
+
+        let sqe = Sqe::poll_add(fd, crate::interest().read().edge_triggered(), 8);
+        assert_eq!(sqe.poll32_events, libc::EPOLLIN as u32);
+    }
+
+    #[test]
+    fn backlog_beyond_the_submission_queue() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        use nix::sys::eventfd::{EfdFlags, EventFd};
+
+        use crate::tri_subscriber::WithHandler;
+        use crate::Subscriber;
+
+        let mut ep = UringEventp::new(8).unwrap();
+        let calls = Rc::new(Cell::new(0));
+        for _ in 0..100 {
+            let efd = EventFd::from_value_and_flags(1, EfdFlags::EFD_NONBLOCK).unwrap();
+            let c = calls.clone();
+            crate::interest()
+                .read()
+                .with_fd(efd)
+                .with_handler(move |efd: &mut EventFd| {
+                    let _ = efd.read();
+                    c.set(c.get() + 1);
+                })
+                .register_into(&mut ep)
+                .unwrap();
+        }
+        assert_eq!(ep.ring.backlog.len(), 100 - ep.ring.sq_entries as usize);
+
+        while calls.get() < 100 {
+            ep.run_once_with_timeout(EpollTimeout::from(500u16))
+                .unwrap();
+        }
+        assert!(ep.ring.backlog.is_empty());
+    }
+
+    #[test]
+    fn deleted_subscriber_is_freed_once_its_poll_completes() {
+        use nix::sys::eventfd::{EfdFlags, EventFd};
+
+        use crate::tri_subscriber::WithHandler;
+        use crate::Subscriber;
+
+        let mut ep = UringEventp::default();
+        let efd = EventFd::from_flags(EfdFlags::EFD_NONBLOCK).unwrap();
+        let raw = efd.as_raw_fd();
+        crate::interest()
+            .read()
+            .edge_triggered()
+            .with_fd(efd)
+            .with_handler(|| {})
+            .register_into(&mut ep)
+            .unwrap();
+        ep.try_run_once().unwrap();
+        assert_eq!(ep.in_flight.len(), 1);
+
+        ep.delete(raw).unwrap();
+        assert_eq!(ep.zombies.len(), 1);
+        ep.run_once_with_timeout(EpollTimeout::from(500u16))
+            .unwrap();
+        assert!(ep.zombies.is_empty() && ep.in_flight.is_empty());
+    }
+
+    #[test]
+    fn failed_poll_is_returned_by_the_batch() {
+        use crate::tri_subscriber::WithHandler;
+        use crate::{Error, Subscriber};
+
+        let mut ep = UringEventp::default();
+        let (read, _write) = nix::unistd::pipe().unwrap();
+        // SAFETY: `F_DUPFD_CLOEXEC` takes no pointer. A number above those other
+        // tests open, so that it is not reused before the poll is submitted.
+        let raw = unsafe { libc::fcntl(read.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 1300) };
+        assert!(raw >= 1300, "{}", io::Error::last_os_error());
+        // SAFETY: Closed below on purpose, and never read by the handler.
+        unsafe { crate::interest().read().with_raw_fd(raw) }
+            .with_handler(|| -> () { unreachable!() })
+            .register_into(&mut ep)
+            .unwrap();
+        // SAFETY: Owned by nothing else.
+        drop(unsafe { OwnedFd::from_raw_fd(raw) });
+
+        let err = ep.try_run_once().unwrap_err();
+        assert_eq!(Error::raw_os_error(&err), Some(libc::EBADF));
+        assert!(matches!(
+            Error::from_io(&err),
+            Some(Error::Syscall { op: "IORING_OP_POLL_ADD", fd, .. }) if *fd == raw
+        ));
+        // Registered, but no longer polled.
+        assert!(ep.contains(raw) && ep.in_flight.is_empty());
+        ep.try_run_once().unwrap();
+    }
+
+    #[test]
+    fn first_handler_error_is_returned_after_the_batch() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        use nix::sys::eventfd::{EfdFlags, EventFd};
+
+        use crate::tri_subscriber::WithHandler;
+        use crate::Subscriber;
+
+        let mut ep = UringEventp::default();
+        let calls = Rc::new(Cell::new(0));
+        for _ in 0..2 {
+            let efd = EventFd::from_value_and_flags(1, EfdFlags::EFD_NONBLOCK).unwrap();
+            let c = calls.clone();
+            crate::interest()
+                .read()
+                .with_fd(efd)
+                .with_handler(move |efd: &mut EventFd| {
+                    let _ = efd.read();
+                    c.set(c.get() + 1);
+                    Err::<(), _>(io::Error::new(io::ErrorKind::Other, c.get().to_string()))
+                })
+                .register_into(&mut ep)
+                .unwrap();
+        }
+
+        let mut err = None;
+        while calls.get() < 2 {
+            err = err.or(ep.run_once_with_timeout(EpollTimeout::from(500u16)).err());
+        }
+        assert_eq!(err.unwrap().to_string(), "1");
+    }
+
+    #[test]
+    #[should_panic(expected = "Entries must be greater than zero")]
+    fn zero_entries_panics() {
+        let _ = UringEventp::new(0);
+    }
+}
@@ -1,3 +1,6 @@
+use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd};
+
 // Code from `likely_stable@0.1.3`, since `std::intrinsics::unlikely`
 // is not stable at this moment.
 pub(crate) const fn unlikely(b: bool) -> bool {
@@ -8,3 +11,20 @@ pub(crate) const fn unlikely(b: bool) -> bool {
         false
     }
 }
+
+/// Sets `O_NONBLOCK` on `fd`, unless set already.
+pub(crate) fn set_nonblocking(fd: BorrowedFd<'_>) -> io::Result<()> {
+    let fd = fd.as_raw_fd();
+    // SAFETY: `F_GETFL` and `F_SETFL` take no pointer.
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    if flags & libc::O_NONBLOCK != 0 {
+        return Ok(());
+    }
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
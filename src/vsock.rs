@@ -0,0 +1,424 @@
+//! `AF_VSOCK` stream sockets, for the communication between a virtual machine
+//! and its host.
+//!
+//! [`VsockListener`] and [`VsockStream`] are thin wrappers of the socket, in the
+//! manner of [`TcpListener`](std::net::TcpListener) and
+//! [`TcpStream`](std::net::TcpStream). The listener implements [`Accept`], so the
+//! [`acceptor`](crate::acceptor()) subscriber runs its accept loop as for any other
+//! listener, and the handlers of the connections can stay generic over the
+//! transport.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! # use std::io;
+//! use std::io::{Read, Write};
+//! use std::os::fd::AsRawFd;
+//!
+//! use eventp::vsock::{VsockAddr, VsockListener, VsockStream};
+//! use eventp::{acceptor, Eventp, EventpOps, Pinned, Subscriber};
+//!
+//! # fn main() -> io::Result<()> {
+//! let listener = VsockListener::bind(VsockAddr::new(VsockAddr::CID_ANY, 1024))?;
+//! listener.set_nonblocking(true)?;
+//!
+//! let mut eventp = Eventp::default();
+//! acceptor(listener)
+//!     .with_conn_handler(|_stream: &VsockStream, addr: VsockAddr| {
+//!         println!("connection from {addr}");
+//!         |stream: &mut VsockStream, mut eventp: Pinned<'_, Eventp>| -> io::Result<()> {
+//!             let mut buf = [0; 512];
+//!             loop {
+//!                 match stream.read(&mut buf) {
+//!                     Ok(0) => return eventp.delete(stream.as_raw_fd()),
+//!                     Ok(n) => stream.write_all(&buf[..n])?, // Send buffer omitted.
+//!                     Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+//!                     Err(_) => return eventp.delete(stream.as_raw_fd()),
+//!                 }
+//!             }
+//!         }
+//!     })
+//!     .register_into(&mut eventp)?;
+//! eventp.run_forever()
+//! # }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::{fmt, mem};
+
+use crate::net::Accept;
+
+/// The address of a vsock socket: the context id of a machine, and a port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VsockAddr {
+    cid: u32,
+    port: u32,
+}
+
+impl VsockAddr {
+    /// Binds to any context id of the machine.
+    pub const CID_ANY: u32 = libc::VMADDR_CID_ANY;
+    /// The hypervisor.
+    pub const CID_HYPERVISOR: u32 = libc::VMADDR_CID_HYPERVISOR;
+    /// The machine itself, through the loopback transport.
+    pub const CID_LOCAL: u32 = libc::VMADDR_CID_LOCAL;
+    /// The host, seen from a guest.
+    pub const CID_HOST: u32 = libc::VMADDR_CID_HOST;
+    /// Binds to any free port.
+    pub const PORT_ANY: u32 = libc::VMADDR_PORT_ANY;
+
+    /// Creates an address from a context id and a port.
+    pub const fn new(cid: u32, port: u32) -> Self {
+        Self { cid, port }
+    }
+
+    /// Returns the context id.
+    pub const fn cid(&self) -> u32 {
+        self.cid
+    }
+
+    /// Returns the port.
+    pub const fn port(&self) -> u32 {
+        self.port
+    }
+
+    fn to_raw(self) -> libc::sockaddr_vm {
+        // SAFETY: All zeroes is a valid `sockaddr_vm`, `svm_zero` included.
+        let mut raw: libc::sockaddr_vm = unsafe { mem::zeroed() };
+        raw.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+        raw.svm_cid = self.cid;
+        raw.svm_port = self.port;
+        raw
+    }
+
+    fn from_raw(raw: &libc::sockaddr_vm) -> Self {
+        Self::new(raw.svm_cid, raw.svm_port)
+    }
+}
+
+impl fmt::Display for VsockAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.cid, self.port)
+    }
+}
+
+/// A vsock socket listening for connections.
+///
+/// Set it nonblocking with [`set_nonblocking`](Self::set_nonblocking) before
+/// handing it to the [`acceptor`](crate::acceptor()), as for a `TcpListener`.
+#[derive(Debug)]
+pub struct VsockListener {
+    fd: OwnedFd,
+}
+
+impl VsockListener {
+    /// Creates a socket bound to `addr`, and listening.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from creating, binding or listening on the socket, e.g.
+    /// `EADDRNOTAVAIL` when no vsock transport serves `addr`.
+    pub fn bind(addr: VsockAddr) -> io::Result<Self> {
+        let fd = socket()?;
+        let raw = addr.to_raw();
+        // SAFETY: `raw` is a valid `sockaddr_vm` of the given length.
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                (&raw as *const libc::sockaddr_vm).cast(),
+                mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `listen` takes no pointer.
+        if unsafe { libc::listen(fd.as_raw_fd(), libc::SOMAXCONN) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd })
+    }
+
+    /// Returns the address the socket is bound to, with the port picked by the
+    /// kernel if bound to [`VsockAddr::PORT_ANY`].
+    pub fn local_addr(&self) -> io::Result<VsockAddr> {
+        sock_name(self.fd.as_fd(), libc::getsockname)
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        set_nonblocking(self.fd.as_fd(), nonblocking)
+    }
+}
+
+impl Accept for VsockListener {
+    type Stream = VsockStream;
+    type Addr = VsockAddr;
+
+    fn accept(&self) -> io::Result<(VsockStream, VsockAddr)> {
+        // SAFETY: All zeroes is a valid `sockaddr_vm`.
+        let mut raw: libc::sockaddr_vm = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+        // SAFETY: `raw` is writable for `len` bytes.
+        let fd = unsafe {
+            libc::accept4(
+                self.fd.as_raw_fd(),
+                (&mut raw as *mut libc::sockaddr_vm).cast(),
+                &mut len,
+                libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: Just accepted, and owned by nothing else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok((VsockStream { fd }, VsockAddr::from_raw(&raw)))
+    }
+}
+
+/// A connected vsock stream socket.
+///
+/// Writes never raise `SIGPIPE`: writing to a connection shut down by the peer
+/// fails with [`io::ErrorKind::BrokenPipe`] instead.
+#[derive(Debug)]
+pub struct VsockStream {
+    fd: OwnedFd,
+}
+
+impl VsockStream {
+    /// Connects to `addr`, blocking until connected.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from creating or connecting the socket.
+    pub fn connect(addr: VsockAddr) -> io::Result<Self> {
+        let fd = socket()?;
+        let raw = addr.to_raw();
+        // SAFETY: `raw` is a valid `sockaddr_vm` of the given length.
+        let ret = unsafe {
+            libc::connect(
+                fd.as_raw_fd(),
+                (&raw as *const libc::sockaddr_vm).cast(),
+                mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd })
+    }
+
+    /// Returns the address of the local end.
+    pub fn local_addr(&self) -> io::Result<VsockAddr> {
+        sock_name(self.fd.as_fd(), libc::getsockname)
+    }
+
+    /// Returns the address of the peer.
+    pub fn peer_addr(&self) -> io::Result<VsockAddr> {
+        sock_name(self.fd.as_fd(), libc::getpeername)
+    }
+
+    /// Shuts down the read half, the write half, or both.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        let how = match how {
+            Shutdown::Read => libc::SHUT_RD,
+            Shutdown::Write => libc::SHUT_WR,
+            Shutdown::Both => libc::SHUT_RDWR,
+        };
+        // SAFETY: `shutdown` takes no pointer.
+        if unsafe { libc::shutdown(self.fd.as_raw_fd(), how) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        set_nonblocking(self.fd.as_fd(), nonblocking)
+    }
+}
+
+impl Read for VsockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Read for &VsockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // SAFETY: `buf` is writable for its length.
+        let n = unsafe { libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+}
+
+impl Write for VsockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for &VsockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // SAFETY: `buf` is readable for its length.
+        let n = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                buf.as_ptr().cast(),
+                buf.len(),
+                libc::MSG_NOSIGNAL,
+            )
+        };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_fd_traits {
+    ($($ty:ty),*) => {$(
+        impl AsFd for $ty {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.fd.as_fd()
+            }
+        }
+
+        impl AsRawFd for $ty {
+            fn as_raw_fd(&self) -> RawFd {
+                self.fd.as_raw_fd()
+            }
+        }
+
+        /// Takes ownership of an `AF_VSOCK` socket of type `SOCK_STREAM`.
+        impl From<OwnedFd> for $ty {
+            fn from(fd: OwnedFd) -> Self {
+                Self { fd }
+            }
+        }
+
+        impl From<$ty> for OwnedFd {
+            fn from(socket: $ty) -> Self {
+                socket.fd
+            }
+        }
+    )*};
+}
+
+impl_fd_traits!(VsockListener, VsockStream);
+
+fn socket() -> io::Result<OwnedFd> {
+    // SAFETY: `socket` takes no pointer.
+    let fd = unsafe { libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: Just opened, and owned by nothing else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+type SockNameFn =
+    unsafe extern "C" fn(libc::c_int, *mut libc::sockaddr, *mut libc::socklen_t) -> libc::c_int;
+
+/// Runs `getsockname` or `getpeername` on `fd`.
+fn sock_name(fd: BorrowedFd<'_>, f: SockNameFn) -> io::Result<VsockAddr> {
+    // SAFETY: All zeroes is a valid `sockaddr_vm`.
+    let mut raw: libc::sockaddr_vm = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+    // SAFETY: `raw` is writable for `len` bytes.
+    if unsafe {
+        f(
+            fd.as_raw_fd(),
+            (&mut raw as *mut libc::sockaddr_vm).cast(),
+            &mut len,
+        )
+    } < 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(VsockAddr::from_raw(&raw))
+}
+
+fn set_nonblocking(fd: BorrowedFd<'_>, nonblocking: bool) -> io::Result<()> {
+    let mut value = libc::c_int::from(nonblocking);
+    // SAFETY: `value` is a valid `c_int` for `FIONBIO`.
+    if unsafe { libc::ioctl(fd.as_raw_fd(), libc::FIONBIO, &mut value) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Binds on the loopback transport, or returns `None` where the kernel has no
+    /// vsock support, or no loopback transport.
+    fn loopback_listener() -> Option<VsockListener> {
+        match VsockListener::bind(VsockAddr::new(VsockAddr::CID_LOCAL, VsockAddr::PORT_ANY)) {
+            Ok(listener) => Some(listener),
+            Err(e)
+                if matches!(
+                    e.raw_os_error(),
+                    Some(libc::EAFNOSUPPORT | libc::EADDRNOTAVAIL | libc::ENODEV)
+                ) =>
+            {
+                None
+            }
+            Err(e) => panic!("bind failed: {e}"),
+        }
+    }
+
+    #[test]
+    fn addr_roundtrips_through_sockaddr_vm() {
+        let addr = VsockAddr::new(3, 1024);
+        let raw = addr.to_raw();
+        assert_eq!(raw.svm_family, libc::AF_VSOCK as libc::sa_family_t);
+        assert_eq!(VsockAddr::from_raw(&raw), addr);
+        assert_eq!(addr.to_string(), "3:1024");
+    }
+
+    #[test]
+    fn accepts_over_loopback_nonblocking() {
+        let Some(listener) = loopback_listener() else {
+            return;
+        };
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_ne!(addr.port(), VsockAddr::PORT_ANY);
+        let err = listener.accept().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        let mut client = VsockStream::connect(addr).unwrap();
+        client.write_all(b"ping").unwrap();
+        let (mut server, peer) = loop {
+            match listener.accept() {
+                Ok(accepted) => break accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::yield_now(),
+                Err(e) => panic!("accept failed: {e}"),
+            }
+        };
+        assert_eq!(peer, client.local_addr().unwrap());
+
+        let mut buf = [0; 4];
+        server.set_nonblocking(false).unwrap();
+        server.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+        server.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(client.read(&mut buf).unwrap(), 0);
+    }
+}
@@ -0,0 +1,231 @@
+use std::cell::Cell;
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::sync::Arc;
+
+use nix::errno::Errno;
+use nix::sys::eventfd::{EfdFlags, EventFd};
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::thin::ThinBoxSubscriber;
+use crate::{interest, Event, EventpOps, EventpOpsAdd, Interest, Pinned};
+
+/// Wakes an `Eventp` from another thread, e.g. to make it notice a flag change.
+///
+/// A lighter alternative to `remote_endpoint`, behind the `remote-endpoint`
+/// feature, when there is nothing to send: it registers an `eventfd` whose handler
+/// drains it, and optionally runs a callback on the loop thread. Wakeups that
+/// happen before the loop gets to the `eventfd` are coalesced, so the callback
+/// runs once for any number of them.
+///
+/// `Waker` is cheap to clone and is both `Send` and `Sync`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::io;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// use eventp::{Eventp, Waker};
+///
+/// # fn main() -> io::Result<()> {
+/// let mut eventp = Eventp::default();
+/// let waker = Waker::new(&mut eventp)?;
+/// let stop = Arc::new(AtomicBool::new(false));
+///
+/// let s = stop.clone();
+/// thread::spawn(move || {
+///     s.store(true, Ordering::Release);
+///     waker.wake()
+/// });
+///
+/// while !stop.load(Ordering::Acquire) {
+///     eventp.run_once()?;
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Waker {
+    eventfd: Arc<EventFd>,
+}
+
+/// The loop-side end of a [`Waker`].
+struct WakerSubscriber<F> {
+    eventfd: Arc<EventFd>,
+    interest: Cell<Interest>,
+    on_wake: F,
+}
+
+impl Waker {
+    /// Creates a `Waker` whose wakeups only make the loop return from waiting.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from creating the `eventfd` or registering it.
+    pub fn new<Ep, R>(eventp: &mut R) -> io::Result<Self>
+    where
+        Ep: EventpOps,
+        R: EventpOpsAdd<Ep>,
+    {
+        Self::with_callback(eventp, |_: Pinned<'_, Ep>| {})
+    }
+
+    /// Creates a `Waker` whose wakeups run `on_wake` on the loop thread.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`new`](Self::new).
+    pub fn with_callback<Ep, R, F>(eventp: &mut R, on_wake: F) -> io::Result<Self>
+    where
+        Ep: EventpOps,
+        R: EventpOpsAdd<Ep>,
+        F: FnMut(Pinned<'_, Ep>) + 'static,
+    {
+        let eventfd = EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)
+            .map_err(io::Error::from)?;
+        let eventfd = Arc::new(eventfd);
+
+        eventp.add(ThinBoxSubscriber::new(WakerSubscriber {
+            eventfd: Arc::clone(&eventfd),
+            interest: Cell::new(interest().read()),
+            on_wake,
+        }))?;
+
+        Ok(Self { eventfd })
+    }
+
+    /// Wakes the loop, if it is not already woken.
+    ///
+    /// # Errors
+    ///
+    /// Forwards any error from writing the `eventfd`.
+    pub fn wake(&self) -> io::Result<()> {
+        match self.eventfd.write(1) {
+            // The counter is saturated, so the loop is woken already.
+            Ok(_) | Err(Errno::EAGAIN) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl<F> AsFd for WakerSubscriber<F> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.eventfd.as_fd()
+    }
+}
+
+impl<F> HasInterest for WakerSubscriber<F> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, F> Handler<Ep> for WakerSubscriber<F>
+where
+    Ep: EventpOps,
+    F: FnMut(Pinned<'_, Ep>),
+{
+    fn handle(&mut self, _event: Event, eventp: Pinned<'_, Ep>) {
+        // Resets the counter, coalescing every wakeup so far into this one.
+        let _ = self.eventfd.read();
+        (self.on_wake)(eventp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Barrier;
+    use std::thread;
+
+    use nix::sys::epoll::EpollTimeout;
+
+    use super::*;
+    use crate::Eventp;
+
+    const fn assert_send_sync<T: Send + Sync>() {}
+    const _: () = assert_send_sync::<Waker>();
+
+    fn counting_waker(eventp: &mut Eventp) -> (Waker, Rc<Cell<u32>>) {
+        let wakes = Rc::new(Cell::new(0));
+        let w = wakes.clone();
+        let waker =
+            Waker::with_callback(eventp, move |_: Pinned<'_, Eventp>| w.set(w.get() + 1)).unwrap();
+        (waker, wakes)
+    }
+
+    #[test]
+    fn wakes_before_run_are_coalesced() {
+        let mut eventp = Eventp::default();
+        let (waker, wakes) = counting_waker(&mut eventp);
+
+        waker.wake().unwrap();
+        waker.clone().wake().unwrap();
+        eventp.run_once().unwrap();
+        assert_eq!(wakes.get(), 1);
+
+        eventp.try_run_once().unwrap();
+        assert_eq!(wakes.get(), 1);
+    }
+
+    #[test]
+    fn wake_from_another_thread_returns_run_once() {
+        let mut eventp = Eventp::default();
+        let waker = Waker::new(&mut eventp).unwrap();
+
+        let t = thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(20));
+            waker.wake().unwrap();
+        });
+        // Blocks without a timeout: only the wakeup can return it.
+        eventp.run_once().unwrap();
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn concurrent_wake_storm() {
+        const THREADS: usize = 8;
+        const WAKES: u32 = 1000;
+
+        let mut eventp = Eventp::default();
+        let (waker, wakes) = counting_waker(&mut eventp);
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let threads: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let (waker, barrier) = (waker.clone(), barrier.clone());
+                thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..WAKES {
+                        waker.wake().unwrap();
+                    }
+                })
+            })
+            .collect();
+        let joiner = {
+            let done = done.clone();
+            thread::spawn(move || {
+                threads.into_iter().for_each(|t| t.join().unwrap());
+                done.store(true, Ordering::Release);
+            })
+        };
+
+        while !done.load(Ordering::Acquire) {
+            eventp
+                .run_once_with_timeout(EpollTimeout::from(10u16))
+                .unwrap();
+        }
+        joiner.join().unwrap();
+        // Pick up the wakeups that raced with `done`.
+        eventp.try_run_once().unwrap();
+
+        let after_storm = wakes.get();
+        assert!((1..=THREADS as u32 * WAKES).contains(&after_storm));
+        eventp.try_run_once().unwrap();
+        assert_eq!(wakes.get(), after_storm);
+    }
+}
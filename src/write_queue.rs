@@ -0,0 +1,521 @@
+//! Queued writes, with `EPOLLOUT` requested only while bytes are left over.
+//!
+//! A [`WriteQueue`] owns a stream and the bytes still to be written to it. Bytes are
+//! [queued](WriteQueue::queue) without writing, then [`on_writable`](WriteQueue::on_writable)
+//! writes as much as the stream takes. If it would block, `EPOLLOUT` is added to the
+//! interest of the fd, through [`try_modify`](crate::EventpOps::try_modify), and
+//! removed again once everything was written, so that the loop is not woken up by a
+//! writable socket with nothing to write.
+//!
+//! [`buffered_writer()`] wraps a stream into a subscriber reading it, handing every
+//! chunk read to its handler along with the queue, and writing what the handler
+//! queued.
+//!
+//! # Examples
+//!
+//! An echo server, whose connections are never written to faster than their peer
+//! reads:
+//!
+//! ```rust
+//! # use std::io;
+//! use std::os::unix::net::UnixStream;
+//!
+//! use eventp::write_queue::{self, WriteQueue};
+//! use eventp::{Eventp, Pinned, Subscriber};
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut eventp = Eventp::default();
+//! let (stream, _peer) = UnixStream::pair()?;
+//! stream.set_nonblocking(true)?;
+//! write_queue::buffered_writer(stream)
+//!     .with_read_handler(
+//!         |bytes: &[u8], queue: &mut WriteQueue<UnixStream>, _: Pinned<'_, Eventp>| {
+//!             queue.queue(bytes);
+//!         },
+//!     )
+//!     .register_into(&mut eventp)?;
+//! # Ok(()) }
+//! ```
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io::{self, IoSlice, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+
+use crate::subscriber::{Handler, HasInterest};
+use crate::tri_subscriber::HandlerReturn;
+use crate::{interest, Event, EventpOps, Interest, Pinned};
+
+/// The number of buffers written at once, with writev(2).
+const MAX_SLICES: usize = 64;
+
+/// The size of the buffer [`Subscriber`] reads into.
+const READ_BUF_LEN: usize = 4096;
+
+/// What [`WriteQueue::on_writable`] achieved.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Progress {
+    /// Everything queued was written, and `EPOLLOUT` is no longer requested.
+    Drained,
+
+    /// The stream would block with `remaining` bytes still queued, and `EPOLLOUT`
+    /// is requested, to call [`on_writable`](WriteQueue::on_writable) again once
+    /// it is reported.
+    Blocked {
+        /// The number of bytes still queued.
+        remaining: usize,
+    },
+}
+
+/// A stream, and the bytes queued to be written to it. See the
+/// [module level docs](self).
+#[derive(Debug)]
+pub struct WriteQueue<Fd> {
+    fd: Fd,
+    bufs: VecDeque<Vec<u8>>,
+    /// The number of bytes of the front buffer already written.
+    offset: usize,
+    /// The number of bytes queued and not yet written.
+    len: usize,
+    /// The interest of the fd while nothing is left over, without `EPOLLOUT`.
+    interest: Interest,
+    /// Whether `EPOLLOUT` was added to `interest` for the fd.
+    writing: bool,
+}
+
+impl<Fd: AsFd + Write> WriteQueue<Fd> {
+    /// Creates an empty queue for `fd`, registered with `interest` while nothing is
+    /// left over. `EPOLLOUT` is removed from it, as the queue adds it as needed.
+    pub fn new(fd: Fd, interest: Interest) -> Self {
+        Self {
+            fd,
+            bufs: VecDeque::new(),
+            offset: 0,
+            len: 0,
+            interest: interest.remove_write(),
+            writing: false,
+        }
+    }
+
+    /// Queues `bytes` after those already queued, without writing them. Empty ones
+    /// are ignored.
+    pub fn queue(&mut self, bytes: impl Into<Vec<u8>>) {
+        let bytes = bytes.into();
+        if bytes.is_empty() {
+            return;
+        }
+        self.len += bytes.len();
+        self.bufs.push_back(bytes);
+    }
+
+    /// Writes as much of the queue as the stream takes, then requests `EPOLLOUT`
+    /// if bytes are left over, or stops requesting it if none are, with
+    /// [`try_modify`](crate::EventpOps::try_modify) on a change only.
+    ///
+    /// To be called once `EPOLLOUT` is reported, and after queueing bytes, to
+    /// write them right away if the stream takes them.
+    ///
+    /// # Errors
+    ///
+    /// - The error of writing, but `EINTR`, which is retried, and `EAGAIN`. E.g.
+    ///   [`io::ErrorKind::BrokenPipe`] or [`io::ErrorKind::ConnectionReset`] once
+    ///   the peer is gone. What was not written stays queued.
+    /// - [`io::ErrorKind::WriteZero`] if the stream took none of the bytes.
+    /// - The error of modifying the interest, see
+    ///   [`modify`](crate::EventpOps::modify).
+    pub fn on_writable<Ep: EventpOps>(
+        &mut self,
+        mut eventp: Pinned<'_, Ep>,
+    ) -> io::Result<Progress> {
+        let progress = self.write_out()?;
+        let writing = progress != Progress::Drained;
+        if writing != self.writing {
+            let interest = if writing {
+                self.interest.write()
+            } else {
+                self.interest
+            };
+            eventp.try_modify(self.fd.as_fd().as_raw_fd(), interest)?;
+            self.writing = writing;
+        }
+        Ok(progress)
+    }
+
+    /// Writes as much of the queue as the stream takes, leaving the interest as is.
+    fn write_out(&mut self) -> io::Result<Progress> {
+        while !self.bufs.is_empty() {
+            let mut slices = [IoSlice::new(&[]); MAX_SLICES];
+            let mut count = 0;
+            for (slot, buf) in slices.iter_mut().zip(&self.bufs) {
+                *slot = IoSlice::new(if count == 0 { &buf[self.offset..] } else { buf });
+                count += 1;
+            }
+            match self.fd.write_vectored(&slices[..count]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the queued bytes",
+                    ))
+                }
+                Ok(n) => self.consume(n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Ok(Progress::Blocked {
+                        remaining: self.len,
+                    })
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Progress::Drained)
+    }
+
+    /// Drops the first `n` bytes of the queue, just written.
+    fn consume(&mut self, mut n: usize) {
+        self.len -= n;
+        while let Some(front) = self.bufs.front() {
+            let left = front.len() - self.offset;
+            if n < left {
+                self.offset += n;
+                return;
+            }
+            n -= left;
+            self.offset = 0;
+            self.bufs.pop_front();
+        }
+    }
+}
+
+impl<Fd> WriteQueue<Fd> {
+    /// Returns the number of bytes queued and not yet written.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if everything queued was written.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether `EPOLLOUT` is requested, as bytes were left over by the last
+    /// [`on_writable`](Self::on_writable).
+    pub fn is_writing(&self) -> bool {
+        self.writing
+    }
+
+    /// Returns a reference to the stream.
+    pub fn get_ref(&self) -> &Fd {
+        &self.fd
+    }
+
+    /// Returns a mutable reference to the stream. Writing to it directly bypasses
+    /// the queue, and may reorder the bytes written.
+    pub fn get_mut(&mut self) -> &mut Fd {
+        &mut self.fd
+    }
+}
+
+/// Creates a [`BufferedWriter`] over a nonblocking stream, such as a
+/// [`TcpStream`](std::net::TcpStream) or a
+/// [`UnixStream`](std::os::unix::net::UnixStream), registered with
+/// [`Interest::stream_read`].
+///
+/// When the stream is readable, the subscriber reads it until it would block,
+/// calling the handler with every chunk read, then writes what the handler queued.
+/// When it is writable, the subscriber writes the queue, see
+/// [`WriteQueue::on_writable`]. Once the peer has closed its end, the subscriber
+/// writes what is left of the queue, and deletes itself.
+pub fn buffered_writer<Fd: AsFd + Read + Write>(stream: Fd) -> BufferedWriter<Fd> {
+    BufferedWriter { stream }
+}
+
+/// A not yet complete buffered writer, waiting for its handler.
+pub struct BufferedWriter<Fd> {
+    stream: Fd,
+}
+
+impl<Fd: AsFd + Read + Write> BufferedWriter<Fd> {
+    /// Completes the buffered writer with the handler called for every chunk read,
+    /// with the queue of the stream, e.g. to reply.
+    ///
+    /// The handler returns either `()` or `io::Result<()>`. After an error, what is
+    /// left to read is left for the next time the loop polls.
+    pub fn with_read_handler<F>(self, handler: F) -> Subscriber<Fd, F> {
+        let interest = Interest::stream_read();
+        Subscriber {
+            queue: WriteQueue::new(self.stream, interest),
+            interest: Cell::new(interest),
+            read_closed: false,
+            handler,
+        }
+    }
+}
+
+/// The subscriber created by [`BufferedWriter::with_read_handler`].
+pub struct Subscriber<Fd, F> {
+    queue: WriteQueue<Fd>,
+    interest: Cell<Interest>,
+    /// Whether the peer has closed its end, so that only the queue is left to write.
+    read_closed: bool,
+    handler: F,
+}
+
+impl<Fd, F> Subscriber<Fd, F> {
+    /// Returns a reference to the queue of the stream.
+    pub fn queue(&self) -> &WriteQueue<Fd> {
+        &self.queue
+    }
+}
+
+impl<Fd: AsFd + Read + Write, F> Subscriber<Fd, F> {
+    /// Reads the stream until it would block, or the end of file, calling the
+    /// handler with every chunk. Stops at the first error of the handler, which is
+    /// returned as the outer one, and the inner one is that of reading.
+    fn read<Ep, R>(&mut self, mut eventp: Pinned<'_, Ep>) -> io::Result<io::Result<()>>
+    where
+        Ep: EventpOps,
+        F: FnMut(&[u8], &mut WriteQueue<Fd>, Pinned<'_, Ep>) -> R,
+        R: HandlerReturn,
+    {
+        let mut buf = [0; READ_BUF_LEN];
+        loop {
+            match self.queue.fd.read(&mut buf) {
+                Ok(0) => {
+                    self.read_closed = true;
+                    return Ok(Ok(()));
+                }
+                Ok(n) => {
+                    (self.handler)(&buf[..n], &mut self.queue, eventp.as_mut()).into_result()?
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Ok(())),
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+    }
+
+    /// Reads and writes the stream, and returns the result of the handler as the
+    /// outer one, and that of reading and writing as the inner one.
+    fn handle_stream<Ep, R>(
+        &mut self,
+        event: Event,
+        mut eventp: Pinned<'_, Ep>,
+    ) -> io::Result<io::Result<()>>
+    where
+        Ep: EventpOps,
+        F: FnMut(&[u8], &mut WriteQueue<Fd>, Pinned<'_, Ep>) -> R,
+        R: HandlerReturn,
+    {
+        if !self.read_closed && (event.is_readable() || event.is_closed()) {
+            if let Err(e) = self.read(eventp.as_mut())? {
+                return Ok(Err(e));
+            }
+        }
+        let fd = self.queue.fd.as_fd().as_raw_fd();
+        if !self.read_closed {
+            // Also writes what the handler just queued, without waiting for `EPOLLOUT`.
+            if event.is_writable() || !self.queue.is_empty() {
+                self.queue.on_writable(eventp)?;
+            }
+            return Ok(Ok(()));
+        }
+
+        // The fd stays readable at the end of file, so only `EPOLLOUT` is left.
+        match self.queue.write_out() {
+            Ok(Progress::Drained) => eventp.delete(fd)?,
+            Ok(Progress::Blocked { .. }) => {
+                eventp.try_modify(fd, interest().write())?;
+                self.queue.writing = true;
+            }
+            Err(e) => return Ok(Err(e)),
+        }
+        Ok(Ok(()))
+    }
+}
+
+impl<Fd: AsFd, F> AsFd for Subscriber<Fd, F> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.queue.fd.as_fd()
+    }
+}
+
+impl<Fd, F> HasInterest for Subscriber<Fd, F> {
+    fn interest(&self) -> &Cell<Interest> {
+        &self.interest
+    }
+}
+
+impl<Ep, Fd, F, R> Handler<Ep> for Subscriber<Fd, F>
+where
+    Ep: EventpOps,
+    Fd: AsFd + Read + Write,
+    F: FnMut(&[u8], &mut WriteQueue<Fd>, Pinned<'_, Ep>) -> R,
+    R: HandlerReturn,
+{
+    fn handle(&mut self, event: Event, eventp: Pinned<'_, Ep>) {
+        // The error, if any, can only be observed through `try_handle`.
+        let _ = self.try_handle(event, eventp);
+    }
+
+    /// # Errors
+    ///
+    /// - The error of the handler, leaving the subscriber registered.
+    /// - The error of reading or writing the stream, e.g.
+    ///   [`io::ErrorKind::ConnectionReset`], after deleting the subscriber.
+    /// - The error of modifying or deleting the fd.
+    fn try_handle(&mut self, event: Event, mut eventp: Pinned<'_, Ep>) -> io::Result<()> {
+        let result = self.handle_stream(event, eventp.as_mut())?;
+        if result.is_err() {
+            eventp.delete(self.queue.fd.as_fd().as_raw_fd())?;
+        }
+        result
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+    use crate::epoll::EpollTimeout;
+    use crate::{Eventp, Subscriber as _};
+
+    const REPLY_LEN: usize = 1 << 20;
+
+    /// A nonblocking pair, whose buffers are much smaller than `REPLY_LEN`.
+    fn stream_pair() -> (UnixStream, UnixStream) {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        ours.set_nonblocking(true).unwrap();
+        theirs.set_nonblocking(true).unwrap();
+        (ours, theirs)
+    }
+
+    fn read_available(stream: &mut UnixStream, into: &mut Vec<u8>) {
+        let mut buf = [0; READ_BUF_LEN];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return,
+                Ok(n) => into.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    fn reply() -> Vec<u8> {
+        (0..REPLY_LEN).map(|i| i as u8).collect()
+    }
+
+    /// Registers `stream`, replying to every chunk with `reply()`.
+    fn register_replier(eventp: &mut Eventp, stream: UnixStream) {
+        buffered_writer(stream)
+            .with_read_handler(
+                |_: &[u8], queue: &mut WriteQueue<UnixStream>, _: Pinned<'_, Eventp>| {
+                    queue.queue(reply());
+                },
+            )
+            .register_into(eventp)
+            .unwrap();
+    }
+
+    fn timeout() -> EpollTimeout {
+        EpollTimeout::from(500u16)
+    }
+
+    #[test]
+    fn writes_in_order_across_partial_writes_and_ignores_empty_bytes() {
+        let (ours, mut theirs) = stream_pair();
+        let mut queue = WriteQueue::new(ours, interest().read_write());
+        assert_eq!(queue.interest, interest().read());
+        queue.queue(b"ab".as_slice());
+        queue.queue(Vec::new());
+        queue.queue(reply());
+        queue.queue(b"yz".as_slice());
+        assert_eq!(queue.len(), REPLY_LEN + 4);
+        assert_eq!(queue.bufs.len(), 3);
+
+        let mut received = Vec::new();
+        loop {
+            let progress = queue.write_out().unwrap();
+            read_available(&mut theirs, &mut received);
+            match progress {
+                Progress::Drained => break,
+                Progress::Blocked { remaining } => assert_eq!(remaining, queue.len()),
+            }
+        }
+        assert!(queue.is_empty());
+        let mut expected = b"ab".to_vec();
+        expected.extend(reply());
+        expected.extend(b"yz");
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn a_stalled_reader_fills_the_buffer_then_drains_it() {
+        let mut eventp = Eventp::default();
+        let (ours, mut theirs) = stream_pair();
+        let raw = ours.as_raw_fd();
+        register_replier(&mut eventp, ours);
+
+        theirs.write_all(b"go").unwrap();
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        assert_eq!(eventp.interest(&raw), Some(Interest::stream_read().write()));
+
+        // Not reported writable while the peer reads nothing.
+        eventp.run_once_with_timeout(EpollTimeout::ZERO).unwrap();
+        assert_eq!(eventp.stats().events_dispatched, 1);
+
+        let mut received = Vec::new();
+        while received.len() < REPLY_LEN {
+            read_available(&mut theirs, &mut received);
+            eventp.run_once_with_timeout(timeout()).unwrap();
+        }
+        read_available(&mut theirs, &mut received);
+        assert_eq!(received, reply());
+        assert_eq!(eventp.interest(&raw), Some(Interest::stream_read()));
+    }
+
+    #[test]
+    fn the_queue_is_written_out_after_the_peer_closes_its_end() {
+        let mut eventp = Eventp::default();
+        let (ours, mut theirs) = stream_pair();
+        let raw = ours.as_raw_fd();
+        register_replier(&mut eventp, ours);
+
+        theirs.write_all(b"go").unwrap();
+        theirs.shutdown(Shutdown::Write).unwrap();
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        // Only writable, as the end of file would always be readable.
+        assert_eq!(eventp.interest(&raw), Some(interest().write()));
+
+        let mut received = Vec::new();
+        while eventp.contains(raw) {
+            read_available(&mut theirs, &mut received);
+            eventp.run_once_with_timeout(timeout()).unwrap();
+        }
+        read_available(&mut theirs, &mut received);
+        assert_eq!(received, reply());
+    }
+
+    #[test]
+    fn a_write_error_deletes_the_subscriber() {
+        let mut eventp = Eventp::default();
+        let (ours, mut theirs) = stream_pair();
+        let raw = ours.as_raw_fd();
+        register_replier(&mut eventp, ours);
+
+        theirs.write_all(b"go").unwrap();
+        eventp.run_once_with_timeout(timeout()).unwrap();
+        drop(theirs);
+        let err = eventp.run_once_with_timeout(timeout()).unwrap_err();
+        assert!(
+            matches!(
+                err.kind(),
+                io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset
+            ),
+            "{err}"
+        );
+        assert!(!eventp.contains(raw));
+    }
+}
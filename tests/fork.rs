@@ -0,0 +1,100 @@
+//! Rebuilds a loop in the child of a `fork`, with `Eventp::prepare_fork`.
+//!
+//! Without the test harness, so that the process forks with a single thread.
+
+use std::cell::Cell;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+use eventp::epoll::EpollTimeout;
+use eventp::tri_subscriber::{RawFdSource, WithHandler};
+use eventp::{interest, Eventp, ForkBlueprint, Subscriber};
+use nix::sys::eventfd::{EfdFlags, EventFd};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{fork, ForkResult};
+
+fn main() {
+    let mut eventp = Eventp::default();
+    let efd = EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).unwrap();
+    let raw = efd.as_fd().as_raw_fd();
+    let handled = Rc::new(Cell::new(0));
+    let h = handled.clone();
+    interest()
+        .read()
+        .with_fd(efd)
+        .with_handler(move |efd: &mut EventFd| {
+            let _ = efd.read();
+            h.set(h.get() + 1);
+        })
+        .named("counter")
+        .register_into(&mut eventp)
+        .unwrap();
+
+    let blueprint = eventp.prepare_fork();
+    assert_eq!(
+        blueprint.registrations(),
+        [(raw, interest().read(), Some("counter"))]
+    );
+
+    match unsafe { fork() }.unwrap() {
+        ForkResult::Child => {
+            let ok = panic::catch_unwind(AssertUnwindSafe(|| child(eventp, blueprint, raw)));
+            // Skips the destructors and atexit handlers of the parent's copy.
+            unsafe { libc::_exit(if matches!(ok, Ok(true)) { 0 } else { 1 }) }
+        }
+        ForkResult::Parent { child } => {
+            assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Exited(child, 0));
+        }
+    }
+
+    // The loop of the parent is unaffected by the child's.
+    let efd = unsafe { BorrowedFd::borrow_raw(raw) };
+    nix::unistd::write(efd, &1u64.to_ne_bytes()).unwrap();
+    eventp
+        .run_once_with_timeout(EpollTimeout::from(1000u16))
+        .unwrap();
+    assert_eq!(handled.get(), 1);
+    println!("fork: ok");
+}
+
+fn child(mut inherited: Eventp, blueprint: ForkBlueprint, raw: RawFd) -> bool {
+    // The inherited loop would change the interest list of the parent.
+    if cfg!(debug_assertions) {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let used = panic::catch_unwind(AssertUnwindSafe(|| inherited.try_run_once()));
+        panic::set_hook(hook);
+        if used.is_ok() {
+            return false;
+        }
+    }
+    mem::forget(inherited);
+
+    let handled = Rc::new(Cell::new(0));
+    let h = handled.clone();
+    let mut eventp = blueprint
+        .rebuild_in_child(|fd, interest| {
+            let h = h.clone();
+            // SAFETY: The inherited loop, which owns the fd, is forgotten.
+            unsafe { interest.with_raw_fd(fd) }
+                .with_handler(move |fd: &mut RawFdSource| {
+                    let mut buf = [0; 8];
+                    let _ = nix::unistd::read(fd.as_fd(), &mut buf);
+                    h.set(h.get() + 1);
+                })
+                .into()
+        })
+        .unwrap();
+    if eventp.dump() != [(raw, interest().read(), Some("counter"))] {
+        return false;
+    }
+
+    let efd = unsafe { BorrowedFd::borrow_raw(raw) };
+    nix::unistd::write(efd, &1u64.to_ne_bytes()).unwrap();
+    eventp
+        .run_once_with_timeout(EpollTimeout::from(1000u16))
+        .unwrap();
+    handled.get() == 1
+}
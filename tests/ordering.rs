@@ -0,0 +1,155 @@
+//! The order in which events are dispatched, see `DispatchOrder`.
+//!
+//! Every test writes to pipes in a known sequence, and records the order their
+//! handlers run in.
+
+use std::cell::RefCell;
+use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+use std::rc::Rc;
+
+use eventp::epoll::EpollTimeout;
+use eventp::tri_subscriber::WithHandler;
+use eventp::{interest, DispatchOrder, Eventp, Interest, Subscriber};
+
+type Log = Rc<RefCell<Vec<usize>>>;
+
+fn pipe() -> (OwnedFd, OwnedFd) {
+    let mut fds = [0; 2];
+    // SAFETY: `pipe2` writes two fresh fds into `fds` on success, owned here.
+    unsafe {
+        assert_eq!(
+            libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK),
+            0
+        );
+        (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]))
+    }
+}
+
+/// Edge-triggered, as a level-triggered fd keeps the place of its last event in
+/// the ready list of the kernel, whatever the order it is written to next.
+fn edge() -> Interest {
+    interest().read().edge_triggered()
+}
+
+/// Registers the read ends of `interests.len()` pipes, whose handlers drain them
+/// and log their index, and returns the write ends.
+fn register_pipes(eventp: &mut Eventp, interests: &[Interest], log: &Log) -> Vec<OwnedFd> {
+    interests
+        .iter()
+        .enumerate()
+        .map(|(index, &interest)| {
+            let (reader, writer) = pipe();
+            let log = log.clone();
+            interest
+                .with_fd(reader)
+                .with_handler(move |fd: &mut OwnedFd| {
+                    let mut buf = [0; 64];
+                    while nix::unistd::read(fd.as_fd(), &mut buf).is_ok_and(|n| n > 0) {}
+                    log.borrow_mut().push(index);
+                })
+                .register_into(eventp)
+                .unwrap();
+            writer
+        })
+        .collect()
+}
+
+fn write(writers: &[OwnedFd], order: &[usize]) {
+    for &index in order {
+        nix::unistd::write(&writers[index], b"x").unwrap();
+    }
+}
+
+fn wait() -> EpollTimeout {
+    EpollTimeout::from(1000u16)
+}
+
+#[test]
+fn a_batch_is_dispatched_in_kernel_order() {
+    let mut eventp = Eventp::default();
+    let log = Log::default();
+    let writers = register_pipes(&mut eventp, &[edge(); 5], &log);
+
+    for order in [[3, 0, 4, 1, 2], [1, 2, 3, 4, 0]] {
+        write(&writers, &order);
+        eventp.run_once_with_timeout(wait()).unwrap();
+        assert_eq!(*log.borrow(), order);
+        log.borrow_mut().clear();
+    }
+}
+
+#[test]
+fn priorities_are_ignored_in_kernel_order() {
+    let mut eventp = Eventp::builder()
+        .dispatch_order(DispatchOrder::KernelOrder)
+        .build()
+        .unwrap();
+    let log = Log::default();
+    let interests = [0, 255, 7].map(|priority| interest().read().dispatch_priority(priority));
+    let writers = register_pipes(&mut eventp, &interests, &log);
+
+    write(&writers, &[1, 2, 0]);
+    eventp.run_once_with_timeout(wait()).unwrap();
+    assert_eq!(*log.borrow(), [1, 2, 0]);
+}
+
+#[test]
+fn equal_priorities_keep_kernel_order() {
+    let mut eventp = Eventp::builder()
+        .dispatch_order(DispatchOrder::PriorityStable)
+        .build()
+        .unwrap();
+    let log = Log::default();
+    let interests = [1, 0, 1, 0, 1, 0].map(|priority| edge().dispatch_priority(priority));
+    let writers = register_pipes(&mut eventp, &interests, &log);
+
+    for order in [[4, 3, 2, 1, 0, 5], [0, 2, 5, 4, 1, 3]] {
+        write(&writers, &order);
+        eventp.run_once_with_timeout(wait()).unwrap();
+        let (mut first, mut last): (Vec<_>, Vec<_>) =
+            order.iter().partition(|&&index| index % 2 == 1);
+        first.append(&mut last);
+        assert_eq!(*log.borrow(), first);
+        log.borrow_mut().clear();
+    }
+}
+
+#[test]
+fn events_beyond_a_budget_keep_their_order_across_calls() {
+    let mut eventp = Eventp::default();
+    let log = Log::default();
+    let writers = register_pipes(&mut eventp, &[interest().read(); 6], &log);
+
+    write(&writers, &[4, 1, 3, 0, 2]);
+    assert_eq!(eventp.run_once_budgeted(wait(), 2).unwrap(), 2);
+    assert_eq!(eventp.pending_events(), 3);
+
+    // Ready after the batch was returned, so dispatched after what is left of it.
+    write(&writers, &[5]);
+    assert_eq!(eventp.run_once_budgeted(wait(), 2).unwrap(), 2);
+    assert_eq!(eventp.run_once_budgeted(wait(), 2).unwrap(), 1);
+    assert_eq!(eventp.pending_events(), 0);
+    assert_eq!(eventp.run_once_budgeted(wait(), 2).unwrap(), 1);
+    assert_eq!(*log.borrow(), [4, 1, 3, 0, 2, 5]);
+}
+
+#[test]
+fn events_beyond_a_budget_keep_their_priority_order() {
+    let mut eventp = Eventp::builder()
+        .dispatch_order(DispatchOrder::PriorityStable)
+        .build()
+        .unwrap();
+    let log = Log::default();
+    let interests = [2, 1, 0, 1].map(|priority| interest().read().dispatch_priority(priority));
+    let writers = register_pipes(&mut eventp, &interests, &log);
+
+    write(&writers, &[0, 3, 1, 2]);
+    assert_eq!(eventp.run_once_budgeted(wait(), 1).unwrap(), 1);
+    // Ready meanwhile, but not sorted into the events left over.
+    write(&writers, &[2]);
+    while eventp.pending_events() > 0 {
+        eventp.run_once_budgeted(wait(), 1).unwrap();
+    }
+    eventp.run_once_with_timeout(wait()).unwrap();
+    assert_eq!(*log.borrow(), [2, 3, 1, 0, 2]);
+}